@@ -0,0 +1,255 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Attribute, Data, DeriveInput, Fields, Ident, LitStr, Path, Token,
+};
+use uuid::Uuid;
+
+// Asset groups declared as enums, one variant per file:
+//
+// #[texture_group]
+// pub enum Textures {
+//     #[texture("./dog.png")]
+//     Dog,
+//     #[texture("./cat.png")]
+//     Cat,
+// }
+//
+// Ids are generated from the enum and variant names, so they're the same every build (and
+// have to be unique across groups). The enum gets Clone, Copy, PartialEq, Eq, Hash and Debug,
+// and an impl with:
+//  - GROUP_ID and id(), uuid strings like the ones in ember::constants
+//  - path() and ALL
+//  - handle(), for Engine::clone_mesh or a component's texture id
+//  - group(), for EngineBuilder::with_texture_group / with_mesh_group
+
+// Group ids are v5 uuids of the enum name in this namespace, and asset ids of the variant name
+// in their group's
+const NAMESPACE: &str = "5b0cf7a4-2e61-4f83-9d1a-c6e84a7b3f25";
+
+// Optionally a texture type (Image by default; only unit variants of TextureType) and a group
+// id to load into instead of the generated one, eg.
+// #[texture_group(Image, group = ember::constants::RENDER_2D_TEXTURE_GROUP)]
+#[proc_macro_attribute]
+pub fn texture_group(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as GroupArgs);
+    let tex_type = args.tex_type.unwrap_or_else(|| quote!(Image));
+    let group = Group::parse(item, "texture", args.group);
+    let (name, group_id) = (&group.name, &group.group_id);
+
+    group.expand(quote!(
+        impl #name {
+            pub const GROUP_ID: &'static str = #group_id;
+
+            pub fn handle(
+                &self,
+            ) -> ::ember::sources::handles::Handle<::ember::renderer::buffer::texture::Texture> {
+                ::ember::sources::handles::Handle::new(
+                    ::ember::constants::ID(self.id()),
+                    ::ember::constants::ID(Self::GROUP_ID),
+                )
+            }
+
+            pub fn group() -> ::ember::TextureGroup {
+                ::ember::TextureGroup {
+                    id: ::ember::constants::ID(Self::GROUP_ID),
+                    textures: Self::ALL
+                        .iter()
+                        .map(|texture| {
+                            (::ember::constants::ID(texture.id()), texture.path().to_owned())
+                        })
+                        .collect(),
+                    tex_type: ::ember::sources::registry::TextureType::#tex_type,
+                }
+            }
+        }
+    ))
+}
+
+// Optionally a group id to load into instead of the generated one, eg.
+// #[mesh_group(group = ember::constants::NAMED_MESH_GROUP_ID)]
+#[proc_macro_attribute]
+pub fn mesh_group(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as GroupArgs);
+    if args.tex_type.is_some() {
+        panic!("mesh groups don't have a texture type");
+    }
+    let group = Group::parse(item, "mesh", args.group);
+    let (name, group_id) = (&group.name, &group.group_id);
+
+    group.expand(quote!(
+        impl #name {
+            pub const GROUP_ID: &'static str = #group_id;
+
+            pub fn handle(&self) -> ::ember::sources::handles::Handle<::ember::renderer::mesh::Mesh> {
+                ::ember::sources::handles::Handle::new(
+                    ::ember::constants::ID(self.id()),
+                    ::ember::constants::ID(Self::GROUP_ID),
+                )
+            }
+
+            pub fn group() -> ::ember::MeshGroup {
+                ::ember::MeshGroup {
+                    id: ::ember::constants::ID(Self::GROUP_ID),
+                    meshes: Self::ALL
+                        .iter()
+                        .map(|mesh| (::ember::constants::ID(mesh.id()), mesh.path().to_owned()))
+                        .collect(),
+                }
+            }
+        }
+    ))
+}
+
+struct GroupArgs {
+    tex_type: Option<TokenStream2>,
+    group: Option<Path>,
+}
+
+impl Parse for GroupArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = GroupArgs {
+            tex_type: None,
+            group: None,
+        };
+        for arg in Punctuated::<GroupArg, Token![,]>::parse_terminated(input)? {
+            match arg {
+                GroupArg::TexType(ident) => args.tex_type = Some(quote!(#ident)),
+                GroupArg::Group(path) => args.group = Some(path),
+            }
+        }
+        Ok(args)
+    }
+}
+
+enum GroupArg {
+    TexType(Ident),
+    Group(Path),
+}
+
+impl Parse for GroupArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if !input.peek(Token![=]) {
+            return Ok(GroupArg::TexType(ident));
+        }
+        input.parse::<Token![=]>()?;
+        match ident.to_string().as_str() {
+            "group" => Ok(GroupArg::Group(input.parse()?)),
+            other => Err(syn::Error::new(
+                ident.span(),
+                format!("unknown asset group argument: {}", other),
+            )),
+        }
+    }
+}
+
+struct Group {
+    enum_ast: DeriveInput,
+    name: Ident,
+    // Expression for the group's uuid string
+    group_id: TokenStream2,
+    // (variant, uuid string, path)
+    variants: Vec<(Ident, String, String)>,
+}
+
+impl Group {
+    fn parse(item: TokenStream, attr_name: &str, group: Option<Path>) -> Self {
+        let mut enum_ast: DeriveInput = syn::parse(item).unwrap();
+        let name = enum_ast.ident.clone();
+        let generated_id = Uuid::new_v5(
+            &Uuid::parse_str(NAMESPACE).unwrap(),
+            name.to_string().as_bytes(),
+        );
+        let group_id = match group {
+            Some(path) => quote!(#path),
+            None => {
+                let id = generated_id.to_string();
+                quote!(#id)
+            }
+        };
+
+        let enum_data = match &mut enum_ast.data {
+            Data::Enum(enum_data) => enum_data,
+            _ => panic!("{} groups can only be generated for enums", attr_name),
+        };
+        let variants = enum_data
+            .variants
+            .iter_mut()
+            .map(|variant| {
+                if !matches!(variant.fields, Fields::Unit) {
+                    panic!("{} group variants can't have fields", attr_name);
+                }
+                let path = take_path(&mut variant.attrs, attr_name).unwrap_or_else(|| {
+                    panic!("{} is missing a #[{}(\"path\")]", variant.ident, attr_name)
+                });
+                let id = Uuid::new_v5(&generated_id, variant.ident.to_string().as_bytes());
+                (variant.ident.clone(), id.to_string(), path)
+            })
+            .collect();
+
+        Self {
+            enum_ast,
+            name,
+            group_id,
+            variants,
+        }
+    }
+
+    // The enum without its path attributes, and the methods every group has alongside impls
+    fn expand(&self, impls: TokenStream2) -> TokenStream {
+        let Group {
+            enum_ast,
+            name,
+            variants,
+            ..
+        } = self;
+        let count = variants.len();
+        let idents: Vec<&Ident> = variants.iter().map(|(ident, _, _)| ident).collect();
+        let ids: Vec<&String> = variants.iter().map(|(_, id, _)| id).collect();
+        let paths: Vec<&String> = variants.iter().map(|(_, _, path)| path).collect();
+
+        quote!(
+            // Original enum
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+            #enum_ast
+
+            // Generated ids and paths
+            impl #name {
+                pub const ALL: [Self; #count] = [#(Self::#idents),*];
+
+                pub fn id(&self) -> &'static str {
+                    match *self {
+                        #(Self::#idents => #ids,)*
+                    }
+                }
+
+                pub fn path(&self) -> &'static str {
+                    match *self {
+                        #(Self::#idents => #paths,)*
+                    }
+                }
+            }
+
+            #impls
+        )
+        .into()
+    }
+}
+
+// Removes a variant's #[texture("path")] or #[mesh("path")]
+fn take_path(attrs: &mut Vec<Attribute>, attr_name: &str) -> Option<String> {
+    let index = attrs
+        .iter()
+        .position(|attr| attr.path.is_ident(attr_name))?;
+    let attr = attrs.remove(index);
+    let path: LitStr = attr
+        .parse_args()
+        .unwrap_or_else(|_| panic!("expected #[{}(\"path\")]", attr_name));
+    Some(path.value())
+}
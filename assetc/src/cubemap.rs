@@ -0,0 +1,138 @@
+use ember::{renderer::buffer::ktx2::Ktx2Image, sources::environment::CubeCoords};
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+
+// Size of the prefiltered cubemap's first level, and of the downsampled environment
+// it is convolved from. Both are kept small; prefiltering is O(texels * samples).
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_LEVELS: usize = 6;
+const SAMPLE_SIZE: u32 = 32;
+
+// Each level is the environment convolved with a Phong lobe approximating increasing
+// roughness, from a mirror at level 0 to fully rough at the last level.
+pub fn prefilter(faces: &[RgbaImage]) -> Ktx2Image {
+    let size = PREFILTERED_SIZE.min(faces[0].width());
+    let base: Vec<RgbaImage> = faces
+        .iter()
+        .map(|face| image::imageops::resize(face, size, size, FilterType::Triangle))
+        .collect();
+    let samples = environment_samples(faces);
+    let level_count = PREFILTERED_LEVELS.min(32 - size.leading_zeros() as usize);
+
+    let levels = (0..level_count)
+        .map(|level| {
+            let level_size = (size >> level).max(1);
+            if level == 0 {
+                return base.iter().flat_map(|face| face.as_raw().clone()).collect();
+            }
+
+            let roughness = level as f32 / (level_count - 1) as f32;
+            let power = (2.0 / roughness.powi(4) - 2.0).max(1.0);
+            (0..6)
+                .flat_map(|face| {
+                    (0..level_size * level_size)
+                        .into_par_iter()
+                        .flat_map_iter(|texel| {
+                            let direction = texel_direction(face, texel, level_size);
+                            convolve(&samples, direction, power)
+                        })
+                        .collect::<Vec<u8>>()
+                })
+                .collect()
+        })
+        .collect();
+
+    Ktx2Image {
+        width: size,
+        height: size,
+        srgb: true,
+        compression: None,
+        faces: 6,
+        levels,
+    }
+}
+
+// (direction, linear color, solid angle) for every texel of a downsampled environment
+fn environment_samples(faces: &[RgbaImage]) -> Vec<([f32; 3], [f32; 3], f32)> {
+    let mut samples = vec![];
+    for (index, face) in faces.iter().enumerate() {
+        let small = image::imageops::resize(face, SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle);
+        for texel in 0..SAMPLE_SIZE * SAMPLE_SIZE {
+            let pixel = small.get_pixel(texel % SAMPLE_SIZE, texel / SAMPLE_SIZE).0;
+
+            // Texels towards the corners of a face cover less of the sphere
+            let vector = texel_vector(index, texel, SAMPLE_SIZE);
+            let length_sq = dot(vector, vector);
+            let solid_angle = 1.0 / (length_sq * length_sq.sqrt());
+
+            samples.push((
+                normalize(vector),
+                [
+                    srgb_to_linear(pixel[0]),
+                    srgb_to_linear(pixel[1]),
+                    srgb_to_linear(pixel[2]),
+                ],
+                solid_angle,
+            ));
+        }
+    }
+    samples
+}
+
+fn convolve(samples: &[([f32; 3], [f32; 3], f32)], normal: [f32; 3], power: f32) -> [u8; 4] {
+    let mut color = [0.0f32; 3];
+    let mut total = 0.0;
+    for (direction, sample, solid_angle) in samples {
+        let cos = dot(normal, *direction);
+        if cos <= 0.0 {
+            continue;
+        }
+        let weight = cos.powf(power) * solid_angle;
+        for c in 0..3 {
+            color[c] += sample[c] * weight;
+        }
+        total += weight;
+    }
+    if total > 0.0 {
+        for c in color.iter_mut() {
+            *c /= total;
+        }
+    }
+    [
+        linear_to_srgb(color[0]),
+        linear_to_srgb(color[1]),
+        linear_to_srgb(color[2]),
+        255,
+    ]
+}
+
+fn texel_direction(index: usize, texel: u32, size: u32) -> [f32; 3] {
+    normalize(texel_vector(index, texel, size))
+}
+
+// Unnormalized, through the texel center, on the unit cube
+fn texel_vector(index: usize, texel: u32, size: u32) -> [f32; 3] {
+    CubeCoords {
+        index,
+        u: ((texel % size) as f32 + 0.5) / size as f32,
+        v: ((texel / size) as f32 + 0.5) / size as f32,
+    }
+    .to_cartesian()
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    [v[0] / length, v[1] / length, v[2] / length]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    (c as f32 / 255.0).powf(2.2)
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    (c.max(0.0).powf(1.0 / 2.2) * 255.0).round().min(255.0) as u8
+}
@@ -0,0 +1,256 @@
+use anyhow::{anyhow, Result};
+use ember::{
+    renderer::{buffer::ktx2::Ktx2Image, gltf::GltfLoader, mesh::ObjLoader},
+    sources::{
+        environment::IrradianceCoefficients,
+        manifest::{AssetKind, AssetManifest},
+    },
+};
+use image::{imageops::FilterType, RgbaImage};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+mod cubemap;
+
+// Pre-processes a directory of source assets into a directory of fast-loading ones:
+//
+//   textures (png, jpg)       -> KTX2 with a full mip chain
+//   cubemaps (px.png..nz.png) -> KTX2 cubemap, its irradiance (as an irradiance.txt, read
+//                                when it is the environment; see load_environment), and a
+//                                KTX2 cubemap prefiltered for roughness, one level per step
+//   meshes (obj, gltf, glb)   -> .embm binary meshes (glTF materials are still read from the
+//                                model; see MeshRegistryBuilder::parse_entries)
+//
+// along with an assets.manifest, which EngineBuilder::with_asset_manifest hands to the registries.
+//
+// usage: ember-assetc <input dir> <output dir>
+
+const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        return Err(anyhow!("usage: ember-assetc <input dir> <output dir>"));
+    }
+
+    let input = PathBuf::from(&args[1]);
+    let output = PathBuf::from(&args[2]);
+    fs::create_dir_all(&output)?;
+
+    let start_time = Instant::now();
+    let mut manifest = AssetManifest::new(&output);
+    process_dir(&input, &input, &output, &mut manifest)?;
+    manifest.save()?;
+
+    println!(
+        "processed {} assets in {:.2}s",
+        manifest.entries.len(),
+        start_time.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn process_dir(
+    dir: &Path,
+    input: &Path,
+    output: &Path,
+    manifest: &mut AssetManifest,
+) -> Result<()> {
+    if is_cubemap_dir(dir) {
+        return process_cubemap(dir, input, output, manifest);
+    }
+
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            process_dir(&path, input, output, manifest)?;
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let res = match extension.as_str() {
+            "png" | "jpg" | "jpeg" => process_texture(&path, input, output, manifest),
+            "obj" | "gltf" | "glb" => process_mesh(&path, input, output, manifest),
+            _ => continue,
+        };
+
+        // Keep going; the engine falls back to the source asset
+        if let Err(err) = res {
+            println!("skipping {:?}: {}", path, err);
+        }
+    }
+    Ok(())
+}
+
+fn process_texture(
+    path: &Path,
+    input: &Path,
+    output: &Path,
+    manifest: &mut AssetManifest,
+) -> Result<()> {
+    let source = relative(path, input)?;
+    let target = format!("{}.ktx2", source);
+    println!("texture: {} -> {}", source, target);
+
+    let image = image::open(path)?.into_rgba8();
+    let levels = mip_chain(&image);
+    write_ktx2(
+        &output.join(&target),
+        &Ktx2Image {
+            width: image.width(),
+            height: image.height(),
+            srgb: true,
+            compression: None,
+            faces: 1,
+            levels: levels.into_iter().map(|level| level.into_raw()).collect(),
+        },
+    )?;
+
+    manifest.insert(AssetKind::Texture, &source, &target);
+    Ok(())
+}
+
+fn process_cubemap(
+    dir: &Path,
+    input: &Path,
+    output: &Path,
+    manifest: &mut AssetManifest,
+) -> Result<()> {
+    let source = relative(dir, input)?;
+    println!("cubemap: {}", source);
+
+    let faces = CUBE_FACES
+        .iter()
+        .map(|face| Ok(image::open(dir.join(format!("{}.png", face)))?.into_rgba8()))
+        .collect::<Result<Vec<RgbaImage>>>()?;
+    let (width, height) = faces[0].dimensions();
+    if faces
+        .iter()
+        .any(|face| face.dimensions() != (width, height))
+    {
+        return Err(anyhow!(
+            "cubemap faces must all be the same size: {}",
+            source
+        ));
+    }
+
+    // Plain cubemap, with mips
+    let target = format!("{}.ktx2", source);
+    let face_levels: Vec<Vec<RgbaImage>> = faces.iter().map(mip_chain).collect();
+    write_ktx2(
+        &output.join(&target),
+        &Ktx2Image {
+            width,
+            height,
+            srgb: true,
+            compression: None,
+            faces: 6,
+            levels: (0..face_levels[0].len())
+                .map(|level| {
+                    face_levels
+                        .iter()
+                        .flat_map(|levels| levels[level].as_raw().clone())
+                        .collect()
+                })
+                .collect(),
+        },
+    )?;
+    manifest.insert(AssetKind::Cubemap, &source, &target);
+
+    let irradiance_target = format!("{}.irradiance.txt", source);
+    println!("  irradiance -> {}", irradiance_target);
+    let irradiance_path = output.join(&irradiance_target);
+    create_parent(&irradiance_path)?;
+    IrradianceCoefficients::from_cubemap(&faces).write_cache(&irradiance_path)?;
+    manifest.insert(AssetKind::Irradiance, &source, &irradiance_target);
+
+    let prefiltered_target = format!("{}.prefiltered.ktx2", source);
+    println!("  prefiltered -> {}", prefiltered_target);
+    write_ktx2(
+        &output.join(&prefiltered_target),
+        &cubemap::prefilter(&faces),
+    )?;
+    manifest.insert(AssetKind::Prefiltered, &source, &prefiltered_target);
+
+    Ok(())
+}
+
+fn process_mesh(
+    path: &Path,
+    input: &Path,
+    output: &Path,
+    manifest: &mut AssetManifest,
+) -> Result<()> {
+    let source = relative(path, input)?;
+    let target = format!("{}.embm", source);
+    println!("mesh: {} -> {}", source, target);
+
+    let path = path.to_string_lossy().into_owned();
+    let mesh = match GltfLoader::is_gltf(&path) {
+        true => GltfLoader::new(path).parse()?.mesh,
+        false => ObjLoader::new(path).parse()?,
+    };
+
+    let target_path = output.join(&target);
+    create_parent(&target_path)?;
+    mesh.write(target_path.to_str().unwrap())?;
+
+    manifest.insert(AssetKind::Mesh, &source, &target);
+    Ok(())
+}
+
+fn is_cubemap_dir(dir: &Path) -> bool {
+    CUBE_FACES
+        .iter()
+        .all(|face| dir.join(format!("{}.png", face)).is_file())
+}
+
+// Halved down to 1x1
+fn mip_chain(image: &RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec![image.clone()];
+    loop {
+        let last = levels.last().unwrap();
+        if last.width() == 1 && last.height() == 1 {
+            return levels;
+        }
+        let next = image::imageops::resize(
+            last,
+            (last.width() / 2).max(1),
+            (last.height() / 2).max(1),
+            FilterType::Triangle,
+        );
+        levels.push(next);
+    }
+}
+
+fn write_ktx2(path: &Path, image: &Ktx2Image) -> Result<()> {
+    create_parent(path)?;
+    fs::write(path, image.to_bytes())?;
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn relative(path: &Path, root: &Path) -> Result<String> {
+    Ok(path
+        .strip_prefix(root)
+        .map_err(|_| anyhow!("{:?} is not inside {:?}", path, root))?
+        .to_string_lossy()
+        .replace('\\', "/"))
+}
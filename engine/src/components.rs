@@ -1,4 +1,7 @@
-use std::time::{Duration, Instant};
+use cgmath::{Matrix3, Vector3};
+use instant::Instant;
+use legion::Entity;
+use std::time::Duration;
 
 use crate::renderer::{
     buffer::instance::InstanceMutator, systems::render_2d::forward_instance::Render2DInstance,
@@ -11,27 +14,100 @@ use crate::renderer::{
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct FrameMetrics {
     delta: Duration,
+    // Of the last frame's delta, the time spent running it (rather than waiting on the frame
+    // cap or the event loop)
+    work: Duration,
     start: Instant,
+    elapsed: Duration,
+    frame: u64,
+
+    // Set while the fixed update stage steps; see sources::schedule::FixedStage
+    fixed_step: Option<Duration>,
+    alpha: f32,
 }
 
 impl FrameMetrics {
     pub fn new() -> Self {
         Self {
             delta: Duration::from_secs(0),
+            work: Duration::from_secs(0),
             start: Instant::now(),
+            elapsed: Duration::from_secs(0),
+            frame: 0,
+            fixed_step: None,
+            alpha: 1.0,
         }
     }
 
+    // The fixed timestep for systems in the fixed update stage, the last frame's time otherwise.
+    // Unscaled; simulation should advance by sources::time::Time::delta instead.
     pub fn delta(&self) -> Duration {
+        self.fixed_step.unwrap_or(self.delta)
+    }
+
+    // From the start of the last frame to the start of this one
+    pub fn frame_delta(&self) -> Duration {
         self.delta
     }
 
+    // How long the last frame took to run; frame_delta less this is time spent waiting (on the
+    // frame cap, vsync between frames or window events)
+    pub fn frame_work(&self) -> Duration {
+        self.work
+    }
+
+    // How far the frame is between the last fixed step and the next one, in [0, 1); eg. to
+    // draw lerp(previous, current, alpha). Always 1 without a fixed update stage.
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    // Total time spent in frames since the engine started
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
     pub(crate) fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if self.frame > 0 {
+            self.delta = now - self.start;
+            self.elapsed += self.delta;
+        }
+        self.start = now;
+    }
+
+    // Frames stopped for a while (eg. minimized); the next one shouldn't count all of it
+    pub(crate) fn resume(&mut self) {
         self.start = Instant::now();
     }
 
+    pub(crate) fn begin_fixed_step(&mut self, timestep: Duration) {
+        self.fixed_step = Some(timestep);
+    }
+
+    pub(crate) fn end_fixed_steps(&mut self, alpha: f32) {
+        self.fixed_step = None;
+        self.alpha = alpha;
+    }
+
     pub(crate) fn end_frame(&mut self) {
-        self.delta = self.start.elapsed();
+        self.work = self.start.elapsed();
+        self.frame += 1;
+    }
+}
+
+// Human-readable label for an entity, shown in diagnostics instead of its id.
+// Names don't need to be unique; see sources::names::NameIndex.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Name(pub String);
+
+impl Name {
+    pub fn new(name: &str) -> Self {
+        Self(name.to_owned())
     }
 }
 
@@ -121,6 +197,27 @@ impl Transform3D {
             rotation,
         }
     }
+
+    // Rx * Ry * Rz, as the render systems build their model matrices
+    pub fn rotation_matrix(&self) -> Matrix3<f32> {
+        Matrix3::from_angle_x(cgmath::Deg(self.rotation[0]))
+            * Matrix3::from_angle_y(cgmath::Deg(self.rotation[1]))
+            * Matrix3::from_angle_z(cgmath::Deg(self.rotation[2]))
+    }
+
+    // Back to euler angles in degrees; see rotation_matrix
+    pub fn euler_from_matrix(matrix: Matrix3<f32>) -> [f32; 3] {
+        let sin_y = matrix.z.x.max(-1.0).min(1.0);
+        let (x, z) = match sin_y.abs() < 0.9999 {
+            true => (
+                (-matrix.z.y).atan2(matrix.z.z),
+                (-matrix.y.x).atan2(matrix.x.x),
+            ),
+            // Gimbal lock; put all of it on x
+            false => (matrix.y.z.atan2(matrix.y.y), 0.0),
+        };
+        [x.to_degrees(), sin_y.asin().to_degrees(), z.to_degrees()]
+    }
 }
 
 impl Default for Transform3D {
@@ -150,6 +247,105 @@ pub struct Scale3D {
     pub z: f32,
 }
 
+// --------------------------------------------------
+// Hierarchy
+// --------------------------------------------------
+
+// Attaches an entity to another, eg. a weapon to a hand or a turret to a tank. The child's
+// LocalTransform3D (LocalPosition2D) is then relative to the parent's Transform3D
+// (Position2D), and the child's own is written from the two by the transform propagation
+// systems (systems::hierarchy). Parents can have parents of their own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Parent(pub Entity);
+
+// An entity's children, kept in sync with their Parents by the propagation systems; added to
+// new parents a frame late
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Children(pub Vec<Entity>);
+
+// A transform that a child can have relative to its parent's
+pub trait Relative: Copy + PartialEq + Send + Sync + 'static {
+    fn relative_to(&self, parent: &Self) -> Self;
+}
+
+// Scale is kept per axis, so a rotated child of a non-uniformly scaled parent isn't skewed
+// like it would be by multiplying their model matrices
+impl Relative for Transform3D {
+    fn relative_to(&self, parent: &Self) -> Self {
+        let rotation = parent.rotation_matrix();
+        let offset = rotation
+            * Vector3::new(
+                self.position[0] * parent.scale[0],
+                self.position[1] * parent.scale[1],
+                self.position[2] * parent.scale[2],
+            );
+        Self {
+            position: [
+                parent.position[0] + offset.x,
+                parent.position[1] + offset.y,
+                parent.position[2] + offset.z,
+            ],
+            rotation: Self::euler_from_matrix(rotation * self.rotation_matrix()),
+            scale: [
+                parent.scale[0] * self.scale[0],
+                parent.scale[1] * self.scale[1],
+                parent.scale[2] * self.scale[2],
+            ],
+        }
+    }
+}
+
+impl Relative for Position2D {
+    fn relative_to(&self, parent: &Self) -> Self {
+        Self {
+            x: parent.x + self.x,
+            y: parent.y + self.y,
+        }
+    }
+}
+
+// A child's transform relative to its Parent's. Dirty checked: the child's own is only
+// rewritten when this or the parent's has changed since, so still hierarchies cost a
+// comparison per child, and anything moving the child directly (eg. the gizmos) sticks until
+// then; see mark_dirty.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Local<T> {
+    pub value: T,
+    // The (local, parent) the child's was last worked out from
+    applied: Option<(T, T)>,
+}
+
+pub type LocalTransform3D = Local<Transform3D>;
+pub type LocalPosition2D = Local<Position2D>;
+
+impl<T: Relative> Local<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            applied: None,
+        }
+    }
+
+    // The child's transform, if it's out of date
+    pub fn pending(&self, parent: &T) -> Option<T> {
+        if self.applied == Some((self.value, *parent)) {
+            return None;
+        }
+        Some(self.value.relative_to(parent))
+    }
+
+    // Once the child's has actually been written, so a child without one yet gets it as soon
+    // as it's added
+    pub fn applied(&mut self, parent: &T) {
+        self.applied = Some((self.value, *parent));
+    }
+
+    // Rewrites the child's transform next frame, even if nothing changed
+    pub fn mark_dirty(&mut self) {
+        self.applied = None;
+    }
+}
+
 // --------------------------------------------------
 // Instance Mutators
 // --------------------------------------------------
@@ -235,6 +431,8 @@ impl InstanceMutator<Render2DInstance> for ParticleMotion2D {
 pub struct ParticleMutator2D {
     pub motion: ParticleMotion2D,
     pub lifetime: f32,
+    // Between a ParticleCurve::Between's two curves, from launch
+    pub variation: f32,
 }
 
 impl ParticleMutator2D {
@@ -266,6 +464,7 @@ impl Default for ParticleMutator2D {
         Self {
             lifetime: -1.0,
             motion: Default::default(),
+            variation: 0.0,
         }
     }
 }
@@ -7,9 +7,28 @@ pub const DEFAULT_SCREEN_HEIGHT: u32 = 1080;
 
 // Buffers
 pub const DEFAULT_TEXTURE_BUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+// Rgba, so headless frames read back straight into an RgbaImage
+pub const HEADLESS_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 pub const DEFAULT_MAX_DYNAMIC_ENTITIES_PER_PASS: u32 = 128;
 pub const DEFAULT_DYNAMIC_BUFFER_MIN_BINDING_SIZE: u64 = 128;
 pub const DEFAULT_MAX_INSTANCES_PER_BUFFER: u32 = 65536;
+// Indirect draw commands per instance buffer; batches past this are drawn directly
+pub const DEFAULT_MAX_INDIRECT_DRAWS_PER_BUFFER: u32 = 4096;
+// Textures per 2D instance batch, ie. bound at once by render_2d_instance.wgsl
+pub const MAX_TEXTURES_2D_BATCH: usize = 8;
+// Staging belt chunks for buffer uploads; bigger writes get a chunk of their own
+pub const DEFAULT_UPLOAD_CHUNK_SIZE: u64 = 1 << 20;
+pub const DEFAULT_FIXED_UPDATE_HZ: f32 = 60.0;
+// Past this, a slow frame drops fixed steps instead of falling further behind
+pub const MAX_FIXED_STEPS_PER_FRAME: u32 = 8;
+// How much of a capped frame's wait is spun rather than slept, since sleeps overshoot
+pub const FRAME_LIMITER_SPIN_MICROS: u64 = 1500;
+// Frame cap while the window is unfocused or minimized; see BackgroundPolicy
+pub const DEFAULT_BACKGROUND_FPS: f32 = 10.0;
+// How different a pixel may look from its golden one, perceptually (0 to 1), and how many
+// pixels may differ past that, in snapshot tests; see testing::SnapshotTest
+pub const DEFAULT_SNAPSHOT_THRESHOLD: f32 = 0.1;
+pub const DEFAULT_SNAPSHOT_MAX_DIFF_RATIO: f32 = 0.001;
 
 // --------------------------------------------------
 //                       UUIDs
@@ -26,6 +45,24 @@ pub const QUAD_NODE_ID: &str = "eaf2b9f7-1e96-4b6b-964f-29e2da214823";
 pub const CHANNEL_NODE_ID: &str = "36b2546b-cdff-4288-b4a8-f177bc899ed5";
 pub const CHAIN_NODE_ID: &str = "60b92c2e-d58b-4162-a311-ca56d5a31d21";
 pub const ICED_NODE_ID: &str = "7f3e5b5a-aeb9-4f2d-83c2-ac2ea7688b77";
+pub const DEBUG_OVERLAY_NODE_ID: &str = "5d0e8c1a-7b24-4e93-a6f1-3c9b2e7d4a18";
+pub const DEBUG_OVERLAY_ON_TOP_NODE_ID: &str = "b4c7e2f9-1a36-4d58-9e0b-6f2a8d3c5e71";
+pub const DEBUG_DRAW_NODE_ID: &str = "e28b7c4d-93f1-4a06-b5d2-7c1e9f4a8b63";
+pub const GIZMO_NODE_ID: &str = "4a9d2f71-c6e8-4b35-8f0a-1d7b3e5c9a24";
+pub const SHADOW_MAP_NODE_ID: &str = "c93a5f1e-2d84-4b7a-8e16-0f5b9d7c2a43";
+// Sorts after SHADOW_MAP_NODE_ID, so it's forward_basic's second input
+pub const POINT_SHADOW_MAP_NODE_ID: &str = "d2f6a8c4-5b19-4e73-8a0d-3c7e9b1f6a52";
+pub const FORWARD_SKINNED_NODE_ID: &str = "1e7d4b92-6a3f-4c85-b0d2-9f8e7a6c5b14";
+pub const TEXT_2D_NODE_ID: &str = "8a4f2e6d-1c93-4b57-a0e8-d7b5c3f91e26";
+pub const PARTICLES_GPU_2D_NODE_ID: &str = "3b9e6f12-c47a-4d08-91e5-a2d7f4c6b830";
+pub const NORMAL_DEPTH_NODE_ID: &str = "6f2c8a41-d93e-4b07-a5f1-8e4b2c7d9a36";
+pub const SSAO_NODE_ID: &str = "a71d3e95-4c2b-4f86-9e0a-b5c8f2d46e17";
+// Sorts after POINT_SHADOW_MAP_NODE_ID, so it's forward_basic's third input
+pub const SSAO_BLUR_NODE_ID: &str = "e4b1d7a2-8f35-4c69-b2e0-7d9a5c3f1b84";
+pub const FOLIAGE_NODE_ID: &str = "9d3b7e15-c62a-4f80-b1e4-5a8c2f6d0e93";
+pub const FORWARD_TOON_NODE_ID: &str = "4a7f0c26-e3b8-4d51-9f6a-18c5d2e7b039";
+pub const TOON_OUTLINE_NODE_ID: &str = "b85e2d19-7c40-4a6f-a3d8-e0f1c96b5724";
+pub const MATERIAL_PREVIEW_NODE_ID: &str = "004e9e59-6d76-486b-8e95-1aacc960278a";
 
 // Engine systems (excluding renderer)
 pub const RENDER_UI_SYSTEM_ID: &str = "7a370e52-053a-46dc-82d6-4fd8d41c1c19";
@@ -33,11 +70,20 @@ pub const RENDER_UI_SYSTEM_ID: &str = "7a370e52-053a-46dc-82d6-4fd8d41c1c19";
 // Engine uniform groups
 pub const RENDER_2D_BIND_GROUP_ID: &str = "2fc8e285-38ca-45e2-a910-00f49a7455d1";
 pub const RENDER_3D_BIND_GROUP_ID: &str = "4baacb83-6d2a-4a7e-ba6f-935d0b4d6c4d";
+pub const SKINNED_3D_BIND_GROUP_ID: &str = "e5a2c7f0-3b19-4d6e-8c47-2a1f0d9b8e63";
+pub const TOON_3D_BIND_GROUP_ID: &str = "69c1f4a8-2e57-4b03-8d9c-a4b7e6f01d32";
 pub const CAMERA_2D_BIND_GROUP_ID: &str = "50cdf623-c003-4c7c-ae56-646339c4f026";
 pub const CAMERA_3D_BIND_GROUP_ID: &str = "76a7bf47-812f-4612-be5e-c4ec9dba5477";
 pub const LIGHTING_2D_BIND_GROUP_ID: &str = "eb964ee1-abc3-435f-ab03-0dceb692661e";
 pub const LIGHTING_3D_BIND_GROUP_ID: &str = "b08c391a-8726-4665-87c3-cdd5102b175e";
-pub const QUAD_BIND_GROUP_ID: &str = "6ced9414-e8fc-4de1-aba0-fc64fa48202e";
+pub const FRAME_BIND_GROUP_ID: &str = "3600e2be-d55a-4158-84ec-cee80a9ff2e0";
+pub const ENVIRONMENT_BIND_GROUP_ID: &str = "1963f86b-7e21-4a4a-82cd-37ea4318348e";
+pub const TONEMAP_BIND_GROUP_ID: &str = "5d0e7c3a-92b4-4f1e-b6a8-3c71e0f94d26";
+pub const SSAO_BIND_GROUP_ID: &str = "2b8f5e1d-6a47-4c93-8d0b-f1e7a3c92d58";
+pub const DOF_BIND_GROUP_ID: &str = "7c4a9e2f-1d63-4b85-a0e7-5f2c8b3d16a9";
+pub const FOG_BIND_GROUP_ID: &str = "3e81d5c7-a94f-4b26-8d0e-c6f2b7a41953";
+pub const DECAL_BIND_GROUP_ID: &str = "c0d84f72-5b19-4e3a-96a7-2f8e1b3d6c05";
+pub const FOLIAGE_WIND_BIND_GROUP_ID: &str = "f2a6c94e-0b5d-4e71-8c3a-d7e1b4f92a60";
 
 // Engine imgui windows
 pub const METRICS_UI_IMGUI_ID: &str = "cb7550b5-e8a7-49b0-954a-c156f69db093";
@@ -51,15 +97,30 @@ pub const RENDER_2D_COMMON_TEXTURE_ID: &str = "8a22d465-7935-41e5-9e90-686ef5632
 pub const RENDER_3D_COMMON_TEXTURE_ID: &str = "fb378338-4d98-4b48-bd6d-1ca28515988f";
 pub const RENDER_3D_SKYBOX_TEXTURE_ID: &str = "1aa08d8c-6c4b-48ff-9e8f-9a3bb37f0847";
 pub const RENDER_3D_SKYBOX_BLUR_TEXTURE_ID: &str = "e2e12d22-01b6-40c2-bd4b-e6df96434ea2";
+pub const RENDER_3D_CUSTOM_SKYBOX_TEXTURE_ID: &str = "d58dfa3e-8aaf-4234-a5dc-c2289927da90";
 
 // Engine shared texture groups
 pub const SKYBOX_SHARED_GROUP: &str = "26787b7e-de9b-4010-93bf-a56fe6b3b6b5";
 
+// Engine materials
+pub const PBR_DEFAULT_MATERIAL_ID: &str = "7a99bfc4-5f38-44e9-92cd-40af6516a46a";
+
 // Primitive meshes
 pub const PRIMITIVE_MESH_GROUP_ID: &str = "437b63d4-5c7d-49e9-958b-8f68b4931355";
 pub const UNIT_SQUARE_MESH_ID: &str = "6fd0eeb3-9847-4a26-9eec-370e9839cbd3";
 pub const UNIT_CUBE_MESH_ID: &str = "85603817-f080-4a3b-959f-c629da179da5";
+pub const UNIT_QUAD_MESH_ID: &str = "d2a7c5e3-8f14-4b69-a03e-7c1b9e5f2d86";
 pub const SCREEN_QUAD_MESH_ID: &str = "4cc51b12-9edb-4ecb-b963-95c9de3928a1";
+// Generated by PrimitiveMesh, at the sizes given in sources::primitives
+pub const UNIT_SPHERE_MESH_ID: &str = "b3f1e0a4-7d2c-4e58-9a61-2c8f4d7e9b15";
+pub const UNIT_CYLINDER_MESH_ID: &str = "5e8a2c71-0b94-4f3d-8e26-a17c9d4b3f60";
+pub const UNIT_CAPSULE_MESH_ID: &str = "9c4d7b26-e153-4a8f-b0d9-6f2e81a5c734";
+pub const UNIT_PLANE_MESH_ID: &str = "27e9f5b8-4a1d-4c63-97f2-d85b0e3a6c19";
+pub const UNIT_TORUS_MESH_ID: &str = "e6a03d94-8b57-4f12-a4c8-3b9d71f0e285";
+pub const UNIT_CONE_MESH_ID: &str = "41b8c6e2-5f09-4d7a-bc35-9e0a2d84f1c7";
+
+// Meshes loaded with EngineBuilder::with_mesh
+pub const NAMED_MESH_GROUP_ID: &str = "4253c73f-c699-47c3-8409-e2b699532f81";
 
 // --------------------------------------------------
 
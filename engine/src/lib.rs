@@ -1,6 +1,7 @@
 #![windows_subsystem = "windows"]
 #![allow(dead_code)]
 
+#[cfg(not(target_arch = "wasm32"))]
 extern crate pretty_env_logger;
 extern crate vertex_traits;
 
@@ -13,7 +14,7 @@ extern crate legion;
 #[macro_use]
 extern crate vertex_layout_derive;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use iced::Size;
 use iced_wgpu::Viewport;
 use iced_winit::{
@@ -21,22 +22,32 @@ use iced_winit::{
     winit::{self, event::WindowEvent},
     Clipboard, Debug,
 };
-use image::{DynamicImage, ImageBuffer, Rgba};
-use legion::{Resources, Schedule, World};
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+use instant::Instant;
+use legion::{systems::ParallelRunnable, Entity, Resources, Schedule, World};
 use renderer::systems::render_3d::forward_pbr::RenderPBRForwardUniformGroup;
-use sources::{registry::TextureType, ui::iced::IcedWinitHelper};
+use sources::{
+    inspector::{Inspect, Inspector, InspectorRegistry},
+    registry::TextureType,
+    ui::{
+        iced::{IcedWinitHelper, UIPanel},
+        inspector::InspectorPanel,
+        material_editor::MaterialEditorPanel,
+    },
+};
 use std::{
     env,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, Mutex, RwLock},
-    time::{Duration, Instant},
+    time::Duration,
 };
 use uuid::Uuid;
 use winit_input_helper::WinitInputHelper;
 
+#[cfg(not(target_arch = "wasm32"))]
+use winit::platform::run_return::EventLoopExtRunReturn;
 use winit::{
-    dpi::LogicalSize,
     event::{Event, VirtualKeyCode},
     event_loop::{ControlFlow, EventLoop},
     window::{Fullscreen, Window, WindowBuilder},
@@ -47,62 +58,175 @@ use crate::{
     constants::*,
     renderer::{
         buffer::{instance::*, texture::Texture, *},
+        gltf::GltfLoader,
         graph::{
             node::{NodeBuilder, ShaderSource},
-            GraphBuilder, RenderGraph,
+            GraphBuilder, GraphWiring, RenderGraph,
         },
         mesh::Mesh,
+        shader::ShaderRegistry,
         systems::{
-            quad::QuadUniformGroup,
-            render_2d::forward_dynamic::Render2DForwardDynamicGroup,
-            render_3d::forward_basic::{Render3D, Render3DForwardUniformGroup},
+            capture::CaptureQueue,
+            dof::DofSettings,
+            fog::FogSettings,
+            post_fx::PostFxChainBuilder,
+            render_2d::{forward_dynamic::Render2DForwardDynamicGroup, text::GlyphAtlas},
+            render_3d::{
+                decal::DecalVolume,
+                foliage::{FoliageInstanceBuffer, FoliageScatter, FoliageWindUniformGroup},
+                forward_basic::{Render3D, Render3DForwardUniformGroup},
+                forward_skinned::Render3DSkinnedUniformGroup,
+                forward_toon::Render3DToonUniformGroup,
+                material_preview::{self, MaterialPreview},
+                ssao::{Ssao, SsaoUniformGroup},
+            },
+            sky::EnvironmentUniformGroup,
+            streaming::AssetStream,
+            tonemap::{Exposure, TonemapOperator},
             *,
         },
-        uniform::group::{GroupStateBuilder, UniformGroupBuilder, UniformGroupType},
-        GpuState, GpuStateBuilder,
+        uniform::group::{GroupBuilder, GroupStateBuilder, UniformGroupBuilder, UniformGroupType},
+        GpuOptions, GpuState, GpuStateBuilder,
     },
     sources::{
-        camera::{Camera2D, Camera3D},
+        assets,
+        audio::{AudioListener, AudioRegistry},
+        camera::{Camera2D, Camera3D, Units2D},
+        commands::CommandQueue,
+        control::{
+            BackgroundPolicy, BackgroundState, CursorMode, EngineCommand, EngineCommands,
+            ExitPolicy,
+        },
+        display::DisplayQueue,
+        events::{update_events_system, Events, SurfaceEvent, WindowEvent as EngineWindowEvent},
+        fonts::FontRegistry,
+        handles::Handle,
+        input::{Gamepads, InputMap, QUIT_ACTION},
+        limiter::FrameLimiter,
+        loading::{AssetGroupLoaded, LoadProgress},
+        manifest::AssetManifest,
+        materials::{Material, MaterialRegistryBuilder},
         metrics::{EngineMetrics, EngineReporter},
-        registry::{MeshRegistryBuilder, Registry, TextureRegistryBuilder},
-        schedule::{Schedulable, SubSchedule},
-        WindowSize,
+        metrics_export::MetricsExport,
+        names::NameIndex,
+        plugin::Plugin,
+        prefab::{Prefab, PrefabRegistry, PrefabRegistryBuilder},
+        registry::{self, MeshRegistryBuilder, Registry, TextureRegistryBuilder},
+        replay::{InputLog, InputLogMode},
+        rng::EngineRng,
+        schedule::{EngineState, FixedStage, Schedulable, Stage, StagedSchedule, SubSchedule},
+        time::Time,
+        window::{FullscreenMode, WindowConfig},
+        ResourceBuilder, WindowSize,
     },
     systems::{
-        camera_2d::*, camera_3d::*, lighting_2d::*, particle_2d::*, physics_2d::*, physics_3d::*,
+        animation::*, animation_2d::*, audio::*, camera_2d::*, camera_3d::*, collision_2d::*,
+        culling::*, dynamic_mesh::*, frame::*, hierarchy::*, lighting_2d::*, lighting_3d::*,
+        names::*, particle_2d::*, particle_3d::*, physics_2d::*, physics_3d::*, picking::*,
+        viewport::*,
     },
 };
 
-pub fn engine_builder() -> EngineBuilder {
+#[cfg(not(target_arch = "wasm32"))]
+fn init_logger() {
     pretty_env_logger::init();
+}
+
+// To the browser console, along with panics
+#[cfg(target_arch = "wasm32")]
+fn init_logger() {
+    console_error_panic_hook::set_once();
+    if let Err(err) = console_log::init_with_level(log::Level::Info) {
+        web_sys::console::warn_1(&format!("failed to set up logging: {}", err).into());
+    }
+}
+
+pub fn engine_builder() -> EngineBuilder {
+    init_logger();
     EngineBuilder {
-        window_size: (DEFAULT_SCREEN_WIDTH, DEFAULT_SCREEN_HEIGHT),
+        window_config: WindowConfig::default(),
+        headless: false,
+        debug_overlays: false,
+        gizmos: false,
+        pixel_probe: false,
+        frame_capture: false,
+        graph_panel: false,
+        material_editor: false,
+        units_2d: Units2D::Points,
+        skybox: false,
+        ssao: false,
+        foliage: None,
+        post_fx: None,
+        camera_controller: None,
+        render_textures: vec![],
+        capture_hotkey: None,
+        hot_reload_assets: false,
+        sounds: vec![],
+        fonts: vec![],
+        shader_includes: vec![],
+        fixed_hz: None,
+        game_systems: vec![],
+        fixed_systems: vec![],
+        events: vec![],
+        game_resources: vec![],
+        extra_nodes: vec![],
+        graphs: vec![],
+        plugins: vec![],
+        ui_panels: vec![],
+        inspector: None,
+        metrics_export: None,
+        input_log: None,
+        input_map: InputMap::default(),
+        gpu_options: GpuOptions::default(),
+        prebuilt_gpu: None,
+        exit_policy: ExitPolicy::default(),
+        cursor_mode: CursorMode::default(),
+        frame_cap: None,
+        background_policy: BackgroundPolicy::default(),
         texture_registry_builder: TextureRegistryBuilder::new(),
         mesh_registry_builder: MeshRegistryBuilder::new(),
+        material_registry_builder: MaterialRegistryBuilder::new(),
+        prefab_registry_builder: PrefabRegistryBuilder::new(),
     }
 }
 
+// Asset groups declared as enums; see asset_group_derive
+pub use asset_group_derive::{mesh_group, texture_group};
+
+#[macro_use]
+mod parallel;
+
 pub mod components;
 pub mod constants;
 pub mod renderer;
 pub mod sources;
 pub mod systems;
+pub mod testing;
 
 #[allow(dead_code)]
 pub struct Engine {
     gpu: Arc<Mutex<GpuState>>,
     graph: Arc<RenderGraph>,
-    window: Arc<Window>,
+    // None when headless
+    window: Option<Arc<Window>>,
     helper: Arc<Mutex<IcedWinitHelper>>,
     input: Arc<RwLock<WinitInputHelper>>,
-    clipboard: Clipboard,
+    gamepads: Gamepads,
+    clipboard: Option<Clipboard>,
     registry: Registry,
     legion: LegionState,
     reporter: EngineReporter,
     engine_metrics: Arc<EngineMetrics>,
     frame_metrics: Arc<RwLock<FrameMetrics>>,
     cursor_state: CursorState,
+    frame_limiter: Option<FrameLimiter>,
+    background: BackgroundState,
+    exit_policy: ExitPolicy,
+    metrics_export: Option<MetricsExport>,
+    input_log: Option<InputLog>,
     mode: EngineMode,
+    started: bool,
+    metrics_updated: Instant,
 }
 
 enum EngineMode {
@@ -111,23 +235,29 @@ enum EngineMode {
     Quad,
 }
 
-enum CursorMode {
-    Edit,
-    Grab,
-}
-
 struct CursorState {
     pub mode: CursorMode,
+    pub grabbed: bool,
     pub changed: bool,
 }
 
 impl CursorState {
-    pub fn default() -> Self {
+    pub fn new(mode: CursorMode) -> Self {
         Self {
-            mode: CursorMode::Edit,
+            mode,
+            grabbed: mode == CursorMode::Grabbed,
             changed: true,
         }
     }
+
+    pub fn set_mode(&mut self, mode: CursorMode) {
+        *self = Self::new(mode);
+    }
+
+    pub fn set_grabbed(&mut self, grabbed: bool) {
+        self.changed |= self.grabbed != grabbed;
+        self.grabbed = grabbed;
+    }
 }
 
 impl Engine {
@@ -135,12 +265,90 @@ impl Engine {
         &mut self.legion.world
     }
 
-    pub fn clone_mesh(&self, mesh_id: &Uuid, group_id: &Uuid) -> Mesh {
+    // Between frames; game systems should take what they need as #[resource]s instead
+    pub fn resources(&mut self) -> &mut Resources {
+        &mut self.legion.resources
+    }
+
+    // For changing the world from other threads once the engine has started
+    pub fn command_queue(&self) -> CommandQueue {
+        self.legion.resources.get::<CommandQueue>().unwrap().clone()
+    }
+
+    // For loading sounds once the engine has started
+    pub fn audio_registry(&self) -> Arc<RwLock<AudioRegistry>> {
+        self.legion
+            .resources
+            .get::<Arc<RwLock<AudioRegistry>>>()
+            .unwrap()
+            .clone()
+    }
+
+    // For loading fonts once the engine has started (default_2d)
+    pub fn font_registry(&self) -> Arc<RwLock<FontRegistry>> {
+        self.legion
+            .resources
+            .get::<Arc<RwLock<FontRegistry>>>()
+            .unwrap()
+            .clone()
+    }
+
+    // Saves the current frame (or the next, if called between frames) as a PNG, written on
+    // a background thread; see renderer::systems::capture. Windowed engines need
+    // EngineBuilder::with_frame_capture.
+    pub fn capture_frame(&self, path: &str) {
+        self.legion
+            .resources
+            .get::<CaptureQueue>()
+            .unwrap()
+            .request(PathBuf::from(path));
+    }
+
+    // Window size, render scale and fullscreen; see sources::display
+    pub fn display_queue(&self) -> DisplayQueue {
+        self.legion.resources.get::<DisplayQueue>().unwrap().clone()
+    }
+
+    // Exiting and the cursor; see sources::control
+    pub fn engine_commands(&self) -> EngineCommands {
+        self.legion
+            .resources
+            .get::<EngineCommands>()
+            .unwrap()
+            .clone()
+    }
+
+    pub fn clone_mesh(&self, mesh: Handle<Mesh>) -> Mesh {
         self.registry
             .meshes
             .read()
             .unwrap()
-            .clone_mesh(mesh_id, group_id)
+            .clone_mesh(&mesh.id(), &mesh.group_id())
+    }
+
+    // Eg. engine.mesh("unit_square"); see sources::handles
+    pub fn mesh(&self, name: &str) -> Option<Handle<Mesh>> {
+        self.registry.mesh(name)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<Handle<Texture>> {
+        self.registry.texture(name)
+    }
+
+    // Eg. engine.spawn_prefab("enemy", Prefab::new().with(transform)), with Prefab::new() for
+    // no overrides; see sources::prefab
+    pub fn spawn_prefab(&mut self, name: &str, overrides: Prefab) -> Option<Entity> {
+        let prefabs = self.prefabs();
+        prefabs.spawn(&mut self.legion.world, name, &overrides)
+    }
+
+    // For adding prefabs once the engine has started, or spawning them from other threads
+    pub fn prefabs(&self) -> PrefabRegistry {
+        self.legion
+            .resources
+            .get::<PrefabRegistry>()
+            .unwrap()
+            .clone()
     }
 
     pub fn start(mut self, event_loop: EventLoop<()>) {
@@ -149,128 +357,334 @@ impl Engine {
         self.init();
 
         // top-level event loop; hijacks thread
-        let metrics_last_updated = Arc::new(Mutex::new(Instant::now()));
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
+            if !self.handle_event(event) {
+                self.finish_metrics_export();
+                *control_flow = ControlFlow::Exit;
+            }
+        });
+    }
 
-            let mut ui_debug = self.graph.debug.lock().unwrap();
+    // Alternative to start() for embedding, where the caller owns the loop: call this once
+    // per iteration to handle pending window events and render a frame. Returns false once
+    // the window has been closed. Not available on the web, where the browser owns the loop.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll(&mut self, event_loop: &mut EventLoop<()>) -> bool {
+        if !self.started {
+            info!("starting engine (polled)");
+            self.init();
+        }
 
-            self.input.write().unwrap().update(&event);
+        let mut running = true;
+        event_loop.run_return(|event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+            // One frame's worth of events per call
+            if let Event::RedrawEventsCleared = event {
+                *control_flow = ControlFlow::Exit;
+            }
+            if !self.handle_event(event) {
+                running = false;
+                *control_flow = ControlFlow::Exit;
+            }
+        });
+        running
+    }
 
-            match event {
-                Event::WindowEvent { event, .. } => {
-                    let mut helper = self.helper.lock().unwrap();
-                    match event {
-                        WindowEvent::CursorMoved { position, .. } => {
-                            helper.cursor_position = position;
-                        }
-                        WindowEvent::ModifiersChanged(new_modifiers) => {
-                            helper.modifiers = new_modifiers;
-                        }
-                        WindowEvent::Resized(new_size) => {
-                            helper.viewport = Viewport::with_physical_size(
-                                Size::new(new_size.width, new_size.height),
-                                self.window.scale_factor(),
-                            );
+    // Executes every system and the render graph once
+    pub fn run_frame(&mut self) {
+        debug!("executing all systems");
+        self.frame_metrics.write().unwrap().begin_frame();
+        if let Some(commands) = self.legion.resources.get::<CommandQueue>() {
+            commands.apply(&mut self.legion.world);
+        }
+        if let Some(fullscreen) = self.display_queue().take_fullscreen() {
+            if let Some(window) = &self.window {
+                window.set_fullscreen(match fullscreen {
+                    true => Some(Fullscreen::Borderless(None)),
+                    false => None,
+                });
+            }
+        }
+        self.legion.execute();
+        self.reporter.update();
+        self.frame_metrics.write().unwrap().end_frame();
+        let frame_time = self.frame_metrics.read().unwrap().frame_delta();
+        self.engine_metrics
+            .frames
+            .lock()
+            .unwrap()
+            .push(frame_time.as_secs_f64());
 
-                            // resized = true;
+        let inspector = self.legion.resources.get::<Arc<Inspector>>();
+        if let Some(inspector) = &inspector {
+            if let Some(gizmo) = self.legion.resources.get::<gizmo::GizmoState>() {
+                inspector.follow(gizmo.selected);
+            }
+            inspector.refresh_selected(&self.legion.world);
+        }
+
+        if self.metrics_updated.elapsed() >= Duration::from_secs(1) {
+            self.engine_metrics.calculate();
+            if let Some(names) = self.legion.resources.get::<Arc<RwLock<NameIndex>>>() {
+                self.engine_metrics
+                    .calculate_entities(&names.read().unwrap());
+                if let Some(inspector) = &inspector {
+                    inspector.refresh_entities(&self.legion.world, &names.read().unwrap());
+                }
+            }
+            self.metrics_updated = Instant::now();
+        }
+    }
+
+    // Headless only (see EngineBuilder::headless): runs a frame and reads it back
+    pub fn render_frame_to_image(&mut self) -> Result<RgbaImage> {
+        if !self.started {
+            self.init();
+        }
+        self.run_frame();
+
+        let gpu = self.gpu.lock().unwrap();
+        let offscreen = gpu
+            .offscreen
+            .as_ref()
+            .ok_or_else(|| anyhow!("render_frame_to_image: engine isn't headless"))?;
+        offscreen.read(&gpu.device, &gpu.queue)
+    }
+
+    // Read by game systems from Events<sources::events::WindowEvent>
+    fn send_window_event(&self, event: EngineWindowEvent) {
+        if let Some(mut events) = self.legion.resources.get_mut::<Events<EngineWindowEvent>>() {
+            events.send(event);
+        }
+    }
+
+    // Applies the BackgroundPolicy on entering or leaving the background
+    fn update_background(&mut self) {
+        let (changed, shown) = self.background.update();
+        if shown {
+            self.frame_metrics.write().unwrap().resume();
+        }
+        let background = match changed {
+            Some(background) => background,
+            None => return,
+        };
+        debug!("window background: {}", background);
+        self.send_window_event(EngineWindowEvent::Background(background));
+
+        let policy = self.background.policy;
+        if policy.pause {
+            if let Some(mut state) = self.legion.resources.get_mut::<EngineState>() {
+                if background && !state.paused {
+                    state.paused = true;
+                    self.background.paused = true;
+                } else if !background && self.background.paused {
+                    state.paused = false;
+                    self.background.paused = false;
+                }
+            }
+        }
+        if policy.pause_audio {
+            if let Some(mut listener) = self.legion.resources.get_mut::<AudioListener>() {
+                pause_audio(&mut self.legion.world, &mut listener, background);
+            }
+        }
+    }
+
+    // Returns false if the window was asked to close
+    pub fn handle_event(&mut self, event: Event<()>) -> bool {
+        // Headless engines have no event loop to get events from
+        let window = match &self.window {
+            Some(window) => Arc::clone(window),
+            None => return true,
+        };
+        let mut running = true;
+        let frame = self.frame_metrics.read().unwrap().frame();
+        let replaced = match &mut self.input_log {
+            Some(log) => log.filter(frame, &event),
+            None => false,
+        };
+        if let (Some(log), Event::MainEventsCleared) = (&mut self.input_log, &event) {
+            let mut input = self.input.write().unwrap();
+            for replayed in log.end_events(frame) {
+                input.update(&replayed);
+            }
+        }
+        if !replaced {
+            self.input.write().unwrap().update(&event);
+        }
+
+        match event {
+            Event::WindowEvent { event, .. } => {
+                let mut helper = self.helper.lock().unwrap();
+                match event {
+                    WindowEvent::CursorMoved { position, .. } => {
+                        helper.cursor_position = position;
+                    }
+                    WindowEvent::ModifiersChanged(new_modifiers) => {
+                        helper.modifiers = new_modifiers;
+                    }
+                    WindowEvent::Resized(new_size) => {
+                        self.background.minimized = new_size.width == 0 || new_size.height == 0;
+                        self.send_window_event(EngineWindowEvent::Resized {
+                            width: new_size.width,
+                            height: new_size.height,
+                        });
+                        helper.viewport = Viewport::with_physical_size(
+                            Size::new(new_size.width, new_size.height),
+                            window.scale_factor(),
+                        );
+
+                        // Applied by begin_render_graph, before the next frame
+                        self.display_queue()
+                            .request_resize((new_size.width, new_size.height));
+                    }
+                    WindowEvent::CloseRequested => {
+                        self.send_window_event(EngineWindowEvent::CloseRequested);
+                        match self.exit_policy {
+                            ExitPolicy::Manual => self.engine_commands().request_close(),
+                            _ => running = false,
                         }
-                        WindowEvent::CloseRequested => {
-                            *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        if let Some(camera) = self.legion.resources.get::<Arc<Mutex<Camera2D>>>() {
+                            camera.lock().unwrap().scale_factor = scale_factor as f32;
                         }
-                        _ => {}
+                        self.send_window_event(EngineWindowEvent::ScaleFactorChanged(scale_factor));
+                    }
+                    WindowEvent::Focused(focused) => {
+                        self.background.focused = focused;
+                        self.send_window_event(EngineWindowEvent::Focused(focused));
+                    }
+                    WindowEvent::DroppedFile(ref path) => {
+                        self.send_window_event(EngineWindowEvent::DroppedFile(path.clone()));
                     }
+                    _ => {}
+                }
 
-                    // Map window event to iced event
-                    if let Some(event) = iced_winit::conversion::window_event(
-                        &event,
-                        self.window.scale_factor(),
-                        helper.modifiers,
-                    ) {
-                        self.graph.ui.lock().unwrap().state.queue_event(event);
+                // Map window event to iced event
+                if let Some(event) = iced_winit::conversion::window_event(
+                    &event,
+                    window.scale_factor(),
+                    helper.modifiers,
+                ) {
+                    self.graph.ui.lock().unwrap().state.queue_event(event);
+                }
+            }
+            Event::MainEventsCleared => {
+                // If there are events pending
+                let mut ui = self.graph.ui.lock().unwrap();
+
+                if !ui.state.is_queue_empty() {
+                    let helper = self.helper.lock().unwrap();
+                    let mut ui_debug = self.graph.debug.lock().unwrap();
+                    if let Some(clipboard) = &mut self.clipboard {
+                        ui.update(clipboard, &helper, &mut ui_debug);
                     }
+
+                    window.set_cursor_icon(iced_winit::conversion::mouse_interaction(
+                        ui.state.mouse_interaction(),
+                    ));
                 }
-                Event::MainEventsCleared => {
-                    // If there are events pending
-                    let mut ui = self.graph.ui.lock().unwrap();
-
-                    if !ui.state.is_queue_empty() {
-                        let helper = self.helper.lock().unwrap();
-                        ui.update(&mut self.clipboard, &helper, &mut ui_debug);
-
-                        self.window
-                            .set_cursor_icon(iced_winit::conversion::mouse_interaction(
-                                ui.state.mouse_interaction(),
-                            ));
-
-                        let input = self.input.read().unwrap();
-                        if input.mouse_pressed(1) {
-                            self.cursor_state.mode = CursorMode::Grab;
-                            self.cursor_state.changed = true;
-                        }
-                        if input.mouse_released(1) {
-                            self.cursor_state.mode = CursorMode::Edit;
-                            self.cursor_state.changed = true;
-                        }
 
-                        if self.cursor_state.changed {
-                            match self.cursor_state.mode {
-                                CursorMode::Edit => {
-                                    self.window.set_cursor_visible(true);
-                                    let _ = self.window.set_cursor_grab(false);
-                                }
-                                CursorMode::Grab => {
-                                    self.window.set_cursor_visible(false);
-                                    let _ = self.window.set_cursor_grab(true);
-                                }
-                            }
-                            self.cursor_state.changed = false;
-                        }
+                // Panels' actions need the world, so run them without the UI locked
+                let actions = ui.take_actions();
+                drop(ui);
+                for action in actions {
+                    (action.0)(&mut self.legion.world, &mut self.legion.resources);
+                }
+
+                // Input is done for this frame
+                self.gamepads.update();
+                if let Some(mut input_map) = self.legion.resources.get_mut::<InputMap>() {
+                    input_map.update(&self.input.read().unwrap(), &self.gamepads);
+                    if input_map.pressed(QUIT_ACTION)
+                        && self.exit_policy == ExitPolicy::OnCloseOrQuit
+                    {
+                        running = false;
                     }
+                }
 
-                    self.window.request_redraw();
+                for command in self.engine_commands().take() {
+                    debug!("engine command: {:?}", command);
+                    match command {
+                        EngineCommand::Exit => running = false,
+                        EngineCommand::ToggleFullscreen => self.display_queue().toggle_fullscreen(),
+                        EngineCommand::ReleaseCursor => self.cursor_state.set_grabbed(false),
+                        EngineCommand::GrabCursor => self.cursor_state.set_grabbed(true),
+                        EngineCommand::SetCursorMode(mode) => self.cursor_state.set_mode(mode),
+                    }
                 }
-                Event::RedrawRequested(_) => {
-                    debug!("executing all systems");
-                    self.frame_metrics.write().unwrap().begin_frame();
-                    self.legion.execute();
-                    self.reporter.update();
-                    self.frame_metrics.write().unwrap().end_frame();
-
-                    if metrics_last_updated.lock().unwrap().elapsed() >= Duration::from_secs(1) {
-                        self.engine_metrics.calculate();
-                        *metrics_last_updated.lock().unwrap() = Instant::now();
+
+                if self.cursor_state.mode == CursorMode::GrabOnRightClick {
+                    let input = self.input.read().unwrap();
+                    if input.mouse_pressed(1) {
+                        self.cursor_state.set_grabbed(true);
                     }
+                    if input.mouse_released(1) {
+                        self.cursor_state.set_grabbed(false);
+                    }
+                }
+                if self.cursor_state.changed {
+                    window.set_cursor_visible(!self.cursor_state.grabbed);
+                    let _ = window.set_cursor_grab(self.cursor_state.grabbed);
+                    self.cursor_state.changed = false;
+                }
 
-                    self.window.request_redraw();
+                window.request_redraw();
+            }
+            Event::Suspended => {
+                self.background.suspended = true;
+                self.send_window_event(EngineWindowEvent::Suspended);
+            }
+            Event::Resumed => {
+                self.background.suspended = false;
+                self.send_window_event(EngineWindowEvent::Resumed);
+            }
+            Event::RedrawRequested(_) => {
+                let limiter = match self.background.background {
+                    true => self.background.limiter.as_mut(),
+                    false => None,
+                };
+                if let Some(limiter) = limiter.or(self.frame_limiter.as_mut()) {
+                    limiter.wait();
                 }
-                _ => {}
+                // No surface to draw to
+                if self.background.visible {
+                    self.run_frame();
+                }
+                window.request_redraw();
             }
-
-            // let ui = self.legion.resources.get_mut::<Arc<UI>>().unwrap();
-            // let mut context = ui.context.lock().unwrap();
-            // ui.platform
-            //     .lock()
-            //     .unwrap()
-            //     .handle_event(context.io_mut(), &self.window, &event);
-
-            // if self.input.write().unwrap().update(&event) {
-            //     let input = self.input.read().unwrap();
-            //     if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
-            //         debug!("shutting down");
-            //         *control_flow = ControlFlow::Exit;
-            //         return;
-            //     }
-
-            //     if let Some(physical_size) = input.resolution() {
-            //         self.gpu.lock().unwrap().resize(physical_size);
-            //     }
-            //     self.window.request_redraw();
-            // }
-        });
+            _ => {}
+        }
+        self.update_background();
+
+        // let ui = self.legion.resources.get_mut::<Arc<UI>>().unwrap();
+        // let mut context = ui.context.lock().unwrap();
+        // ui.platform
+        //     .lock()
+        //     .unwrap()
+        //     .handle_event(context.io_mut(), &self.window, &event);
+
+        // if self.input.write().unwrap().update(&event) {
+        //     let input = self.input.read().unwrap();
+        //     if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+        //         debug!("shutting down");
+        //         *control_flow = ControlFlow::Exit;
+        //         return;
+        //     }
+
+        //     if let Some(physical_size) = input.resolution() {
+        //         self.gpu.lock().unwrap().resize(physical_size);
+        //     }
+        //     self.window.request_redraw();
+        // }
+
+        running
     }
 
     fn init(&mut self) {
+        self.started = true;
         match &self.mode {
             EngineMode::Forward3D | EngineMode::Quad => {
                 // self.window.set_cursor_visible(false);
@@ -280,16 +694,95 @@ impl Engine {
         }
 
         init_particle_systems(self.world());
+        init_particle_systems_3d(self.world());
+
+        if let Some(export) = &mut self.metrics_export {
+            export.start(&self.engine_metrics);
+        }
+        if let Some(rng) = self.legion.resources.get::<EngineRng>() {
+            info!("engine rng seed: {}", rng.seed());
+        }
     }
+
+    fn finish_metrics_export(&mut self) {
+        if let Some(export) = &mut self.metrics_export {
+            export.finish(&self.engine_metrics);
+        }
+    }
+}
+
+// Engines run by start() never drop, so it finishes the export itself
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.finish_metrics_export();
+    }
+}
+
+// Schedules one game system; see EngineBuilder::with_system
+type GameSystem = Box<dyn FnOnce(&mut legion::systems::Builder)>;
+
+// Adds one game event type; see EngineBuilder::with_event
+type GameEvent = Box<dyn FnOnce(&mut Resources, &mut legion::systems::Builder)>;
+
+// Inserts one game resource; see EngineBuilder::with_resource
+type GameResource = Box<dyn FnOnce(&mut Resources)>;
+
+// Built ahead of everything else by the async builds (EngineBuilder::default_3d_async)
+struct PrebuiltGpu {
+    gpu: Arc<Mutex<GpuState>>,
+    window: Arc<Window>,
+    event_loop: EventLoop<()>,
+    // Holding the gpu's resources (device, queue, uploader)
+    resources: Resources,
 }
 
 pub struct EngineBuilder {
     // Engine config
-    window_size: (u32, u32),
+    window_config: WindowConfig,
+    headless: bool,
+    debug_overlays: bool,
+    gizmos: bool,
+    pixel_probe: bool,
+    frame_capture: bool,
+    graph_panel: bool,
+    material_editor: bool,
+    units_2d: Units2D,
+    skybox: bool,
+    ssao: bool,
+    foliage: Option<FoliageScatter>,
+    post_fx: Option<PostFxChainBuilder>,
+    camera_controller: Option<CameraController3D>,
+    render_textures: Vec<(Uuid, f32)>,
+    capture_hotkey: Option<VirtualKeyCode>,
+    hot_reload_assets: bool,
+    sounds: Vec<(Uuid, String)>,
+    fonts: Vec<(Uuid, String)>,
+    shader_includes: Vec<(String, String)>,
+    fixed_hz: Option<f32>,
+    game_systems: Vec<(Stage, GameSystem)>,
+    fixed_systems: Vec<GameSystem>,
+    events: Vec<GameEvent>,
+    game_resources: Vec<GameResource>,
+    extra_nodes: Vec<(NodeBuilder, GraphWiring)>,
+    graphs: Vec<GraphBuilder>,
+    plugins: Vec<String>,
+    ui_panels: Vec<Box<dyn UIPanel>>,
+    inspector: Option<Arc<Inspector>>,
+    metrics_export: Option<MetricsExport>,
+    input_log: Option<InputLogMode>,
+    input_map: InputMap,
+    gpu_options: GpuOptions,
+    prebuilt_gpu: Option<PrebuiltGpu>,
+    exit_policy: ExitPolicy,
+    cursor_mode: CursorMode,
+    frame_cap: Option<f32>,
+    background_policy: BackgroundPolicy,
 
     // Static assets
     texture_registry_builder: TextureRegistryBuilder,
     mesh_registry_builder: MeshRegistryBuilder,
+    material_registry_builder: MaterialRegistryBuilder,
+    prefab_registry_builder: PrefabRegistryBuilder,
 }
 
 impl EngineBuilder {
@@ -309,14 +802,528 @@ impl EngineBuilder {
         self
     }
 
+    // A mesh file to look up by name once the engine is built; see Engine::mesh
+    pub fn with_mesh(mut self, name: &str, path: &str) -> Self {
+        self.mesh_registry_builder
+            .load_named(name, path, &ID(NAMED_MESH_GROUP_ID));
+        self
+    }
+
+    // An image for Render2D (or 2D instance groups), looked up by name once the engine is
+    // built; see Engine::texture
+    pub fn with_texture_2d(mut self, name: &str, path: &str) -> Self {
+        self.texture_registry_builder.load_named(
+            name,
+            path,
+            TextureType::Image,
+            ID(RENDER_2D_TEXTURE_GROUP),
+        );
+        self
+    }
+
+    // An image for Render3D (or 3D instance groups, decals and foliage); see with_texture_2d
+    pub fn with_texture_3d(mut self, name: &str, path: &str) -> Self {
+        self.texture_registry_builder.load_named(
+            name,
+            path,
+            TextureType::Image,
+            ID(RENDER_3D_TEXTURE_GROUP),
+        );
+        self
+    }
+
+    // For RenderPBR; its maps have to be loaded with with_texture_group. test_channel_node only.
+    pub fn with_material(mut self, id: Uuid, material: Material) -> Self {
+        self.material_registry_builder.register_id(id, material);
+        self
+    }
+
+    // A material file (see Material::parse), which the material editor saves back to.
+    // test_channel_node only.
+    pub fn with_material_file(mut self, id: Uuid, path: &str) -> Self {
+        self.material_registry_builder.load(id, path);
+        self
+    }
+
+    // Spawned by name with Engine::spawn_prefab; see sources::prefab
+    pub fn with_prefab(mut self, name: &str, prefab: Prefab) -> Self {
+        self.prefab_registry_builder.insert(name, prefab);
+        self
+    }
+
+    // A prefab file, parsed once the engine's meshes and textures are registered, so it can
+    // name any of them (with_mesh, with_texture_2d...); see PrefabRegistry
+    pub fn with_prefab_file(mut self, name: &str, path: &str) -> Self {
+        self.prefab_registry_builder.load(name, path);
+        self
+    }
+
+    // Lets prefab files have a game's own component, as a `[name]` section over `template`;
+    // see PrefabFormats
+    pub fn with_prefab_format<T: Inspect + Clone>(
+        mut self,
+        name: &'static str,
+        template: T,
+    ) -> Self {
+        self.prefab_registry_builder.with_format(name, template);
+        self
+    }
+
+    // Cubemap directory (or equirectangular image) whose irradiance lights PBR meshes; see
+    // TextureRegistryBuilder::load_environment. test_channel_node only.
+    pub fn with_environment(mut self, path: &str) -> Self {
+        self.texture_registry_builder.load_environment(path);
+        self
+    }
+
+    // Cubemap directory (px.png, nx.png, ...) or equirectangular image (.hdr, .exr, .png, ...)
+    // drawn behind 3D scenes; rotate or tint it at runtime through the sky::Sky resource. 3D
+    // engines only.
+    pub fn with_skybox(mut self, cubemap_path: &str) -> Self {
+        self.texture_registry_builder.load_id(
+            ID(RENDER_3D_CUSTOM_SKYBOX_TEXTURE_ID),
+            cubemap_path,
+            TextureType::Cubemap,
+            &ID(RENDER_3D_TEXTURE_GROUP),
+            None,
+        );
+        self.skybox = true;
+        self
+    }
+
+    // Screen space ambient occlusion on Render3D entities, tuned through the Ssao resource;
+    // see renderer::systems::render_3d::ssao. default_3d only.
+    pub fn with_ssao(mut self) -> Self {
+        self.ssao = true;
+        self
+    }
+
+    // Instanced meshes scattered in chunks around the camera and swaying in the wind, changed
+    // at runtime through the FoliageScatter resource; see render_3d::foliage. default_3d only.
+    pub fn with_foliage(mut self, scatter: FoliageScatter) -> Self {
+        self.foliage = Some(scatter);
+        self
+    }
+
+    // Fullscreen passes over the finished scene, before the UI; see
+    // renderer::systems::post_fx. default_2d, default_3d and default_quad only.
+    pub fn with_post_fx(mut self, chain: PostFxChainBuilder) -> Self {
+        self.post_fx = Some(chain);
+        self
+    }
+
+    // Renders the scene in hdr and tonemaps it onto the surface, after any post fx. The
+    // Exposure resource can be changed at runtime. default_2d, default_3d and default_quad only.
+    pub fn with_tonemap(mut self, operator: TonemapOperator) -> Self {
+        self.post_fx = Some(self.post_fx.unwrap_or_default().with_tonemap(operator));
+        self
+    }
+
+    // Blurs the scene away from the DofSettings resource's focal distance, before any post
+    // fx. default_3d only.
+    pub fn with_depth_of_field(mut self) -> Self {
+        self.post_fx = Some(self.post_fx.unwrap_or_default().with_depth_of_field());
+        self
+    }
+
+    // Fades the scene into fog with distance (and height), set through the FogSettings
+    // resource, before depth of field and any post fx. default_3d only.
+    pub fn with_fog(mut self) -> Self {
+        self.post_fx = Some(self.post_fx.unwrap_or_default().with_fog());
+        self
+    }
+
+    // Projects the textures of entities with a Decal onto the scene, before fog, depth of
+    // field and any post fx; see render_3d::decal. default_3d only.
+    pub fn with_decals(mut self) -> Self {
+        self.post_fx = Some(self.post_fx.unwrap_or_default().with_decals());
+        self
+    }
+
+    // What a unit of 2D world space is on screen; logical points by default, so sizes hold
+    // up on hiDPI displays. Can be changed later on the Camera2D resource. default_2d only.
+    pub fn with_2d_units(mut self, units: Units2D) -> Self {
+        self.units_2d = units;
+        self
+    }
+
+    // How the Camera3D is moved; FPS controls by default. 3D engines only.
+    pub fn with_camera_controller(mut self, controller: CameraController3D) -> Self {
+        self.camera_controller = Some(controller);
+        self
+    }
+
+    // Draws the scene into a texture registered under id, at scale times the screen size, from
+    // the Camera3D of the entity with a TextureCamera for it. Render3D entities can use the
+    // texture like any other (monitors, mirrors, portals). default_3d only.
+    pub fn with_render_texture(mut self, id: Uuid, scale: f32) -> Self {
+        self.render_textures.push((id, scale));
+        self
+    }
+
+    // Game systems run in the simulation stage, after the engine's, in the order they were
+    // added. They get the engine's resources (and with_resource's) like any legion system, eg.
+    // #[resource] input: &Arc<RwLock<WinitInputHelper>> or #[resource] commands: &CommandQueue.
+    // default_2d, default_3d and default_quad only.
+    pub fn with_system<S: ParallelRunnable + 'static>(self, system: S) -> Self {
+        self.with_stage_system(Stage::Simulation, system)
+    }
+
+    // e.g. Stage::Input for systems that have to run while paused (like the one unpausing);
+    // see sources::schedule::Stage
+    pub fn with_stage_system<S: ParallelRunnable + 'static>(
+        mut self,
+        stage: Stage,
+        system: S,
+    ) -> Self {
+        self.game_systems.push((
+            stage,
+            Box::new(move |schedule| {
+                schedule.add_system(system);
+            }),
+        ));
+        self
+    }
+
+    // Like with_system, but run at the fixed update rate; see with_fixed_timestep
+    pub fn with_fixed_system<S: ParallelRunnable + 'static>(mut self, system: S) -> Self {
+        self.fixed_systems.push(Box::new(move |schedule| {
+            schedule.add_system(system);
+        }));
+        self
+    }
+
+    // An Events<T> resource for game systems to send and read T through; see sources::events.
+    // default_2d, default_3d and default_quad only.
+    pub fn with_event<T: Send + Sync + 'static>(mut self) -> Self {
+        self.events.push(Box::new(add_events::<T>));
+        self
+    }
+
+    // Inserted after the engine's resources, so this replaces any of the same type
+    pub fn with_resource<R: legion::systems::Resource>(mut self, resource: R) -> Self {
+        self.game_resources.push(Box::new(move |resources| {
+            resources.insert(resource);
+        }));
+        self
+    }
+
+    // Drawn into the scene after the engine's overlays (sprites or meshes, particles, text,
+    // debug draw...), before post processing. default_2d and default_3d only.
+    pub fn with_overlay_node(self, node: NodeBuilder) -> Self {
+        self.with_extra_node(node, GraphWiring::Overlay)
+    }
+
+    // A render node of your own in default_2d's or default_3d's graph, between the scene node
+    // and the master node; see renderer::graph::GraphWiring. Extra post-processing passes go
+    // after with_post_fx's effects, in the order they were added.
+    pub fn with_extra_node(mut self, node: NodeBuilder, wiring: GraphWiring) -> Self {
+        self.extra_nodes.push((node, wiring));
+        self
+    }
+
+    // Merged into default_2d's or default_3d's graph, for nodes wired to each other rather
+    // than to the engine's; see GraphBuilder::merge
+    pub fn with_graph(mut self, graph: GraphBuilder) -> Self {
+        self.graphs.push(graph);
+        self
+    }
+
+    // See sources::plugin
+    pub fn with_plugin(mut self, plugin: impl Plugin) -> Self {
+        let name = plugin.name().to_owned();
+        if self.plugins.contains(&name) {
+            debug!("plugin {} was already added", name);
+            return self;
+        }
+        info!("adding plugin {}", name);
+        self.plugins.push(name);
+        plugin.build(self)
+    }
+
+    // Replaces the default bindings (just Escape to quit); see sources::input
+    pub fn with_input_map(mut self, input_map: InputMap) -> Self {
+        self.input_map = input_map;
+        self
+    }
+
+    // When the engine exits by itself; see sources::control
+    pub fn with_exit_policy(mut self, policy: ExitPolicy) -> Self {
+        self.exit_policy = policy;
+        self
+    }
+
+    // Frame and system timings for outside the engine; see sources::metrics_export
+    pub fn with_metrics_export(mut self, export: MetricsExport) -> Self {
+        self.metrics_export = Some(export);
+        self
+    }
+
+    // Changed at runtime with EngineCommand::SetCursorMode
+    pub fn with_cursor_mode(mut self, mode: CursorMode) -> Self {
+        self.cursor_mode = mode;
+        self
+    }
+
+    // Shorthand for WindowConfig::present_mode; call after with_window_config
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.window_config.present_mode = present_mode;
+        self
+    }
+
+    // Waits before each frame so that they start at most `fps` times a second, eg. with
+    // PresentMode::Immediate or Mailbox, which don't wait for vsync; see sources::limiter.
+    // Not on the web, where the browser paces frames.
+    pub fn with_frame_cap(mut self, fps: f32) -> Self {
+        if cfg!(target_arch = "wasm32") {
+            warn!("frame caps aren't available on the web, leaving frames to the browser");
+            return self;
+        }
+        self.frame_cap = Some(fps);
+        self
+    }
+
+    // Throttling (by default) and pausing while the window is unfocused, minimized or
+    // suspended; see sources::control::BackgroundPolicy
+    pub fn with_background_policy(mut self, policy: BackgroundPolicy) -> Self {
+        self.background_policy = policy;
+        self
+    }
+
+    // Game UI drawn over the frame, e.g. debug panels or a HUD; see
+    // sources::ui::iced::UIPanel. default_2d and default_3d only.
+    pub fn with_ui_panel(mut self, panel: impl UIPanel + 'static) -> Self {
+        self.ui_panels.push(Box::new(panel));
+        self
+    }
+
+    // Entity inspector panel, showing the registry's components (InspectorRegistry::default
+    // for the engine's own); see sources::inspector. default_3d only.
+    pub fn with_inspector(mut self, registry: InspectorRegistry) -> Self {
+        let inspector = Arc::new(Inspector::new(registry));
+        self.ui_panels
+            .push(Box::new(InspectorPanel::new(Arc::clone(&inspector))));
+        self.inspector = Some(inspector);
+        self
+    }
+
+    // Steps physics (and any fixed systems) at `hz` instead of once per frame; see
+    // sources::schedule::FixedStage. default_2d, default_3d and default_quad only.
+    pub fn with_fixed_timestep(mut self, hz: f32) -> Self {
+        self.fixed_hz = Some(hz);
+        self
+    }
+
+    // Runs the simulation reproducibly: every frame advances the Time by exactly 1 / hz,
+    // however long it really took, and the EngineRng is seeded with `seed`. With
+    // with_input_replay, for reproducing bugs and for automated gameplay tests. default_2d,
+    // default_3d and default_quad only.
+    pub fn with_deterministic(self, seed: u64, hz: f32) -> Self {
+        self.with_resource(EngineRng::new(seed))
+            .with_resource(Time::fixed(Duration::from_secs_f32(1.0 / hz)))
+    }
+
+    // Writes the keyboard and mouse input to `path` as it arrives, by frame; see
+    // sources::replay
+    pub fn with_input_recording(mut self, path: &str) -> Self {
+        self.input_log = Some(InputLogMode::Record(path.to_owned()));
+        self
+    }
+
+    // Feeds the input recorded by with_input_recording back in, frame by frame, in place of
+    // the real keyboard and mouse
+    pub fn with_input_replay(mut self, path: &str) -> Self {
+        self.input_log = Some(InputLogMode::Replay(path.to_owned()));
+        self
+    }
+
+    // Title, size, fullscreen, vsync and platform metadata; see sources::window
+    pub fn with_window_config(mut self, config: WindowConfig) -> Self {
+        self.window_config = config;
+        self
+    }
+
+    // Backends, adapter power preference, and device features and limits; see
+    // renderer::GpuOptions. Building fails if no adapter has the required features.
+    pub fn with_gpu_options(mut self, options: GpuOptions) -> Self {
+        self.gpu_options = options;
+        self
+    }
+
+    // No window or event loop: frames render into an offscreen texture of this size, read
+    // back with Engine::render_frame_to_image. Build with build_2d or build_3d.
+    pub fn headless(mut self, width: u32, height: u32) -> Self {
+        self.headless = true;
+        self.window_config.size = (width, height);
+        self
+    }
+
+    // 3D only; see renderer::systems::debug. Also inserts the DebugDraw resource.
+    pub fn with_debug_overlays(mut self) -> Self {
+        self.debug_overlays = true;
+        self
+    }
+
+    // Click to select Pickable entities, and drag their handles to move, rotate and scale
+    // them; 3D only, see renderer::systems::gizmo
+    pub fn with_gizmos(mut self) -> Self {
+        self.gizmos = true;
+        self
+    }
+
+    // Color picker over the final frame, shown in a UI panel; F6 toggles it.
+    // default_2d and default_3d only.
+    pub fn with_pixel_probe(mut self) -> Self {
+        self.pixel_probe = true;
+        self
+    }
+
+    // A UI panel showing the render graph's nodes, channels and chains. For the same outside
+    // the engine, RenderGraph::export_dot and export_json (the graph is an Arc<RenderGraph>
+    // in Engine::resources). default_2d and default_3d only.
+    pub fn with_graph_panel(mut self) -> Self {
+        self.graph_panel = true;
+        self
+    }
+
+    // A UI panel listing the materials, with the selected one on a spinning sphere and sliders
+    // for its factors; those from with_material_file can be saved back. test_channel_node only.
+    pub fn with_material_editor(mut self) -> Self {
+        self.material_editor = true;
+        self
+    }
+
+    // Saves a screenshot to the working directory when pressed; see Engine::capture_frame.
+    // Not on the web.
+    pub fn with_capture_hotkey(mut self, key: VirtualKeyCode) -> Self {
+        if cfg!(target_arch = "wasm32") {
+            warn!("frame capture isn't available on the web, ignoring the hotkey");
+            return self;
+        }
+        self.capture_hotkey = Some(key);
+        self.frame_capture = true;
+        self
+    }
+
+    // Lets Engine::capture_frame read frames back from the window's surface, which isn't
+    // asked for otherwise (headless engines can always be captured). Not on the web.
+    pub fn with_frame_capture(mut self) -> Self {
+        if cfg!(target_arch = "wasm32") {
+            warn!("frame capture isn't available on the web");
+            return self;
+        }
+        self.frame_capture = true;
+        self
+    }
+
+    // Surface copies are only asked of the gpu when something reads frames back
+    fn surface_gpu_options(&self) -> GpuOptions {
+        GpuOptions {
+            copy_surface: self.gpu_options.copy_surface || self.frame_capture || self.pixel_probe,
+            ..self.gpu_options.clone()
+        }
+    }
+
+    // wav or ogg, loaded before the engine starts; see sources::audio
+    pub fn with_sound(mut self, id: Uuid, path: &str) -> Self {
+        self.sounds.push((id, path.to_owned()));
+        self
+    }
+
+    // WGSL for `#include "name"` in node shaders; see renderer::shader
+    pub fn with_shader_include(mut self, name: &str, source: &str) -> Self {
+        self.shader_includes
+            .push((name.to_owned(), source.to_owned()));
+        self
+    }
+
+    // ttf or otf, for Text2D; default_2d only
+    pub fn with_font(mut self, id: Uuid, path: &str) -> Self {
+        self.fonts.push((id, path.to_owned()));
+        self
+    }
+
+    // Reloads textures and meshes when their files change; see renderer::systems::hot_reload
+    pub fn with_asset_hot_reload(mut self) -> Self {
+        self.hot_reload_assets = true;
+        self
+    }
+
+    // Starts with placeholders for image textures and meshes, and loads them in the
+    // background instead of before the first frame; see renderer::systems::streaming, and
+    // Registry::status / Events<AssetGroupLoaded> for loading screens. Not on the web.
+    pub fn with_asset_streaming(mut self) -> Self {
+        if cfg!(target_arch = "wasm32") {
+            warn!("asset streaming isn't available on the web, loading assets up front");
+            return self;
+        }
+        self.texture_registry_builder.stream = true;
+        self.mesh_registry_builder.stream = true;
+        self
+    }
+
+    // Called from loader threads as each mesh finishes parsing
+    pub fn with_load_progress(
+        mut self,
+        callback: impl Fn(&LoadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.mesh_registry_builder.with_progress(Arc::new(callback));
+        self
+    }
+
+    // Swap source textures and meshes for the outputs of ember-assetc, where available
+    pub fn with_asset_manifest(mut self, path: &str) -> Self {
+        match AssetManifest::load(Path::new(path)) {
+            Ok(manifest) => {
+                info!(
+                    "using asset manifest at {} ({} entries)",
+                    path,
+                    manifest.entries.len()
+                );
+                let manifest = Arc::new(manifest);
+                self.texture_registry_builder
+                    .with_manifest(Arc::clone(&manifest));
+                self.mesh_registry_builder.with_manifest(manifest);
+            }
+            Err(err) => warn!("{}, loading source assets instead", err),
+        }
+        self
+    }
+
     // Todo: distil this into several functions
     pub fn default_2d(self) -> Result<(Engine, EventLoop<()>)> {
+        let (engine, event_loop) = self.engine_2d()?;
+        let event_loop =
+            event_loop.ok_or_else(|| anyhow!("headless engines are built with build_2d"))?;
+        Ok((engine, event_loop))
+    }
+
+    // Headless only; see headless()
+    pub fn build_2d(self) -> Result<Engine> {
+        if !self.headless {
+            return Err(anyhow!("build_2d is for headless engines, use default_2d"));
+        }
+        Ok(self.engine_2d()?.0)
+    }
+
+    fn engine_2d(self) -> Result<(Engine, Option<EventLoop<()>>)> {
         info!("building engine: default_2d");
 
+        let gpu_options = self.surface_gpu_options();
+
         let (gpu, window, event_loop, registry, mut resources, helper) = build_engine_common(
-            self.window_size,
+            &self.window_config,
+            self.headless,
             self.texture_registry_builder,
             self.mesh_registry_builder,
+            self.prefab_registry_builder,
+            self.hot_reload_assets,
+            self.capture_hotkey,
+            &self.sounds,
+            &self.shader_includes,
+            self.input_map,
+            &gpu_options,
+            self.prebuilt_gpu,
         )?;
         let gpu_mut = gpu.lock().unwrap();
 
@@ -336,57 +1343,150 @@ impl EngineBuilder {
             Arc::clone(&camera_2d_group_builder),
             Arc::clone(&lighting_2d_group_builder),
         );
+        let node_2d_particles_gpu =
+            build_node_2d_particles_gpu(Arc::clone(&camera_2d_group_builder));
+        let (post_fx, extra_nodes) =
+            wire_post_process_nodes(self.post_fx.unwrap_or_default(), self.extra_nodes);
+        let has_post_fx = !post_fx.is_empty();
+        let tonemap = post_fx.tonemap;
+        let node_2d_text = build_node_2d_text(Arc::clone(&camera_2d_group_builder));
+
+        info!("loading fonts");
+        let mut font_registry = FontRegistry::new();
+        for (id, path) in &self.fonts {
+            font_registry.load_id(*id, path)?;
+        }
+        resources.insert(Arc::new(RwLock::new(font_registry)));
+        resources.insert(Arc::new(Mutex::new(GlyphAtlas::new(
+            &gpu_mut.device,
+            registry
+                .textures
+                .read()
+                .unwrap()
+                .bind_group_layout(TextureType::Image),
+        )?)));
+
+        // Read by game systems; see systems::collision_2d and systems::picking
+        resources.insert(Broadphase2D::default());
+        resources.insert(PickedEntity2D::default());
 
         // Todo: replace this with something better
         resources.insert(InstanceBuffer::<
             render_2d::forward_instance::Render2DInstance,
         >::new(
             &gpu_mut.device,
-            Arc::clone(&gpu_mut.queue),
+            Arc::clone(&gpu_mut.uploader),
             DEFAULT_MAX_INSTANCES_PER_BUFFER,
         ));
+        resources.insert(render_2d::forward_instance::TextureBatches2D::default());
+        resources.insert(render_2d::particles_gpu::ParticlePipelineGPU::new(
+            &gpu_mut.device,
+        ));
 
         info!("scheduling systems");
-        let mut schedule = Schedule::builder();
+        let fixed = build_fixed_stage(self.fixed_hz, self.fixed_systems, |schedule| {
+            schedule
+                .add_system(physics_2d_system())
+                .add_system(collision_2d_system());
+        });
+        let mut schedule = StagedSchedule::builder();
+        add_engine_events(&mut resources, schedule.stage(Stage::Clear));
         schedule
-            // Main engine systems
-            .add_system(physics_2d_system())
+            .stage(Stage::Input)
+            .add_system(frame_system())
+            .add_system(name_index_system())
             .add_system(camera_2d_system())
-            .add_system(lighting_2d_system())
+            .add_system(picking_2d_system());
+        let simulation = schedule.stage(Stage::Simulation);
+        if fixed.is_none() {
+            simulation
+                .add_system(physics_2d_system())
+                .add_system(collision_2d_system());
+        }
+        simulation
             .add_system(particle_2d_emission_system())
-            // .add_system(render_2d::forward_instance::attractor_system())
-            // Uniform loading systems
+            .add_system(render_2d::particles_gpu::update_system())
+            .add_system(animation_2d_system())
+            .add_system(audio_system());
+        // .add_system(render_2d::forward_instance::attractor_system())
+        add_events::<CollisionEvent>(&mut resources, schedule.stage(Stage::Clear));
+        for add_event in self.events {
+            add_event(&mut resources, schedule.stage(Stage::Clear));
+        }
+        for insert in self.game_resources {
+            insert(&mut resources);
+        }
+        for (stage, game_system) in self.game_systems {
+            game_system(schedule.stage(stage));
+        }
+        schedule
+            .stage(Stage::UniformLoad)
+            .add_system(transform_propagation_2d_system())
+            .flush()
+            .add_system(lighting_2d_system())
             .flush()
             .add_system(render_2d::forward_instance::load_system())
             .add_system(camera_2d_uniform_system())
-            .add_system(lighting_2d_uniform_system());
+            .add_system(lighting_2d_uniform_system())
+            .add_system(frame_uniform_system());
+        if tonemap.is_some() {
+            schedule
+                .stage(Stage::UniformLoad)
+                .add_system(tonemap::tonemap_uniform_system());
+        }
 
         info!("building render graph");
         let metrics_ui = EngineMetrics::new();
+        let mut graph_builder = GraphBuilder::new();
+        if self.pixel_probe {
+            graph_builder = graph_builder.with_pixel_probe();
+        }
         let mut graph_schedule = SubSchedule::new();
-        let (render_graph, engine_metrics) = GraphBuilder::new()
-            .with_master_node(node_2d_forward_instance)
-            .with_ui_imgui()
-            .build(
-                Arc::clone(&gpu_mut.device),
-                Arc::clone(&gpu_mut.queue),
-                &mut resources,
-                &mut graph_schedule,
-                &registry,
-                &window,
-                metrics_ui,
-                &helper,
-            )?;
+        let mut graph_builder = post_fx
+            .build(graph_builder, node_2d_forward_instance)
+            .with_overlay_node(node_2d_particles_gpu)
+            .with_overlay_node(node_2d_text);
+        graph_builder = wire_extra_nodes(graph_builder, extra_nodes, self.graphs);
+        if self.graph_panel {
+            graph_builder = graph_builder.with_graph_panel();
+        }
+        if !self.ui_panels.is_empty() {
+            graph_builder = graph_builder.with_ui_iced();
+        }
+        for panel in self.ui_panels {
+            graph_builder = graph_builder.with_ui_panel(panel);
+        }
+        let (render_graph, engine_metrics) = graph_builder.build(
+            Arc::clone(&gpu_mut.device),
+            Arc::clone(&gpu_mut.uploader),
+            &mut resources,
+            &mut graph_schedule,
+            &registry,
+            metrics_ui,
+            &helper,
+        )?;
 
         info!("scheduling render graph");
-        graph_schedule.schedule(&mut schedule);
+        graph_schedule.schedule(schedule.stage(Stage::Render));
         let schedule = schedule.build();
 
         // resource
-        let camera_2d = Arc::new(Mutex::new(Camera2D::default(
-            self.window_size.0 as f32,
-            self.window_size.1 as f32,
-        )));
+        if has_post_fx {
+            let quad = build_quad(&registry);
+            resources.insert(quad);
+        }
+        if let Some(operator) = tonemap {
+            resources.insert(Exposure::new(operator));
+        }
+
+        // resource
+        let screen_size = *renderer::SCREEN_SIZE.read().unwrap();
+        let mut camera_2d = Camera2D::default(screen_size.0 as f32, screen_size.1 as f32);
+        camera_2d.units = self.units_2d;
+        if let Some(window) = &window {
+            camera_2d.scale_factor = window.scale_factor() as f32;
+        }
+        let camera_2d = Arc::new(Mutex::new(camera_2d));
 
         // resource
         let frame_metrics = Arc::new(RwLock::new(FrameMetrics::new()));
@@ -397,7 +1497,9 @@ impl EngineBuilder {
 
         drop(gpu_mut);
         resources.insert(Arc::clone(&gpu));
-        resources.insert(Arc::clone(&window));
+        if let Some(window) = &window {
+            resources.insert(Arc::clone(window));
+        }
         resources.insert(Arc::clone(&registry.textures));
         resources.insert(Arc::clone(&registry.meshes));
         resources.insert(Arc::clone(&frame_metrics));
@@ -406,7 +1508,7 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&helper));
         resources.insert(Arc::clone(&input));
 
-        let clipboard = Clipboard::connect(&window);
+        let clipboard = window.as_ref().map(|window| Clipboard::connect(window));
 
         info!("ready to start!");
         Ok((
@@ -419,6 +1521,7 @@ impl EngineBuilder {
                     world: World::default(),
                     schedule,
                     resources,
+                    fixed,
                 },
                 graph: render_graph,
                 registry,
@@ -426,7 +1529,15 @@ impl EngineBuilder {
                 engine_metrics,
                 frame_metrics,
                 clipboard,
-                cursor_state: CursorState::default(),
+                cursor_state: CursorState::new(self.cursor_mode),
+                frame_limiter: self.frame_cap.map(FrameLimiter::new),
+                background: BackgroundState::new(self.background_policy),
+                exit_policy: self.exit_policy,
+                metrics_export: self.metrics_export,
+                input_log: self.input_log.map(InputLogMode::open).transpose()?,
+                started: false,
+                metrics_updated: Instant::now(),
+                gamepads: Gamepads::new(),
                 gpu,
             },
             event_loop,
@@ -434,61 +1545,392 @@ impl EngineBuilder {
     }
 
     pub fn default_3d(self) -> Result<(Engine, EventLoop<()>)> {
+        let (engine, event_loop) = self.engine_3d()?;
+        let event_loop =
+            event_loop.ok_or_else(|| anyhow!("headless engines are built with build_3d"))?;
+        Ok((engine, event_loop))
+    }
+
+    // Headless only; see headless()
+    pub fn build_3d(self) -> Result<Engine> {
+        if !self.headless {
+            return Err(anyhow!("build_3d is for headless engines, use default_3d"));
+        }
+        Ok(self.engine_3d()?.0)
+    }
+
+    // default_3d without blocking, for the web (e.g. from wasm_bindgen_futures::spawn_local):
+    // every asset the builder knows about is fetched first (see sources::assets), and the
+    // gpu is requested asynchronously. Works on desktop too.
+    pub async fn default_3d_async(self) -> Result<(Engine, EventLoop<()>)> {
+        self.prebuild_async().await?.default_3d()
+    }
+
+    // See default_3d_async
+    pub async fn default_2d_async(self) -> Result<(Engine, EventLoop<()>)> {
+        self.prebuild_async().await?.default_2d()
+    }
+
+    async fn prebuild_async(mut self) -> Result<Self> {
+        if self.headless {
+            return Err(anyhow!(
+                "headless engines are built with build_2d or build_3d"
+            ));
+        }
+
+        info!("fetching assets");
+        // The engine's own textures are only queued when the registry is built
+        let mut engine_textures = TextureRegistryBuilder::new();
+        load_engine_textures(&mut engine_textures, &get_crate_directory());
+        let mut paths =
+            registry::fetch_list(&self.texture_registry_builder, &self.mesh_registry_builder);
+        paths.extend(registry::fetch_list(
+            &engine_textures,
+            &MeshRegistryBuilder::new(),
+        ));
+        paths.extend(
+            self.sounds
+                .iter()
+                .chain(self.fonts.iter())
+                .map(|(_, path)| path.to_owned()),
+        );
+        assets::fetch(&paths).await?;
+        let mut buffers: Vec<String> = vec![];
+        for path in paths.iter().filter(|path| path.ends_with(".gltf")) {
+            buffers.extend(GltfLoader::new(path.to_owned()).buffer_paths()?);
+        }
+        assets::fetch(&buffers).await?;
+
+        info!("building gpu");
+        let mut resources = Resources::default();
+        let event_loop = EventLoop::new();
+        let window = build_window(
+            &self.window_config,
+            &self.texture_registry_builder,
+            &event_loop,
+        )?;
+        let gpu = GpuStateBuilder::winit(Arc::clone(&window))
+            .with_present_mode(self.window_config.present_mode)
+            .with_options(self.surface_gpu_options())
+            .build(&mut resources)
+            .await?;
+        self.prebuilt_gpu = Some(PrebuiltGpu {
+            gpu: Arc::new(Mutex::new(gpu)),
+            window,
+            event_loop,
+            resources,
+        });
+        Ok(self)
+    }
+
+    fn engine_3d(self) -> Result<(Engine, Option<EventLoop<()>>)> {
         info!("building engine: default_3d");
 
+        let gpu_options = self.surface_gpu_options();
+
         let (gpu, window, event_loop, registry, mut resources, helper) = build_engine_common(
-            self.window_size,
+            &self.window_config,
+            self.headless,
             self.texture_registry_builder,
             self.mesh_registry_builder,
+            self.prefab_registry_builder,
+            self.hot_reload_assets,
+            self.capture_hotkey,
+            &self.sounds,
+            &self.shader_includes,
+            self.input_map,
+            &gpu_options,
+            self.prebuilt_gpu,
         )?;
         let gpu_mut = gpu.lock().unwrap();
 
         info!("building uniforms");
         let render_3d_group_builder = Arc::new(Mutex::new(Render3DForwardUniformGroup::builder()));
         let camera_3d_group_builder = Arc::new(Mutex::new(Camera3DUniformGroup::builder()));
+        let lighting_3d_group_builder = Arc::new(Mutex::new(Lighting3DUniformGroup::builder()));
+        let render_skinned_group_builder =
+            Arc::new(Mutex::new(Render3DSkinnedUniformGroup::builder()));
+        let render_toon_group_builder = Arc::new(Mutex::new(Render3DToonUniformGroup::builder()));
+        let ssao_group_builder = match self.ssao {
+            true => Some(Arc::new(Mutex::new(SsaoUniformGroup::builder()))),
+            false => None,
+        };
+        let (post_fx, extra_nodes) =
+            wire_post_process_nodes(self.post_fx.unwrap_or_default(), self.extra_nodes);
+        let has_quad_passes = post_fx.needs_quad();
+        let tonemap = post_fx.tonemap;
+        let depth_of_field = post_fx.depth_of_field;
+        let has_fog = post_fx.fog;
+        let has_decals = post_fx.decals;
 
         info!("building render graph nodes");
+        let node_shadow_map = build_node_shadow_map(
+            Arc::clone(&render_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+        );
+        let node_point_shadow_map = build_node_point_shadow_map(
+            Arc::clone(&render_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+        );
+        // Skinned meshes are drawn over the basic node's target, so it needs a depth buffer
         let node_3d_forward_basic = build_node_3d_forward_basic(
             Arc::clone(&render_3d_group_builder),
             Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+            ssao_group_builder.as_ref().map(Arc::clone),
+        )
+        .with_depth_buffer();
+        let node_3d_forward_skinned = build_node_3d_forward_skinned(
+            Arc::clone(&render_skinned_group_builder),
+            Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+        );
+        let node_3d_forward_instance = build_node_3d_forward_instance(
+            Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+        );
+        let node_3d_forward_toon = build_node_3d_forward_toon(
+            Arc::clone(&render_toon_group_builder),
+            Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+            false,
+        );
+        let node_3d_toon_outline = build_node_3d_forward_toon(
+            Arc::clone(&render_toon_group_builder),
+            Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+            true,
         );
+        let node_foliage = self.foliage.as_ref().map(|_| {
+            build_node_foliage(
+                Arc::clone(&camera_3d_group_builder),
+                Arc::clone(&lighting_3d_group_builder),
+            )
+        });
+        // Drawn over the scene, wherever its depth is still at the far plane
+        let node_sky = match self.skybox {
+            true => Some(
+                build_node_sky(
+                    Arc::clone(&render_3d_group_builder),
+                    Arc::clone(&camera_3d_group_builder),
+                    Arc::new(Mutex::new(build_environment_group(&registry))),
+                )
+                .with_depth_buffer()
+                .with_depth_test(wgpu::CompareFunction::LessEqual, false),
+            ),
+            false => None,
+        };
+
+        // Read by game systems; see systems::picking
+        resources.insert(PickedEntity::default());
+        if let Some(inspector) = self.inspector.clone() {
+            resources.insert(inspector);
+        }
+
+        resources.insert(InstanceBuffer::<
+            render_3d::forward_instance::Render3DInstance,
+        >::new(
+            &gpu_mut.device,
+            Arc::clone(&gpu_mut.uploader),
+            DEFAULT_MAX_INSTANCES_PER_BUFFER,
+        ));
+        let has_foliage = self.foliage.is_some();
+        if let Some(foliage) = self.foliage {
+            resources.insert(foliage);
+            resources.insert(FoliageInstanceBuffer(InstanceBuffer::new(
+                &gpu_mut.device,
+                Arc::clone(&gpu_mut.uploader),
+                DEFAULT_MAX_INSTANCES_PER_BUFFER,
+            )));
+        }
 
         info!("scheduling systems");
-        let mut schedule = Schedule::builder();
+        let fixed = build_fixed_stage(self.fixed_hz, self.fixed_systems, |schedule| {
+            schedule.add_system(physics_3d_system());
+        });
+        let mut schedule = StagedSchedule::builder();
+        add_engine_events(&mut resources, schedule.stage(Stage::Clear));
         schedule
-            // Main engine systems
+            .stage(Stage::Input)
+            .add_system(frame_system())
+            .add_system(name_index_system())
             .add_system(camera_3d_system())
-            .add_system(physics_3d_system())
-            // Uniform loading systems
+            .add_system(picking_3d_system());
+        if self.debug_overlays {
+            schedule
+                .stage(Stage::Input)
+                .add_system(debug::debug_overlay_input_system());
+        }
+        if self.gizmos {
+            schedule
+                .stage(Stage::Input)
+                .add_system(gizmo::gizmo_input_system());
+        }
+        let simulation = schedule.stage(Stage::Simulation);
+        simulation.add_system(animation_system());
+        if fixed.is_none() {
+            simulation.add_system(physics_3d_system());
+        }
+        simulation
+            .add_system(particle_3d_emission_system())
+            .add_system(audio_system());
+        for add_event in self.events {
+            add_event(&mut resources, schedule.stage(Stage::Clear));
+        }
+        for insert in self.game_resources {
+            insert(&mut resources);
+        }
+        for (stage, game_system) in self.game_systems {
+            game_system(schedule.stage(stage));
+        }
+        let uniform_load = schedule.stage(Stage::UniformLoad);
+        uniform_load
+            .add_system(transform_propagation_3d_system())
+            .flush()
+            .add_system(lighting_3d_system())
+            .add_system(particle_3d_billboard_system())
+            .add_system(dynamic_mesh_upload_system());
+        if has_foliage {
+            uniform_load.add_system(render_3d::foliage::scatter_system());
+        }
+        uniform_load
             .flush()
+            .add_system(frustum_culling_system())
             .add_system(render_3d::forward_basic::load_system())
-            .add_system(camera_3d_uniform_system());
+            .add_system(render_3d::forward_skinned::load_system())
+            .add_system(render_3d::forward_toon::load_system())
+            .add_system(render_3d::forward_instance::load_system())
+            .add_system(camera_3d_uniform_system())
+            .add_system(viewport_3d_system())
+            .add_system(lighting_3d_uniform_system())
+            .add_system(frame_uniform_system());
+        if has_foliage {
+            uniform_load.add_system(render_3d::foliage::load_system());
+        }
+        if self.skybox {
+            uniform_load.add_system(sky::update_system());
+        }
+        if tonemap.is_some() {
+            uniform_load.add_system(tonemap::tonemap_uniform_system());
+        }
+        if self.ssao {
+            uniform_load.add_system(render_3d::ssao::load_system());
+        }
+        if depth_of_field {
+            uniform_load.add_system(dof::load_system());
+        }
+        if has_fog {
+            uniform_load.add_system(fog::load_system());
+        }
+        if has_decals {
+            uniform_load.add_system(render_3d::decal::load_system());
+        }
 
         let metrics_ui = EngineMetrics::new();
 
         info!("building render graph");
+        let mut graph_builder = GraphBuilder::new()
+            .with_overlay_node(node_3d_forward_skinned)
+            .with_overlay_node(node_3d_forward_toon)
+            .with_overlay_node(node_3d_toon_outline)
+            .with_overlay_node(node_3d_forward_instance);
+        if let Some(node_foliage) = node_foliage {
+            graph_builder = graph_builder.with_overlay_node(node_foliage);
+        }
+        if let Some(node_sky) = node_sky {
+            graph_builder = graph_builder.with_overlay_node(node_sky);
+        }
+        if self.debug_overlays {
+            // Occluded overlays test against the scene's depth
+            graph_builder = graph_builder
+                .with_overlay_node(build_node_debug_overlay(
+                    Arc::clone(&camera_3d_group_builder),
+                    false,
+                ))
+                .with_overlay_node(build_node_debug_overlay(
+                    Arc::clone(&camera_3d_group_builder),
+                    true,
+                ))
+                .with_overlay_node(build_node_debug_draw(Arc::clone(&camera_3d_group_builder)));
+            resources.insert(Arc::new(RwLock::new(debug::DebugOverlayCVars::default())));
+            resources.insert(Arc::new(Mutex::new(debug::DebugDraw::default())));
+        }
+        if self.gizmos {
+            graph_builder = graph_builder
+                .with_overlay_node(build_node_gizmo(Arc::clone(&camera_3d_group_builder)));
+            resources.insert(gizmo::GizmoState::default());
+        }
+        if let Some(ssao_group_builder) = &ssao_group_builder {
+            graph_builder = graph_builder
+                .with_node(build_node_normal_depth(
+                    Arc::clone(&render_3d_group_builder),
+                    Arc::clone(&camera_3d_group_builder),
+                ))
+                .with_node(build_node_ssao(
+                    Arc::clone(&camera_3d_group_builder),
+                    Arc::clone(ssao_group_builder),
+                ))
+                .with_node(build_node_ssao_blur())
+                .with_channel(ID(NORMAL_DEPTH_NODE_ID), 0, ID(SSAO_NODE_ID))
+                .with_channel(ID(SSAO_NODE_ID), 0, ID(SSAO_BLUR_NODE_ID))
+                .with_channel(ID(SSAO_BLUR_NODE_ID), 0, ID(FORWARD_3D_NODE_ID));
+            resources.insert(Ssao::default());
+        }
+        for &(texture_id, scale) in &self.render_textures {
+            let mut node = build_node_3d_forward_basic(
+                Arc::clone(&render_3d_group_builder),
+                Arc::clone(&camera_3d_group_builder),
+                Arc::clone(&lighting_3d_group_builder),
+                None,
+            )
+            .with_id(texture_id)
+            .with_target_scale(scale)
+            .with_depth_buffer();
+            node.dest_name = format!("render_texture_node_{}", texture_id);
+            graph_builder = graph_builder
+                .with_texture_node(node)
+                .with_channel(ID(SHADOW_MAP_NODE_ID), 0, texture_id)
+                .with_channel(ID(POINT_SHADOW_MAP_NODE_ID), 0, texture_id);
+        }
+        graph_builder = wire_extra_nodes(graph_builder, extra_nodes, self.graphs);
+        if self.pixel_probe {
+            graph_builder = graph_builder.with_pixel_probe();
+        }
+        if self.graph_panel {
+            graph_builder = graph_builder.with_graph_panel();
+        }
+        if !self.ui_panels.is_empty() {
+            graph_builder = graph_builder.with_ui_iced();
+        }
+        for panel in self.ui_panels {
+            graph_builder = graph_builder.with_ui_panel(panel);
+        }
+
         let mut graph_schedule = SubSchedule::new();
-        let (render_graph, engine_metrics) = GraphBuilder::new()
-            .with_master_node(node_3d_forward_basic)
+        let (render_graph, engine_metrics) = post_fx
+            .build(graph_builder, node_3d_forward_basic)
+            .with_channel(ID(SHADOW_MAP_NODE_ID), 0, ID(FORWARD_3D_NODE_ID))
+            .with_node(node_shadow_map)
+            .with_channel(ID(POINT_SHADOW_MAP_NODE_ID), 0, ID(FORWARD_3D_NODE_ID))
+            .with_node(node_point_shadow_map)
             .build(
                 Arc::clone(&gpu_mut.device),
-                Arc::clone(&gpu_mut.queue),
+                Arc::clone(&gpu_mut.uploader),
                 &mut resources,
                 &mut graph_schedule,
                 &registry,
-                &window,
                 metrics_ui,
                 &helper,
             )?;
 
         info!("scheduling render graph");
-        graph_schedule.schedule(&mut schedule);
+        graph_schedule.schedule(schedule.stage(Stage::Render));
         let schedule = schedule.build();
 
         // resource
         let camera_3d = Arc::new(Mutex::new(Camera3D::default(
-            self.window_size.0 as f32,
-            self.window_size.1 as f32,
+            self.window_config.size.0 as f32,
+            self.window_config.size.1 as f32,
         )));
 
         // resource
@@ -498,9 +1940,46 @@ impl EngineBuilder {
         // resource
         let frame_metrics = Arc::new(RwLock::new(FrameMetrics::new()));
 
+        // resource
+        if has_quad_passes {
+            let quad = build_quad(&registry);
+            resources.insert(quad);
+        }
+        if let Some(operator) = tonemap {
+            resources.insert(Exposure::new(operator));
+        }
+        if depth_of_field {
+            resources.insert(DofSettings::default());
+        }
+        if has_fog {
+            resources.insert(FogSettings::default());
+        }
+        if has_decals {
+            resources.insert(DecalVolume {
+                mesh: registry
+                    .meshes
+                    .read()
+                    .unwrap()
+                    .clone_mesh(&ID(UNIT_CUBE_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
+            });
+        }
+
+        // resource
+        if self.skybox {
+            let sky = build_sky(
+                &registry,
+                &resources,
+                &gpu_mut,
+                ID(RENDER_3D_CUSTOM_SKYBOX_TEXTURE_ID),
+            )?;
+            resources.insert(sky);
+        }
+
         drop(gpu_mut);
         resources.insert(Arc::clone(&gpu));
-        resources.insert(Arc::clone(&window));
+        if let Some(window) = &window {
+            resources.insert(Arc::clone(window));
+        }
         resources.insert(Arc::clone(&registry.textures));
         resources.insert(Arc::clone(&registry.meshes));
         resources.insert(Arc::clone(&helper));
@@ -509,8 +1988,9 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&render_graph));
         resources.insert(Arc::clone(&render_3d_group_builder));
         resources.insert(Arc::clone(&camera_3d));
+        resources.insert(self.camera_controller.unwrap_or_default());
 
-        let clipboard = Clipboard::connect(&window);
+        let clipboard = window.as_ref().map(|window| Clipboard::connect(window));
 
         info!("ready to start!");
         Ok((
@@ -523,13 +2003,22 @@ impl EngineBuilder {
                     world: World::default(),
                     schedule,
                     resources,
+                    fixed,
                 },
                 graph: render_graph,
                 registry,
                 window,
                 engine_metrics,
                 frame_metrics,
-                cursor_state: CursorState::default(),
+                cursor_state: CursorState::new(self.cursor_mode),
+                frame_limiter: self.frame_cap.map(FrameLimiter::new),
+                background: BackgroundState::new(self.background_policy),
+                exit_policy: self.exit_policy,
+                metrics_export: self.metrics_export,
+                input_log: self.input_log.map(InputLogMode::open).transpose()?,
+                started: false,
+                metrics_updated: Instant::now(),
+                gamepads: Gamepads::new(),
                 gpu,
                 clipboard,
             },
@@ -540,51 +2029,77 @@ impl EngineBuilder {
     pub fn default_quad(self, shader_source: ShaderSource) -> Result<(Engine, EventLoop<()>)> {
         info!("building engine: default_shader");
 
+        let gpu_options = self.surface_gpu_options();
+
         let (gpu, window, event_loop, registry, mut resources, helper) = build_engine_common(
-            self.window_size,
+            &self.window_config,
+            false,
             self.texture_registry_builder,
             self.mesh_registry_builder,
+            self.prefab_registry_builder,
+            self.hot_reload_assets,
+            self.capture_hotkey,
+            &self.sounds,
+            &self.shader_includes,
+            self.input_map,
+            &gpu_options,
+            self.prebuilt_gpu,
         )?;
+        let (window, event_loop) = (window.unwrap(), event_loop.unwrap());
         let gpu_mut = gpu.lock().unwrap();
 
         info!("building uniforms");
-        let quad_group_builder = Arc::new(Mutex::new(QuadUniformGroup::builder()));
         let camera_3d_group_builder = Arc::new(Mutex::new(Camera3DUniformGroup::builder()));
 
         info!("building render graph nodes");
-        let node_quad = build_node_quad(
-            Arc::clone(&quad_group_builder),
-            Arc::clone(&camera_3d_group_builder),
-            shader_source,
-        );
+        let node_quad = build_node_quad(Arc::clone(&camera_3d_group_builder), shader_source);
+
+        let post_fx = self.post_fx.unwrap_or_default();
+        let tonemap = post_fx.tonemap;
 
         info!("scheduling systems");
-        let mut schedule = Schedule::builder();
+        let fixed = build_fixed_stage(self.fixed_hz, self.fixed_systems, |_| {});
+        let mut schedule = StagedSchedule::builder();
+        add_engine_events(&mut resources, schedule.stage(Stage::Clear));
         schedule
-            // Main engine systems
-            .add_system(camera_3d_system())
-            // Uniform loading systems
-            .flush()
+            .stage(Stage::Input)
+            .add_system(frame_system())
+            .add_system(name_index_system())
+            .add_system(camera_3d_system());
+        for add_event in self.events {
+            add_event(&mut resources, schedule.stage(Stage::Clear));
+        }
+        for insert in self.game_resources {
+            insert(&mut resources);
+        }
+        for (stage, game_system) in self.game_systems {
+            game_system(schedule.stage(stage));
+        }
+        schedule
+            .stage(Stage::UniformLoad)
             .add_system(camera_3d_uniform_system())
-            .add_system(quad::load_system());
+            .add_system(frame_uniform_system());
+        if tonemap.is_some() {
+            schedule
+                .stage(Stage::UniformLoad)
+                .add_system(tonemap::tonemap_uniform_system());
+        }
 
         info!("building render graph");
         let metrics_ui = EngineMetrics::new();
         let mut graph_schedule = SubSchedule::new();
-        let (render_graph, engine_metrics) =
-            GraphBuilder::new().with_master_node(node_quad).build(
-                Arc::clone(&gpu_mut.device),
-                Arc::clone(&gpu_mut.queue),
-                &mut resources,
-                &mut graph_schedule,
-                &registry,
-                &window,
-                metrics_ui,
-                &helper,
-            )?;
+        let (render_graph, engine_metrics) = post_fx.build(GraphBuilder::new(), node_quad).build(
+            Arc::clone(&gpu_mut.device),
+            Arc::clone(&gpu_mut.uploader),
+            &mut resources,
+            &mut graph_schedule,
+            &registry,
+            metrics_ui,
+            &helper,
+        )?;
 
         info!("scheduling render graph");
-        graph_schedule.schedule(&mut schedule);
+        graph_schedule.schedule(schedule.stage(Stage::Render));
         let schedule = schedule.build();
 
         // resource
@@ -595,32 +2110,19 @@ impl EngineBuilder {
         let frame_metrics = Arc::new(RwLock::new(FrameMetrics::new()));
 
         // resource
-        let quad = {
-            let quad_group_builder = resources
-                .get::<Arc<Mutex<GroupStateBuilder<QuadUniformGroup>>>>()
-                .unwrap();
-
-            let builder_mut = quad_group_builder.lock().unwrap();
-
-            quad::Quad {
-                mesh: registry
-                    .meshes
-                    .read()
-                    .unwrap()
-                    .clone_mesh(&ID(SCREEN_QUAD_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
-                uniforms: Default::default(),
-                uniform_group: builder_mut.single_state(&gpu_mut.device, &gpu_mut.queue)?,
-            }
-        };
+        let quad = build_quad(&registry);
 
         // resource
         let camera_3d = Arc::new(Mutex::new(Camera3D::default(
-            self.window_size.0 as f32,
-            self.window_size.1 as f32,
+            self.window_config.size.0 as f32,
+            self.window_config.size.1 as f32,
         )));
 
         drop(gpu_mut);
         resources.insert(quad);
+        if let Some(operator) = tonemap {
+            resources.insert(Exposure::new(operator));
+        }
         resources.insert(Arc::clone(&gpu));
         resources.insert(Arc::clone(&window));
         resources.insert(Arc::clone(&registry.textures));
@@ -630,6 +2132,7 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&frame_metrics));
         resources.insert(Arc::clone(&render_graph));
         resources.insert(Arc::clone(&camera_3d));
+        resources.insert(self.camera_controller.unwrap_or_default());
 
         let clipboard = Clipboard::connect(&window);
 
@@ -644,15 +2147,24 @@ impl EngineBuilder {
                     world: World::default(),
                     schedule,
                     resources,
+                    fixed,
                 },
                 graph: render_graph,
-                cursor_state: CursorState::default(),
+                cursor_state: CursorState::new(self.cursor_mode),
+                frame_limiter: self.frame_cap.map(FrameLimiter::new),
+                background: BackgroundState::new(self.background_policy),
+                exit_policy: self.exit_policy,
+                metrics_export: self.metrics_export,
+                input_log: self.input_log.map(InputLogMode::open).transpose()?,
+                started: false,
+                metrics_updated: Instant::now(),
+                gamepads: Gamepads::new(),
                 registry,
-                window,
+                window: Some(window),
                 engine_metrics,
                 frame_metrics,
                 gpu,
-                clipboard,
+                clipboard: Some(clipboard),
             },
             event_loop,
         ))
@@ -663,74 +2175,132 @@ impl EngineBuilder {
         warn!("RUNNING EXPERIMENTAL ENGINE MODE: test_channel_node");
         info!("building engine: test_channel_node");
 
+        let gpu_options = self.surface_gpu_options();
+
         let (gpu, window, event_loop, registry, mut resources, helper) = build_engine_common(
-            self.window_size,
+            &self.window_config,
+            false,
             self.texture_registry_builder,
             self.mesh_registry_builder,
+            self.prefab_registry_builder,
+            self.hot_reload_assets,
+            self.capture_hotkey,
+            &self.sounds,
+            &self.shader_includes,
+            self.input_map,
+            &gpu_options,
+            self.prebuilt_gpu,
         )?;
+        let (window, event_loop) = (window.unwrap(), event_loop.unwrap());
         let gpu_mut = gpu.lock().unwrap();
 
+        info!("building materials");
+        let materials = Arc::new(RwLock::new(self.material_registry_builder.build(
+            &gpu_mut.device,
+            &gpu_mut.queue,
+            &registry.textures.read().unwrap(),
+        )?));
+
         info!("building uniforms");
-        let quad_group_builder = Arc::new(Mutex::new(QuadUniformGroup::builder()));
         let camera_3d_group_builder = Arc::new(Mutex::new(Camera3DUniformGroup::builder()));
         let render_3d_group_builder = Arc::new(Mutex::new(Render3DForwardUniformGroup::builder()));
         let render_pbr_group_builder =
             Arc::new(Mutex::new(RenderPBRForwardUniformGroup::builder()));
+        let lighting_3d_group_builder = Arc::new(Mutex::new(Lighting3DUniformGroup::builder()));
+        let environment_group_builder = Arc::new(Mutex::new(build_environment_group(&registry)));
 
         info!("building render graph nodes");
         let node_sky = build_node_sky(
             Arc::clone(&render_3d_group_builder),
             Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&environment_group_builder),
         );
         let node_pbr = build_node_forward_pbr(
             Arc::clone(&render_pbr_group_builder),
             Arc::clone(&camera_3d_group_builder),
+            Arc::clone(&lighting_3d_group_builder),
+            Arc::clone(&environment_group_builder),
         );
-        let node_channel = build_node_channel(
-            Arc::clone(&quad_group_builder),
-            Arc::clone(&camera_3d_group_builder),
-        );
+        let node_channel = build_node_channel(Arc::clone(&camera_3d_group_builder));
+        let node_preview = match self.material_editor {
+            true => {
+                let mut node = build_node_forward_pbr(
+                    Arc::clone(&render_pbr_group_builder),
+                    Arc::clone(&camera_3d_group_builder),
+                    Arc::clone(&lighting_3d_group_builder),
+                    Arc::clone(&environment_group_builder),
+                )
+                .with_id(ID(MATERIAL_PREVIEW_NODE_ID))
+                .with_target_scale(material_preview::PREVIEW_SCALE)
+                .with_depth_buffer();
+                node.dest_name = "material_preview".to_owned();
+                Some(node)
+            }
+            false => None,
+        };
 
         info!("scheduling systems");
-        let mut schedule = Schedule::builder();
+        let mut schedule = StagedSchedule::builder();
+        add_engine_events(&mut resources, schedule.stage(Stage::Clear));
         schedule
-            // Main engine systems
-            .add_system(camera_3d_system())
-            .flush()
+            .stage(Stage::Input)
+            .add_system(frame_system())
+            .add_system(name_index_system())
+            .add_system(camera_3d_system());
+        // schedule.stage(Stage::Simulation).add_system(physics_3d_system());
+        schedule
+            .stage(Stage::UniformLoad)
+            .add_system(lighting_3d_system())
             .add_system(sky::update_system())
-            // .add_system(physics_3d_system())
-            // Uniform loading systems
+            .add_system(dynamic_mesh_upload_system())
             .flush()
             .add_system(camera_3d_uniform_system())
+            .add_system(lighting_3d_uniform_system())
+            .add_system(frame_uniform_system())
             .add_system(render_3d::forward_basic::load_system())
-            .add_system(render_3d::forward_pbr::load_system())
-            .add_system(quad::load_system());
+            .add_system(render_3d::forward_pbr::load_system());
+        if self.material_editor {
+            schedule
+                .stage(Stage::UniformLoad)
+                .add_system(material_preview::update_system())
+                .add_system(viewport_3d_system());
+        }
 
         info!("building render graph");
         let metrics_ui = EngineMetrics::new();
         let mut graph_schedule = SubSchedule::new();
 
-        let (render_graph, engine_metrics) = GraphBuilder::new()
+        // resource
+        let preview = Arc::new(MaterialPreview::default());
+        let mut graph_builder = GraphBuilder::new()
             .with_channel(node_sky.dest_id.clone(), 0, node_pbr.dest_id.clone())
             .with_channel(node_pbr.dest_id.clone(), 0, node_channel.dest_id.clone())
             .with_chain(vec![node_sky.dest_id.clone(), node_pbr.dest_id.clone()])
             .with_source_node(node_sky)
             .with_source_node(node_pbr)
             .with_master_node(node_channel)
-            .with_ui_iced()
-            .build(
-                Arc::clone(&gpu_mut.device),
-                Arc::clone(&gpu_mut.queue),
-                &mut resources,
-                &mut graph_schedule,
-                &registry,
-                &window,
-                metrics_ui,
-                &helper,
-            )?;
+            .with_ui_iced();
+        if let Some(node) = node_preview {
+            graph_builder = graph_builder
+                .with_texture_node(node)
+                .with_material_preview()
+                .with_ui_panel(Box::new(MaterialEditorPanel::new(
+                    Arc::clone(&materials),
+                    Arc::clone(&preview),
+                )));
+        }
+        let (render_graph, engine_metrics) = graph_builder.build(
+            Arc::clone(&gpu_mut.device),
+            Arc::clone(&gpu_mut.uploader),
+            &mut resources,
+            &mut graph_schedule,
+            &registry,
+            metrics_ui,
+            &helper,
+        )?;
 
         info!("scheduling render graph");
-        graph_schedule.schedule(&mut schedule);
+        graph_schedule.schedule(schedule.stage(Stage::Render));
         let schedule = schedule.build();
 
         // resource
@@ -741,73 +2311,51 @@ impl EngineBuilder {
         let frame_metrics = Arc::new(RwLock::new(FrameMetrics::new()));
 
         // resource
-        let quad = {
-            let quad_group_builder = resources
-                .get::<Arc<Mutex<GroupStateBuilder<QuadUniformGroup>>>>()
-                .unwrap();
-
-            let builder_mut = quad_group_builder.lock().unwrap();
-
-            quad::Quad {
-                mesh: registry
-                    .meshes
-                    .read()
-                    .unwrap()
-                    .clone_mesh(&ID(SCREEN_QUAD_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
-                uniforms: Default::default(),
-                uniform_group: builder_mut.single_state(&gpu_mut.device, &gpu_mut.queue)?,
-            }
-        };
+        let quad = build_quad(&registry);
 
         // resource
         let camera_3d = Arc::new(Mutex::new(Camera3D::default(
-            self.window_size.0 as f32,
-            self.window_size.1 as f32,
+            self.window_config.size.0 as f32,
+            self.window_config.size.1 as f32,
         )));
-        camera_3d.lock().unwrap().right_click_move = true;
+        let camera_controller = self.camera_controller.unwrap_or_else(|| {
+            CameraController3D::Fps(FpsController {
+                right_click_move: true,
+                ..Default::default()
+            })
+        });
 
         // resource
         let sky = {
-            // where the fuck is this added into resources?
-            let r3d_group_builder = resources
-                .get::<Arc<Mutex<GroupStateBuilder<Render3DForwardUniformGroup>>>>()
-                .unwrap();
-
-            let builder_mut = r3d_group_builder.lock().unwrap();
-
-            sky::Sky {
-                cubemap: Arc::clone(
-                    registry
-                        .textures
-                        .read()
-                        .unwrap()
-                        .texture_group(&ID(RENDER_3D_TEXTURE_GROUP))
-                        .get(&ID(RENDER_3D_SKYBOX_TEXTURE_ID))
-                        .unwrap(),
-                ),
-                cubemap_blur: Some(Arc::clone(
-                    registry
-                        .textures
-                        .read()
-                        .unwrap()
-                        .texture_group(&ID(RENDER_3D_TEXTURE_GROUP))
-                        .get(&ID(RENDER_3D_SKYBOX_BLUR_TEXTURE_ID))
-                        .unwrap(),
-                )),
-                shared_group: Some(Arc::clone(
-                    &registry.textures.read().unwrap().shared[&ID(SKYBOX_SHARED_GROUP)],
-                )),
-                mesh: registry
-                    .meshes
-                    .read()
-                    .unwrap()
-                    .clone_mesh(&ID(UNIT_CUBE_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
-                t3d: Transform3D::origin(),
-                r3d: Render3D::default("sky"),
-                r3d_group: builder_mut.single_state(&gpu_mut.device, &gpu_mut.queue)?,
-            }
+            let cubemap = match self.skybox {
+                true => ID(RENDER_3D_CUSTOM_SKYBOX_TEXTURE_ID),
+                false => ID(RENDER_3D_SKYBOX_TEXTURE_ID),
+            };
+            let mut sky = build_sky(&registry, &resources, &gpu_mut, cubemap)?;
+
+            let textures = registry.textures.read().unwrap();
+            sky.cubemap_blur = Some(Arc::clone(
+                textures
+                    .texture_group(&ID(RENDER_3D_TEXTURE_GROUP))
+                    .get(&ID(RENDER_3D_SKYBOX_BLUR_TEXTURE_ID))
+                    .unwrap(),
+            ));
+            sky.shared_group = Some(Arc::clone(&textures.shared[&ID(SKYBOX_SHARED_GROUP)]));
+            sky
         };
 
+        let mut world = World::default();
+        if self.material_editor {
+            material_preview::spawn(
+                &mut world,
+                &gpu_mut.device,
+                (
+                    self.window_config.size.0 as f32,
+                    self.window_config.size.1 as f32,
+                ),
+            );
+        }
+
         drop(gpu_mut);
         resources.insert(quad);
         resources.insert(sky);
@@ -815,6 +2363,8 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&window));
         resources.insert(Arc::clone(&registry.textures));
         resources.insert(Arc::clone(&registry.meshes));
+        resources.insert(materials);
+        resources.insert(preview);
         resources.insert(Arc::clone(&helper));
         resources.insert(Arc::clone(&input));
         resources.insert(Arc::clone(&frame_metrics));
@@ -822,6 +2372,7 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&render_3d_group_builder)); // what the shit is this?
         resources.insert(Arc::clone(&render_pbr_group_builder)); // what the shit is this?
         resources.insert(Arc::clone(&camera_3d));
+        resources.insert(camera_controller);
 
         let clipboard = Clipboard::connect(&window);
 
@@ -833,18 +2384,27 @@ impl EngineBuilder {
                 helper,
                 input,
                 legion: LegionState {
-                    world: World::default(),
+                    world,
                     schedule,
                     resources,
+                    fixed: None,
                 },
                 graph: render_graph,
-                cursor_state: CursorState::default(),
+                cursor_state: CursorState::new(self.cursor_mode),
+                frame_limiter: self.frame_cap.map(FrameLimiter::new),
+                background: BackgroundState::new(self.background_policy),
+                exit_policy: self.exit_policy,
+                metrics_export: self.metrics_export,
+                input_log: self.input_log.map(InputLogMode::open).transpose()?,
+                started: false,
+                metrics_updated: Instant::now(),
+                gamepads: Gamepads::new(),
                 registry,
-                window,
+                window: Some(window),
                 engine_metrics,
                 frame_metrics,
                 gpu,
-                clipboard,
+                clipboard: Some(clipboard),
             },
             event_loop,
         ))
@@ -855,37 +2415,47 @@ impl EngineBuilder {
         warn!("RUNNING EXPERIMENTAL ENGINE MODE: test_automata_node");
         info!("building engine: test_automata_node");
 
+        let gpu_options = self.surface_gpu_options();
+
         let (gpu, window, event_loop, registry, mut resources, helper) = build_engine_common(
-            self.window_size,
+            &self.window_config,
+            false,
             self.texture_registry_builder,
             self.mesh_registry_builder,
+            self.prefab_registry_builder,
+            self.hot_reload_assets,
+            self.capture_hotkey,
+            &self.sounds,
+            &self.shader_includes,
+            self.input_map,
+            &gpu_options,
+            self.prebuilt_gpu,
         )?;
+        let (window, event_loop) = (window.unwrap(), event_loop.unwrap());
         let gpu_mut = gpu.lock().unwrap();
 
         info!("building uniforms");
-        let quad_group_builder = Arc::new(Mutex::new(QuadUniformGroup::builder()));
 
         info!("building render graph nodes");
 
         let node_chain = build_node_chain(
             ShaderSource::WGSL(include_str!("renderer/shaders/automata.wgsl").to_owned()),
             2,
-            Arc::clone(&quad_group_builder),
         );
 
         let camera_3d_group_builder = Arc::new(Mutex::new(Camera3DUniformGroup::builder()));
-        let node_channel = build_node_channel(
-            Arc::clone(&quad_group_builder),
-            Arc::clone(&camera_3d_group_builder),
-        );
+        let node_channel = build_node_channel(Arc::clone(&camera_3d_group_builder));
 
         info!("scheduling systems");
-        let mut schedule = Schedule::builder();
+        let mut schedule = StagedSchedule::builder();
+        add_engine_events(&mut resources, schedule.stage(Stage::Clear));
         schedule
-            // Main engine systems
-            // Uniform loading systems
-            .flush()
-            .add_system(quad::load_system());
+            .stage(Stage::Input)
+            .add_system(frame_system())
+            .add_system(name_index_system());
+        schedule
+            .stage(Stage::UniformLoad)
+            .add_system(frame_uniform_system());
 
         info!("building render graph");
         let metrics_ui = EngineMetrics::new();
@@ -896,17 +2466,16 @@ impl EngineBuilder {
             .with_master_node(node_channel)
             .build(
                 Arc::clone(&gpu_mut.device),
-                Arc::clone(&gpu_mut.queue),
+                Arc::clone(&gpu_mut.uploader),
                 &mut resources,
                 &mut graph_schedule,
                 &registry,
-                &window,
                 metrics_ui,
                 &helper,
             )?;
 
         info!("scheduling render graph");
-        graph_schedule.schedule(&mut schedule);
+        graph_schedule.schedule(schedule.stage(Stage::Render));
         let schedule = schedule.build();
 
         // resource
@@ -917,28 +2486,12 @@ impl EngineBuilder {
         let frame_metrics = Arc::new(RwLock::new(FrameMetrics::new()));
 
         // resource
-        let quad = {
-            let quad_group_builder = resources
-                .get::<Arc<Mutex<GroupStateBuilder<QuadUniformGroup>>>>()
-                .unwrap();
-
-            let builder_mut = quad_group_builder.lock().unwrap();
-
-            quad::Quad {
-                mesh: registry
-                    .meshes
-                    .read()
-                    .unwrap()
-                    .clone_mesh(&ID(SCREEN_QUAD_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
-                uniforms: Default::default(),
-                uniform_group: builder_mut.single_state(&gpu_mut.device, &gpu_mut.queue)?,
-            }
-        };
+        let quad = build_quad(&registry);
 
         // resource
         let camera_3d = Arc::new(Mutex::new(Camera3D::default(
-            self.window_size.0 as f32,
-            self.window_size.1 as f32,
+            self.window_config.size.0 as f32,
+            self.window_config.size.1 as f32,
         )));
 
         drop(gpu_mut);
@@ -953,6 +2506,7 @@ impl EngineBuilder {
         resources.insert(Arc::clone(&render_graph));
         // resources.insert(Arc::clone(&render_3d_group_builder)); // what the shit is this?
         resources.insert(Arc::clone(&camera_3d));
+        resources.insert(self.camera_controller.unwrap_or_default());
 
         let clipboard = Clipboard::connect(&window);
 
@@ -967,15 +2521,24 @@ impl EngineBuilder {
                     world: World::default(),
                     schedule,
                     resources,
+                    fixed: None,
                 },
                 graph: render_graph,
-                cursor_state: CursorState::default(),
+                cursor_state: CursorState::new(self.cursor_mode),
+                frame_limiter: self.frame_cap.map(FrameLimiter::new),
+                background: BackgroundState::new(self.background_policy),
+                exit_policy: self.exit_policy,
+                metrics_export: self.metrics_export,
+                input_log: self.input_log.map(InputLogMode::open).transpose()?,
+                started: false,
+                metrics_updated: Instant::now(),
+                gamepads: Gamepads::new(),
                 registry,
-                window,
+                window: Some(window),
                 engine_metrics,
                 frame_metrics,
                 gpu,
-                clipboard,
+                clipboard: Some(clipboard),
             },
             event_loop,
         ))
@@ -983,51 +2546,215 @@ impl EngineBuilder {
 }
 
 fn build_engine_common(
-    window_size: (u32, u32),
+    window_config: &WindowConfig,
+    headless: bool,
     tex_reg_builder: TextureRegistryBuilder,
     mesh_reg_builder: MeshRegistryBuilder,
+    prefab_reg_builder: PrefabRegistryBuilder,
+    hot_reload_assets: bool,
+    capture_hotkey: Option<VirtualKeyCode>,
+    sounds: &[(Uuid, String)],
+    shader_includes: &[(String, String)],
+    input_map: InputMap,
+    gpu_options: &GpuOptions,
+    prebuilt: Option<PrebuiltGpu>,
 ) -> Result<(
     Arc<Mutex<GpuState>>,
-    Arc<Window>,
-    EventLoop<()>,
+    Option<Arc<Window>>,
+    Option<EventLoop<()>>,
     Registry,
     Resources,
     IcedWinitHelper,
 )> {
-    let mut resources = Resources::default();
+    let (mut resources, prebuilt) = match prebuilt {
+        Some(prebuilt) => (
+            prebuilt.resources,
+            Some((prebuilt.gpu, prebuilt.window, prebuilt.event_loop)),
+        ),
+        None => (Resources::default(), None),
+    };
     resources.insert(RwLock::new(FrameMetrics::new()));
+    resources.insert(Time::new());
+    resources.insert(EngineRng::from_entropy());
+    resources.insert(Arc::new(RwLock::new(NameIndex::new())));
+    resources.insert(CommandQueue::new());
+    resources.insert(EngineCommands::new());
+    resources.insert(EngineState::default());
+    resources.insert(
+        DisplayQueue::new().with_fullscreen(window_config.fullscreen != FullscreenMode::Windowed),
+    );
+    resources.insert(CaptureQueue::new().with_hotkey(capture_hotkey));
+    resources.insert(input_map);
 
     info!("building gpu");
-    let (gpu, window, event_loop) = build_gpu(&mut resources, window_size)?;
+    window_config.install_panic_hook();
+    let (gpu, window, event_loop) = match (prebuilt, headless) {
+        (Some((gpu, window, event_loop)), _) => (gpu, Some(window), Some(event_loop)),
+        (None, true) => (
+            build_headless_gpu(&mut resources, window_config.size, gpu_options)?,
+            None,
+            None,
+        ),
+        (None, false) => {
+            let (gpu, window, event_loop) =
+                build_gpu(&mut resources, window_config, &tex_reg_builder, gpu_options)?;
+            (gpu, Some(window), Some(event_loop))
+        }
+    };
+
+    // Nothing to watch in the browser
+    if hot_reload_assets && cfg!(target_arch = "wasm32") {
+        warn!("hot reloading assets isn't available on the web");
+    } else if hot_reload_assets {
+        let sources = registry::watch(&tex_reg_builder, &mesh_reg_builder);
+        info!("watching {} assets for changes", sources.len());
+        resources.insert(hot_reload::AssetWatcher::new(sources));
+    }
 
     info!("building registry");
-    let registry = build_registry(Arc::clone(&gpu), tex_reg_builder, mesh_reg_builder)?;
+    let mut shaders = ShaderRegistry::default();
+    for (name, source) in shader_includes {
+        shaders.insert(name, source);
+    }
+    let (registry, stream) =
+        build_registry(Arc::clone(&gpu), tex_reg_builder, mesh_reg_builder, shaders)?;
+    if let Some(stream) = stream {
+        resources.insert(stream);
+    }
+    resources.insert(Arc::clone(&registry.loads));
+    resources.insert(prefab_reg_builder.build(&registry)?);
+
+    info!("loading sounds");
+    let mut audio_registry = AudioRegistry::new();
+    for (id, path) in sounds {
+        audio_registry.load_id(*id, path)?;
+    }
+    resources.insert(Arc::new(RwLock::new(audio_registry)));
+    resources.insert(AudioListener::new());
+
+    info!("building frame uniforms");
+    build_frame_uniforms(&gpu, &mut resources)?;
 
     let window_size = WindowSize {
-        width: window_size.0 as f32,
-        height: window_size.1 as f32,
+        width: window_config.size.0 as f32,
+        height: window_config.size.1 as f32,
     };
     resources.insert(Arc::new(window_size));
 
-    let helper = IcedWinitHelper::new(&window);
+    let helper = match &window {
+        Some(window) => IcedWinitHelper::new(window),
+        None => IcedWinitHelper::headless(window_config.size),
+    };
 
     Ok((gpu, window, event_loop, registry, resources, helper))
 }
 
+// An Events<T> resource, updated at the end of every frame by a system in Stage::Clear
+fn add_events<T: Send + Sync + 'static>(
+    resources: &mut Resources,
+    schedule: &mut legion::systems::Builder,
+) {
+    resources.insert(Events::<T>::new());
+    schedule.add_system(update_events_system::<T>());
+}
+
+// The events every engine sends
+fn add_engine_events(resources: &mut Resources, schedule: &mut legion::systems::Builder) {
+    add_events::<EngineWindowEvent>(resources, schedule);
+    add_events::<AssetGroupLoaded>(resources, schedule);
+    add_events::<SurfaceEvent>(resources, schedule);
+}
+
+// Moves the extra nodes wired as post-processing passes into post_fx, returning the rest; see
+// EngineBuilder::with_extra_node
+fn wire_post_process_nodes(
+    mut post_fx: PostFxChainBuilder,
+    nodes: Vec<(NodeBuilder, GraphWiring)>,
+) -> (PostFxChainBuilder, Vec<(NodeBuilder, GraphWiring)>) {
+    let (passes, rest): (Vec<_>, Vec<_>) = nodes
+        .into_iter()
+        .partition(|(_, wiring)| *wiring == GraphWiring::PostProcess);
+    for (node, _) in passes {
+        post_fx = post_fx.with_pass(node);
+    }
+    (post_fx, rest)
+}
+
+fn wire_extra_nodes(
+    mut graph: GraphBuilder,
+    nodes: Vec<(NodeBuilder, GraphWiring)>,
+    graphs: Vec<GraphBuilder>,
+) -> GraphBuilder {
+    for (node, wiring) in nodes {
+        graph = match wiring {
+            GraphWiring::Overlay => graph.with_overlay_node(node),
+            GraphWiring::Texture => graph.with_texture_node(node),
+            GraphWiring::PostProcess => unreachable!("post-processing nodes are wired by post_fx"),
+        };
+    }
+    for other in graphs {
+        graph = graph.merge(other);
+    }
+    graph
+}
+
 // Dimension-agnostic init logic
 fn build_gpu(
     resources: &mut Resources,
-    window_size: (u32, u32),
+    window_config: &WindowConfig,
+    textures: &TextureRegistryBuilder,
+    gpu_options: &GpuOptions,
 ) -> Result<(Arc<Mutex<GpuState>>, Arc<Window>, EventLoop<()>)> {
     let event_loop = EventLoop::new();
-    let window = build_window(window_size, &event_loop)?;
+    let window = build_window(window_config, textures, &event_loop)?;
 
     let gpu = Arc::new(Mutex::new(futures::executor::block_on(
-        GpuStateBuilder::winit(Arc::clone(&window)).build(resources),
+        GpuStateBuilder::winit(Arc::clone(&window))
+            .with_present_mode(window_config.present_mode)
+            .with_options(gpu_options.clone())
+            .build(resources),
     )?));
     Ok((gpu, window, event_loop))
 }
 
+fn build_headless_gpu(
+    resources: &mut Resources,
+    size: (u32, u32),
+    gpu_options: &GpuOptions,
+) -> Result<Arc<Mutex<GpuState>>> {
+    *renderer::SCREEN_SIZE.write().unwrap() = size;
+    info!("INITIAL SCREEN_SIZE (headless): {}, {}", size.0, size.1);
+
+    Ok(Arc::new(Mutex::new(futures::executor::block_on(
+        GpuStateBuilder::headless(size)
+            .with_options(gpu_options.clone())
+            .build(resources),
+    )?)))
+}
+
+// The frame uniform group is built ahead of the render graph, so that its systems can always
+// run and any node can share it via NodeBuilder::with_frame_uniforms()
+fn build_frame_uniforms(gpu: &Arc<Mutex<GpuState>>, resources: &mut Resources) -> Result<()> {
+    let gpu_mut = gpu.lock().unwrap();
+    let frame_group_builder = Arc::new(Mutex::new(FrameUniformGroup::builder()));
+
+    let mut builder_mut = frame_group_builder.lock().unwrap();
+    builder_mut.build(&gpu_mut.device, resources, Arc::clone(&gpu_mut.uploader))?;
+    builder_mut.build_to_resource(resources);
+    drop(builder_mut);
+
+    resources.insert(frame_group_builder);
+    Ok(())
+}
+
+// In the browser, the engine's own assets (src/sources/static) are served under ember/, next
+// to the page
+#[cfg(target_arch = "wasm32")]
+fn get_crate_directory() -> PathBuf {
+    PathBuf::from("ember")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn get_crate_directory() -> PathBuf {
     option_env!("CARGO_MANIFEST_DIR").map_or_else(
         || {
@@ -1041,45 +2768,61 @@ fn get_crate_directory() -> PathBuf {
     )
 }
 
-fn build_window(size: (u32, u32), event_loop: &EventLoop<()>) -> Result<Arc<Window>> {
-    let size = LogicalSize::new(size.0 as f64, size.1 as f64);
-
-    // Set initial size
-    let ss_u32 = (size.width as u32, size.height as u32);
-    *renderer::SCREEN_SIZE.write().unwrap() = ss_u32;
-    info!("INITIAL SCREEN_SIZE: {}, {}", ss_u32.0, ss_u32.1);
-
-    Ok(Arc::new({
-        WindowBuilder::new()
-            .with_title("Ember Engine")
-            .with_inner_size(size)
-            // .with_min_inner_size(size)
-            // .with_max_inner_size(size)
-            .with_resizable(false)
-            .with_fullscreen(None)
-            // .with_fullscreen(Some(Fullscreen::Borderless(None)))
-            .build(event_loop)?
-    }))
+fn build_window(
+    config: &WindowConfig,
+    textures: &TextureRegistryBuilder,
+    event_loop: &EventLoop<()>,
+) -> Result<Arc<Window>> {
+    let window = config
+        .apply(WindowBuilder::new(), textures, event_loop)
+        .build(event_loop)?;
+    #[cfg(target_arch = "wasm32")]
+    config.attach_canvas(&window)?;
+
+    // Fullscreen and hidpi scaling both change the size we asked for, so start from
+    // whatever the surface will actually be
+    let size = window.inner_size();
+    *renderer::SCREEN_SIZE.write().unwrap() = (size.width, size.height);
+    info!("INITIAL SCREEN_SIZE: {}, {}", size.width, size.height);
+
+    Ok(Arc::new(window))
 }
 
 fn build_registry(
     gpu: Arc<Mutex<GpuState>>,
     mut tex_reg_builder: TextureRegistryBuilder,
     mesh_reg_builder: MeshRegistryBuilder,
-) -> Result<Registry> {
+    shaders: ShaderRegistry,
+) -> Result<(Registry, Option<AssetStream>)> {
     let mut gpu_mut = gpu.lock().unwrap();
     let base_dir = get_crate_directory();
 
     load_engine_textures(&mut tex_reg_builder, &base_dir);
+    let streamed = match tex_reg_builder.stream {
+        true => registry::watch(&tex_reg_builder, &mesh_reg_builder),
+        false => vec![],
+    };
 
     let texture_format = gpu_mut.device_preferred_format();
-    Registry::build(
+    let registry = Registry::build(
         Arc::clone(&gpu_mut.device),
         &gpu_mut.queue,
         texture_format,
         tex_reg_builder,
         mesh_reg_builder,
-    )
+        shaders,
+    )?;
+    let stream = match streamed.is_empty() {
+        true => None,
+        false => Some(AssetStream::start(
+            streamed,
+            Arc::clone(&gpu_mut.device),
+            Arc::clone(&gpu_mut.queue),
+            texture_format,
+            &registry.loads,
+        )),
+    };
+    Ok((registry, stream))
 }
 
 fn load_engine_textures(builder: &mut TextureRegistryBuilder, base_dir: &PathBuf) {
@@ -1132,6 +2875,31 @@ fn load_engine_textures(builder: &mut TextureRegistryBuilder, base_dir: &PathBuf
         None,
     );
 
+    for (name, id, group_id) in &[
+        (
+            "render_2d_common",
+            RENDER_2D_COMMON_TEXTURE_ID,
+            RENDER_2D_TEXTURE_GROUP,
+        ),
+        (
+            "render_3d_common",
+            RENDER_3D_COMMON_TEXTURE_ID,
+            RENDER_3D_TEXTURE_GROUP,
+        ),
+        (
+            "render_3d_skybox",
+            RENDER_3D_SKYBOX_TEXTURE_ID,
+            RENDER_3D_TEXTURE_GROUP,
+        ),
+        (
+            "render_3d_skybox_blur",
+            RENDER_3D_SKYBOX_BLUR_TEXTURE_ID,
+            RENDER_3D_TEXTURE_GROUP,
+        ),
+    ] {
+        builder.name(name, Handle::new(ID(id), ID(group_id)));
+    }
+
     builder.with_shared_group(
         ID(SKYBOX_SHARED_GROUP),
         vec![
@@ -1144,6 +2912,82 @@ fn load_engine_textures(builder: &mut TextureRegistryBuilder, base_dir: &PathBuf
     );
 }
 
+// From the environment loaded with EngineBuilder::with_environment, or the default cubemap's
+fn build_environment_group(registry: &Registry) -> UniformGroupBuilder<EnvironmentUniformGroup> {
+    match &registry.textures.read().unwrap().environment {
+        Some(irradiance) => EnvironmentUniformGroup::from_irradiance(irradiance),
+        None => EnvironmentUniformGroup::builder(),
+    }
+}
+
+// The Sky resource for a graph with a sky node, once the graph is built
+fn build_sky(
+    registry: &Registry,
+    resources: &Resources,
+    gpu: &GpuState,
+    cubemap: Uuid,
+) -> Result<sky::Sky> {
+    let r3d_group_builder = resources
+        .get::<Arc<Mutex<GroupStateBuilder<Render3DForwardUniformGroup>>>>()
+        .ok_or_else(|| anyhow!("the sky needs the Render3D uniform group"))?;
+    let builder_mut = r3d_group_builder.lock().unwrap();
+
+    let cubemap = registry
+        .textures
+        .read()
+        .unwrap()
+        .texture_group(&ID(RENDER_3D_TEXTURE_GROUP))
+        .get(&cubemap)
+        .map(Arc::clone)
+        .ok_or_else(|| anyhow!("no skybox cubemap {}", cubemap))?;
+
+    Ok(sky::Sky {
+        rotation: [0.0, 0.0, 0.0],
+        tint: [1.0, 1.0, 1.0, 1.0],
+        cubemap,
+        cubemap_blur: None,
+        shared_group: None,
+        mesh: registry
+            .meshes
+            .read()
+            .unwrap()
+            .clone_mesh(&ID(UNIT_CUBE_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
+        t3d: Transform3D::origin(),
+        r3d: Render3D::default("sky"),
+        r3d_group: builder_mut.single_state(&gpu.device, &gpu.uploader)?,
+    })
+}
+
+fn build_quad(registry: &Registry) -> quad::Quad {
+    quad::Quad {
+        mesh: registry
+            .meshes
+            .read()
+            .unwrap()
+            .clone_mesh(&ID(SCREEN_QUAD_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID)),
+    }
+}
+
+// Engine systems first, then the game's
+fn build_fixed_stage(
+    hz: Option<f32>,
+    game_systems: Vec<GameSystem>,
+    engine_systems: impl FnOnce(&mut legion::systems::Builder),
+) -> Option<FixedStage> {
+    if hz.is_none() && game_systems.is_empty() {
+        return None;
+    }
+    let mut schedule = Schedule::builder();
+    engine_systems(&mut schedule);
+    for game_system in game_systems {
+        game_system(&mut schedule);
+    }
+    Some(FixedStage::new(
+        schedule.build(),
+        hz.unwrap_or(DEFAULT_FIXED_UPDATE_HZ),
+    ))
+}
+
 // --------------------------------------------------
 // Render Graph Node Presets
 // --------------------------------------------------
@@ -1183,7 +3027,9 @@ fn build_node_2d_forward_instance(
     .with_id(ID(INSTANCE_2D_NODE_ID))
     .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
     .with_vertex_layout(render_2d::forward_instance::RENDER2DINSTANCE_BUFFER_LAYOUT)
-    .with_texture_group(ID(RENDER_2D_TEXTURE_GROUP), TextureType::Image)
+    .with_system_texture(TextureType::ImageArray {
+        n: MAX_TEXTURES_2D_BATCH,
+    })
     .with_shared_uniform_group(Arc::clone(&camera_2d_group_builder))
     .with_shared_uniform_group(Arc::clone(&lighting_2d_group_builder))
     .with_system(render_2d::forward_instance::render_system)
@@ -1193,28 +3039,240 @@ fn build_node_2d_forward_instance(
 fn build_node_3d_forward_basic(
     render_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DForwardUniformGroup>>>,
     camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
-    //lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+    ssao_group_builder: Option<Arc<Mutex<UniformGroupBuilder<SsaoUniformGroup>>>>,
 ) -> NodeBuilder {
-    NodeBuilder::new(
+    let node = NodeBuilder::new(
         "render_3d_basic_node".to_owned(),
-        0,
+        2 + ssao_group_builder.is_some() as u32,
         1,
-        ShaderSource::WGSL(include_str!("renderer/shaders/render_3d.wgsl").to_owned()),
+        ShaderSource::WGSL(render_3d::ssao::lighting_shader(
+            include_str!("renderer/shaders/render_3d.wgsl"),
+            ssao_group_builder.is_some(),
+        )),
     )
     .with_id(ID(FORWARD_3D_NODE_ID))
     .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
     .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
     .with_shared_uniform_group(Arc::clone(&render_3d_group_builder))
     .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_shadow_map_input()
+    .with_shadow_cube_input()
     // .with_depth_buffer()
-    .with_system(render_3d::forward_basic::render_system)
+    .with_system(render_3d::forward_basic::render_system);
+
+    // Node inputs are ordered by node id, so the blurred AO comes after the shadow maps
+    match ssao_group_builder {
+        Some(ssao_group_builder) => node
+            .with_node_input()
+            .with_shared_uniform_group(ssao_group_builder),
+        None => node,
+    }
+}
+
+// Render3D entities with a Skeleton, on top of the basic node's target
+fn build_node_3d_forward_instance(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "render_3d_instance_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/render_3d_instance.wgsl").to_owned()),
+    )
+    .with_id(ID(INSTANCE_3D_NODE_ID))
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_vertex_layout(render_3d::forward_instance::RENDER3DINSTANCE_BUFFER_LAYOUT)
+    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_depth_buffer()
+    .with_system(render_3d::forward_instance::render_system)
+}
+
+// FoliageChunk instance groups, with the forward_instance shader plus wind
+fn build_node_foliage(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "render_3d_foliage_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/render_3d_instance.wgsl").to_owned()),
+    )
+    .with_id(ID(FOLIAGE_NODE_ID))
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_vertex_layout(render_3d::forward_instance::RENDER3DINSTANCE_BUFFER_LAYOUT)
+    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_frame_uniforms()
+    .with_shared_uniform_group(Arc::new(Mutex::new(FoliageWindUniformGroup::builder())))
+    .with_shader_define("FOLIAGE_WIND", 1)
+    .with_depth_buffer()
+    .with_system(render_3d::foliage::render_system)
+}
+
+fn build_node_3d_forward_skinned(
+    render_skinned_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DSkinnedUniformGroup>>>,
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "render_3d_skinned_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/render_3d_skinned.wgsl").to_owned()),
+    )
+    .with_id(ID(FORWARD_SKINNED_NODE_ID))
+    .with_vertex_layout(VERTEX3DSKINNED_BUFFER_LAYOUT)
+    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    .with_shared_uniform_group(Arc::clone(&render_skinned_group_builder))
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_depth_buffer()
+    .with_system(render_3d::forward_skinned::render_system)
+}
+
+// Render3D entities with a RenderToon, on top of the basic node's target; or with outline,
+// their inverted hulls
+fn build_node_3d_forward_toon(
+    render_toon_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DToonUniformGroup>>>,
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+    outline: bool,
+) -> NodeBuilder {
+    let node = NodeBuilder::new(
+        match outline {
+            true => "render_3d_toon_outline_node",
+            false => "render_3d_toon_node",
+        }
+        .to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/render_3d_toon.wgsl").to_owned()),
+    )
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    .with_shared_uniform_group(Arc::clone(&render_toon_group_builder))
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_depth_buffer();
+    match outline {
+        true => node
+            .with_id(ID(TOON_OUTLINE_NODE_ID))
+            .with_shader_define("TOON_OUTLINE", 1)
+            .with_reverse_culling()
+            .with_system(render_3d::forward_toon::render_outline_system),
+        false => node
+            .with_id(ID(FORWARD_TOON_NODE_ID))
+            .with_system(render_3d::forward_toon::render_system),
+    }
+}
+
+// depth of every Render3D entity, from the directional light
+fn build_node_shadow_map(
+    render_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DForwardUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "shadow_map_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/shadow.wgsl").to_owned()),
+    )
+    .with_id(ID(SHADOW_MAP_NODE_ID))
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&render_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_depth_target(2048)
+    .with_system(render_3d::shadow::render_system)
+}
+
+// depth of every Render3D entity around the point light with shadows, a cube face at a time
+fn build_node_point_shadow_map(
+    render_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DForwardUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "point_shadow_map_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/shadow_point.wgsl").to_owned()),
+    )
+    .with_id(ID(POINT_SHADOW_MAP_NODE_ID))
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&render_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_depth_cube_target(1024)
+    // The faces are mirrored; see CubeTarget::view_projs
+    .with_reverse_culling()
+    .with_system(render_3d::shadow::render_point_system)
+}
+
+// G-buffer for SSAO: world normals and depth of every Render3D entity
+fn build_node_normal_depth(
+    render_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DForwardUniformGroup>>>,
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "normal_depth_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/ssao/normal_depth.wgsl").to_owned()),
+    )
+    .with_id(ID(NORMAL_DEPTH_NODE_ID))
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&render_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_hdr_target(render_3d::ssao::NORMAL_DEPTH_FORMAT)
+    .with_depth_buffer()
+    .with_system(render_3d::ssao::normal_depth_system)
+}
+
+// Ambient occlusion from the normal_depth node, at half resolution
+fn build_node_ssao(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    ssao_group_builder: Arc<Mutex<UniformGroupBuilder<SsaoUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "ssao_node".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/ssao/ssao.wgsl").to_owned()),
+    )
+    .with_id(ID(SSAO_NODE_ID))
+    .with_unfilterable_node_input()
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&ssao_group_builder))
+    .with_hdr_target(render_3d::ssao::SSAO_FORMAT)
+    .with_target_scale(render_3d::ssao::SSAO_SCALE)
+    .with_system(render_3d::ssao::render_system)
+}
+
+fn build_node_ssao_blur() -> NodeBuilder {
+    NodeBuilder::new(
+        "ssao_blur_node".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/ssao/blur.wgsl").to_owned()),
+    )
+    .with_id(ID(SSAO_BLUR_NODE_ID))
+    .with_node_input()
+    .with_hdr_target(render_3d::ssao::SSAO_FORMAT)
+    .with_target_scale(render_3d::ssao::SSAO_SCALE)
+    .with_system(render_3d::ssao::blur_system)
 }
 
 // pbr meshes
 fn build_node_forward_pbr(
     render_pbr_group_builder: Arc<Mutex<UniformGroupBuilder<RenderPBRForwardUniformGroup>>>,
     camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
-    //lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+    lighting_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Lighting3DUniformGroup>>>,
+    environment_group_builder: Arc<Mutex<UniformGroupBuilder<EnvironmentUniformGroup>>>,
 ) -> NodeBuilder {
     NodeBuilder::new(
         "render_pbr_node".to_owned(),
@@ -1224,10 +3282,13 @@ fn build_node_forward_pbr(
     )
     .with_id(ID(FORWARD_PBR_NODE_ID))
     .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
-    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    // Each RenderPBR's material; see sources::materials
+    .with_system_texture(TextureType::Material)
     .with_shared_uniform_group(Arc::clone(&render_pbr_group_builder))
     .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
     .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::CubemapN { n: 2 })
+    .with_shared_uniform_group(Arc::clone(&lighting_3d_group_builder))
+    .with_shared_uniform_group(Arc::clone(&environment_group_builder))
     // .with_depth_buffer()
     .with_system(render_3d::forward_pbr::render_system)
 }
@@ -1236,6 +3297,7 @@ fn build_node_forward_pbr(
 fn build_node_sky(
     render_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Render3DForwardUniformGroup>>>,
     camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    environment_group_builder: Arc<Mutex<UniformGroupBuilder<EnvironmentUniformGroup>>>,
 ) -> NodeBuilder {
     //
     // The sky node requires a Sky in the legion resources (singleton).
@@ -1251,28 +3313,127 @@ fn build_node_sky(
     .with_shared_uniform_group(Arc::clone(&render_3d_group_builder))
     .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
     .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Cubemap)
+    .with_shared_uniform_group(Arc::clone(&environment_group_builder))
     .with_reverse_culling()
     // .with_depth_buffer()
     .with_system(sky::render_system)
 }
 
+// Text2D, drawn on top of the master target
+// ParticleSystemGPU quads, drawn over the instanced 2d scene
+fn build_node_2d_particles_gpu(
+    camera_2d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera2DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "render_particles_gpu_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/particle_2d_gpu.wgsl").to_owned()),
+    )
+    .with_id(ID(PARTICLES_GPU_2D_NODE_ID))
+    .with_vertex_layout(render_2d::particles_gpu::GPUPARTICLE_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&camera_2d_group_builder))
+    .with_system(render_2d::particles_gpu::render_system)
+}
+
+fn build_node_2d_text(
+    camera_2d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera2DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "render_text_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/text.wgsl").to_owned()),
+    )
+    .with_id(ID(TEXT_2D_NODE_ID))
+    .with_vertex_layout(render_2d::text::TEXTVERTEX_BUFFER_LAYOUT)
+    .with_system_texture(TextureType::Image)
+    .with_shared_uniform_group(Arc::clone(&camera_2d_group_builder))
+    .with_frame_uniforms()
+    .with_system(render_2d::text::render_system)
+}
+
+// world-space debug lines, drawn on top of the master target
+fn build_node_debug_overlay(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+    on_top: bool,
+) -> NodeBuilder {
+    let node = NodeBuilder::new(
+        "debug_overlay_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/debug_lines.wgsl").to_owned()),
+    )
+    .with_vertex_layout(debug::DEBUGVERTEX_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_topology(wgpu::PrimitiveTopology::LineList)
+    .with_depth_buffer();
+
+    match on_top {
+        true => node
+            .with_id(ID(DEBUG_OVERLAY_ON_TOP_NODE_ID))
+            .with_depth_test(wgpu::CompareFunction::Always, false)
+            .with_system(debug::render_on_top_system),
+        false => node
+            .with_id(ID(DEBUG_OVERLAY_NODE_ID))
+            .with_depth_test(wgpu::CompareFunction::LessEqual, false)
+            .with_system(debug::render_occluded_system),
+    }
+}
+
+// lines from the DebugDraw resource, depth tested against the scene
+fn build_node_debug_draw(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "debug_draw_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/debug_lines.wgsl").to_owned()),
+    )
+    .with_id(ID(DEBUG_DRAW_NODE_ID))
+    .with_vertex_layout(debug::DEBUGVERTEX_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_topology(wgpu::PrimitiveTopology::LineList)
+    .with_depth_buffer()
+    .with_depth_test(wgpu::CompareFunction::LessEqual, false)
+    .with_system(debug::render_draw_system)
+}
+
+// transform handles for the selected entity, on top of everything
+fn build_node_gizmo(
+    camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
+) -> NodeBuilder {
+    NodeBuilder::new(
+        "gizmo_node".to_owned(),
+        0,
+        1,
+        ShaderSource::WGSL(include_str!("renderer/shaders/debug_lines.wgsl").to_owned()),
+    )
+    .with_id(ID(GIZMO_NODE_ID))
+    .with_vertex_layout(debug::DEBUGVERTEX_BUFFER_LAYOUT)
+    .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
+    .with_topology(wgpu::PrimitiveTopology::LineList)
+    .with_depth_buffer()
+    .with_depth_test(wgpu::CompareFunction::Always, false)
+    .with_system(gizmo::render_system)
+}
+
 // shader renders onto a flat fullscreen quad, intended for ray-tracing
 fn build_node_quad(
-    quad_group_builder: Arc<Mutex<UniformGroupBuilder<QuadUniformGroup>>>,
     camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
     shader_source: ShaderSource,
 ) -> NodeBuilder {
     NodeBuilder::new("render_quad_node".to_owned(), 0, 1, shader_source)
         .with_id(ID(QUAD_NODE_ID))
         .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
-        .with_shared_uniform_group(Arc::clone(&quad_group_builder))
+        .with_frame_uniforms()
         .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
         .with_system(quad::render_system)
 }
 
 // shader renders onto a flat fullscreen quad, intended for post-processing
 fn build_node_channel(
-    quad_group_builder: Arc<Mutex<UniformGroupBuilder<QuadUniformGroup>>>,
     camera_3d_group_builder: Arc<Mutex<UniformGroupBuilder<Camera3DUniformGroup>>>,
 ) -> NodeBuilder {
     NodeBuilder::new(
@@ -1284,17 +3445,13 @@ fn build_node_channel(
     .with_id(ID(CHANNEL_NODE_ID))
     .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
     .with_node_input()
-    .with_shared_uniform_group(Arc::clone(&quad_group_builder))
+    .with_frame_uniforms()
     .with_shared_uniform_group(Arc::clone(&camera_3d_group_builder))
     .with_system(channel::render_system)
 }
 
 // node swaps the inputs and render targets to the node each time (ping-pong)
-fn build_node_chain(
-    shader_source: ShaderSource,
-    chain_size: u32,
-    quad_group_builder: Arc<Mutex<UniformGroupBuilder<QuadUniformGroup>>>,
-) -> NodeBuilder {
+fn build_node_chain(shader_source: ShaderSource, chain_size: u32) -> NodeBuilder {
     //
     // Notes for Nodes and NodeBuilders
     //
@@ -1331,7 +3488,7 @@ fn build_node_chain(
         //
         // Regular setup, vertex layout + uniform groups + system
         .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
-        .with_shared_uniform_group(Arc::clone(&quad_group_builder))
+        .with_frame_uniforms()
         .with_system(chain::render_system)
     //
     // Eventually:
@@ -1357,13 +3514,26 @@ fn build_node_chain(
 // --------------------------------------------------
 
 pub struct LegionState {
-    pub schedule: Schedule,
+    pub schedule: StagedSchedule,
     pub world: World,
     pub resources: Resources,
+    pub fixed: Option<FixedStage>,
 }
 
 impl LegionState {
     pub fn execute(&mut self) {
+        let frame_metrics =
+            Arc::clone(&*self.resources.get::<Arc<RwLock<FrameMetrics>>>().unwrap());
+        self.resources
+            .get_mut::<Time>()
+            .unwrap()
+            .begin_frame(frame_metrics.read().unwrap().frame_delta());
+
+        // Fixed steps are simulation too; they pick up where they were once unpaused
+        let paused = StagedSchedule::paused(&self.resources);
+        if let Some(fixed) = self.fixed.as_mut().filter(|_| !paused) {
+            fixed.execute(&mut self.world, &mut self.resources, &frame_metrics);
+        }
         self.schedule.execute(&mut self.world, &mut self.resources);
     }
 }
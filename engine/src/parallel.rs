@@ -0,0 +1,91 @@
+// rayon on native. wasm32 has no threads, so there the same calls run in order on the
+// calling thread: `into_par_iter`, `par_iter` and `par_iter_mut` hand back plain iterators,
+// which already have everything the engine uses apart from `for_each_with`
+#[cfg(not(target_arch = "wasm32"))]
+pub use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+    IntoParallelRefMutIterator, ParallelIterator,
+};
+
+#[cfg(target_arch = "wasm32")]
+pub use sequential::*;
+
+#[cfg(target_arch = "wasm32")]
+mod sequential {
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefIterator<'a> for I
+    where
+        &'a I: IntoIterator,
+    {
+        type Iter = <&'a I as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait IntoParallelRefMutIterator<'a> {
+        type Iter: Iterator;
+        fn par_iter_mut(&'a mut self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefMutIterator<'a> for I
+    where
+        &'a mut I: IntoIterator,
+    {
+        type Iter = <&'a mut I as IntoIterator>::IntoIter;
+
+        fn par_iter_mut(&'a mut self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait ParallelIterator: Iterator + Sized {
+        fn for_each_with<T, F>(self, mut init: T, mut op: F)
+        where
+            F: FnMut(&mut T, Self::Item),
+        {
+            self.for_each(|item| op(&mut init, item))
+        }
+    }
+
+    impl<I: Iterator> ParallelIterator for I {}
+
+    // zip and enumerate are already on Iterator
+    pub trait IndexedParallelIterator: Iterator {}
+
+    impl<I: Iterator> IndexedParallelIterator for I {}
+}
+
+// Legion's parallel queries need its `parallel` feature, which is off on wasm32. These
+// take the query, world and body of a `par_for_each(_mut)` call, and fall back to
+// `for_each(_mut)` there
+macro_rules! par_for_each {
+    ($query:expr, $world:expr, $body:expr) => {{
+        #[cfg(not(target_arch = "wasm32"))]
+        $query.par_for_each($world, $body);
+        #[cfg(target_arch = "wasm32")]
+        $query.for_each($world, $body);
+    }};
+}
+
+macro_rules! par_for_each_mut {
+    ($query:expr, $world:expr, $body:expr) => {{
+        #[cfg(not(target_arch = "wasm32"))]
+        $query.par_for_each_mut($world, $body);
+        #[cfg(target_arch = "wasm32")]
+        $query.for_each_mut($world, $body);
+    }};
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use std::convert::TryInto;
+
+use super::bptc;
+
+// Block compressed formats (BC1-7, a.k.a. DXTn), as stored in .ktx2 and .dds files: 4x4
+// texel blocks of 8 or 16 bytes. Adapters without TEXTURE_COMPRESSION_BC (most mobile GPUs)
+// get them decoded to RGBA8 at load time, with BC6H's HDR colors clamped to [0, 1]; see
+// Ktx2Image::decompress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockCompression {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6h,
+    Bc6hSigned,
+    Bc7,
+}
+
+impl BlockCompression {
+    pub fn block_bytes(self) -> usize {
+        match self {
+            BlockCompression::Bc1 | BlockCompression::Bc4 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn format(self, srgb: bool) -> wgpu::TextureFormat {
+        match (self, srgb) {
+            (BlockCompression::Bc1, false) => wgpu::TextureFormat::Bc1RgbaUnorm,
+            (BlockCompression::Bc1, true) => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            (BlockCompression::Bc2, false) => wgpu::TextureFormat::Bc2RgbaUnorm,
+            (BlockCompression::Bc2, true) => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            (BlockCompression::Bc3, false) => wgpu::TextureFormat::Bc3RgbaUnorm,
+            (BlockCompression::Bc3, true) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            (BlockCompression::Bc4, _) => wgpu::TextureFormat::Bc4RUnorm,
+            (BlockCompression::Bc5, _) => wgpu::TextureFormat::Bc5RgUnorm,
+            (BlockCompression::Bc6h, _) => wgpu::TextureFormat::Bc6hRgbUfloat,
+            (BlockCompression::Bc6hSigned, _) => wgpu::TextureFormat::Bc6hRgbSfloat,
+            (BlockCompression::Bc7, false) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            (BlockCompression::Bc7, true) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    // One face of one level, as whole blocks, into width * height RGBA8 texels. BC4 and BC5
+    // come out as (r, 0, 0, 1) and (r, g, 0, 1), the way they're sampled.
+    pub fn decode(self, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+        let (width, height) = (width as usize, height as usize);
+        let blocks_x = (width + 3) / 4;
+        let blocks_y = (height + 3) / 4;
+        if data.len() < blocks_x * blocks_y * self.block_bytes() {
+            return Err(anyhow!("truncated {:?} data", self));
+        }
+
+        let mut rgba = vec![0u8; width * height * 4];
+        let mut texels = [[0u8; 4]; 16];
+        for (index, block) in data
+            .chunks_exact(self.block_bytes())
+            .take(blocks_x * blocks_y)
+            .enumerate()
+        {
+            match self {
+                BlockCompression::Bc1 => decode_color(block, true, &mut texels),
+                BlockCompression::Bc2 => {
+                    decode_color(&block[8..], false, &mut texels);
+                    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+                    for (i, texel) in texels.iter_mut().enumerate() {
+                        texel[3] = ((alpha >> (4 * i)) & 0xF) as u8 * 17;
+                    }
+                }
+                BlockCompression::Bc3 => {
+                    decode_color(&block[8..], false, &mut texels);
+                    decode_channel(&block[..8], 3, &mut texels);
+                }
+                BlockCompression::Bc4 => {
+                    texels = [[0, 0, 0, 255]; 16];
+                    decode_channel(block, 0, &mut texels);
+                }
+                BlockCompression::Bc5 => {
+                    texels = [[0, 0, 0, 255]; 16];
+                    decode_channel(&block[..8], 0, &mut texels);
+                    decode_channel(&block[8..], 1, &mut texels);
+                }
+                BlockCompression::Bc6h => bptc::decode_bc6h(block, false, &mut texels),
+                BlockCompression::Bc6hSigned => bptc::decode_bc6h(block, true, &mut texels),
+                BlockCompression::Bc7 => bptc::decode_bc7(block, &mut texels),
+            }
+
+            // Blocks hang over the edges of textures that aren't a multiple of 4
+            let (block_x, block_y) = (index % blocks_x * 4, index / blocks_x * 4);
+            for (i, texel) in texels.iter().enumerate() {
+                let (x, y) = (block_x + i % 4, block_y + i / 4);
+                if x < width && y < height {
+                    let offset = (y * width + x) * 4;
+                    rgba[offset..offset + 4].copy_from_slice(texel);
+                }
+            }
+        }
+        Ok(rgba)
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let (r, g, b) = ((color >> 11) & 31, (color >> 5) & 63, color & 31);
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+        255,
+    ]
+}
+
+// Two 565 endpoints and 2 bits per texel. Only BC1 has the 3 color + transparent mode; BC2
+// and BC3 always interpolate 4 colors.
+fn decode_color(block: &[u8], bc1: bool, texels: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |w0: u16, w1: u16| {
+        let mut color = [0, 0, 0, 255];
+        for ((c, a), b) in color.iter_mut().zip(e0.iter()).zip(e1.iter()).take(3) {
+            *c = ((*a as u16 * w0 + *b as u16 * w1) / (w0 + w1)) as u8;
+        }
+        color
+    };
+    let palette = match c0 > c1 || !bc1 {
+        true => [e0, e1, mix(2, 1), mix(1, 2)],
+        false => [e0, e1, mix(1, 1), [0; 4]],
+    };
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+// BC4's block (also BC3's alpha, BC5's red and green): two 8-bit endpoints and 3 bits per
+// texel, written into one channel
+fn decode_channel(block: &[u8], channel: usize, texels: &mut [[u8; 4]; 16]) {
+    let (e0, e1) = (block[0] as u16, block[1] as u16);
+    let mut palette = [e0 as u8, e1 as u8, 0, 0, 0, 0, 0, 255];
+    // 6 interpolated values, or 4 and then 0 and 255
+    let steps: u16 = match e0 > e1 {
+        true => 7,
+        false => 5,
+    };
+    for (i, value) in palette[2..steps as usize + 1].iter_mut().enumerate() {
+        let i = i as u16 + 1;
+        *value = ((e0 * (steps - i) + e1 * i) / steps) as u8;
+    }
+
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[channel] = palette[((indices >> (3 * i)) & 7) as usize];
+    }
+}
@@ -0,0 +1,505 @@
+use std::convert::TryInto;
+
+// BC6H and BC7 (a.k.a. BPTC) blocks, decoded to RGBA8 for adapters without
+// TEXTURE_COMPRESSION_BC; see BlockCompression::decode. Both formats have several modes, told
+// apart by the first bits of the block, each with its own layout: up to 3 subsets of texels
+// (picked from the partition tables below), two endpoints per subset, and an index per texel
+// that blends between them.
+//
+// Reference: the Khronos Data Format Specification, "BC6H" and "BC7".
+
+// Bits are read from the least significant end of the block
+struct Bits {
+    bits: u128,
+    offset: u32,
+}
+
+impl Bits {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            offset: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.offset) & ((1 << count) - 1);
+        self.offset += count;
+        value as u32
+    }
+}
+
+// Blend weights (out of 64) for 2, 3 and 4 bit indices
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weight(index: u32, bits: u32) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+// Subset of each texel for the 64 two subset partitions, one bit per texel
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// Subset of each texel for the 64 three subset partitions
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// Every subset has an anchor texel, texel 0 for the first, whose index is stored with one bit
+// less: its top bit is always 0
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => PARTITIONS_3[partition][texel] as usize,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            2 => texel == ANCHORS_2[partition] as usize,
+            3 => {
+                texel == ANCHORS_3_SECOND[partition] as usize
+                    || texel == ANCHORS_3_THIRD[partition] as usize
+            }
+            _ => false,
+        }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    // One p-bit (an extra low bit for every channel) per endpoint, or one per subset
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    // Modes 4 and 5 have separate indices for alpha
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+// To 8 bits, repeating the top bits in the bottom ones
+fn expand(value: u32, bits: u32) -> u8 {
+    let value = value << (8 - bits);
+    (value | value >> bits) as u8
+}
+
+pub fn decode_bc7(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    // The mode is the number of zero bits before the first one; a block without one is
+    // reserved, and decodes to transparent black
+    let mode = block[0].trailing_zeros() as usize;
+    if mode >= BC7_MODES.len() {
+        *texels = [[0; 4]; 16];
+        return;
+    }
+    let m = &BC7_MODES[mode];
+    let mut bits = Bits::new(block);
+    bits.read(mode as u32 + 1);
+    let partition = bits.read(m.partition_bits) as usize;
+    let rotation = bits.read(m.rotation_bits);
+    let index_selection = bits.read(m.index_selection_bits);
+
+    // Channel by channel, then endpoint by endpoint
+    let endpoints = m.subsets * 2;
+    let mut raw = [[0u32; 4]; 6];
+    for channel in 0..4 {
+        let channel_bits = match channel {
+            3 => m.alpha_bits,
+            _ => m.color_bits,
+        };
+        for endpoint in raw.iter_mut().take(endpoints) {
+            endpoint[channel] = bits.read(channel_bits);
+        }
+    }
+    let mut p_bits = [0u32; 6];
+    for i in 0..endpoints {
+        p_bits[i] = match (m.endpoint_p_bits, m.shared_p_bits) {
+            (true, _) => bits.read(1),
+            (_, true) if i % 2 == 0 => bits.read(1),
+            (_, true) => p_bits[i - 1],
+            _ => 0,
+        };
+    }
+
+    let mut colors = [[0u8; 4]; 6];
+    for (i, color) in colors.iter_mut().enumerate().take(endpoints) {
+        for (channel, value) in color.iter_mut().enumerate() {
+            let channel_bits = match channel {
+                3 => m.alpha_bits,
+                _ => m.color_bits,
+            };
+            *value = match (channel_bits, m.endpoint_p_bits || m.shared_p_bits) {
+                (0, _) => 255,
+                (_, true) => expand(raw[i][channel] << 1 | p_bits[i], channel_bits + 1),
+                (_, false) => expand(raw[i][channel], channel_bits),
+            };
+        }
+    }
+
+    let mut indices = [0u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(m.subsets, partition, texel) as u32;
+        *index = bits.read(m.index_bits - anchor);
+    }
+    let mut secondary = [0u32; 16];
+    if m.secondary_index_bits > 0 {
+        for (texel, index) in secondary.iter_mut().enumerate() {
+            *index = bits.read(m.secondary_index_bits - (texel == 0) as u32);
+        }
+    }
+    // Mode 4 can swap which indices are used for color and which for alpha
+    let ((color_indices, color_bits), (alpha_indices, alpha_bits)) =
+        match (m.secondary_index_bits, index_selection) {
+            (0, _) => ((&indices, m.index_bits), (&indices, m.index_bits)),
+            (_, 0) => (
+                (&indices, m.index_bits),
+                (&secondary, m.secondary_index_bits),
+            ),
+            _ => (
+                (&secondary, m.secondary_index_bits),
+                (&indices, m.index_bits),
+            ),
+        };
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let subset = subset(m.subsets, partition, i);
+        let (e0, e1) = (colors[subset * 2], colors[subset * 2 + 1]);
+        for (channel, value) in texel.iter_mut().enumerate() {
+            let w = match channel {
+                3 => weight(alpha_indices[i], alpha_bits),
+                _ => weight(color_indices[i], color_bits),
+            };
+            let (a, b) = (e0[channel] as u32, e1[channel] as u32);
+            *value = (((64 - w) * a + w * b + 32) >> 6) as u8;
+        }
+        // Modes 4 and 5 can store a color channel in the alpha's place
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+// Endpoints are named as in the specification: w and x are the first subset's, y and z the
+// second's
+const W: usize = 0;
+const X: usize = 1;
+const Y: usize = 2;
+const Z: usize = 3;
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+struct Bc6hMode {
+    // 1 or 2; two subset modes end with 5 partition bits, from the first 32 two subset
+    // partitions
+    subsets: usize,
+    // w's precision, and x, y and z's. In transformed modes these are signed offsets from w.
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    transformed: bool,
+    // (channel, endpoint, first bit, bit count), in the order they're stored after the mode
+    fields: &'static [(usize, usize, u32, u32)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { subsets: 2, endpoint_bits: 10, delta_bits: [5, 5, 5], transformed: true, fields: &[
+        (G, Y, 4, 1), (B, Y, 4, 1), (B, Z, 4, 1), (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10),
+        (R, X, 0, 5), (G, Z, 4, 1), (G, Y, 0, 4), (G, X, 0, 5), (B, Z, 0, 1), (G, Z, 0, 4),
+        (B, X, 0, 5), (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 5), (B, Z, 2, 1), (R, Z, 0, 5),
+        (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 7, delta_bits: [6, 6, 6], transformed: true, fields: &[
+        (G, Y, 5, 1), (G, Z, 4, 1), (G, Z, 5, 1), (R, W, 0, 7), (B, Z, 0, 1), (B, Z, 1, 1),
+        (B, Y, 4, 1), (G, W, 0, 7), (B, Y, 5, 1), (B, Z, 2, 1), (G, Y, 4, 1), (B, W, 0, 7),
+        (B, Z, 3, 1), (B, Z, 5, 1), (B, Z, 4, 1), (R, X, 0, 6), (G, Y, 0, 4), (G, X, 0, 6),
+        (G, Z, 0, 4), (B, X, 0, 6), (B, Y, 0, 4), (R, Y, 0, 6), (R, Z, 0, 6),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 11, delta_bits: [5, 4, 4], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 5), (R, W, 10, 1), (G, Y, 0, 4),
+        (G, X, 0, 4), (G, W, 10, 1), (B, Z, 0, 1), (G, Z, 0, 4), (B, X, 0, 4), (B, W, 10, 1),
+        (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 5), (B, Z, 2, 1), (R, Z, 0, 5), (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 11, delta_bits: [4, 5, 4], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 4), (R, W, 10, 1), (G, Z, 4, 1),
+        (G, Y, 0, 4), (G, X, 0, 5), (G, W, 10, 1), (G, Z, 0, 4), (B, X, 0, 4), (B, W, 10, 1),
+        (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 4), (B, Z, 0, 1), (B, Z, 2, 1), (R, Z, 0, 4),
+        (G, Y, 4, 1), (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 11, delta_bits: [4, 4, 5], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 4), (R, W, 10, 1), (B, Y, 4, 1),
+        (G, Y, 0, 4), (G, X, 0, 4), (G, W, 10, 1), (B, Z, 0, 1), (G, Z, 0, 4), (B, X, 0, 5),
+        (B, W, 10, 1), (B, Y, 0, 4), (R, Y, 0, 4), (B, Z, 1, 1), (B, Z, 2, 1), (R, Z, 0, 4),
+        (B, Z, 4, 1), (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 9, delta_bits: [5, 5, 5], transformed: true, fields: &[
+        (R, W, 0, 9), (B, Y, 4, 1), (G, W, 0, 9), (G, Y, 4, 1), (B, W, 0, 9), (B, Z, 4, 1),
+        (R, X, 0, 5), (G, Z, 4, 1), (G, Y, 0, 4), (G, X, 0, 5), (B, Z, 0, 1), (G, Z, 0, 4),
+        (B, X, 0, 5), (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 5), (B, Z, 2, 1), (R, Z, 0, 5),
+        (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 8, delta_bits: [6, 5, 5], transformed: true, fields: &[
+        (R, W, 0, 8), (G, Z, 4, 1), (B, Y, 4, 1), (G, W, 0, 8), (B, Z, 2, 1), (G, Y, 4, 1),
+        (B, W, 0, 8), (B, Z, 3, 1), (B, Z, 4, 1), (R, X, 0, 6), (G, Y, 0, 4), (G, X, 0, 5),
+        (B, Z, 0, 1), (G, Z, 0, 4), (B, X, 0, 5), (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 6),
+        (R, Z, 0, 6),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 8, delta_bits: [5, 6, 5], transformed: true, fields: &[
+        (R, W, 0, 8), (B, Z, 0, 1), (B, Y, 4, 1), (G, W, 0, 8), (G, Y, 5, 1), (G, Y, 4, 1),
+        (B, W, 0, 8), (G, Z, 5, 1), (B, Z, 4, 1), (R, X, 0, 5), (G, Z, 4, 1), (G, Y, 0, 4),
+        (G, X, 0, 6), (G, Z, 0, 4), (B, X, 0, 5), (B, Z, 1, 1), (B, Y, 0, 4), (R, Y, 0, 5),
+        (B, Z, 2, 1), (R, Z, 0, 5), (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 8, delta_bits: [5, 5, 6], transformed: true, fields: &[
+        (R, W, 0, 8), (B, Z, 1, 1), (B, Y, 4, 1), (G, W, 0, 8), (B, Y, 5, 1), (G, Y, 4, 1),
+        (B, W, 0, 8), (B, Z, 5, 1), (B, Z, 4, 1), (R, X, 0, 5), (G, Z, 4, 1), (G, Y, 0, 4),
+        (G, X, 0, 5), (B, Z, 0, 1), (G, Z, 0, 4), (B, X, 0, 6), (B, Y, 0, 4), (R, Y, 0, 5),
+        (B, Z, 2, 1), (R, Z, 0, 5), (B, Z, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, endpoint_bits: 6, delta_bits: [6, 6, 6], transformed: false, fields: &[
+        (R, W, 0, 6), (G, Z, 4, 1), (B, Z, 0, 1), (B, Z, 1, 1), (B, Y, 4, 1), (G, W, 0, 6),
+        (G, Y, 5, 1), (B, Y, 5, 1), (B, Z, 2, 1), (G, Y, 4, 1), (B, W, 0, 6), (G, Z, 5, 1),
+        (B, Z, 3, 1), (B, Z, 5, 1), (B, Z, 4, 1), (R, X, 0, 6), (G, Y, 0, 4), (G, X, 0, 6),
+        (G, Z, 0, 4), (B, X, 0, 6), (B, Y, 0, 4), (R, Y, 0, 6), (R, Z, 0, 6),
+    ] },
+    Bc6hMode { subsets: 1, endpoint_bits: 10, delta_bits: [10, 10, 10], transformed: false, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 10), (G, X, 0, 10), (B, X, 0, 10),
+    ] },
+    Bc6hMode { subsets: 1, endpoint_bits: 11, delta_bits: [9, 9, 9], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 9), (R, W, 10, 1), (G, X, 0, 9),
+        (G, W, 10, 1), (B, X, 0, 9), (B, W, 10, 1),
+    ] },
+    // The top bits of w are stored in reverse in the last two modes
+    Bc6hMode { subsets: 1, endpoint_bits: 12, delta_bits: [8, 8, 8], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 8), (R, W, 11, 1), (R, W, 10, 1),
+        (G, X, 0, 8), (G, W, 11, 1), (G, W, 10, 1), (B, X, 0, 8), (B, W, 11, 1), (B, W, 10, 1),
+    ] },
+    Bc6hMode { subsets: 1, endpoint_bits: 16, delta_bits: [4, 4, 4], transformed: true, fields: &[
+        (R, W, 0, 10), (G, W, 0, 10), (B, W, 0, 10), (R, X, 0, 4), (R, W, 15, 1), (R, W, 14, 1),
+        (R, W, 13, 1), (R, W, 12, 1), (R, W, 11, 1), (R, W, 10, 1), (G, X, 0, 4), (G, W, 15, 1),
+        (G, W, 14, 1), (G, W, 13, 1), (G, W, 12, 1), (G, W, 11, 1), (G, W, 10, 1), (B, X, 0, 4),
+        (B, W, 15, 1), (B, W, 14, 1), (B, W, 13, 1), (B, W, 12, 1), (B, W, 11, 1), (B, W, 10, 1),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    value << shift >> shift
+}
+
+// To the 16 bit range interpolation happens in
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        return match value {
+            _ if bits >= 15 => value,
+            0 => 0,
+            _ if value == (1 << bits) - 1 => 0xffff,
+            _ => ((value << 16) + 0x8000) >> bits,
+        };
+    }
+    if bits >= 16 {
+        return value;
+    }
+    let magnitude = match value.abs() {
+        0 => 0,
+        abs if abs >= (1 << (bits - 1)) - 1 => 0x7fff,
+        abs => ((abs << 15) + 0x4000) >> (bits - 1),
+    };
+    match value < 0 {
+        true => -magnitude,
+        false => magnitude,
+    }
+}
+
+// From the interpolated value to a half float
+fn finish_unquantize(value: i32, signed: bool) -> half::f16 {
+    if !signed {
+        return half::f16::from_bits(((value * 31) >> 6) as u16);
+    }
+    let bits = match value < 0 {
+        true => 0x8000 | ((-value * 31) >> 5) as u16,
+        false => ((value * 31) >> 5) as u16,
+    };
+    half::f16::from_bits(bits)
+}
+
+// HDR colors are clamped to [0, 1], with alpha 1
+pub fn decode_bc6h(block: &[u8], signed: bool, texels: &mut [[u8; 4]; 16]) {
+    // 2 mode bits, or 5 when the first two are 10 or 11; 10011, 10111, 11011 and 11111 are
+    // reserved, and decode to black
+    let (mode, mode_bits) = match (block[0] & 3, block[0] >> 2 & 7) {
+        (low, _) if low < 2 => (low as usize, 2),
+        (2, high) => (2 + high as usize, 5),
+        (_, high) if high < 4 => (10 + high as usize, 5),
+        _ => {
+            *texels = [[0, 0, 0, 255]; 16];
+            return;
+        }
+    };
+    let m = &BC6H_MODES[mode];
+    let mut bits = Bits::new(block);
+    bits.read(mode_bits);
+
+    let mut endpoints = [[0i32; 3]; 4];
+    for &(channel, endpoint, first, count) in m.fields {
+        endpoints[endpoint][channel] |= (bits.read(count) << first) as i32;
+    }
+    let partition = match m.subsets {
+        2 => bits.read(5) as usize,
+        _ => 0,
+    };
+
+    let count = m.subsets * 2;
+    for channel in 0..3 {
+        let delta_bits = m.delta_bits[channel];
+        let base = endpoints[W][channel];
+        if signed {
+            endpoints[W][channel] = sign_extend(base, m.endpoint_bits);
+        }
+        for endpoint in endpoints.iter_mut().take(count).skip(1) {
+            if m.transformed {
+                let delta = sign_extend(endpoint[channel], delta_bits);
+                let value = (base + delta) & ((1 << m.endpoint_bits) - 1);
+                endpoint[channel] = match signed {
+                    true => sign_extend(value, m.endpoint_bits),
+                    false => value,
+                };
+            } else if signed {
+                endpoint[channel] = sign_extend(endpoint[channel], delta_bits);
+            }
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize(*value, m.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = match m.subsets {
+        2 => 3,
+        _ => 4,
+    };
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let anchor = is_anchor(m.subsets, partition, i) as u32;
+        let w = weight(bits.read(index_bits - anchor), index_bits) as i32;
+        let subset = subset(m.subsets, partition, i);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        for channel in 0..3 {
+            let value = ((64 - w) * e0[channel] + w * e1[channel] + 32) >> 6;
+            let value = finish_unquantize(value, signed).to_f32();
+            texel[channel] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        texel[3] = 255;
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use std::convert::TryInto;
+
+use super::{bcn::BlockCompression, ktx2::Ktx2Image};
+
+// DirectDraw Surface files, as exported by most texture tools: BC1-7 (legacy DXTn or DX10
+// headers) or 32-bit RGBA/BGRA, as images or complete cubemaps. Read into a Ktx2Image, which
+// is what the texture registry uploads.
+//
+// https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dds-header
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+pub fn read(bytes: &[u8]) -> Result<Ktx2Image> {
+    if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+        return Err(anyhow!("not a dds file"));
+    }
+    let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+    let height = word(12);
+    let width = word(16);
+    let level_count = word(28).max(1) as usize;
+    let pixel_flags = word(80);
+    let four_cc = &bytes[84..88];
+    let caps2 = word(112);
+
+    let mut data_offset = HEADER_SIZE;
+    let mut faces = match caps2 & DDSCAPS2_CUBEMAP != 0 {
+        true if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES => {
+            return Err(anyhow!("dds cubemaps must have all 6 faces"))
+        }
+        true => 6,
+        false => 1,
+    };
+    // Red and blue swapped, to be swapped back
+    let mut bgra = false;
+
+    let (compression, srgb) = if pixel_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => (Some(BlockCompression::Bc1), false),
+            b"DXT2" | b"DXT3" => (Some(BlockCompression::Bc2), false),
+            b"DXT4" | b"DXT5" => (Some(BlockCompression::Bc3), false),
+            b"ATI1" | b"BC4U" => (Some(BlockCompression::Bc4), false),
+            b"ATI2" | b"BC5U" => (Some(BlockCompression::Bc5), false),
+            b"DX10" => {
+                if bytes.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+                    return Err(anyhow!("truncated dds dx10 header"));
+                }
+                data_offset += DX10_HEADER_SIZE;
+                if word(HEADER_SIZE + 8) & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+                    faces = 6;
+                }
+                if word(HEADER_SIZE + 12) > 1 {
+                    return Err(anyhow!("dds texture arrays are not supported"));
+                }
+                // DXGI_FORMAT
+                match word(HEADER_SIZE) {
+                    28 => (None, false),
+                    29 => (None, true),
+                    87 => {
+                        bgra = true;
+                        (None, false)
+                    }
+                    91 => {
+                        bgra = true;
+                        (None, true)
+                    }
+                    71 => (Some(BlockCompression::Bc1), false),
+                    72 => (Some(BlockCompression::Bc1), true),
+                    74 => (Some(BlockCompression::Bc2), false),
+                    75 => (Some(BlockCompression::Bc2), true),
+                    77 => (Some(BlockCompression::Bc3), false),
+                    78 => (Some(BlockCompression::Bc3), true),
+                    80 => (Some(BlockCompression::Bc4), false),
+                    83 => (Some(BlockCompression::Bc5), false),
+                    95 => (Some(BlockCompression::Bc6h), false),
+                    96 => (Some(BlockCompression::Bc6hSigned), false),
+                    98 => (Some(BlockCompression::Bc7), false),
+                    99 => (Some(BlockCompression::Bc7), true),
+                    other => return Err(anyhow!("unsupported dds dxgi format: {}", other)),
+                }
+            }
+            other => {
+                return Err(anyhow!(
+                    "unsupported dds fourcc: {}",
+                    String::from_utf8_lossy(other)
+                ))
+            }
+        }
+    } else if pixel_flags & DDPF_RGB != 0 && word(88) == 32 {
+        match (word(92), word(100)) {
+            (0xFF, 0xFF_0000) => (None, false),
+            (0xFF_0000, 0xFF) => {
+                bgra = true;
+                (None, false)
+            }
+            _ => return Err(anyhow!("unsupported dds rgb channel masks")),
+        }
+    } else {
+        return Err(anyhow!("unsupported dds pixel format"));
+    };
+
+    let mut image = Ktx2Image {
+        width,
+        height,
+        srgb,
+        compression,
+        faces,
+        levels: vec![vec![]; level_count],
+    };
+
+    // Stored face by face, each with its whole mip chain; ktx2 levels hold every face
+    let mut offset = data_offset;
+    for _ in 0..faces {
+        for level in 0..level_count {
+            let (bytes_per_row, rows, _) = image.level_layout(level);
+            let len = (bytes_per_row * rows) as usize;
+            let data = bytes
+                .get(offset..offset + len)
+                .ok_or_else(|| anyhow!("truncated dds level {}", level))?;
+            image.levels[level].extend_from_slice(data);
+            offset += len;
+        }
+    }
+
+    if bgra {
+        for level in &mut image.levels {
+            for texel in level.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+    }
+    Ok(image)
+}
@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+use std::num::NonZeroU32;
+
+use super::texture::{Texture, HDR_FORMAT};
+
+// Equirectangular (latitude/longitude) environments, the way most HDRIs are published,
+// rendered into the six faces of a cubemap at load time, so skyboxes and environments don't
+// have to be split into face images first. Faces are a quarter of the image's width.
+
+const MAX_FACE_SIZE: u32 = 2048;
+
+pub fn face_size(width: u32) -> u32 {
+    (width / 4).max(1).min(MAX_FACE_SIZE)
+}
+
+// The equirect has to have been loaded with equirect_layout (an Image layout), and the
+// cubemap gets a bind group from cube_layout
+pub fn to_cubemap(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    equirect: &Texture,
+    width: u32,
+    equirect_layout: &wgpu::BindGroupLayout,
+    cube_layout: &wgpu::BindGroupLayout,
+    label: Option<&str>,
+) -> Result<Texture> {
+    let equirect_bind_group = equirect
+        .bind_group
+        .as_ref()
+        .ok_or_else(|| anyhow!("equirect texture has no bind group"))?;
+    let size = face_size(width);
+    let cubemap =
+        Texture::blank_cubemap((size, size), device, HDR_FORMAT, cube_layout, label, true)?;
+
+    let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("equirect_to_cube"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/equirect_to_cube.wgsl").into()),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("equirect_to_cube_pipeline_layout"),
+        bind_group_layouts: &[equirect_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("equirect_to_cube_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &module,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("equirect_to_cube_encoder"),
+    });
+    for face in 0..6 {
+        let view = cubemap.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: face,
+            array_layer_count: NonZeroU32::new(1),
+            ..Default::default()
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("equirect_to_cube_pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, equirect_bind_group, &[]);
+        // The shader picks the face from the instance index
+        pass.draw(0..3, face..face + 1);
+    }
+    queue.submit(Some(encoder.finish()));
+
+    Ok(cubemap)
+}
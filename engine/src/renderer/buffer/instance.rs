@@ -1,12 +1,23 @@
 use std::{
     any::type_name,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
     marker::PhantomData,
+    mem::size_of,
+    ops::Range,
     sync::{Arc, Mutex, RwLock},
 };
 use uuid::Uuid;
 use wgpu::util::DeviceExt;
 
-use crate::renderer::uniform::{generic::BufferState, group::BufferMode};
+use crate::{
+    constants::DEFAULT_MAX_INDIRECT_DRAWS_PER_BUFFER,
+    renderer::{
+        buffer::upload::Uploader,
+        mesh::Mesh,
+        uniform::{generic::BufferState, group::BufferMode},
+    },
+};
 
 pub trait Instance: bytemuck::Pod + bytemuck::Zeroable + Clone + Default {
     fn id(&self) -> (u32, u32);
@@ -21,15 +32,55 @@ pub trait InstanceMutator<I: Instance>: Send + Sync {
     fn mutate(&mut self, instance: &mut I, delta: f32);
 }
 
+// One draw_indexed_indirect's arguments, laid out as wgpu reads them
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirect {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+// Recorded before the draws of each instanced pass, once its commands are loaded into draws.
+// Command i draws the instances in its batch's range of the instance buffer, so a culling
+// compute pass can compact the visible ones to the front of that range and lower i's
+// instance_count to match. Set with InstanceBuffer::set_culling.
+pub trait IndirectCulling: Send + Sync {
+    fn cull(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &wgpu::Buffer,
+        draws: &wgpu::Buffer,
+        draw_count: u32,
+    );
+}
+
+// One instanced draw in a pass: a mesh and a range (in bytes) of the instance buffer
+pub struct InstanceDraw<'a> {
+    pub texture: &'a wgpu::BindGroup,
+    pub mesh: &'a Mesh,
+    pub range: Range<u64>,
+    pub instances: u32,
+}
+
 // InstanceBuffer is allocated with enough space
 // for DEFAULT_MAX_INSTANCES_PER_BUFFER instances of type <I>.
 //
 // All InstanceGroups of type <I> share one instance buffer, because
 // all of their render passes will all be recorded on the same
 // thread via the same system.
+//
+// Batches are drawn with draw_indexed_indirect, from commands in draws, so that culling
+// (see IndirectCulling) can change how many instances are drawn without a CPU round trip.
 pub struct InstanceBuffer<I: Instance> {
     pub state: BufferState,
-    pub queue: Arc<wgpu::Queue>,
+    pub uploader: Arc<Uploader>,
+
+    pub draws: wgpu::Buffer,
+    pub max_draws: u32,
+    pub culling: Option<Arc<dyn IndirectCulling>>,
     marker: PhantomData<I>,
 }
 
@@ -37,38 +88,125 @@ impl<I> InstanceBuffer<I>
 where
     I: Instance,
 {
-    pub fn new(device: &wgpu::Device, queue: Arc<wgpu::Queue>, max_elements: u32) -> Self {
+    pub fn new(device: &wgpu::Device, uploader: Arc<Uploader>, max_elements: u32) -> Self {
         let source = &[I::default()];
         let source_bytes = bytemuck::cast_slice(source);
         let source_size = source_bytes.len();
         let source_bytes = source_bytes.repeat(max_elements as usize);
+        let max_draws = DEFAULT_MAX_INDIRECT_DRAWS_PER_BUFFER;
         Self {
             state: BufferState {
                 buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some(&format!("Instance Buffer: {}", type_name::<I>())),
                     contents: &source_bytes,
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    // Storage, for culling passes
+                    usage: wgpu::BufferUsages::VERTEX
+                        | wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_DST,
                 }),
                 element_size: source_size as u64,
                 mode: BufferMode::Dynamic(max_elements),
             },
-            queue,
+            draws: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("Indirect Draw Buffer: {}", type_name::<I>())),
+                size: max_draws as u64 * size_of::<DrawIndexedIndirect>() as u64,
+                usage: wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            max_draws,
+            culling: None,
+            uploader,
             marker: PhantomData,
         }
     }
 
-    pub fn load_group(&self, bytes: &[u8]) {
-        self.queue.write_buffer(&self.state.buffer, 0, bytes);
+    pub fn set_culling(&mut self, culling: Arc<dyn IndirectCulling>) {
+        self.culling = Some(culling);
+    }
+
+    // Each group in a pass must be loaded at its own offset (in bytes); writes are only
+    // copied in on submit, so groups loaded at the same offset would all draw the last one
+    pub fn load_group(&self, offset: u64, bytes: &[u8]) {
+        self.uploader.write(&self.state.buffer, offset, bytes);
+    }
+
+    // Loads a command per draw and records the culling pass, if any, so this has to be
+    // called on the encoder before the render pass is begun
+    pub fn load_draws(&self, encoder: &mut wgpu::CommandEncoder, draws: &[InstanceDraw]) {
+        let commands: Vec<DrawIndexedIndirect> = draws
+            .iter()
+            .take(self.max_draws as usize)
+            .map(|draw| DrawIndexedIndirect {
+                index_count: draw.mesh.index_buffer.buffer.1,
+                instance_count: draw.instances,
+                ..Default::default()
+            })
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+        if draws.len() > commands.len() {
+            warn!(
+                "{}: {} instanced draws but only {} indirect commands, drawing the rest directly",
+                type_name::<I>(),
+                draws.len(),
+                commands.len()
+            );
+        }
+        self.uploader
+            .write(&self.draws, 0, bytemuck::cast_slice(&commands));
+
+        if let Some(culling) = &self.culling {
+            culling.cull(
+                encoder,
+                &self.state.buffer,
+                &self.draws,
+                commands.len() as u32,
+            );
+        }
+    }
+
+    // Draws loaded with load_draws, binding each one's texture at group 0
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, draws: &[InstanceDraw<'a>]) {
+        for (i, draw) in draws.iter().enumerate() {
+            pass.set_bind_group(0, draw.texture, &[]);
+            pass.set_vertex_buffer(0, draw.mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                draw.mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            pass.set_vertex_buffer(1, self.state.buffer.slice(draw.range.clone()));
+
+            match i < self.max_draws as usize {
+                true => pass.draw_indexed_indirect(
+                    &self.draws,
+                    (i * size_of::<DrawIndexedIndirect>()) as u64,
+                ),
+                false => {
+                    pass.draw_indexed(0..draw.mesh.index_buffer.buffer.1, 0, 0..draw.instances)
+                }
+            }
+        }
     }
 }
 
-// A group of components which can be rendered with one instanced draw call.
-// Each group shares one texture and mesh.
+// A group of components which can be rendered with one instanced draw call per mesh.
+// Each group shares one texture, or (2D instances) picks from the ones added with add_texture.
+// Instances use the entity's Mesh, unless pushed with push_with_mesh, in which case the render
+// system batches them per mesh.
 pub struct InstanceGroup<I: Instance> {
     pub id: u32,
     pub instances: Vec<I>,
     pub components: Arc<RwLock<Vec<Vec<Arc<Mutex<dyn InstanceMutator<I>>>>>>>,
     pub texture: Uuid,
+    // After texture; see add_texture
+    pub textures: Vec<Uuid>,
+
+    // Per instance (None => the entity's mesh), and the extra meshes they reference
+    pub mesh_ids: Vec<Option<Uuid>>,
+    pub meshes: HashMap<Uuid, Mesh>,
     next_id: InstanceId,
 }
 
@@ -82,17 +220,73 @@ where
             instances: vec![],
             components: Arc::new(RwLock::new(vec![])),
             texture,
+            textures: vec![],
+            mesh_ids: vec![],
+            meshes: HashMap::new(),
             id,
         }
     }
 
+    // Eg. engine.clone_mesh(engine.mesh("unit_cube").unwrap())
+    pub fn add_mesh(&mut self, mesh_id: Uuid, mesh: Mesh) {
+        self.meshes.insert(mesh_id, mesh);
+    }
+
+    // Another texture from the group's texture group, for instances to pick by the returned
+    // index (0 being texture). Only 2D instances can; see Render2DInstance::with_texture.
+    pub fn add_texture(&mut self, texture: Uuid) -> u32 {
+        if texture == self.texture {
+            return 0;
+        }
+        match self.textures.iter().position(|added| *added == texture) {
+            Some(index) => index as u32 + 1,
+            None => {
+                self.textures.push(texture);
+                self.textures.len() as u32
+            }
+        }
+    }
+
+    // texture, then the added ones, by index
+    pub fn texture_ids(&self) -> Vec<Uuid> {
+        let mut ids = vec![self.texture];
+        ids.extend(&self.textures);
+        ids
+    }
+
     pub fn push(
+        &mut self,
+        instance: I,
+        instance_components: Vec<Arc<Mutex<dyn InstanceMutator<I>>>>,
+    ) -> InstanceId {
+        self.push_inner(instance, None, instance_components)
+    }
+
+    // The mesh must have been added with add_mesh first
+    pub fn push_with_mesh(
+        &mut self,
+        instance: I,
+        mesh_id: Uuid,
+        instance_components: Vec<Arc<Mutex<dyn InstanceMutator<I>>>>,
+    ) -> InstanceId {
+        if !self.meshes.contains_key(&mesh_id) {
+            warn!(
+                "instance group {}: mesh {} was never added, instance won't be drawn",
+                self.id, mesh_id
+            );
+        }
+        self.push_inner(instance, Some(mesh_id), instance_components)
+    }
+
+    fn push_inner(
         &mut self,
         mut instance: I,
+        mesh_id: Option<Uuid>,
         instance_components: Vec<Arc<Mutex<dyn InstanceMutator<I>>>>,
     ) -> InstanceId {
         instance.set_id(self.id, self.next_id.1);
         self.instances.push(instance);
+        self.mesh_ids.push(mesh_id);
         self.components.write().unwrap().push(instance_components);
 
         let old_id = self.next_id;
@@ -103,7 +297,49 @@ where
     pub fn delete(&mut self, id: u32) {
         if let Some(index) = self.instances.iter().position(|inst| inst.id().1 == id) {
             self.instances.swap_remove(index);
+            self.mesh_ids.swap_remove(index);
+        }
+    }
+
+    // Stable, so equal instances keep their order from frame to frame. Does nothing if the
+    // instances are already in order.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&I, &I) -> Ordering,
+    {
+        if self
+            .instances
+            .windows(2)
+            .all(|pair| compare(&pair[0], &pair[1]) != Ordering::Greater)
+        {
+            return;
+        }
+        let mut sorted: Vec<(I, Option<Uuid>)> = self
+            .instances
+            .drain(..)
+            .zip(self.mesh_ids.drain(..))
+            .collect();
+        sorted.sort_by(|a, b| compare(&a.0, &b.0));
+        let (instances, mesh_ids) = sorted.into_iter().unzip();
+        self.instances = instances;
+        self.mesh_ids = mesh_ids;
+    }
+
+    // Whether any instance uses a mesh other than the entity's
+    pub fn is_partitioned(&self) -> bool {
+        self.mesh_ids.iter().any(Option::is_some)
+    }
+
+    // Instances batched by mesh, in a stable order from frame to frame
+    pub fn partitions(&self) -> BTreeMap<Option<Uuid>, Vec<I>> {
+        let mut partitions: BTreeMap<Option<Uuid>, Vec<I>> = BTreeMap::new();
+        for (instance, mesh_id) in self.instances.iter().zip(&self.mesh_ids) {
+            partitions
+                .entry(*mesh_id)
+                .or_insert_with(Vec::new)
+                .push(*instance);
         }
+        partitions
     }
 }
 
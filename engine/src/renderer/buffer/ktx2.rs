@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Result};
+use std::convert::TryInto;
+
+use super::bcn::BlockCompression;
+
+// Minimal KTX2 support: RGBA8 or BC1-7, no supercompression or key/value data. Written
+// (RGBA8 only) by ember-assetc, read by the texture registry, which also reads .dds files into
+// the same Ktx2Image (see buffer::dds).
+//
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGB_UNORM: u32 = 131;
+const VK_FORMAT_BC7_SRGB: u32 = 146;
+
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+const DFD_SIZE: usize = 92;
+
+pub struct Ktx2Image {
+    pub width: u32,
+    pub height: u32,
+    pub srgb: bool,
+    // None for RGBA8
+    pub compression: Option<BlockCompression>,
+    // 1 for images, 6 for cubemaps
+    pub faces: u32,
+    // Largest first; each level holds all faces back to back
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Image {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match (self.compression, self.srgb) {
+            (Some(compression), srgb) => compression.format(srgb),
+            (None, true) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            (None, false) => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    // Bytes per row and rows of one face of a level, and the size to copy it as: compressed
+    // levels are rows of whole 4x4 blocks, even where the level is smaller than that
+    pub fn level_layout(&self, level: usize) -> (u32, u32, (u32, u32)) {
+        let (width, height) = self.level_size(level);
+        match self.compression {
+            Some(compression) => {
+                let (blocks_x, blocks_y) = ((width + 3) / 4, (height + 3) / 4);
+                (
+                    blocks_x * compression.block_bytes() as u32,
+                    blocks_y,
+                    (blocks_x * 4, blocks_y * 4),
+                )
+            }
+            None => (4 * width, height, (width, height)),
+        }
+    }
+
+    // Whether a device can sample it as is; compressed textures need the feature, and a size
+    // in whole blocks
+    pub fn is_supported(&self, features: wgpu::Features) -> bool {
+        match self.compression {
+            Some(_) => {
+                features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC)
+                    && self.width % 4 == 0
+                    && self.height % 4 == 0
+            }
+            None => true,
+        }
+    }
+
+    // The same texture as RGBA8, for devices it isn't supported on; BC6H is clamped to [0, 1]
+    pub fn decompress(&self) -> Result<Self> {
+        let compression = match self.compression {
+            Some(compression) => compression,
+            None => return Err(anyhow!("ktx2 image is not compressed")),
+        };
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let (width, height) = self.level_size(level);
+                let (bytes_per_row, rows, _) = self.level_layout(level);
+                let face_bytes = (bytes_per_row * rows) as usize;
+                let mut rgba = vec![];
+                for face in 0..self.faces as usize {
+                    let face_data = data
+                        .get(face * face_bytes..(face + 1) * face_bytes)
+                        .ok_or_else(|| anyhow!("truncated ktx2 level {}", level))?;
+                    rgba.extend(compression.decode(face_data, width, height)?);
+                }
+                Ok(rgba)
+            })
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+
+        Ok(Self {
+            width: self.width,
+            height: self.height,
+            srgb: self.srgb,
+            compression: None,
+            faces: self.faces,
+            levels,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        assert!(
+            self.compression.is_none(),
+            "only RGBA8 ktx2 images can be written"
+        );
+        let level_count = self.levels.len();
+        let dfd_offset = HEADER_SIZE + LEVEL_INDEX_SIZE * level_count;
+        let data_offset = dfd_offset + DFD_SIZE;
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&IDENTIFIER);
+        for word in &[
+            match self.srgb {
+                true => VK_FORMAT_R8G8B8A8_SRGB,
+                false => VK_FORMAT_R8G8B8A8_UNORM,
+            },
+            1, // typeSize
+            self.width,
+            self.height,
+            0, // pixelDepth
+            0, // layerCount
+            self.faces,
+            level_count as u32,
+            0, // supercompressionScheme
+            dfd_offset as u32,
+            DFD_SIZE as u32,
+            0, // kvdByteOffset
+            0, // kvdByteLength
+        ] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&0u64.to_le_bytes()); // sgdByteOffset
+        bytes.extend_from_slice(&0u64.to_le_bytes()); // sgdByteLength
+
+        // Level data is stored smallest first, but indexed largest first
+        let mut offsets = vec![0usize; level_count];
+        let mut offset = data_offset;
+        for level in (0..level_count).rev() {
+            offsets[level] = offset;
+            offset += self.levels[level].len();
+        }
+        for level in 0..level_count {
+            let len = self.levels[level].len() as u64;
+            bytes.extend_from_slice(&(offsets[level] as u64).to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&self.data_format_descriptor());
+        for level in (0..level_count).rev() {
+            bytes.extend_from_slice(&self.levels[level]);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[0..12] != IDENTIFIER {
+            return Err(anyhow!("not a ktx2 file"));
+        }
+        let word = |i: usize| u32::from_le_bytes(bytes[12 + i * 4..16 + i * 4].try_into().unwrap());
+
+        let (compression, srgb) = match word(0) {
+            VK_FORMAT_R8G8B8A8_SRGB => (None, true),
+            VK_FORMAT_R8G8B8A8_UNORM => (None, false),
+            format @ VK_FORMAT_BC1_RGB_UNORM..=VK_FORMAT_BC7_SRGB => {
+                // BC1 RGB and RGBA, then the rest, each as unorm/ufloat then srgb/snorm/sfloat
+                let compression = match format {
+                    131..=134 => BlockCompression::Bc1,
+                    135 | 136 => BlockCompression::Bc2,
+                    137 | 138 => BlockCompression::Bc3,
+                    139 => BlockCompression::Bc4,
+                    141 => BlockCompression::Bc5,
+                    143 => BlockCompression::Bc6h,
+                    144 => BlockCompression::Bc6hSigned,
+                    145 | 146 => BlockCompression::Bc7,
+                    other => return Err(anyhow!("unsupported ktx2 vkFormat: {}", other)),
+                };
+                let srgb = format % 2 == 0
+                    && !matches!(
+                        compression,
+                        BlockCompression::Bc6h | BlockCompression::Bc6hSigned
+                    );
+                (Some(compression), srgb)
+            }
+            other => return Err(anyhow!("unsupported ktx2 vkFormat: {}", other)),
+        };
+        if word(8) != 0 {
+            return Err(anyhow!("supercompressed ktx2 files are not supported"));
+        }
+
+        let faces = word(6);
+        let level_count = word(7).max(1) as usize;
+        if bytes.len() < HEADER_SIZE + LEVEL_INDEX_SIZE * level_count {
+            return Err(anyhow!("truncated ktx2 level index"));
+        }
+
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = HEADER_SIZE + LEVEL_INDEX_SIZE * level;
+                let offset = u64::from_le_bytes(bytes[entry..entry + 8].try_into().unwrap());
+                let len = u64::from_le_bytes(bytes[entry + 8..entry + 16].try_into().unwrap());
+                bytes
+                    .get(offset as usize..(offset + len) as usize)
+                    .map(|data| data.to_vec())
+                    .ok_or_else(|| anyhow!("truncated ktx2 level {}", level))
+            })
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+
+        Ok(Self {
+            width: word(2),
+            height: word(3),
+            srgb,
+            compression,
+            faces,
+            levels,
+        })
+    }
+
+    // Basic data format descriptor for 8-bit RGBA
+    fn data_format_descriptor(&self) -> Vec<u8> {
+        let transfer = match self.srgb {
+            true => 2,
+            false => 1,
+        };
+
+        let mut words: Vec<u32> = vec![
+            DFD_SIZE as u32,
+            0,                                 // vendorId, descriptorType
+            2 | ((DFD_SIZE as u32 - 4) << 16), // versionNumber, descriptorBlockSize
+            1 | (1 << 8) | (transfer << 16),   // RGBSDA, BT709, transfer, straight alpha
+            0,                                 // texelBlockDimension
+            4,                                 // bytesPlane0
+            0,
+        ];
+        for (channel, id) in [0u32, 1, 2, 15].iter().enumerate() {
+            // Alpha is always linear
+            let qualifiers = if *id == 15 { 0x10 } else { 0 };
+            words.push((channel as u32 * 8) | (7 << 16) | ((id | qualifiers) << 24));
+            words.push(0);
+            words.push(0);
+            words.push(255);
+        }
+
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
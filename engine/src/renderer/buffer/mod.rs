@@ -1,9 +1,15 @@
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
+pub mod bcn;
+pub mod bptc;
+pub mod dds;
+pub mod equirect;
 pub mod instance;
+pub mod ktx2;
 pub mod target;
 pub mod texture;
+pub mod upload;
 
 // Vertex Layout Builder
 // - Automatically generate vertex buffer layouts
@@ -26,6 +32,21 @@ pub struct Vertex3D {
 unsafe impl bytemuck::Pod for Vertex3D {}
 unsafe impl bytemuck::Zeroable for Vertex3D {}
 
+// Vertex3D plus the (up to) 4 joints which move it, for skinned meshes
+#[vertex((0, 64usize))]
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex3DSkinned {
+    pub position: [f32; 3],
+    pub uvs: [f32; 2],
+    pub normal: [f32; 3],
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for Vertex3DSkinned {}
+unsafe impl bytemuck::Zeroable for Vertex3DSkinned {}
+
 #[vertex((0, 16usize))]
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -71,6 +92,20 @@ impl VertexBuffer {
         }
     }
 
+    pub fn new_3d_skinned(name: &str, vertices: &[Vertex3DSkinned], device: &wgpu::Device) -> Self {
+        VertexBuffer {
+            buffer: Arc::new((
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("3D Skinned Vertex Buffer: {}", name)),
+                    contents: bytemuck::cast_slice(vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+                vertices.len() as u32,
+            )),
+            size: vertices.len() as u32,
+        }
+    }
+
     pub fn raw(name: &str, data: &[f32], num_vertices: u32, device: &wgpu::Device) -> Self {
         VertexBuffer {
             buffer: Arc::new((
@@ -85,45 +120,48 @@ impl VertexBuffer {
         }
     }
 
-    pub fn from_flat_slices(
+    // Room for capacity vertices, of which the first vertices.len() are drawn; the rest
+    // can be written later (see systems::dynamic_mesh)
+    pub fn dynamic_3d(
         name: &str,
-        vertices_flat: &[f32],
-        uvs_flat: &[f32],
-        normals_flat: &[f32],
+        vertices: &[Vertex3D],
+        capacity: usize,
         device: &wgpu::Device,
-    ) -> (Self, Vec<f32>) {
-        let num_vertices = vertices_flat.len() / 3;
-        assert_eq!(num_vertices, uvs_flat.len() / 2);
-        assert_eq!(num_vertices, normals_flat.len() / 3);
-
-        let mut buf: Vec<f32> = vec![];
-        for i in 0..num_vertices {
-            buf.push(vertices_flat[i * 3]);
-            buf.push(vertices_flat[i * 3 + 1]);
-            buf.push(vertices_flat[i * 3 + 2]);
-
-            buf.push(uvs_flat[i * 2]);
-            buf.push(uvs_flat[i * 2 + 1]);
-
-            buf.push(normals_flat[i * 3]);
-            buf.push(normals_flat[i * 3 + 1]);
-            buf.push(normals_flat[i * 3 + 2]);
+    ) -> Self {
+        VertexBuffer {
+            buffer: Arc::new((
+                create_dynamic_buffer(
+                    &format!("3D Dynamic Vertex Buffer: {}", name),
+                    bytemuck::cast_slice(vertices),
+                    (capacity.max(1) * std::mem::size_of::<Vertex3D>()) as u64,
+                    wgpu::BufferUsages::VERTEX,
+                    device,
+                ),
+                vertices.len() as u32,
+            )),
+            size: vertices.len() as u32,
         }
+    }
+}
 
-        (
-            VertexBuffer {
-                buffer: Arc::new((
-                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some(&format!("3D Vertex Buffer: {}", name)),
-                        contents: bytemuck::cast_slice(buf.as_slice()),
-                        usage: wgpu::BufferUsages::VERTEX,
-                    }),
-                    buf.len() as u32,
-                )),
-                size: buf.len() as u32,
-            },
-            buf,
-        )
+// Vertex3D layout: [position, uvs, normal] per vertex
+pub const VERTEX3D_FLOATS: usize = 8;
+
+pub fn interleave_into(
+    out: &mut [f32],
+    vertices_flat: &[f32],
+    uvs_flat: &[f32],
+    normals_flat: &[f32],
+) {
+    let num_vertices = vertices_flat.len() / 3;
+    assert_eq!(num_vertices, uvs_flat.len() / 2);
+    assert_eq!(num_vertices, normals_flat.len() / 3);
+    assert_eq!(num_vertices * VERTEX3D_FLOATS, out.len());
+
+    for (i, vertex) in out.chunks_exact_mut(VERTEX3D_FLOATS).enumerate() {
+        vertex[0..3].copy_from_slice(&vertices_flat[i * 3..i * 3 + 3]);
+        vertex[3..5].copy_from_slice(&uvs_flat[i * 2..i * 2 + 2]);
+        vertex[5..8].copy_from_slice(&normals_flat[i * 3..i * 3 + 3]);
     }
 }
 
@@ -146,4 +184,40 @@ impl IndexBuffer {
             size: indices.len() as u32,
         }
     }
+
+    // See VertexBuffer::dynamic_3d
+    pub fn dynamic(indices: &[u32], capacity: usize, device: &wgpu::Device) -> Self {
+        IndexBuffer {
+            buffer: Arc::new((
+                create_dynamic_buffer(
+                    "Dynamic Index Buffer",
+                    bytemuck::cast_slice(indices),
+                    (capacity.max(1) * std::mem::size_of::<u32>()) as u64,
+                    wgpu::BufferUsages::INDEX,
+                    device,
+                ),
+                indices.len() as u32,
+            )),
+            size: indices.len() as u32,
+        }
+    }
+}
+
+// Writable after creation, with contents at the start and the rest zeroed
+fn create_dynamic_buffer(
+    label: &str,
+    contents: &[u8],
+    size: u64,
+    usage: wgpu::BufferUsages,
+    device: &wgpu::Device,
+) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: true,
+    });
+    buffer.slice(..).get_mapped_range_mut()[..contents.len()].copy_from_slice(contents);
+    buffer.unmap();
+    buffer
 }
@@ -4,6 +4,12 @@ use wgpu::TextureViewDimension;
 
 use crate::sources::registry::TextureType;
 
+use super::ktx2::Ktx2Image;
+
+// Linear float images (.hdr, .exr) and the cubemaps made from them; 32 bit floats wouldn't
+// be filterable
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -74,6 +80,151 @@ impl Texture {
         Ok(texture)
     }
 
+    pub fn load_hdr(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::Rgba32FImage,
+        group_layout: &wgpu::BindGroupLayout,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let dimensions = image.dimensions();
+        let texture = Self::blank(dimensions, device, HDR_FORMAT, group_layout, label, false)?;
+
+        let texels: Vec<u8> = image
+            .as_raw()
+            .iter()
+            .flat_map(|value| half::f16::from_f32(*value).to_bits().to_le_bytes())
+            .collect();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(8 * dimensions.0),
+                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+            },
+            wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(texture)
+    }
+
+    // Images and cubemaps preprocessed by ember-assetc, or read from a .dds, including their
+    // mip chain. Compressed ones the device can't sample are decoded to RGBA8 first.
+    pub fn load_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &Ktx2Image,
+        group_layout: &wgpu::BindGroupLayout,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        if !image.is_supported(device.features()) {
+            debug!(
+                "decoding {:?} texture {} to rgba8",
+                image.compression,
+                label.unwrap_or("")
+            );
+            return Self::load_ktx2(device, queue, &image.decompress()?, group_layout, label);
+        }
+
+        let is_cubemap = match image.faces {
+            1 => false,
+            6 => true,
+            n => return Err(anyhow!("ktx2 textures must have 1 or 6 faces, found {}", n)),
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label,
+            size: wgpu::Extent3d {
+                width: image.width,
+                height: image.height,
+                depth_or_array_layers: image.faces,
+            },
+            format: image.format(),
+        });
+
+        for (level, data) in image.levels.iter().enumerate() {
+            let (bytes_per_row, rows, (width, height)) = image.level_layout(level);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(rows),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: image.faces,
+                },
+            );
+        }
+
+        let view = texture.create_view(&match is_cubemap {
+            true => wgpu::TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::Cube),
+                base_array_layer: 0,
+                array_layer_count: Some(NonZeroU32::new(6).unwrap()),
+                ..Default::default()
+            },
+            false => wgpu::TextureViewDescriptor::default(),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("ktx2_texture_bind_group"),
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+            texture_type: match is_cubemap {
+                true => TextureType::Cubemap,
+                false => TextureType::Image,
+            },
+            bind_group: Some(Arc::new(bind_group)),
+        })
+    }
+
     pub fn blank(
         dimensions: (u32, u32),
         device: &wgpu::Device,
@@ -94,9 +245,11 @@ impl Texture {
             dimension: wgpu::TextureDimension::D2,
             usage: match is_render_target {
                 false => wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                // Copied from for readbacks, eg. the material editor's preview
                 true => {
                     wgpu::TextureUsages::TEXTURE_BINDING
                         | wgpu::TextureUsages::COPY_DST
+                        | wgpu::TextureUsages::COPY_SRC
                         | wgpu::TextureUsages::RENDER_ATTACHMENT
                 }
             },
@@ -106,11 +259,16 @@ impl Texture {
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let filterable = is_filterable(format);
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
+            // Non-filtering samplers must be nearest all the way down
+            mag_filter: match filterable {
+                true => wgpu::FilterMode::Linear,
+                false => wgpu::FilterMode::Nearest,
+            },
             min_filter: wgpu::FilterMode::Nearest,
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
@@ -135,7 +293,10 @@ impl Texture {
             texture,
             view,
             sampler,
-            texture_type: TextureType::Image,
+            texture_type: match filterable {
+                true => TextureType::Image,
+                false => TextureType::UnfilterableImage,
+            },
             bind_group: Some(Arc::new(bind_group)),
         })
     }
@@ -186,12 +347,57 @@ impl Texture {
         }
     }
 
-    fn blank_cubemap(
+    // Six layers, viewed as a cube, with the same comparison sampler; for point light shadow
+    // maps (see graph::target::CubeTarget)
+    pub fn depth_cube(name: &str, device: &wgpu::Device, size: u32) -> Self {
+        debug!("building depth cube: {}", name);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            format: wgpu::TextureFormat::Depth32Float,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            base_array_layer: 0,
+            array_layer_count: Some(NonZeroU32::new(6).unwrap()),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            texture_type: TextureType::DepthCube,
+            bind_group: None,
+        }
+    }
+
+    pub(crate) fn blank_cubemap(
         dimensions: (u32, u32),
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
         group_layout: &wgpu::BindGroupLayout,
         label: Option<&str>,
+        is_render_target: bool,
     ) -> Result<Texture> {
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -203,7 +409,12 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage: match is_render_target {
+                false => wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                true => {
+                    wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT
+                }
+            },
             label,
             size,
             format,
@@ -265,8 +476,14 @@ impl Texture {
             depth_or_array_layers: 6,
         };
 
-        let texture =
-            Self::blank_cubemap(dimensions, device, preferred_format, group_layout, label)?;
+        let texture = Self::blank_cubemap(
+            dimensions,
+            device,
+            preferred_format,
+            group_layout,
+            label,
+            false,
+        )?;
 
         let slice0: &[u8] = &faces[0];
         let slice1: &[u8] = &faces[1];
@@ -314,3 +531,12 @@ impl Texture {
         Ok(texture)
     }
 }
+
+// Whether textures of this format can be sampled with a filtering sampler (without
+// extra device features); 32 bit float formats can't
+pub fn is_filterable(format: wgpu::TextureFormat) -> bool {
+    match format.describe().sample_type {
+        wgpu::TextureSampleType::Float { filterable } => filterable,
+        _ => false,
+    }
+}
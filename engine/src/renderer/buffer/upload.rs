@@ -0,0 +1,147 @@
+use futures::{future::BoxFuture, task::noop_waker_ref, FutureExt};
+use std::{
+    num::NonZeroU64,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use wgpu::util::StagingBelt;
+
+use crate::constants::DEFAULT_UPLOAD_CHUNK_SIZE;
+
+// Buffer writes for the whole frame, copied out of one staging belt by one encoder instead
+// of a queue.write_buffer (and its own staging allocation) each. GroupStates, UniformGroups
+// and InstanceBuffers all write through it.
+//
+// Unlike queue.write_buffer, a write isn't seen by anything submitted before the uploads
+// are: begin_render_graph flushes whatever the update systems wrote, and render systems
+// which write during the graph submit with Uploader::submit. Node systems record instead
+// (Uploader::record), so the graph's commands go in dependency order however legion's threads
+// happened to finish; submit_recorded submits them before the UI. end_render_graph recalls the
+// belt's chunks for the next frame.
+pub struct Uploader {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    state: Mutex<UploaderState>,
+}
+
+// Where a node's commands go in the frame's submission: by layer (its group between flushes
+// in the graph's schedule), then by order within that. Handed out by GraphBuilder::build.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Submission {
+    pub layer: u32,
+    pub order: u32,
+}
+
+struct UploaderState {
+    belt: StagingBelt,
+    encoder: Option<wgpu::CommandEncoder>,
+    // (layer, uploads before commands, order) -> commands; a node's own stay in record order
+    recorded: Vec<((u32, u8, u32), wgpu::CommandBuffer)>,
+    // Chunks still being mapped for reuse
+    recalls: Vec<BoxFuture<'static, ()>>,
+}
+
+impl Uploader {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        Self {
+            device,
+            queue,
+            state: Mutex::new(UploaderState {
+                belt: StagingBelt::new(DEFAULT_UPLOAD_CHUNK_SIZE),
+                encoder: None,
+                recorded: vec![],
+                recalls: vec![],
+            }),
+        }
+    }
+
+    // Same rules as queue.write_buffer: offset and length must be multiples of 4
+    pub fn write(&self, buffer: &wgpu::Buffer, offset: u64, bytes: &[u8]) {
+        let size = match NonZeroU64::new(bytes.len() as u64) {
+            Some(size) => size,
+            None => return,
+        };
+        let mut state = self.state.lock().unwrap();
+        let UploaderState { belt, encoder, .. } = &mut *state;
+        let encoder = encoder.get_or_insert_with(|| {
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("upload_encoder"),
+                })
+        });
+        belt.write_buffer(encoder, buffer, offset, size, &self.device)
+            .copy_from_slice(bytes);
+    }
+
+    // Submits the writes so far
+    pub fn flush(&self) {
+        self.submit_after(None);
+    }
+
+    // Submits the writes so far, then commands, in one queue.submit
+    pub fn submit(&self, commands: wgpu::CommandBuffer) {
+        self.submit_after(Some(commands));
+    }
+
+    // For node systems: keeps commands for submit_recorded instead of submitting them. The
+    // writes so far go ahead of every node's commands in the layer, which is all that can have
+    // written them.
+    pub fn record(&self, submission: Submission, commands: wgpu::CommandBuffer) {
+        let mut state = self.state.lock().unwrap();
+        let Submission { layer, order } = submission;
+        if let Some(uploads) = Self::take_uploads(&mut state) {
+            state.recorded.push(((layer, 0, order), uploads));
+        }
+        state.recorded.push(((layer, 1, order), commands));
+    }
+
+    // Submits everything recorded this frame, in submission order, in one queue.submit
+    pub fn submit_recorded(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut recorded = std::mem::take(&mut state.recorded);
+        // Stable, so a node's own commands keep their order
+        recorded.sort_by_key(|(key, _)| *key);
+        let uploads = Self::take_uploads(&mut state);
+        if uploads.is_some() || !recorded.is_empty() {
+            self.queue.submit(
+                uploads
+                    .into_iter()
+                    .chain(recorded.into_iter().map(|(_, commands)| commands)),
+            );
+        }
+    }
+
+    fn submit_after(&self, commands: Option<wgpu::CommandBuffer>) {
+        // Held until submitted, so writes from other threads can't land out of order
+        let mut state = self.state.lock().unwrap();
+        let uploads = Self::take_uploads(&mut state);
+        if uploads.is_some() || commands.is_some() {
+            self.queue.submit(uploads.into_iter().chain(commands));
+        }
+    }
+
+    fn take_uploads(state: &mut UploaderState) -> Option<wgpu::CommandBuffer> {
+        let encoder = state.encoder.take()?;
+        state.belt.finish();
+        Some(encoder.finish())
+    }
+
+    // Once a frame, after everything which wrote this frame has been submitted
+    pub fn recall(&self) {
+        self.submit_recorded();
+        let mut state = self.state.lock().unwrap();
+        let recall = state.belt.recall().boxed();
+        state.recalls.push(recall);
+
+        // The mappings finish as the device is maintained (on submit), so just check on
+        // them here rather than waiting
+        let mut context = Context::from_waker(noop_waker_ref());
+        state.recalls = std::mem::take(&mut state.recalls)
+            .into_iter()
+            .filter_map(|mut recall| match recall.poll_unpin(&mut context) {
+                Poll::Ready(()) => None,
+                Poll::Pending => Some(recall),
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{anyhow, Result};
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use std::{path::Path, sync::Arc};
+use uuid::Uuid;
+
+use crate::sources::{assets, registry::MeshBuilder};
+
+use super::{
+    buffer::{interleave_into, VERTEX3D_FLOATS},
+    mesh::{BinaryMesh, Mesh, Submesh},
+};
+
+// glTF 2.0 (.gltf and .glb) models. Every triangle primitive of every mesh in the default
+// scene is baked into one vertex buffer with its node transform applied, one submesh per
+// primitive, so a model renders as a single Mesh. The registry also registers each
+// primitive as a mesh of its own, with its material (see MeshRegistry::model).
+
+pub struct GltfLoader {
+    pub id: Uuid,
+    pub path: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    // Resolved relative to the model; embedded images (.glb, data uris) aren't supported yet
+    pub base_color_texture: Option<String>,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+pub struct GltfModel {
+    pub mesh: BinaryMesh,
+    // One per submesh
+    pub materials: Vec<GltfMaterial>,
+}
+
+impl GltfModel {
+    // A standalone mesh for one primitive, with only the vertices it uses
+    pub fn primitive_mesh(&self, index: usize) -> BinaryMesh {
+        let submesh = self.mesh.submeshes[index];
+        let indices = &self.mesh.indices
+            [submesh.first_index as usize..(submesh.first_index + submesh.index_count) as usize];
+        let first_vertex = indices.iter().min().copied().unwrap_or(0);
+        let last_vertex = indices.iter().max().copied().unwrap_or(0);
+
+        let vertices = match indices.is_empty() {
+            true => vec![],
+            false => self.mesh.vertices[first_vertex as usize * VERTEX3D_FLOATS
+                ..(last_vertex as usize + 1) * VERTEX3D_FLOATS]
+                .to_vec(),
+        };
+        let indices: Vec<u32> = indices.iter().map(|i| i - first_vertex).collect();
+        let submeshes = vec![Submesh {
+            first_index: 0,
+            index_count: indices.len() as u32,
+        }];
+        BinaryMesh::new(vertices, indices, submeshes)
+    }
+}
+
+impl GltfLoader {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    pub fn arc_dyn(self) -> Arc<dyn MeshBuilder> {
+        Arc::new(self)
+    }
+
+    pub fn is_gltf(path: &str) -> bool {
+        path.ends_with(".gltf") || path.ends_with(".glb")
+    }
+
+    pub fn parse(&self) -> Result<GltfModel> {
+        debug!("building gltf meshes from file: {}", &self.path);
+
+        let ::gltf::Gltf { document, blob } = ::gltf::Gltf::from_slice(&assets::read(&self.path)?)?;
+        let buffers = self.import_buffers(&document, blob)?;
+
+        let mut materials: Vec<GltfMaterial> = vec![];
+        let mut vertices: Vec<f32> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        let mut submeshes: Vec<Submesh> = vec![];
+
+        for (primitive, transform) in self.primitives(&document)? {
+            let normal_transform = normal_matrix(transform);
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = reader
+                .read_positions()
+                .ok_or_else(|| anyhow!("primitive without positions"))?
+                .collect();
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => vec![[0.0, 1.0, 0.0]; positions.len()],
+            };
+            let uvs: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                Some(uvs) => uvs.into_f32().collect(),
+                None => vec![[0.0, 0.0]; positions.len()],
+            };
+            let primitive_indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+
+            let positions_flat: Vec<f32> = positions
+                .iter()
+                .flat_map(|p| {
+                    let p = transform * Vector4::new(p[0], p[1], p[2], 1.0);
+                    vec![p.x, p.y, p.z]
+                })
+                .collect();
+            let normals_flat: Vec<f32> = normals
+                .iter()
+                .flat_map(|n| {
+                    let n = (normal_transform * Vector3::from(*n)).normalize();
+                    vec![n.x, n.y, n.z]
+                })
+                .collect();
+            let uvs_flat: Vec<f32> = uvs.iter().flat_map(|uv| vec![uv[0], uv[1]]).collect();
+
+            let first_vertex = (vertices.len() / VERTEX3D_FLOATS) as u32;
+            let mut range = vec![0.0f32; positions.len() * VERTEX3D_FLOATS];
+            interleave_into(&mut range, &positions_flat, &uvs_flat, &normals_flat);
+            vertices.extend(range);
+
+            submeshes.push(Submesh {
+                first_index: indices.len() as u32,
+                index_count: primitive_indices.len() as u32,
+            });
+            indices.extend(primitive_indices.iter().map(|i| first_vertex + i));
+
+            materials.push(self.material(&primitive.material()));
+        }
+
+        debug!(
+            "gltf contains {} primitives which will be merged into one mesh",
+            submeshes.len()
+        );
+        Ok(GltfModel {
+            mesh: BinaryMesh::new(vertices, indices, submeshes),
+            materials,
+        })
+    }
+
+    // The materials parse returns, one per submesh, without reading any buffers; for models
+    // whose geometry was preprocessed (see ember-assetc)
+    pub fn materials(&self) -> Result<Vec<GltfMaterial>> {
+        let document = ::gltf::Gltf::from_slice(&assets::read(&self.path)?)?.document;
+        Ok(self
+            .primitives(&document)?
+            .iter()
+            .map(|(primitive, _)| self.material(&primitive.material()))
+            .collect())
+    }
+
+    // Every triangle primitive in the default scene, with its node transform. Depth first,
+    // carrying each node's parent transform down.
+    fn primitives<'a>(
+        &self,
+        document: &'a ::gltf::Document,
+    ) -> Result<Vec<(::gltf::Primitive<'a>, Matrix4<f32>)>> {
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| anyhow!("gltf has no scenes"))?;
+
+        let mut primitives = vec![];
+        let mut nodes: Vec<(::gltf::Node, Matrix4<f32>)> = scene
+            .nodes()
+            .map(|node| (node, Matrix4::identity()))
+            .collect();
+        while let Some((node, parent)) = nodes.pop() {
+            let transform = parent * Matrix4::from(node.transform().matrix());
+            nodes.extend(node.children().map(|child| (child, transform)));
+
+            let mesh = match node.mesh() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            for primitive in mesh.primitives() {
+                if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                    warn!(
+                        "{}: skipping primitive with unsupported mode {:?}",
+                        &self.path,
+                        primitive.mode()
+                    );
+                    continue;
+                }
+                primitives.push((primitive, transform));
+            }
+        }
+        Ok(primitives)
+    }
+
+    // A file next to the model
+    fn relative(&self, uri: &str) -> String {
+        Path::new(&self.path)
+            .with_file_name(uri)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // External buffers of a .gltf, which have to be fetched before it's parsed on the web
+    // (see sources::assets)
+    pub fn buffer_paths(&self) -> Result<Vec<String>> {
+        let document = ::gltf::Gltf::from_slice(&assets::read(&self.path)?)?.document;
+        Ok(document
+            .buffers()
+            .filter_map(|buffer| match buffer.source() {
+                ::gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => {
+                    Some(self.relative(uri))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn import_buffers(
+        &self,
+        document: &::gltf::Document,
+        mut blob: Option<Vec<u8>>,
+    ) -> Result<Vec<::gltf::buffer::Data>> {
+        if cfg!(not(target_arch = "wasm32")) {
+            let base = Path::new(&self.path).parent();
+            return Ok(::gltf::import_buffers(document, base, blob)?);
+        }
+
+        // Through sources::assets, without data uris
+        document
+            .buffers()
+            .map(|buffer| {
+                let mut data = match buffer.source() {
+                    ::gltf::buffer::Source::Bin => blob
+                        .take()
+                        .ok_or_else(|| anyhow!("{}: missing binary chunk", self.path))?,
+                    ::gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                        return Err(anyhow!(
+                            "{}: data uri buffers aren't supported on the web, use .glb",
+                            self.path
+                        ))
+                    }
+                    ::gltf::buffer::Source::Uri(uri) => assets::read(&self.relative(uri))?,
+                };
+                // As gltf::import_buffers does
+                while data.len() % 4 != 0 {
+                    data.push(0);
+                }
+                Ok(::gltf::buffer::Data(data))
+            })
+            .collect()
+    }
+
+    fn material(&self, material: &::gltf::Material) -> GltfMaterial {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color_texture =
+            pbr.base_color_texture()
+                .and_then(|info| match info.texture().source().source() {
+                    ::gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                        Some(self.relative(uri))
+                    }
+                    _ => None,
+                });
+
+        GltfMaterial {
+            name: material.name().map(|name| name.to_owned()),
+            base_color: pbr.base_color_factor(),
+            base_color_texture,
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+        }
+    }
+}
+
+// Inverse transpose of the upper 3x3, so normals survive non-uniform scale
+fn normal_matrix(transform: Matrix4<f32>) -> Matrix3<f32> {
+    let upper = Matrix3::from_cols(
+        transform.x.truncate(),
+        transform.y.truncate(),
+        transform.z.truncate(),
+    );
+    upper
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(upper)
+}
+
+impl MeshBuilder for GltfLoader {
+    fn build(&self, device: Arc<wgpu::Device>) -> Mesh {
+        let model = self.parse().unwrap();
+
+        info!(
+            "loaded gltf with {} triangles from {}",
+            model.mesh.indices.len() / 3,
+            self.path.split("/").last().unwrap(),
+        );
+
+        model.mesh.into_mesh(&self.path, &device)
+    }
+}
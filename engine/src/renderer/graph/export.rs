@@ -0,0 +1,249 @@
+use std::fmt::Write as _;
+use uuid::Uuid;
+
+use super::RenderGraph;
+
+// What export_dot, export_json and the graph panel (sources::ui::graph) show of a node
+#[derive(Clone, Debug)]
+pub struct NodeSummary {
+    pub id: Uuid,
+    pub name: String,
+    // "master", "overlay", "texture", "source" or "node"
+    pub kind: &'static str,
+    // Channels and depth channels into the node
+    pub inputs: u32,
+    pub outputs: u32,
+    // Debug name of the target format; "surface" for the swap chain's (or the registry's)
+    pub format: String,
+    // Index into RenderGraph::chains
+    pub chain: Option<usize>,
+    pub loopback: bool,
+    pub depth_buffer: bool,
+    pub target_scale: f32,
+}
+
+impl RenderGraph {
+    // Every node, sorted by name so exports of the same graph diff cleanly
+    pub fn summarize(&self) -> Vec<NodeSummary> {
+        let mut summaries: Vec<NodeSummary> = self
+            .nodes
+            .values()
+            .map(|node| {
+                let id = node.id;
+                let inputs = self.channels.iter().filter(|(_, _, to)| *to == id).count()
+                    + self
+                        .depth_channels
+                        .iter()
+                        .filter(|(_, to)| *to == id)
+                        .count();
+                let format = match (node.cube_target, node.depth_target, node.target_format) {
+                    (Some(size), Some(_), _) => format!("depth cube {}x{}", size, size),
+                    (Some(size), None, format) => format!(
+                        "cube {}x{} {}",
+                        size,
+                        size,
+                        format.map_or("surface".to_owned(), |format| format!("{:?}", format))
+                    ),
+                    (None, Some(size), _) => format!("depth {}x{}", size, size),
+                    (None, None, Some(format)) => format!("{:?}", format),
+                    (None, None, None) => "surface".to_owned(),
+                };
+                NodeSummary {
+                    id,
+                    name: node.name.clone(),
+                    kind: self.node_kind(id),
+                    inputs: inputs as u32,
+                    outputs: node.render_outputs,
+                    format,
+                    chain: self.chains.iter().position(|chain| chain.contains(&id)),
+                    loopback: node.loopback,
+                    depth_buffer: node.depth_buffer,
+                    target_scale: node.target_scale,
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        summaries
+    }
+
+    fn node_kind(&self, id: Uuid) -> &'static str {
+        if id == self.master_node {
+            "master"
+        } else if self.overlay_nodes.contains(&id) {
+            "overlay"
+        } else if self.texture_nodes.contains(&id) {
+            "texture"
+        } else if self.source_nodes.contains(&id) {
+            "source"
+        } else {
+            "node"
+        }
+    }
+
+    // Node name for logs and exports, or the id if it isn't in the graph
+    pub fn node_name(&self, id: &Uuid) -> String {
+        match self.nodes.get(id) {
+            Some(node) => node.name.clone(),
+            None => id.to_string(),
+        }
+    }
+
+    // A Graphviz description of the graph, eg. for `dot -Tsvg`: a box per node (name, kind,
+    // input / output counts, target format), an edge per channel labelled with its output
+    // index (dashed for depth channels), and chains as clusters with bold edges in their
+    // draw order.
+    pub fn export_dot(&self) -> String {
+        let summaries = self.summarize();
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph render_graph {{");
+        let _ = writeln!(out, "    rankdir=LR;");
+        let _ = writeln!(out, "    node [shape=record, fontname=\"monospace\"];");
+
+        for summary in summaries.iter().filter(|summary| summary.chain.is_none()) {
+            let _ = writeln!(out, "    {}", dot_node(summary));
+        }
+        for (index, chain) in self.chains.iter().enumerate() {
+            let _ = writeln!(out, "    subgraph cluster_chain_{} {{", index);
+            let _ = writeln!(out, "        label=\"chain {}\";", index);
+            let _ = writeln!(out, "        style=dashed;");
+            for summary in summaries
+                .iter()
+                .filter(|summary| summary.chain == Some(index))
+            {
+                let _ = writeln!(out, "        {}", dot_node(summary));
+            }
+            for link in chain.windows(2) {
+                let _ = writeln!(
+                    out,
+                    "        \"{}\" -> \"{}\" [style=bold];",
+                    link[0], link[1]
+                );
+            }
+            let _ = writeln!(out, "    }}");
+        }
+
+        for (from, output, to) in &self.channels {
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                from, to, output
+            );
+        }
+        for (from, to) in &self.depth_channels {
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"depth\", style=dashed];",
+                from, to
+            );
+        }
+        let _ = writeln!(out, "}}");
+        out
+    }
+
+    // The same as export_dot, as JSON: {"nodes": [NodeSummary...], "channels": [{"from",
+    // "output", "to"}...], "depth_channels": [{"from", "to"}...], "chains": [[id...]...]}
+    pub fn export_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .summarize()
+            .iter()
+            .map(|summary| {
+                format!(
+                    "{{\"id\":\"{}\",\"name\":\"{}\",\"kind\":\"{}\",\"inputs\":{},\"outputs\":{},\"format\":\"{}\",\"chain\":{},\"loopback\":{},\"depth_buffer\":{},\"target_scale\":{}}}",
+                    summary.id,
+                    escape_json(&summary.name),
+                    summary.kind,
+                    summary.inputs,
+                    summary.outputs,
+                    escape_json(&summary.format),
+                    summary
+                        .chain
+                        .map_or("null".to_owned(), |chain| chain.to_string()),
+                    summary.loopback,
+                    summary.depth_buffer,
+                    summary.target_scale,
+                )
+            })
+            .collect();
+        let channels: Vec<String> = self
+            .channels
+            .iter()
+            .map(|(from, output, to)| {
+                format!(
+                    "{{\"from\":\"{}\",\"output\":{},\"to\":\"{}\"}}",
+                    from, output, to
+                )
+            })
+            .collect();
+        let depth_channels: Vec<String> = self
+            .depth_channels
+            .iter()
+            .map(|(from, to)| format!("{{\"from\":\"{}\",\"to\":\"{}\"}}", from, to))
+            .collect();
+        let chains: Vec<String> = self
+            .chains
+            .iter()
+            .map(|chain| {
+                let ids: Vec<String> = chain.iter().map(|id| format!("\"{}\"", id)).collect();
+                format!("[{}]", ids.join(","))
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"channels\":[{}],\"depth_channels\":[{}],\"chains\":[{}]}}",
+            nodes.join(","),
+            channels.join(","),
+            depth_channels.join(","),
+            chains.join(",")
+        )
+    }
+}
+
+fn dot_node(summary: &NodeSummary) -> String {
+    let mut details = format!(
+        "in {}, out {}\\n{}",
+        summary.inputs,
+        summary.outputs,
+        escape_dot(&summary.format)
+    );
+    if summary.target_scale != 1.0 {
+        let _ = write!(details, " @ {}", summary.target_scale);
+    }
+    if summary.depth_buffer {
+        details.push_str("\\ndepth buffer");
+    }
+    if summary.loopback {
+        details.push_str("\\nloopback");
+    }
+    format!(
+        "\"{}\" [label=\"{{{}|{}|{}}}\"];",
+        summary.id,
+        escape_dot(&summary.name),
+        summary.kind,
+        details
+    )
+}
+
+// For record labels, where braces, bars and angle brackets are structure
+fn escape_dot(value: &str) -> String {
+    value.chars().fold(String::new(), |mut out, c| {
+        if "\\\"{}|<>".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+        out
+    })
+}
+
+fn escape_json(value: &str) -> String {
+    value.chars().fold(String::new(), |mut out, c| {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+        out
+    })
+}
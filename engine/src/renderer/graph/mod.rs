@@ -2,30 +2,45 @@ use anyhow::{anyhow, Result};
 use iced_winit::Debug;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock, Weak},
 };
 use uuid::Uuid;
 use wgpu::BindGroup;
 
 use crate::{
     constants::{ID, METRICS_UI_IMGUI_ID, RENDER_UI_SYSTEM_ID},
-    renderer::{graph::target::DepthBuffer, SCREEN_SIZE, systems::ui},
+    renderer::{
+        graph::target::DepthBuffer,
+        systems::{capture, hot_reload, probe, render_3d::material_preview, streaming, ui},
+        SCREEN_SIZE,
+    },
     sources::{
         metrics::{EngineMetrics, SystemReporter},
-        registry::Registry,
-        schedule::{StatelessSystem, SubSchedule, LocalReporterSystem},
-        ui::{iced::{IcedUI, IcedWinitHelper}},
+        registry::{Registry, TextureRegistry},
+        schedule::{LocalReporterSystem, StatelessSystem, SubSchedule},
+        ui::{
+            graph::RenderGraphPanel,
+            iced::{IcedUI, IcedWinitHelper, UIPanel},
+            probe::PixelProbePanel,
+        },
     },
     texture::Texture,
 };
 
-use super::{buffer::target::TargetBuffer, systems::graph::*};
+use super::{
+    buffer::{
+        target::TargetBuffer,
+        upload::{Submission, Uploader},
+    },
+    systems::graph::*,
+};
 
 use self::{
-    node::{NodeBuilder, NodeBuilderTrait, NodeInput, RenderNode},
+    node::{InputSlot, NodeBuilder, NodeBuilderTrait, NodeInput, RenderNode},
     target::RenderTarget,
 };
 
+pub mod export;
 pub mod node;
 pub mod target;
 
@@ -48,6 +63,12 @@ pub struct NodeState {
 
     pub last_target: u32,
 
+    // Registered as a texture under the node's id; see GraphBuilder::with_texture_node
+    pub texture_node: bool,
+
+    // Where the node's commands go in the frame's submission; see Uploader::record
+    pub submission: Submission,
+
     // uniform group id -> [(element size, buffer size)]
     pub dyn_offset_state: HashMap<Uuid, (Arc<Mutex<u64>>, Vec<(u64, u64)>)>,
     // pub common_buffers: HashMap<Uuid, Arc<(wgpu::Buffer, u32)>>,
@@ -82,6 +103,27 @@ impl NodeState {
     // }
 }
 
+// Hands out the nodes' Submissions as GraphBuilder::build schedules them
+#[derive(Default)]
+struct SubmissionOrder {
+    next: Submission,
+}
+
+impl SubmissionOrder {
+    fn next(&mut self, state: &NodeState) -> NodeState {
+        let mut state = state.clone();
+        state.submission = self.next;
+        self.next.order += 1;
+        state
+    }
+
+    // After a flush
+    fn next_layer(&mut self) {
+        self.next.layer += 1;
+        self.next.order = 0;
+    }
+}
+
 pub struct RenderGraph {
     // Channels represent I/O between nodes. The output buffer of source_node
     // will be used as a texture input to dest_node.
@@ -89,6 +131,12 @@ pub struct RenderGraph {
     // (source_node, source_channel, dest_node)
     pub channels: Vec<(Uuid, u32, Uuid)>,
 
+    // Depth channels pass the depth buffer of source_node to dest_node, as an input
+    // after its color inputs.
+    //
+    // (source_node, dest_node)
+    pub depth_channels: Vec<(Uuid, Uuid)>,
+
     // Chains represent shared render targets between nodes. All nodes will
     // render to the same target in the given order (configurable blending).
     //
@@ -98,46 +146,189 @@ pub struct RenderGraph {
     pub nodes: HashMap<Uuid, Arc<RenderNode>>,
     pub source_nodes: Vec<Uuid>,
     pub master_node: Uuid,
+    pub overlay_nodes: Vec<Uuid>,
+    pub texture_nodes: Vec<Uuid>,
 
     // Targets
     pub swap_chain_target: Arc<Mutex<RenderTarget>>,
     pub ui_target: Arc<Mutex<RenderTarget>>,
     pub node_targets: TargetBuffer,
 
+    // Bind groups of every node's targets, shared with the NodeInputs reading from them.
+    // Rewritten on resize so that inputs pick up the new textures.
+    pub input_slots: HashMap<Uuid, InputSlot>,
+    // Same for the depth buffers read through depth channels
+    pub depth_slots: HashMap<Uuid, InputSlot>,
+
     pub metrics: bool,
 
     pub ui: Arc<Mutex<IcedUI>>,
     pub debug: Mutex<Debug>,
 }
 
+impl RenderGraph {
+    // Recreates all node targets at the new size. Must be called outside of the
+    // render schedule, since it locks every target.
+    //
+    // Targets are rebuilt in place, so ring order (NodeState::last_target, NodeInput::Ring)
+    // and chain sharing are left untouched; only the bind groups in input_slots are swapped.
+    //
+    // Node targets are scaled by render_scale; the master (swap chain) target never is.
+    pub fn resize(
+        &self,
+        size: (u32, u32),
+        render_scale: f32,
+        device: Arc<wgpu::Device>,
+        textures: &RwLock<TextureRegistry>,
+    ) {
+        info!(
+            "resizing render graph targets: {}, {} (render scale {})",
+            size.0, size.1, render_scale
+        );
+        let texture_registry = textures.read().unwrap();
+        let scaled_size = scale_size(size, render_scale);
+
+        // Chain links share their leader's target, so only rebuild each target once
+        let mut resized: Vec<*const Mutex<RenderTarget>> = vec![];
+        for (id, targets) in self.node_targets.targets.iter() {
+            for target in targets {
+                if resized.contains(&Arc::as_ptr(target)) {
+                    continue;
+                }
+                resized.push(Arc::as_ptr(target));
+                let mut target = target.lock().unwrap();
+                let target_size = match &*target {
+                    RenderTarget::Master { .. } => size,
+                    _ => scale_size(scaled_size, self.nodes[id].target_scale),
+                };
+                target.resize(
+                    &self.nodes[id].name,
+                    target_size,
+                    &texture_registry,
+                    Arc::clone(&device),
+                );
+            }
+        }
+
+        for (id, slot) in self.input_slots.iter() {
+            *slot.write().unwrap() = bind_groups_for_node(&self.node_targets, id);
+        }
+        for (id, slot) in self.depth_slots.iter() {
+            *slot.write().unwrap() = depth_bind_groups_for_node(&self.node_targets, id).unwrap();
+        }
+
+        drop(texture_registry);
+        self.register_textures(&mut textures.write().unwrap());
+    }
+
+    // Puts the targets of texture nodes in the registry's shared bind groups, and in every
+    // node which draws textures, under the texture node's id. A texture node can't sample
+    // its own target, so it's left out of that node.
+    pub fn register_textures(&self, textures: &mut TextureRegistry) {
+        for texture_id in &self.texture_nodes {
+            let bind_group = self.node_targets.get(texture_id)[0]
+                .lock()
+                .unwrap()
+                .get_bind_group()
+                .unwrap();
+            textures.shared.insert(*texture_id, Arc::clone(&bind_group));
+            for (id, node) in self.nodes.iter() {
+                let mut texture_groups = node.binder.texture_groups.write().unwrap();
+                if id != texture_id && !texture_groups.is_empty() {
+                    texture_groups.insert(*texture_id, Arc::clone(&bind_group));
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn scale_size(size: (u32, u32), scale: f32) -> (u32, u32) {
+    (
+        ((size.0 as f32 * scale) as u32).max(1),
+        ((size.1 as f32 * scale) as u32).max(1),
+    )
+}
+
+fn bind_groups_for_node(target_buffer: &TargetBuffer, id: &Uuid) -> Vec<Arc<BindGroup>> {
+    target_buffer
+        .get(id)
+        .into_iter()
+        .map(|target| target.lock().unwrap().get_bind_group().unwrap())
+        .collect()
+}
+
+// None if any of the node's targets has no depth buffer
+fn depth_bind_groups_for_node(
+    target_buffer: &TargetBuffer,
+    id: &Uuid,
+) -> Option<Vec<Arc<BindGroup>>> {
+    target_buffer
+        .get(id)
+        .into_iter()
+        .map(|target| target.lock().unwrap().get_depth_bind_group())
+        .collect()
+}
+
 pub struct GraphBuilder {
     pub node_builders: HashMap<Uuid, Box<dyn NodeBuilderTrait>>,
     pub source_nodes: Vec<Uuid>,
     pub master_node: Option<Uuid>,
 
+    // Overlay nodes render to the master target, after the master node (e.g. debug overlays)
+    pub overlay_nodes: Vec<Uuid>,
+    // Nodes whose target is used as a texture, rather than as a graph input
+    pub texture_nodes: Vec<Uuid>,
+    // A source node which overlays draw on instead, when the master is a post-processing pass
+    pub scene_node: Option<Uuid>,
+
     pub channels: Vec<(Uuid, u32, Uuid)>,
+    pub depth_channels: Vec<(Uuid, Uuid)>,
     pub chains: Vec<Vec<Uuid>>,
 
     pub node_states: HashMap<Uuid, NodeState>,
     pub dest: Option<Arc<RenderGraph>>,
     pub ui_mode: UIMode,
+    pub ui_panels: Vec<Box<dyn UIPanel>>,
     pub metrics: bool,
+    pub pixel_probe: bool,
+    pub graph_panel: bool,
+    pub material_preview: bool,
 }
 
 pub struct MasterDepthBuffer(DepthBuffer);
 
+// Where EngineBuilder::with_extra_node puts a node in default_2d's or default_3d's graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphWiring {
+    // Drawn into the scene after the engine's overlays
+    Overlay,
+    // A fullscreen pass reading the scene, after any post-processing effects; see
+    // PostFxChainBuilder::with_pass
+    PostProcess,
+    // Rendered into a texture of its own; see with_texture_node
+    Texture,
+}
+
 impl GraphBuilder {
     pub fn new() -> GraphBuilder {
         Self {
             node_builders: HashMap::new(),
             node_states: HashMap::new(),
             master_node: None,
+            overlay_nodes: vec![],
+            texture_nodes: vec![],
+            scene_node: None,
             dest: None,
             source_nodes: vec![],
             channels: vec![],
+            depth_channels: vec![],
             chains: vec![],
             ui_mode: UIMode::Disabled,
+            ui_panels: vec![],
             metrics: false,
+            pixel_probe: false,
+            graph_panel: false,
+            material_preview: false,
         }
     }
 
@@ -157,16 +348,64 @@ impl GraphBuilder {
         self.with_node(node)
     }
 
+    // A source node whose target overlays share, so that they're drawn before whatever
+    // reads the scene (see renderer::systems::post_fx); it needs its own depth buffer
+    // for overlays with a depth test
+    pub fn with_scene_node(mut self, node: NodeBuilder) -> Self {
+        self.scene_node = Some(node.dest_id.to_owned());
+        self.with_source_node(node)
+    }
+
+    pub fn with_overlay_node(mut self, node: NodeBuilder) -> Self {
+        self.overlay_nodes.push(node.dest_id.to_owned());
+        self.with_node(node)
+    }
+
+    // Renders into a texture registered under the node's id (TextureRegistry::shared), which
+    // Render3D entities can use like any other. Texture nodes are drawn right before the
+    // master node, so whatever reads them sees the previous frame's, and all of their
+    // inputs have to feed the master node too.
+    pub fn with_texture_node(mut self, node: NodeBuilder) -> Self {
+        self.texture_nodes.push(node.dest_id.to_owned());
+        self.with_node(node)
+    }
+
     pub fn with_channel(mut self, input: Uuid, input_index: u32, output: Uuid) -> Self {
         self.channels.push((input, input_index, output));
         self
     }
 
+    // Reads input's depth buffer (or depth target) in output, through a depth input
+    pub fn with_depth_channel(mut self, input: Uuid, output: Uuid) -> Self {
+        self.depth_channels.push((input, output));
+        self
+    }
+
     pub fn with_chain(mut self, chain: Vec<Uuid>) -> Self {
         self.chains.push(chain);
         self
     }
 
+    // Adds other's nodes and channels, for extending a graph built elsewhere (eg. by
+    // EngineBuilder::with_graph). This graph's master and scene nodes stay: other's master
+    // node is left out, and its scene node becomes a plain source node.
+    pub fn merge(mut self, mut other: GraphBuilder) -> Self {
+        if let Some(master) = other.master_node {
+            warn!("merged graphs can't bring their own master node, leaving it out");
+            other.node_builders.remove(&master);
+        }
+        self.node_builders.extend(other.node_builders);
+        self.source_nodes.extend(other.source_nodes);
+        self.overlay_nodes.extend(other.overlay_nodes);
+        self.texture_nodes.extend(other.texture_nodes);
+        self.channels.extend(other.channels);
+        self.depth_channels.extend(other.depth_channels);
+        self.chains.extend(other.chains);
+        self.ui_panels.extend(other.ui_panels);
+        self.graph_panel |= other.graph_panel;
+        self
+    }
+
     pub fn with_ui_imgui(mut self) -> Self {
         self.ui_mode = UIMode::Imgui;
         self
@@ -177,25 +416,59 @@ impl GraphBuilder {
         self
     }
 
+    // Drawn with the iced UI; see sources::ui::iced::UIPanel
+    pub fn with_ui_panel(mut self, panel: Box<dyn UIPanel>) -> Self {
+        self.ui_panels.push(panel);
+        self
+    }
+
+    // A UI panel listing the built graph's nodes, channels and chains; see
+    // sources::ui::graph::RenderGraphPanel, and RenderGraph::export_dot for the same offline
+    pub fn with_graph_panel(mut self) -> Self {
+        self.graph_panel = true;
+        self.ui_mode = UIMode::Iced;
+        self
+    }
+
     pub fn enable_renderer_metrics(mut self) -> Self {
         self.metrics = true;
         self
     }
 
+    pub fn with_pixel_probe(mut self) -> Self {
+        self.pixel_probe = true;
+        self
+    }
+
+    // Reads the MATERIAL_PREVIEW_NODE_ID texture node back for the material editor, which
+    // needs an Arc<MaterialPreview> resource; see render_3d::material_preview
+    pub fn with_material_preview(mut self) -> Self {
+        self.material_preview = true;
+        self
+    }
+
     // TODO: distil this into several functions
     pub fn build(
         &mut self,
         device: Arc<wgpu::Device>,
-        queue: Arc<wgpu::Queue>,
+        uploader: Arc<Uploader>,
         resources: &mut legion::Resources,
         sub_schedule: &mut SubSchedule,
         registry: &Registry,
-        window: &iced_winit::winit::window::Window,
         mut metrics_ui: EngineMetrics,
         helper: &IcedWinitHelper,
     ) -> Result<(Arc<RenderGraph>, Arc<EngineMetrics>)> {
-        if self.master_node.is_none() {
-            return Err(anyhow!("render graph requires a master node"));
+        // Overlays on an hdr scene have to render in its format
+        if let Some(scene) = &self.scene_node {
+            let format = self
+                .node_builders
+                .get(scene)
+                .and_then(|node| node.target_format());
+            for overlay in &self.overlay_nodes {
+                if let Some(node) = self.node_builders.get_mut(overlay) {
+                    node.set_target_format(format);
+                }
+            }
         }
 
         debug!("building render graph nodes");
@@ -203,11 +476,14 @@ impl GraphBuilder {
             .node_builders
             .iter_mut()
             .map(|(id, builder)| {
-                let node = builder.build(resources, &device, Arc::clone(&queue), registry)?;
+                let node = builder.build(resources, &device, Arc::clone(&uploader), registry)?;
                 Ok((*id, node))
             })
             .collect::<Result<HashMap<Uuid, Arc<RenderNode>>>>()?;
 
+        debug!("validating render graph");
+        self.validate(&nodes)?;
+
         debug!("creating render graph node_targets");
         let screen_size = SCREEN_SIZE.read().unwrap();
         info!(
@@ -221,46 +497,67 @@ impl GraphBuilder {
         // --------------------------------------------------
         //                  Render Targets
         // --------------------------------------------------
-        
+
         let mut chained_nodes: Vec<Uuid> = self.chains.clone().into_iter().flatten().collect();
         chained_nodes.sort_unstable();
         chained_nodes.dedup();
 
-        let link_to_leader: HashMap<Uuid, Uuid> = chained_nodes.iter().map(|link| {
-            for chain in &self.chains {
-                if chain.contains(link) {
-                    return (*link, chain[chain.len() - 1])
-                }
-            }
-            panic!("wtf");
-        }).collect();
+        // Chains are validated to be non-empty and disjoint
+        let link_to_leader: HashMap<Uuid, Uuid> = self
+            .chains
+            .iter()
+            .flat_map(|chain| {
+                chain
+                    .iter()
+                    .map(move |link| (*link, chain[chain.len() - 1]))
+            })
+            .collect();
 
         // For now, chains can only have 1 render output
-        let chain_targets: HashMap<Uuid, Arc<Mutex<RenderTarget>>> = self.chains.iter().map(|chain| {
-            let leader = chain[chain.len() - 1];
-            let leader_node = Arc::clone(&nodes[&leader]);
-
-            let depth = match leader_node.depth_buffer {
-                true => Some(Arc::new(DepthBuffer::new(&leader_node.name, (screen_size.0, screen_size.1), Arc::clone(&device)))),
-                false => None,
-            };
-            let target = Arc::new(Mutex::new(RenderTarget::new(&leader_node.name, (screen_size.0, screen_size.1), depth, &texture_registry, Arc::clone(&device))));
-
-            (leader, target)
-        }).collect();
-
+        let chain_targets: HashMap<Uuid, Arc<Mutex<RenderTarget>>> = self
+            .chains
+            .iter()
+            .map(|chain| {
+                let leader = chain[chain.len() - 1];
+                let leader_node = Arc::clone(&nodes[&leader]);
+                let size = scale_size(*screen_size, leader_node.target_scale);
+
+                let depth = match leader_node.depth_buffer {
+                    true => Some(Arc::new(DepthBuffer::new(
+                        &leader_node.name,
+                        size,
+                        &texture_registry,
+                        Arc::clone(&device),
+                    ))),
+                    false => None,
+                };
+                let target = Arc::new(Mutex::new(RenderTarget::new(
+                    &leader_node.name,
+                    size,
+                    depth,
+                    leader_node.target_format,
+                    &texture_registry,
+                    Arc::clone(&device),
+                )));
+
+                (leader, target)
+            })
+            .collect();
 
-        let targets = nodes
+        let mut targets = nodes
             .iter()
+            .filter(|(id, _)| !self.overlay_nodes.contains(id))
             .map(|(id, node)| {
-                let depth_buffers = match node.depth_buffer {
+                let size = scale_size(*screen_size, node.target_scale);
+                // Cube targets have depth buffers of their own, sized to their faces
+                let depth_buffers = match node.depth_buffer && node.cube_target.is_none() {
                     false => None,
                     true => {
                         debug!("building depth buffer for {}", node.name);
                         Some(
                             (0..node.render_outputs)
                                 .map(|_| {
-                                    Arc::new(DepthBuffer::new(&node.name, (screen_size.0, screen_size.1), Arc::clone(&device))) 
+                                    Arc::new(DepthBuffer::new(&node.name, size, &texture_registry, Arc::clone(&device)))
                                 })
                                 .collect::<Vec<Arc<DepthBuffer>>>(),
                         )
@@ -274,6 +571,17 @@ impl GraphBuilder {
                             depth_buffers
                                 .map_or_else(|| None, |bufs| Some(Arc::clone(&bufs[0]))),
                         )))]
+                    } else if let Some(size) = node.cube_target {
+                        //
+                        // Six faces, fixed size (point light shadow maps, reflection probes)
+                        vec![Arc::new(Mutex::new(match node.depth_target {
+                            Some(_) => RenderTarget::depth_cube(&node.name, size, &texture_registry, Arc::clone(&device)),
+                            None => RenderTarget::cube(&node.name, size, node.depth_buffer, node.target_format, &texture_registry, Arc::clone(&device)),
+                        }))]
+                    } else if let Some(size) = node.depth_target {
+                        //
+                        // Depth only target, fixed size (shadow maps)
+                        vec![Arc::new(Mutex::new(RenderTarget::depth(&node.name, size, &texture_registry, Arc::clone(&device))))]
                     } else {
                         //
                         // Multiple render targets even though render_outputs is 1 (loopback)
@@ -281,12 +589,12 @@ impl GraphBuilder {
                             (0..2)
                                 .map(|out_index| {
                                     Arc::new(Mutex::new(
-                                        RenderTarget::new(&node.name, (screen_size.0, screen_size.1), match &depth_buffers {
+                                        RenderTarget::new(&node.name, size, match &depth_buffers {
                                             Some(bufs) => {
                                                 Some(Arc::clone(&bufs[out_index as usize]))
                                             }
                                             None => None,
-                                        }, &texture_registry, Arc::clone(&device))
+                                        }, node.target_format, &texture_registry, Arc::clone(&device))
                                     ))
                                 })
                                 .collect::<Vec<Arc<Mutex<RenderTarget>>>>()
@@ -302,18 +610,18 @@ impl GraphBuilder {
                             //
                             // If this node is part of a chain, arc the target from
                             // the chain leader instead of creating a new one.
-                            // 
+                            //
 
-                            if chained_nodes.contains(&node.id) { 
+                            if chained_nodes.contains(&node.id) {
                                 vec![Arc::clone(&chain_targets[&link_to_leader[&node.id]])]
                             } else {
                                 vec![Arc::new(Mutex::new(
-                                    RenderTarget::new(&node.name, (screen_size.0, screen_size.1), match &depth_buffers {
+                                    RenderTarget::new(&node.name, size, match &depth_buffers {
                                         Some(bufs) => {
                                             Some(Arc::clone(&bufs[0 as usize]))
                                         }
                                         None => None,
-                                    }, &texture_registry, Arc::clone(&device))
+                                    }, node.target_format, &texture_registry, Arc::clone(&device))
                                 ))]
                             }
                         }
@@ -322,9 +630,59 @@ impl GraphBuilder {
             })
             .collect::<Result<HashMap<Uuid, Vec<Arc<Mutex<RenderTarget>>>>>>()?;
 
+        // Overlay nodes share the master (or scene) target, including its depth buffer
+        let overlay_host = self.scene_node.unwrap_or(master);
+        let host_target = Arc::clone(&targets[&overlay_host][0]);
+        for overlay in &self.overlay_nodes {
+            if nodes[overlay].depth_buffer
+                && host_target.lock().unwrap().get_depth_buffer().is_none()
+            {
+                return Err(anyhow!(
+                    "{}: overlay nodes with a depth test require {} to have a depth buffer",
+                    nodes[overlay].name,
+                    nodes[&overlay_host].name
+                ));
+            }
+            targets.insert(*overlay, vec![Arc::clone(&host_target)]);
+        }
+
         let target_buffer = TargetBuffer::new(targets, master);
         let swap_chain_target = target_buffer.master();
 
+        let input_slots: HashMap<Uuid, InputSlot> = target_buffer
+            .targets
+            .keys()
+            .filter(|id| **id != master && !self.overlay_nodes.contains(id))
+            .map(|id| {
+                (
+                    *id,
+                    Arc::new(RwLock::new(bind_groups_for_node(&target_buffer, id))),
+                )
+            })
+            .collect();
+
+        let mut depth_inputs: Vec<Uuid> = self
+            .depth_channels
+            .iter()
+            .map(|(input, _)| *input)
+            .collect();
+        depth_inputs.sort_unstable();
+        depth_inputs.dedup();
+        // Chain links have their leader's depth buffer, so this is only known now
+        let depth_slots: HashMap<Uuid, InputSlot> = depth_inputs
+            .iter()
+            .map(|id| {
+                let bind_groups =
+                    depth_bind_groups_for_node(&target_buffer, id).ok_or_else(|| {
+                        anyhow!(
+                            "{} is a depth channel input, but has no depth buffer",
+                            nodes[id].name
+                        )
+                    })?;
+                Ok((*id, Arc::new(RwLock::new(bind_groups))))
+            })
+            .collect::<Result<HashMap<Uuid, InputSlot>>>()?;
+
         // Build UI if enabled
         let ui_target = match &self.ui_mode {
             UIMode::Disabled => Arc::new(Mutex::new(RenderTarget::Empty)),
@@ -348,34 +706,30 @@ impl GraphBuilder {
                     .input_targets_for_node(*node_id)
                     .iter()
                     .map(|(input_id, input_channel)| {
-                        let bind_groups = target_buffer
-                            .get(input_id)
-                            .into_iter()
-                            .map(|target| target.lock().unwrap().get_bind_group().unwrap())
-                            .collect::<Vec<Arc<BindGroup>>>();
+                        let slot = Arc::clone(&input_slots[input_id]);
 
                         // If this out channel of the input_node is a Ring, add all targets
-                        if bind_groups.len() > 1 {
-                            NodeInput::new_ring(bind_groups)
+                        if slot.read().unwrap().len() > 1 {
+                            NodeInput::new_ring(slot)
                         // Otherwise it is a single target
                         } else {
-                            NodeInput::new_single(Arc::clone(&bind_groups[*input_channel as usize]))
+                            NodeInput::new_single(slot, *input_channel as usize)
                         }
                     })
                     .collect::<Vec<NodeInput>>();
 
                 // If this is a loopback node, set own outputs as inputs
                 if node.loopback {
-                    input_channels.insert(
-                        0,
-                        NodeInput::new_ring(
-                            target_buffer
-                                .get(node_id)
-                                .into_iter()
-                                .map(|target| target.lock().unwrap().get_bind_group().unwrap())
-                                .collect(),
-                        ),
-                    );
+                    input_channels
+                        .insert(0, NodeInput::new_ring(Arc::clone(&input_slots[node_id])));
+                }
+
+                for input_id in self.depth_inputs_for_node(*node_id) {
+                    let slot = Arc::clone(&depth_slots[&input_id]);
+                    input_channels.push(match slot.read().unwrap().len() > 1 {
+                        true => NodeInput::new_ring(Arc::clone(&slot)),
+                        false => NodeInput::new_single(Arc::clone(&slot), 0),
+                    });
                 }
 
                 let render_targets = target_buffer
@@ -399,6 +753,8 @@ impl GraphBuilder {
                         // give them a system reporter
                         reporter: metrics_ui.register_system_id(&node.name, *node_id),
                         last_target: 0,
+                        texture_node: self.texture_nodes.contains(node_id),
+                        submission: Submission::default(),
                     },
                 )
             })
@@ -414,8 +770,31 @@ impl GraphBuilder {
         //     UIBuilder::new()
         // };
 
+        // Pointed at the graph once it's built, below
+        let graph_panel = match self.graph_panel {
+            true => {
+                let graph = Arc::new(RwLock::new(Weak::new()));
+                self.ui_panels
+                    .push(Box::new(RenderGraphPanel::new(Arc::clone(&graph))));
+                Some(graph)
+            }
+            false => None,
+        };
+
+        if self.pixel_probe {
+            self.ui_panels
+                .push(Box::new(PixelProbePanel::new(Arc::clone(&metrics_arc))));
+        }
+
         let mut ui_debug = Debug::new();
-        let (iced_ui, staging_belt) = IcedUI::new(Arc::clone(&ui_target), &device, window, texture_registry.format, helper, &mut ui_debug);
+        let (iced_ui, staging_belt) = IcedUI::new(
+            Arc::clone(&ui_target),
+            &device,
+            texture_registry.format,
+            helper,
+            &mut ui_debug,
+            std::mem::take(&mut self.ui_panels),
+        );
         let iced_ui = Arc::new(Mutex::new(iced_ui));
         resources.insert(Arc::clone(&iced_ui));
         resources.insert(staging_belt);
@@ -432,7 +811,7 @@ impl GraphBuilder {
         //         // );
         //     },
         //     UIMode::Iced => {
-                
+
         //     },
         //     UIMode::Disabled => {},
         // }
@@ -444,9 +823,34 @@ impl GraphBuilder {
         //////////////////////////////////
         // BEGIN RENDER GRAPH SCHEDULER //
         //////////////////////////////////
-         
+
         debug!("scheduling render systems");
 
+        // Rebuild pipelines whose shaders changed on disk, before any node renders
+        if let Some(watcher) = hot_reload::ShaderWatcher::new(&nodes) {
+            resources.insert(watcher);
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                hot_reload::reload_shaders_system,
+            ))));
+            sub_schedule.flush();
+        }
+
+        // Textures and meshes changed on disk; the watcher is only there if the engine asked for it
+        if resources.contains::<hot_reload::AssetWatcher>() {
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                hot_reload::reload_assets_system,
+            ))));
+            sub_schedule.flush();
+        }
+
+        // Textures and meshes streamed in after startup, if the engine asked for that
+        if resources.contains::<streaming::AssetStream>() {
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                streaming::stream_assets_system,
+            ))));
+            sub_schedule.flush();
+        }
+
         // Request target from swap chain, store in graph
         sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
             begin_render_graph_system,
@@ -468,19 +872,34 @@ impl GraphBuilder {
         // Recurse backwards from the master node to find these babies.
 
         let master_map = self.build_map(master);
+        let mut submissions = SubmissionOrder::default();
 
         match master_map {
             Some(mut mm) => {
                 mm.reverse();
                 for mut exec_layer in mm {
                     exec_layer.reverse();
+                    let scene_layer = exec_layer
+                        .iter()
+                        .any(|(node, _)| Some(*node) == self.scene_node);
                     for (node, _out_index) in exec_layer {
                         sub_schedule.add_node(
                             Arc::clone(&nodes.get(&node).unwrap().system),
-                            node_states.get(&node).unwrap().to_owned(),
+                            submissions.next(node_states.get(&node).unwrap()),
                         );
                     }
                     sub_schedule.flush();
+                    submissions.next_layer();
+
+                    // Overlays finish the scene before anything reads it
+                    if scene_layer {
+                        self.schedule_overlays(
+                            &nodes,
+                            &node_states,
+                            sub_schedule,
+                            &mut submissions,
+                        );
+                    }
                 }
 
                 // let mut exec_order: Vec<(Uuid, u32)> = mm.clone().into_iter().flatten().collect();
@@ -499,19 +918,39 @@ impl GraphBuilder {
 
         // --------------------------------------------------
 
+        // Then, texture nodes, once everything they read from has been drawn
+        sub_schedule.flush();
+        for node in &self.texture_nodes {
+            sub_schedule.add_node(
+                Arc::clone(&nodes[node].system),
+                submissions.next(&node_states[node]),
+            );
+        }
+        submissions.next_layer();
+
         // Then, schedule master node
         sub_schedule.flush();
         sub_schedule.add_node(
             Arc::clone(&nodes.get(&self.master_node.unwrap()).unwrap().system),
-            node_states
-                .get(&self.master_node.unwrap())
-                .unwrap()
-                .to_owned(),
+            submissions.next(node_states.get(&self.master_node.unwrap()).unwrap()),
         );
+        submissions.next_layer();
+
+        // Then, schedule overlay nodes on top of the master target
+        if self.scene_node.is_none() {
+            self.schedule_overlays(&nodes, &node_states, sub_schedule, &mut submissions);
+        }
 
         // --------------------------------------------------
         sub_schedule.flush();
-        
+
+        // Submit what the nodes recorded, in the order they were scheduled in above; they
+        // encode in parallel, but finish in any order
+        sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+            submit_render_graph_system,
+        ))));
+        sub_schedule.flush();
+
         // Run ui system
         match self.ui_mode {
             UIMode::Imgui => panic!("IMGUI IS DISABLED RN"),
@@ -525,47 +964,353 @@ impl GraphBuilder {
             ),
             UIMode::Disabled => {}
         };
-   
 
         // --------------------------------------------------
         sub_schedule.flush();
 
+        // The material editor's preview, once its node has been submitted
+        if self.material_preview {
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                material_preview::read_back_system,
+            ))));
+            sub_schedule.flush();
+        }
+
+        // Read back the final frame under the cursor, before it's presented
+        if self.pixel_probe {
+            resources.insert(probe::PixelProbe::new(&device));
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                probe::probe_pixel_system,
+            ))));
+            sub_schedule.flush();
+        }
+
+        // Screenshots, also before the frame is presented
+        if resources.contains::<capture::CaptureQueue>() {
+            sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
+                capture::capture_frame_system,
+            ))));
+            sub_schedule.flush();
+        }
+
         // Release lock on swap chain, end of frame
 
         sub_schedule.add_stateless(Arc::new(Box::new(StatelessSystem::new(
-            end_render_graph_system, 
+            end_render_graph_system,
         ))));
 
-        //////////////////////////////// 
+        ////////////////////////////////
         // END RENDER GRAPH SCHEDULER //
         ////////////////////////////////
 
         self.dest = Some(Arc::new(RenderGraph {
             nodes,
             node_targets: target_buffer,
+            input_slots,
+            depth_slots,
             swap_chain_target,
             channels: self.channels.clone(),
+            depth_channels: self.depth_channels.clone(),
             chains: self.chains.clone(),
             source_nodes: self.source_nodes.clone(),
             master_node: self
                 .master_node
                 .expect("RenderGraphBuilder: master node required"),
+            overlay_nodes: self.overlay_nodes.clone(),
+            texture_nodes: self.texture_nodes.clone(),
             ui_target,
             metrics: self.metrics,
             ui: iced_ui,
             debug: Mutex::new(ui_debug),
         }));
 
+        if let Some(graph) = graph_panel {
+            *graph.write().unwrap() = Arc::downgrade(self.dest.as_ref().unwrap());
+        }
+
+        drop(texture_registry);
+        self.dest
+            .as_ref()
+            .unwrap()
+            .register_textures(&mut registry.textures.write().unwrap());
+
         debug!("done building render graph!");
         Ok((Arc::clone(&self.dest.as_ref().unwrap()), metrics_arc))
     }
 
+    fn schedule_overlays(
+        &self,
+        nodes: &HashMap<Uuid, Arc<RenderNode>>,
+        node_states: &HashMap<Uuid, NodeState>,
+        sub_schedule: &mut SubSchedule,
+        submissions: &mut SubmissionOrder,
+    ) {
+        if self.overlay_nodes.is_empty() {
+            return;
+        }
+        sub_schedule.flush();
+        // In the same layer, so they draw over the scene in the order they were added
+        for overlay in &self.overlay_nodes {
+            sub_schedule.add_node(
+                Arc::clone(&nodes.get(overlay).unwrap().system),
+                submissions.next(node_states.get(overlay).unwrap()),
+            );
+        }
+        submissions.next_layer();
+    }
+
+    // Checks the graph's shape, so that a malformed graph is an error naming its nodes
+    // instead of a panic somewhere in build()
+    fn validate(&self, nodes: &HashMap<Uuid, Arc<RenderNode>>) -> Result<()> {
+        let name = |id: &Uuid| match nodes.get(id) {
+            Some(node) => node.name.clone(),
+            None => format!("<unknown node {}>", id),
+        };
+        let names = |ids: &[Uuid]| ids.iter().map(name).collect::<Vec<String>>().join(", ");
+        let draws_to_master =
+            |id: &Uuid| Some(*id) == self.master_node || self.overlay_nodes.contains(id);
+        let is_texture = |id: &Uuid| self.texture_nodes.contains(id);
+
+        let master = match self.master_node {
+            Some(master) => master,
+            None => {
+                let mut all: Vec<String> = nodes.values().map(|node| node.name.clone()).collect();
+                all.sort();
+                return Err(anyhow!(
+                    "render graph requires a master node; nodes: [{}]",
+                    all.join(", ")
+                ));
+            }
+        };
+        if !nodes.get(&master).map_or(false, |node| node.master) {
+            return Err(anyhow!(
+                "master node {} was replaced by another node with its id",
+                name(&master)
+            ));
+        }
+
+        // Overlays are scheduled right after the scene node, so it has to feed the master
+        if let Some(scene) = &self.scene_node {
+            if !nodes.contains_key(scene) || draws_to_master(scene) {
+                return Err(anyhow!(
+                    "scene node {} must be a source node in the graph",
+                    name(scene)
+                ));
+            }
+            if !self.channels.iter().any(|(input, _, _)| input == scene) {
+                return Err(anyhow!(
+                    "scene node {} isn't an input to any node",
+                    name(scene)
+                ));
+            }
+        }
+
+        for texture in &self.texture_nodes {
+            if !nodes.contains_key(texture) || draws_to_master(texture) {
+                return Err(anyhow!(
+                    "texture node {} must be a node in the graph with its own target",
+                    name(texture)
+                ));
+            }
+            if self.chains.iter().any(|chain| chain.contains(texture)) {
+                return Err(anyhow!("texture node {} can't be chained", name(texture)));
+            }
+        }
+
+        for (input, input_index, output) in &self.channels {
+            if !nodes.contains_key(input) || !nodes.contains_key(output) {
+                return Err(anyhow!(
+                    "channel {} -> {} references a node that isn't in the graph",
+                    name(input),
+                    name(output)
+                ));
+            }
+            // Scheduled on their own, so nothing else can be waiting on them
+            if is_texture(input) {
+                return Err(anyhow!(
+                    "channel {} -> {}: {} is a texture node, which can't be a graph input",
+                    name(input),
+                    name(output),
+                    name(input)
+                ));
+            }
+            if draws_to_master(input) {
+                return Err(anyhow!(
+                    "channel {} -> {}: {} draws to the master target, which can't be an input",
+                    name(input),
+                    name(output),
+                    name(input)
+                ));
+            }
+
+            // Loopback nodes with one output ring between two targets
+            let node = &nodes[input];
+            let outputs = match node.loopback {
+                true => node.render_outputs.max(2),
+                false => node.render_outputs,
+            };
+            if *input_index >= outputs {
+                return Err(anyhow!(
+                    "channel {} -> {}: {} has {} output(s), so there is no output {}",
+                    name(input),
+                    name(output),
+                    name(input),
+                    outputs,
+                    input_index
+                ));
+            }
+        }
+
+        for (input, output) in &self.depth_channels {
+            if !nodes.contains_key(input) || !nodes.contains_key(output) {
+                return Err(anyhow!(
+                    "depth channel {} -> {} references a node that isn't in the graph",
+                    name(input),
+                    name(output)
+                ));
+            }
+            if is_texture(input) {
+                return Err(anyhow!(
+                    "depth channel {} -> {}: {} is a texture node, which can't be a graph input",
+                    name(input),
+                    name(output),
+                    name(input)
+                ));
+            }
+            if draws_to_master(input) {
+                return Err(anyhow!(
+                    "depth channel {} -> {}: {} draws to the master target, which can't be an input",
+                    name(input),
+                    name(output),
+                    name(input)
+                ));
+            }
+        }
+
+        let mut chained: Vec<Uuid> = vec![];
+        for chain in &self.chains {
+            if chain.is_empty() {
+                return Err(anyhow!("render graph has an empty chain"));
+            }
+            for link in chain {
+                if !nodes.contains_key(link) {
+                    return Err(anyhow!(
+                        "chain [{}] references a node that isn't in the graph",
+                        names(chain)
+                    ));
+                }
+                if draws_to_master(link) {
+                    return Err(anyhow!(
+                        "chain [{}]: {} draws to the master target, which can't be chained",
+                        names(chain),
+                        name(link)
+                    ));
+                }
+                if nodes[link].cube_target.is_some() {
+                    return Err(anyhow!(
+                        "chain [{}]: {} has a cube target, which can't be chained",
+                        names(chain),
+                        name(link)
+                    ));
+                }
+                if chained.contains(link) {
+                    return Err(anyhow!(
+                        "chain [{}]: {} is already in a chain",
+                        names(chain),
+                        name(link)
+                    ));
+                }
+                chained.push(*link);
+            }
+        }
+
+        for (id, node) in nodes {
+            if !node.master && node.depth_target.is_none() && node.render_outputs > 1 {
+                return Err(anyhow!(
+                    "{} has {} render outputs; use multiple attachments on one target instead",
+                    node.name,
+                    node.render_outputs
+                ));
+            }
+
+            // Chained nodes share a target, so channels between them only order them
+            let inputs: Vec<Uuid> = self
+                .input_targets_for_node(*id)
+                .into_iter()
+                .map(|(input, _)| input)
+                .filter(|input| {
+                    !self
+                        .chains
+                        .iter()
+                        .any(|chain| chain.contains(input) && chain.contains(id))
+                })
+                .collect();
+            if inputs.len() > node.graph_inputs as usize {
+                return Err(anyhow!(
+                    "{} takes {} graph input(s), but has channels from [{}]",
+                    node.name,
+                    node.graph_inputs,
+                    names(&inputs)
+                ));
+            }
+        }
+
+        let mut done: Vec<Uuid> = vec![];
+        for id in nodes.keys() {
+            if let Some(cycle) = self.find_cycle(*id, nodes, &mut vec![], &mut done) {
+                return Err(anyhow!(
+                    "render graph has a cycle: {}",
+                    cycle.iter().map(name).collect::<Vec<String>>().join(" <- ")
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Depth first through each node's inputs. A loopback node reading its own outputs
+    // isn't a cycle.
+    fn find_cycle(
+        &self,
+        id: Uuid,
+        nodes: &HashMap<Uuid, Arc<RenderNode>>,
+        path: &mut Vec<Uuid>,
+        done: &mut Vec<Uuid>,
+    ) -> Option<Vec<Uuid>> {
+        if let Some(start) = path.iter().position(|link| *link == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id);
+            return Some(cycle);
+        }
+        if done.contains(&id) {
+            return None;
+        }
+
+        path.push(id);
+        for (input, _) in self.all_inputs_for_node(id) {
+            if input == id && nodes[&id].loopback {
+                continue;
+            }
+            if let Some(cycle) = self.find_cycle(input, nodes, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.push(id);
+        None
+    }
+
     // Running this on the master node will return a map of all layers below the master node.
     //
     // [[l1, l1], [l2, l2, l2], [l3, l3]] etc. where master = l0 <- l1 <- l2 <- ...
     //
     fn build_map(&self, current_node: Uuid) -> Option<Vec<Vec<(Uuid, u32)>>> {
-        let current_inputs: Vec<(Uuid, u32)> = self.input_targets_for_node(current_node);
+        // Loopback nodes reading their own outputs don't depend on themselves
+        let current_inputs: Vec<(Uuid, u32)> = self
+            .all_inputs_for_node(current_node)
+            .into_iter()
+            .filter(|(in_id, _)| *in_id != current_node)
+            .collect();
         let mut dependency_layers: Vec<Vec<(Uuid, u32)>> = vec![];
 
         if current_inputs.len() > 0 {
@@ -601,4 +1346,29 @@ impl GraphBuilder {
         inputs.dedup();
         inputs
     }
+
+    fn depth_inputs_for_node(&self, node_id: Uuid) -> Vec<Uuid> {
+        let mut inputs = self
+            .depth_channels
+            .iter()
+            .filter(|(_, out_id)| *out_id == node_id)
+            .map(|(in_id, _)| *in_id)
+            .collect::<Vec<Uuid>>();
+        inputs.sort_unstable();
+        inputs.dedup();
+        inputs
+    }
+
+    // Color and depth inputs, for scheduling; a node has to render before its depth is read
+    fn all_inputs_for_node(&self, node_id: Uuid) -> Vec<(Uuid, u32)> {
+        let mut inputs = self.input_targets_for_node(node_id);
+        inputs.extend(
+            self.depth_inputs_for_node(node_id)
+                .into_iter()
+                .map(|input| (input, 0)),
+        );
+        inputs.sort_unstable();
+        inputs.dedup();
+        inputs
+    }
 }
@@ -2,17 +2,24 @@ use anyhow::{anyhow, Result};
 use legion::{systems::ParallelRunnable, Resources};
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
 };
 use uuid::Uuid;
 use wgpu::BindGroup;
 
 use crate::{
-    renderer::uniform::group::GroupResourceBuilder,
+    renderer::{
+        buffer::{texture::is_filterable, upload::Uploader},
+        shader::{check_bindings, ShaderRegistry},
+        uniform::group::{GroupResourceBuilder, UniformGroupBuilder},
+    },
     sources::{
+        assets,
         registry::{Registry, TextureType},
         schedule::{NodeSystem, SubSchedulable},
     },
+    systems::frame::FrameUniformGroup,
 };
 
 use super::NodeState;
@@ -28,6 +35,18 @@ pub struct RenderNode {
     // Pipeline settings
     pub reverse_cull: bool, //  Should front faces be culled instead of back faces?
 
+    // Overrides the registry (swap chain) format for this node's targets
+    pub target_format: Option<wgpu::TextureFormat>,
+
+    // Renders depth only, into a square depth target of this size (eg. shadow maps)
+    pub depth_target: Option<u32>,
+
+    // Renders into the faces of a cube target of this size; depth only with a depth target
+    pub cube_target: Option<u32>,
+
+    // Fraction of the screen size this node's targets are built at (eg. 0.5 for half res)
+    pub target_scale: f32,
+
     // pub blend: bool, //  Should this node render/blend into another node's target?
     //
     // Currently, each render graph node has its own outputs, because it is assumed
@@ -47,8 +66,14 @@ pub struct RenderNode {
     pub render_outputs: u32,
     pub graph_inputs: u32,
 
-    pub pipeline: wgpu::RenderPipeline,
-    pub shader_module: wgpu::ShaderModule,
+    // Locked for writing only when the shader is reloaded, before the graph runs
+    pub pipeline: RwLock<wgpu::RenderPipeline>,
+    pub shader_module: RwLock<wgpu::ShaderModule>,
+    pub shader_source: ShaderSource,
+    pub shader_defines: Vec<(String, String)>,
+    // For includes, when the shader is reloaded
+    pub shaders: Arc<RwLock<ShaderRegistry>>,
+    pub pipeline_config: PipelineConfig,
     pub binder: PipelineBinder,
 
     pub system: Arc<Box<dyn SubSchedulable>>,
@@ -61,32 +86,60 @@ pub enum NodeOutput {
 
 // If the input node renders to different targets per-frame,
 // it will be represented as a "Ring" (increments every frame).
+//
+// Bind groups are read through an InputSlot which is shared with the RenderGraph,
+// so that they can be swapped out when the targets behind them are rebuilt (resize).
 pub enum NodeInput {
     Single {
+        slot: InputSlot,
+        index: usize,
         target: Arc<BindGroup>,
     },
     Ring {
+        slot: InputSlot,
         targets: Vec<Arc<BindGroup>>,
         last: usize,
     },
 }
 
+pub type InputSlot = Arc<RwLock<Vec<Arc<BindGroup>>>>;
+
 impl NodeInput {
-    pub fn new_single(target: Arc<BindGroup>) -> Self {
-        Self::Single { target }
+    pub fn new_single(slot: InputSlot, index: usize) -> Self {
+        let target = Arc::clone(&slot.read().unwrap()[index]);
+        Self::Single {
+            slot,
+            index,
+            target,
+        }
     }
 
-    pub fn new_ring(targets: Vec<Arc<BindGroup>>) -> Self {
+    pub fn new_ring(slot: InputSlot) -> Self {
+        let targets = slot.read().unwrap().clone();
         Self::Ring {
             last: targets.len(),
             targets,
+            slot,
         }
     }
 
     pub fn bind_group_ref(&mut self) -> &BindGroup {
         match self {
-            NodeInput::Single { target } => target,
-            NodeInput::Ring { targets, last } => {
+            NodeInput::Single {
+                slot,
+                index,
+                target,
+            } => {
+                *target = Arc::clone(&slot.read().unwrap()[*index]);
+                target
+            }
+            NodeInput::Ring {
+                slot,
+                targets,
+                last,
+            } => {
+                // Ring length never changes on resize, so ping-pong order is kept
+                *targets = slot.read().unwrap().clone();
                 *last += 1;
                 if *last >= targets.len() {
                     *last = 0;
@@ -118,10 +171,21 @@ impl NodeInput {
 
     pub fn arc(&self) -> Self {
         match self {
-            NodeInput::Single { target } => NodeInput::Single {
+            NodeInput::Single {
+                slot,
+                index,
+                target,
+            } => NodeInput::Single {
+                slot: Arc::clone(slot),
+                index: *index,
                 target: Arc::clone(target),
             },
-            NodeInput::Ring { targets, last } => NodeInput::Ring {
+            NodeInput::Ring {
+                slot,
+                targets,
+                last,
+            } => NodeInput::Ring {
+                slot: Arc::clone(slot),
                 targets: targets.into_iter().map(Arc::clone).collect(),
                 last: *last,
             },
@@ -136,7 +200,8 @@ impl Clone for NodeInput {
 }
 
 pub struct PipelineBinder {
-    pub texture_groups: HashMap<Uuid, Arc<wgpu::BindGroup>>,
+    // Swapped by hot_reload::reload_assets when a texture changes on disk
+    pub texture_groups: RwLock<HashMap<Uuid, Arc<wgpu::BindGroup>>>,
     pub uniform_groups: HashMap<Uuid, Arc<wgpu::BindGroup>>,
 
     // uniform group id -> (dyn_entity_count, [(dyn uniform size, max count)])
@@ -144,10 +209,123 @@ pub struct PipelineBinder {
     pub dyn_offset_state: HashMap<Uuid, (Arc<Mutex<u64>>, Vec<(u64, u64)>)>,
 }
 
+impl RenderNode {
+    // Recompiles the shader from disk and rebuilds the pipeline. On a compile or validation
+    // error the old pipeline is kept, so a typo in a shader doesn't take the game down.
+    pub fn reload_shader(&self, device: &wgpu::Device) -> Result<()> {
+        if !matches!(self.shader_source, ShaderSource::Path(_)) {
+            return Ok(());
+        }
+
+        let label = format!("shader_{}", &self.name);
+        let wgsl = preprocess_shader(
+            &self.shader_source,
+            &self.shader_defines,
+            &self.shaders.read().unwrap(),
+            &label,
+        )?;
+        check_bindings(&wgsl, &self.name, &self.pipeline_config.bind_group_entries)?;
+
+        // Can't block in the browser, where errors go to the console instead
+        #[cfg(not(target_arch = "wasm32"))]
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader_module = build_shader(&wgsl, &label, device);
+        let pipeline = self
+            .pipeline_config
+            .build(&self.name, &shader_module, device);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(err) = futures::executor::block_on(device.pop_error_scope()) {
+            return Err(anyhow!("{}: shader failed to compile: {}", &self.name, err));
+        }
+
+        *self.shader_module.write().unwrap() = shader_module;
+        *self.pipeline.write().unwrap() = pipeline;
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub enum ShaderSource {
     WGSL(String),
     _SPIRV(String),
+    // WGSL read from disk, and hot-reloaded whenever the file changes
+    Path(PathBuf),
+}
+
+// Everything besides the shader that goes into a node's pipeline, kept around
+// so that the pipeline can be rebuilt when the shader is reloaded.
+pub struct PipelineConfig {
+    pub layout: wgpu::PipelineLayout,
+    // Entries of each bind group in the layout, which the shader is checked against
+    pub bind_group_entries: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+    pub vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    // None for depth target nodes, which have no fragment stage
+    pub color_format: Option<wgpu::TextureFormat>,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub topology: wgpu::PrimitiveTopology,
+    pub polygon_mode: wgpu::PolygonMode,
+    pub blend: Option<wgpu::BlendState>,
+    pub reverse_cull: bool,
+}
+
+impl PipelineConfig {
+    pub fn build(
+        &self,
+        name: &str,
+        shader_module: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> wgpu::RenderPipeline {
+        let color_targets = self
+            .color_format
+            .iter()
+            .map(|format| wgpu::ColorTargetState {
+                format: *format,
+                // 32 bit float targets can't be blended
+                blend: match is_filterable(*format) {
+                    true => self.blend,
+                    false => None,
+                },
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .collect::<Vec<wgpu::ColorTargetState>>();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("render_pipeline_{}", name)),
+            layout: Some(&self.layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "vs_main",
+                buffers: self.vertex_buffer_layouts.as_slice(),
+            },
+            fragment: match self.color_format {
+                Some(_) => Some(wgpu::FragmentState {
+                    module: shader_module,
+                    entry_point: "fs_main",
+                    targets: color_targets.as_slice(),
+                }),
+                None => None,
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: self.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(match self.reverse_cull {
+                    true => wgpu::Face::Front,
+                    false => wgpu::Face::Back,
+                }),
+                polygon_mode: self.polygon_mode,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
 }
 
 pub enum BindIndex {
@@ -159,6 +337,22 @@ pub enum BindIndex {
         tex_type: TextureType,
     },
     NodeInput,
+    // For inputs from nodes with non-filterable targets (eg. Rgba32Float)
+    UnfilterableNodeInput,
+    // For inputs from depth target nodes, sampled with a comparison sampler
+    ShadowMapInput,
+    // For inputs from cube target nodes (see NodeBuilder::with_cube_target)
+    CubeInput,
+    // For inputs from depth cube target nodes, sampled with a comparison sampler
+    ShadowCubeInput,
+    // For depth channels (GraphBuilder::with_depth_channel), read as plain depth values
+    DepthInput,
+    // Resolved into a Uniform bind index for the engine's frame group at build time
+    FrameUniforms,
+    // Only the layout; the node's system binds a texture of its own (eg. the glyph atlas)
+    SystemTexture {
+        tex_type: TextureType,
+    },
 }
 
 /// RenderGraph node builder.
@@ -173,8 +367,19 @@ pub struct NodeBuilder {
     pub depth_buffer: bool,
 
     pub reverse_cull: bool,
+    pub target_format: Option<wgpu::TextureFormat>,
+    pub depth_target: Option<u32>,
+    pub cube_target: Option<u32>,
+    pub target_scale: f32,
+
+    pub topology: wgpu::PrimitiveTopology,
+    pub polygon_mode: wgpu::PolygonMode,
+    pub blend: Option<wgpu::BlendState>,
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_write: bool,
 
     pub shader_source: ShaderSource,
+    pub shader_defines: Vec<(String, String)>,
     pub bind_groups: Vec<BindIndex>,
     pub vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
     pub uniform_group_builders: Vec<Arc<Mutex<dyn GroupResourceBuilder>>>,
@@ -196,12 +401,22 @@ impl NodeBuilder {
             master: false,
             loopback: false,
             reverse_cull: false,
+            target_format: None,
+            depth_target: None,
+            cube_target: None,
+            target_scale: 1.0,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_write: true,
             uniform_group_builders: vec![],
             vertex_buffer_layouts: vec![],
             bind_groups: vec![],
             system: None,
             dest: None,
             shader_source: shader,
+            shader_defines: vec![],
             dest_name: name,
             render_outputs,
             graph_inputs,
@@ -242,6 +457,48 @@ impl NodeBuilder {
         self
     }
 
+    pub fn with_unfilterable_node_input(mut self) -> Self {
+        self.bind_groups.push(BindIndex::UnfilterableNodeInput);
+        self
+    }
+
+    pub fn with_shadow_map_input(mut self) -> Self {
+        self.bind_groups.push(BindIndex::ShadowMapInput);
+        self
+    }
+
+    pub fn with_cube_input(mut self) -> Self {
+        self.bind_groups.push(BindIndex::CubeInput);
+        self
+    }
+
+    pub fn with_shadow_cube_input(mut self) -> Self {
+        self.bind_groups.push(BindIndex::ShadowCubeInput);
+        self
+    }
+
+    // Depth inputs come after the color inputs in NodeState::inputs
+    pub fn with_depth_input(mut self) -> Self {
+        self.bind_groups.push(BindIndex::DepthInput);
+        self
+    }
+
+    pub fn reads_depth(&self) -> bool {
+        self.bind_groups
+            .iter()
+            .any(|bind_group| matches!(bind_group, BindIndex::DepthInput))
+    }
+
+    pub fn with_system_texture(mut self, tex_type: TextureType) -> Self {
+        self.bind_groups.push(BindIndex::SystemTexture { tex_type });
+        self
+    }
+
+    pub fn with_frame_uniforms(mut self) -> Self {
+        self.bind_groups.push(BindIndex::FrameUniforms);
+        self
+    }
+
     pub fn with_vertex_layout(mut self, layout: wgpu::VertexBufferLayout<'static>) -> Self {
         self.vertex_buffer_layouts.push(layout);
         self
@@ -273,10 +530,88 @@ impl NodeBuilder {
         self
     }
 
+    // Render into float targets, eg. Rgba16Float, for bloom and tonemapping chains.
+    // Not for the master node, which always renders to the swap chain. 32 bit float
+    // targets can't be blended, so their pipelines drop the blend state (see
+    // PipelineConfig::build).
+    pub fn with_hdr_target(mut self, format: wgpu::TextureFormat) -> Self {
+        self.target_format = Some(format);
+        self
+    }
+
+    // Depth only: no fragment stage and no color outputs, the node's single target is
+    // a size x size depth texture which later nodes can take with_shadow_map_input.
+    pub fn with_depth_target(mut self, size: u32) -> Self {
+        self.depth_target = Some(size);
+        self
+    }
+
+    // The node's single target is a cube of size x size faces, which its system draws a face
+    // at a time (see target::CubeTarget), and later nodes can take with_cube_input. With a
+    // depth buffer, shared by the faces. Eg. for capturing reflection probes at runtime.
+    pub fn with_cube_target(mut self, size: u32) -> Self {
+        self.cube_target = Some(size);
+        self
+    }
+
+    // Both: depth only cube faces, which later nodes can take with_shadow_cube_input (point
+    // light shadow maps)
+    pub fn with_depth_cube_target(mut self, size: u32) -> Self {
+        self.depth_target = Some(size);
+        self.cube_target = Some(size);
+        self
+    }
+
+    // Render at a fraction of the screen size, eg. 0.5 for half res. Inputs are sampled by
+    // uv, so nodes reading this one don't need to know.
+    pub fn with_target_scale(mut self, scale: f32) -> Self {
+        self.target_scale = scale;
+        self
+    }
+
+    // Replaces NAME in the shader with value; see renderer::shader
+    pub fn with_shader_define<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.shader_defines
+            .push((name.to_owned(), value.to_string()));
+        self
+    }
+
     pub fn with_reverse_culling(mut self) -> Self {
         self.reverse_cull = true;
         self
     }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    // Line (wireframe) and Point need the matching device feature, requested when the
+    // adapter has it
+    pub fn with_polygon_mode(mut self, mode: wgpu::PolygonMode) -> Self {
+        self.polygon_mode = mode;
+        self
+    }
+
+    // Alpha blending by default; None for opaque passes. Ignored for 32 bit float targets,
+    // which can't be blended.
+    pub fn with_blend_state(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    // Only used if the node has a depth buffer
+    pub fn with_depth_test(mut self, compare: wgpu::CompareFunction, write: bool) -> Self {
+        self.depth_compare = compare;
+        self.depth_write = write;
+        self
+    }
+
+    // Same, keeping depth writes as they are
+    pub fn with_depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        self.depth_compare = compare;
+        self
+    }
 }
 
 impl NodeBuilderTrait for NodeBuilder {
@@ -284,11 +619,19 @@ impl NodeBuilderTrait for NodeBuilder {
         self.dest_id
     }
 
+    fn target_format(&self) -> Option<wgpu::TextureFormat> {
+        self.target_format
+    }
+
+    fn set_target_format(&mut self, format: Option<wgpu::TextureFormat>) {
+        self.target_format = format;
+    }
+
     fn build(
         &mut self,
         resources: &mut Resources,
         device: &wgpu::Device,
-        queue: Arc<wgpu::Queue>,
+        uploader: Arc<Uploader>,
         registry: &Registry,
     ) -> Result<Arc<RenderNode>> {
         debug!("building node: {}", self.dest_id);
@@ -298,17 +641,76 @@ impl NodeBuilderTrait for NodeBuilder {
             return Ok(Arc::clone(&node));
         }
 
+        if self.master && self.target_format.is_some() {
+            return Err(anyhow!(
+                "{}: the master node renders to the swap chain and can't have an hdr target",
+                &self.name
+            ));
+        }
+
+        let feature = match self.polygon_mode {
+            wgpu::PolygonMode::Fill => wgpu::Features::empty(),
+            wgpu::PolygonMode::Line => wgpu::Features::POLYGON_MODE_LINE,
+            wgpu::PolygonMode::Point => wgpu::Features::POLYGON_MODE_POINT,
+        };
+        if !device.features().contains(feature) {
+            return Err(anyhow!(
+                "{}: polygon mode {:?} isn't supported by this device",
+                &self.name,
+                self.polygon_mode
+            ));
+        }
+
+        if self.master && self.target_scale != 1.0 {
+            return Err(anyhow!(
+                "{}: the master node renders to the swap chain and can't be scaled",
+                &self.name
+            ));
+        }
+
+        if self.depth_target.is_some() && (self.master || self.render_outputs != 1) {
+            return Err(anyhow!(
+                "{}: depth target nodes can't be the master node, and have exactly one output",
+                &self.name
+            ));
+        }
+
+        if self.cube_target.is_some() && (self.master || self.render_outputs != 1 || self.loopback)
+        {
+            return Err(anyhow!(
+                "{}: cube target nodes can't be the master node or loop back, and have exactly \
+                 one output",
+                &self.name
+            ));
+        }
+
+        if let (Some(_), None, Some(format)) =
+            (self.cube_target, self.depth_target, self.target_format)
+        {
+            if !is_filterable(format) {
+                return Err(anyhow!(
+                    "{}: cube targets are sampled with filtering, so {:?} can't be one",
+                    &self.name,
+                    format
+                ));
+            }
+        }
+
         if self.vertex_buffer_layouts.len() == 0 {
             return Err(anyhow!(
                 "{{}}: render nodes require at least one vertex buffer"
             ));
         }
 
-        let shader_module = build_shader(
+        self.resolve_frame_uniforms(resources)?;
+
+        let label = format!("shader_{}", &self.name);
+        let wgsl = preprocess_shader(
             &self.shader_source,
-            &format!("shader_{}", &self.name),
-            device,
-        );
+            &self.shader_defines,
+            &registry.shaders.read().unwrap(),
+            &label,
+        )?;
 
         let bind_group_layouts = &self
             .bind_groups
@@ -321,15 +723,37 @@ impl NodeBuilderTrait for NodeBuilder {
                             self.uniform_group_builders[node_index]
                                 .lock()
                                 .unwrap()
-                                .build(device, resources, Arc::clone(&queue))?,
+                                .build(device, resources, Arc::clone(&uploader))?,
                         ),
                         None,
                     ),
                     BindIndex::NodeInput {} => (None, Some(TextureType::Image)),
+                    BindIndex::UnfilterableNodeInput => {
+                        (None, Some(TextureType::UnfilterableImage))
+                    }
+                    BindIndex::ShadowMapInput => (None, Some(TextureType::Depth)),
+                    BindIndex::CubeInput => (None, Some(TextureType::Cubemap)),
+                    BindIndex::ShadowCubeInput => (None, Some(TextureType::DepthCube)),
+                    BindIndex::DepthInput => (None, Some(TextureType::DepthBuffer)),
+                    BindIndex::SystemTexture { tex_type } => (None, Some(tex_type)),
+                    BindIndex::FrameUniforms => unreachable!(),
                 })
             })
             .collect::<Result<Vec<(Option<wgpu::BindGroupLayout>, Option<TextureType>)>>>()?;
 
+        let bind_group_entries = self
+            .bind_groups
+            .iter()
+            .zip(bind_group_layouts.iter())
+            .map(|(bind_index, (_, tex_type))| match bind_index {
+                BindIndex::Uniform { node_index } => self.uniform_group_builders[*node_index]
+                    .lock()
+                    .unwrap()
+                    .entries(),
+                _ => tex_type.unwrap().layout_entries(),
+            })
+            .collect();
+
         let texture_registry = registry.textures.read().unwrap();
         let layout_refs = bind_group_layouts
             .into_iter()
@@ -346,55 +770,50 @@ impl NodeBuilderTrait for NodeBuilder {
                 push_constant_ranges: &[],
             });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(&format!("render_pipeline_{}", &self.name)),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: "vs_main",
-                buffers: self.vertex_buffer_layouts.as_slice(),
+        let pipeline_config = PipelineConfig {
+            layout: render_pipeline_layout,
+            bind_group_entries,
+            vertex_buffer_layouts: self.vertex_buffer_layouts.clone(),
+            color_format: match self.depth_target {
+                Some(_) => None,
+                None => Some(
+                    self.target_format
+                        .unwrap_or(registry.textures.read().unwrap().format),
+                ),
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: registry.textures.read().unwrap().format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(match self.reverse_cull {
-                    true => wgpu::Face::Front,
-                    false => wgpu::Face::Back,
+            depth_stencil: match (self.depth_buffer, self.depth_target) {
+                (_, Some(_)) => Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    // Slope scaled bias keeps surfaces from shadowing themselves (acne)
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
                 }),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-                unclipped_depth: false,
-            },
-            depth_stencil: match self.depth_buffer {
-                false => None,
-                true => {
+                (false, None) => None,
+                (true, None) => {
                     debug!("adding depth buffer to pipeline: {}", self.name);
                     Some(wgpu::DepthStencilState {
                         format: wgpu::TextureFormat::Depth32Float,
-                        depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::Less,
+                        depth_write_enabled: self.depth_write,
+                        depth_compare: self.depth_compare,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     })
                 }
             },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+            topology: self.topology,
+            polygon_mode: self.polygon_mode,
+            blend: self.blend,
+            reverse_cull: self.reverse_cull,
+        };
+        check_bindings(&wgsl, &self.name, &pipeline_config.bind_group_entries)?;
+        let shader_module = build_shader(&wgsl, &label, device);
+        let pipeline = pipeline_config.build(&self.name, &shader_module, device);
 
         // Move registered uniform groups and sources into system resources
         for builder in &self.uniform_group_builders {
@@ -445,7 +864,7 @@ impl NodeBuilderTrait for NodeBuilder {
         //
 
         let binder = PipelineBinder {
-            texture_groups,
+            texture_groups: RwLock::new(texture_groups),
             uniform_groups,
             dyn_offset_state,
         };
@@ -460,35 +879,98 @@ impl NodeBuilderTrait for NodeBuilder {
             depth_buffer: self.depth_buffer,
             loopback: self.loopback,
             reverse_cull: self.reverse_cull,
+            target_format: self.target_format,
+            depth_target: self.depth_target,
+            cube_target: self.cube_target,
+            target_scale: self.target_scale,
             binder,
-            pipeline,
-            shader_module,
+            pipeline: RwLock::new(pipeline),
+            shader_module: RwLock::new(shader_module),
+            shader_source: self.shader_source.clone(),
+            shader_defines: self.shader_defines.clone(),
+            shaders: Arc::clone(&registry.shaders),
+            pipeline_config,
         }));
 
         Ok(Arc::clone(&self.dest.as_ref().unwrap()))
     }
 }
 
+impl NodeBuilder {
+    // The frame uniform group is built by the engine before the render graph, so
+    // every node which asks for it shares the same group builder from resources.
+    fn resolve_frame_uniforms(&mut self, resources: &Resources) -> Result<()> {
+        if !self
+            .bind_groups
+            .iter()
+            .any(|bind| matches!(bind, BindIndex::FrameUniforms))
+        {
+            return Ok(());
+        }
+
+        let frame_group_builder = Arc::clone(
+            &*resources
+                .get::<Arc<Mutex<UniformGroupBuilder<FrameUniformGroup>>>>()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{}: frame uniforms requested but the engine has not built them",
+                        &self.name
+                    )
+                })?,
+        );
+
+        let node_index = self.uniform_group_builders.len();
+        self.uniform_group_builders.push(frame_group_builder);
+        for bind in self.bind_groups.iter_mut() {
+            if let BindIndex::FrameUniforms = bind {
+                *bind = BindIndex::Uniform { node_index };
+            }
+        }
+        Ok(())
+    }
+}
+
 pub trait NodeBuilderTrait {
     fn id(&self) -> Uuid;
+    fn target_format(&self) -> Option<wgpu::TextureFormat>;
+    // Overlays draw on another node's target, so their pipelines take its format
+    fn set_target_format(&mut self, format: Option<wgpu::TextureFormat>);
     fn build(
         &mut self,
         resources: &mut Resources,
         device: &wgpu::Device,
-        queue: Arc<wgpu::Queue>,
+        uploader: Arc<Uploader>,
         registry: &Registry,
     ) -> Result<Arc<RenderNode>>;
 }
 
-fn build_shader(source: &ShaderSource, label: &str, device: &wgpu::Device) -> wgpu::ShaderModule {
+fn build_shader(wgsl: &str, label: &str, device: &wgpu::Device) -> wgpu::ShaderModule {
     device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: Some(label),
-        source: match source {
-            ShaderSource::WGSL(src) => wgpu::ShaderSource::Wgsl(src.clone().into()),
-            _ => panic!(
-                "Error building shader {}: only WGSL shaders are supported currently",
-                label
-            ),
-        },
+        source: wgpu::ShaderSource::Wgsl(wgsl.into()),
     })
 }
+
+fn preprocess_shader(
+    source: &ShaderSource,
+    defines: &[(String, String)],
+    shaders: &ShaderRegistry,
+    label: &str,
+) -> Result<String> {
+    let wgsl = read_shader(source, label)?;
+    shaders
+        .preprocess(&wgsl, label, defines)
+        .map_err(|err| anyhow!("{}: failed to preprocess shader: {}", label, err))
+}
+
+fn read_shader(source: &ShaderSource, label: &str) -> Result<String> {
+    match source {
+        ShaderSource::WGSL(src) => Ok(src.clone()),
+        ShaderSource::Path(path) => assets::read_to_string(&path.to_string_lossy())
+            .map_err(|err| anyhow!("{}: failed to read {}: {}", label, path.display(), err)),
+        _ => Err(anyhow!(
+            "Error building shader {}: only WGSL shaders are supported currently",
+            label
+        )),
+    }
+}
@@ -1,12 +1,18 @@
 use anyhow::{anyhow, Result};
+use cgmath::{Matrix4, Point3, Vector3};
 use std::{
     borrow::BorrowMut,
+    num::NonZeroU32,
     sync::{Arc, RwLockReadGuard},
 };
 use wgpu::{BindGroupLayout, Device};
 
 use crate::{
-    renderer::buffer::texture::Texture,
+    constants::OPENGL_TO_WGPU_MATRIX,
+    renderer::{
+        buffer::texture::{is_filterable, Texture},
+        SCREEN_SIZE,
+    },
     sources::registry::{TextureRegistry, TextureType},
 };
 
@@ -15,27 +21,120 @@ pub enum RenderTarget {
     Texture {
         color_buffer: Arc<Texture>,
         depth_buffer: Option<Arc<DepthBuffer>>,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
     },
     Master {
         screen_buffer: Option<Arc<wgpu::SurfaceTexture>>,
         screen_view: Option<Arc<wgpu::TextureView>>,
         depth_buffer: Option<Arc<DepthBuffer>>,
     },
+    // Depth only, at a fixed size (eg. shadow maps); inputs sample it with a comparison sampler
+    Depth {
+        depth_buffer: Arc<DepthBuffer>,
+        bind_group: Arc<wgpu::BindGroup>,
+    },
+    // Six faces at a fixed size, rendered one at a time; see CubeTarget
+    Cube(Arc<CubeTarget>),
 }
 
 pub struct DepthBuffer(pub Texture);
 
 impl DepthBuffer {
-    pub fn new(name: &str, size: (u32, u32), device: Arc<Device>) -> Self {
-        DepthBuffer(Texture::depth_buffer(
+    // Sampleable, with a bind group for depth inputs (TextureType::DepthBuffer), which read
+    // plain depth values instead of comparing against them
+    pub fn new(
+        name: &str,
+        size: (u32, u32),
+        tex_reg: &RwLockReadGuard<TextureRegistry>,
+        device: Arc<Device>,
+    ) -> Self {
+        let mut texture = Texture::depth_buffer(
             &format!("{}_depth_target", name),
             &device,
             size,
             wgpu::TextureFormat::Depth32Float,
-        ))
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        texture.bind_group = Some(Arc::new(device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: tex_reg.bind_group_layout(TextureType::DepthBuffer),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+                label: Some(&format!("{}_depth_input_bind_group", name)),
+            },
+        )));
+        texture.texture_type = TextureType::DepthBuffer;
+        DepthBuffer(texture)
+    }
+}
+
+// Six square faces, drawn a pass per face with RenderTarget::create_face_pass and sampled as
+// one cube texture: color (TextureType::Cubemap) for eg. reflection probes captured at
+// runtime, or depth only (TextureType::DepthCube) for point light shadow maps.
+pub struct CubeTarget {
+    // Its bind group is the cube's
+    pub texture: Texture,
+    // 2D views of the faces to render into, in cube order (+x, -x, +y, -y, +z, -z)
+    pub faces: Vec<wgpu::TextureView>,
+    // Shared by every face; color cubes only, depth cubes are depth already
+    pub depth_buffer: Option<Arc<DepthBuffer>>,
+    pub size: u32,
+}
+
+impl CubeTarget {
+    pub fn is_depth(&self) -> bool {
+        matches!(self.texture.texture_type, TextureType::DepthCube)
+    }
+
+    // World space to each face's clip space, in cube order, for a cube centered on position.
+    // Cube faces are laid out mirrored compared to a camera's view, so this flips them, and
+    // they have to be drawn with reverse culling (NodeBuilder::with_reverse_culling).
+    pub fn view_projs(position: [f32; 3], near: f32, far: f32) -> [Matrix4<f32>; 6] {
+        let faces = [
+            (Vector3::unit_x(), -Vector3::unit_y()),
+            (-Vector3::unit_x(), -Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (-Vector3::unit_y(), -Vector3::unit_z()),
+            (Vector3::unit_z(), -Vector3::unit_y()),
+            (-Vector3::unit_z(), -Vector3::unit_y()),
+        ];
+        let eye = Point3::from(position);
+        let flip = Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0);
+        let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(cgmath::Deg(90.0), 1.0, near, far);
+        faces.map(|(forward, up)| flip * proj * Matrix4::look_at_rh(eye, eye + forward, up))
     }
 }
 
+fn face_views(texture: &wgpu::Texture) -> Vec<wgpu::TextureView> {
+    (0..6)
+        .map(|face| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
 impl RenderTarget {
     pub fn empty_master(depth_buffer: Option<Arc<DepthBuffer>>) -> Self {
         RenderTarget::Master {
@@ -45,20 +144,27 @@ impl RenderTarget {
         }
     }
 
+    // Format defaults to the registry's (swap chain) format; pass eg. Rgba16Float for HDR
     pub fn new(
         name: &str,
         size: (u32, u32),
         depth: Option<Arc<DepthBuffer>>,
+        format: Option<wgpu::TextureFormat>,
         tex_reg: &RwLockReadGuard<TextureRegistry>,
         device: Arc<Device>,
     ) -> Self {
+        let format = format.unwrap_or(tex_reg.format);
+        let tex_type = match is_filterable(format) {
+            true => TextureType::Image,
+            false => TextureType::UnfilterableImage,
+        };
         RenderTarget::Texture {
             color_buffer: Arc::new(
                 Texture::blank(
                     size,
                     &device,
-                    tex_reg.format,
-                    &tex_reg.bind_group_layout(TextureType::Image),
+                    format,
+                    &tex_reg.bind_group_layout(tex_type),
                     Some(&format!("{}_render_target", name)),
                     true,
                 )
@@ -68,6 +174,133 @@ impl RenderTarget {
                 Some(buf) => Some(Arc::clone(&buf)),
                 None => None,
             },
+            format,
+            size,
+        }
+    }
+
+    pub fn depth(
+        name: &str,
+        size: u32,
+        tex_reg: &RwLockReadGuard<TextureRegistry>,
+        device: Arc<Device>,
+    ) -> Self {
+        let depth_buffer = DepthBuffer::new(name, (size, size), tex_reg, Arc::clone(&device));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: tex_reg.bind_group_layout(TextureType::Depth),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_buffer.0.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&depth_buffer.0.sampler),
+                },
+            ],
+            label: Some(&format!("{}_depth_bind_group", name)),
+        });
+        RenderTarget::Depth {
+            depth_buffer: Arc::new(depth_buffer),
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    // Color faces, in format (the registry's by default), which has to be filterable
+    pub fn cube(
+        name: &str,
+        size: u32,
+        depth: bool,
+        format: Option<wgpu::TextureFormat>,
+        tex_reg: &RwLockReadGuard<TextureRegistry>,
+        device: Arc<Device>,
+    ) -> Self {
+        let texture = Texture::blank_cubemap(
+            (size, size),
+            &device,
+            format.unwrap_or(tex_reg.format),
+            tex_reg.bind_group_layout(TextureType::Cubemap),
+            Some(&format!("{}_cube_target", name)),
+            true,
+        )
+        .unwrap();
+        let depth_buffer = match depth {
+            true => Some(Arc::new(DepthBuffer::new(
+                name,
+                (size, size),
+                tex_reg,
+                Arc::clone(&device),
+            ))),
+            false => None,
+        };
+        RenderTarget::Cube(Arc::new(CubeTarget {
+            faces: face_views(&texture.texture),
+            texture,
+            depth_buffer,
+            size,
+        }))
+    }
+
+    // Depth only faces, sampled with a comparison sampler
+    pub fn depth_cube(
+        name: &str,
+        size: u32,
+        tex_reg: &RwLockReadGuard<TextureRegistry>,
+        device: Arc<Device>,
+    ) -> Self {
+        let mut texture = Texture::depth_cube(&format!("{}_depth_cube", name), &device, size);
+        texture.bind_group = Some(Arc::new(device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: tex_reg.bind_group_layout(TextureType::DepthCube),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+                label: Some(&format!("{}_depth_cube_bind_group", name)),
+            },
+        )));
+        RenderTarget::Cube(Arc::new(CubeTarget {
+            faces: face_views(&texture.texture),
+            texture,
+            depth_buffer: None,
+            size,
+        }))
+    }
+
+    // Rebuilds the textures behind this target in place, so that every node
+    // holding an Arc to it (chains, rings) sees the new size.
+    pub fn resize(
+        &mut self,
+        name: &str,
+        size: (u32, u32),
+        tex_reg: &RwLockReadGuard<TextureRegistry>,
+        device: Arc<Device>,
+    ) {
+        match self {
+            RenderTarget::Empty => (),
+            RenderTarget::Texture {
+                depth_buffer,
+                format,
+                ..
+            } => {
+                let depth = depth_buffer
+                    .as_ref()
+                    .map(|_| Arc::new(DepthBuffer::new(name, size, tex_reg, Arc::clone(&device))));
+                *self = RenderTarget::new(name, size, depth, Some(*format), tex_reg, device);
+            }
+            // Not tied to the screen size
+            RenderTarget::Depth { .. } | RenderTarget::Cube(_) => (),
+            RenderTarget::Master { depth_buffer, .. } => {
+                if depth_buffer.is_some() {
+                    *depth_buffer = Some(Arc::new(DepthBuffer::new(name, size, tex_reg, device)));
+                }
+            }
         }
     }
 
@@ -82,6 +315,7 @@ impl RenderTarget {
             RenderTarget::Texture {
                 color_buffer,
                 depth_buffer,
+                ..
             } => Ok(create_render_pass(
                 name,
                 &color_buffer.view,
@@ -90,26 +324,115 @@ impl RenderTarget {
                 clear,
             )),
             RenderTarget::Master {
-                screen_buffer,
                 screen_view,
                 depth_buffer,
-            } => match screen_buffer {
-                Some(buf) => Ok(create_render_pass(
+                ..
+            } => match screen_view {
+                Some(view) => Ok(create_render_pass(
                     name,
-                    screen_view.as_ref().unwrap(),
+                    view,
                     depth_buffer.as_ref().map(|tex| &tex.0.view),
                     encoder,
                     clear,
                 )),
                 None => Err(anyhow!("no screen buffer")),
             },
+            RenderTarget::Depth { depth_buffer, .. } => Ok(create_depth_pass(
+                name,
+                &depth_buffer.0.view,
+                encoder,
+                clear,
+            )),
+            RenderTarget::Cube(_) => Err(anyhow!(
+                "cube targets are drawn a face at a time, with create_face_pass"
+            )),
         }
     }
 
+    // One of a cube target's faces, in cube order; see CubeTarget
+    pub fn create_face_pass<'a>(
+        &'a self,
+        name: &'a str,
+        face: usize,
+        encoder: &'a mut wgpu::CommandEncoder,
+        clear: bool,
+    ) -> Result<wgpu::RenderPass<'a>> {
+        let cube = match self {
+            RenderTarget::Cube(cube) => cube,
+            _ => return Err(anyhow!("only cube targets have faces")),
+        };
+        let view = cube
+            .faces
+            .get(face)
+            .ok_or_else(|| anyhow!("cube targets have 6 faces, not {}", face + 1))?;
+        Ok(match cube.is_depth() {
+            true => create_depth_pass(name, view, encoder, clear),
+            false => create_render_pass(
+                name,
+                view,
+                cube.depth_buffer.as_ref().map(|depth| &depth.0.view),
+                encoder,
+                clear,
+            ),
+        })
+    }
+
+    // Loads both color and depth, for nodes which draw on top of another node's output
+    pub fn create_overlay_pass<'a>(
+        &'a self,
+        name: &'a str,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> Result<wgpu::RenderPass<'a>> {
+        let (color_view, depth_buffer) = match self {
+            RenderTarget::Empty => return Err(anyhow!("cannot render to an empty target")),
+            RenderTarget::Texture {
+                color_buffer,
+                depth_buffer,
+                ..
+            } => (&color_buffer.view, depth_buffer),
+            RenderTarget::Master {
+                screen_view,
+                depth_buffer,
+                ..
+            } => match screen_view {
+                Some(view) => (view.as_ref(), depth_buffer),
+                None => return Err(anyhow!("no screen buffer")),
+            },
+            RenderTarget::Depth { .. } => {
+                return Err(anyhow!("cannot draw overlays on a depth-only target"))
+            }
+            RenderTarget::Cube(_) => return Err(anyhow!("cannot draw overlays on a cube target")),
+        };
+
+        Ok(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(name),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: depth_buffer.as_ref().map(|depth| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.0.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+        }))
+    }
+
     pub fn borrow_if_master(&self) -> Option<Arc<wgpu::SurfaceTexture>> {
         match self {
             RenderTarget::Empty => None,
             RenderTarget::Texture { .. } => None,
+            RenderTarget::Depth { .. } => None,
+            RenderTarget::Cube(_) => None,
             RenderTarget::Master {
                 screen_buffer,
                 screen_view: _,
@@ -121,10 +444,9 @@ impl RenderTarget {
     pub fn get_view(&self) -> &wgpu::TextureView {
         match self {
             RenderTarget::Empty => todo!(),
-            RenderTarget::Texture {
-                color_buffer,
-                depth_buffer: _,
-            } => &color_buffer.view,
+            RenderTarget::Texture { color_buffer, .. } => &color_buffer.view,
+            RenderTarget::Depth { depth_buffer, .. } => &depth_buffer.0.view,
+            RenderTarget::Cube(cube) => &cube.texture.view,
             RenderTarget::Master {
                 screen_view,
                 screen_buffer: _,
@@ -133,13 +455,24 @@ impl RenderTarget {
         }
     }
 
+    // In pixels, of a face for cube targets; None for depth targets (fixed size) and empty ones
+    pub fn size(&self) -> Option<(u32, u32)> {
+        match self {
+            RenderTarget::Texture { size, .. } => Some(*size),
+            RenderTarget::Cube(cube) => Some((cube.size, cube.size)),
+            RenderTarget::Master { .. } => Some(*SCREEN_SIZE.read().unwrap()),
+            RenderTarget::Empty | RenderTarget::Depth { .. } => None,
+        }
+    }
+
     pub fn get_bind_group(&self) -> Option<Arc<wgpu::BindGroup>> {
         match self {
             RenderTarget::Empty => None,
-            RenderTarget::Texture {
-                color_buffer,
-                depth_buffer: _,
-            } => Some(Arc::clone(color_buffer.bind_group.as_ref().unwrap())),
+            RenderTarget::Texture { color_buffer, .. } => {
+                Some(Arc::clone(color_buffer.bind_group.as_ref().unwrap()))
+            }
+            RenderTarget::Depth { bind_group, .. } => Some(Arc::clone(bind_group)),
+            RenderTarget::Cube(cube) => cube.texture.bind_group.as_ref().map(Arc::clone),
             // Master node cannot be used as input
             RenderTarget::Master { .. } => None,
         }
@@ -148,7 +481,9 @@ impl RenderTarget {
     pub fn get_depth_buffer(&self) -> Option<Arc<DepthBuffer>> {
         match self {
             RenderTarget::Empty => None,
-            RenderTarget::Texture { .. } => None,
+            RenderTarget::Texture { depth_buffer, .. } => depth_buffer.as_ref().map(Arc::clone),
+            RenderTarget::Depth { depth_buffer, .. } => Some(Arc::clone(depth_buffer)),
+            RenderTarget::Cube(cube) => cube.depth_buffer.as_ref().map(Arc::clone),
             RenderTarget::Master {
                 screen_buffer: _,
                 screen_view: _,
@@ -157,13 +492,19 @@ impl RenderTarget {
         }
     }
 
+    // For depth channels; see DepthBuffer::new
+    pub fn get_depth_bind_group(&self) -> Option<Arc<wgpu::BindGroup>> {
+        self.get_depth_buffer()
+            .and_then(|depth| depth.0.bind_group.as_ref().map(Arc::clone))
+    }
+
     pub fn set_depth_buffer(&mut self, buffer: Arc<DepthBuffer>) {
         match self {
             RenderTarget::Empty => (),
-            RenderTarget::Texture {
-                color_buffer: _,
-                depth_buffer,
-            } => *depth_buffer = Some(buffer),
+            RenderTarget::Texture { depth_buffer, .. } => *depth_buffer = Some(buffer),
+            // Own their depth buffers: the depth target's bind group points at it, and cube
+            // faces are their own size
+            RenderTarget::Depth { .. } | RenderTarget::Cube(_) => (),
             RenderTarget::Master {
                 screen_buffer: _,
                 screen_view: _,
@@ -188,6 +529,19 @@ impl RenderTarget {
         }
     }
 
+    // Headless: render into an offscreen texture instead of the swap chain
+    pub fn set_offscreen(&mut self, view: Arc<wgpu::TextureView>) {
+        if let RenderTarget::Master {
+            screen_buffer,
+            screen_view,
+            depth_buffer: _,
+        } = self
+        {
+            *screen_view = Some(view);
+            *screen_buffer = None;
+        }
+    }
+
     // Release lock on swap chain so that buffer can
     // be drawn to window
     pub fn release_swap_chain(&mut self) {
@@ -198,8 +552,10 @@ impl RenderTarget {
         } = self
         {
             let screen_buffer = screen_buffer.borrow_mut();
-            let released = std::mem::replace(screen_buffer, None).unwrap();
-            Arc::try_unwrap(released).unwrap().present();
+            // Nothing to present when headless
+            if let Some(released) = std::mem::replace(screen_buffer, None) {
+                Arc::try_unwrap(released).unwrap().present();
+            }
         }
     }
 
@@ -209,17 +565,29 @@ impl RenderTarget {
             RenderTarget::Texture {
                 color_buffer,
                 depth_buffer,
+                format,
+                size,
             } => RenderTarget::Texture {
                 color_buffer: Arc::clone(&color_buffer),
                 depth_buffer: depth_buffer.as_ref().map(Arc::clone),
+                format: *format,
+                size: *size,
+            },
+            RenderTarget::Depth {
+                depth_buffer,
+                bind_group,
+            } => RenderTarget::Depth {
+                depth_buffer: Arc::clone(depth_buffer),
+                bind_group: Arc::clone(bind_group),
             },
+            RenderTarget::Cube(cube) => RenderTarget::Cube(Arc::clone(cube)),
             RenderTarget::Master {
                 screen_buffer,
                 screen_view,
                 depth_buffer,
             } => RenderTarget::Master {
-                screen_buffer: Some(Arc::clone(screen_buffer.as_ref().unwrap())),
-                screen_view: Some(Arc::clone(screen_view.as_ref().unwrap())),
+                screen_buffer: screen_buffer.as_ref().map(Arc::clone),
+                screen_view: screen_view.as_ref().map(Arc::clone),
                 depth_buffer: depth_buffer.as_ref().map(Arc::clone),
             },
         }
@@ -279,3 +647,30 @@ pub fn create_render_pass<'a>(
         }),
     })
 }
+
+pub fn create_depth_pass<'a>(
+    name: &'a str,
+    depth_target: &'a wgpu::TextureView,
+    encoder: &'a mut wgpu::CommandEncoder,
+    clear: bool,
+) -> wgpu::RenderPass<'a> {
+    debug!(
+        "creating depth-only render pass: {}, clear: {}",
+        name, clear
+    );
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(name),
+        color_attachments: &[],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: depth_target,
+            depth_ops: Some(wgpu::Operations {
+                load: match clear {
+                    true => wgpu::LoadOp::Clear(1.0),
+                    false => wgpu::LoadOp::Load,
+                },
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+    })
+}
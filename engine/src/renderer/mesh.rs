@@ -1,15 +1,63 @@
-use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use std::{convert::TryInto, fs, io::Cursor, ops::Range, sync::Arc};
 use uuid::Uuid;
 
-use crate::sources::registry::MeshBuilder;
+use crate::{
+    parallel::*,
+    sources::{assets, registry::MeshBuilder},
+};
 
-use super::buffer::{IndexBuffer, VertexBuffer};
+use super::buffer::{interleave_into, IndexBuffer, Vertex3DSkinned, VertexBuffer, VERTEX3D_FLOATS};
 
 pub struct Mesh {
-    pub vertices: Vec<f32>,
-    pub indices: Vec<u32>,
+    // CPU copies, eg. for picking. Meshes cloned from the same registry entry share them;
+    // see systems::dynamic_mesh for writing to them.
+    pub vertices: Arc<Vec<f32>>,
+    pub indices: Arc<Vec<u32>>,
     pub vertex_buffer: VertexBuffer,
     pub index_buffer: IndexBuffer,
+
+    // (group, mesh) when cloned from the MeshRegistry, for hot reloading
+    pub source: Option<(Uuid, Uuid)>,
+}
+
+impl Mesh {
+    // For entities with a Skeleton, drawn by render_3d::forward_skinned
+    pub fn skinned(
+        name: &str,
+        vertices: &[Vertex3DSkinned],
+        indices: &[u32],
+        device: &wgpu::Device,
+    ) -> Self {
+        Mesh {
+            vertex_buffer: VertexBuffer::new_3d_skinned(name, vertices, device),
+            index_buffer: IndexBuffer::new(indices, device),
+            vertices: Arc::new(bytemuck::cast_slice(vertices).to_vec()),
+            indices: Arc::new(indices.to_vec()),
+            source: None,
+        }
+    }
+
+    // Interleaved Vertex3D vertices, uploaded straight from the shared copies
+    pub fn shared_3d(
+        name: &str,
+        vertices: Arc<Vec<f32>>,
+        indices: Arc<Vec<u32>>,
+        device: &wgpu::Device,
+    ) -> Self {
+        Mesh {
+            vertex_buffer: VertexBuffer::raw(
+                name,
+                &vertices,
+                (vertices.len() / VERTEX3D_FLOATS) as u32,
+                device,
+            ),
+            index_buffer: IndexBuffer::new(&indices, device),
+            vertices,
+            indices,
+            source: None,
+        }
+    }
 }
 
 pub struct ObjLoader {
@@ -32,8 +80,9 @@ impl ObjLoader {
     }
 }
 
-impl MeshBuilder for ObjLoader {
-    fn build(&self, device: Arc<wgpu::Device>) -> Mesh {
+impl ObjLoader {
+    // Interleaved Vertex3D vertices, with one submesh per model in the file
+    pub fn parse(&self) -> Result<BinaryMesh> {
         debug!("building obj meshes from file: {}", &self.path);
 
         let options = tobj::LoadOptions {
@@ -43,64 +92,421 @@ impl MeshBuilder for ObjLoader {
             ignore_points: false,
             ..Default::default()
         };
-        let (models, _) = tobj::load_obj(&self.path, &options).unwrap();
+        // Materials aren't used, so their libraries aren't read
+        let bytes = assets::read(&self.path)?;
+        let (models, _) = tobj::load_obj_buf(&mut Cursor::new(bytes), &options, |_| {
+            Err(tobj::LoadError::OpenFileFailed)
+        })?;
         debug!(
             "obj contains {} models which will be merged into one mesh",
             models.len()
         );
 
-        let mut flat_vertices: Vec<f32> = vec![];
-        let mut flat_uvs: Vec<f32> = vec![];
-        let mut flat_normals: Vec<f32> = vec![];
+        // Each model is interleaved on its own thread, straight into its range of the
+        // merged vertex data, which the built meshes then share (see ParsedMesh)
+        let vertex_count = |model: &tobj::Model| model.mesh.positions.len() / 3;
+        let mut vertices =
+            vec![0.0f32; models.iter().map(vertex_count).sum::<usize>() * VERTEX3D_FLOATS];
+        let mut ranges: Vec<&mut [f32]> = Vec::with_capacity(models.len());
+        let mut rest = vertices.as_mut_slice();
+        for model in &models {
+            let (range, tail) =
+                std::mem::take(&mut rest).split_at_mut(vertex_count(model) * VERTEX3D_FLOATS);
+            ranges.push(range);
+            rest = tail;
+        }
+        ranges
+            .into_par_iter()
+            .zip(models.par_iter())
+            .enumerate()
+            .for_each(|(i, (range, model))| {
+                let mesh = &model.mesh;
+                debug!(
+                    "building mesh {} with {} triangles and {} indices (faces: {})",
+                    i,
+                    mesh.positions.len() / 3,
+                    mesh.indices.len(),
+                    mesh.face_arities.len(),
+                );
+                interleave_into(range, &mesh.positions, &mesh.texcoords, &mesh.normals);
+            });
 
-        let mut indices: Vec<u32> = vec![];
+        let mut indices: Vec<u32> =
+            Vec::with_capacity(models.iter().map(|model| model.mesh.indices.len()).sum());
+        let mut submeshes: Vec<Submesh> = Vec::with_capacity(models.len());
         let mut mesh_index_offset: u32 = 0;
-        for i in 0..models.len() {
-            let mesh = &models[i].mesh;
-            debug!(
-                "building mesh {} with {} triangles and {} indices (faces: {})",
-                i,
-                mesh.positions.len() / 3,
-                mesh.indices.len(),
-                mesh.face_arities.len(),
+        for model in &models {
+            submeshes.push(Submesh {
+                first_index: indices.len() as u32,
+                index_count: model.mesh.indices.len() as u32,
+            });
+            indices.extend(
+                model
+                    .mesh
+                    .indices
+                    .iter()
+                    .map(|i| mesh_index_offset + (*i as u32)),
             );
+            mesh_index_offset += vertex_count(model) as u32;
+        }
 
-            for index in 0..mesh.positions.len() / 3 {
-                flat_vertices.push(mesh.positions[3 * index]);
-                flat_vertices.push(mesh.positions[3 * index + 1]);
-                flat_vertices.push(mesh.positions[3 * index + 2]);
-
-                flat_uvs.push(mesh.texcoords[2 * index]);
-                flat_uvs.push(mesh.texcoords[2 * index + 1]);
+        Ok(BinaryMesh::new(vertices, indices, submeshes))
+    }
 
-                flat_normals.push(mesh.normals[3 * index]);
-                flat_normals.push(mesh.normals[3 * index + 1]);
-                flat_normals.push(mesh.normals[3 * index + 2]);
+    // Parsed OBJs are cached next to the source as <path>.embm, and reused until the
+    // source is modified. Failing to write the cache (e.g. read-only assets) is not an error.
+    pub fn parse_cached(&self) -> Result<BinaryMesh> {
+        // Nowhere to cache to in the browser
+        if cfg!(target_arch = "wasm32") {
+            return self.parse();
+        }
+        let cache_path = format!("{}.{}", self.path, BINARY_MESH_EXTENSION);
+        if is_fresh(&cache_path, &self.path) {
+            match BinaryMesh::read(&cache_path) {
+                Ok(mesh) => {
+                    debug!("using cached binary mesh: {}", cache_path);
+                    return Ok(mesh);
+                }
+                Err(err) => warn!("ignoring mesh cache: {}", err),
             }
+        }
 
-            indices.extend(mesh.indices.iter().map(|i| mesh_index_offset + (*i as u32)));
-            mesh_index_offset += (mesh.positions.len() / 3) as u32;
+        let mesh = self.parse()?;
+        if let Err(err) = mesh.write(&cache_path) {
+            warn!("could not cache mesh at {}: {}", cache_path, err);
         }
+        Ok(mesh)
+    }
+}
 
-        let (vertex_buffer, vertices) = VertexBuffer::from_flat_slices(
-            &self.path,
-            flat_vertices.as_slice(),
-            flat_uvs.as_slice(),
-            flat_normals.as_slice(),
-            &device,
-        );
+impl MeshBuilder for ObjLoader {
+    fn build(&self, device: Arc<wgpu::Device>) -> Mesh {
+        let mesh = self.parse_cached().unwrap();
 
         info!(
             "loaded mesh with {} triangles from {}",
-            indices.len() / 3,
+            mesh.indices.len() / 3,
             self.path.split("/").last().unwrap(),
         );
 
-        Mesh {
-            index_buffer: IndexBuffer::new(&indices, &device),
+        mesh.into_mesh(&self.path, &device)
+    }
+}
+
+// Cache is fresh if it was written after the source was last modified
+fn is_fresh(cache_path: &str, source_path: &str) -> bool {
+    let modified = |path: &str| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match (modified(cache_path), modified(source_path)) {
+        (Some(cache), Some(source)) => cache >= source,
+        _ => false,
+    }
+}
+
+// Binary mesh (.embm), written by ember-assetc and the OBJ cache. Every field is a
+// little endian u32 or f32, so each section is 4-byte aligned and can be used in place:
+//
+// magic "EMBM" | version: u32 | floats per vertex: u32 | vertex count: u32 | index count: u32
+// submesh count: u32 | bounds: min [f32; 3], max [f32; 3]
+// submeshes: [first index: u32, index count: u32] | vertices: [f32] (interleaved Vertex3D)
+// indices: [u32]
+pub const BINARY_MESH_EXTENSION: &str = "embm";
+const BINARY_MESH_MAGIC: &[u8; 4] = b"EMBM";
+const BINARY_MESH_VERSION: u32 = 2;
+const BINARY_MESH_HEADER_SIZE: usize = 48;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MeshBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl MeshBounds {
+    // From the positions at the start of each vertex
+    pub fn from_vertices(vertices: &[f32], floats_per_vertex: usize) -> Self {
+        let mut bounds = MeshBounds {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+        };
+        for vertex in vertices.chunks_exact(floats_per_vertex) {
+            for axis in 0..3 {
+                bounds.min[axis] = bounds.min[axis].min(vertex[axis]);
+                bounds.max[axis] = bounds.max[axis].max(vertex[axis]);
+            }
+        }
+        if vertices.is_empty() {
+            bounds.min = [0.0; 3];
+            bounds.max = [0.0; 3];
+        }
+        bounds
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Submesh {
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+#[derive(Clone)]
+pub struct BinaryMesh {
+    // Interleaved Vertex3D
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+    pub bounds: MeshBounds,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl BinaryMesh {
+    pub fn new(vertices: Vec<f32>, indices: Vec<u32>, submeshes: Vec<Submesh>) -> Self {
+        Self {
+            bounds: MeshBounds::from_vertices(&vertices, VERTEX3D_FLOATS),
+            vertices,
             indices,
+            submeshes,
+        }
+    }
+
+    pub fn into_mesh(self, name: &str, device: &wgpu::Device) -> Mesh {
+        Mesh::shared_3d(
+            name,
+            Arc::new(self.vertices),
+            Arc::new(self.indices),
+            device,
+        )
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let mut bytes = Vec::with_capacity(
+            BINARY_MESH_HEADER_SIZE
+                + 8 * self.submeshes.len()
+                + 4 * (self.vertices.len() + self.indices.len()),
+        );
+        bytes.extend_from_slice(BINARY_MESH_MAGIC);
+        for word in &[
+            BINARY_MESH_VERSION,
+            VERTEX3D_FLOATS as u32,
+            (self.vertices.len() / VERTEX3D_FLOATS) as u32,
+            self.indices.len() as u32,
+            self.submeshes.len() as u32,
+        ] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for v in self.bounds.min.iter().chain(self.bounds.max.iter()) {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for submesh in &self.submeshes {
+            bytes.extend_from_slice(&submesh.first_index.to_le_bytes());
+            bytes.extend_from_slice(&submesh.index_count.to_le_bytes());
+        }
+        for v in &self.vertices {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for i in &self.indices {
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    pub fn read(path: &str) -> Result<Self> {
+        let bytes = assets::read(path)?;
+        Self::from_bytes(&bytes).map_err(|err| anyhow!("{}: {}", path, err))
+    }
+
+    // An owned copy, for meshes kept past their bytes (eg. the registry's ParsedMesh); to
+    // upload straight from the bytes, see BinaryMeshView::to_mesh. Copied in bulk when the
+    // bytes can be viewed in place, and value by value otherwise.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if let Ok(view) = BinaryMeshView::new(bytes) {
+            return Ok(view.to_binary_mesh());
+        }
+        let layout = BinaryMeshLayout::parse(bytes)?;
+        let vertices = bytes[layout.vertices]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        let indices = bytes[layout.indices]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
             vertices,
-            vertex_buffer,
+            indices,
+            bounds: layout.bounds,
+            submeshes: layout.submeshes,
+        })
+    }
+}
+
+// A binary mesh's vertices and indices, borrowed in place from its bytes (eg. a memory
+// mapped .embm) rather than copied
+pub struct BinaryMeshView<'a> {
+    pub vertices: &'a [f32],
+    pub indices: &'a [u32],
+    pub bounds: MeshBounds,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl<'a> BinaryMeshView<'a> {
+    // Fails on big endian targets, and when bytes doesn't start 4-byte aligned
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(anyhow!(
+                "binary meshes can only be viewed on little endian targets"
+            ));
+        }
+        let layout = BinaryMeshLayout::parse(bytes)?;
+        let unaligned = |_| anyhow!("binary mesh is not 4-byte aligned");
+        Ok(Self {
+            vertices: bytemuck::try_cast_slice(&bytes[layout.vertices]).map_err(unaligned)?,
+            indices: bytemuck::try_cast_slice(&bytes[layout.indices]).map_err(unaligned)?,
+            bounds: layout.bounds,
+            submeshes: layout.submeshes,
+        })
+    }
+
+    pub fn to_binary_mesh(&self) -> BinaryMesh {
+        BinaryMesh {
+            vertices: self.vertices.to_vec(),
+            indices: self.indices.to_vec(),
+            bounds: self.bounds,
+            submeshes: self.submeshes.clone(),
+        }
+    }
+
+    // Uploaded from the borrowed slices, without a BinaryMesh in between; only the Mesh's
+    // own CPU copies are made
+    pub fn to_mesh(&self, name: &str, device: &wgpu::Device) -> Mesh {
+        Mesh {
+            vertex_buffer: VertexBuffer::raw(
+                name,
+                self.vertices,
+                (self.vertices.len() / VERTEX3D_FLOATS) as u32,
+                device,
+            ),
+            index_buffer: IndexBuffer::new(self.indices, device),
+            vertices: Arc::new(self.vertices.to_vec()),
+            indices: Arc::new(self.indices.to_vec()),
+            source: None,
+        }
+    }
+}
+
+// Where each section of a binary mesh is, from its header
+struct BinaryMeshLayout {
+    vertices: Range<usize>,
+    indices: Range<usize>,
+    bounds: MeshBounds,
+    submeshes: Vec<Submesh>,
+}
+
+impl BinaryMeshLayout {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < BINARY_MESH_HEADER_SIZE || &bytes[0..4] != BINARY_MESH_MAGIC {
+            return Err(anyhow!("not a binary mesh"));
+        }
+        let word = |i: usize| u32::from_le_bytes(bytes[4 + i * 4..8 + i * 4].try_into().unwrap());
+        let float = |i: usize| f32::from_le_bytes(bytes[4 + i * 4..8 + i * 4].try_into().unwrap());
+
+        if word(0) != BINARY_MESH_VERSION {
+            return Err(anyhow!(
+                "unsupported binary mesh version {}, re-run ember-assetc",
+                word(0)
+            ));
+        }
+        if word(1) as usize != VERTEX3D_FLOATS {
+            return Err(anyhow!("unsupported vertex layout"));
+        }
+
+        let vertex_floats = word(2) as usize * VERTEX3D_FLOATS;
+        let index_count = word(3) as usize;
+        let submesh_count = word(4) as usize;
+        let vertices_start = BINARY_MESH_HEADER_SIZE + 8 * submesh_count;
+        let indices_start = vertices_start + 4 * vertex_floats;
+        if bytes.len() != indices_start + 4 * index_count {
+            return Err(anyhow!("truncated binary mesh"));
+        }
+
+        Ok(Self {
+            vertices: vertices_start..indices_start,
+            indices: indices_start..bytes.len(),
+            bounds: MeshBounds {
+                min: [float(5), float(6), float(7)],
+                max: [float(8), float(9), float(10)],
+            },
+            submeshes: bytes[BINARY_MESH_HEADER_SIZE..vertices_start]
+                .chunks_exact(8)
+                .map(|b| Submesh {
+                    first_index: u32::from_le_bytes(b[0..4].try_into().unwrap()),
+                    index_count: u32::from_le_bytes(b[4..8].try_into().unwrap()),
+                })
+                .collect(),
+        })
+    }
+}
+
+pub struct BinaryMeshLoader {
+    pub id: Uuid,
+    pub path: String,
+}
+
+impl BinaryMeshLoader {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    pub fn arc_dyn(self) -> Arc<dyn MeshBuilder> {
+        Arc::new(self)
+    }
+}
+
+impl MeshBuilder for BinaryMeshLoader {
+    fn build(&self, device: Arc<wgpu::Device>) -> Mesh {
+        debug!("building binary mesh from file: {}", &self.path);
+        let bytes = assets::read(&self.path).unwrap();
+        match BinaryMeshView::new(&bytes) {
+            Ok(view) => view.to_mesh(&self.path, &device),
+            Err(_) => BinaryMesh::from_bytes(&bytes)
+                .map_err(|err| anyhow!("{}: {}", self.path, err))
+                .unwrap()
+                .into_mesh(&self.path, &device),
         }
     }
 }
+
+// Parsed once when the mesh registry is built, then uploaded on every build. The built
+// meshes share its vertices and indices rather than copying them.
+pub struct ParsedMesh {
+    pub name: String,
+    pub vertices: Arc<Vec<f32>>,
+    pub indices: Arc<Vec<u32>>,
+}
+
+impl ParsedMesh {
+    pub fn new(name: String, mesh: BinaryMesh) -> Self {
+        Self {
+            name,
+            vertices: Arc::new(mesh.vertices),
+            indices: Arc::new(mesh.indices),
+        }
+    }
+
+    pub fn arc_dyn(self) -> Arc<dyn MeshBuilder> {
+        Arc::new(self)
+    }
+}
+
+impl MeshBuilder for ParsedMesh {
+    fn build(&self, device: Arc<wgpu::Device>) -> Mesh {
+        Mesh::shared_3d(
+            &self.name,
+            Arc::clone(&self.vertices),
+            Arc::clone(&self.indices),
+            &device,
+        )
+    }
+}
@@ -1,16 +1,23 @@
 use anyhow::{anyhow, Result};
 use iced_winit::winit::window::Window;
+use image::RgbaImage;
 use once_cell::sync::Lazy;
 use raw_window_handle::HasRawWindowHandle;
 use std::sync::{Arc, RwLock};
 
-use crate::constants::{
-    DEFAULT_SCREEN_HEIGHT, DEFAULT_SCREEN_WIDTH, DEFAULT_TEXTURE_BUFFER_FORMAT,
+use crate::{
+    constants::{
+        DEFAULT_SCREEN_HEIGHT, DEFAULT_SCREEN_WIDTH, DEFAULT_TEXTURE_BUFFER_FORMAT,
+        HEADLESS_TEXTURE_FORMAT,
+    },
+    renderer::{buffer::upload::Uploader, systems::capture::FrameReadback},
 };
 
 pub mod buffer;
+pub mod gltf;
 pub mod graph;
 pub mod mesh;
+pub mod shader;
 pub mod systems;
 pub mod uniform;
 
@@ -22,8 +29,15 @@ pub struct GpuState {
     pub device: Arc<wgpu::Device>,
     pub queue: Arc<wgpu::Queue>,
     pub adapter: Arc<wgpu::Adapter>,
+    // Also a resource; see buffer::upload
+    pub uploader: Arc<Uploader>,
 
-    pub surface: wgpu::Surface,
+    // None when headless, in which case the graph renders into `offscreen` instead
+    pub surface: Option<wgpu::Surface>,
+    pub offscreen: Option<OffscreenTarget>,
+    // Drawn into instead of the surface on frames it can't provide; see fallback_view
+    pub fallback: Option<OffscreenTarget>,
+    // Format and size of whichever of the two is in use
     pub surface_config: wgpu::SurfaceConfiguration,
     // pub chain_descriptor: wgpu::SwapChainDescriptor,
     // pub swap_chain: wgpu::SwapChain,
@@ -31,10 +45,43 @@ pub struct GpuState {
 }
 
 pub struct GpuStateBuilder {
-    pub window: Arc<WindowWrapper>,
+    pub window: Option<Arc<WindowWrapper>>,
     pub screen_size: (u32, u32),
     pub instance: Option<wgpu::Instance>,
     pub surface: Option<wgpu::Surface>,
+    pub present_mode: wgpu::PresentMode,
+    pub options: GpuOptions,
+}
+
+// Which GPUs the engine may run on, and what it asks of them; see
+// EngineBuilder::with_gpu_options. Features and limits the engine needs itself (the wireframe
+// polygon modes and BC texture compression where available, 5 bind groups for the forward 3d
+// node) are added on top.
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    // Building fails if the adapter doesn't support all of them
+    pub required_features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    // Configures the surface so frames can be copied out of it (frame captures, the pixel
+    // probe), where its format allows
+    pub copy_surface: bool,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            backends: match cfg!(target_arch = "wasm32") {
+                true => wgpu::Backends::BROWSER_WEBGPU,
+                false => wgpu::Backends::VULKAN | wgpu::Backends::METAL,
+            },
+            power_preference: wgpu::PowerPreference::default(),
+            required_features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            copy_surface: false,
+        }
+    }
 }
 
 pub struct WindowWrapper {
@@ -48,43 +95,79 @@ unsafe impl HasRawWindowHandle for WindowWrapper {
 }
 
 impl GpuStateBuilder {
+    // The instance and surface are created in build, with the options' backends
     pub fn winit(window: Arc<Window>) -> Self {
         let size = window.inner_size();
-
-        // Instance is a handle to the GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::Backends::VULKAN | wgpu::Backends::METAL);
-
-        // Surface is used to create a swap chain
-        let window_wrapper = WindowWrapper { window };
-        let surface = unsafe { instance.create_surface(&window_wrapper) };
-
         Self {
-            window: Arc::new(window_wrapper),
+            window: Some(Arc::new(WindowWrapper { window })),
             screen_size: (size.width, size.height),
-            instance: Some(instance),
-            surface: Some(surface),
+            instance: None,
+            surface: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            options: GpuOptions::default(),
         }
     }
 
+    // No window or surface; frames are rendered into an OffscreenTarget of this size
+    pub fn headless(screen_size: (u32, u32)) -> Self {
+        Self {
+            window: None,
+            screen_size,
+            instance: None,
+            surface: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            options: GpuOptions::default(),
+        }
+    }
+
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_options(mut self, options: GpuOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     // Depends on TextureStore being in resources
-    pub async fn build(self, resources: &mut legion::Resources) -> Result<GpuState> {
-        let surface = self
-            .surface
-            .ok_or_else(|| anyhow!("GpuStateBuilder: must provide a surface"))?;
+    pub async fn build(mut self, resources: &mut legion::Resources) -> Result<GpuState> {
+        // Instance is a handle to the GPU
         let instance = self
             .instance
-            .ok_or_else(|| anyhow!("GpuStateBuilder: must provide an instance"))?;
+            .take()
+            .unwrap_or_else(|| wgpu::Instance::new(self.options.backends));
+
+        // Surface is used to create a swap chain
+        if let (Some(wrapper), None) = (&self.window, &self.surface) {
+            self.surface = Some(unsafe { instance.create_surface(wrapper.as_ref()) });
+        }
 
         // Adapter is used to request a device and queue
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                power_preference: self.options.power_preference,
+                compatible_surface: self.surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
-            .ok_or(anyhow!("GpuStateBuilder: failed to request adapter"))?;
+            .ok_or_else(|| {
+                anyhow!(
+                    "GpuStateBuilder: failed to request adapter (backends: {:?})",
+                    self.options.backends
+                )
+            })?;
+        let info = adapter.get_info();
+        info!("using adapter: {} ({:?})", info.name, info.backend);
+
+        let missing = self.options.required_features - adapter.features();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "GpuStateBuilder: adapter {} does not support required features {:?}",
+                info.name,
+                missing
+            ));
+        }
 
         // Device is an open connection to the GPU
         // Queue is a handle to the GPU's command buffer executor
@@ -92,8 +175,23 @@ impl GpuStateBuilder {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
+                    // For wireframe and point nodes (NodeBuilder::with_polygon_mode), and
+                    // compressed textures (which are decoded where it's missing)
+                    features: self.options.required_features
+                        | (adapter.features()
+                            & (wgpu::Features::POLYGON_MODE_LINE
+                                | wgpu::Features::POLYGON_MODE_POINT
+                                | wgpu::Features::TEXTURE_COMPRESSION_BC)),
+                    // The forward 3d node uses 6 bind groups (texture, entity, camera,
+                    // lighting, shadow maps), 8 with SSAO, more than the default allows
+                    limits: wgpu::Limits {
+                        max_bind_groups: self
+                            .options
+                            .limits
+                            .max_bind_groups
+                            .max(adapter.limits().max_bind_groups.min(8)),
+                        ..self.options.limits.clone()
+                    },
                 },
                 None,
             )
@@ -104,19 +202,60 @@ impl GpuStateBuilder {
 
         resources.insert(Arc::clone(&device));
         resources.insert(Arc::clone(&queue));
+        let uploader = Arc::new(Uploader::new(Arc::clone(&device), Arc::clone(&queue)));
+        resources.insert(Arc::clone(&uploader));
 
         // Swap chain is used to store rendered textures which
         // are synced with the display
 
-        let size = self.window.window.inner_size();
+        let size = match &self.window {
+            Some(wrapper) => {
+                let size = wrapper.window.inner_size();
+                (size.width, size.height)
+            }
+            None => self.screen_size,
+        };
+        let format = match &self.surface {
+            Some(surface) => surface.get_preferred_format(&adapter).unwrap(),
+            None => HEADLESS_TEXTURE_FORMAT,
+        };
+        // Copy source for frame captures and the pixel probe (systems::capture, systems::probe).
+        // The offscreen target is always read back (OffscreenTarget::read).
+        let copy_src = match &self.surface {
+            Some(_) if self.options.copy_surface => {
+                let supported = adapter
+                    .get_texture_format_features(format)
+                    .allowed_usages
+                    .contains(wgpu::TextureUsages::COPY_SRC);
+                if !supported {
+                    warn!(
+                        "surface format {:?} can't be copied from; frame captures and the pixel \
+                         probe are unavailable",
+                        format
+                    );
+                }
+                supported
+            }
+            Some(_) => false,
+            None => true,
+        };
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            usage: match copy_src {
+                true => wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                false => wgpu::TextureUsages::RENDER_ATTACHMENT,
+            },
+            format,
+            width: size.0,
+            height: size.1,
+            present_mode: self.present_mode,
+        };
+        let offscreen = match &self.surface {
+            Some(surface) => {
+                surface.configure(&device, &surface_config);
+                None
+            }
+            None => Some(OffscreenTarget::new(&device, &surface_config)),
         };
-        surface.configure(&device, &surface_config);
 
         // let chain_descriptor = wgpu::SwapChainDescriptor {
         //     usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
@@ -131,9 +270,12 @@ impl GpuStateBuilder {
 
         Ok(GpuState {
             adapter: Arc::new(adapter),
-            surface,
+            surface: self.surface,
+            offscreen,
+            fallback: None,
             device,
             queue,
+            uploader,
             surface_config,
             // chain_descriptor,
             // swap_chain,
@@ -163,7 +305,10 @@ impl GpuState {
 
         self.surface_config.width = new_size.0;
         self.surface_config.height = new_size.1;
-        self.surface.configure(&self.device, &self.surface_config);
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.surface_config),
+            None => self.offscreen = Some(OffscreenTarget::new(&self.device, &self.surface_config)),
+        }
 
         // self.swap_chain = self
         //     .device
@@ -172,34 +317,82 @@ impl GpuState {
         info!("SCREEN_SIZE CHANGED TO: {}, {}", new_size.0, new_size.1);
     }
 
-    pub fn force_new_swap_chain(&mut self) {
-        warn!("running force_new_swap_chain; something might be wrong");
-        // warn!("> chain descriptor: {:?}", self.chain_descriptor);
-
-        let current_size = SCREEN_SIZE.read().unwrap();
-        warn!(
-            "> current screen size: {}, {}",
-            current_size.0, current_size.1
+    // After the surface was outdated or lost; at the current size
+    pub fn reconfigure_surface(&mut self) {
+        debug!(
+            "reconfiguring surface at {}, {}",
+            self.surface_config.width, self.surface_config.height
         );
-        drop(current_size);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
 
-        self.surface.configure(&self.device, &self.surface_config);
-        // self.swap_chain = self
-        //     .device
-        //     .create_swap_chain(&self.surface, &self.chain_descriptor);
+    // A target in the surface's format and size for frames that can't be presented, so the
+    // graph can still draw them (see begin_render_graph)
+    pub fn fallback_view(&mut self) -> Arc<wgpu::TextureView> {
+        let size = (self.surface_config.width, self.surface_config.height);
+        let stale = self.fallback.as_ref().map_or(true, |fallback| {
+            fallback.size != size || fallback.format != self.surface_config.format
+        });
+        if stale {
+            self.fallback = Some(OffscreenTarget::new(&self.device, &self.surface_config));
+        }
+        Arc::clone(&self.fallback.as_ref().unwrap().view)
     }
 
     pub fn device_preferred_format(&mut self) -> wgpu::TextureFormat {
-        let fmt = self
-            .surface
-            .get_preferred_format(&self.adapter)
-            .unwrap_or(DEFAULT_TEXTURE_BUFFER_FORMAT);
+        let fmt = match &self.surface {
+            Some(surface) => surface
+                .get_preferred_format(&self.adapter)
+                .unwrap_or(DEFAULT_TEXTURE_BUFFER_FORMAT),
+            None => self.surface_config.format,
+        };
 
         debug!("device preferred texture format: {:?}", fmt);
         fmt
     }
 }
 
+// Stands in for the surface when headless: the master node renders here, and the frame can
+// be read back with read()
+pub struct OffscreenTarget {
+    pub texture: wgpu::Texture,
+    pub view: Arc<wgpu::TextureView>,
+    pub size: (u32, u32),
+    pub format: wgpu::TextureFormat,
+}
+
+impl OffscreenTarget {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            texture,
+            view: Arc::new(view),
+            size: (config.width, config.height),
+            format: config.format,
+        }
+    }
+
+    // Copies the last frame back from the gpu; waits for it to finish rendering
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<RgbaImage> {
+        FrameReadback::copy(device, queue, &self.texture, self.size, self.format).wait(device)
+    }
+}
+
 // -----------------------------------------------------------
 
 // pub struct RenderPass<N> {
@@ -0,0 +1,329 @@
+use anyhow::{anyhow, Result};
+use naga::{
+    Expression, GlobalVariable, ImageClass, ImageDimension, ScalarKind, StorageAccess,
+    StorageClass, TypeInner,
+};
+use std::collections::HashMap;
+
+use crate::systems::{
+    lighting_2d::MAX_OCCLUDERS_2D,
+    lighting_3d::{MAX_LIGHTS_3D, POINT_SHADOW_NEAR},
+};
+
+// Run over every node's WGSL before it's compiled:
+//
+//   #include "name"       pastes the ShaderRegistry's source for name, once per shader
+//   #define NAME value    replaces the identifier NAME from then on
+//   #ifdef NAME / #ifndef NAME / #else / #endif
+//
+// A #define in a shader is only a default; defines from the registry, and then the node's
+// (NodeBuilder::with_shader_define), take precedence.
+
+// Sources for #include, and defines every shader gets. The engine's includes are always
+// there; games add their own with EngineBuilder::with_shader_include.
+pub struct ShaderRegistry {
+    includes: HashMap<String, String>,
+    defines: Vec<(String, String)>,
+}
+
+impl Default for ShaderRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            includes: HashMap::new(),
+            defines: vec![],
+        };
+        registry.insert(
+            "lighting_3d.wgsl",
+            include_str!("shaders/include/lighting_3d.wgsl"),
+        );
+        registry.insert(
+            "lighting_2d.wgsl",
+            include_str!("shaders/include/lighting_2d.wgsl"),
+        );
+        registry.insert(
+            "sprite_2d.wgsl",
+            include_str!("shaders/include/sprite_2d.wgsl"),
+        );
+        registry.define("MAX_LIGHTS_3D", MAX_LIGHTS_3D);
+        registry.define("POINT_SHADOW_NEAR", format!("{:?}", POINT_SHADOW_NEAR));
+        registry.define("MAX_OCCLUDERS_2D", MAX_OCCLUDERS_2D);
+        registry
+    }
+}
+
+impl ShaderRegistry {
+    pub fn insert(&mut self, name: &str, source: &str) {
+        self.includes.insert(name.to_owned(), source.to_owned());
+    }
+
+    pub fn define<T: ToString>(&mut self, name: &str, value: T) {
+        self.defines.push((name.to_owned(), value.to_string()));
+    }
+
+    pub fn preprocess(
+        &self,
+        source: &str,
+        label: &str,
+        defines: &[(String, String)],
+    ) -> Result<String> {
+        let mut preprocessor = Preprocessor {
+            includes: &self.includes,
+            defines: self.defines.iter().chain(defines).cloned().collect(),
+            included: vec![],
+        };
+        let mut out = String::with_capacity(source.len());
+        preprocessor.run(source, label, &mut out)?;
+        Ok(out)
+    }
+}
+
+struct Preprocessor<'a> {
+    includes: &'a HashMap<String, String>,
+    defines: HashMap<String, String>,
+    included: Vec<String>,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn run(&mut self, source: &str, file: &str, out: &mut String) -> Result<()> {
+        // Whether each enclosing #ifdef's current branch is taken
+        let mut branches: Vec<bool> = vec![];
+        for (i, line) in source.lines().enumerate() {
+            let active = branches.iter().all(|taken| *taken);
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive,
+                None => {
+                    if active {
+                        self.substitute(line, out);
+                        out.push('\n');
+                    }
+                    continue;
+                }
+            };
+
+            let mut parts = directive.splitn(2, char::is_whitespace);
+            let name = parts.next().unwrap_or("");
+            let args = parts.next().unwrap_or("").trim();
+            let error = |message: &str| anyhow!("{}:{}: {}", file, i + 1, message);
+            match name {
+                "ifdef" | "ifndef" => {
+                    branches.push(self.defines.contains_key(args) == (name == "ifdef"))
+                }
+                "else" => {
+                    let taken = branches
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef"))?;
+                    *taken = !*taken;
+                }
+                "endif" => {
+                    branches
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                _ if !active => (),
+                "include" => {
+                    let include = args.trim_matches('"');
+                    if self.included.iter().any(|done| done == include) {
+                        continue;
+                    }
+                    let includes = self.includes;
+                    let source = includes
+                        .get(include)
+                        .ok_or_else(|| error(&format!("no shader include named {}", include)))?;
+                    self.included.push(include.to_owned());
+                    self.run(source, include, out)?;
+                }
+                "define" => {
+                    let mut parts = args.splitn(2, char::is_whitespace);
+                    let define = parts.next().unwrap_or("");
+                    if define.is_empty() {
+                        return Err(error("#define without a name"));
+                    }
+                    let value = parts.next().unwrap_or("").trim().to_owned();
+                    self.defines.entry(define.to_owned()).or_insert(value);
+                }
+                _ => return Err(error(&format!("unknown directive #{}", name))),
+            }
+        }
+
+        match branches.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("{}: #ifdef without #endif", file)),
+        }
+    }
+
+    // Copies line into out, with defined identifiers replaced by their values
+    fn substitute(&self, line: &str, out: &mut String) {
+        let mut word = String::new();
+        for c in line.chars().chain(std::iter::once('\n')) {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+            match self.defines.get(&word) {
+                Some(value) => out.push_str(value),
+                None => out.push_str(&word),
+            }
+            word.clear();
+            if c != '\n' {
+                out.push(c);
+            }
+        }
+    }
+}
+
+// Checks the bindings the shader uses against its node's bind group layouts (groups[i] is
+// group i's), so that a mismatch fails the node's build with the group and binding rather
+// than as a wgpu validation error. Bindings which are declared but never used aren't
+// checked, since wgpu doesn't either.
+pub fn check_bindings(
+    wgsl: &str,
+    label: &str,
+    groups: &[Vec<wgpu::BindGroupLayoutEntry>],
+) -> Result<()> {
+    let module = naga::front::wgsl::parse_str(wgsl)
+        .map_err(|err| anyhow!("{}: {}", label, err.emit_to_string(wgsl)))?;
+
+    // Referenced anywhere in a function counts as used
+    let used: Vec<_> = module
+        .functions
+        .iter()
+        .map(|(_, function)| function)
+        .chain(module.entry_points.iter().map(|entry| &entry.function))
+        .flat_map(|function| function.expressions.iter())
+        .filter_map(|(_, expression)| match *expression {
+            Expression::GlobalVariable(global) => Some(global),
+            _ => None,
+        })
+        .collect();
+
+    for (handle, global) in module.global_variables.iter() {
+        let binding = match &global.binding {
+            Some(binding) if used.contains(&handle) => binding,
+            _ => continue,
+        };
+        let location = format!(
+            "{}: group {}, binding {} ({})",
+            label,
+            binding.group,
+            binding.binding,
+            global.name.as_deref().unwrap_or("unnamed")
+        );
+        let entry = groups
+            .get(binding.group as usize)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is used by the shader, but the node only has {} bind group(s)",
+                    location,
+                    groups.len()
+                )
+            })?
+            .iter()
+            .find(|entry| entry.binding == binding.binding)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is used by the shader, but isn't in the node's bind group",
+                    location
+                )
+            })?;
+
+        let in_shader = describe_global(&module, global);
+        let in_node = describe_entry(&entry.ty);
+        if in_shader != in_node {
+            return Err(anyhow!(
+                "{} is a {} in the shader, but a {} in the node's bind group",
+                location,
+                in_shader,
+                in_node
+            ));
+        }
+    }
+    Ok(())
+}
+
+// eg. "uniform buffer", "2d float texture", "comparison sampler"
+fn describe_global(module: &naga::Module, global: &GlobalVariable) -> String {
+    match global.class {
+        StorageClass::Uniform => return "uniform buffer".to_owned(),
+        StorageClass::Storage { access } if access.contains(StorageAccess::STORE) => {
+            return "read write storage buffer".to_owned()
+        }
+        StorageClass::Storage { .. } => return "read only storage buffer".to_owned(),
+        _ => (),
+    }
+    match module.types[global.ty].inner {
+        TypeInner::Image {
+            dim,
+            arrayed,
+            ref class,
+        } => {
+            let dimension = describe_dimension(match (dim, arrayed) {
+                (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+            });
+            match class {
+                ImageClass::Sampled { kind, .. } => {
+                    let kind = match kind {
+                        ScalarKind::Sint => "int",
+                        ScalarKind::Uint => "uint",
+                        _ => "float",
+                    };
+                    format!("{} {} texture", dimension, kind)
+                }
+                ImageClass::Depth { .. } => format!("{} depth texture", dimension),
+                ImageClass::Storage { .. } => format!("{} storage texture", dimension),
+            }
+        }
+        TypeInner::Sampler { comparison: true } => "comparison sampler".to_owned(),
+        TypeInner::Sampler { comparison: false } => "sampler".to_owned(),
+        _ => "resource of unknown kind".to_owned(),
+    }
+}
+
+fn describe_entry(ty: &wgpu::BindingType) -> String {
+    match *ty {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            ..
+        } => "uniform buffer".to_owned(),
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            ..
+        } => "read only storage buffer".to_owned(),
+        wgpu::BindingType::Buffer { .. } => "read write storage buffer".to_owned(),
+        wgpu::BindingType::Texture {
+            view_dimension,
+            sample_type,
+            ..
+        } => {
+            let kind = match sample_type {
+                wgpu::TextureSampleType::Float { .. } => "float",
+                wgpu::TextureSampleType::Sint => "int",
+                wgpu::TextureSampleType::Uint => "uint",
+                wgpu::TextureSampleType::Depth => "depth",
+            };
+            format!("{} {} texture", describe_dimension(view_dimension), kind)
+        }
+        wgpu::BindingType::StorageTexture { view_dimension, .. } => {
+            format!("{} storage texture", describe_dimension(view_dimension))
+        }
+        wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison) => {
+            "comparison sampler".to_owned()
+        }
+        wgpu::BindingType::Sampler(_) => "sampler".to_owned(),
+    }
+}
+
+fn describe_dimension(dimension: wgpu::TextureViewDimension) -> &'static str {
+    match dimension {
+        wgpu::TextureViewDimension::D1 => "1d",
+        wgpu::TextureViewDimension::D2 => "2d",
+        wgpu::TextureViewDimension::D2Array => "2d array",
+        wgpu::TextureViewDimension::D3 => "3d",
+        wgpu::TextureViewDimension::Cube => "cube",
+        wgpu::TextureViewDimension::CubeArray => "cube array",
+    }
+}
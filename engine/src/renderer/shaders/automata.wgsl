@@ -3,14 +3,17 @@
 // -------------------------------------------------
 
 
-struct QuadUniforms {
-    dimensions: vec2<f32>;
+struct FrameUniforms {
     time: f32;
     delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
 };
 
 [[group(1), binding(0)]]
-var<uniform> quad: QuadUniforms;
+var<uniform> frame_uniforms: FrameUniforms;
 
 // --------------------------------------------------
 // Vertex shader
@@ -113,7 +116,7 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let cell_width: vec2<f32> = vec2<f32>(1.0 / f32(SCREEN_WIDTH), 1.0 / f32(SCREEN_HEIGHT));
 
     // initial condition
-    if (quad.time < 10.0) {
+    if (frame_uniforms.time < 10.0) {
         let r: f32 = random(cell_pos / 123.456);
         if (r > 0.6) {
             return vec4<f32>(1.0, 1.0, 1.0, 1.0);
@@ -0,0 +1,44 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera_uniforms.view_proj * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
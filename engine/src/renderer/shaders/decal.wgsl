@@ -0,0 +1,91 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+// Built in, see renderer::systems::render_3d::decal. The scene, with a fullscreen triangle,
+// and then each decal's box: its texture, wherever the scene's depth lands inside the box.
+
+// DecalUniforms in renderer/systems/render_3d/decal.rs
+struct DecalUniforms {
+    mvp: mat4x4<f32>;
+    inv_mvp: mat4x4<f32>;
+    color: vec4<f32>;
+    // [1 for a decal, 0 for the fullscreen draw, fade, 0]
+    params: vec4<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> decal: DecalUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+    [[builtin(vertex_index)]] index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    if (decal.params.x == 0.0) {
+        // Clockwise, since the node culls front faces
+        let x = select(-1.0, 3.0, index == 2u);
+        let y = select(-1.0, 3.0, index == 1u);
+        out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+        return out;
+    }
+    out.clip_position = decal.mvp * vec4<f32>(in.position, 1.0);
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var node_input_tex: texture_2d<f32>;
+[[group(0), binding(1)]]
+var node_input_smp: sampler;
+
+[[group(1), binding(0)]]
+var decal_texture: texture_2d<f32>;
+[[group(1), binding(1)]]
+var decal_sampler: sampler;
+
+[[group(3), binding(0)]]
+var scene_depth: texture_depth_2d;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let size: vec2<i32> = textureDimensions(scene_depth);
+    let texel: vec2<i32> = clamp(vec2<i32>(in.clip_position.xy), vec2<i32>(0), size - vec2<i32>(1));
+    let uv = in.clip_position.xy / vec2<f32>(size);
+
+    if (decal.params.x == 0.0) {
+        return vec4<f32>(textureSampleLevel(node_input_tex, node_input_smp, uv, 0.0).rgb, 1.0);
+    }
+
+    // Back into the unit cube, where the box is [-0.5, 0.5] on every axis
+    let depth: f32 = textureLoad(scene_depth, texel, 0);
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let local_h = decal.inv_mvp * ndc;
+    let local = local_h.xyz / local_h.w;
+    if (any(abs(local) > vec3<f32>(0.5))) {
+        discard;
+    }
+
+    let fade = max(decal.params.y, 0.0001);
+    let edge = clamp((0.5 - abs(local.y)) / (fade * 0.5), 0.0, 1.0);
+    let texture = textureSampleLevel(decal_texture, decal_sampler, local.xz + vec2<f32>(0.5), 0.0) * decal.color;
+    return vec4<f32>(texture.rgb, texture.a * edge);
+}
@@ -0,0 +1,72 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+// Renders an equirectangular environment into a cubemap, one face per instance; see
+// renderer/buffer/equirect.rs
+
+let PI: f32 = 3.14159265;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] face: u32;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] index: u32,
+    [[builtin(instance_index)]] face: u32,
+) -> VertexOutput {
+    // One triangle covering the face, with uv (0, 0) at its top left
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    out.face = face;
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var equirect_tex: texture_2d<f32>;
+[[group(0), binding(1)]]
+var equirect_smp: sampler;
+
+// Layers in order +x, -x, +y, -y, +z, -z
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let u = uv.x * 2.0 - 1.0;
+    let v = uv.y * 2.0 - 1.0;
+    if (face == 0u) {
+        return vec3<f32>(1.0, -v, -u);
+    }
+    if (face == 1u) {
+        return vec3<f32>(-1.0, -v, u);
+    }
+    if (face == 2u) {
+        return vec3<f32>(u, 1.0, v);
+    }
+    if (face == 3u) {
+        return vec3<f32>(u, -1.0, -v);
+    }
+    if (face == 4u) {
+        return vec3<f32>(u, -v, 1.0);
+    }
+    return vec3<f32>(-u, -v, -1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let dir = normalize(face_direction(in.face, in.uv));
+    // Same mapping as IrradianceCoefficients::from_equirect
+    let uv = vec2<f32>(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+    return vec4<f32>(textureSampleLevel(equirect_tex, equirect_smp, uv, 0.0).rgb, 1.0);
+}
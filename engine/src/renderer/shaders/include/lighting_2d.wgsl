@@ -0,0 +1,99 @@
+// Lighting2DUniforms in systems/lighting_2d.rs, bound as light_uniforms in group
+// LIGHTING_2D_GROUP; #define it before including this.
+struct Occluder2D {
+    // [x, y, half width, half height] for boxes, [x, y, radius, 0] for circles
+    bounds: vec4<f32>;
+    // x: 0 box, 1 circle
+    kind: vec4<u32>;
+};
+
+// MAX_OCCLUDERS_2D is defined by the engine, from systems/lighting_2d.rs
+struct Light2DUniforms {
+    // [x, y, linear, quadratic]
+    light_0: vec4<f32>;
+    light_1: vec4<f32>;
+    light_2: vec4<f32>;
+    light_3: vec4<f32>;
+    light_4: vec4<f32>;
+    // x: ambient
+    global: vec4<f32>;
+    // Per light, x: 0 unshadowed, 1 hard, 2 soft; y: the soft shadow's light radius
+    shadows: array<vec4<f32>, 5>;
+    // x: lights, y: occluders
+    count: vec4<u32>;
+    occluders: array<Occluder2D, MAX_OCCLUDERS_2D>;
+};
+
+[[group(LIGHTING_2D_GROUP), binding(0)]]
+var<uniform> light_uniforms: Light2DUniforms;
+
+fn point_light_2d(pos: vec2<f32>, light: vec4<f32>) -> f32 {
+    if (light.z == 0.0) {
+        return 0.0;
+    }
+    let d: f32 = length(light.xy - pos);
+    let attenuation: f32 = 1.0 / (1.0 + light.z * d + light.w * (d * d));
+    return attenuation;
+}
+
+// Distance to the nearest occluder, negative inside one
+fn occluder_distance_2d(pos: vec2<f32>) -> f32 {
+    var distance: f32 = 1000000.0;
+    for (var i: u32 = 0u; i < light_uniforms.count.y; i = i + 1u) {
+        let occluder = light_uniforms.occluders[i];
+        let d = pos - occluder.bounds.xy;
+        if (occluder.kind.x == 1u) {
+            distance = min(distance, length(d) - occluder.bounds.z);
+        } else {
+            let q = abs(d) - occluder.bounds.zw;
+            distance = min(distance, length(max(q, vec2<f32>(0.0, 0.0))) + min(max(q.x, q.y), 0.0));
+        }
+    }
+    return distance;
+}
+
+let SHADOW_2D_STEPS: i32 = 32;
+
+// How much of a light reaches pos, from 0 (in shadow) to 1, by marching the occluders' distance
+// field towards it. Soft shadows treat the light as a disc of the given radius. Occluders
+// themselves are lit.
+fn shadow_2d(pos: vec2<f32>, light: vec4<f32>, shadow: vec4<f32>) -> f32 {
+    if (shadow.x == 0.0 || light.z == 0.0 || occluder_distance_2d(pos) < 0.0) {
+        return 1.0;
+    }
+    let to_light = light.xy - pos;
+    let light_distance = length(to_light);
+    let dir = to_light / max(light_distance, 0.0001);
+
+    var visibility: f32 = 1.0;
+    var t: f32 = 1.0;
+    for (var i: i32 = 0; i < SHADOW_2D_STEPS; i = i + 1) {
+        if (t >= light_distance) {
+            break;
+        }
+        let h = occluder_distance_2d(pos + dir * t);
+        if (h < 0.5) {
+            return 0.0;
+        }
+        if (shadow.x == 2.0) {
+            // Clearance over the cone from pos to the light's disc
+            visibility = min(visibility, h * light_distance / (max(shadow.y, 0.0001) * t));
+        }
+        t = t + h;
+    }
+    return clamp(visibility, 0.0, 1.0);
+}
+
+// Every light's attenuated (and shadowed) contribution at pos, on top of the ambient. Unlit
+// (1.0) without any Light2Ds.
+fn lighting_2d(pos: vec2<f32>) -> f32 {
+    if (light_uniforms.count.x == 0u) {
+        return 1.0;
+    }
+    return light_uniforms.global.x
+        + point_light_2d(pos, light_uniforms.light_0) * shadow_2d(pos, light_uniforms.light_0, light_uniforms.shadows[0])
+        + point_light_2d(pos, light_uniforms.light_1) * shadow_2d(pos, light_uniforms.light_1, light_uniforms.shadows[1])
+        + point_light_2d(pos, light_uniforms.light_2) * shadow_2d(pos, light_uniforms.light_2, light_uniforms.shadows[2])
+        + point_light_2d(pos, light_uniforms.light_3) * shadow_2d(pos, light_uniforms.light_3, light_uniforms.shadows[3])
+        + point_light_2d(pos, light_uniforms.light_4) * shadow_2d(pos, light_uniforms.light_4, light_uniforms.shadows[4]);
+}
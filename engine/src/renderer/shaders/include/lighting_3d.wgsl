@@ -0,0 +1,25 @@
+// Light3DUniform in systems/lighting_3d.rs
+struct Light3D {
+    // w: 0 directional, 1 point, 2 spot
+    position: vec4<f32>;
+    // w: range
+    direction: vec4<f32>;
+    // a: intensity
+    color: vec4<f32>;
+    // cos of the spot's inner and outer angles
+    cone: vec4<f32>;
+};
+
+// MAX_LIGHTS_3D is defined by the engine, from systems/lighting_3d.rs
+struct Lighting3DUniforms {
+    light_view_proj: mat4x4<f32>;
+    direction: vec4<f32>;
+    color: vec4<f32>;
+    // x: how many lights; y: 1 + the index of the one casting point shadows, or 0
+    count: vec4<u32>;
+    lights: array<Light3D, MAX_LIGHTS_3D>;
+    // One per face of the point shadow map
+    point_shadow_view_proj: array<mat4x4<f32>, 6>;
+    // xyz: the light's position; w: the faces' far plane
+    point_shadow: vec4<f32>;
+};
@@ -0,0 +1,77 @@
+// SpriteMode in systems/render_2d/mod.rs: 0 stretch, 1 nine-slice, 2 tiled
+
+// One axis of a nine-slice, in texels of the uv rect: borders at one world unit per texel, the
+// middle stretched over the rest (or the borders squashed, if they don't fit)
+fn nine_slice_axis(p: f32, size: f32, start: f32, end: f32, texels: f32) -> f32 {
+    let borders = start + end;
+    if (size <= borders) {
+        return p / max(size, 0.0001) * texels;
+    }
+    if (p < start) {
+        return p;
+    }
+    if (p > size - end) {
+        return texels - (size - p);
+    }
+    return start + (p - start) / (size - borders) * (texels - borders);
+}
+
+// Where to sample a sprite's texture, with the gradients to sample it with
+struct SpriteUv {
+    uv: vec2<f32>;
+    ddx: vec2<f32>;
+    ddy: vec2<f32>;
+};
+
+// Maps local, the position on a sprite of the given world size from (0, 0) to (1, 1), into
+// uv_rect ([u, v, width, height]) of a texture of dims texels
+fn sprite_uv(
+    local: vec2<f32>,
+    size: vec2<f32>,
+    uv_rect: vec4<f32>,
+    mode: u32,
+    params: vec4<f32>,
+    dims: vec2<f32>,
+) -> SpriteUv {
+    var coords: vec2<f32> = local;
+    // Before wrapping, for gradients; tiles would otherwise seam where fract jumps
+    var unwrapped: vec2<f32> = local;
+
+    if (mode == 1u) {
+        // params: [left, right, top, bottom] borders in texels
+        let texels = dims * uv_rect.zw;
+        let p = local * size;
+        coords = vec2<f32>(
+            nine_slice_axis(p.x, size.x, params.x, params.y, texels.x),
+            nine_slice_axis(p.y, size.y, params.z, params.w, texels.y),
+        ) / max(texels, vec2<f32>(1.0, 1.0));
+        unwrapped = coords;
+    }
+    if (mode == 2u) {
+        // params: [width, height] of one repeat in world units
+        unwrapped = local * size / max(params.xy, vec2<f32>(0.0001, 0.0001));
+        coords = fract(unwrapped);
+    }
+
+    let grad = unwrapped * uv_rect.zw;
+    var sprite: SpriteUv;
+    sprite.uv = uv_rect.xy + coords * uv_rect.zw;
+    sprite.ddx = dpdx(grad);
+    sprite.ddy = dpdy(grad);
+    return sprite;
+}
+
+// Samples uv_rect of tex over a sprite; see sprite_uv
+fn sample_sprite(
+    tex: texture_2d<f32>,
+    smp: sampler,
+    local: vec2<f32>,
+    size: vec2<f32>,
+    uv_rect: vec4<f32>,
+    mode: u32,
+    params: vec4<f32>,
+) -> vec4<f32> {
+    let dims = vec2<f32>(textureDimensions(tex));
+    let sprite = sprite_uv(local, size, uv_rect, mode, params, dims);
+    return textureSampleGrad(tex, smp, sprite.uv, sprite.ddx, sprite.ddy);
+}
@@ -0,0 +1,63 @@
+// Vertex shader
+
+struct Camera2DUniforms {
+    // [x, y, half width, half height] in world units
+    view: vec4<f32>;
+    // x: world units per physical pixel, y: the window's scale factor
+    pixel: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera_uniforms: Camera2DUniforms;
+
+// One per particle, straight from the storage buffer written by particle_2d_update.wgsl
+struct InstanceInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] velocity: vec2<f32>;
+    [[location(2)]] scale: vec2<f32>;
+    [[location(3)]] age: f32;
+    [[location(4)]] padding: f32;
+    [[location(5)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = instance.color;
+
+    // Dead particles below the live count are pushed outside the clip volume
+    if (instance.age < 0.0) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    // Two triangles covering the unit square ([-1, 1], like the UNIT_SQUARE mesh)
+    var corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let world_space: vec2<f32> = corners[vertex_index] * instance.scale + instance.position;
+    let camera_space: vec2<f32> = (world_space + camera_uniforms.view.xy) / camera_uniforms.view.zw;
+    out.clip_position = vec4<f32>(camera_space, 0.0, 1.0);
+
+    return out;
+}
+
+// Fragment shader
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(in.color.rgb, 1.0);
+}
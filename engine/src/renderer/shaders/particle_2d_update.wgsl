@@ -0,0 +1,176 @@
+// Compute shader; see renderer::systems::render_2d::particles_gpu
+
+struct Particle {
+    position: vec2<f32>;
+    velocity: vec2<f32>;
+    scale: vec2<f32>;
+    // -1 when dead
+    age: f32;
+    padding: f32;
+    color: vec4<f32>;
+};
+
+struct Particles {
+    particles: array<Particle>;
+};
+
+struct Emitter {
+    // [x, y, end_x, end_y] for lines, [x, y, radius_x, radius_y] for arcs, [x, y, width, height]
+    // for rectangles, [x, y, inner, outer] for rings and [x, y, dir_x, dir_y] for points
+    shape: vec4<f32>;
+    // [angle (arcs) or spread (points), zones, next, 0]
+    config: vec4<f32>;
+    // [kind (0 line, 1 arc, 2 rectangle, 3 ring, 4 point), reverse (lines),
+    //  mode (0 random, 1 direction, 2 reversed direction), rate (this frame)]
+    flags: vec4<u32>;
+};
+
+struct Params {
+    lifetime: f32;
+    delta: f32;
+    seed: u32;
+    spawn: u32;
+    num_particles: u32;
+    num_emitters: u32;
+    // [from, to]
+    speed: vec4<f32>;
+    scale: vec4<f32>;
+    color_from: vec4<f32>;
+    color_to: vec4<f32>;
+    emitters: array<Emitter, 8>;
+};
+
+// The first four are read by draw_indirect
+struct DrawArgs {
+    vertex_count: u32;
+    instance_count: atomic<u32>;
+    base_vertex: u32;
+    base_instance: u32;
+    spawned: atomic<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+[[group(0), binding(1)]]
+var<storage, read_write> particles: Particles;
+
+[[group(0), binding(2)]]
+var<storage, read_write> args: DrawArgs;
+
+fn hash(x: u32) -> u32 {
+    let state: u32 = x * 747796405u + 2891336453u;
+    let word: u32 = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(i: u32) -> f32 {
+    return f32(hash(i ^ hash(params.seed))) / 4294967295.0;
+}
+
+// naga has no radians()
+let DEG_TO_RAD: f32 = 0.0174532925;
+
+// Same as EmitterShape::parametric, with r in place of the ring's random radius; returns
+// [x, y, dir_x, dir_y]
+fn parametric(emitter: Emitter, t: f32, r: f32) -> vec4<f32> {
+    let pos: vec2<f32> = emitter.shape.xy;
+    if (emitter.flags.x == 0u) {
+        let d: vec2<f32> = emitter.shape.zw - pos;
+        var dir: vec2<f32> = vec2<f32>(-d.y, d.x);
+        if (emitter.flags.y == 1u) {
+            dir = vec2<f32>(d.y, -d.x);
+        }
+        return vec4<f32>(pos + t * d, normalize(dir));
+    }
+    if (emitter.flags.x == 2u) {
+        let size: vec2<f32> = emitter.shape.zw;
+        let corner: vec2<f32> = pos - size / 2.0;
+        let p: f32 = t * 2.0 * (size.x + size.y);
+        if (p < size.x) {
+            return vec4<f32>(corner + vec2<f32>(p, 0.0), 0.0, -1.0);
+        }
+        if (p < size.x + size.y) {
+            return vec4<f32>(corner + vec2<f32>(size.x, p - size.x), 1.0, 0.0);
+        }
+        if (p < 2.0 * size.x + size.y) {
+            return vec4<f32>(corner + vec2<f32>(size.x - (p - size.x - size.y), size.y), 0.0, 1.0);
+        }
+        return vec4<f32>(corner + vec2<f32>(0.0, size.y - (p - 2.0 * size.x - size.y)), -1.0, 0.0);
+    }
+    if (emitter.flags.x == 3u) {
+        let angle: f32 = t * 360.0 * DEG_TO_RAD;
+        let dir: vec2<f32> = vec2<f32>(cos(angle), sin(angle));
+        return vec4<f32>(pos + dir * mix(emitter.shape.z, emitter.shape.w, r), dir);
+    }
+    if (emitter.flags.x == 4u) {
+        let spread: f32 = (t - 0.5) * emitter.config.x * DEG_TO_RAD;
+        let angle: f32 = atan2(emitter.shape.w, emitter.shape.z) + spread;
+        return vec4<f32>(pos, cos(angle), sin(angle));
+    }
+    let angle: f32 = t * emitter.config.x * DEG_TO_RAD;
+    let dir: vec2<f32> = vec2<f32>(cos(angle), sin(angle));
+    return vec4<f32>(pos + dir * emitter.shape.zw, dir);
+}
+
+// The slot'th particle launched this frame, counting through each emitter's rate in order
+fn emit(i: u32, slot: u32) -> vec4<f32> {
+    var local: u32 = slot;
+    var e: u32 = 0u;
+    loop {
+        if (e + 1u >= params.num_emitters || local < params.emitters[e].flags.w) {
+            break;
+        }
+        local = local - params.emitters[e].flags.w;
+        e = e + 1u;
+    }
+
+    let emitter: Emitter = params.emitters[e];
+    let zones: u32 = u32(emitter.config.y);
+    var t: f32 = random(i);
+    if (emitter.flags.z > 0u && zones > 0u) {
+        let next: u32 = u32(emitter.config.z);
+        var zone: u32 = (next + local) % zones;
+        if (emitter.flags.z == 2u) {
+            zone = (next + zones - local % zones) % zones;
+        }
+        t = f32(zone) / f32(zones);
+    } else if (zones > 0u) {
+        t = f32(u32(t * f32(zones))) / f32(zones);
+    }
+    return parametric(emitter, t, random(i ^ 2654435769u));
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let i: u32 = id.x;
+    if (i >= params.num_particles) {
+        return;
+    }
+
+    var p: Particle = particles.particles[i];
+    if (p.age < 0.0 || p.age > params.lifetime) {
+        p.age = -1.0;
+        let slot: u32 = atomicAdd(&args.spawned, 1u);
+        if (slot < params.spawn && params.num_emitters > 0u) {
+            let launch: vec4<f32> = emit(i, slot);
+            p.position = launch.xy;
+            p.velocity = launch.zw * params.speed.xy;
+            p.scale = params.scale.xy;
+            p.color = params.color_from;
+            p.age = 0.0;
+        }
+    }
+
+    if (p.age >= 0.0) {
+        let t: f32 = p.age / params.lifetime;
+        let speed: vec2<f32> = mix(params.speed.xy, params.speed.zw, t);
+        p.scale = mix(params.scale.xy, params.scale.zw, t);
+        p.color = mix(params.color_from, params.color_to, t);
+        p.position = p.position + p.velocity * speed;
+        p.age = p.age + params.delta;
+        atomicMax(&args.instance_count, i + 1u);
+    }
+
+    particles.particles[i] = p;
+}
@@ -6,7 +6,6 @@ struct RenderPBRUniforms {
     model_mat: mat4x4<f32>;
     normal_mat: mat4x4<f32>;
     color: vec4<f32>;
-    params: vec4<f32>;
 };
 
 struct Camera3DUniforms {
@@ -14,15 +13,7 @@ struct Camera3DUniforms {
     view_proj: mat4x4<f32>;
 };
 
-// 
-// struct Light3DUniforms {
-//     // [x, y, linear, quadratic]
-//     light_0: vec4<f32>;
-//     light_1: vec4<f32>;
-//     light_2: vec4<f32>;
-//     light_3: vec4<f32>;
-//     light_4: vec4<f32>;
-// };
+#include "lighting_3d.wgsl"
 
 [[group(1), binding(0)]]
 var<uniform> render_pbr_uniforms: RenderPBRUniforms;
@@ -30,8 +21,8 @@ var<uniform> render_pbr_uniforms: RenderPBRUniforms;
 [[group(2), binding(0)]]
 var<uniform> camera_uniforms: Camera3DUniforms;
 
-// [[group(3), binding(0)]]
-// var<uniform> light_uniforms: LightUniforms;
+[[group(4), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
 
 // --------------------------------------------------
 // Vertex shader
@@ -77,10 +68,25 @@ fn vs_main(
 // Fragment shader
 // -------------------------------------------------
 
+// MaterialUniforms in sources/materials.rs
+struct MaterialUniforms {
+    base_color: vec4<f32>;
+    // [metallic, roughness, occlusion strength, has normal map]
+    params: vec4<f32>;
+};
+
 [[group(0), binding(0)]]
-var texture0: texture_2d<f32>;
+var albedo_map: texture_2d<f32>;
 [[group(0), binding(1)]]
-var sampler0: sampler;
+var normal_map: texture_2d<f32>;
+[[group(0), binding(2)]]
+var metallic_roughness_map: texture_2d<f32>;
+[[group(0), binding(3)]]
+var occlusion_map: texture_2d<f32>;
+[[group(0), binding(4)]]
+var material_sampler: sampler;
+[[group(0), binding(5)]]
+var<uniform> material_uniforms: MaterialUniforms;
 
 [[group(3), binding(0)]]
 var sky_cube: texture_cube<f32>;
@@ -95,48 +101,23 @@ var sky_sampler_blur: sampler;
 // ----- HIGH-PERFORMANCE IRRADIANCE (IBL) -----
 // Implementation based on http://graphics.stanford.edu/papers/envmap/envmap.pdf
 
-struct SHCoefficients {
-    l00: vec3<f32>;
-    l1m1: vec3<f32>;
-    l10: vec3<f32>;
-    l11: vec3<f32>;
-    l2m2: vec3<f32>;
-    l2m1: vec3<f32>;
-    l20: vec3<f32>;
-    l21: vec3<f32>;
-    l22: vec3<f32>;
+// EnvironmentUniforms in renderer/systems/sky.rs; one matrix per color channel
+struct EnvironmentUniforms {
+    irradiance_r: mat4x4<f32>;
+    irradiance_g: mat4x4<f32>;
+    irradiance_b: mat4x4<f32>;
 };
 
-let SHC: SHCoefficients = SHCoefficients(
-    vec3<f32>(0.4167677, 0.41648358, 0.38331264),
-	vec3<f32>(-0.0043605487, -0.0026134395, -0.0006568894),
-	vec3<f32>(-0.01213964, -0.008434562, 0.023041306),
-	vec3<f32>(0.46987548, 0.4635618, 0.42295167),
-	vec3<f32>(0.015393221, 0.015422308, 0.010281778),
-	vec3<f32>(-0.011692239, -0.014198665, -0.019392435),
-	vec3<f32>(0.27746662, 0.27147454, 0.24605234),
-	vec3<f32>(-0.00097278244, 0.010546771, 0.045822047),
-	vec3<f32>(0.3920225, 0.36590222, 0.32920602)
-);
+[[group(5), binding(0)]]
+var<uniform> environment_uniforms: EnvironmentUniforms;
 
 fn sh_irradiance(nrm: vec3<f32>) -> vec3<f32> {
-    let c = SHC;
-    let c1 = 0.429043;
-	let c2 = 0.511664;
-	let c3 = 0.743125;
-	let c4 = 0.886227;
-	let c5 = 0.247708;
-
-    return c1 * c.l22 * (nrm.x * nrm.x - nrm.y * nrm.y) +
-		c3 * c.l20 * nrm.z * nrm.z +
-		c4 * c.l00 -
-		c5 * c.l20 +
-		2.0 * c1 * c.l2m2 * nrm.x * nrm.y +
-		2.0 * c1 * c.l21  * nrm.x * nrm.z +
-		2.0 * c1 * c.l2m1 * nrm.y * nrm.z +
-		2.0 * c2 * c.l11  * nrm.x +
-		2.0 * c2 * c.l1m1 * nrm.y +
-		2.0 * c2 * c.l10  * nrm.z;
+    let n = vec4<f32>(nrm, 1.0);
+    return vec3<f32>(
+        dot(n, environment_uniforms.irradiance_r * n),
+        dot(n, environment_uniforms.irradiance_g * n),
+        dot(n, environment_uniforms.irradiance_b * n),
+    );
 }
 // ----- HIGH PERFORMANCE BRDF
 // Implementation based on https://www.unrealengine.com/en-US/blog/physically-based-shading-on-mobile
@@ -174,8 +155,22 @@ fn fresnel_term(specular_color: vec3<f32>, vdoth: f32) -> vec3<f32> {
 	return fresnel;
 }
 
-fn ambient_occlusion() -> f32 {
-    return 1.0;
+// Meshes don't carry tangents, so the frame comes from screen space derivatives
+// http://www.thetenthplanet.de/archives/1180
+fn normal_mapped(normal: vec3<f32>, world_pos: vec3<f32>, uvs: vec2<f32>, sample: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(world_pos);
+    let dp2 = dpdy(world_pos);
+    let duv1 = dpdx(uvs);
+    let duv2 = dpdy(uvs);
+
+    let dp2perp = cross(dp2, normal);
+    let dp1perp = cross(normal, dp1);
+    let tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    let bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    let scale = inverseSqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 0.00001));
+
+    let tbn = mat3x3<f32>(tangent * scale, bitangent * scale, normal);
+    return normalize(tbn * (sample * 2.0 - 1.0));
 }
 
 fn clamp0(in: vec3<f32>) -> vec3<f32> {
@@ -190,36 +185,64 @@ fn remap(in: vec3<f32>) -> vec3<f32> {
     return pow(2.0 * in, vec3<f32>(2.2, 2.2, 2.2));
 }
 
-[[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {    
-    var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
-    var sample_final: vec4<f32> = (render_pbr_uniforms.color * (1.0 - render_pbr_uniforms.params.x)) + (render_pbr_uniforms.params.x * sample_texture);
+// Diffuse and specular light from a Light3D, shaded like the directional light in fs_main
+fn light_3d(light: Light3D, frag_pos: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>, diffuse_color: vec3<f32>, specular_color: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let kind = u32(light.position.w);
+    var light_dir: vec3<f32> = -light.direction.xyz;
+    var strength: f32 = light.color.a;
+    if (kind != 0u) {
+        let to_light = light.position.xyz - frag_pos;
+        let dist = length(to_light);
+        light_dir = to_light / max(dist, 0.0001);
+
+        // Inverse square, windowed to reach zero at the range
+        let window = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+        strength = strength * window * window / (dist * dist + 1.0);
+
+        if (kind == 2u) {
+            strength = strength * smoothstep(light.cone.y, light.cone.x, dot(-light_dir, light.direction.xyz));
+        }
+    }
 
-    let light_color = vec3<f32>(0.3);
-    let light_dir = normalize(vec3<f32>(0.7, 0.9, 0.2));
-    let world_light = vec3<f32>(0.001);
+    let radiance = light.color.rgb * strength;
+    let half_vec = normalize(view_dir + light_dir);
+    let vdoth = clampf(dot(view_dir, half_vec));
+    let ndoth = clampf(dot(normal, half_vec));
+    let ndotv = clampf(dot(normal, view_dir));
+    let ndotl = clampf(dot(normal, light_dir));
+
+    let specular = radiance * fresnel_term(specular_color, vdoth) * (distribution_term(roughness, ndoth) * visibility_term(roughness, ndotv, ndotl) * ndotl * 2.0);
+    return diffuse_color * radiance * ndotl + specular;
+}
 
-    let TEMPBASE = vec3<f32>(0.5, 0.5, 0.5);
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {    
+    // MATERIAL
 
-    let base_color = pow(TEMPBASE, vec3<f32>(2.2, 2.2, 2.2));
-    let metal = true;
+    // Sampled up front; textureSample has to be in uniform control flow
+    let albedo = textureSample(albedo_map, material_sampler, in.uvs);
+    let normal_sample = textureSample(normal_map, material_sampler, in.uvs).xyz;
+    let metallic_roughness = textureSample(metallic_roughness_map, material_sampler, in.uvs);
+    let occlusion = textureSample(occlusion_map, material_sampler, in.uvs).r;
 
-    var diffuse_color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
-    if (!metal) {
-        diffuse_color = base_color;
-    }
+    let base_color = (albedo * material_uniforms.base_color * render_pbr_uniforms.color).rgb;
+    let metallic = clampf(metallic_roughness.b * material_uniforms.params.x);
+    let roughness = clampf(metallic_roughness.g * material_uniforms.params.y);
+    let ao = mix(1.0, occlusion, material_uniforms.params.z);
 
-    var specular_color: vec3<f32> = base_color;
-    if (!metal) {
-        specular_color = vec3<f32>(0.02, 0.02, 0.02);
-    }
+    // Metals have no diffuse, and tint their reflections
+    let diffuse_color = base_color * (1.0 - metallic);
+    let specular_color = mix(vec3<f32>(0.04, 0.04, 0.04), base_color, vec3<f32>(metallic));
 
-    let roughness = render_pbr_uniforms.params.y;
+    let light_color = lighting_uniforms.color.rgb;
+    let light_dir = normalize(-lighting_uniforms.direction.xyz);
 
     let roughnessE = roughness * roughness;
 	let roughnessL = max(0.01, roughnessE);
 
-    let normal = normalize(in.world_normal);
+    let vertex_normal = normalize(in.world_normal);
+    let mapped_normal = normal_mapped(vertex_normal, in.world_pos, in.uvs, normal_sample);
+    let normal = select(vertex_normal, mapped_normal, material_uniforms.params.w > 0.5);
     let ray_dir = normalize(normalize(in.world_pos - camera_uniforms.view_pos.xyz));
     let view_dir = -ray_dir;
     let half_vec = normalize(view_dir + light_dir);
@@ -239,7 +262,6 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     env = mix(env, env_refl_irrad, vec3<f32>(clampf((roughnessE - 0.25) / 0.75)));
 
     let irradiance = remap(sh_irradiance(normal));
-    let ao: f32 = ambient_occlusion();
 
     // DIRECTIONAL LIGHT
 
@@ -264,7 +286,10 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     // [(material color via BRDF)(environment irradiance via blur lerping and reflected irradiance) + (light color via fresnel lighting)][ambient occlusion]
     let specular: vec3<f32> = (env_specular_color * env + specular_light) * clampf(pow(ndotv + ao, roughnessE) - 1.0 + ao);
 
-    let color = diffuse + specular;
+    var color: vec3<f32> = diffuse + specular;
+    for (var i: u32 = 0u; i < lighting_uniforms.count.x; i = i + 1u) {
+        color = color + light_3d(lighting_uniforms.lights[i], in.world_pos, normal, view_dir, diffuse_color, specular_color, roughnessL) * ao;
+    }
     let gamma_corrected = pow(color * 0.4, vec3<f32>(1.0 / 2.2));
 
     // let thot = light_distribution * light_visibility;
@@ -0,0 +1,10 @@
+// Splits red and blue away from the center; ABERRATION_OFFSET (pixels at the corners) is
+// set by PostFx::ChromaticAberration
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let shift: vec2<f32> = (uv - vec2<f32>(0.5, 0.5)) * 2.0 * ABERRATION_OFFSET * frame_uniforms.inv_resolution;
+    let center: vec4<f32> = sample_input(uv);
+    let r: f32 = sample_input(uv + shift).r;
+    let b: f32 = sample_input(uv - shift).b;
+    return vec4<f32>(r, center.g, b, center.a);
+}
@@ -0,0 +1,41 @@
+// Built in, see renderer::systems::dof. One direction of the separable blur, with a radius
+// of coc * max_radius around each pixel. The horizontal pass keeps the coc in alpha for the
+// vertical one, which writes the final color (DOF_FINAL).
+
+struct DofUniforms {
+    focal_distance: f32;
+    aperture: f32;
+    max_radius: f32;
+    orthographic: u32;
+    near: f32;
+    far: f32;
+    padding: vec2<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> dof: DofUniforms;
+
+let DOF_TAPS: i32 = 8;
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let center: vec4<f32> = sample_input(uv);
+    let offset: vec2<f32> = vec2<f32>(DOF_DIRECTION_X, DOF_DIRECTION_Y) * frame_uniforms.inv_resolution
+        * center.a * dof.max_radius / f32(DOF_TAPS);
+
+    var color: vec3<f32> = vec3<f32>(0.0);
+    var total: f32 = 0.0;
+    for (var i: i32 = -DOF_TAPS; i <= DOF_TAPS; i = i + 1) {
+        let t: f32 = f32(i) / f32(DOF_TAPS);
+        let tap: vec4<f32> = sample_input(uv + offset * f32(i));
+        // Sharper neighbours count less, so in focus edges don't bleed into the blur
+        let weight: f32 = exp(-2.0 * t * t) * clamp(tap.a / max(center.a, 0.0001), 0.05, 1.0);
+        color = color + tap.rgb * weight;
+        total = total + weight;
+    }
+    color = color / total;
+
+    if (DOF_FINAL == 1u) {
+        return vec4<f32>(color, 1.0);
+    }
+    return vec4<f32>(color, center.a);
+}
@@ -0,0 +1,36 @@
+// Built in, see renderer::systems::dof. First pass: the scene color, with each pixel's
+// circle of confusion (0 in focus, 1 at max_radius) in alpha.
+
+struct DofUniforms {
+    focal_distance: f32;
+    aperture: f32;
+    // In pixels
+    max_radius: f32;
+    // 1 for orthographic cameras, where depth is already linear
+    orthographic: u32;
+    near: f32;
+    far: f32;
+    padding: vec2<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> dof: DofUniforms;
+
+[[group(3), binding(0)]]
+var scene_depth: texture_depth_2d;
+
+fn linear_depth(d: f32) -> f32 {
+    if (dof.orthographic == 1u) {
+        return dof.near + d * (dof.far - dof.near);
+    }
+    return dof.near * dof.far / (dof.far - d * (dof.far - dof.near));
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let size: vec2<i32> = textureDimensions(scene_depth);
+    let texel: vec2<i32> = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - vec2<i32>(1));
+    let z: f32 = linear_depth(textureLoad(scene_depth, texel, 0));
+
+    let coc: f32 = clamp(dof.aperture * abs(z - dof.focal_distance) / max(z, 0.0001), 0.0, 1.0);
+    return vec4<f32>(sample_input(uv).rgb, coc);
+}
@@ -0,0 +1,62 @@
+// Built in, see renderer::systems::fog. Exponential (and height) fog between the camera and
+// each pixel, with a linear ramp from start to end on top.
+
+struct FogUniforms {
+    inv_view_proj: mat4x4<f32>;
+    camera_pos: vec4<f32>;
+    color: vec4<f32>;
+    density: f32;
+    start: f32;
+    end: f32;
+    height: f32;
+    height_falloff: f32;
+    sky: f32;
+    padding: vec2<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> fog: FogUniforms;
+
+[[group(3), binding(0)]]
+var scene_depth: texture_depth_2d;
+
+fn world_position(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = fog.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+// Fog density integrated along the ray, from start to dist
+fn optical_depth(dist: f32, dir_y: f32) -> f32 {
+    let span = max(dist - fog.start, 0.0);
+    if (fog.height_falloff <= 0.0) {
+        return fog.density * span;
+    }
+
+    let start_y = fog.camera_pos.y + dir_y * fog.start;
+    let base = fog.density * exp(-fog.height_falloff * (start_y - fog.height));
+    let k = fog.height_falloff * dir_y * span;
+    if (abs(k) < 0.0001) {
+        return base * span;
+    }
+    return base * span * (1.0 - exp(-k)) / k;
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let size: vec2<i32> = textureDimensions(scene_depth);
+    let texel: vec2<i32> = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - vec2<i32>(1));
+    let depth: f32 = textureLoad(scene_depth, texel, 0);
+
+    let to_pixel = world_position(uv, depth) - fog.camera_pos.xyz;
+    let dist = length(to_pixel);
+    let dir_y = to_pixel.y / max(dist, 0.0001);
+
+    var amount: f32 = 1.0 - exp(-optical_depth(dist, dir_y));
+    amount = max(amount, smoothstep(fog.start, fog.end, dist));
+    if (depth >= 1.0) {
+        amount = amount * fog.sky;
+    }
+
+    let scene = sample_input(uv);
+    return vec4<f32>(mix(scene.rgb, fog.color.rgb, clamp(amount, 0.0, 1.0)), scene.a);
+}
@@ -0,0 +1,41 @@
+// FXAA, the simple (console) variant: blurs along edges found by luma contrast
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let px: vec2<f32> = frame_uniforms.inv_resolution;
+    let center: vec4<f32> = sample_input(uv);
+    let luma_nw: f32 = luma(sample_input(uv + vec2<f32>(-1.0, -1.0) * px).rgb);
+    let luma_ne: f32 = luma(sample_input(uv + vec2<f32>(1.0, -1.0) * px).rgb);
+    let luma_sw: f32 = luma(sample_input(uv + vec2<f32>(-1.0, 1.0) * px).rgb);
+    let luma_se: f32 = luma(sample_input(uv + vec2<f32>(1.0, 1.0) * px).rgb);
+    let luma_m: f32 = luma(center.rgb);
+
+    let luma_min: f32 = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max: f32 = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir: vec2<f32> = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce: f32 = max((luma_nw + luma_ne + luma_sw + luma_se) * (0.25 / 8.0), 1.0 / 128.0);
+    let dir_scale: f32 = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * dir_scale, vec2<f32>(-8.0, -8.0), vec2<f32>(8.0, 8.0)) * px;
+
+    let rgb_a: vec3<f32> = 0.5 * (
+        sample_input(uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        sample_input(uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let rgb_b: vec3<f32> = rgb_a * 0.5 + 0.25 * (
+        sample_input(uv - dir * 0.5).rgb +
+        sample_input(uv + dir * 0.5).rgb
+    );
+
+    let luma_b: f32 = luma(rgb_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4<f32>(rgb_a, center.a);
+    }
+    return vec4<f32>(rgb_b, center.a);
+}
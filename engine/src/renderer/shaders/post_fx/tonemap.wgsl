@@ -0,0 +1,51 @@
+// Built in, see renderer::systems::tonemap. Reads the hdr scene (or the last effect) and
+// writes the swap chain.
+
+struct TonemapUniforms {
+    exposure: f32;
+    // 0 aces, 1 reinhard, 2 clamp
+    operator: u32;
+    // 1 when the surface isn't an sRGB format, so the encoding has to be done here
+    encode_srgb: u32;
+    padding: u32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> tonemap: TonemapUniforms;
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a: f32 = 2.51;
+    let b: f32 = 0.03;
+    let c: f32 = 2.43;
+    let d: f32 = 0.59;
+    let e: f32 = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (vec3<f32>(1.0) + x);
+}
+
+fn linear_to_srgb(x: vec3<f32>) -> vec3<f32> {
+    let low: vec3<f32> = x * 12.92;
+    let high: vec3<f32> = 1.055 * pow(x, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(high, low, x <= vec3<f32>(0.0031308));
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let hdr: vec3<f32> = max(sample_input(uv).rgb * tonemap.exposure, vec3<f32>(0.0));
+
+    var color: vec3<f32> = hdr;
+    if (tonemap.operator == 0u) {
+        color = aces(hdr);
+    } elseif (tonemap.operator == 1u) {
+        color = reinhard(hdr);
+    }
+    color = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    if (tonemap.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
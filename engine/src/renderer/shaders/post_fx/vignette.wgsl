@@ -0,0 +1,8 @@
+// Darkens the edges; VIGNETTE_STRENGTH and VIGNETTE_RADIUS are set by PostFx::Vignette
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let color: vec4<f32> = sample_input(uv);
+    let d: f32 = distance(uv, vec2<f32>(0.5, 0.5));
+    let vignette: f32 = 1.0 - VIGNETTE_STRENGTH * smoothstep(VIGNETTE_RADIUS, 0.75, d);
+    return vec4<f32>(color.rgb * vignette, color.a);
+}
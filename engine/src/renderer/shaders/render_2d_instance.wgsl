@@ -1,28 +1,19 @@
 // Vertex shader
 
+#define LIGHTING_2D_GROUP 2
+#include "lighting_2d.wgsl"
+#include "sprite_2d.wgsl"
 
 struct Camera2DUniforms {
-    // [x, y, width, height]
+    // [x, y, half width, half height] in world units
     view: vec4<f32>;
-};
-
-
-struct Light2DUniforms {
-    // [x, y, linear, quadratic]
-    light_0: vec4<f32>;
-    light_1: vec4<f32>;
-    light_2: vec4<f32>;
-    light_3: vec4<f32>;
-    light_4: vec4<f32>;
-    global: vec4<f32>;
+    // x: world units per physical pixel, y: the window's scale factor
+    pixel: vec4<f32>;
 };
 
 [[group(1), binding(0)]]
 var<uniform> camera_uniforms: Camera2DUniforms;
 
-[[group(2), binding(0)]]
-var<uniform> light_uniforms: Light2DUniforms;
-
 // These two utilities should be moved into
 // their own file when I write the shader linker
 
@@ -33,8 +24,9 @@ fn multiply_vec4_as_mat2(in: vec2<f32>, mat2: vec4<f32>) -> vec2<f32> {
     );
 } 
 
-fn snap2grid(in: vec2<f32>, grid_size: i32) -> vec2<f32> {
-    return vec2<f32>(f32(i32(in.x/f32(grid_size))*grid_size), f32(i32(in.y/f32(grid_size))*grid_size));
+// Snaps to a grid; camera_uniforms.pixel.x for whole physical pixels
+fn snap2grid(in: vec2<f32>, grid_size: f32) -> vec2<f32> {
+    return trunc(in / grid_size) * grid_size;
 }
 
 struct VertexInput {
@@ -48,6 +40,14 @@ struct InstanceInput {
     [[location(6)]] mix: f32;
     [[location(7)]] group_id: u32;
     [[location(8)]] id: u32;
+    [[location(9)]] uv_rect: vec4<f32>;
+    [[location(11)]] sprite_mode: u32;
+    [[location(12)]] sprite_params: vec4<f32>;
+    [[location(14)]] texture_slot: u32;
+    // [pivot x, pivot y, rotation, 0]
+    [[location(15)]] transform: vec4<f32>;
+    // FLIP_2D_X (1) | FLIP_2D_Y (2) in forward_instance.rs
+    [[location(16)]] flip: u32;
 };
 
 struct VertexOutput {
@@ -56,6 +56,11 @@ struct VertexOutput {
     [[location(1)]] world_pos: vec2<f32>;
     [[location(2)]] color: vec4<f32>;
     [[location(3)]] mix: f32;
+    [[location(4)]] size: vec2<f32>;
+    [[location(5)]] uv_rect: vec4<f32>;
+    [[location(6), interpolate(flat)]] sprite_mode: u32;
+    [[location(7)]] sprite_params: vec4<f32>;
+    [[location(8), interpolate(flat)]] texture_slot: u32;
 };
 
 [[stage(vertex)]]
@@ -63,49 +68,102 @@ fn vs_main(
     vertex: VertexInput,
     instance: InstanceInput,
 ) -> VertexOutput {
-    var world_space: vec2<f32> = vertex.position * instance.model.zw + instance.model.xy;
+    // Scaled about the pivot, then rotated around it
+    let local = (vertex.position - instance.transform.xy) * instance.model.zw;
+    let c = cos(instance.transform.z);
+    let s = sin(instance.transform.z);
+    var world_space: vec2<f32> = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c) + instance.model.xy;
     // var snapped: vec2<f32> = vec2<f32>(round(world_space.x), round(world_space.y));
-    var camera_space: vec2<f32> = snap2grid(world_space + camera_uniforms.view.xy, i32(1)) / camera_uniforms.view.zw;
+    var camera_space: vec2<f32> = snap2grid(world_space + camera_uniforms.view.xy, camera_uniforms.pixel.x) / camera_uniforms.view.zw;
 
     var out: VertexOutput;
     out.clip_position = vec4<f32>(camera_space, 0.0, 1.0);
     out.uvs = vertex.uvs;
+    if ((instance.flip & 1u) != 0u) {
+        out.uvs.x = 1.0 - out.uvs.x;
+    }
+    if ((instance.flip & 2u) != 0u) {
+        out.uvs.y = 1.0 - out.uvs.y;
+    }
     out.world_pos = world_space;
     out.color = instance.color;
     out.mix = instance.mix;
+    out.size = instance.model.zw;
+    out.uv_rect = instance.uv_rect;
+    out.sprite_mode = instance.sprite_mode;
+    out.sprite_params = instance.sprite_params;
+    out.texture_slot = instance.texture_slot;
 
     return out;
 }
 
 // Fragment shader
 
+// One batch's textures (TextureType::ImageArray { n: MAX_TEXTURES_2D_BATCH }, see
+// forward_instance::TextureBatches2D) and the first one's sampler. Unused slots repeat the
+// first texture.
 [[group(0), binding(0)]]
 var texture0: texture_2d<f32>;
 [[group(0), binding(1)]]
+var texture1: texture_2d<f32>;
+[[group(0), binding(2)]]
+var texture2: texture_2d<f32>;
+[[group(0), binding(3)]]
+var texture3: texture_2d<f32>;
+[[group(0), binding(4)]]
+var texture4: texture_2d<f32>;
+[[group(0), binding(5)]]
+var texture5: texture_2d<f32>;
+[[group(0), binding(6)]]
+var texture6: texture_2d<f32>;
+[[group(0), binding(7)]]
+var texture7: texture_2d<f32>;
+[[group(0), binding(8)]]
 var sampler0: sampler;
 
-fn point_light_2d(pos: vec2<f32>, light: vec4<f32>) -> f32 {
-    if (light.z == 0.0) {
-        return 0.0;
+fn slot_dimensions(slot: u32) -> vec2<i32> {
+    switch (i32(slot)) {
+        case 1: { return textureDimensions(texture1); }
+        case 2: { return textureDimensions(texture2); }
+        case 3: { return textureDimensions(texture3); }
+        case 4: { return textureDimensions(texture4); }
+        case 5: { return textureDimensions(texture5); }
+        case 6: { return textureDimensions(texture6); }
+        case 7: { return textureDimensions(texture7); }
+        default: { return textureDimensions(texture0); }
+    }
+}
+
+// With explicit gradients, since the slot isn't uniform across a draw
+fn sample_slot(slot: u32, sprite: SpriteUv) -> vec4<f32> {
+    switch (i32(slot)) {
+        case 1: { return textureSampleGrad(texture1, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 2: { return textureSampleGrad(texture2, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 3: { return textureSampleGrad(texture3, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 4: { return textureSampleGrad(texture4, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 5: { return textureSampleGrad(texture5, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 6: { return textureSampleGrad(texture6, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        case 7: { return textureSampleGrad(texture7, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
+        default: { return textureSampleGrad(texture0, sampler0, sprite.uv, sprite.ddx, sprite.ddy); }
     }
-    let d: f32 = length(light.xy - pos);
-    let attenuation: f32 = 1.0 / (1.0 + light.z * d + light.w * (d * d));
-    return attenuation;
 }
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     var world_pos: vec2<f32> = in.world_pos;
     
-    var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
+    let sprite = sprite_uv(
+        in.uvs,
+        in.size,
+        in.uv_rect,
+        in.sprite_mode,
+        in.sprite_params,
+        vec2<f32>(slot_dimensions(in.texture_slot)),
+    );
+    var sample_texture: vec4<f32> = sample_slot(in.texture_slot, sprite);
     var sample_final: vec4<f32> = (in.color * in.mix) + ((1.0 - in.mix) * sample_texture);
 
-    var lighting_0: f32 = point_light_2d(world_pos.xy, light_uniforms.light_0);
-    var lighting_1: f32 = point_light_2d(world_pos.xy, light_uniforms.light_1);
-    var lighting_2: f32 = point_light_2d(world_pos.xy, light_uniforms.light_2);
-    var lighting_3: f32 = point_light_2d(world_pos.xy, light_uniforms.light_3);
-    var lighting_4: f32 = point_light_2d(world_pos.xy, light_uniforms.light_4);
-    var lighting: f32 = 1.0; //light_uniforms.global.x + lighting_0 + lighting_1 + lighting_2 + lighting_3 + lighting_4;
+    var lighting: f32 = lighting_2d(world_pos.xy);
 
     return vec4<f32>(sample_final.rgb * lighting, 1.0);
 }
\ No newline at end of file
@@ -16,15 +16,7 @@ struct Camera3DUniforms {
     view_proj: mat4x4<f32>;
 };
 
-// 
-// struct Light3DUniforms {
-//     // [x, y, linear, quadratic]
-//     light_0: vec4<f32>;
-//     light_1: vec4<f32>;
-//     light_2: vec4<f32>;
-//     light_3: vec4<f32>;
-//     light_4: vec4<f32>;
-// };
+#include "lighting_3d.wgsl"
 
 [[group(1), binding(0)]]
 var<uniform> render_3d_uniforms: Render3DUniforms;
@@ -32,8 +24,8 @@ var<uniform> render_3d_uniforms: Render3DUniforms;
 [[group(2), binding(0)]]
 var<uniform> camera_uniforms: Camera3DUniforms;
 
-// [[group(3), binding(0)]]
-// var<uniform> light_uniforms: LightUniforms;
+[[group(3), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
 
 // --------------------------------------------------
 // Vertex shader
@@ -50,6 +42,7 @@ struct VertexOutput {
     [[location(0)]] uvs: vec2<f32>;
     [[location(1)]] world_pos: vec3<f32>;
     [[location(2)]] world_normal: vec3<f32>;
+    [[location(3)]] light_space_pos: vec4<f32>;
 };
 
 [[stage(vertex)]]
@@ -71,6 +64,7 @@ fn vs_main(
 
     out.world_pos = world_space.xyz;
     out.world_normal = normalize(normal_matrix * in.normal);
+    out.light_space_pos = lighting_uniforms.light_view_proj * world_space;
 
     return out;
 }
@@ -84,6 +78,48 @@ var texture0: texture_2d<f32>;
 [[group(0), binding(1)]]
 var sampler0: sampler;
 
+[[group(4), binding(0)]]
+var shadow_map: texture_depth_2d;
+[[group(4), binding(1)]]
+var shadow_sampler: sampler_comparison;
+
+// 1.0 when lit, 0.0 when fully in shadow
+fn shadow(light_space_pos: vec4<f32>) -> f32 {
+    let ndc = light_space_pos.xyz / light_space_pos.w;
+
+    // Outside the light's box nothing is known, so call it lit
+    if (ndc.x < -1.0 || ndc.x > 1.0 || ndc.y < -1.0 || ndc.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    // The Level variant has no derivatives, so it's fine after the early return
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 1.0 - (ndc.y * 0.5 + 0.5));
+    return textureSampleCompareLevel(shadow_map, shadow_sampler, uv, ndc.z);
+}
+
+[[group(5), binding(0)]]
+var point_shadow_map: texture_depth_cube;
+[[group(5), binding(1)]]
+var point_shadow_sampler: sampler_comparison;
+
+// Same, for the Light3D casting point shadows
+fn point_shadow(frag_pos: vec3<f32>) -> f32 {
+    let to_frag = frag_pos - lighting_uniforms.point_shadow.xyz;
+    let far = lighting_uniforms.point_shadow.w;
+
+    // The cube face's view depth is the distance along its axis, the longest one
+    let axes = abs(to_frag);
+    let dist = max(axes.x, max(axes.y, axes.z));
+    if (dist >= far) {
+        return 1.0;
+    }
+
+    // Into depth the way the face's perspective projection does
+    let near = POINT_SHADOW_NEAR;
+    let depth = far / (far - near) - far * near / ((far - near) * dist);
+    return textureSampleCompareLevel(point_shadow_map, point_shadow_sampler, to_frag, depth);
+}
+
 fn diffuse(light_dir: vec3<f32>, fragment_normal: vec3<f32>) -> f32 {
     return max(dot(normalize(fragment_normal), normalize(light_dir)), 0.0);
 }
@@ -102,14 +138,49 @@ fn directed_diffuse_specular(light_dir: vec3<f32>, light_color: vec3<f32>, frag_
     return light_color * diffuse(-light_dir, frag_normal) + light_color * specular(8.0, -light_dir, view_pos, frag_pos, frag_normal);
 }
 
+// The light reaching a fragment from a Light3D
+fn light_3d(light: Light3D, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    let kind = u32(light.position.w);
+    var light_dir: vec3<f32> = light.direction.xyz;
+    var strength: f32 = light.color.a;
+    if (kind != 0u) {
+        let to_frag = frag_pos - light.position.xyz;
+        let dist = length(to_frag);
+        light_dir = to_frag / max(dist, 0.0001);
+
+        // Inverse square, windowed to reach zero at the range
+        let window = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+        strength = strength * window * window / (dist * dist + 1.0);
+
+        if (kind == 2u) {
+            strength = strength * smoothstep(light.cone.y, light.cone.x, dot(light_dir, light.direction.xyz));
+        }
+    }
+    return directed_diffuse_specular(light_dir, light.color.rgb * strength, frag_normal, frag_pos, view_pos);
+}
+
+// Filled in by render_3d::ssao::lighting_shader, 1.0 without SSAO:
+//
+//   fn ambient_occlusion(frag_coord: vec2<f32>) -> f32
+//
+// <ambient_occlusion>
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {    
     var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
     var sample_final: vec4<f32> = (render_3d_uniforms.color * (1.0 - render_3d_uniforms.mix)) + (render_3d_uniforms.mix * sample_texture);
 
-    let ambient_light = vec3<f32>(0.05, 0.05, 0.05);
-    var light_0: vec3<f32> = directed_diffuse_specular(vec3<f32>(0.0, -0.3, 1.0), vec3<f32>(0.5, 0.5, 0.5), in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
-    let fragment_light = ambient_light + light_0;
+    let ambient_light = vec3<f32>(0.05, 0.05, 0.05) * ambient_occlusion(in.clip_position.xy);
+    var light_0: vec3<f32> = directed_diffuse_specular(lighting_uniforms.direction.xyz, lighting_uniforms.color.rgb, in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+    light_0 = light_0 * shadow(in.light_space_pos);
+    var fragment_light: vec3<f32> = ambient_light + light_0;
+    for (var i: u32 = 0u; i < lighting_uniforms.count.x; i = i + 1u) {
+        var light: vec3<f32> = light_3d(lighting_uniforms.lights[i], in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+        if (i + 1u == lighting_uniforms.count.y) {
+            light = light * point_shadow(in.world_pos);
+        }
+        fragment_light = fragment_light + light;
+    }
     
     return vec4<f32>(sample_final.rgb * fragment_light, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+#include "lighting_3d.wgsl"
+
+[[group(1), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+[[group(2), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
+
+// The foliage node's; see render_3d::foliage
+#ifdef FOLIAGE_WIND
+struct FrameUniforms {
+    time: f32;
+    delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
+};
+
+struct FoliageWindUniforms {
+    direction: vec2<f32>;
+    strength: f32;
+    frequency: f32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> frame_uniforms: FrameUniforms;
+
+[[group(4), binding(0)]]
+var<uniform> wind_uniforms: FoliageWindUniforms;
+#endif
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_x: vec4<f32>;
+    [[location(6)]] model_y: vec4<f32>;
+    [[location(7)]] model_z: vec4<f32>;
+    [[location(8)]] model_w: vec4<f32>;
+    [[location(9)]] color: vec4<f32>;
+    [[location(10)]] mix: f32;
+    [[location(11)]] group_id: u32;
+    [[location(12)]] id: u32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uvs: vec2<f32>;
+    [[location(1)]] world_pos: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
+    [[location(3)]] color: vec4<f32>;
+    [[location(4)]] mix: f32;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_x, instance.model_y, instance.model_z, instance.model_w);
+    var world_space: vec4<f32> = model * vec4<f32>(in.position, 1.0);
+
+#ifdef FOLIAGE_WIND
+    // Sways more the higher up the mesh, out of phase across the field
+    let phase = dot(instance.model_w.xz, vec2<f32>(0.37, 0.61));
+    let sway = sin(frame_uniforms.time * wind_uniforms.frequency + phase) * 0.5 + 0.5;
+    let bend = wind_uniforms.strength * sway * max(in.position.y, 0.0);
+    world_space = world_space + vec4<f32>(wind_uniforms.direction.x * bend, 0.0, wind_uniforms.direction.y * bend, 0.0);
+#endif
+
+    // Uniform scaling only, so the model matrix stands in for the normal matrix
+    let normal_matrix = mat3x3<f32>(model.x.xyz, model.y.xyz, model.z.xyz);
+
+    var out: VertexOutput;
+    out.uvs = in.uvs;
+    out.clip_position = camera_uniforms.view_proj * world_space;
+    out.world_pos = world_space.xyz;
+    out.world_normal = normalize(normal_matrix * in.normal);
+    out.color = instance.color;
+    out.mix = instance.mix;
+
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var texture0: texture_2d<f32>;
+[[group(0), binding(1)]]
+var sampler0: sampler;
+
+fn diffuse(light_dir: vec3<f32>, fragment_normal: vec3<f32>) -> f32 {
+    return max(dot(normalize(fragment_normal), normalize(light_dir)), 0.0);
+}
+
+fn specular(shine: f32, light_dir: vec3<f32>, view_pos: vec3<f32>, frag_pos: vec3<f32>, frag_normal: vec3<f32>) -> f32 {
+    var view_dir: vec3<f32> = normalize(view_pos - frag_pos);
+    let half_dir = normalize(light_dir + view_dir);
+    return pow(max(dot(frag_normal, half_dir), 0.0), shine);
+}
+
+fn directed_diffuse_specular(light_dir: vec3<f32>, light_color: vec3<f32>, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    return light_color * diffuse(-light_dir, frag_normal) + light_color * specular(8.0, -light_dir, view_pos, frag_pos, frag_normal);
+}
+
+// The light reaching a fragment from a Light3D
+fn light_3d(light: Light3D, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    let kind = u32(light.position.w);
+    var light_dir: vec3<f32> = light.direction.xyz;
+    var strength: f32 = light.color.a;
+    if (kind != 0u) {
+        let to_frag = frag_pos - light.position.xyz;
+        let dist = length(to_frag);
+        light_dir = to_frag / max(dist, 0.0001);
+
+        // Inverse square, windowed to reach zero at the range
+        let window = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+        strength = strength * window * window / (dist * dist + 1.0);
+
+        if (kind == 2u) {
+            strength = strength * smoothstep(light.cone.y, light.cone.x, dot(light_dir, light.direction.xyz));
+        }
+    }
+    return directed_diffuse_specular(light_dir, light.color.rgb * strength, frag_normal, frag_pos, view_pos);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
+    var sample_final: vec4<f32> = (in.color * (1.0 - in.mix)) + (in.mix * sample_texture);
+
+    let ambient_light = vec3<f32>(0.05, 0.05, 0.05);
+    let light_0 = directed_diffuse_specular(lighting_uniforms.direction.xyz, lighting_uniforms.color.rgb, in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+    var fragment_light: vec3<f32> = ambient_light + light_0;
+    for (var i: u32 = 0u; i < lighting_uniforms.count.x; i = i + 1u) {
+        fragment_light = fragment_light + light_3d(lighting_uniforms.lights[i], in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+    }
+
+    return vec4<f32>(sample_final.rgb * fragment_light, 1.0);
+}
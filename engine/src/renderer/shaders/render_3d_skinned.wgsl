@@ -0,0 +1,140 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+struct Render3DUniforms {
+    model_mat: mat4x4<f32>;
+    normal_mat: mat4x4<f32>;
+    color: vec4<f32>;
+    mix: f32;
+};
+
+// MAX_JOINTS in systems/animation.rs
+struct BoneUniforms {
+    joints: array<mat4x4<f32>, 64>;
+};
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+#include "lighting_3d.wgsl"
+
+[[group(1), binding(0)]]
+var<uniform> render_3d_uniforms: Render3DUniforms;
+[[group(1), binding(1)]]
+var<uniform> bone_uniforms: BoneUniforms;
+
+[[group(2), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+[[group(3), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] joints: vec4<u32>;
+    [[location(4)]] weights: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uvs: vec2<f32>;
+    [[location(1)]] world_pos: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    let skin = bone_uniforms.joints[in.joints.x] * in.weights.x
+        + bone_uniforms.joints[in.joints.y] * in.weights.y
+        + bone_uniforms.joints[in.joints.z] * in.weights.z
+        + bone_uniforms.joints[in.joints.w] * in.weights.w;
+
+    var world_space: vec4<f32> = render_3d_uniforms.model_mat * skin * vec4<f32>(in.position, 1.0);
+    var camera_space: vec4<f32> = camera_uniforms.view_proj * world_space;
+
+    let normal_matrix = mat3x3<f32>(
+        render_3d_uniforms.normal_mat.x.xyz,
+        render_3d_uniforms.normal_mat.y.xyz,
+        render_3d_uniforms.normal_mat.z.xyz,
+    );
+    let skin_normal = (skin * vec4<f32>(in.normal, 0.0)).xyz;
+
+    var out: VertexOutput;
+    out.uvs = in.uvs;
+    out.clip_position = camera_space;
+
+    out.world_pos = world_space.xyz;
+    out.world_normal = normalize(normal_matrix * skin_normal);
+
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var texture0: texture_2d<f32>;
+[[group(0), binding(1)]]
+var sampler0: sampler;
+
+fn diffuse(light_dir: vec3<f32>, fragment_normal: vec3<f32>) -> f32 {
+    return max(dot(normalize(fragment_normal), normalize(light_dir)), 0.0);
+}
+
+fn specular(shine: f32, light_dir: vec3<f32>, view_pos: vec3<f32>, frag_pos: vec3<f32>, frag_normal: vec3<f32>) -> f32 {
+    var view_dir: vec3<f32> = normalize(view_pos - frag_pos);
+    let half_dir = normalize(light_dir + view_dir);
+    return pow(max(dot(frag_normal, half_dir), 0.0), shine);
+}
+
+fn directed_diffuse_specular(light_dir: vec3<f32>, light_color: vec3<f32>, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    return light_color * diffuse(-light_dir, frag_normal) + light_color * specular(8.0, -light_dir, view_pos, frag_pos, frag_normal);
+}
+
+// The light reaching a fragment from a Light3D
+fn light_3d(light: Light3D, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    let kind = u32(light.position.w);
+    var light_dir: vec3<f32> = light.direction.xyz;
+    var strength: f32 = light.color.a;
+    if (kind != 0u) {
+        let to_frag = frag_pos - light.position.xyz;
+        let dist = length(to_frag);
+        light_dir = to_frag / max(dist, 0.0001);
+
+        // Inverse square, windowed to reach zero at the range
+        let window = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+        strength = strength * window * window / (dist * dist + 1.0);
+
+        if (kind == 2u) {
+            strength = strength * smoothstep(light.cone.y, light.cone.x, dot(light_dir, light.direction.xyz));
+        }
+    }
+    return directed_diffuse_specular(light_dir, light.color.rgb * strength, frag_normal, frag_pos, view_pos);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
+    var sample_final: vec4<f32> = (render_3d_uniforms.color * (1.0 - render_3d_uniforms.mix)) + (render_3d_uniforms.mix * sample_texture);
+
+    let ambient_light = vec3<f32>(0.05, 0.05, 0.05);
+    let light_0 = directed_diffuse_specular(lighting_uniforms.direction.xyz, lighting_uniforms.color.rgb, in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+    var fragment_light: vec3<f32> = ambient_light + light_0;
+    for (var i: u32 = 0u; i < lighting_uniforms.count.x; i = i + 1u) {
+        fragment_light = fragment_light + light_3d(lighting_uniforms.lights[i], in.world_normal, in.world_pos, camera_uniforms.view_pos.xyz);
+    }
+
+    return vec4<f32>(sample_final.rgb * fragment_light, 1.0);
+}
@@ -0,0 +1,159 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+// Built in, see renderer::systems::render_3d::forward_toon. With TOON_OUTLINE defined, the
+// outline pass instead: back faces pushed out along their normals, in the outline color.
+
+struct Render3DUniforms {
+    model_mat: mat4x4<f32>;
+    normal_mat: mat4x4<f32>;
+    color: vec4<f32>;
+    mix: f32;
+};
+
+struct ToonUniforms {
+    outline_color: vec4<f32>;
+    bands: u32;
+    outline_width: f32;
+    specular: f32;
+    rim: f32;
+};
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+#include "lighting_3d.wgsl"
+
+[[group(1), binding(0)]]
+var<uniform> render_3d_uniforms: Render3DUniforms;
+[[group(1), binding(1)]]
+var<uniform> toon_uniforms: ToonUniforms;
+
+[[group(2), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+[[group(3), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uvs: vec2<f32>;
+    [[location(1)]] world_pos: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    let normal_matrix = mat3x3<f32>(
+        render_3d_uniforms.normal_mat.x.xyz,
+        render_3d_uniforms.normal_mat.y.xyz,
+        render_3d_uniforms.normal_mat.z.xyz,
+    );
+    let world_normal = normalize(normal_matrix * in.normal);
+    var world_space: vec4<f32> = render_3d_uniforms.model_mat * vec4<f32>(in.position, 1.0);
+
+#ifdef TOON_OUTLINE
+    // In world units, so the outline keeps its width however the mesh is scaled
+    world_space = world_space + vec4<f32>(world_normal * toon_uniforms.outline_width, 0.0);
+#endif
+
+    var out: VertexOutput;
+    out.uvs = in.uvs;
+    out.clip_position = camera_uniforms.view_proj * world_space;
+    out.world_pos = world_space.xyz;
+    out.world_normal = world_normal;
+
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var texture0: texture_2d<f32>;
+[[group(0), binding(1)]]
+var sampler0: sampler;
+
+// Light in [0, 1] snapped down to one of toon_uniforms.bands levels, the lowest being 0
+fn ramp(light: f32) -> f32 {
+    let bands = f32(max(toon_uniforms.bands, 1u));
+    if (bands <= 1.0) {
+        return 1.0;
+    }
+    return min(floor(clamp(light, 0.0, 1.0) * bands), bands - 1.0) / (bands - 1.0);
+}
+
+// Hard edged highlight, for light arriving along light_dir
+fn toon_specular(light_dir: vec3<f32>, frag_normal: vec3<f32>, view_dir: vec3<f32>) -> f32 {
+    let half_dir = normalize(-light_dir + view_dir);
+    let highlight = pow(max(dot(frag_normal, half_dir), 0.0), 32.0);
+    return toon_uniforms.specular * step(0.5, highlight);
+}
+
+fn toon_light(light_dir: vec3<f32>, light_color: vec3<f32>, frag_normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let diffuse = ramp(max(dot(frag_normal, -light_dir), 0.0));
+    return light_color * (diffuse + diffuse * toon_specular(light_dir, frag_normal, view_dir));
+}
+
+// The light reaching a fragment from a Light3D, banded
+fn light_3d(light: Light3D, frag_normal: vec3<f32>, frag_pos: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let kind = u32(light.position.w);
+    var light_dir: vec3<f32> = light.direction.xyz;
+    var strength: f32 = light.color.a;
+    if (kind != 0u) {
+        let to_frag = frag_pos - light.position.xyz;
+        let dist = length(to_frag);
+        light_dir = to_frag / max(dist, 0.0001);
+
+        // Inverse square, windowed to reach zero at the range
+        let window = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+        strength = strength * window * window / (dist * dist + 1.0);
+
+        if (kind == 2u) {
+            strength = strength * smoothstep(light.cone.y, light.cone.x, dot(light_dir, light.direction.xyz));
+        }
+    }
+    return toon_light(light_dir, light.color.rgb * strength, frag_normal, view_dir);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+#ifdef TOON_OUTLINE
+    return toon_uniforms.outline_color;
+#else
+    var sample_texture: vec4<f32> = textureSample(texture0, sampler0, in.uvs);
+    var sample_final: vec4<f32> = (render_3d_uniforms.color * (1.0 - render_3d_uniforms.mix)) + (render_3d_uniforms.mix * sample_texture);
+
+    let normal = normalize(in.world_normal);
+    let view_dir = normalize(camera_uniforms.view_pos.xyz - in.world_pos);
+
+    let ambient_light = vec3<f32>(0.15, 0.15, 0.15);
+    var fragment_light: vec3<f32> = ambient_light + toon_light(normalize(lighting_uniforms.direction.xyz), lighting_uniforms.color.rgb, normal, view_dir);
+    for (var i: u32 = 0u; i < lighting_uniforms.count.x; i = i + 1u) {
+        fragment_light = fragment_light + light_3d(lighting_uniforms.lights[i], normal, in.world_pos, view_dir);
+    }
+
+    // Lights up the silhouette's lit side
+    let rim = step(0.7, 1.0 - max(dot(view_dir, normal), 0.0)) * toon_uniforms.rim;
+    let lit = ramp(max(dot(normal, -normalize(lighting_uniforms.direction.xyz)), 0.0));
+    fragment_light = fragment_light + lighting_uniforms.color.rgb * rim * lit;
+
+    return vec4<f32>(sample_final.rgb * fragment_light, 1.0);
+#endif
+}
@@ -0,0 +1,35 @@
+// --------------------------------------------------
+// Shadow map (depth only, no fragment stage)
+// --------------------------------------------------
+
+struct Render3DUniforms {
+    model_mat: mat4x4<f32>;
+    normal_mat: mat4x4<f32>;
+    color: vec4<f32>;
+    mix: f32;
+};
+
+struct Lighting3DUniforms {
+    light_view_proj: mat4x4<f32>;
+    direction: vec4<f32>;
+    color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> render_3d_uniforms: Render3DUniforms;
+
+[[group(1), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> [[builtin(position)]] vec4<f32> {
+    return lighting_uniforms.light_view_proj * render_3d_uniforms.model_mat * vec4<f32>(in.position, 1.0);
+}
@@ -0,0 +1,33 @@
+// --------------------------------------------------
+// Point shadow map (depth only, no fragment stage), drawn once per cube face with the face
+// as the instance index
+// --------------------------------------------------
+
+struct Render3DUniforms {
+    model_mat: mat4x4<f32>;
+    normal_mat: mat4x4<f32>;
+    color: vec4<f32>;
+    mix: f32;
+};
+
+#include "lighting_3d.wgsl"
+
+[[group(0), binding(0)]]
+var<uniform> render_3d_uniforms: Render3DUniforms;
+
+[[group(1), binding(0)]]
+var<uniform> lighting_uniforms: Lighting3DUniforms;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+    [[builtin(instance_index)]] face: u32,
+) -> [[builtin(position)]] vec4<f32> {
+    return lighting_uniforms.point_shadow_view_proj[face] * render_3d_uniforms.model_mat * vec4<f32>(in.position, 1.0);
+}
@@ -53,7 +53,8 @@ fn vs_main(
 
     var out: VertexOutput;
     out.uvs = in.uvs;
-    out.clip_position = camera_space;
+    // Always at the far plane, so the sky can be depth tested behind a scene
+    out.clip_position = camera_space.xyww;
 
     out.world_pos = in.position;
     out.world_normal = normalize(normal_matrix * in.normal);
@@ -70,6 +71,25 @@ var sky_cube: texture_cube<f32>;
 [[group(2), binding(1)]]
 var sky_sampler: sampler;
 
+// EnvironmentUniforms in renderer/systems/sky.rs; one matrix per color channel
+struct EnvironmentUniforms {
+    irradiance_r: mat4x4<f32>;
+    irradiance_g: mat4x4<f32>;
+    irradiance_b: mat4x4<f32>;
+};
+
+[[group(3), binding(0)]]
+var<uniform> environment_uniforms: EnvironmentUniforms;
+
+fn sh_irradiance(nrm: vec3<f32>) -> vec3<f32> {
+    let n = vec4<f32>(nrm, 1.0);
+    return vec3<f32>(
+        dot(n, environment_uniforms.irradiance_r * n),
+        dot(n, environment_uniforms.irradiance_g * n),
+        dot(n, environment_uniforms.irradiance_b * n),
+    );
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let sky_pos = normalize(in.world_pos);
@@ -78,10 +98,11 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let hdri_dir = sky_pos;
     let hdri = textureSample(sky_cube, sky_sampler, hdri_dir);
 
+    // Holes in the cubemap get the environment's ambient light instead
     if (hdri.a == 0.0) {
-        return vec4<f32>(0.1, 0.1, 0.4, 1.0);
+        return vec4<f32>(sh_irradiance(sky_pos), 1.0) * render_3d_uniforms.color;
     } else {
-        return hdri;
+        return hdri * render_3d_uniforms.color;
     }
 
     // let sunlight_dir = normalize(vec3<f32>(0.0, -0.3, 1.0));
@@ -0,0 +1,40 @@
+// --------------------------------------------------
+// 4x4 box blur over the ssao target, hiding the kernel rotation
+// --------------------------------------------------
+
+[[group(0), binding(0)]]
+var ssao_tex: texture_2d<f32>;
+[[group(0), binding(1)]]
+var ssao_smp: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// One triangle covering the screen, no vertex buffer
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(vertex_index & 2u), f32((vertex_index << 1u) & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(ssao_tex));
+
+    var sum: f32 = 0.0;
+    for (var x: i32 = -2; x < 2; x = x + 1) {
+        for (var y: i32 = -2; y < 2; y = y + 1) {
+            let offset = vec2<f32>(f32(x) + 0.5, f32(y) + 0.5) * texel;
+            sum = sum + textureSampleLevel(ssao_tex, ssao_smp, in.uv + offset, 0.0).r;
+        }
+    }
+
+    let ao = sum / 16.0;
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
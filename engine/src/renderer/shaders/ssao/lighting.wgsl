@@ -0,0 +1,22 @@
+// Replaces the <ambient_occlusion> line in render_3d.wgsl when SSAO is on
+
+struct SsaoUniforms {
+    kernel: array<vec4<f32>, 16>;
+    // [strength, radius, bias, 0]
+    params: vec4<f32>;
+};
+
+[[group(6), binding(0)]]
+var ao_tex: texture_2d<f32>;
+[[group(6), binding(1)]]
+var ao_smp: sampler;
+
+[[group(7), binding(0)]]
+var<uniform> ssao: SsaoUniforms;
+
+// frag_coord is the fragment's position builtin, in (full resolution) pixels
+fn ambient_occlusion(frag_coord: vec2<f32>) -> f32 {
+    let size = vec2<f32>(textureDimensions(ao_tex)) / SSAO_SCALE;
+    let ao = textureSample(ao_tex, ao_smp, frag_coord / size).r;
+    return mix(1.0, ao, ssao.params.x);
+}
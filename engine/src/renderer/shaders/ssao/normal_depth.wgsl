@@ -0,0 +1,54 @@
+// --------------------------------------------------
+// Normal/depth prepass, read by ssao.wgsl
+// --------------------------------------------------
+
+struct Render3DUniforms {
+    model_mat: mat4x4<f32>;
+    normal_mat: mat4x4<f32>;
+    color: vec4<f32>;
+    mix: f32;
+};
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> render_3d_uniforms: Render3DUniforms;
+
+[[group(1), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    let normal_matrix = mat3x3<f32>(
+        render_3d_uniforms.normal_mat.x.xyz,
+        render_3d_uniforms.normal_mat.y.xyz,
+        render_3d_uniforms.normal_mat.z.xyz,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = camera_uniforms.view_proj * render_3d_uniforms.model_mat * vec4<f32>(in.position, 1.0);
+    out.world_normal = normal_matrix * in.normal;
+    return out;
+}
+
+// [world normal, depth]; the target is cleared to zero, so a zero normal means nothing was drawn
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(normalize(in.world_normal), in.clip_position.z);
+}
@@ -0,0 +1,113 @@
+// --------------------------------------------------
+// Hemisphere ambient occlusion, at SSAO_SCALE resolution
+// --------------------------------------------------
+
+struct Camera3DUniforms {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    clip: vec2<f32>;
+};
+
+// SsaoUniforms in renderer/systems/render_3d/ssao.rs
+struct SsaoUniforms {
+    // Offsets in the unit hemisphere around +z, denser towards the center
+    kernel: array<vec4<f32>, 16>;
+    // [strength, radius, bias, 0]
+    params: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var normal_depth_tex: texture_2d<f32>;
+[[group(0), binding(1)]]
+var normal_depth_smp: sampler;
+
+[[group(1), binding(0)]]
+var<uniform> camera_uniforms: Camera3DUniforms;
+
+[[group(2), binding(0)]]
+var<uniform> ssao: SsaoUniforms;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// One triangle covering the screen, no vertex buffer
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(vertex_index & 2u), f32((vertex_index << 1u) & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn normal_depth(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(normal_depth_tex, normal_depth_smp, uv, 0.0);
+}
+
+fn world_pos(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let pos = camera_uniforms.inv_view_proj * ndc;
+    return pos.xyz / pos.w;
+}
+
+fn hash(x: u32) -> u32 {
+    let state: u32 = x * 747796405u + 2891336453u;
+    let word: u32 = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Per pixel, so the kernel is rotated differently across each blur_system window
+fn random(pixel: vec2<u32>) -> f32 {
+    return f32(hash(pixel.x ^ hash(pixel.y))) / 4294967295.0;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let center = normal_depth(in.uv);
+    if (dot(center.xyz, center.xyz) == 0.0) {
+        return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+
+    let normal = normalize(center.xyz);
+    let pos = world_pos(in.uv, center.w);
+    let view_dist = distance(pos, camera_uniforms.view_pos.xyz);
+    let radius = ssao.params.y;
+    let bias = ssao.params.z;
+
+    // Kernel space to world space, spun around the normal
+    let angle = random(vec2<u32>(in.position.xy)) * 6.2831853;
+    var helper: vec3<f32> = vec3<f32>(cos(angle), sin(angle), 0.0);
+    if (abs(dot(helper, normal)) > 0.99) {
+        helper = vec3<f32>(0.0, cos(angle), sin(angle));
+    }
+    let tangent = normalize(helper - normal * dot(helper, normal));
+    let tbn = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    var occlusion: f32 = 0.0;
+    for (var i: u32 = 0u; i < 16u; i = i + 1u) {
+        let sample_pos = pos + tbn * ssao.kernel[i].xyz * radius;
+        let clip = camera_uniforms.view_proj * vec4<f32>(sample_pos, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 1.0 - (ndc.y * 0.5 + 0.5));
+
+        let occluder = normal_depth(uv);
+        if (dot(occluder.xyz, occluder.xyz) == 0.0) {
+            continue;
+        }
+        let occluder_dist = distance(world_pos(uv, occluder.w), camera_uniforms.view_pos.xyz);
+        let sample_dist = distance(sample_pos, camera_uniforms.view_pos.xyz);
+
+        // Anything much further than the radius in front isn't near enough to occlude
+        let range = smoothstep(0.0, 1.0, radius / max(abs(view_dist - occluder_dist), 0.0001));
+        if (occluder_dist <= sample_dist - bias) {
+            occlusion = occlusion + range;
+        }
+    }
+
+    let ao = 1.0 - occlusion / 16.0;
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
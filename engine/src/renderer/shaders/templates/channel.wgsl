@@ -3,14 +3,17 @@
 // -------------------------------------------------
 
 
-struct QuadUniforms {
-    dimensions: vec2<f32>;
+struct FrameUniforms {
     time: f32;
     delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
 };
 
 [[group(1), binding(0)]]
-var<uniform> quad: QuadUniforms;
+var<uniform> frame_uniforms: FrameUniforms;
 
 // --------------------------------------------------
 // Vertex shader
@@ -0,0 +1,72 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+// One pass of a PostFxChainBuilder chain. The effect replaces the <effect> line below and
+// must define:
+//
+//   fn effect(uv: vec2<f32>) -> vec4<f32>
+//
+// sample_input(uv) reads the previous pass (or the scene), and frame_uniforms holds the
+// resolution and time (see systems/frame.rs).
+
+struct FrameUniforms {
+    time: f32;
+    delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> frame_uniforms: FrameUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] screen_pos: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Convert from quad space to discrete space so quad texture can be sampled in fragment stage.
+    out.screen_pos = vec2<f32>((in.position.x / 2.0) + 0.5, (1.0 - ((in.position.y / 2.0) + 0.5)));
+
+    out.position = vec4<f32>(in.position, 0.0, 1.0);
+    return out;
+}
+
+// --------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[group(0), binding(0)]]
+var node_input_tex: texture_2d<f32>;
+[[group(0), binding(1)]]
+var node_input_smp: sampler;
+
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(node_input_tex, node_input_smp, uv);
+}
+
+// --------------------------------------------------
+// Effect
+// -------------------------------------------------
+
+// <effect>
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return effect(in.screen_pos);
+}
@@ -0,0 +1,75 @@
+// --------------------------------------------------
+// Common
+// -------------------------------------------------
+
+struct Camera2DUniforms {
+    // [x, y, half width, half height] in world units
+    view: vec4<f32>;
+    // x: world units per physical pixel, y: the window's scale factor
+    pixel: vec4<f32>;
+};
+
+struct FrameUniforms {
+    time: f32;
+    delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var atlas: texture_2d<f32>;
+[[group(0), binding(1)]]
+var atlas_sampler: sampler;
+
+[[group(1), binding(0)]]
+var<uniform> camera_uniforms: Camera2DUniforms;
+
+[[group(2), binding(0)]]
+var<uniform> frame_uniforms: FrameUniforms;
+
+// --------------------------------------------------
+// Vertex shader
+// --------------------------------------------------
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] uvs: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+    [[location(3)]] screen: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uvs: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    // Same transform as the rest of the 2D pipeline
+    var world: vec2<f32> = (in.position + camera_uniforms.view.xy) / camera_uniforms.view.zw;
+
+    // Window pixels, from the top left
+    var pixels: vec2<f32> = in.position * frame_uniforms.inv_resolution * 2.0;
+    var screen: vec2<f32> = vec2<f32>(pixels.x - 1.0, 1.0 - pixels.y);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(mix(world, screen, in.screen), 0.0, 1.0);
+    out.uvs = in.uvs;
+    out.color = in.color;
+    return out;
+}
+
+// -------------------------------------------------
+// Fragment shader
+// -------------------------------------------------
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coverage: f32 = textureSample(atlas, atlas_sampler, in.uvs).a;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
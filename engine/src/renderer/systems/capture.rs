@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use iced_winit::winit::event::VirtualKeyCode;
+use image::RgbaImage;
+use instant::SystemTime;
+use std::{
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+use winit_input_helper::WinitInputHelper;
+
+use crate::renderer::{
+    graph::{target::RenderTarget, RenderGraph},
+    GpuState,
+};
+
+// Screenshots: capture_frame_system copies the final frame into a readback buffer right
+// before it's presented, and a background thread maps it and writes the PNG, so only the
+// copy lands on the frame. Requested with Engine::capture_frame, or the hotkey if set.
+#[derive(Clone, Default)]
+pub struct CaptureQueue {
+    inner: Arc<Mutex<CaptureState>>,
+}
+
+#[derive(Default)]
+struct CaptureState {
+    pending: Vec<PathBuf>,
+    hotkey: Option<VirtualKeyCode>,
+}
+
+impl CaptureQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Saves screenshot_<unix millis>.png in the working directory
+    pub fn with_hotkey(self, key: Option<VirtualKeyCode>) -> Self {
+        self.inner.lock().unwrap().hotkey = key;
+        self
+    }
+
+    // Written once the current (or next, between frames) frame is done
+    pub fn request(&self, path: PathBuf) {
+        self.inner.lock().unwrap().pending.push(path);
+    }
+
+    fn take(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.inner.lock().unwrap().pending)
+    }
+
+    fn hotkey(&self) -> Option<VirtualKeyCode> {
+        self.inner.lock().unwrap().hotkey
+    }
+}
+
+// A texture copied into a mappable buffer, rows padded to the copy alignment
+pub struct FrameReadback {
+    buffer: wgpu::Buffer,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    padded_row_bytes: u32,
+}
+
+impl FrameReadback {
+    // Records and submits the copy; 4 byte color formats only
+    pub fn copy(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = (4 * size.0 + align - 1) / align * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame_readback"),
+            size: (padded_row_bytes * size.1) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_readback_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            buffer,
+            size,
+            format,
+            padded_row_bytes,
+        }
+    }
+
+    // Blocks until the copy has finished on the gpu
+    pub fn wait(self, device: &wgpu::Device) -> Result<RgbaImage> {
+        let (width, height) = self.size;
+        let slice = self.buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping)?;
+
+        let row_bytes = (4 * width) as usize;
+        let padded = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+        for row in padded.chunks(self.padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes]);
+        }
+        drop(padded);
+        self.buffer.unmap();
+
+        if let wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb = self.format {
+            for texel in pixels.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow!("frame readback: wrong buffer size"))
+    }
+}
+
+fn hotkey_path() -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+    PathBuf::from(format!("screenshot_{}.png", millis))
+}
+
+#[system]
+pub fn capture_frame(
+    #[resource] captures: &CaptureQueue,
+    #[resource] gpu: &Arc<Mutex<GpuState>>,
+    #[resource] graph: &Arc<RenderGraph>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+) {
+    if let Some(key) = captures.hotkey() {
+        if input.read().unwrap().key_pressed(key) {
+            captures.request(hotkey_path());
+        }
+    }
+    let paths = captures.take();
+    if paths.is_empty() {
+        return;
+    }
+    // Reading back blocks, and saving needs a file system and a thread
+    if cfg!(target_arch = "wasm32") {
+        warn!(
+            "frame capture isn't available on the web, dropping {} capture(s)",
+            paths.len()
+        );
+        return;
+    }
+    debug!("running system capture_frame");
+
+    let gpu = gpu.lock().unwrap();
+    if !gpu
+        .surface_config
+        .usage
+        .contains(wgpu::TextureUsages::COPY_SRC)
+    {
+        warn!(
+            "the surface can't be copied from (see EngineBuilder::with_frame_capture), \
+             dropping {} capture(s)",
+            paths.len()
+        );
+        return;
+    }
+    let size = (gpu.surface_config.width, gpu.surface_config.height);
+    let target = graph.swap_chain_target.lock().unwrap();
+    let texture = match (&*target, &gpu.offscreen) {
+        (
+            RenderTarget::Master {
+                screen_buffer: Some(frame),
+                ..
+            },
+            _,
+        ) => &frame.texture,
+        // Headless
+        (_, Some(offscreen)) => &offscreen.texture,
+        _ => {
+            warn!("no frame to capture, dropping {} capture(s)", paths.len());
+            return;
+        }
+    };
+
+    let readback = FrameReadback::copy(
+        &gpu.device,
+        &gpu.queue,
+        texture,
+        size,
+        gpu.surface_config.format,
+    );
+    let device = Arc::clone(&gpu.device);
+    thread::spawn(move || {
+        let image = match readback.wait(&device) {
+            Ok(image) => image,
+            Err(err) => {
+                warn!("frame capture failed: {}", err);
+                return;
+            }
+        };
+        for path in paths {
+            match image.save(&path) {
+                Ok(()) => info!("saved frame capture to {}", path.display()),
+                Err(err) => warn!("failed to save frame capture {}: {}", path.display(), err),
+            }
+        }
+    });
+}
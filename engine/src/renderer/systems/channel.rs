@@ -1,8 +1,9 @@
-use std::{sync::Arc, time::Instant};
+use instant::Instant;
+use std::sync::Arc;
 
 use crate::{
-    constants::{CAMERA_3D_BIND_GROUP_ID, ID},
-    renderer::{graph::NodeState, systems::quad::Quad},
+    constants::{CAMERA_3D_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID},
+    renderer::{buffer::upload::Uploader, graph::NodeState, systems::quad::Quad},
 };
 
 #[system]
@@ -10,11 +11,12 @@ pub fn render(
     #[state] state: &mut NodeState,
     #[resource] quad: &Quad,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     debug!("running system render_channel (graph node)");
     let start_time = Instant::now();
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
 
     let render_target = state.render_target();
     let render_target_mut = render_target.lock().unwrap();
@@ -30,9 +32,13 @@ pub fn render(
     }
 
     let mut pass = pass_res.unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
-    pass.set_bind_group(1, &quad.uniform_group.bind_group, &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
     pass.set_bind_group(
         2,
         &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
@@ -51,7 +57,7 @@ pub fn render(
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("channel_render pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
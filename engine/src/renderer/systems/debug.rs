@@ -0,0 +1,636 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+use iced_winit::winit::event::VirtualKeyCode;
+use instant::Instant;
+use legion::world::SubWorld;
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex, RwLock},
+};
+use wgpu::util::DeviceExt;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    components::Transform3D,
+    constants::{CAMERA_3D_BIND_GROUP_ID, ID},
+    legion::IntoQuery,
+    renderer::{buffer::upload::Uploader, graph::NodeState},
+    sources::camera::Camera3D,
+};
+
+// World-space debug overlays, drawn as lines by two overlay nodes on top of the master
+// target: one depth tested against the scene (occluded), one always on top.
+//
+// There is no navmesh or terrain in the engine itself; whatever builds them should attach
+// DebugNavMesh / DebugChunkBounds components so they show up here.
+//
+// Anything else can be drawn from any system through the DebugDraw resource, which the
+// debug_draw node draws (depth tested) and empties every frame.
+
+// Overlay cvars; F1-F5 toggle them at runtime
+#[derive(Clone, Debug)]
+pub struct DebugOverlayCVars {
+    pub grid: bool,
+    pub navmesh: bool,
+    pub terrain_chunks: bool,
+    pub light_volumes: bool,
+    pub on_top: bool,
+
+    // Half-extent in cells, and cell size in world units
+    pub grid_extent: u32,
+    pub grid_spacing: f32,
+}
+
+impl Default for DebugOverlayCVars {
+    fn default() -> Self {
+        Self {
+            grid: false,
+            navmesh: false,
+            terrain_chunks: false,
+            light_volumes: false,
+            on_top: false,
+            grid_extent: 32,
+            grid_spacing: 1.0,
+        }
+    }
+}
+
+// Navmesh polygons in world space, drawn as closed outlines
+pub struct DebugNavMesh {
+    pub polygons: Vec<Vec<[f32; 3]>>,
+}
+
+// World-space bounds of a terrain chunk
+pub struct DebugChunkBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+// Light influence, drawn as three circles around the entity's Transform3D position
+pub struct DebugLightVolume {
+    pub radius: f32,
+    pub color: [f32; 3],
+}
+
+#[vertex((0, 28usize))]
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for DebugVertex {}
+unsafe impl bytemuck::Zeroable for DebugVertex {}
+
+const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.5];
+const NAVMESH_COLOR: [f32; 4] = [0.1, 0.9, 0.4, 1.0];
+const CHUNK_COLOR: [f32; 4] = [0.9, 0.7, 0.1, 1.0];
+const LIGHT_VOLUME_SEGMENTS: u32 = 32;
+
+// Resource, inserted with the debug overlays. Shapes last one frame, so systems that want
+// something to stay on screen draw it every frame.
+#[derive(Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugVertex>,
+    text: Vec<DebugText>,
+
+    // Reused across frames, and only recreated when a frame outgrows it
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+}
+
+struct DebugText {
+    position: [f32; 3],
+    content: String,
+    size: f32,
+    color: [f32; 4],
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 4]) {
+        push_line(&mut self.lines, from, to, color);
+    }
+
+    pub fn wire_box(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        push_box(&mut self.lines, min, max, color);
+    }
+
+    pub fn wire_sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        push_sphere(&mut self.lines, center, radius, color);
+    }
+
+    // From origin to origin + direction, with a cross at the end to tell which end is which
+    pub fn ray(&mut self, origin: [f32; 3], direction: [f32; 3], color: [f32; 4]) {
+        let end = [
+            origin[0] + direction[0],
+            origin[1] + direction[1],
+            origin[2] + direction[2],
+        ];
+        push_line(&mut self.lines, origin, end, color);
+
+        let tip = cgmath::Vector3::from(direction).magnitude() * 0.05;
+        for axis in 0..3 {
+            let (mut a, mut b) = (end, end);
+            a[axis] -= tip;
+            b[axis] += tip;
+            push_line(&mut self.lines, a, b, color);
+        }
+    }
+
+    // Uppercase stroked letters, digits and some punctuation, centered on position and
+    // facing the camera; size is the height of a capital in world units
+    pub fn text_3d(&mut self, position: [f32; 3], content: &str, size: f32, color: [f32; 4]) {
+        self.text.push(DebugText {
+            position,
+            content: content.to_owned(),
+            size,
+            color,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.text.clear();
+    }
+}
+
+#[system]
+pub fn render_draw(
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] debug_draw: &Arc<Mutex<DebugDraw>>,
+) {
+    debug!("running system debug_draw (graph node)");
+    let start_time = Instant::now();
+    let mut debug_draw = debug_draw.lock().unwrap();
+    if debug_draw.is_empty() {
+        return;
+    }
+
+    // Text is only laid out now, when the camera has settled for the frame
+    let camera = camera.lock().unwrap();
+    let forward = camera.dir.to_vec().normalize();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    drop(camera);
+
+    let text = std::mem::take(&mut debug_draw.text);
+    for text in text.iter() {
+        push_text(&mut debug_draw.lines, text, right.into(), up.into());
+    }
+
+    let vertex_count = debug_draw.lines.len();
+    if vertex_count > debug_draw.capacity {
+        let capacity = vertex_count.next_power_of_two();
+        debug_draw.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Draw Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<DebugVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+        debug_draw.capacity = capacity;
+    }
+    let vertex_buffer = debug_draw.buffer.as_ref().unwrap();
+    queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&debug_draw.lines));
+
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Debug Draw Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass(&node.name, &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        debug_draw.clear();
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(
+        0,
+        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    pass.draw(0..vertex_count as u32, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    debug_draw.clear();
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+#[system]
+pub fn debug_overlay_input(
+    #[resource] cvars: &Arc<RwLock<DebugOverlayCVars>>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+) {
+    let input = input.read().unwrap();
+    let mut cvars = cvars.write().unwrap();
+
+    if input.key_pressed(VirtualKeyCode::F1) {
+        cvars.grid = !cvars.grid;
+    }
+    if input.key_pressed(VirtualKeyCode::F2) {
+        cvars.navmesh = !cvars.navmesh;
+    }
+    if input.key_pressed(VirtualKeyCode::F3) {
+        cvars.terrain_chunks = !cvars.terrain_chunks;
+    }
+    if input.key_pressed(VirtualKeyCode::F4) {
+        cvars.light_volumes = !cvars.light_volumes;
+    }
+    if input.key_pressed(VirtualKeyCode::F5) {
+        cvars.on_top = !cvars.on_top;
+    }
+}
+
+#[system]
+#[read_component(DebugNavMesh)]
+#[read_component(DebugChunkBounds)]
+#[read_component(DebugLightVolume)]
+#[read_component(Transform3D)]
+pub fn render_occluded(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] cvars: &Arc<RwLock<DebugOverlayCVars>>,
+) {
+    debug!("running system debug_overlay_occluded (graph node)");
+    render(
+        world,
+        state,
+        device,
+        uploader,
+        &cvars.read().unwrap(),
+        false,
+    );
+}
+
+#[system]
+#[read_component(DebugNavMesh)]
+#[read_component(DebugChunkBounds)]
+#[read_component(DebugLightVolume)]
+#[read_component(Transform3D)]
+pub fn render_on_top(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] cvars: &Arc<RwLock<DebugOverlayCVars>>,
+) {
+    debug!("running system debug_overlay_on_top (graph node)");
+    render(world, state, device, uploader, &cvars.read().unwrap(), true);
+}
+
+fn render(
+    world: &mut SubWorld,
+    state: &mut NodeState,
+    device: &wgpu::Device,
+    uploader: &Uploader,
+    cvars: &DebugOverlayCVars,
+    on_top: bool,
+) {
+    let start_time = Instant::now();
+    if cvars.on_top != on_top {
+        return;
+    }
+
+    let vertices = build_lines(world, cvars);
+    if vertices.is_empty() {
+        return;
+    }
+
+    // Rebuilt every frame; overlays are only meant for diagnosis
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Debug Overlay Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Debug Overlay Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass("debug_overlay", &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: debug_overlay");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(
+        0,
+        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    pass.draw(0..vertices.len() as u32, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+fn build_lines(world: &mut SubWorld, cvars: &DebugOverlayCVars) -> Vec<DebugVertex> {
+    let mut lines = vec![];
+
+    if cvars.grid {
+        let extent = cvars.grid_extent as f32 * cvars.grid_spacing;
+        for i in -(cvars.grid_extent as i32)..=cvars.grid_extent as i32 {
+            let offset = i as f32 * cvars.grid_spacing;
+            push_line(
+                &mut lines,
+                [offset, 0.0, -extent],
+                [offset, 0.0, extent],
+                GRID_COLOR,
+            );
+            push_line(
+                &mut lines,
+                [-extent, 0.0, offset],
+                [extent, 0.0, offset],
+                GRID_COLOR,
+            );
+        }
+    }
+
+    if cvars.navmesh {
+        let mut query = <&DebugNavMesh>::query();
+        for navmesh in query.iter(world) {
+            for polygon in &navmesh.polygons {
+                for i in 0..polygon.len() {
+                    let next = (i + 1) % polygon.len();
+                    push_line(&mut lines, polygon[i], polygon[next], NAVMESH_COLOR);
+                }
+            }
+        }
+    }
+
+    if cvars.terrain_chunks {
+        let mut query = <&DebugChunkBounds>::query();
+        for bounds in query.iter(world) {
+            push_box(&mut lines, bounds.min, bounds.max, CHUNK_COLOR);
+        }
+    }
+
+    if cvars.light_volumes {
+        let mut query = <(&DebugLightVolume, &Transform3D)>::query();
+        for (volume, transform) in query.iter(world) {
+            let color = [volume.color[0], volume.color[1], volume.color[2], 1.0];
+            push_sphere(&mut lines, transform.position, volume.radius, color);
+        }
+    }
+
+    lines
+}
+
+pub(crate) fn push_line(
+    lines: &mut Vec<DebugVertex>,
+    from: [f32; 3],
+    to: [f32; 3],
+    color: [f32; 4],
+) {
+    lines.push(DebugVertex {
+        position: from,
+        color,
+    });
+    lines.push(DebugVertex {
+        position: to,
+        color,
+    });
+}
+
+pub(crate) fn push_box(
+    lines: &mut Vec<DebugVertex>,
+    min: [f32; 3],
+    max: [f32; 3],
+    color: [f32; 4],
+) {
+    let corner = |i: usize| {
+        [
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        ]
+    };
+
+    // Connect every pair of corners which differ along exactly one axis
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                push_line(lines, corner(i), corner(i | axis), color);
+            }
+        }
+    }
+}
+
+fn push_sphere(lines: &mut Vec<DebugVertex>, center: [f32; 3], radius: f32, color: [f32; 4]) {
+    let point = |plane: usize, angle: f32| {
+        let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+        match plane {
+            0 => [center[0] + cos, center[1] + sin, center[2]],
+            1 => [center[0] + cos, center[1], center[2] + sin],
+            _ => [center[0], center[1] + cos, center[2] + sin],
+        }
+    };
+
+    for plane in 0..3 {
+        for i in 0..LIGHT_VOLUME_SEGMENTS {
+            let a = 2.0 * PI * i as f32 / LIGHT_VOLUME_SEGMENTS as f32;
+            let b = 2.0 * PI * (i + 1) as f32 / LIGHT_VOLUME_SEGMENTS as f32;
+            push_line(lines, point(plane, a), point(plane, b), color);
+        }
+    }
+}
+
+// Glyphs are 4 units wide and 6 tall, drawn as strokes between grid points: [x0, y0, x1, y1]
+const GLYPH_ADVANCE: f32 = 6.0;
+const GLYPH_LINE_HEIGHT: f32 = 9.0;
+
+fn push_text(lines: &mut Vec<DebugVertex>, text: &DebugText, right: [f32; 3], up: [f32; 3]) {
+    let scale = text.size / 6.0;
+    let rows: Vec<&str> = text.content.lines().collect();
+    let height = (rows.len().max(1) - 1) as f32 * GLYPH_LINE_HEIGHT + 6.0;
+
+    for (row, content) in rows.iter().enumerate() {
+        // Each row is centered, and the block of rows is centered on the position
+        let width = content.chars().count() as f32 * GLYPH_ADVANCE - 2.0;
+        let top = height / 2.0 - row as f32 * GLYPH_LINE_HEIGHT;
+        for (i, character) in content.chars().enumerate() {
+            let left = i as f32 * GLYPH_ADVANCE - width / 2.0;
+            for stroke in glyph(character) {
+                let point = |x: u8, y: u8| {
+                    let (x, y) = ((left + x as f32) * scale, (top - 6.0 + y as f32) * scale);
+                    [
+                        text.position[0] + right[0] * x + up[0] * y,
+                        text.position[1] + right[1] * x + up[1] * y,
+                        text.position[2] + right[2] * x + up[2] * y,
+                    ]
+                };
+                push_line(
+                    lines,
+                    point(stroke[0], stroke[1]),
+                    point(stroke[2], stroke[3]),
+                    text.color,
+                );
+            }
+        }
+    }
+}
+
+fn glyph(character: char) -> &'static [[u8; 4]] {
+    match character.to_ascii_uppercase() {
+        ' ' => &[],
+        '0' => &[
+            [0, 0, 4, 0],
+            [4, 0, 4, 6],
+            [4, 6, 0, 6],
+            [0, 6, 0, 0],
+            [0, 0, 4, 6],
+        ],
+        '1' => &[[2, 0, 2, 6], [2, 6, 1, 5], [1, 0, 3, 0]],
+        '2' => &[
+            [0, 6, 4, 6],
+            [4, 6, 4, 3],
+            [4, 3, 0, 3],
+            [0, 3, 0, 0],
+            [0, 0, 4, 0],
+        ],
+        '3' => &[[0, 6, 4, 6], [4, 6, 4, 0], [4, 0, 0, 0], [1, 3, 4, 3]],
+        '4' => &[[0, 6, 0, 3], [0, 3, 4, 3], [4, 6, 4, 0]],
+        '5' | 'S' => &[
+            [4, 6, 0, 6],
+            [0, 6, 0, 3],
+            [0, 3, 4, 3],
+            [4, 3, 4, 0],
+            [4, 0, 0, 0],
+        ],
+        '6' => &[
+            [4, 6, 0, 6],
+            [0, 6, 0, 0],
+            [0, 0, 4, 0],
+            [4, 0, 4, 3],
+            [4, 3, 0, 3],
+        ],
+        '7' => &[[0, 6, 4, 6], [4, 6, 1, 0]],
+        '8' => &[
+            [0, 0, 4, 0],
+            [4, 0, 4, 6],
+            [4, 6, 0, 6],
+            [0, 6, 0, 0],
+            [0, 3, 4, 3],
+        ],
+        '9' => &[
+            [4, 3, 0, 3],
+            [0, 3, 0, 6],
+            [0, 6, 4, 6],
+            [4, 6, 4, 0],
+            [4, 0, 0, 0],
+        ],
+        'A' => &[
+            [0, 0, 0, 4],
+            [0, 4, 2, 6],
+            [2, 6, 4, 4],
+            [4, 4, 4, 0],
+            [0, 3, 4, 3],
+        ],
+        'B' => &[
+            [0, 0, 0, 6],
+            [0, 6, 3, 6],
+            [3, 6, 4, 5],
+            [4, 5, 4, 4],
+            [4, 4, 3, 3],
+            [0, 3, 3, 3],
+            [3, 3, 4, 2],
+            [4, 2, 4, 1],
+            [4, 1, 3, 0],
+            [3, 0, 0, 0],
+        ],
+        'C' => &[[4, 6, 0, 6], [0, 6, 0, 0], [0, 0, 4, 0]],
+        'D' => &[
+            [0, 0, 0, 6],
+            [0, 6, 2, 6],
+            [2, 6, 4, 4],
+            [4, 4, 4, 2],
+            [4, 2, 2, 0],
+            [2, 0, 0, 0],
+        ],
+        'E' => &[[4, 6, 0, 6], [0, 6, 0, 0], [0, 0, 4, 0], [0, 3, 3, 3]],
+        'F' => &[[4, 6, 0, 6], [0, 6, 0, 0], [0, 3, 3, 3]],
+        'G' => &[
+            [4, 6, 0, 6],
+            [0, 6, 0, 0],
+            [0, 0, 4, 0],
+            [4, 0, 4, 3],
+            [4, 3, 2, 3],
+        ],
+        'H' => &[[0, 0, 0, 6], [4, 0, 4, 6], [0, 3, 4, 3]],
+        'I' => &[[0, 6, 4, 6], [2, 6, 2, 0], [0, 0, 4, 0]],
+        'J' => &[[4, 6, 4, 0], [4, 0, 0, 0], [0, 0, 0, 2]],
+        'K' => &[[0, 0, 0, 6], [0, 3, 4, 6], [0, 3, 4, 0]],
+        'L' => &[[0, 6, 0, 0], [0, 0, 4, 0]],
+        'M' => &[[0, 0, 0, 6], [0, 6, 2, 3], [2, 3, 4, 6], [4, 6, 4, 0]],
+        'N' => &[[0, 0, 0, 6], [0, 6, 4, 0], [4, 0, 4, 6]],
+        'O' => &[[0, 0, 4, 0], [4, 0, 4, 6], [4, 6, 0, 6], [0, 6, 0, 0]],
+        'P' => &[[0, 0, 0, 6], [0, 6, 4, 6], [4, 6, 4, 3], [4, 3, 0, 3]],
+        'Q' => &[
+            [0, 0, 4, 0],
+            [4, 0, 4, 6],
+            [4, 6, 0, 6],
+            [0, 6, 0, 0],
+            [2, 2, 4, 0],
+        ],
+        'R' => &[
+            [0, 0, 0, 6],
+            [0, 6, 4, 6],
+            [4, 6, 4, 3],
+            [4, 3, 0, 3],
+            [1, 3, 4, 0],
+        ],
+        'T' => &[[0, 6, 4, 6], [2, 6, 2, 0]],
+        'U' => &[[0, 6, 0, 0], [0, 0, 4, 0], [4, 0, 4, 6]],
+        'V' => &[[0, 6, 2, 0], [2, 0, 4, 6]],
+        'W' => &[[0, 6, 0, 0], [0, 0, 2, 3], [2, 3, 4, 0], [4, 0, 4, 6]],
+        'X' => &[[0, 0, 4, 6], [0, 6, 4, 0]],
+        'Y' => &[[0, 6, 2, 3], [4, 6, 2, 3], [2, 3, 2, 0]],
+        'Z' => &[[0, 6, 4, 6], [4, 6, 0, 0], [0, 0, 4, 0]],
+        '-' => &[[1, 3, 3, 3]],
+        '+' => &[[1, 3, 3, 3], [2, 2, 2, 4]],
+        '=' => &[[1, 2, 3, 2], [1, 4, 3, 4]],
+        '_' => &[[0, 0, 4, 0]],
+        '.' => &[[2, 0, 2, 1]],
+        ',' => &[[2, 1, 1, 0]],
+        ':' => &[[2, 1, 2, 2], [2, 4, 2, 5]],
+        '/' => &[[0, 0, 4, 6]],
+        '(' => &[[3, 6, 1, 4], [1, 4, 1, 2], [1, 2, 3, 0]],
+        ')' => &[[1, 6, 3, 4], [3, 4, 3, 2], [3, 2, 1, 0]],
+        // And anything else
+        _ => &[
+            [0, 5, 1, 6],
+            [1, 6, 4, 6],
+            [4, 6, 4, 3],
+            [4, 3, 2, 3],
+            [2, 3, 2, 2],
+            [2, 1, 2, 0],
+        ],
+    }
+}
@@ -0,0 +1,204 @@
+use instant::Instant;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    constants::{DOF_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID},
+    renderer::{
+        buffer::{upload::Uploader, VERTEX2D_BUFFER_LAYOUT},
+        graph::{
+            node::{NodeBuilder, ShaderSource},
+            NodeState,
+        },
+        systems::{post_fx, quad::Quad},
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    sources::camera::{Camera3D, Projection},
+};
+
+// Depth of field, as three nodes right after the scene (PostFxChainBuilder::with_depth_of_field):
+//
+//   coc:        scene color, and the circle of confusion from the scene's depth channel in alpha
+//   horizontal: gaussian blur along x, scaled by each pixel's coc
+//   vertical:   the same along y, writing the final color
+//
+// The first two render into unfilterable COC_FORMAT targets, so that alpha isn't blended.
+
+pub const COC_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+// Resource, inserted when the engine has depth of field. Read every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct DofSettings {
+    // Distance from the camera which is in focus, in world units
+    pub focal_distance: f32,
+    // Larger apertures blur more, and narrow the range in focus
+    pub aperture: f32,
+    // Largest blur radius, in pixels
+    pub max_radius: f32,
+}
+
+impl Default for DofSettings {
+    fn default() -> Self {
+        Self {
+            focal_distance: 20.0,
+            aperture: 0.5,
+            max_radius: 8.0,
+        }
+    }
+}
+
+pub struct DofUniformGroup {}
+
+impl UniformGroupType<Self> for DofUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        let settings = DofSettings::default();
+        UniformGroup::<DofUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(DofUniforms {
+                focal_distance: settings.focal_distance,
+                aperture: settings.aperture,
+                max_radius: settings.max_radius,
+                orthographic: 0,
+                clip: [0.01, 10000.0],
+                _padding: [0.0; 2],
+            }))
+            .with_id(ID(DOF_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DofUniforms {
+    pub focal_distance: f32,
+    pub aperture: f32,
+    pub max_radius: f32,
+    pub orthographic: u32,
+    // [near, far], to linearize the depth buffer
+    pub clip: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+#[system]
+pub fn load(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] settings: &DofSettings,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] dof_uniforms: &Arc<Mutex<GenericUniform<DofUniforms>>>,
+    #[resource] dof_uniforms_group: &Arc<Mutex<UniformGroup<DofUniformGroup>>>,
+) {
+    debug!("running system dof_uniform_loader");
+    let projection = camera.lock().unwrap().projection;
+    let mut forms = dof_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.focal_distance = settings.focal_distance;
+    uniforms.aperture = settings.aperture;
+    uniforms.max_radius = settings.max_radius;
+    uniforms.orthographic = matches!(projection, Projection::Orthographic { .. }) as u32;
+    uniforms.clip = projection.clip();
+
+    forms.write_buffer(&queue, dof_uniforms_group.lock().unwrap().default_buffer(0));
+}
+
+// [coc, horizontal, vertical], to be chained in that order; coc reads the scene through
+// node input 0 and its depth buffer through a depth channel
+pub fn build_nodes_dof() -> Vec<NodeBuilder> {
+    let dof_group_builder = Arc::new(Mutex::new(DofUniformGroup::builder()));
+    let blur = |name: &str, x: f32, y: f32, last: bool| {
+        let shader = format!(
+            "let DOF_DIRECTION_X: f32 = {:?};\nlet DOF_DIRECTION_Y: f32 = {:?};\nlet DOF_FINAL: u32 = {}u;\n\n{}",
+            x,
+            y,
+            last as u32,
+            include_str!("../shaders/post_fx/dof_blur.wgsl")
+        );
+        NodeBuilder::new(
+            name.to_owned(),
+            1,
+            1,
+            ShaderSource::WGSL(post_fx::with_template(&shader)),
+        )
+        .with_id(Uuid::new_v4())
+        .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
+        .with_unfilterable_node_input()
+        .with_frame_uniforms()
+        .with_shared_uniform_group(Arc::clone(&dof_group_builder))
+        .with_system(render_system)
+    };
+
+    let coc = NodeBuilder::new(
+        "dof_coc".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(post_fx::with_template(include_str!(
+            "../shaders/post_fx/dof_coc.wgsl"
+        ))),
+    )
+    .with_id(Uuid::new_v4())
+    .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
+    .with_node_input()
+    .with_frame_uniforms()
+    .with_shared_uniform_group(Arc::clone(&dof_group_builder))
+    .with_depth_input()
+    .with_hdr_target(COC_FORMAT)
+    .with_system(render_system);
+
+    vec![
+        coc,
+        blur("dof_horizontal", 1.0, 0.0, false).with_hdr_target(COC_FORMAT),
+        blur("dof_vertical", 0.0, 1.0, true),
+    ]
+}
+
+#[system]
+pub fn render(
+    #[state] state: &mut NodeState,
+    #[resource] quad: &Quad,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_dof (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Depth Of Field Encoder"),
+    });
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(2, &node.binder.uniform_groups[&ID(DOF_BIND_GROUP_ID)], &[]);
+    // Only the coc node has the depth channel
+    if state.inputs.len() > 1 {
+        pass.set_bind_group(3, state.inputs[1].bind_group_ref(), &[]);
+    }
+
+    pass.set_vertex_buffer(0, quad.mesh.vertex_buffer.buffer.0.slice(..));
+    pass.set_index_buffer(
+        quad.mesh.index_buffer.buffer.0.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    pass.draw_indexed(0..quad.mesh.index_buffer.buffer.1, 0, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
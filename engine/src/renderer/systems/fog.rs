@@ -0,0 +1,194 @@
+use cgmath::SquareMatrix;
+use instant::Instant;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    constants::{FOG_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID},
+    renderer::{
+        buffer::{upload::Uploader, VERTEX2D_BUFFER_LAYOUT},
+        graph::{
+            node::{NodeBuilder, ShaderSource},
+            NodeState,
+        },
+        systems::{post_fx, quad::Quad},
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    sources::camera::Camera3D,
+    systems::camera_3d::matrix2array_4d,
+};
+
+// Fog, as one node right after the scene (PostFxChainBuilder::with_fog), ahead of depth of
+// field. It rebuilds each pixel's world position from the scene's depth channel, and fades
+// it towards the fog color by how much fog lies between it and the camera.
+
+// Resource, inserted when the engine has fog. Read every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct FogSettings {
+    pub color: [f32; 3],
+    // Exponential fog, per world unit; 0 leaves just the linear fog from start to end
+    pub density: f32,
+    // Distances from the camera. Nothing nearer than start is fogged, and everything past
+    // end is fully fogged.
+    pub start: f32,
+    pub end: f32,
+    // Height fog: density falls off exponentially above height, this much per world unit.
+    // 0 fogs every height the same.
+    pub height: f32,
+    pub height_falloff: f32,
+    // How much of the fog covers the far plane (and the sky), in [0, 1]
+    pub sky: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            color: [0.6, 0.65, 0.7],
+            density: 0.02,
+            start: 0.0,
+            end: 10000.0,
+            height: 0.0,
+            height_falloff: 0.0,
+            sky: 1.0,
+        }
+    }
+}
+
+pub struct FogUniformGroup {}
+
+impl UniformGroupType<Self> for FogUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        let settings = FogSettings::default();
+        UniformGroup::<FogUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(FogUniforms {
+                inv_view_proj: matrix2array_4d(cgmath::Matrix4::identity()),
+                camera_pos: [0.0; 4],
+                color: [settings.color[0], settings.color[1], settings.color[2], 1.0],
+                density: settings.density,
+                start: settings.start,
+                end: settings.end,
+                height: settings.height,
+                height_falloff: settings.height_falloff,
+                sky: settings.sky,
+                _padding: [0.0; 2],
+            }))
+            .with_id(ID(FOG_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniforms {
+    // Screen space back to world space
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub camera_pos: [f32; 4],
+    pub color: [f32; 4],
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+    pub height: f32,
+    pub height_falloff: f32,
+    pub sky: f32,
+    pub _padding: [f32; 2],
+}
+
+#[system]
+pub fn load(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] settings: &FogSettings,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] fog_uniforms: &Arc<Mutex<GenericUniform<FogUniforms>>>,
+    #[resource] fog_uniforms_group: &Arc<Mutex<UniformGroup<FogUniformGroup>>>,
+) {
+    debug!("running system fog_uniform_loader");
+    let camera = camera.lock().unwrap();
+    let inv_view_proj = camera
+        .build_view_proj()
+        .invert()
+        .unwrap_or_else(cgmath::Matrix4::identity);
+
+    let mut forms = fog_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.inv_view_proj = matrix2array_4d(inv_view_proj);
+    uniforms.camera_pos = [camera.pos.x, camera.pos.y, camera.pos.z, 1.0];
+    uniforms.color = [settings.color[0], settings.color[1], settings.color[2], 1.0];
+    uniforms.density = settings.density;
+    uniforms.start = settings.start;
+    uniforms.end = settings.end.max(settings.start + 0.0001);
+    uniforms.height = settings.height;
+    uniforms.height_falloff = settings.height_falloff;
+    uniforms.sky = settings.sky;
+
+    forms.write_buffer(&queue, fog_uniforms_group.lock().unwrap().default_buffer(0));
+}
+
+// Reads the scene through node input 0 and its depth buffer through a depth channel
+pub fn build_node_fog() -> NodeBuilder {
+    NodeBuilder::new(
+        "fog".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(post_fx::with_template(include_str!(
+            "../shaders/post_fx/fog.wgsl"
+        ))),
+    )
+    .with_id(Uuid::new_v4())
+    .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
+    .with_node_input()
+    .with_frame_uniforms()
+    .with_shared_uniform_group(Arc::new(Mutex::new(FogUniformGroup::builder())))
+    .with_depth_input()
+    .with_system(render_system)
+}
+
+#[system]
+pub fn render(
+    #[state] state: &mut NodeState,
+    #[resource] quad: &Quad,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_fog (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Fog Encoder"),
+    });
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(2, &node.binder.uniform_groups[&ID(FOG_BIND_GROUP_ID)], &[]);
+    pass.set_bind_group(3, state.inputs[1].bind_group_ref(), &[]);
+
+    pass.set_vertex_buffer(0, quad.mesh.vertex_buffer.buffer.0.slice(..));
+    pass.set_index_buffer(
+        quad.mesh.index_buffer.buffer.0.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    pass.draw_indexed(0..quad.mesh.index_buffer.buffer.1, 0, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -0,0 +1,403 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix3, Rad, Vector3};
+use iced_winit::winit::event::VirtualKeyCode;
+use instant::Instant;
+use legion::{world::SubWorld, Entity, EntityStore};
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex, RwLock},
+};
+use wgpu::util::DeviceExt;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    components::Transform3D,
+    constants::{CAMERA_3D_BIND_GROUP_ID, ID},
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        systems::debug::{push_box, push_line, DebugVertex},
+        SCREEN_SIZE,
+    },
+    sources::camera::Camera3D,
+    systems::picking::{PickedEntity, Ray},
+};
+
+// Translate, rotate and scale handles for one selected entity, drawn on top of everything by
+// the gizmo overlay node. Left clicking a Pickable entity selects it, and left clicking
+// nothing deselects it; dragging a handle edits the entity's Transform3D along that axis.
+// 1, 2 and 3 switch between translating, rotating and scaling.
+//
+// Handles are along the world axes, and keep the same size on screen wherever the entity is.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+// Resource, inserted with the gizmos
+#[derive(Clone, Debug)]
+pub struct GizmoState {
+    pub selected: Option<Entity>,
+    pub mode: GizmoMode,
+    // Handle length, as a fraction of the distance from the camera
+    pub size: f32,
+    // The axis under the cursor, or being dragged
+    pub hovered: Option<usize>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for GizmoState {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            mode: GizmoMode::Translate,
+            size: 0.15,
+            hovered: None,
+            drag: None,
+        }
+    }
+}
+
+impl GizmoState {
+    pub fn dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GizmoDrag {
+    axis: usize,
+    start: Transform3D,
+    // Along the axis when translating or scaling, and around it (radians) when rotating
+    from: f32,
+}
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.2, 0.9, 0.2, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+];
+const HOVERED_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+const RING_SEGMENTS: u32 = 48;
+// How near the cursor's ray has to pass a handle, as a fraction of the handle length
+const HANDLE_RADIUS: f32 = 0.08;
+
+fn axis(index: usize) -> Vector3<f32> {
+    let mut axis = [0.0; 3];
+    axis[index] = 1.0;
+    axis.into()
+}
+
+// Handle length in world units
+fn handle_length(gizmo: &GizmoState, camera: &Camera3D, center: [f32; 3]) -> f32 {
+    (Vector3::from(center) - camera.pos.to_vec()).magnitude() * gizmo.size
+}
+
+// Where on the axis through center the ray passes nearest, and how near
+fn nearest_on_axis(ray: &Ray, center: [f32; 3], axis: Vector3<f32>) -> Option<(f32, f32)> {
+    let (origin, dir) = (Vector3::from(ray.origin), Vector3::from(ray.dir));
+    let offset = Vector3::from(center) - origin;
+    let alignment = dir.dot(axis);
+    let denominator = 1.0 - alignment * alignment;
+    // Looking straight down the axis
+    if denominator < 0.0001 {
+        return None;
+    }
+    let along_axis = (alignment * offset.dot(dir) - offset.dot(axis)) / denominator;
+    let along_ray = (offset.dot(dir) - alignment * offset.dot(axis)) / denominator;
+    if along_ray < 0.0 {
+        return None;
+    }
+    let on_axis = Vector3::from(center) + axis * along_axis;
+    let on_ray = origin + dir * along_ray;
+    Some((along_axis, (on_axis - on_ray).magnitude()))
+}
+
+// Where the ray crosses the plane through center facing the axis, as an angle around the
+// axis and a distance from center
+fn angle_around_axis(ray: &Ray, center: [f32; 3], index: usize) -> Option<(f32, f32)> {
+    let (origin, dir) = (Vector3::from(ray.origin), Vector3::from(ray.dir));
+    let normal = axis(index);
+    let facing = dir.dot(normal);
+    if facing.abs() < 0.0001 {
+        return None;
+    }
+    let t = (Vector3::from(center) - origin).dot(normal) / facing;
+    if t < 0.0 {
+        return None;
+    }
+    let local = origin + dir * t - Vector3::from(center);
+    let (u, v) = (axis((index + 1) % 3), axis((index + 2) % 3));
+    Some((local.dot(v).atan2(local.dot(u)), local.magnitude()))
+}
+
+// The handle under the ray, if any
+fn hovered_axis(gizmo: &GizmoState, ray: &Ray, center: [f32; 3], length: f32) -> Option<usize> {
+    let mut nearest: Option<(usize, f32)> = None;
+    for index in 0..3 {
+        let distance = match gizmo.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                match nearest_on_axis(ray, center, axis(index)) {
+                    Some((along, distance)) if along >= 0.0 && along <= length => distance,
+                    _ => continue,
+                }
+            }
+            GizmoMode::Rotate => match angle_around_axis(ray, center, index) {
+                Some((_, radius)) => (radius - length).abs(),
+                None => continue,
+            },
+        };
+        if distance > length * HANDLE_RADIUS {
+            continue;
+        }
+        if nearest.map_or(true, |(_, nearest)| distance < nearest) {
+            nearest = Some((index, distance));
+        }
+    }
+    nearest.map(|(index, _)| index)
+}
+
+fn selected_transform(world: &SubWorld, entity: Entity) -> Option<Transform3D> {
+    world
+        .entry_ref(entity)
+        .ok()?
+        .get_component::<Transform3D>()
+        .ok()
+        .copied()
+}
+
+#[system]
+#[write_component(Transform3D)]
+pub fn gizmo_input(
+    world: &mut SubWorld,
+    #[resource] gizmo: &mut GizmoState,
+    #[resource] picked: &PickedEntity,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+) {
+    debug!("running system gizmo_input");
+    let input = input.read().unwrap();
+    if gizmo.drag.is_none() {
+        if input.key_pressed(VirtualKeyCode::Key1) {
+            gizmo.mode = GizmoMode::Translate;
+        }
+        if input.key_pressed(VirtualKeyCode::Key2) {
+            gizmo.mode = GizmoMode::Rotate;
+        }
+        if input.key_pressed(VirtualKeyCode::Key3) {
+            gizmo.mode = GizmoMode::Scale;
+        }
+    }
+    if input.mouse_released(0) {
+        gizmo.drag = None;
+    }
+
+    let camera = camera.lock().unwrap();
+    let screen = *SCREEN_SIZE.read().unwrap();
+    let ray = input
+        .mouse()
+        .and_then(|cursor| Ray::from_screen(&camera, cursor, (screen.0 as f32, screen.1 as f32)));
+
+    // The selection may have been despawned, or lost its transform
+    let selected = gizmo
+        .selected
+        .and_then(|entity| Some((entity, selected_transform(world, entity)?)));
+    if selected.is_none() {
+        gizmo.selected = None;
+        gizmo.drag = None;
+    }
+
+    let ray = match ray {
+        Some(ray) => ray,
+        None => return,
+    };
+
+    let (entity, transform) = match selected {
+        Some(selected) => selected,
+        None => {
+            gizmo.hovered = None;
+            if input.mouse_pressed(0) {
+                gizmo.selected = picked.entity;
+            }
+            return;
+        }
+    };
+
+    let center = match gizmo.drag {
+        Some(drag) => drag.start.position,
+        None => transform.position,
+    };
+    let length = handle_length(gizmo, &camera, center);
+
+    let drag = match gizmo.drag {
+        Some(drag) => drag,
+        None => {
+            gizmo.hovered = hovered_axis(gizmo, &ray, center, length);
+            if !input.mouse_pressed(0) {
+                return;
+            }
+            let index = match gizmo.hovered {
+                Some(index) => index,
+                None => {
+                    gizmo.selected = picked.entity;
+                    return;
+                }
+            };
+            let from = match gizmo.mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    nearest_on_axis(&ray, center, axis(index)).map(|(along, _)| along)
+                }
+                GizmoMode::Rotate => angle_around_axis(&ray, center, index).map(|(angle, _)| angle),
+            };
+            let drag = match from {
+                Some(from) => GizmoDrag {
+                    axis: index,
+                    start: transform,
+                    from,
+                },
+                None => return,
+            };
+            gizmo.drag = Some(drag);
+            drag
+        }
+    };
+
+    let mut edited = drag.start;
+    match gizmo.mode {
+        GizmoMode::Translate => {
+            if let Some((along, _)) = nearest_on_axis(&ray, center, axis(drag.axis)) {
+                edited.position[drag.axis] += along - drag.from;
+            }
+        }
+        GizmoMode::Scale => {
+            // Dragging a handle's length out doubles the scale
+            if let Some((along, _)) = nearest_on_axis(&ray, center, axis(drag.axis)) {
+                let factor = (1.0 + (along - drag.from) / length).max(0.01);
+                edited.scale[drag.axis] *= factor;
+            }
+        }
+        GizmoMode::Rotate => {
+            if let Some((angle, _)) = angle_around_axis(&ray, center, drag.axis) {
+                let around = Matrix3::from_axis_angle(axis(drag.axis), Rad(angle - drag.from));
+                edited.rotation =
+                    Transform3D::euler_from_matrix(around * drag.start.rotation_matrix());
+            }
+        }
+    }
+
+    if let Ok(mut entry) = world.entry_mut(entity) {
+        if let Ok(transform) = entry.get_component_mut::<Transform3D>() {
+            *transform = edited;
+        }
+    }
+}
+
+#[system]
+#[read_component(Transform3D)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] gizmo: &GizmoState,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_gizmo (graph node)");
+    let start_time = Instant::now();
+    let transform = match gizmo
+        .selected
+        .and_then(|entity| selected_transform(world, entity))
+    {
+        Some(transform) => transform,
+        None => return,
+    };
+
+    let center = transform.position;
+    let length = handle_length(gizmo, &camera.lock().unwrap(), center);
+    let vertices = build_handles(gizmo, center, length);
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Gizmo Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Gizmo Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass(&node.name, &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(
+        0,
+        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    pass.draw(0..vertices.len() as u32, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+fn build_handles(gizmo: &GizmoState, center: [f32; 3], length: f32) -> Vec<DebugVertex> {
+    let mut lines = vec![];
+    let offset =
+        |direction: Vector3<f32>| -> [f32; 3] { (Vector3::from(center) + direction).into() };
+
+    for index in 0..3 {
+        let color = match gizmo.hovered == Some(index) {
+            true => HOVERED_COLOR,
+            false => AXIS_COLORS[index],
+        };
+        let tip = offset(axis(index) * length);
+        match gizmo.mode {
+            GizmoMode::Translate => {
+                push_line(&mut lines, center, tip, color);
+                // Arrowhead, as four lines back from the tip
+                let back = axis(index) * length * 0.85;
+                for side in [(index + 1) % 3, (index + 2) % 3] {
+                    for sign in [-1.0, 1.0] {
+                        let base = offset(back + axis(side) * length * 0.05 * sign);
+                        push_line(&mut lines, base, tip, color);
+                    }
+                }
+            }
+            GizmoMode::Scale => {
+                push_line(&mut lines, center, tip, color);
+                let half = length * 0.05;
+                push_box(
+                    &mut lines,
+                    [tip[0] - half, tip[1] - half, tip[2] - half],
+                    [tip[0] + half, tip[1] + half, tip[2] + half],
+                    color,
+                );
+            }
+            GizmoMode::Rotate => {
+                let (u, v) = (axis((index + 1) % 3), axis((index + 2) % 3));
+                let point = |i: u32| {
+                    let angle = 2.0 * PI * i as f32 / RING_SEGMENTS as f32;
+                    offset((u * angle.cos() + v * angle.sin()) * length)
+                };
+                for i in 0..RING_SEGMENTS {
+                    push_line(&mut lines, point(i), point(i + 1), color);
+                }
+            }
+        }
+    }
+    lines
+}
@@ -1,31 +1,136 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
-use crate::renderer::{graph::RenderGraph, GpuState};
+use crate::{
+    renderer::{buffer::upload::Uploader, graph::RenderGraph, GpuState, SCREEN_SIZE},
+    sources::{
+        display::{DisplayChange, DisplayQueue},
+        events::{Events, SurfaceEvent},
+        registry::TextureRegistry,
+    },
+};
 
 #[system]
 pub fn begin_render_graph(
     #[resource] gpu: &Arc<Mutex<GpuState>>,
     #[resource] graph: &Arc<RenderGraph>,
+    #[resource] display: &DisplayQueue,
+    #[resource] textures: &Arc<RwLock<TextureRegistry>>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] surface_events: &mut Events<SurfaceEvent>,
 ) {
     debug!("running system begin_render_graph");
-    let gpu = gpu.lock().unwrap();
-
-    match gpu.surface.get_current_texture() {
-        Ok(frame) => graph
-            .swap_chain_target
-            .lock()
-            .unwrap()
-            .set_swap_chain(Arc::new(frame)),
-        Err(err) => {
-            warn!("failed to get swapchain frame: {}", err);
-            warn!("cannot draw to any windows, attempting to recreate swapchain");
-            // gpu.force_new_swap_chain();
+    let mut gpu = gpu.lock().unwrap();
+
+    // Everything the update systems wrote, ahead of the first node
+    uploader.flush();
+
+    // Nothing else is rendering yet, so the surface and all targets can change together
+    if let Some(change) = display.take_change() {
+        apply_display_change(&mut gpu, graph, display, textures, change);
+    }
+
+    let surface = match &gpu.surface {
+        Some(surface) => surface,
+        None => {
+            // Headless
+            if let Some(offscreen) = &gpu.offscreen {
+                graph
+                    .swap_chain_target
+                    .lock()
+                    .unwrap()
+                    .set_offscreen(Arc::clone(&offscreen.view));
+            }
+            return;
+        }
+    };
+    let frame = match surface.get_current_texture() {
+        // The surface no longer matches the window, eg. a resize landed between the last
+        // Resized event and now. Reconfigure and try once more; node targets follow with
+        // the Resized event that's on its way.
+        Err(wgpu::SurfaceError::Outdated) | Err(wgpu::SurfaceError::Lost) => {
+            gpu.reconfigure_surface();
+            surface_events.send(SurfaceEvent::Reconfigured);
+            gpu.surface.as_ref().unwrap().get_current_texture()
         }
+        frame => frame,
+    };
+    let err = match frame {
+        Ok(frame) => {
+            graph
+                .swap_chain_target
+                .lock()
+                .unwrap()
+                .set_swap_chain(Arc::new(frame));
+            return;
+        }
+        Err(err) => err,
+    };
+
+    // Skip the frame: the nodes still draw, into a target that's never presented
+    let event = match err {
+        wgpu::SurfaceError::Timeout => SurfaceEvent::Timeout,
+        wgpu::SurfaceError::Outdated => SurfaceEvent::Outdated,
+        wgpu::SurfaceError::Lost => SurfaceEvent::Lost,
+        wgpu::SurfaceError::OutOfMemory => SurfaceEvent::OutOfMemory,
+    };
+    match event {
+        SurfaceEvent::OutOfMemory => error!("surface out of memory, skipping frame"),
+        _ => debug!("no surface frame ({}), skipping", err),
     }
+    surface_events.send(event);
+    let fallback = gpu.fallback_view();
+    graph
+        .swap_chain_target
+        .lock()
+        .unwrap()
+        .set_offscreen(fallback);
 }
 
+fn apply_display_change(
+    gpu: &mut GpuState,
+    graph: &RenderGraph,
+    display: &DisplayQueue,
+    textures: &RwLock<TextureRegistry>,
+    change: DisplayChange,
+) {
+    let current_size = *SCREEN_SIZE.read().unwrap();
+    let size = change.size.unwrap_or(current_size);
+
+    // Minimized
+    if size.0 == 0 || size.1 == 0 {
+        display.defer(&change);
+        return;
+    }
+    if size == current_size && change.render_scale.is_none() {
+        return;
+    }
+    if size != current_size {
+        gpu.resize(size);
+    }
+    graph.resize(
+        size,
+        change
+            .render_scale
+            .unwrap_or_else(|| display.settings().render_scale),
+        Arc::clone(&gpu.device),
+        textures,
+    );
+    display.applied(&change);
+}
+
+// After the node systems: their commands, in the graph's dependency order
 #[system]
-pub fn end_render_graph(#[resource] graph: &Arc<RenderGraph>) {
+pub fn submit_render_graph(#[resource] uploader: &Arc<Uploader>) {
+    debug!("running system submit_render_graph");
+    uploader.submit_recorded();
+}
+
+#[system]
+pub fn end_render_graph(
+    #[resource] graph: &Arc<RenderGraph>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
     debug!("running system end_render_graph");
+    uploader.recall();
     graph.swap_chain_target.lock().unwrap().release_swap_chain();
 }
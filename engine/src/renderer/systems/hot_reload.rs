@@ -0,0 +1,193 @@
+use instant::Instant;
+use legion::{world::SubWorld, IntoQuery};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+use wgpu::BindGroup;
+
+use crate::{
+    renderer::{
+        graph::{
+            node::{RenderNode, ShaderSource},
+            RenderGraph,
+        },
+        mesh::Mesh,
+    },
+    sources::registry::{AssetSource, MeshRegistry, TextureRegistry},
+};
+
+// Watches the shaders of nodes built with ShaderSource::Path, and rebuilds their
+// pipelines when the files change. Polls modification times instead of subscribing
+// to the filesystem, which is plenty for a handful of files edited by hand.
+//
+// Runs before begin_render_graph, so no render system is holding a pipeline.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct ShaderWatcher {
+    shaders: Vec<WatchedShader>,
+    last_poll: Instant,
+}
+
+struct WatchedShader {
+    node: Arc<RenderNode>,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderWatcher {
+    // None if no node has a shader on disk
+    pub fn new(nodes: &HashMap<Uuid, Arc<RenderNode>>) -> Option<Self> {
+        let shaders = nodes
+            .values()
+            .filter_map(|node| match &node.shader_source {
+                ShaderSource::Path(path) => Some(WatchedShader {
+                    node: Arc::clone(node),
+                    modified: modified(path),
+                    path: path.clone(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<WatchedShader>>();
+
+        match shaders.is_empty() {
+            true => None,
+            false => Some(Self {
+                shaders,
+                last_poll: Instant::now(),
+            }),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[system]
+pub fn reload_shaders(
+    #[resource] watcher: &mut ShaderWatcher,
+    #[resource] device: &Arc<wgpu::Device>,
+) {
+    if watcher.last_poll.elapsed() < POLL_INTERVAL {
+        return;
+    }
+    watcher.last_poll = Instant::now();
+
+    for shader in watcher.shaders.iter_mut() {
+        let modified = modified(&shader.path);
+        if modified.is_none() || modified == shader.modified {
+            continue;
+        }
+        shader.modified = modified;
+
+        info!(
+            "reloading shader for {}: {}",
+            shader.node.name,
+            shader.path.display()
+        );
+        if let Err(err) = shader.node.reload_shader(device) {
+            error!("{}", err);
+        }
+    }
+}
+
+// Watches the files behind the texture and mesh registries (see sources::registry::watch),
+// on the same schedule as the shaders. A reloaded texture's bind group is swapped into every
+// node which binds it; entities whose Mesh was cloned from a reloaded mesh are rebuilt.
+// Meshes held outside of entities (InstanceGroups, the Quad resource) keep their old buffers.
+pub struct AssetWatcher {
+    assets: Vec<(AssetSource, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl AssetWatcher {
+    pub fn new(sources: Vec<AssetSource>) -> Self {
+        Self {
+            assets: sources
+                .into_iter()
+                .map(|source| {
+                    let modified = modified(Path::new(source.path()));
+                    (source, modified)
+                })
+                .collect(),
+            last_poll: Instant::now(),
+        }
+    }
+}
+
+#[system]
+#[write_component(Mesh)]
+pub fn reload_assets(
+    world: &mut SubWorld,
+    #[resource] watcher: &mut AssetWatcher,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] textures: &Arc<RwLock<TextureRegistry>>,
+    #[resource] meshes: &Arc<RwLock<MeshRegistry>>,
+    #[resource] graph: &Arc<RenderGraph>,
+) {
+    if watcher.last_poll.elapsed() < POLL_INTERVAL {
+        return;
+    }
+    watcher.last_poll = Instant::now();
+
+    let mut reloaded_meshes: Vec<Uuid> = vec![];
+    for (source, last_modified) in watcher.assets.iter_mut() {
+        let source = &*source;
+        let modified = modified(Path::new(source.path()));
+        if modified.is_none() || modified == *last_modified {
+            continue;
+        }
+        *last_modified = modified;
+
+        info!("reloading asset: {}", source.path());
+        match source {
+            AssetSource::Texture { id, .. } => {
+                let bind_group = match textures.write().unwrap().reload(source, device, queue) {
+                    Ok(bind_group) => bind_group,
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                };
+                swap_texture(graph, id, &bind_group);
+            }
+            AssetSource::Mesh { .. } => match meshes.write().unwrap().reload(source) {
+                Ok(ids) => reloaded_meshes.extend(ids),
+                Err(err) => error!("error reloading mesh {}: {}", source.path(), err),
+            },
+        }
+    }
+
+    rebuild_meshes(world, &meshes.read().unwrap(), &reloaded_meshes);
+}
+
+// Hands a replaced texture's bind group to every node which binds the old one
+pub(crate) fn swap_texture(graph: &RenderGraph, id: &Uuid, bind_group: &Arc<BindGroup>) {
+    for node in graph.nodes.values() {
+        if let Some(group) = node.binder.texture_groups.write().unwrap().get_mut(id) {
+            *group = Arc::clone(bind_group);
+        }
+    }
+}
+
+// Entities whose Mesh was cloned from one of these
+pub(crate) fn rebuild_meshes(world: &mut SubWorld, meshes: &MeshRegistry, replaced: &[Uuid]) {
+    if replaced.is_empty() {
+        return;
+    }
+    <&mut Mesh>::query().for_each_mut(world, |mesh| {
+        if let Some((group_id, mesh_id)) = mesh.source {
+            if replaced.contains(&mesh_id) {
+                *mesh = meshes.clone_mesh(&mesh_id, &group_id);
+            }
+        }
+    });
+}
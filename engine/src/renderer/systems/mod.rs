@@ -1,8 +1,18 @@
+pub mod capture;
 pub mod chain;
 pub mod channel;
+pub mod debug;
+pub mod dof;
+pub mod fog;
+pub mod gizmo;
 pub mod graph;
+pub mod hot_reload;
+pub mod post_fx;
+pub mod probe;
 pub mod quad;
 pub mod render_2d;
 pub mod render_3d;
 pub mod sky;
+pub mod streaming;
+pub mod tonemap;
 pub mod ui;
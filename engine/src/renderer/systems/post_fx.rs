@@ -0,0 +1,299 @@
+use instant::Instant;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    constants::{FRAME_BIND_GROUP_ID, ID},
+    renderer::{
+        buffer::{upload::Uploader, VERTEX2D_BUFFER_LAYOUT},
+        graph::{
+            node::{NodeBuilder, ShaderSource},
+            GraphBuilder, NodeState,
+        },
+        systems::{
+            dof::build_nodes_dof,
+            fog::build_node_fog,
+            quad::Quad,
+            render_3d::decal::build_node_decals,
+            tonemap::{build_node_tonemap, TonemapOperator, HDR_FORMAT},
+        },
+    },
+};
+
+const TEMPLATE: &str = include_str!("../shaders/templates/post_fx.wgsl");
+const EFFECT_MARKER: &str = "// <effect>";
+
+pub enum PostFx {
+    // strength in [0, 1]; radius is where the darkening starts, from the center (< 0.75)
+    Vignette { strength: f32, radius: f32 },
+    // How far red and blue are pulled apart at the corners, in pixels
+    ChromaticAberration { offset: f32 },
+    Fxaa,
+    // WGSL defining fn effect(uv: vec2<f32>) -> vec4<f32>; see shaders/templates/post_fx.wgsl
+    Custom { name: String, wgsl: String },
+}
+
+impl PostFx {
+    pub fn name(&self) -> &str {
+        match self {
+            PostFx::Vignette { .. } => "vignette",
+            PostFx::ChromaticAberration { .. } => "chromatic_aberration",
+            PostFx::Fxaa => "fxaa",
+            PostFx::Custom { name, .. } => name,
+        }
+    }
+
+    // The full shader for this effect's pass
+    pub fn shader(&self) -> String {
+        let effect = match self {
+            PostFx::Vignette { strength, radius } => format!(
+                "let VIGNETTE_STRENGTH: f32 = {:?};\nlet VIGNETTE_RADIUS: f32 = {:?};\n\n{}",
+                strength,
+                radius,
+                include_str!("../shaders/post_fx/vignette.wgsl")
+            ),
+            PostFx::ChromaticAberration { offset } => format!(
+                "let ABERRATION_OFFSET: f32 = {:?};\n\n{}",
+                offset,
+                include_str!("../shaders/post_fx/chromatic_aberration.wgsl")
+            ),
+            PostFx::Fxaa => include_str!("../shaders/post_fx/fxaa.wgsl").to_owned(),
+            PostFx::Custom { wgsl, .. } => wgsl.clone(),
+        };
+        with_template(&effect)
+    }
+}
+
+// Wraps an effect in the fullscreen pass template
+pub fn with_template(effect: &str) -> String {
+    TEMPLATE.replace(EFFECT_MARKER, effect)
+}
+
+// Post-processing as a list of fullscreen passes, applied in order. Each pass is a node
+// reading the previous one (the first reads the scene) through a channel, and the last
+// one becomes the master node. Overlays still draw on the scene, before any effect.
+//
+// With a tonemap operator, the scene and the effects render into HDR_FORMAT targets and a
+// tonemap node (renderer::systems::tonemap) becomes the master instead.
+//
+// Decals (renderer::systems::render_3d::decal), fog (renderer::systems::fog) and then depth
+// of field (renderer::systems::dof) always come first, reading the scene's depth buffer, so
+// the scene node needs one. Passes of your own (with_pass) come after the effects.
+//
+// Engine modes take one through EngineBuilder::with_post_fx; for a hand-built graph, see
+// build().
+#[derive(Default)]
+pub struct PostFxChainBuilder {
+    pub effects: Vec<PostFx>,
+    pub tonemap: Option<TonemapOperator>,
+    pub depth_of_field: bool,
+    pub fog: bool,
+    pub decals: bool,
+    pub passes: Vec<NodeBuilder>,
+}
+
+impl PostFxChainBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_effect(mut self, effect: PostFx) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn with_vignette(self, strength: f32, radius: f32) -> Self {
+        self.with_effect(PostFx::Vignette { strength, radius })
+    }
+
+    pub fn with_chromatic_aberration(self, offset: f32) -> Self {
+        self.with_effect(PostFx::ChromaticAberration { offset })
+    }
+
+    pub fn with_fxaa(self) -> Self {
+        self.with_effect(PostFx::Fxaa)
+    }
+
+    pub fn with_custom(self, name: &str, wgsl: &str) -> Self {
+        self.with_effect(PostFx::Custom {
+            name: name.to_owned(),
+            wgsl: wgsl.to_owned(),
+        })
+    }
+
+    // A node of your own, with one graph input and a node input for the previous pass; one with
+    // a depth input also reads the scene's depth buffer
+    pub fn with_pass(mut self, node: NodeBuilder) -> Self {
+        self.passes.push(node);
+        self
+    }
+
+    pub fn with_tonemap(mut self, operator: TonemapOperator) -> Self {
+        self.tonemap = Some(operator);
+        self
+    }
+
+    // Focus and aperture come from the DofSettings resource
+    pub fn with_depth_of_field(mut self) -> Self {
+        self.depth_of_field = true;
+        self
+    }
+
+    // Color, density and distances come from the FogSettings resource
+    pub fn with_fog(mut self) -> Self {
+        self.fog = true;
+        self
+    }
+
+    // Entities with a Decal; see render_3d::decal
+    pub fn with_decals(mut self) -> Self {
+        self.decals = true;
+        self
+    }
+
+    // Every pass but the decals is drawn with the Quad resource
+    pub fn needs_quad(&self) -> bool {
+        !self.effects.is_empty()
+            || !self.passes.is_empty()
+            || self.tonemap.is_some()
+            || self.depth_of_field
+            || self.fog
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+            && self.passes.is_empty()
+            && self.tonemap.is_none()
+            && !self.depth_of_field
+            && !self.fog
+            && !self.decals
+    }
+
+    // Adds scene and the effect nodes to the graph, or just scene as the master when there
+    // are no effects. The passes need a Quad resource and the frame uniforms, and the tonemap
+    // node an Exposure resource, loaded by tonemap::tonemap_uniform_system. Depth of field
+    // needs DofSettings, loaded by dof::load_system, fog FogSettings, loaded by
+    // fog::load_system, and decals a DecalVolume, with decal::load_system loading each Decal.
+    pub fn build(self, graph: GraphBuilder, mut scene: NodeBuilder) -> GraphBuilder {
+        if self.is_empty() {
+            return graph.with_master_node(scene);
+        }
+
+        let hdr = self.tonemap.is_some();
+        if hdr {
+            scene = scene.with_hdr_target(HDR_FORMAT);
+        }
+
+        let scene_id = scene.dest_id;
+        let mut nodes: Vec<NodeBuilder> = vec![];
+        // Those reading the scene's depth
+        let mut depth_readers = vec![];
+        if self.decals {
+            depth_readers.push(nodes.len());
+            nodes.push(build_node_decals());
+        }
+        if self.fog {
+            depth_readers.push(nodes.len());
+            nodes.push(build_node_fog());
+        }
+        if self.depth_of_field {
+            depth_readers.push(nodes.len());
+            nodes.extend(build_nodes_dof());
+        }
+        for (i, effect) in self.effects.iter().enumerate() {
+            nodes.push(build_node_post_fx(effect, i));
+        }
+        for pass in self.passes {
+            if pass.reads_depth() {
+                depth_readers.push(nodes.len());
+            }
+            nodes.push(pass);
+        }
+        if hdr {
+            // Passes with a format of their own (depth of field) keep it
+            for node in nodes.iter_mut().filter(|node| node.target_format.is_none()) {
+                node.target_format = Some(HDR_FORMAT);
+            }
+            nodes.push(build_node_tonemap());
+        }
+
+        let mut graph = graph.with_scene_node(scene);
+        for i in depth_readers {
+            graph = graph.with_depth_channel(scene_id, nodes[i].dest_id);
+        }
+
+        // The last pass (an effect or the tonemap node) is the master
+        let mut previous = scene_id;
+        let last = nodes.len() - 1;
+        for (i, node) in nodes.into_iter().enumerate() {
+            let id = node.dest_id;
+            graph = graph.with_channel(previous, 0, id);
+            graph = match i == last {
+                true => graph.with_master_node(node),
+                false => graph.with_node(node),
+            };
+            previous = id;
+        }
+        graph
+    }
+}
+
+fn build_node_post_fx(effect: &PostFx, index: usize) -> NodeBuilder {
+    NodeBuilder::new(
+        format!("post_fx_{}_{}", index, effect.name()),
+        1,
+        1,
+        ShaderSource::WGSL(effect.shader()),
+    )
+    .with_id(Uuid::new_v4())
+    .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
+    .with_node_input()
+    .with_frame_uniforms()
+    .with_system(render_system)
+}
+
+#[system]
+pub fn render(
+    #[state] state: &mut NodeState,
+    #[resource] quad: &Quad,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_post_fx (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Post FX Encoder"),
+    });
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
+
+    pass.set_vertex_buffer(0, quad.mesh.vertex_buffer.buffer.0.slice(..));
+    pass.set_index_buffer(
+        quad.mesh.index_buffer.buffer.0.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    pass.draw_indexed(0..quad.mesh.index_buffer.buffer.1, 0, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -0,0 +1,149 @@
+use iced_winit::winit::event::VirtualKeyCode;
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex, RwLock},
+};
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    renderer::{
+        graph::{target::RenderTarget, RenderGraph},
+        GpuState,
+    },
+    sources::{
+        metrics::{EngineMetrics, ProbeSample},
+        ui::iced::IcedWinitHelper,
+    },
+};
+
+// Color picker for the final frame: copies the master target's pixel under the cursor
+// into a readback buffer, right before the frame is presented, for the probe panel
+// (sources::ui::probe). F6 toggles it at runtime.
+//
+// The readback waits on the gpu, so this costs a pipeline stall every frame while it's on.
+
+pub struct PixelProbe {
+    pub enabled: bool,
+    buffer: wgpu::Buffer,
+}
+
+impl PixelProbe {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            enabled: true,
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pixel_probe_readback"),
+                // One row, padded to the copy alignment
+                size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        }
+    }
+}
+
+#[system]
+pub fn probe_pixel(
+    #[resource] probe: &mut PixelProbe,
+    #[resource] gpu: &Arc<Mutex<GpuState>>,
+    #[resource] graph: &Arc<RenderGraph>,
+    #[resource] helper: &Arc<Mutex<IcedWinitHelper>>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+    #[resource] metrics: &Arc<EngineMetrics>,
+) {
+    if input.read().unwrap().key_pressed(VirtualKeyCode::F6) {
+        probe.enabled = !probe.enabled;
+        if !probe.enabled {
+            metrics.ui.lock().unwrap().probe = None;
+        }
+    }
+    if !probe.enabled {
+        return;
+    }
+    debug!("running system probe_pixel");
+
+    let gpu = gpu.lock().unwrap();
+    if !gpu
+        .surface_config
+        .usage
+        .contains(wgpu::TextureUsages::COPY_SRC)
+    {
+        warn!("the surface's format can't be copied from, turning the pixel probe off");
+        probe.enabled = false;
+        return;
+    }
+    let format = gpu.surface_config.format;
+    let size = (gpu.surface_config.width, gpu.surface_config.height);
+
+    // Cursor is at (-1, -1) until it first enters the window
+    let cursor = helper.lock().unwrap().cursor_position;
+    if cursor.x < 0.0 || cursor.y < 0.0 {
+        return;
+    }
+    let position = (cursor.x as u32, cursor.y as u32);
+    if position.0 >= size.0 || position.1 >= size.1 {
+        return;
+    }
+
+    let target = graph.swap_chain_target.lock().unwrap();
+    let frame = match &*target {
+        RenderTarget::Master {
+            screen_buffer: Some(frame),
+            ..
+        } => frame,
+        _ => return,
+    };
+
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("pixel_probe_encoder"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: &frame.texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: position.0,
+                y: position.1,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &probe.buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+    drop(target);
+
+    let slice = probe.buffer.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    gpu.device.poll(wgpu::Maintain::Wait);
+    if let Err(err) = futures::executor::block_on(mapping) {
+        warn!("pixel probe readback failed: {}", err);
+        return;
+    }
+
+    let bytes = slice.get_mapped_range();
+    let texel = match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            [bytes[2], bytes[1], bytes[0], bytes[3]]
+        }
+        _ => [bytes[0], bytes[1], bytes[2], bytes[3]],
+    };
+    drop(bytes);
+    probe.buffer.unmap();
+
+    metrics.ui.lock().unwrap().probe = Some(ProbeSample::from_rgba8(position, texel));
+}
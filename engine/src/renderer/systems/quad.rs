@@ -1,65 +1,15 @@
-use std::{
-    sync::{Arc, RwLock},
-    time::Instant,
-};
+use instant::Instant;
+use std::sync::Arc;
 
 use crate::{
-    components::FrameMetrics,
-    constants::{CAMERA_3D_BIND_GROUP_ID, ID, QUAD_BIND_GROUP_ID},
-    renderer::{
-        graph::NodeState,
-        mesh::Mesh,
-        uniform::{
-            generic::GenericUniformBuilder,
-            group::{GroupState, UniformGroup, UniformGroupBuilder, UniformGroupType},
-        },
-        SCREEN_SIZE,
-    },
-    sources::WindowSize,
+    constants::{CAMERA_3D_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID},
+    renderer::{buffer::upload::Uploader, graph::NodeState, mesh::Mesh},
 };
 
-// Resource
+// Resource. Quad shaders read time and resolution from the frame uniforms (see
+// systems/frame.rs), which their nodes bind with NodeBuilder::with_frame_uniforms().
 pub struct Quad {
     pub mesh: Mesh,
-    pub uniforms: QuadUniforms,
-    pub uniform_group: GroupState,
-}
-
-#[repr(C)]
-#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct QuadUniforms {
-    pub dimensions: [f32; 2],
-    pub time: f32,
-    pub delta: f32,
-}
-
-pub struct QuadUniformGroup {}
-
-impl UniformGroupType<Self> for QuadUniformGroup {
-    fn builder() -> UniformGroupBuilder<QuadUniformGroup> {
-        let screen_size = SCREEN_SIZE.read().unwrap();
-        UniformGroup::<QuadUniformGroup>::builder()
-            .with_uniform(GenericUniformBuilder::from_source(QuadUniforms {
-                dimensions: [screen_size.0 as f32, screen_size.1 as f32],
-                time: 0.0,
-                delta: 0.0,
-            }))
-            .with_id(ID(QUAD_BIND_GROUP_ID))
-    }
-}
-
-#[system]
-pub fn load(
-    #[resource] quad: &mut Quad,
-    #[resource] window_size: &Arc<WindowSize>,
-    #[resource] frame_metrics: &Arc<RwLock<FrameMetrics>>,
-) {
-    debug!("running system render_quad_uniform_loader (graph node)");
-    quad.uniforms.dimensions = [window_size.width, window_size.height];
-    quad.uniforms.delta += frame_metrics.read().unwrap().delta().as_secs_f32();
-    quad.uniforms.time += quad.uniforms.delta;
-    quad.uniform_group
-        .write_buffer(0, bytemuck::cast_slice(&[quad.uniforms]));
 }
 
 #[system]
@@ -67,11 +17,12 @@ pub fn render(
     #[state] state: &mut NodeState,
     #[resource] quad: &Quad,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     debug!("running system render_quad (graph node)");
     let start_time = Instant::now();
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Quad Encoder"),
@@ -83,9 +34,13 @@ pub fn render(
     let mut pass = render_target_mut
         .create_render_pass("quad_render", &mut encoder, false)
         .unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
-    pass.set_bind_group(0, &quad.uniform_group.bind_group, &[]);
+    pass.set_bind_group(
+        0,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
     pass.set_bind_group(
         1,
         &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
@@ -101,7 +56,7 @@ pub fn render(
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("quad_render pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
@@ -1,7 +1,8 @@
+use instant::Instant;
 use legion::{world::SubWorld, IntoQuery};
 use std::{
+    cmp::Ordering,
     sync::{Arc, Mutex},
-    time::Instant,
 };
 
 use crate::{
@@ -11,8 +12,9 @@ use crate::{
         RENDER_2D_COMMON_TEXTURE_ID,
     },
     renderer::{
+        buffer::upload::Uploader,
         graph::NodeState,
-        systems::render_2d::Render2D,
+        systems::render_2d::{Layer2D, Render2D},
         uniform::{
             generic::{GenericUniform, GenericUniformBuilder},
             group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
@@ -25,9 +27,11 @@ use crate::{
 pub struct Render2DForwardDynamicUniforms {
     pub model: [f32; 4],
     pub color: [f32; 4],
+    pub sprite_params: [f32; 4],
     pub mix: f32,
+    pub sprite_mode: u32,
     pub _padding: [f32; 32],
-    pub __padding: [f32; 23],
+    pub __padding: [f32; 18],
 }
 
 // Phantom type
@@ -40,9 +44,11 @@ impl UniformGroupType<Self> for Render2DForwardDynamicGroup {
                 Render2DForwardDynamicUniforms {
                     model: [0.0, 0.0, 1.0, 1.0],
                     color: [1.0, 1.0, 1.0, 1.0],
+                    sprite_params: [0.0; 4],
                     mix: 1.0,
+                    sprite_mode: 0,
                     _padding: [0.0; 32],
-                    __padding: [0.0; 23],
+                    __padding: [0.0; 18],
                 },
             ))
             .with_id(ID(RENDER_2D_BIND_GROUP_ID))
@@ -54,6 +60,7 @@ impl UniformGroupType<Self> for Render2DForwardDynamicGroup {
 #[system]
 #[read_component(Render2D)]
 #[read_component(Position2D)]
+#[read_component(Layer2D)]
 pub fn load(
     world: &mut SubWorld,
     #[resource] base_uniforms: &Arc<Mutex<GenericUniform<Render2DForwardDynamicUniforms>>>,
@@ -64,14 +71,27 @@ pub fn load(
     let mut base_uniforms = base_uniforms.lock().unwrap();
     let mut base_uniforms_group = base_uniforms_group.lock().unwrap();
 
-    let mut query = <(&Render2D, &Position2D)>::query();
+    // Loaded in draw order: by layer, then zindex, then ECS order (stable sort)
+    let mut entities = <(&Render2D, &Position2D, Option<&Layer2D>)>::query()
+        .iter(world)
+        .collect::<Vec<_>>();
+    entities.sort_by(|(a, _, a_layer), (b, _, b_layer)| {
+        let a_layer = a_layer.copied().unwrap_or_default();
+        let b_layer = b_layer.copied().unwrap_or_default();
+        a_layer
+            .cmp(&b_layer)
+            .then(a.zindex.partial_cmp(&b.zindex).unwrap_or(Ordering::Equal))
+    });
 
     base_uniforms_group.begin_dynamic_loading();
     let mut count: u64 = 0;
-    for (render_2d, pos) in query.iter_mut(world) {
+    for (render_2d, pos, _) in entities {
         base_uniforms.mut_ref().model = [pos.x, pos.y, render_2d.width, render_2d.height];
         base_uniforms.mut_ref().color = render_2d.color;
         base_uniforms.mut_ref().mix = render_2d.mix;
+        let (sprite_mode, sprite_params) = render_2d.sprite_mode.params();
+        base_uniforms.mut_ref().sprite_mode = sprite_mode;
+        base_uniforms.mut_ref().sprite_params = sprite_params;
         // base_uniforms_group.load_dynamic_uniform(base_uniforms.as_bytes());
         count += 1;
     }
@@ -88,11 +108,13 @@ pub fn load(
 pub fn render(
     #[state] state: &mut NodeState,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     let start_time = Instant::now();
     debug!("running system render_2d_forward_dynamic (graph node)");
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Render2D Encoder"),
@@ -104,7 +126,7 @@ pub fn render(
         .create_render_pass("forward_render_2d", &mut encoder, true)
         .unwrap();
 
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
     pass.set_bind_group(
         2,
@@ -144,11 +166,7 @@ pub fn render(
         .collect::<Vec<u32>>();
 
     for _ in 0..*entity_count.lock().unwrap() {
-        pass.set_bind_group(
-            0,
-            &node.binder.texture_groups[&ID(RENDER_2D_COMMON_TEXTURE_ID)],
-            &[],
-        );
+        pass.set_bind_group(0, &texture_groups[&ID(RENDER_2D_COMMON_TEXTURE_ID)], &[]);
 
         pass.set_bind_group(
             1,
@@ -169,7 +187,7 @@ pub fn render(
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("forward_render_2d pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
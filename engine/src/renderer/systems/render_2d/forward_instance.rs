@@ -1,24 +1,37 @@
-use legion::{world::SubWorld, IntoQuery};
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use instant::Instant;
+use legion::{world::SubWorld, Entity, IntoQuery};
 use std::{
+    cmp::Ordering,
+    collections::HashMap,
     sync::{Arc, RwLock},
-    time::Instant,
 };
+use uuid::Uuid;
 
 use crate::{
-    components::{FrameMetrics, Position2D},
+    components::Position2D,
     constants::{
-        CAMERA_2D_BIND_GROUP_ID, ID, LIGHTING_2D_BIND_GROUP_ID, RENDER_2D_COMMON_TEXTURE_ID,
+        CAMERA_2D_BIND_GROUP_ID, ID, LIGHTING_2D_BIND_GROUP_ID, MAX_TEXTURES_2D_BATCH,
+        RENDER_2D_COMMON_TEXTURE_ID, RENDER_2D_TEXTURE_GROUP,
     },
+    parallel::*,
     renderer::{
-        buffer::instance::{Instance, InstanceBuffer, InstanceGroup, InstanceGroupBinder},
+        buffer::{
+            instance::{
+                Instance, InstanceBuffer, InstanceDraw, InstanceGroup, InstanceGroupBinder,
+            },
+            upload::Uploader,
+        },
         graph::NodeState,
         mesh::Mesh,
+        systems::render_2d::{Layer2D, SpriteMode},
+    },
+    sources::{
+        registry::{MeshRegistry, TextureRegistry},
+        time::Time,
     },
-    sources::registry::MeshRegistry,
 };
 
-#[instance((4, 44usize))]
+#[instance((4, 112usize))]
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Render2DInstance {
@@ -27,8 +40,29 @@ pub struct Render2DInstance {
     pub mix: f32,
     pub group_id: u32,
     pub id: u32,
+    // [u, v, width, height] of the texture to show, eg. a sprite sheet cell
+    pub uv_rect: [f32; 4],
+    // Draw order within the group, lowest first; see Layer2D for ordering between groups
+    pub zindex: f32,
+    // From set_sprite_mode
+    pub sprite_mode: u32,
+    pub sprite_params: [f32; 4],
+    // Which of the group's textures to show: 0 for its texture, or an index from
+    // InstanceGroup::add_texture
+    pub texture: u32,
+    // Where that texture is bound in the group's batch; set by the load system
+    pub texture_slot: u32,
+    // [pivot x, pivot y, rotation, 0]: the point of the mesh (in its own coordinates, so -1 to
+    // 1 across the unit square) placed at the position and rotated around, counterclockwise in
+    // radians. The center, unrotated, by default.
+    pub transform: [f32; 4],
+    // FLIP_2D_X | FLIP_2D_Y, mirroring the texture within the instance
+    pub flip: u32,
 }
 
+pub const FLIP_2D_X: u32 = 1;
+pub const FLIP_2D_Y: u32 = 2;
+
 impl Render2DInstance {
     pub fn new(color: [f32; 4]) -> Self {
         Self {
@@ -37,9 +71,59 @@ impl Render2DInstance {
             mix: 1.0,
             group_id: 0,
             id: 0,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            zindex: 0.0,
+            sprite_mode: 0,
+            sprite_params: [0.0; 4],
+            texture: 0,
+            texture_slot: 0,
+            transform: [0.0; 4],
+            flip: 0,
         }
     }
 
+    // Within uv_rect, so sprite sheet cells can be nine-sliced or tiled too
+    pub fn set_sprite_mode(&mut self, mode: SpriteMode) {
+        let (sprite_mode, sprite_params) = mode.params();
+        self.sprite_mode = sprite_mode;
+        self.sprite_params = sprite_params;
+    }
+
+    pub fn with_sprite_mode(mut self, mode: SpriteMode) -> Self {
+        self.set_sprite_mode(mode);
+        self
+    }
+
+    pub fn with_texture(mut self, texture: u32) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn set_rotation(&mut self, radians: f32) {
+        self.transform[2] = radians;
+    }
+
+    pub fn with_rotation(mut self, radians: f32) -> Self {
+        self.set_rotation(radians);
+        self
+    }
+
+    // Eg. [0.0, -1.0] to stand a sprite on its bottom edge
+    pub fn with_pivot(mut self, pivot: [f32; 2]) -> Self {
+        self.transform[0] = pivot[0];
+        self.transform[1] = pivot[1];
+        self
+    }
+
+    pub fn set_flip(&mut self, x: bool, y: bool) {
+        self.flip = (x as u32 * FLIP_2D_X) | (y as u32 * FLIP_2D_Y);
+    }
+
+    pub fn with_flip(mut self, x: bool, y: bool) -> Self {
+        self.set_flip(x, y);
+        self
+    }
+
     pub fn new_default_group() -> InstanceGroup<Render2DInstance> {
         InstanceGroup::new(0, ID(RENDER_2D_COMMON_TEXTURE_ID))
     }
@@ -67,7 +151,133 @@ impl Instance for Render2DInstance {
     }
 
     fn size() -> usize {
-        36
+        112
+    }
+}
+
+// 2D instance groups in draw order, batched by texture: a batch binds up to
+// MAX_TEXTURES_2D_BATCH textures from RENDER_2D_TEXTURE_GROUP at once, so groups on the same
+// layer with different textures still share draws (one per mesh). Rebuilt every frame by the
+// load system, which also points each instance's texture_slot into its batch.
+#[derive(Default)]
+pub struct TextureBatches2D {
+    pub batches: Vec<TextureBatch2D>,
+    // By batch textures; see update_sets
+    sets: HashMap<Vec<Uuid>, TextureSet2D>,
+}
+
+pub struct TextureBatch2D {
+    pub layer: Layer2D,
+    pub textures: Vec<Uuid>,
+    pub groups: Vec<Entity>,
+}
+
+// A batch's bind group, and the bind groups of the textures it was built from, which are
+// replaced when a texture is reloaded or streamed in
+struct TextureSet2D {
+    sources: Vec<Arc<wgpu::BindGroup>>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextureBatches2D {
+    // Adds a group's textures to the last batch, or a new one if they don't fit, returning
+    // each one's slot in it
+    fn push(&mut self, entity: Entity, layer: Layer2D, textures: &[Uuid]) -> Vec<u32> {
+        let fits = self.batches.last().map_or(false, |batch| {
+            let new = textures
+                .iter()
+                .filter(|texture| !batch.textures.contains(texture))
+                .count();
+            batch.layer == layer && batch.textures.len() + new <= MAX_TEXTURES_2D_BATCH
+        });
+        if !fits {
+            self.batches.push(TextureBatch2D {
+                layer,
+                textures: vec![],
+                groups: vec![],
+            });
+        }
+
+        let batch = self.batches.last_mut().unwrap();
+        batch.groups.push(entity);
+        textures
+            .iter()
+            .map(
+                |texture| match batch.textures.iter().position(|t| t == texture) {
+                    Some(slot) => slot as u32,
+                    None => {
+                        batch.textures.push(*texture);
+                        batch.textures.len() as u32 - 1
+                    }
+                },
+            )
+            .collect()
+    }
+
+    // Builds bind groups for new (or changed) texture sets, and drops unused ones
+    fn update_sets(&mut self, device: &wgpu::Device, textures: &TextureRegistry) {
+        let group_id = ID(RENDER_2D_TEXTURE_GROUP);
+        let mut sets = HashMap::new();
+        for batch in &self.batches {
+            let sources: Vec<Arc<wgpu::BindGroup>> = batch
+                .textures
+                .iter()
+                .filter_map(|id| {
+                    textures
+                        .textures
+                        .get(&group_id)?
+                        .get(id)?
+                        .bind_group
+                        .clone()
+                })
+                .collect();
+            let current = match self.sets.remove(&batch.textures) {
+                Some(set)
+                    if set.sources.len() == sources.len()
+                        && set
+                            .sources
+                            .iter()
+                            .zip(&sources)
+                            .all(|(a, b)| Arc::ptr_eq(a, b)) =>
+                {
+                    set
+                }
+                _ => match textures.image_array(
+                    device,
+                    &group_id,
+                    &batch.textures,
+                    MAX_TEXTURES_2D_BATCH,
+                ) {
+                    Ok(bind_group) => TextureSet2D {
+                        sources,
+                        bind_group,
+                    },
+                    Err(err) => {
+                        warn!("can't bind 2D instance textures: {}", err);
+                        continue;
+                    }
+                },
+            };
+            sets.insert(batch.textures.clone(), current);
+        }
+        self.sets = sets;
+    }
+}
+
+// Draws in a batch are per mesh: meshes cloned from the MeshRegistry can be shared by groups,
+// other ones are the group's own
+#[derive(Clone, Copy, PartialEq)]
+enum BatchMesh {
+    Registry(Uuid, Uuid),
+    Group(Entity, Option<Uuid>),
+}
+
+impl BatchMesh {
+    fn of(mesh: &Mesh, entity: Entity, mesh_id: Option<Uuid>) -> Self {
+        match mesh.source {
+            Some((group_id, mesh_id)) => BatchMesh::Registry(group_id, mesh_id),
+            None => BatchMesh::Group(entity, mesh_id),
+        }
     }
 }
 
@@ -80,10 +290,16 @@ pub struct Render2DUniformGroup {}
 #[system]
 #[write_component(InstanceGroup<Render2DInstance>)]
 #[write_component(Mesh)]
-pub fn load(world: &mut SubWorld, #[resource] frame_metrics: &Arc<RwLock<FrameMetrics>>) {
+#[read_component(Layer2D)]
+pub fn load(
+    world: &mut SubWorld,
+    #[resource] time: &Time,
+    #[resource] texture_batches: &mut TextureBatches2D,
+) {
     debug!("running system render_2d_instance_loader");
-    let delta = frame_metrics.read().unwrap().delta().as_secs_f32();
-    <(&mut InstanceGroup<Render2DInstance>, &Mesh)>::query().par_for_each_mut(
+    let delta = time.delta_secs();
+    par_for_each_mut!(
+        <(&mut InstanceGroup<Render2DInstance>, &Mesh)>::query(),
         world,
         |(group, _)| {
             let components = Arc::clone(&group.components);
@@ -92,9 +308,42 @@ pub fn load(world: &mut SubWorld, #[resource] frame_metrics: &Arc<RwLock<FrameMe
                 for component in &mutators[instance.id as usize] {
                     component.lock().unwrap().mutate(instance, delta);
                 }
-            })
+            });
         },
     );
+
+    // Stable, so groups on the same layer keep their ECS order
+    let mut groups = <(
+        Entity,
+        &mut InstanceGroup<Render2DInstance>,
+        &Mesh,
+        Option<&Layer2D>,
+    )>::query()
+    .iter_mut(world)
+    .map(|(entity, group, _, layer)| (*entity, group, layer.copied().unwrap_or_default()))
+    .collect::<Vec<_>>();
+    groups.sort_by_key(|(_, _, layer)| *layer);
+
+    texture_batches.batches.clear();
+    for (entity, group, layer) in groups {
+        let mut textures = group.texture_ids();
+        if textures.len() > MAX_TEXTURES_2D_BATCH {
+            warn!(
+                "instance group {}: {} textures, only the first {} are drawn",
+                group.id,
+                textures.len(),
+                MAX_TEXTURES_2D_BATCH
+            );
+            textures.truncate(MAX_TEXTURES_2D_BATCH);
+        }
+        let slots = texture_batches.push(entity, layer, &textures);
+        for instance in group.instances.iter_mut() {
+            instance.texture_slot = slots
+                .get(instance.texture as usize)
+                .copied()
+                .unwrap_or(slots[0]);
+        }
+    }
 }
 
 #[system]
@@ -104,26 +353,95 @@ pub fn render(
     world: &SubWorld,
     #[state] state: &mut NodeState,
     #[resource] mesh_registry: &Arc<RwLock<MeshRegistry>>,
+    #[resource] texture_registry: &Arc<RwLock<TextureRegistry>>,
+    #[resource] texture_batches: &mut TextureBatches2D,
     #[resource] instance_buffer: &InstanceBuffer<Render2DInstance>,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     let start_time = Instant::now();
     debug!("running system render_2d_forward_instance (graph node)");
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
     let mesh_registry = mesh_registry.read().unwrap();
 
+    texture_batches.update_sets(device, &texture_registry.read().unwrap());
+    let texture_batches = &*texture_batches;
+
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("render_2d_forward_instance_encoder"),
     });
 
+    let groups = <(Entity, &InstanceGroup<Render2DInstance>, &Mesh)>::query()
+        .iter(world)
+        .map(|(entity, group, mesh)| (*entity, (group, mesh)))
+        .collect::<HashMap<_, _>>();
+
+    let mut offset = 0;
+    let mut draws = vec![];
+    for batch in &texture_batches.batches {
+        let texture = match texture_batches.sets.get(&batch.textures) {
+            Some(set) => &set.bind_group,
+            None => continue,
+        };
+
+        // Every instance in the batch, by mesh, in the order the meshes are first seen
+        let mut partitions: Vec<(BatchMesh, &Mesh, Vec<Render2DInstance>)> = vec![];
+        for entity in &batch.groups {
+            let (group, mesh) = match groups.get(entity) {
+                Some(group) => *group,
+                None => continue,
+            };
+            debug!(
+                "rendering instance group => type: render_2d, name: {}, size: {}",
+                "",
+                group.num_instances()
+            );
+            for (instance, mesh_id) in group.instances.iter().zip(&group.mesh_ids) {
+                let instance_mesh = match mesh_id {
+                    Some(id) => match group.meshes.get(id) {
+                        Some(instance_mesh) => instance_mesh,
+                        None => continue,
+                    },
+                    None => mesh,
+                };
+                let key = BatchMesh::of(instance_mesh, *entity, *mesh_id);
+                match partitions
+                    .iter_mut()
+                    .find(|(partition, _, _)| *partition == key)
+                {
+                    Some((_, _, instances)) => instances.push(*instance),
+                    None => partitions.push((key, instance_mesh, vec![*instance])),
+                }
+            }
+        }
+
+        // One instance buffer is managed per group type
+        // (in this case: InstanceBuffer<Render2DInstance>)
+        for (_, mesh, mut instances) in partitions {
+            // Stable, so instances with the same zindex keep their group's order
+            instances.sort_by(|a, b| a.zindex.partial_cmp(&b.zindex).unwrap_or(Ordering::Equal));
+            let bytes: &[u8] = bytemuck::cast_slice(instances.as_slice());
+            let range = offset..offset + bytes.len() as u64;
+            instance_buffer.load_group(offset, bytes);
+            offset = range.end;
+            draws.push(InstanceDraw {
+                texture,
+                mesh,
+                range,
+                instances: instances.len() as u32,
+            });
+        }
+    }
+    instance_buffer.load_draws(&mut encoder, &draws);
+
     let render_target = state.render_target();
     let render_target_mut = render_target.lock().unwrap();
 
     let mut pass = render_target_mut
         .create_render_pass("render_2d_forward_instance_pass", &mut encoder, true)
         .unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
     // Global bindings
     pass.set_bind_group(
@@ -136,39 +454,12 @@ pub fn render(
         &node.binder.uniform_groups[&ID(LIGHTING_2D_BIND_GROUP_ID)],
         &[],
     );
-
-    for (group, mesh) in <(&InstanceGroup<Render2DInstance>, &Mesh)>::query().iter(world) {
-        debug!(
-            "rendering instance group => type: render_2d, name: {}, size: {}",
-            "",
-            group.num_instances()
-        );
-
-        // One instance buffer is managed per group type
-        // (in this case: InstanceBuffer<Render2DInstance>)
-        instance_buffer.load_group(group.buffer_bytes());
-
-        // Every instance in a group shares the same texture and mesh
-        pass.set_bind_group(0, &node.binder.texture_groups[&group.texture()], &[]);
-        pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
-        pass.set_index_buffer(
-            mesh.index_buffer.buffer.0.slice(..),
-            wgpu::IndexFormat::Uint32,
-        );
-
-        // Load and draw all instances in the group
-        pass.set_vertex_buffer(1, instance_buffer.state.buffer.slice(..));
-        pass.draw_indexed(
-            0..mesh.index_buffer.buffer.1,
-            0,
-            0..group.num_instances() as _,
-        );
-    }
+    instance_buffer.draw(&mut pass, &draws);
 
     debug!("done recording; submitting render pass");
     drop(pass);
     drop(mesh_registry);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("render_2d_forward_instance pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
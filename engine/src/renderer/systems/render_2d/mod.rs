@@ -5,6 +5,8 @@ use crate::constants::{ID, RENDER_2D_COMMON_TEXTURE_ID, UNIT_SQUARE_MESH_ID};
 
 pub mod forward_dynamic;
 pub mod forward_instance;
+pub mod particles_gpu;
+pub mod text;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Render2D {
@@ -19,8 +21,53 @@ pub struct Render2D {
     pub height: f32,
 
     pub mesh: Uuid,
+
+    // Draw order within a Layer2D, lowest first
+    pub zindex: f32,
+
+    pub sprite_mode: SpriteMode,
+}
+
+// How a sprite's texture (or an instance's uv_rect) fills its width and height
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpriteMode {
+    Stretch,
+    // Borders in texels, drawn at one world unit per texel while the middle stretches, so
+    // panels can be any size
+    NineSlice {
+        left: f32,
+        right: f32,
+        top: f32,
+        bottom: f32,
+    },
+    // Repeats every width x height world units, eg. backgrounds
+    Tiled {
+        width: f32,
+        height: f32,
+    },
 }
 
+impl SpriteMode {
+    // (mode, params) as the 2D shaders take them; see shaders/include/sprite_2d.wgsl
+    pub fn params(&self) -> (u32, [f32; 4]) {
+        match *self {
+            SpriteMode::Stretch => (0, [0.0; 4]),
+            SpriteMode::NineSlice {
+                left,
+                right,
+                top,
+                bottom,
+            } => (1, [left, right, top, bottom]),
+            SpriteMode::Tiled { width, height } => (2, [width, height, 0.0, 0.0]),
+        }
+    }
+}
+
+// Draw order between 2D entities (and instance groups), lowest first. Entities without one
+// are on layer 0; within a layer they're ordered by zindex, then kept in ECS order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Layer2D(pub i32);
+
 impl Render2D {
     pub fn _test(name: &str, width: f32, height: f32) -> Self {
         Render2D::solid_rect(name, width, height, [1.0, 1.0, 1.0, 1.0])
@@ -35,6 +82,8 @@ impl Render2D {
             height,
             texture: Uuid::from_str(RENDER_2D_COMMON_TEXTURE_ID).unwrap(),
             mesh: ID(UNIT_SQUARE_MESH_ID),
+            zindex: 0.0,
+            sprite_mode: SpriteMode::Stretch,
         }
     }
 
@@ -47,8 +96,15 @@ impl Render2D {
             height,
             texture,
             mesh: ID(UNIT_SQUARE_MESH_ID),
+            zindex: 0.0,
+            sprite_mode: SpriteMode::Stretch,
         }
     }
+
+    pub fn with_sprite_mode(mut self, sprite_mode: SpriteMode) -> Self {
+        self.sprite_mode = sprite_mode;
+        self
+    }
 }
 
 // pub fn _flatten(mat: Matrix2<f32>) -> [f32; 4] {
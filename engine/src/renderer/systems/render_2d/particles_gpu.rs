@@ -0,0 +1,408 @@
+use instant::Instant;
+use legion::{world::SubWorld, IntoQuery};
+use rand::RngCore;
+use std::sync::{Arc, Mutex};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    constants::{CAMERA_2D_BIND_GROUP_ID, ID},
+    renderer::{buffer::upload::Uploader, graph::NodeState},
+    sources::{rng::EngineRng, time::Time},
+    systems::particle_2d::{
+        EmitterMode, EmitterShape, Interpolator, ParticleEmitter2D, SmoothF32x2, SmoothF32x4,
+    },
+};
+
+// Per system; any more are ignored
+pub const MAX_GPU_EMITTERS: usize = 8;
+const WORKGROUP_SIZE: u32 = 64;
+
+// Particles live in a storage buffer which is read as an instance buffer by the draw
+#[instance((0, 48usize))]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GPUParticle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub scale: [f32; 2],
+    // -1 when dead
+    pub age: f32,
+    pub padding: f32,
+    pub color: [f32; 4],
+}
+
+impl Default for GPUParticle {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            velocity: [0.0, 0.0],
+            scale: [0.0, 0.0],
+            age: -1.0,
+            padding: 0.0,
+            color: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+// See Emitter in particle_2d_update.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct GPUEmitter {
+    shape: [f32; 4],
+    config: [f32; 4],
+    flags: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct GPUParticleParams {
+    lifetime: f32,
+    delta: f32,
+    seed: u32,
+    spawn: u32,
+    num_particles: u32,
+    num_emitters: u32,
+    padding: [u32; 2],
+    speed: [f32; 4],
+    scale: [f32; 4],
+    color_from: [f32; 4],
+    color_to: [f32; 4],
+    emitters: [GPUEmitter; MAX_GPU_EMITTERS],
+}
+
+// [vertex_count, instance_count, base_vertex, base_instance, spawned], reset every update
+const DRAW_ARGS_RESET: [u32; 8] = [6, 0, 0, 0, 0, 0, 0, 0];
+
+// Same configuration as ParticleSystem2D (but lerping between two values, rather than along
+// Curves), with emission and mutation in a compute shader, so it scales to hundreds of
+// thousands of particles. Particles are drawn as solid quads by the particles_gpu node;
+// there's no InstanceGroup to add.
+pub struct ParticleSystemGPU {
+    pub num_particles: u32,
+
+    pub emitters: Vec<Arc<Mutex<ParticleEmitter2D>>>,
+
+    pub lifetime: f32,
+    pub scale: Interpolator<SmoothF32x2>,
+    pub speed: Interpolator<SmoothF32x2>,
+    pub color: Interpolator<SmoothF32x4>,
+
+    // Created by the first update
+    buffers: Option<ParticleBuffers>,
+}
+
+impl Default for ParticleSystemGPU {
+    fn default() -> Self {
+        Self::new(
+            3.0,
+            Interpolator::<SmoothF32x2>::new([3.0, 3.0], [0.0, 0.0]),
+            Interpolator::<SmoothF32x2>::new([3.0, 3.0], [-2.0, -2.0]),
+            Interpolator::<SmoothF32x4>::new([1.0, 1.0, 0.0, 1.0], [1.0, 0.0, 1.0, 1.0]),
+            100_000,
+            vec![],
+        )
+    }
+}
+
+impl ParticleSystemGPU {
+    pub fn new(
+        lifetime: f32,
+        speed: Interpolator<SmoothF32x2>,
+        scale: Interpolator<SmoothF32x2>,
+        color: Interpolator<SmoothF32x4>,
+        num_particles: u32,
+        emitters: Vec<ParticleEmitter2D>,
+    ) -> Self {
+        Self {
+            emitters: emitters.into_iter().map(Mutex::new).map(Arc::new).collect(),
+            num_particles,
+            lifetime,
+            speed,
+            scale,
+            color,
+            buffers: None,
+        }
+    }
+
+    pub fn push(&mut self, emitter: ParticleEmitter2D) {
+        self.emitters.push(Arc::new(Mutex::new(emitter)));
+    }
+
+    // Also advances the emitters (and Direction modes), like ParticleEmitter2D::emit. The
+    // shader draws its randomness from seed.
+    fn params(&self, delta: f32, seed: u32) -> GPUParticleParams {
+        if self.emitters.len() > MAX_GPU_EMITTERS {
+            warn!(
+                "gpu particle system has {} emitters, only the first {} are used",
+                self.emitters.len(),
+                MAX_GPU_EMITTERS
+            );
+        }
+
+        let mut params = GPUParticleParams {
+            lifetime: self.lifetime,
+            delta,
+            seed,
+            num_particles: self.num_particles,
+            speed: concat2(self.speed.initial().0, self.speed.target().0),
+            scale: concat2(self.scale.initial().0, self.scale.target().0),
+            color_from: self.color.initial().0,
+            color_to: self.color.target().0,
+            ..Default::default()
+        };
+        for (i, emitter) in self.emitters.iter().take(MAX_GPU_EMITTERS).enumerate() {
+            let mut emitter = emitter.lock().unwrap();
+            let rate = emitter.advance(delta);
+            params.emitters[i] = gpu_emitter(&emitter, rate);
+            params.spawn += rate;
+            params.num_emitters += 1;
+
+            let zones = emitter.zones;
+            if let EmitterMode::Direction { next, reverse } = &mut emitter.mode {
+                if zones > 0 {
+                    *next = if *reverse {
+                        (*next + zones - rate % zones) % zones
+                    } else {
+                        (*next + rate) % zones
+                    };
+                }
+            }
+        }
+        params
+    }
+}
+
+fn concat2(from: [f32; 2], to: [f32; 2]) -> [f32; 4] {
+    [from[0], from[1], to[0], to[1]]
+}
+
+// rate being how many it emits this frame
+fn gpu_emitter(emitter: &ParticleEmitter2D, rate: u32) -> GPUEmitter {
+    let [x, y] = emitter.position;
+    let (shape, angle, kind, reverse_shape) = match emitter.shape {
+        EmitterShape::Line { end, reverse } => ([x, y, end[0], end[1]], 0.0, 0, reverse as u32),
+        EmitterShape::Arc { radius, angle } => ([x, y, radius[0], radius[1]], angle, 1, 0),
+        EmitterShape::Rectangle { size } => ([x, y, size[0], size[1]], 0.0, 2, 0),
+        EmitterShape::Ring { inner, outer } => ([x, y, inner, outer], 0.0, 3, 0),
+        EmitterShape::Point { direction, spread } => {
+            ([x, y, direction[0], direction[1]], spread, 4, 0)
+        }
+    };
+    let (mode, next) = match emitter.mode {
+        EmitterMode::Random => (0, 0),
+        EmitterMode::Direction { next, reverse } => (1 + reverse as u32, next),
+    };
+    GPUEmitter {
+        shape,
+        config: [angle, emitter.zones as f32, next as f32, 0.0],
+        flags: [kind, reverse_shape, mode, rate],
+    }
+}
+
+struct ParticleBuffers {
+    num_particles: u32,
+    params: wgpu::Buffer,
+    particles: wgpu::Buffer,
+    args: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ParticleBuffers {
+    fn new(device: &wgpu::Device, pipeline: &ParticlePipelineGPU, num_particles: u32) -> Self {
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles_gpu_params"),
+            size: std::mem::size_of::<GPUParticleParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let particles = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("particles_gpu_particles"),
+            contents: bytemuck::cast_slice(&vec![GPUParticle::default(); num_particles as usize]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+        let args = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("particles_gpu_args"),
+            contents: bytemuck::cast_slice(&DRAW_ARGS_RESET),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particles_gpu_bind_group"),
+            layout: &pipeline.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: args.as_entire_binding(),
+                },
+            ],
+        });
+        Self {
+            num_particles,
+            params,
+            particles,
+            args,
+            bind_group,
+        }
+    }
+}
+
+// The render graph only builds render pipelines, so the update pipeline lives here as a
+// resource (inserted by EngineBuilder::default_2d)
+pub struct ParticlePipelineGPU {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl ParticlePipelineGPU {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particles_gpu_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("particles_gpu_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("particle_2d_update"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../shaders/particle_2d_update.wgsl").into(),
+            ),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("particles_gpu_pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_main",
+        });
+        Self { pipeline, layout }
+    }
+}
+
+// Emits and mutates every ParticleSystemGPU. Scheduled in Stage::Simulation, so particles
+// freeze while the engine is paused.
+#[system]
+#[write_component(ParticleSystemGPU)]
+pub fn update(
+    world: &mut SubWorld,
+    #[resource] pipeline: &ParticlePipelineGPU,
+    #[resource] time: &Time,
+    #[resource] rng: &mut EngineRng,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system particles_gpu_update");
+    let delta = time.delta_secs();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("particles_gpu_update_encoder"),
+    });
+
+    for system in <&mut ParticleSystemGPU>::query().iter_mut(world) {
+        if system.num_particles == 0 {
+            continue;
+        }
+        let params = system.params(delta, rng.next_u32());
+        let num_particles = system.num_particles;
+        if system
+            .buffers
+            .as_ref()
+            .map_or(true, |buffers| buffers.num_particles != num_particles)
+        {
+            system.buffers = Some(ParticleBuffers::new(device, pipeline, num_particles));
+        }
+        let buffers = system.buffers.as_ref().unwrap();
+
+        // Written before the encoder's commands run
+        queue.write_buffer(&buffers.params, 0, bytemuck::bytes_of(&params));
+        queue.write_buffer(&buffers.args, 0, bytemuck::cast_slice(&DRAW_ARGS_RESET));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("particles_gpu_update_pass"),
+        });
+        pass.set_pipeline(&pipeline.pipeline);
+        pass.set_bind_group(0, &buffers.bind_group, &[]);
+        pass.dispatch((num_particles + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+    }
+
+    uploader.submit(encoder.finish());
+}
+
+#[system]
+#[read_component(ParticleSystemGPU)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    let start_time = Instant::now();
+    debug!("running system particles_gpu_render (graph node)");
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("particles_gpu_render_encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass("particles_gpu_render", &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: particles_gpu_render");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(
+        0,
+        &node.binder.uniform_groups[&ID(CAMERA_2D_BIND_GROUP_ID)],
+        &[],
+    );
+
+    // The instance count is the highest live particle, written by the update pass
+    for system in <&ParticleSystemGPU>::query().iter(world) {
+        if let Some(buffers) = &system.buffers {
+            pass.set_vertex_buffer(0, buffers.particles.slice(..));
+            pass.draw_indirect(&buffers.args, 0);
+        }
+    }
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
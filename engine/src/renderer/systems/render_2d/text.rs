@@ -0,0 +1,344 @@
+use anyhow::Result;
+use fontdue::Font;
+use instant::Instant;
+use legion::{world::SubWorld, IntoQuery};
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::{Arc, Mutex, RwLock},
+};
+use uuid::Uuid;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    components::Position2D,
+    constants::{CAMERA_2D_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID},
+    renderer::{
+        buffer::{texture::Texture, upload::Uploader},
+        graph::NodeState,
+    },
+    sources::{camera::Camera2D, fonts::FontRegistry},
+};
+
+// Text drawn by the render_text overlay node, at the entity's Position2D: the top left of
+// the first line. Glyphs are rasterized on first use into a shared atlas, at the text's size
+// in physical pixels (see Camera2D::pixels_per_unit), rounded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text2D {
+    pub content: String,
+    pub font: Uuid,
+    // Em size, in world units (or logical points in screen space)
+    pub size: f32,
+    pub color: [f32; 4],
+    // Position2D in logical points from the window's top left (HUDs), instead of world
+    // space (labels)
+    pub screen_space: bool,
+}
+
+impl Text2D {
+    pub fn new(content: &str, font: Uuid, size: f32) -> Self {
+        Self {
+            content: content.to_owned(),
+            font,
+            size,
+            color: [1.0, 1.0, 1.0, 1.0],
+            screen_space: false,
+        }
+    }
+
+    pub fn screen(mut self) -> Self {
+        self.screen_space = true;
+        self
+    }
+}
+
+#[vertex((0, 36usize))]
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub uvs: [f32; 2],
+    pub color: [f32; 4],
+    // 1.0 for screen space, where position is in physical pixels
+    pub screen: f32,
+}
+
+unsafe impl bytemuck::Pod for TextVertex {}
+unsafe impl bytemuck::Zeroable for TextVertex {}
+
+const ATLAS_SIZE: u32 = 1024;
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Clone, Copy, Debug)]
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    size: [f32; 2],
+    // Bottom left of the bitmap, from the pen position on the baseline
+    offset: [f32; 2],
+    advance: f32,
+}
+
+// Shelf packed: glyphs fill rows left to right, and a row is as tall as its tallest glyph.
+// Once full, the atlas is cleared and refilled with whatever the current frame needs.
+pub struct GlyphAtlas {
+    pub texture: Texture,
+    glyphs: HashMap<(Uuid, char, u32), Glyph>,
+    cursor: (u32, u32),
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Result<Self> {
+        Ok(Self {
+            texture: Texture::blank(
+                (ATLAS_SIZE, ATLAS_SIZE),
+                device,
+                wgpu::TextureFormat::Rgba8Unorm,
+                layout,
+                Some("glyph_atlas"),
+                false,
+            )?,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        debug!("glyph atlas full, clearing {} glyphs", self.glyphs.len());
+        self.glyphs.clear();
+        self.cursor = (0, 0);
+        self.row_height = 0;
+    }
+
+    // None once the atlas is full
+    fn glyph(
+        &mut self,
+        font_id: Uuid,
+        font: &Font,
+        character: char,
+        px: u32,
+        queue: &wgpu::Queue,
+    ) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&(font_id, character, px)) {
+            return Some(*glyph);
+        }
+
+        let (metrics, coverage) = font.rasterize(character, px as f32);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        if self.cursor.0 + width + GLYPH_PADDING > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + GLYPH_PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + height > ATLAS_SIZE || width > ATLAS_SIZE {
+            return None;
+        }
+
+        let (x, y) = self.cursor;
+        if width > 0 && height > 0 {
+            let rgba: Vec<u8> = coverage
+                .iter()
+                .flat_map(|alpha| vec![255, 255, 255, *alpha])
+                .collect();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * width),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.cursor.0 += width + GLYPH_PADDING;
+        self.row_height = self.row_height.max(height);
+
+        let glyph = Glyph {
+            uv_min: [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32],
+            uv_max: [
+                (x + width) as f32 / ATLAS_SIZE as f32,
+                (y + height) as f32 / ATLAS_SIZE as f32,
+            ],
+            size: [width as f32, height as f32],
+            offset: [metrics.xmin as f32, metrics.ymin as f32],
+            advance: metrics.advance_width,
+        };
+        self.glyphs.insert((font_id, character, px), glyph);
+        Some(glyph)
+    }
+}
+
+// None if the atlas filled up along the way
+fn build_vertices(
+    world: &mut SubWorld,
+    fonts: &FontRegistry,
+    atlas: &mut GlyphAtlas,
+    queue: &wgpu::Queue,
+    camera: &Camera2D,
+) -> Option<Vec<TextVertex>> {
+    let mut vertices: Vec<TextVertex> = vec![];
+
+    let mut query = <(&Text2D, &Position2D)>::query();
+    for (text, position) in query.iter(world) {
+        let font = match fonts.get(&text.font) {
+            Some(font) => font,
+            None => continue,
+        };
+        // Physical pixels per unit of the text's size
+        let scale = match text.screen_space {
+            true => camera.scale_factor,
+            false => camera.pixels_per_unit(),
+        };
+        let px = (text.size * scale).round().max(1.0) as u32;
+        let (ascent, line_height) = match font.horizontal_line_metrics(px as f32) {
+            Some(line) => (line.ascent, line.new_line_size),
+            None => (px as f32, px as f32 * 1.2),
+        };
+        let screen = match text.screen_space {
+            true => 1.0,
+            false => 0.0,
+        };
+
+        // Laid out in pixels with y up from the top left; screen space flips it back
+        let to_position = |x: f32, y: f32| -> [f32; 2] {
+            match text.screen_space {
+                true => [position.x * scale + x, position.y * scale - y],
+                false => [position.x + x / scale, position.y + y / scale],
+            }
+        };
+
+        let mut pen = [0.0, -ascent];
+        let mut previous: Option<char> = None;
+        for character in text.content.chars() {
+            if character == '\n' {
+                pen = [0.0, pen[1] - line_height];
+                previous = None;
+                continue;
+            }
+            if let Some(previous) = previous {
+                pen[0] += font
+                    .horizontal_kern(previous, character, px as f32)
+                    .unwrap_or(0.0);
+            }
+            previous = Some(character);
+
+            let glyph = atlas.glyph(text.font, font, character, px, queue)?;
+            if glyph.size[0] > 0.0 && glyph.size[1] > 0.0 {
+                let x0 = pen[0] + glyph.offset[0];
+                let y0 = pen[1] + glyph.offset[1];
+                let (x1, y1) = (x0 + glyph.size[0], y0 + glyph.size[1]);
+
+                // Bitmap rows run top to bottom
+                let vertex = |x: f32, y: f32, u: f32, v: f32| TextVertex {
+                    position: to_position(x, y),
+                    uvs: [u, v],
+                    color: text.color,
+                    screen,
+                };
+                let (uv_min, uv_max) = (glyph.uv_min, glyph.uv_max);
+                let bottom_left = vertex(x0, y0, uv_min[0], uv_max[1]);
+                let bottom_right = vertex(x1, y0, uv_max[0], uv_max[1]);
+                let top_right = vertex(x1, y1, uv_max[0], uv_min[1]);
+                let top_left = vertex(x0, y1, uv_min[0], uv_min[1]);
+                vertices.extend_from_slice(&[
+                    bottom_left,
+                    bottom_right,
+                    top_right,
+                    bottom_left,
+                    top_right,
+                    top_left,
+                ]);
+            }
+            pen[0] += glyph.advance;
+        }
+    }
+
+    Some(vertices)
+}
+
+#[system]
+#[read_component(Text2D)]
+#[read_component(Position2D)]
+pub fn render(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] fonts: &Arc<RwLock<FontRegistry>>,
+    #[resource] atlas: &Arc<Mutex<GlyphAtlas>>,
+    #[resource] camera: &Arc<Mutex<Camera2D>>,
+) {
+    debug!("running system render_text (graph node)");
+    let start_time = Instant::now();
+
+    let fonts = fonts.read().unwrap();
+    let mut atlas = atlas.lock().unwrap();
+    let camera = camera.lock().unwrap();
+    let vertices = match build_vertices(world, &fonts, &mut atlas, queue, &camera) {
+        Some(vertices) => vertices,
+        None => {
+            atlas.clear();
+            build_vertices(world, &fonts, &mut atlas, queue, &camera).unwrap_or_else(|| {
+                warn!("text doesn't fit in the glyph atlas, skipping");
+                vec![]
+            })
+        }
+    };
+    if vertices.is_empty() {
+        return;
+    }
+
+    // Rebuilt every frame
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Text Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Text Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass("render_text", &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: render_text");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, atlas.texture.bind_group.as_ref().unwrap(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(CAMERA_2D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(
+        2,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    pass.draw(0..vertices.len() as u32, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
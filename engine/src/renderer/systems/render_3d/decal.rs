@@ -0,0 +1,243 @@
+use cgmath::SquareMatrix;
+use instant::Instant;
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+use crate::{
+    components::Transform3D,
+    constants::{
+        DECAL_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, RENDER_3D_COMMON_TEXTURE_ID,
+        RENDER_3D_TEXTURE_GROUP,
+    },
+    legion::IntoQuery,
+    renderer::{
+        buffer::{upload::Uploader, VERTEX3D_BUFFER_LAYOUT},
+        graph::{
+            node::{NodeBuilder, ShaderSource},
+            NodeState,
+        },
+        mesh::Mesh,
+        uniform::{
+            generic::GenericUniformBuilder,
+            group::{
+                GroupState, GroupStateBuilder, UniformGroup, UniformGroupBuilder, UniformGroupType,
+            },
+        },
+    },
+    sources::{camera::Camera3D, names::NameIndex, registry::TextureType},
+    systems::camera_3d::matrix2array_4d,
+};
+
+// Textures projected onto the scene (bullet holes, blob shadows, ground markings), as one
+// node right after the scene (PostFxChainBuilder::with_decals), ahead of fog. An entity
+// with a Decal and a Transform3D projects its texture down its local y axis, onto whatever
+// the scene's depth channel has inside its box. Moving objects passing through the box get
+// it too.
+//
+// The node draws the scene first, with a fullscreen triangle, and then each decal's box
+// over it, all with one pipeline: the node's own uniform group tells the shader it's the
+// fullscreen draw, and each Decal's DecalGroupState that it's a box.
+
+pub struct Decal {
+    // In RENDER_3D_TEXTURE_GROUP. Its alpha (times color's) is how much it covers the scene.
+    pub texture: Uuid,
+    pub color: [f32; 4],
+    // Of the box, before the Transform3D's scale; the texture covers x and z, and y is
+    // how far it reaches above and below the position
+    pub size: [f32; 3],
+    // Fraction of the box's height over which the decal fades out, at the top and bottom
+    pub fade: f32,
+}
+
+impl Decal {
+    pub fn new(texture: Uuid, size: [f32; 3]) -> Self {
+        Self {
+            texture,
+            color: [1.0, 1.0, 1.0, 1.0],
+            size,
+            fade: 0.25,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DecalUniforms {
+    // The unit cube to clip space
+    pub mvp: [[f32; 4]; 4],
+    // Clip space (with the depth channel's depth) back to the unit cube
+    pub inv_mvp: [[f32; 4]; 4],
+    pub color: [f32; 4],
+    // [1 for a decal, 0 for the fullscreen draw, fade, 0]
+    pub params: [f32; 4],
+}
+
+impl DecalUniforms {
+    pub fn new(decal: &Decal, transform: &Transform3D, view_proj: cgmath::Matrix4<f32>) -> Self {
+        let model = cgmath::Matrix4::from_translation(transform.position.into())
+            * cgmath::Matrix4::from_angle_x(cgmath::Deg(transform.rotation[0]))
+            * cgmath::Matrix4::from_angle_y(cgmath::Deg(transform.rotation[1]))
+            * cgmath::Matrix4::from_angle_z(cgmath::Deg(transform.rotation[2]))
+            * cgmath::Matrix4::from_nonuniform_scale(
+                transform.scale[0] * decal.size[0],
+                transform.scale[1] * decal.size[1],
+                transform.scale[2] * decal.size[2],
+            );
+        let mvp = view_proj * model;
+        Self {
+            mvp: matrix2array_4d(mvp),
+            inv_mvp: matrix2array_4d(mvp.invert().unwrap_or_else(cgmath::Matrix4::identity)),
+            color: decal.color,
+            params: [1.0, decal.fade, 0.0, 0.0],
+        }
+    }
+}
+
+// Separate from the GroupState that forward_basic gives Render3D entities
+pub struct DecalGroupState(pub GroupState);
+
+// Resource, inserted when the engine has decals
+pub struct DecalVolume {
+    // PRIMITIVE_MESH_GROUP_ID's unit cube
+    pub mesh: Mesh,
+}
+
+pub struct DecalUniformGroup {}
+
+impl UniformGroupType<Self> for DecalUniformGroup {
+    fn builder() -> UniformGroupBuilder<DecalUniformGroup> {
+        UniformGroup::<DecalUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(DecalUniforms {
+                mvp: IDENTITY_MATRIX_4,
+                inv_mvp: IDENTITY_MATRIX_4,
+                color: [1.0, 1.0, 1.0, 1.0],
+                params: [0.0, 0.0, 0.0, 0.0],
+            }))
+            .with_id(ID(DECAL_BIND_GROUP_ID))
+    }
+}
+
+#[system]
+#[read_component(Decal)]
+#[read_component(Transform3D)]
+#[read_component(DecalGroupState)]
+pub fn load(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<DecalUniformGroup>>>,
+) {
+    debug!("running system decal_uniform_loader");
+
+    let group_builder = group_builder.lock().unwrap();
+    let mut query =
+        <(Entity, &Decal, &Transform3D)>::query().filter(!component::<DecalGroupState>());
+    query.for_each(world, |(entity, _, _)| {
+        command_buffer.add_component(
+            *entity,
+            DecalGroupState(group_builder.single_state(device, uploader).unwrap()),
+        );
+    });
+
+    let view_proj = camera.lock().unwrap().build_view_proj();
+    let mut query = <(&Decal, &Transform3D, &DecalGroupState)>::query();
+    par_for_each!(query, world, |(decal, transform, group_state)| {
+        let source = &[DecalUniforms::new(decal, transform, view_proj)];
+        group_state.0.write_buffer(0, bytemuck::cast_slice(source));
+    });
+}
+
+// Reads the scene through node input 0 and its depth buffer through a depth channel
+pub fn build_node_decals() -> NodeBuilder {
+    NodeBuilder::new(
+        "decals".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(include_str!("../../shaders/decal.wgsl").to_owned()),
+    )
+    .with_id(Uuid::new_v4())
+    .with_vertex_layout(VERTEX3D_BUFFER_LAYOUT)
+    .with_node_input()
+    .with_texture_group(ID(RENDER_3D_TEXTURE_GROUP), TextureType::Image)
+    .with_shared_uniform_group(Arc::new(Mutex::new(DecalUniformGroup::builder())))
+    .with_depth_input()
+    // Boxes the camera is inside still draw their far side
+    .with_reverse_culling()
+    .with_system(render_system)
+}
+
+#[system]
+#[read_component(Decal)]
+#[read_component(DecalGroupState)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] volume: &DecalVolume,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
+) {
+    debug!("running system render_decals (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Decal Encoder"),
+    });
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(3, state.inputs[1].bind_group_ref(), &[]);
+    pass.set_vertex_buffer(0, volume.mesh.vertex_buffer.buffer.0.slice(..));
+    pass.set_index_buffer(
+        volume.mesh.index_buffer.buffer.0.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+
+    // The scene, with the node's own group; the texture is unused, but has to be bound
+    let common_texture = &texture_groups[&ID(RENDER_3D_COMMON_TEXTURE_ID)];
+    pass.set_bind_group(1, common_texture, &[]);
+    pass.set_bind_group(
+        2,
+        &node.binder.uniform_groups[&ID(DECAL_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.draw(0..3, 0..1);
+
+    let mut query = <(Entity, &Decal, &DecalGroupState)>::query();
+    for (entity, decal, group_state) in query.iter(world) {
+        let texture = match texture_groups.get(&decal.texture) {
+            Some(texture) => texture,
+            None => {
+                warn!(
+                    "{}: missing decal texture {}, skipping",
+                    names.read().unwrap().label(*entity),
+                    decal.texture
+                );
+                continue;
+            }
+        };
+        pass.set_bind_group(1, texture, &[]);
+        pass.set_bind_group(2, &group_state.0.bind_group, &[]);
+        pass.draw_indexed(0..volume.mesh.index_buffer.buffer.1, 0, 0..1);
+    }
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
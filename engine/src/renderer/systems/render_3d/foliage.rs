@@ -0,0 +1,408 @@
+use cgmath::MetricSpace;
+use instant::Instant;
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity, IntoQuery};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, RwLock},
+};
+use uuid::Uuid;
+
+use crate::{
+    components::Transform3D,
+    constants::{FOLIAGE_WIND_BIND_GROUP_ID, FRAME_BIND_GROUP_ID, ID, RENDER_3D_COMMON_TEXTURE_ID},
+    renderer::{
+        buffer::{
+            instance::{InstanceBuffer, InstanceGroup},
+            upload::Uploader,
+        },
+        graph::NodeState,
+        mesh::Mesh,
+        systems::render_3d::forward_instance::{render_groups, Render3DInstance},
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    sources::{camera::Camera3D, materials::Material, registry::MeshRegistry},
+    systems::{
+        culling::Bounds3D,
+        viewport::{TextureCamera, Viewport, ViewportCamera},
+    },
+};
+
+// Grass, flowers and the like, scattered over a grid of square chunks around the camera
+// (EngineBuilder::with_foliage). Each chunk is an entity with one InstanceGroup, generated
+// the first frame it's within view_distance and despawned once it's past it again. The
+// same seed always scatters a chunk the same way, so chunks which come back look unchanged.
+//
+// Chunks are drawn by their own instanced node, over the forward_basic node's target, with
+// the mesh swaying in the wind above y = 0.
+
+// Resource, inserted when the engine has foliage. Chunks already generated keep the
+// settings they were generated with.
+#[derive(Clone)]
+pub struct FoliageScatter {
+    // (mesh id, mesh group id) in the MeshRegistry
+    pub mesh: (Uuid, Uuid),
+    // In RENDER_3D_TEXTURE_GROUP, mixed with color like any Render3DInstance
+    pub texture: Uuid,
+    pub color: [f32; 4],
+    pub mix: f32,
+
+    pub density: FoliageDensity,
+    // Ground height at (x, z), y = 0 if None
+    pub height: Option<Arc<dyn Fn(f32, f32) -> f32 + Send + Sync>>,
+
+    // In world units
+    pub chunk_size: f32,
+    pub view_distance: f32,
+    // Each instance is scaled uniformly by a random amount between these
+    pub scale: [f32; 2],
+    pub seed: u64,
+    // Chunks generated each frame at most, nearest first
+    pub max_chunks_per_frame: usize,
+
+    pub wind: FoliageWind,
+}
+
+impl FoliageScatter {
+    pub fn new(mesh_id: Uuid, group_id: Uuid, density: FoliageDensity) -> Self {
+        Self {
+            mesh: (mesh_id, group_id),
+            texture: ID(RENDER_3D_COMMON_TEXTURE_ID),
+            color: [0.3, 0.6, 0.2, 1.0],
+            mix: 0.0,
+            density,
+            height: None,
+            chunk_size: 16.0,
+            view_distance: 64.0,
+            scale: [0.8, 1.2],
+            seed: 0,
+            max_chunks_per_frame: 4,
+            wind: FoliageWind::default(),
+        }
+    }
+
+    // The material's albedo and base color. Foliage is lit like any instance, so the rest
+    // of it is ignored.
+    pub fn with_material(mut self, material: &Material) -> Self {
+        self.color = material.base_color;
+        match material.albedo {
+            Some(albedo) => {
+                self.texture = albedo;
+                self.mix = 1.0;
+            }
+            None => self.mix = 0.0,
+        }
+        self
+    }
+
+    pub fn with_height(mut self, height: Arc<dyn Fn(f32, f32) -> f32 + Send + Sync>) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_chunks(mut self, chunk_size: f32, view_distance: f32) -> Self {
+        self.chunk_size = chunk_size;
+        self.view_distance = view_distance;
+        self
+    }
+
+    pub fn with_scale(mut self, min: f32, max: f32) -> Self {
+        self.scale = [min, max];
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_wind(mut self, wind: FoliageWind) -> Self {
+        self.wind = wind;
+        self
+    }
+
+    fn chunk_center(&self, coords: (i32, i32)) -> [f32; 2] {
+        [
+            (coords.0 as f32 + 0.5) * self.chunk_size,
+            (coords.1 as f32 + 0.5) * self.chunk_size,
+        ]
+    }
+
+    fn generate(&self, coords: (i32, i32)) -> InstanceGroup<Render3DInstance> {
+        let mut group = InstanceGroup::new(0, self.texture);
+        let max = self.density.max();
+        if max <= 0.0 {
+            return group;
+        }
+
+        // Uniform candidates, kept in proportion to the density there
+        let seed = self.seed
+            ^ (coords.0 as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (coords.1 as u32 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let candidates = (max * self.chunk_size * self.chunk_size).round() as usize;
+        for _ in 0..candidates {
+            let x = (coords.0 as f32 + rng.gen::<f32>()) * self.chunk_size;
+            let z = (coords.1 as f32 + rng.gen::<f32>()) * self.chunk_size;
+            let yaw = rng.gen_range(0.0..360.0);
+            let scale = self.scale[0] + rng.gen::<f32>() * (self.scale[1] - self.scale[0]);
+            if rng.gen::<f32>() * max >= self.density.at(x, z) {
+                continue;
+            }
+
+            let y = self.height.as_ref().map_or(0.0, |height| height(x, z));
+            let mut instance = Render3DInstance::new(self.color).with_transform(&Transform3D {
+                position: [x, y, z],
+                rotation: [0.0, yaw, 0.0],
+                scale: [scale, scale, scale],
+            });
+            instance.mix = self.mix;
+            group.push(instance, vec![]);
+        }
+        group
+    }
+}
+
+// Instances per square world unit
+#[derive(Clone)]
+pub enum FoliageDensity {
+    Uniform(f32),
+    // Density at (x, z), which should never be more than max
+    Procedural {
+        density: Arc<dyn Fn(f32, f32) -> f32 + Send + Sync>,
+        max: f32,
+    },
+    // Stretched over the square from origin (x, z) to origin + size, with white pixels at max
+    // density. Nothing grows outside it.
+    Map {
+        image: Arc<image::GrayImage>,
+        origin: [f32; 2],
+        size: f32,
+        max: f32,
+    },
+}
+
+impl FoliageDensity {
+    pub fn from_map(path: &str, origin: [f32; 2], size: f32, max: f32) -> anyhow::Result<Self> {
+        Ok(Self::Map {
+            image: Arc::new(image::open(path)?.into_luma8()),
+            origin,
+            size,
+            max,
+        })
+    }
+
+    pub fn max(&self) -> f32 {
+        match self {
+            Self::Uniform(density) => *density,
+            Self::Procedural { max, .. } | Self::Map { max, .. } => *max,
+        }
+    }
+
+    pub fn at(&self, x: f32, z: f32) -> f32 {
+        match self {
+            Self::Uniform(density) => *density,
+            Self::Procedural { density, .. } => density(x, z),
+            Self::Map {
+                image,
+                origin,
+                size,
+                max,
+            } => {
+                let u = (x - origin[0]) / size;
+                let v = (z - origin[1]) / size;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    return 0.0;
+                }
+                let pixel = image.get_pixel(
+                    (u * image.width() as f32) as u32,
+                    (v * image.height() as f32) as u32,
+                );
+                pixel.0[0] as f32 / 255.0 * max
+            }
+        }
+    }
+}
+
+// Sway, in world units at y = 1 in the mesh, growing linearly with height
+#[derive(Clone, Copy, Debug)]
+pub struct FoliageWind {
+    // Along the xz plane
+    pub direction: [f32; 2],
+    pub strength: f32,
+    // Radians per second
+    pub frequency: f32,
+}
+
+impl Default for FoliageWind {
+    fn default() -> Self {
+        Self {
+            direction: [1.0, 0.0],
+            strength: 0.1,
+            frequency: 2.0,
+        }
+    }
+}
+
+// Marks the entities scatter_system generated; forward_instance leaves them to this node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FoliageChunk {
+    pub coords: (i32, i32),
+}
+
+// Resource, separate from the forward_instance node's so the two nodes don't write over
+// each other's instances
+pub struct FoliageInstanceBuffer(pub InstanceBuffer<Render3DInstance>);
+
+pub struct FoliageWindUniformGroup {}
+
+impl UniformGroupType<Self> for FoliageWindUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        let wind = FoliageWind::default();
+        UniformGroup::<FoliageWindUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(FoliageWindUniforms {
+                direction: wind.direction,
+                strength: wind.strength,
+                frequency: wind.frequency,
+            }))
+            .with_id(ID(FOLIAGE_WIND_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FoliageWindUniforms {
+    pub direction: [f32; 2],
+    pub strength: f32,
+    pub frequency: f32,
+}
+
+#[system]
+pub fn load(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] scatter: &FoliageScatter,
+    #[resource] wind_uniforms: &Arc<Mutex<GenericUniform<FoliageWindUniforms>>>,
+    #[resource] wind_uniforms_group: &Arc<Mutex<UniformGroup<FoliageWindUniformGroup>>>,
+) {
+    debug!("running system foliage_uniform_loader");
+    let mut forms = wind_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.direction = scatter.wind.direction;
+    uniforms.strength = scatter.wind.strength;
+    uniforms.frequency = scatter.wind.frequency;
+
+    forms.write_buffer(
+        &queue,
+        wind_uniforms_group.lock().unwrap().default_buffer(0),
+    );
+}
+
+// Runs before frustum_culling_system, so new chunks get their Bounds3D the same frame
+#[system]
+#[read_component(Entity)]
+#[read_component(FoliageChunk)]
+pub fn scatter(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] scatter: &FoliageScatter,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] mesh_registry: &Arc<RwLock<MeshRegistry>>,
+) {
+    debug!("running system foliage_scatter");
+    if scatter.chunk_size <= 0.0 {
+        return;
+    }
+    let pos = camera.lock().unwrap().pos;
+    let camera_xz = cgmath::Point2::new(pos.x, pos.z);
+    let in_range = |coords: (i32, i32), distance: f32| {
+        let center = scatter.chunk_center(coords);
+        camera_xz.distance(center.into()) <= distance
+    };
+
+    // A chunk further out than view_distance before it goes, so chunks on the edge aren't
+    // despawned and regenerated as the camera moves back and forth
+    let keep_distance = scatter.view_distance + scatter.chunk_size;
+    let mut loaded = HashSet::new();
+    <(Entity, &FoliageChunk)>::query().for_each(world, |(entity, chunk)| {
+        if in_range(chunk.coords, keep_distance) {
+            loaded.insert(chunk.coords);
+        } else {
+            command_buffer.remove(*entity);
+        }
+    });
+
+    let reach = (scatter.view_distance / scatter.chunk_size).ceil() as i32 + 1;
+    let cx = (camera_xz.x / scatter.chunk_size).floor() as i32;
+    let cz = (camera_xz.y / scatter.chunk_size).floor() as i32;
+    let mut missing = vec![];
+    for x in cx - reach..=cx + reach {
+        for z in cz - reach..=cz + reach {
+            if !loaded.contains(&(x, z)) && in_range((x, z), scatter.view_distance) {
+                missing.push((x, z));
+            }
+        }
+    }
+    missing.sort_by(|a, b| {
+        let a = camera_xz.distance2(scatter.chunk_center(*a).into());
+        let b = camera_xz.distance2(scatter.chunk_center(*b).into());
+        a.partial_cmp(&b).unwrap()
+    });
+
+    let registry = mesh_registry.read().unwrap();
+    for coords in missing.into_iter().take(scatter.max_chunks_per_frame) {
+        let group = scatter.generate(coords);
+        debug!(
+            "generated foliage chunk {:?} with {} instances",
+            coords,
+            group.num_instances()
+        );
+        // Empty chunks are still spawned, so they aren't generated again every frame
+        let mesh = registry.clone_mesh(&scatter.mesh.0, &scatter.mesh.1);
+        command_buffer.push((group, mesh, FoliageChunk { coords }));
+    }
+}
+
+#[system]
+#[read_component(InstanceGroup<Render3DInstance>)]
+#[read_component(Mesh)]
+#[read_component(Bounds3D)]
+#[read_component(FoliageChunk)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] instance_buffer: &FoliageInstanceBuffer,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    let start_time = Instant::now();
+    debug!("running system render_3d_foliage (graph node)");
+
+    let node = Arc::clone(&state.node);
+    let mut query = <(&InstanceGroup<Render3DInstance>, &Mesh, Option<&Bounds3D>)>::query()
+        .filter(component::<FoliageChunk>());
+    render_groups(
+        world,
+        query.iter(world),
+        state,
+        &instance_buffer.0,
+        device,
+        uploader,
+        &[
+            (3, &*node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)]),
+            (
+                4,
+                &*node.binder.uniform_groups[&ID(FOLIAGE_WIND_BIND_GROUP_ID)],
+            ),
+        ],
+    );
+
+    debug!("render_3d_foliage pass submitted");
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -1,21 +1,21 @@
 use cgmath::{Matrix, SquareMatrix};
+use instant::Instant;
 use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
-use std::{
-    sync::{Arc, Mutex},
-    time::Instant,
-};
+use std::sync::{Arc, Mutex, RwLock};
 use uuid::Uuid;
 
 use crate::{
     components::Transform3D,
     constants::{
-        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, RENDER_3D_BIND_GROUP_ID,
-        RENDER_3D_COMMON_TEXTURE_ID,
+        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, LIGHTING_3D_BIND_GROUP_ID,
+        RENDER_3D_BIND_GROUP_ID, RENDER_3D_COMMON_TEXTURE_ID, SSAO_BIND_GROUP_ID,
     },
     legion::IntoQuery,
     renderer::{
+        buffer::upload::Uploader,
         graph::NodeState,
         mesh::Mesh,
+        systems::render_3d::forward_toon::RenderToon,
         uniform::{
             generic::GenericUniformBuilder,
             group::{
@@ -23,12 +23,19 @@ use crate::{
             },
         },
     },
-    systems::camera_3d::matrix2array_4d,
+    sources::names::NameIndex,
+    systems::{
+        animation::Skeleton,
+        camera_3d::matrix2array_4d,
+        culling::Bounds3D,
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
 };
 
 // Todo: go through all todo comments and make tickets for them
 // Todo: remove unnecessary builders
 
+#[derive(Clone)]
 pub struct Render3D {
     pub name: String,
 
@@ -110,25 +117,30 @@ pub fn load(
     world: &mut SubWorld,
     command_buffer: &mut CommandBuffer,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
     #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<Render3DForwardUniformGroup>>>,
 ) {
     debug!("running system render_3d_forward_basic_uniform_loader (graph node)");
 
-    // Add a GroupState to any Render3D component without one
+    // Add a GroupState to any Render3D component without one (skinned ones are
+    // forward_skinned's, and toon ones forward_toon's)
     let group_builder = group_builder.lock().unwrap();
-    let mut query = <(Entity, &Render3D, &Transform3D)>::query().filter(!component::<GroupState>());
+    let mut query = <(Entity, &Render3D, &Transform3D)>::query()
+        .filter(!component::<GroupState>() & !component::<Skeleton>() & !component::<RenderToon>());
     query.for_each(world, |(entity, builder_3d, _)| {
         debug!(
             "allocating buffers for new render_3d component: {}",
             builder_3d.name
         );
-        command_buffer.add_component(*entity, group_builder.single_state(device, queue).unwrap());
+        command_buffer.add_component(
+            *entity,
+            group_builder.single_state(device, uploader).unwrap(),
+        );
     });
 
     // Load all Render3D components into their GroupStates
     let mut query = <(&Render3D, &Transform3D, &GroupState)>::query();
-    query.par_for_each(world, |(render_3d, transform_3d, group_state)| {
+    par_for_each!(query, world, |(render_3d, transform_3d, group_state)| {
         debug!(
             "loading uniform group state for existing render_3d component: {}",
             render_3d.name
@@ -142,15 +154,22 @@ pub fn load(
 #[read_component(Render3D)]
 #[read_component(Mesh)]
 #[read_component(GroupState)]
+#[read_component(Bounds3D)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
 pub fn render(
     world: &mut SubWorld,
     #[state] state: &mut NodeState,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
 ) {
     debug!("running system render_3d_forward_basic (graph node)");
     let start_time = Instant::now();
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Render3D Encoder"),
@@ -166,35 +185,75 @@ pub fn render(
     }
 
     let mut pass = pass_res.unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
     pass.set_bind_group(
-        2,
-        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        3,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
         &[],
     );
 
-    let mut query = <(&Render3D, &Mesh, &GroupState)>::query();
-    for (render_3d, mesh, group_state) in query.iter(world) {
-        pass.set_bind_group(0, &node.binder.texture_groups[&render_3d.texture], &[]);
-        pass.set_bind_group(1, &group_state.bind_group, &[]);
+    // SHADOW MAP INPUTS (directional, then point; see render_3d::shadow)
+    pass.set_bind_group(4, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(5, state.inputs[1].bind_group_ref(), &[]);
 
-        pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
-        pass.set_index_buffer(
-            mesh.index_buffer.buffer.0.slice(..),
-            wgpu::IndexFormat::Uint32,
-        );
+    // AMBIENT OCCLUSION INPUT (see render_3d::ssao)
+    if let Some(ssao_group) = node.binder.uniform_groups.get(&ID(SSAO_BIND_GROUP_ID)) {
+        pass.set_bind_group(6, state.inputs[2].bind_group_ref(), &[]);
+        pass.set_bind_group(7, ssao_group, &[]);
+    }
 
-        // info!(
-        //     "RENDER 3D drawing entity with {} triangles",
-        //     mesh.indices.len() / 3
-        // );
-        pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+    // Once per view; see systems::viewport
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    let SceneViews { views, cull } = SceneViews::new(world, state, camera, size);
+    for (rect, camera_group) in views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(2, camera_group, &[]);
+
+        let mut query = <(Entity, &Render3D, &Mesh, &GroupState, Option<&Bounds3D>)>::query();
+        for (entity, render_3d, mesh, group_state, bounds) in query.iter(world) {
+            // Outside the camera; see systems::culling
+            if cull && !bounds.map_or(true, |bounds| bounds.visible) {
+                continue;
+            }
+            // The texture being drawn to can't be sampled
+            if render_3d.texture == node.id {
+                continue;
+            }
+            let texture = match texture_groups.get(&render_3d.texture) {
+                Some(texture) => texture,
+                None => {
+                    warn!(
+                        "{}: missing texture {}, skipping",
+                        names.read().unwrap().label(*entity),
+                        render_3d.texture
+                    );
+                    continue;
+                }
+            };
+            pass.set_bind_group(0, texture, &[]);
+            pass.set_bind_group(1, &group_state.bind_group, &[]);
+
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+
+            // info!(
+            //     "RENDER 3D drawing entity with {} triangles",
+            //     mesh.indices.len() / 3
+            // );
+            pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+        }
     }
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("forward_render_3d pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
@@ -0,0 +1,294 @@
+use cgmath::Matrix4;
+use instant::Instant;
+use legion::{component, world::SubWorld, IntoQuery};
+use std::sync::Arc;
+
+use crate::{
+    components::Transform3D,
+    constants::{
+        CAMERA_3D_BIND_GROUP_ID, ID, LIGHTING_3D_BIND_GROUP_ID, RENDER_3D_COMMON_TEXTURE_ID,
+    },
+    parallel::*,
+    renderer::{
+        buffer::{
+            instance::{
+                Instance, InstanceBuffer, InstanceDraw, InstanceGroup, InstanceGroupBinder,
+            },
+            upload::Uploader,
+        },
+        graph::NodeState,
+        mesh::Mesh,
+        systems::render_3d::foliage::FoliageChunk,
+    },
+    sources::time::Time,
+    systems::{
+        camera_3d::matrix2array_4d,
+        culling::Bounds3D,
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
+};
+
+// Lots of copies of one mesh (foliage, debris) in one draw call: an entity with a Mesh and an
+// InstanceGroup<Render3DInstance>. Drawn over the forward_basic node's target, unshadowed.
+#[instance((5, 92usize))]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Render3DInstance {
+    // Model matrix, by column. Normals use it as is, so keep scaling uniform.
+    pub model_x: [f32; 4],
+    pub model_y: [f32; 4],
+    pub model_z: [f32; 4],
+    pub model_w: [f32; 4],
+    pub color: [f32; 4],
+    pub mix: f32,
+    pub group_id: u32,
+    pub id: u32,
+}
+
+impl Render3DInstance {
+    pub fn new(color: [f32; 4]) -> Self {
+        Self {
+            model_x: [1.0, 0.0, 0.0, 0.0],
+            model_y: [0.0, 1.0, 0.0, 0.0],
+            model_z: [0.0, 0.0, 1.0, 0.0],
+            model_w: [0.0, 0.0, 0.0, 1.0],
+            color,
+            mix: 0.0,
+            group_id: 0,
+            id: 0,
+        }
+    }
+
+    pub fn new_default_group() -> InstanceGroup<Render3DInstance> {
+        InstanceGroup::new(0, ID(RENDER_3D_COMMON_TEXTURE_ID))
+    }
+
+    pub fn with_transform(mut self, transform: &Transform3D) -> Self {
+        self.update_transform(transform);
+        self
+    }
+
+    // Same order as forward_basic: translate, rotate x, y, z, then scale
+    pub fn update_transform(&mut self, transform: &Transform3D) {
+        let model = Matrix4::from_translation(transform.position.into())
+            * Matrix4::from_angle_x(cgmath::Deg(transform.rotation[0]))
+            * Matrix4::from_angle_y(cgmath::Deg(transform.rotation[1]))
+            * Matrix4::from_angle_z(cgmath::Deg(transform.rotation[2]))
+            * Matrix4::from_nonuniform_scale(
+                transform.scale[0],
+                transform.scale[1],
+                transform.scale[2],
+            );
+        let [x, y, z, w] = matrix2array_4d(model);
+        self.model_x = x;
+        self.model_y = y;
+        self.model_z = z;
+        self.model_w = w;
+    }
+}
+
+impl Default for Render3DInstance {
+    fn default() -> Self {
+        Self::new([1.0, 1.0, 1.0, 1.0])
+    }
+}
+
+impl Instance for Render3DInstance {
+    fn id(&self) -> (u32, u32) {
+        (self.group_id, self.id)
+    }
+
+    fn set_id(&mut self, group_id: u32, inst_id: u32) {
+        self.group_id = group_id;
+        self.id = inst_id;
+    }
+
+    fn size() -> usize {
+        92
+    }
+}
+
+#[system]
+#[write_component(InstanceGroup<Render3DInstance>)]
+#[write_component(Mesh)]
+pub fn load(world: &mut SubWorld, #[resource] time: &Time) {
+    debug!("running system render_3d_instance_loader");
+    let delta = time.delta_secs();
+    par_for_each_mut!(
+        <(&mut InstanceGroup<Render3DInstance>, &Mesh)>::query(),
+        world,
+        |(group, _)| {
+            let components = Arc::clone(&group.components);
+            let mutators = components.read().unwrap();
+            group.instances.par_iter_mut().for_each(|instance| {
+                for component in &mutators[instance.id as usize] {
+                    component.lock().unwrap().mutate(instance, delta);
+                }
+            })
+        },
+    );
+}
+
+#[system]
+#[read_component(InstanceGroup<Render3DInstance>)]
+#[read_component(Mesh)]
+#[read_component(Bounds3D)]
+#[read_component(FoliageChunk)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] instance_buffer: &InstanceBuffer<Render3DInstance>,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    let start_time = Instant::now();
+    debug!("running system render_3d_forward_instance (graph node)");
+
+    // Foliage has its own node; see render_3d::foliage
+    let mut query = <(&InstanceGroup<Render3DInstance>, &Mesh, Option<&Bounds3D>)>::query()
+        .filter(!component::<FoliageChunk>());
+    render_groups(
+        world,
+        query.iter(world),
+        state,
+        instance_buffer,
+        device,
+        uploader,
+        &[],
+    );
+
+    debug!("render_3d_forward_instance pass submitted");
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+// Loads groups into instance_buffer and draws them over the node's target once per view (see
+// systems::viewport), with the camera and lighting at bind groups 1 and 2 and then any of
+// the node's own
+pub fn render_groups<'a>(
+    world: &SubWorld,
+    groups: impl Iterator<
+        Item = (
+            &'a InstanceGroup<Render3DInstance>,
+            &'a Mesh,
+            Option<&'a Bounds3D>,
+        ),
+    >,
+    state: &NodeState,
+    instance_buffer: &InstanceBuffer<Render3DInstance>,
+    device: &wgpu::Device,
+    uploader: &Uploader,
+    bind_groups: &[(u32, &wgpu::BindGroup)],
+) {
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("render_3d_forward_instance_encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    let SceneViews { views, cull } = SceneViews::new(world, state, camera, size);
+
+    let mut offset = 0;
+    let mut draws = vec![];
+    for (group, mesh, bounds) in groups {
+        // No instances in the camera; see systems::culling
+        if cull && !bounds.map_or(true, |bounds| bounds.visible) {
+            continue;
+        }
+        debug!(
+            "rendering instance group => type: render_3d, size: {}",
+            group.num_instances()
+        );
+
+        let texture = match texture_groups.get(&group.texture()) {
+            Some(texture) => texture,
+            None => {
+                warn!(
+                    "instance group {}: missing texture {}, skipping",
+                    group.id,
+                    group.texture()
+                );
+                continue;
+            }
+        };
+
+        // Same batching as render_2d::forward_instance
+        if !group.is_partitioned() {
+            let bytes = group.buffer_bytes();
+            if bytes.is_empty() {
+                continue;
+            }
+            let range = offset..offset + bytes.len() as u64;
+            instance_buffer.load_group(offset, bytes);
+            offset = range.end;
+            draws.push(InstanceDraw {
+                texture,
+                mesh,
+                range,
+                instances: group.num_instances() as u32,
+            });
+            continue;
+        }
+
+        for (mesh_id, instances) in group.partitions() {
+            let partition_mesh = match mesh_id {
+                Some(id) => match group.meshes.get(&id) {
+                    Some(partition_mesh) => partition_mesh,
+                    None => continue,
+                },
+                None => mesh,
+            };
+            let bytes: &[u8] = bytemuck::cast_slice(instances.as_slice());
+            let range = offset..offset + bytes.len() as u64;
+            instance_buffer.load_group(offset, bytes);
+            offset = range.end;
+            draws.push(InstanceDraw {
+                texture,
+                mesh: partition_mesh,
+                range,
+                instances: instances.len() as u32,
+            });
+        }
+    }
+    instance_buffer.load_draws(&mut encoder, &draws);
+
+    let pass_res =
+        render_target_mut.create_overlay_pass("render_3d_forward_instance_pass", &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: render_3d_forward_instance");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+
+    // Global bindings
+    pass.set_bind_group(
+        2,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    for (index, bind_group) in bind_groups {
+        pass.set_bind_group(*index, bind_group, &[]);
+    }
+    for (rect, camera_group) in views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(1, camera_group, &[]);
+        instance_buffer.draw(&mut pass, &draws);
+    }
+
+    debug!("done recording; submitting render pass");
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+}
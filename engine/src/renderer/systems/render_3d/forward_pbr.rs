@@ -1,22 +1,22 @@
 use cgmath::{Matrix, SquareMatrix};
+use instant::Instant;
 use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
-use std::{
-    sync::{Arc, Mutex},
-    time::Instant,
-};
+use std::sync::{Arc, Mutex, RwLock};
 use uuid::Uuid;
 
 use crate::{
     components::Transform3D,
     constants::{
-        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, RENDER_3D_BIND_GROUP_ID,
-        RENDER_3D_COMMON_TEXTURE_ID,
+        CAMERA_3D_BIND_GROUP_ID, ENVIRONMENT_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4,
+        LIGHTING_3D_BIND_GROUP_ID, MATERIAL_PREVIEW_NODE_ID, PBR_DEFAULT_MATERIAL_ID,
+        RENDER_3D_BIND_GROUP_ID,
     },
     legion::IntoQuery,
     renderer::{
+        buffer::upload::Uploader,
         graph::NodeState,
         mesh::Mesh,
-        systems::sky::Sky,
+        systems::{render_3d::material_preview::PreviewSphere, sky::Sky},
         uniform::{
             generic::GenericUniformBuilder,
             group::{
@@ -24,42 +24,44 @@ use crate::{
             },
         },
     },
-    systems::camera_3d::matrix2array_4d,
+    sources::{materials::MaterialRegistry, names::NameIndex},
+    systems::{
+        camera_3d::matrix2array_4d,
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
 };
 
 // Todo: go through all todo comments and make tickets for them
 // Todo: remove unnecessary builders
 
+#[derive(Clone)]
 pub struct RenderPBR {
     pub name: String,
 
-    // Todo: make these into a "BasicMaterial" component
+    // In the MaterialRegistry; see sources::materials
+    pub material: Uuid,
+    // Multiplies the material's base color
     pub color: [f32; 4],
-    pub texture: Uuid,
-    pub mix: f32,
-
-    // pbr
-    pub roughness: f32,
 }
 
 impl RenderPBR {
     pub fn default(name: &str) -> Self {
+        Self::new(name, ID(PBR_DEFAULT_MATERIAL_ID))
+    }
+
+    pub fn new(name: &str, material: Uuid) -> Self {
         Self {
             name: name.to_owned(),
+            material,
             color: [1.0, 1.0, 1.0, 1.0],
-            texture: ID(RENDER_3D_COMMON_TEXTURE_ID),
-            mix: 0.0,
-            roughness: 0.3,
         }
     }
 
+    // The default material, tinted
     pub fn colored(name: &str, color: [f32; 4]) -> Self {
         Self {
-            name: name.to_owned(),
             color,
-            texture: ID(RENDER_3D_COMMON_TEXTURE_ID),
-            mix: 0.0,
-            roughness: 0.3,
+            ..Self::default(name)
         }
     }
 }
@@ -70,7 +72,6 @@ pub struct RenderPBRUniforms {
     pub model_mat: [[f32; 4]; 4],
     pub normal_mat: [[f32; 4]; 4],
     pub color: [f32; 4],
-    pub params: [f32; 4], // [mix, roughness, ]
 }
 
 impl From<(&RenderPBR, &Transform3D)> for RenderPBRUniforms {
@@ -97,7 +98,6 @@ impl From<(&RenderPBR, &Transform3D)> for RenderPBRUniforms {
             model_mat: matrix2array_4d(model_mat),
             normal_mat: matrix2array_4d(normal_mat),
             color: entity.0.color,
-            params: [entity.0.mix, entity.0.roughness, 0.0, 0.0],
         }
     }
 }
@@ -111,7 +111,6 @@ impl UniformGroupType<Self> for RenderPBRForwardUniformGroup {
                 model_mat: IDENTITY_MATRIX_4,
                 normal_mat: IDENTITY_MATRIX_4,
                 color: [1.0, 1.0, 1.0, 1.0],
-                params: [1.0, 0.5, 0.0, 0.0],
             }))
             .with_id(ID(RENDER_3D_BIND_GROUP_ID))
     }
@@ -125,7 +124,7 @@ pub fn load(
     world: &mut SubWorld,
     command_buffer: &mut CommandBuffer,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
     #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<RenderPBRForwardUniformGroup>>>,
 ) {
     debug!("running system render_3d_forward_basic_uniform_loader (graph node)");
@@ -139,12 +138,15 @@ pub fn load(
             "allocating buffers for new render_3d component: {}",
             builder_3d.name
         );
-        command_buffer.add_component(*entity, group_builder.single_state(device, queue).unwrap());
+        command_buffer.add_component(
+            *entity,
+            group_builder.single_state(device, uploader).unwrap(),
+        );
     });
 
     // Load all RenderPBR components into their GroupStates
     let mut query = <(&RenderPBR, &Transform3D, &GroupState)>::query();
-    query.par_for_each(world, |(render_3d, transform_3d, group_state)| {
+    par_for_each!(query, world, |(render_3d, transform_3d, group_state)| {
         debug!(
             "loading uniform group state for existing render_3d component: {}",
             render_3d.name
@@ -158,16 +160,24 @@ pub fn load(
 #[read_component(RenderPBR)]
 #[read_component(Mesh)]
 #[read_component(GroupState)]
+#[read_component(PreviewSphere)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
 pub fn render(
     world: &mut SubWorld,
     #[state] state: &mut NodeState,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
     #[resource] sky: &Sky,
+    #[resource] materials: &Arc<RwLock<MaterialRegistry>>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
 ) {
     debug!("running system render_forward_pbr (graph node)");
     let start_time = Instant::now();
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let materials = materials.read().unwrap();
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("RenderPBR Encoder"),
@@ -176,43 +186,84 @@ pub fn render(
     let render_target = state.render_target();
     let render_target_mut = render_target.lock().unwrap();
 
-    let pass_res = render_target_mut.create_render_pass("forward_render_pbr", &mut encoder, false);
+    // Texture nodes have nothing drawn under them to keep
+    let pass_res = render_target_mut.create_render_pass(
+        "forward_render_pbr",
+        &mut encoder,
+        state.texture_node,
+    );
     if pass_res.is_err() {
         warn!("no target, aborting render pass: render_forward_pbr");
         return;
     }
 
     let mut pass = pass_res.unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
+    pass.set_bind_group(3, sky.shared_group.as_ref().unwrap(), &[]);
     pass.set_bind_group(
-        2,
-        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        4,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(
+        5,
+        &node.binder.uniform_groups[&ID(ENVIRONMENT_BIND_GROUP_ID)],
         &[],
     );
-    pass.set_bind_group(3, sky.shared_group.as_ref().unwrap(), &[]);
 
-    let mut query = <(&RenderPBR, &Mesh, &GroupState)>::query();
-    for (render_pbr, mesh, group_state) in query.iter(world) {
-        pass.set_bind_group(0, &node.binder.texture_groups[&render_pbr.texture], &[]);
-        pass.set_bind_group(1, &group_state.bind_group, &[]);
+    // Once per view; see systems::viewport
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    for (rect, camera_group) in SceneViews::new(world, state, camera, size).views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(2, camera_group, &[]);
 
-        pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
-        pass.set_index_buffer(
-            mesh.index_buffer.buffer.0.slice(..),
-            wgpu::IndexFormat::Uint32,
-        );
+        let mut query = <(
+            Entity,
+            &RenderPBR,
+            &Mesh,
+            &GroupState,
+            Option<&PreviewSphere>,
+        )>::query();
+        for (entity, render_pbr, mesh, group_state, preview) in query.iter(world) {
+            // The material editor's sphere; see render_3d::material_preview
+            if preview.is_some() != (node.id == ID(MATERIAL_PREVIEW_NODE_ID)) {
+                continue;
+            }
+            let material = match materials.bind_group(&render_pbr.material) {
+                Some(material) => material,
+                None => {
+                    warn!(
+                        "{}: missing material {}, skipping",
+                        names.read().unwrap().label(*entity),
+                        render_pbr.material
+                    );
+                    continue;
+                }
+            };
+            pass.set_bind_group(0, material, &[]);
+            pass.set_bind_group(1, &group_state.bind_group, &[]);
+
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
 
-        // info!(
-        //     "RENDER 3D drawing entity with {} triangles",
-        //     mesh.indices.len() / 3
-        // );
-        pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+            // info!(
+            //     "RENDER 3D drawing entity with {} triangles",
+            //     mesh.indices.len() / 3
+            // );
+            pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+        }
     }
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("forward_render_pbr pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
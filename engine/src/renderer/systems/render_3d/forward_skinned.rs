@@ -0,0 +1,193 @@
+use instant::Instant;
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{
+    components::Transform3D,
+    constants::{
+        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, LIGHTING_3D_BIND_GROUP_ID,
+        SKINNED_3D_BIND_GROUP_ID,
+    },
+    legion::IntoQuery,
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        mesh::Mesh,
+        uniform::{
+            generic::GenericUniformBuilder,
+            group::{
+                GroupState, GroupStateBuilder, UniformGroup, UniformGroupBuilder, UniformGroupType,
+            },
+        },
+    },
+    sources::names::NameIndex,
+    systems::{
+        animation::{Skeleton, MAX_JOINTS},
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
+};
+
+use super::forward_basic::{Render3D, Render3DUniforms};
+
+// Render3D entities with a Skeleton and a skinned Mesh (Vertex3DSkinned). Drawn on top of
+// the forward_basic target, and left out of the basic and shadow map nodes.
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BoneUniforms {
+    pub joints: [[[f32; 4]; 4]; MAX_JOINTS],
+}
+
+// Separate from the GroupState that forward_basic gives every other Render3D entity
+pub struct SkinnedGroupState(pub GroupState);
+
+pub struct Render3DSkinnedUniformGroup {}
+
+impl UniformGroupType<Self> for Render3DSkinnedUniformGroup {
+    fn builder() -> UniformGroupBuilder<Render3DSkinnedUniformGroup> {
+        UniformGroup::<Render3DSkinnedUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(Render3DUniforms {
+                model_mat: IDENTITY_MATRIX_4,
+                normal_mat: IDENTITY_MATRIX_4,
+                color: [1.0, 1.0, 1.0, 1.0],
+                mix: [1.0, 0.0, 0.0, 0.0],
+            }))
+            .with_uniform(GenericUniformBuilder::from_source(BoneUniforms {
+                joints: [IDENTITY_MATRIX_4; MAX_JOINTS],
+            }))
+            .with_id(ID(SKINNED_3D_BIND_GROUP_ID))
+    }
+}
+
+#[system]
+#[read_component(Render3D)]
+#[read_component(Transform3D)]
+#[read_component(Skeleton)]
+#[read_component(SkinnedGroupState)]
+pub fn load(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<Render3DSkinnedUniformGroup>>>,
+) {
+    debug!("running system render_3d_forward_skinned_uniform_loader (graph node)");
+
+    let group_builder = group_builder.lock().unwrap();
+    let mut query = <(Entity, &Render3D, &Transform3D, &Skeleton)>::query()
+        .filter(!component::<SkinnedGroupState>());
+    query.for_each(world, |(entity, render_3d, _, _)| {
+        debug!(
+            "allocating buffers for new skinned render_3d component: {}",
+            render_3d.name
+        );
+        command_buffer.add_component(
+            *entity,
+            SkinnedGroupState(group_builder.single_state(device, uploader).unwrap()),
+        );
+    });
+
+    let mut query = <(&Render3D, &Transform3D, &Skeleton, &SkinnedGroupState)>::query();
+    par_for_each!(query, world, |(
+        render_3d,
+        transform_3d,
+        skeleton,
+        group_state,
+    )| {
+        let source = &[Render3DUniforms::from((render_3d, transform_3d))];
+        group_state.0.write_buffer(0, bytemuck::cast_slice(source));
+
+        let mut bones = BoneUniforms {
+            joints: [IDENTITY_MATRIX_4; MAX_JOINTS],
+        };
+        let count = skeleton.skin.len().min(MAX_JOINTS);
+        bones.joints[..count].copy_from_slice(&skeleton.skin[..count]);
+        group_state
+            .0
+            .write_buffer(1, bytemuck::cast_slice(&[bones]));
+    });
+}
+
+#[system]
+#[read_component(Render3D)]
+#[read_component(Mesh)]
+#[read_component(SkinnedGroupState)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
+pub fn render(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
+) {
+    debug!("running system render_3d_forward_skinned (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render3D Skinned Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass("forward_render_3d_skinned", &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: render_3d_forward_skinned");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+
+    pass.set_bind_group(
+        3,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+        &[],
+    );
+
+    // Once per view; see systems::viewport
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    for (rect, camera_group) in SceneViews::new(world, state, camera, size).views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(2, camera_group, &[]);
+
+        let mut query = <(Entity, &Render3D, &Mesh, &SkinnedGroupState)>::query();
+        for (entity, render_3d, mesh, group_state) in query.iter(world) {
+            let texture = match texture_groups.get(&render_3d.texture) {
+                Some(texture) => texture,
+                None => {
+                    warn!(
+                        "{}: missing texture {}, skipping",
+                        names.read().unwrap().label(*entity),
+                        render_3d.texture
+                    );
+                    continue;
+                }
+            };
+            pass.set_bind_group(0, texture, &[]);
+            pass.set_bind_group(1, &group_state.0.bind_group, &[]);
+
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+        }
+    }
+
+    debug!("done recording; submitting render pass");
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+
+    debug!("forward_render_3d_skinned pass submitted");
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
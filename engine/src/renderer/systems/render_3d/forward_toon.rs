@@ -0,0 +1,282 @@
+use instant::Instant;
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{
+    components::Transform3D,
+    constants::{
+        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, LIGHTING_3D_BIND_GROUP_ID,
+        TOON_3D_BIND_GROUP_ID,
+    },
+    legion::IntoQuery,
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        mesh::Mesh,
+        uniform::{
+            generic::GenericUniformBuilder,
+            group::{
+                GroupState, GroupStateBuilder, UniformGroup, UniformGroupBuilder, UniformGroupType,
+            },
+        },
+    },
+    sources::names::NameIndex,
+    systems::{
+        animation::Skeleton,
+        culling::Bounds3D,
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
+};
+
+use super::forward_basic::{Render3D, Render3DUniforms};
+
+// Render3D entities with a RenderToon, lit in flat bands instead of smoothly. Drawn on top
+// of the forward_basic target by two nodes: the toon node, and the outline node, which
+// draws each mesh's back faces pushed out along their normals (an inverted hull) in the
+// outline color. Meshes with split normals (hard edges) get gaps in their outline.
+//
+// Like skinned meshes, they're left out of the basic and shadow map nodes.
+
+pub struct RenderToon {
+    // Levels of diffuse light, from unlit to fully lit
+    pub bands: u32,
+    // In world units; 0 for no outline
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+    // Brightness of the hard edged highlight; 0 for none
+    pub specular: f32,
+    // Brightness of the lit side's silhouette; 0 for none
+    pub rim: f32,
+}
+
+impl Default for RenderToon {
+    fn default() -> Self {
+        Self {
+            bands: 3,
+            outline_width: 0.02,
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            specular: 0.5,
+            rim: 0.3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ToonUniforms {
+    pub outline_color: [f32; 4],
+    pub bands: u32,
+    pub outline_width: f32,
+    pub specular: f32,
+    pub rim: f32,
+}
+
+impl From<&RenderToon> for ToonUniforms {
+    fn from(toon: &RenderToon) -> Self {
+        Self {
+            outline_color: toon.outline_color,
+            bands: toon.bands,
+            outline_width: toon.outline_width,
+            specular: toon.specular,
+            rim: toon.rim,
+        }
+    }
+}
+
+// Separate from the GroupState that forward_basic gives every other Render3D entity
+pub struct ToonGroupState(pub GroupState);
+
+pub struct Render3DToonUniformGroup {}
+
+impl UniformGroupType<Self> for Render3DToonUniformGroup {
+    fn builder() -> UniformGroupBuilder<Render3DToonUniformGroup> {
+        UniformGroup::<Render3DToonUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(Render3DUniforms {
+                model_mat: IDENTITY_MATRIX_4,
+                normal_mat: IDENTITY_MATRIX_4,
+                color: [1.0, 1.0, 1.0, 1.0],
+                mix: [1.0, 0.0, 0.0, 0.0],
+            }))
+            .with_uniform(GenericUniformBuilder::from_source(ToonUniforms::from(
+                &RenderToon::default(),
+            )))
+            .with_id(ID(TOON_3D_BIND_GROUP_ID))
+    }
+}
+
+#[system]
+#[read_component(Render3D)]
+#[read_component(RenderToon)]
+#[read_component(Transform3D)]
+#[read_component(Skeleton)]
+#[read_component(ToonGroupState)]
+pub fn load(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<Render3DToonUniformGroup>>>,
+) {
+    debug!("running system render_3d_forward_toon_uniform_loader (graph node)");
+
+    let group_builder = group_builder.lock().unwrap();
+    let mut query = <(Entity, &Render3D, &RenderToon, &Transform3D)>::query()
+        .filter(!component::<ToonGroupState>() & !component::<Skeleton>());
+    query.for_each(world, |(entity, render_3d, _, _)| {
+        debug!(
+            "allocating buffers for new toon render_3d component: {}",
+            render_3d.name
+        );
+        command_buffer.add_component(
+            *entity,
+            ToonGroupState(group_builder.single_state(device, uploader).unwrap()),
+        );
+    });
+
+    let mut query = <(&Render3D, &RenderToon, &Transform3D, &ToonGroupState)>::query();
+    par_for_each!(query, world, |(
+        render_3d,
+        toon,
+        transform_3d,
+        group_state,
+    )| {
+        let source = &[Render3DUniforms::from((render_3d, transform_3d))];
+        group_state.0.write_buffer(0, bytemuck::cast_slice(source));
+        group_state
+            .0
+            .write_buffer(1, bytemuck::cast_slice(&[ToonUniforms::from(toon)]));
+    });
+}
+
+#[system]
+#[read_component(Render3D)]
+#[read_component(RenderToon)]
+#[read_component(Mesh)]
+#[read_component(ToonGroupState)]
+#[read_component(Bounds3D)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
+pub fn render(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
+) {
+    debug!("running system render_3d_forward_toon (graph node)");
+    let start_time = Instant::now();
+    draw(world, state, device, uploader, names, false);
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+#[system]
+#[read_component(Render3D)]
+#[read_component(RenderToon)]
+#[read_component(Mesh)]
+#[read_component(ToonGroupState)]
+#[read_component(Bounds3D)]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
+pub fn render_outline(
+    world: &SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] names: &Arc<RwLock<NameIndex>>,
+) {
+    debug!("running system render_3d_toon_outline (graph node)");
+    let start_time = Instant::now();
+    draw(world, state, device, uploader, names, true);
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+// Both nodes draw the same entities with the same bindings; only their pipelines differ
+fn draw(
+    world: &SubWorld,
+    state: &NodeState,
+    device: &wgpu::Device,
+    uploader: &Uploader,
+    names: &RwLock<NameIndex>,
+    outline: bool,
+) {
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+    let texture_groups = node.binder.texture_groups.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render3D Toon Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_overlay_pass(&node.name, &mut encoder);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+
+    pass.set_bind_group(
+        3,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+        &[],
+    );
+
+    // Once per view; see systems::viewport
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    let SceneViews { views, cull } = SceneViews::new(world, state, camera, size);
+    for (rect, camera_group) in views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(2, camera_group, &[]);
+
+        let mut query = <(
+            Entity,
+            &Render3D,
+            &RenderToon,
+            &Mesh,
+            &ToonGroupState,
+            Option<&Bounds3D>,
+        )>::query();
+        for (entity, render_3d, toon, mesh, group_state, bounds) in query.iter(world) {
+            // Outside the camera; see systems::culling
+            if cull && !bounds.map_or(true, |bounds| bounds.visible) {
+                continue;
+            }
+            if outline && toon.outline_width <= 0.0 {
+                continue;
+            }
+            let texture = match texture_groups.get(&render_3d.texture) {
+                Some(texture) => texture,
+                None => {
+                    warn!(
+                        "{}: missing texture {}, skipping",
+                        names.read().unwrap().label(*entity),
+                        render_3d.texture
+                    );
+                    continue;
+                }
+            };
+            pass.set_bind_group(0, texture, &[]);
+            pass.set_bind_group(1, &group_state.0.bind_group, &[]);
+
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+        }
+    }
+
+    debug!("done recording; submitting render pass");
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+}
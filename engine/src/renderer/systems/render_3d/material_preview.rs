@@ -0,0 +1,127 @@
+use legion::{component, world::SubWorld, IntoQuery, World};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    components::Transform3D,
+    constants::{ID, MATERIAL_PREVIEW_NODE_ID},
+    renderer::{
+        graph::{target::RenderTarget, RenderGraph},
+        systems::{capture::FrameReadback, render_3d::forward_pbr::RenderPBR},
+        GpuState,
+    },
+    sources::{camera::Camera3D, primitives::PrimitiveMesh, registry::MeshBuilder, time::Time},
+    systems::viewport::TextureCamera,
+};
+
+// The material editor's preview (sources::ui::material_editor): the selected material on a
+// spinning sphere, drawn by a PBR texture node of its own (MATERIAL_PREVIEW_NODE_ID) and
+// read back for the panel to show. Like the pixel probe, the readback waits on the gpu, so
+// it costs a pipeline stall every frame while a material is selected. test_channel_node only.
+#[derive(Default)]
+pub struct MaterialPreview {
+    pub selected: Mutex<Option<Uuid>>,
+    // Size and BGRA8 pixels, as iced takes them
+    pub image: Mutex<Option<((u32, u32), Vec<u8>)>>,
+}
+
+// Marks the preview sphere. The preview node draws nothing else, and no other node draws it.
+pub struct PreviewSphere;
+
+// Of the screen size, like any texture node
+pub const PREVIEW_SCALE: f32 = 0.25;
+// Degrees per second
+const SPIN_SPEED: f32 = 30.0;
+
+// The sphere (radius 0.5, at the origin) and the camera drawing it into the preview node
+pub fn spawn(world: &mut World, device: &Arc<wgpu::Device>, screen_size: (f32, f32)) {
+    let mesh = PrimitiveMesh::Sphere {
+        segments: 48,
+        rings: 24,
+    }
+    .build(Arc::clone(device));
+    world.push((
+        PreviewSphere,
+        RenderPBR::default("material_preview"),
+        Transform3D::origin(),
+        mesh,
+    ));
+
+    let mut camera = Camera3D::default(screen_size.0, screen_size.1);
+    camera.pos = (0.0, 0.0, -2.0).into();
+    camera.dir = (0.0, 0.0, 1.0).into();
+    camera.pitch = 0.0;
+    world.push((
+        camera,
+        TextureCamera {
+            texture: ID(MATERIAL_PREVIEW_NODE_ID),
+        },
+    ));
+}
+
+// Spins the sphere, with the selected material on it
+#[system]
+#[write_component(RenderPBR)]
+#[write_component(Transform3D)]
+#[read_component(PreviewSphere)]
+pub fn update(
+    world: &mut SubWorld,
+    #[resource] preview: &Arc<MaterialPreview>,
+    #[resource] time: &Time,
+) {
+    let selected = *preview.selected.lock().unwrap();
+    let mut query =
+        <(&mut RenderPBR, &mut Transform3D)>::query().filter(component::<PreviewSphere>());
+    for (render_pbr, transform) in query.iter_mut(world) {
+        if let Some(material) = selected {
+            render_pbr.material = material;
+        }
+        transform.rotation[1] = (transform.rotation[1] + SPIN_SPEED * time.delta_secs()) % 360.0;
+    }
+}
+
+// Copies the preview node's target into the MaterialPreview, once the graph has been submitted
+#[system]
+pub fn read_back(
+    #[resource] preview: &Arc<MaterialPreview>,
+    #[resource] gpu: &Arc<Mutex<GpuState>>,
+    #[resource] graph: &Arc<RenderGraph>,
+) {
+    if preview.selected.lock().unwrap().is_none() {
+        return;
+    }
+    debug!("running system material_preview_read_back");
+
+    let gpu = gpu.lock().unwrap();
+    let targets = graph.node_targets.get(&ID(MATERIAL_PREVIEW_NODE_ID));
+    let target = targets[0].lock().unwrap();
+    let readback = match &*target {
+        RenderTarget::Texture {
+            color_buffer,
+            format,
+            size,
+            ..
+        } => FrameReadback::copy(
+            &gpu.device,
+            &gpu.queue,
+            &color_buffer.texture,
+            *size,
+            *format,
+        ),
+        _ => return,
+    };
+    drop(target);
+
+    match readback.wait(&gpu.device) {
+        Ok(image) => {
+            let size = image.dimensions();
+            let mut pixels = image.into_raw();
+            // To BGRA
+            for texel in pixels.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+            *preview.image.lock().unwrap() = Some((size, pixels));
+        }
+        Err(err) => warn!("material preview readback failed: {}", err),
+    }
+}
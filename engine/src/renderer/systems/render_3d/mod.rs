@@ -1,2 +1,10 @@
+pub mod decal;
+pub mod foliage;
 pub mod forward_basic;
+pub mod forward_instance;
 pub mod forward_pbr;
+pub mod forward_skinned;
+pub mod forward_toon;
+pub mod material_preview;
+pub mod shadow;
+pub mod ssao;
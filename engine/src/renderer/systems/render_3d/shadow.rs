@@ -0,0 +1,145 @@
+use instant::Instant;
+use legion::world::SubWorld;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    constants::{ID, LIGHTING_3D_BIND_GROUP_ID},
+    legion::IntoQuery,
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        mesh::Mesh,
+        uniform::{generic::GenericUniform, group::GroupState},
+    },
+    systems::lighting_3d::Lighting3DUniforms,
+};
+
+use super::forward_basic::Render3D;
+
+// Draws every Render3D entity's depth from the directional light's point of view.
+// Reuses the GroupStates allocated by forward_basic::load, so it has nothing to load itself.
+#[system]
+#[read_component(Render3D)]
+#[read_component(Mesh)]
+#[read_component(GroupState)]
+pub fn render(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_3d_shadow_map (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Shadow Map Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_render_pass("shadow_map_3d", &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: render_3d_shadow_map");
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+        &[],
+    );
+
+    let mut query = <(&Render3D, &Mesh, &GroupState)>::query();
+    for (_, mesh, group_state) in query.iter(world) {
+        pass.set_bind_group(0, &group_state.bind_group, &[]);
+
+        pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+        pass.set_index_buffer(
+            mesh.index_buffer.buffer.0.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+    }
+
+    debug!("done recording; submitting render pass");
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+
+    debug!("shadow_map_3d pass submitted");
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+// Same, into each face of the point shadow map (a depth cube), from the point Light3D with
+// shadows; nothing without one. The shader picks the face's view_proj by instance index.
+#[system]
+#[read_component(Render3D)]
+#[read_component(Mesh)]
+#[read_component(GroupState)]
+pub fn render_point(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] lighting_uniforms: &Arc<Mutex<GenericUniform<Lighting3DUniforms>>>,
+) {
+    if lighting_uniforms.lock().unwrap().mut_ref().count[1] == 0 {
+        return;
+    }
+    debug!("running system render_3d_point_shadow_map (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Point Shadow Map Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    for face in 0..6u32 {
+        let pass_res = render_target_mut.create_face_pass(
+            "point_shadow_map_3d",
+            face as usize,
+            &mut encoder,
+            true,
+        );
+        if pass_res.is_err() {
+            warn!("no target, aborting render pass: render_3d_point_shadow_map");
+            return;
+        }
+
+        let mut pass = pass_res.unwrap();
+        pass.set_pipeline(&pipeline);
+
+        pass.set_bind_group(
+            1,
+            &node.binder.uniform_groups[&ID(LIGHTING_3D_BIND_GROUP_ID)],
+            &[],
+        );
+
+        let mut query = <(&Render3D, &Mesh, &GroupState)>::query();
+        for (_, mesh, group_state) in query.iter(world) {
+            pass.set_bind_group(0, &group_state.bind_group, &[]);
+
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+            pass.set_index_buffer(
+                mesh.index_buffer.buffer.0.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, face..face + 1);
+        }
+    }
+
+    debug!("done recording; submitting render passes");
+    uploader.record(state.submission, encoder.finish());
+
+    debug!("point_shadow_map_3d passes submitted");
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -0,0 +1,265 @@
+use cgmath::{InnerSpace, Vector3};
+use instant::Instant;
+use legion::world::SubWorld;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    constants::{CAMERA_3D_BIND_GROUP_ID, ID, SSAO_BIND_GROUP_ID},
+    legion::IntoQuery,
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        mesh::Mesh,
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{GroupState, UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    systems::culling::Bounds3D,
+};
+
+use super::forward_basic::Render3D;
+
+// Screen space ambient occlusion, as three nodes ahead of forward_basic:
+//
+//   normal_depth: world normals and depth of every Render3D entity (the G-buffer)
+//   ssao:         hemisphere occlusion from normal_depth, at SSAO_SCALE resolution
+//   blur:         4x4 box blur of ssao, read by forward_basic through lighting_shader()
+//
+// Skinned and instanced meshes aren't in the prepass, so they neither cast nor receive AO.
+
+// Resolution of the ssao and blur targets, relative to the screen
+pub const SSAO_SCALE: f32 = 0.5;
+pub const NORMAL_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+pub const SSAO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+const KERNEL_SIZE: usize = 16;
+const AO_MARKER: &str = "// <ambient_occlusion>";
+
+// Resource, inserted when the engine has SSAO on. Read every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Ssao {
+    // How much of the occlusion reaches the lighting, in [0, 1]
+    pub strength: f32,
+    // World space radius of the sampled hemisphere
+    pub radius: f32,
+    // Depth difference below which a sample doesn't count, against self occlusion
+    pub bias: f32,
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            radius: 0.5,
+            bias: 0.025,
+        }
+    }
+}
+
+// Fills in render_3d.wgsl's ambient_occlusion(), which samples the blurred AO (bind group 6)
+// with the strength from the ssao uniforms (bind group 7), or is always 1 without SSAO
+pub fn lighting_shader(source: &str, ssao: bool) -> String {
+    let ao = match ssao {
+        true => format!(
+            "let SSAO_SCALE: f32 = {:?};\n\n{}",
+            SSAO_SCALE,
+            include_str!("../../shaders/ssao/lighting.wgsl")
+        ),
+        false => {
+            "fn ambient_occlusion(frag_coord: vec2<f32>) -> f32 {\n    return 1.0;\n}".to_owned()
+        }
+    };
+    source.replace(AO_MARKER, &ao)
+}
+
+pub struct SsaoUniformGroup {}
+
+impl UniformGroupType<Self> for SsaoUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        let ssao = Ssao::default();
+        UniformGroup::<SsaoUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(SsaoUniforms {
+                kernel: kernel(),
+                params: [ssao.strength, ssao.radius, ssao.bias, 0.0],
+            }))
+            .with_id(ID(SSAO_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SsaoUniforms {
+    pub kernel: [[f32; 4]; KERNEL_SIZE],
+    // [strength, radius, bias, 0]
+    pub params: [f32; 4],
+}
+
+// Random offsets in the unit hemisphere around +z, packed closer to the center. The same
+// every run, so that frames are reproducible.
+fn kernel() -> [[f32; 4]; KERNEL_SIZE] {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut kernel = [[0.0; 4]; KERNEL_SIZE];
+    for (i, offset) in kernel.iter_mut().enumerate() {
+        let dir = Vector3::new(
+            rng.gen_range(-1.0f32..=1.0),
+            rng.gen_range(-1.0f32..=1.0),
+            rng.gen_range(0.05f32..=1.0),
+        );
+        let t = i as f32 / KERNEL_SIZE as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        let dir = dir.normalize() * rng.gen_range(0.0f32..=1.0) * scale;
+        *offset = [dir.x, dir.y, dir.z, 0.0];
+    }
+    kernel
+}
+
+#[system]
+pub fn load(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] ssao: &Ssao,
+    #[resource] ssao_uniforms: &Arc<Mutex<GenericUniform<SsaoUniforms>>>,
+    #[resource] ssao_uniforms_group: &Arc<Mutex<UniformGroup<SsaoUniformGroup>>>,
+) {
+    debug!("running system ssao_uniform_loader");
+    let mut forms = ssao_uniforms.lock().unwrap();
+    forms.mut_ref().params = [ssao.strength, ssao.radius, ssao.bias, 0.0];
+    forms.write_buffer(
+        &queue,
+        ssao_uniforms_group.lock().unwrap().default_buffer(0),
+    );
+}
+
+// Reuses the GroupStates allocated by forward_basic::load, like the shadow map
+#[system]
+#[read_component(Render3D)]
+#[read_component(Mesh)]
+#[read_component(GroupState)]
+#[read_component(Bounds3D)]
+pub fn normal_depth(
+    world: &mut SubWorld,
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_3d_normal_depth (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Normal Depth Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        &[],
+    );
+
+    let mut query = <(&Render3D, &Mesh, &GroupState, Option<&Bounds3D>)>::query();
+    for (_, mesh, group_state, bounds) in query.iter(world) {
+        if !bounds.map_or(true, |bounds| bounds.visible) {
+            continue;
+        }
+        pass.set_bind_group(0, &group_state.bind_group, &[]);
+
+        pass.set_vertex_buffer(0, mesh.vertex_buffer.buffer.0.slice(..));
+        pass.set_index_buffer(
+            mesh.index_buffer.buffer.0.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.draw_indexed(0..mesh.index_buffer.buffer.1, 0, 0..1);
+    }
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+#[system]
+pub fn render(
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_3d_ssao (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("SSAO Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(2, &node.binder.uniform_groups[&ID(SSAO_BIND_GROUP_ID)], &[]);
+    pass.draw(0..3, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
+
+#[system]
+pub fn blur(
+    #[state] state: &mut NodeState,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_3d_ssao_blur (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("SSAO Blur Encoder"),
+    });
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.draw(0..3, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -1,21 +1,19 @@
 use cgmath::{Matrix, SquareMatrix};
+use instant::Instant;
 use legion::{component, systems::CommandBuffer, world::SubWorld, Entity};
-use std::{
-    sync::{Arc, Mutex},
-    time::Instant,
-};
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 use wgpu::BindGroup;
 
 use crate::{
     components::Transform3D,
     constants::{
-        CAMERA_3D_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4, RENDER_3D_BIND_GROUP_ID,
-        RENDER_3D_COMMON_TEXTURE_ID,
+        CAMERA_3D_BIND_GROUP_ID, ENVIRONMENT_BIND_GROUP_ID, ID, IDENTITY_MATRIX_4,
+        RENDER_3D_BIND_GROUP_ID, RENDER_3D_COMMON_TEXTURE_ID,
     },
     legion::IntoQuery,
     renderer::{
-        buffer::texture::Texture,
+        buffer::{texture::Texture, upload::Uploader},
         graph::NodeState,
         mesh::Mesh,
         uniform::{
@@ -25,15 +23,26 @@ use crate::{
             },
         },
     },
-    sources::camera::Camera3D,
-    systems::camera_3d::matrix2array_4d,
+    sources::{
+        camera::Camera3D,
+        environment::{IrradianceCoefficients, DEFAULT_IRRADIANCE},
+    },
+    systems::{
+        camera_3d::matrix2array_4d,
+        viewport::{set_view, SceneViews, TextureCamera, Viewport, ViewportCamera},
+    },
 };
 
 use super::render_3d::forward_basic::{Render3D, Render3DUniforms};
 
 // Sky is a SINGLETON (one Render3D/Mesh/GroupState which is stored as a resource)
+// Games can change rotation (degrees, like Transform3D) and tint at runtime; the PBR
+// shader's reflections don't follow the rotation yet.
 
 pub struct Sky {
+    pub rotation: [f32; 3],
+    pub tint: [f32; 4],
+
     pub mesh: Mesh,
     pub r3d: Render3D,
     pub t3d: Transform3D,
@@ -43,27 +52,64 @@ pub struct Sky {
     pub shared_group: Option<Arc<BindGroup>>,
 }
 
+// Diffuse image based lighting for the PBR and sky shaders, from the environment loaded with
+// TextureRegistryBuilder::load_environment (or the default cubemap's). Never changes, so
+// there's no system writing it.
+pub struct EnvironmentUniformGroup {}
+
+impl UniformGroupType<Self> for EnvironmentUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        Self::from_irradiance(&IrradianceCoefficients {
+            data: DEFAULT_IRRADIANCE,
+        })
+    }
+}
+
+impl EnvironmentUniformGroup {
+    pub fn from_irradiance(irradiance: &IrradianceCoefficients) -> UniformGroupBuilder<Self> {
+        UniformGroup::<EnvironmentUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(EnvironmentUniforms {
+                irradiance: irradiance.to_matrices(),
+            }))
+            .with_id(ID(ENVIRONMENT_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniforms {
+    // One matrix per color channel; see IrradianceCoefficients::to_matrices
+    pub irradiance: [[[f32; 4]; 4]; 3],
+}
+
 #[system]
 pub fn update(#[resource] sky: &mut Sky, #[resource] camera: &Arc<Mutex<Camera3D>>) {
     debug!("running system update_sky");
 
     let cam_pos = camera.lock().unwrap().pos;
     sky.t3d.position = [cam_pos.x, cam_pos.y, cam_pos.z];
+    sky.t3d.rotation = sky.rotation;
+    sky.r3d.color = sky.tint;
 
     let source = &[Render3DUniforms::from((&sky.r3d, &sky.t3d))];
     sky.r3d_group.write_buffer(0, bytemuck::cast_slice(source));
 }
 
 #[system]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[read_component(ViewportCamera)]
 pub fn render(
+    world: &SubWorld,
     #[state] state: &mut NodeState,
     #[resource] sky: &mut Sky,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     debug!("running system render_sky (graph node)");
     let start_time = Instant::now();
     let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Sky Encoder"),
@@ -72,22 +118,27 @@ pub fn render(
     let render_target = state.render_target();
     let render_target_mut = render_target.lock().unwrap();
 
-    let pass_res = render_target_mut.create_render_pass("render_sky", &mut encoder, true);
+    // As an overlay (default_3d), the sky is drawn at the far plane behind the scene's depth
+    let pass_res = if node.depth_buffer {
+        render_target_mut.create_overlay_pass("render_sky", &mut encoder)
+    } else {
+        render_target_mut.create_render_pass("render_sky", &mut encoder, true)
+    };
     if pass_res.is_err() {
         warn!("no target, aborting render pass: render_sky");
         return;
     }
 
     let mut pass = pass_res.unwrap();
-    pass.set_pipeline(&node.pipeline);
+    pass.set_pipeline(&pipeline);
 
     pass.set_bind_group(0, &sky.r3d_group.bind_group, &[]);
+    pass.set_bind_group(2, &sky.cubemap, &[]);
     pass.set_bind_group(
-        1,
-        &node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)],
+        3,
+        &node.binder.uniform_groups[&ID(ENVIRONMENT_BIND_GROUP_ID)],
         &[],
     );
-    pass.set_bind_group(2, &sky.cubemap, &[]);
 
     pass.set_vertex_buffer(0, sky.mesh.vertex_buffer.buffer.0.slice(..));
     pass.set_index_buffer(
@@ -95,11 +146,20 @@ pub fn render(
         wgpu::IndexFormat::Uint32,
     );
 
-    pass.draw_indexed(0..sky.mesh.index_buffer.buffer.1, 0, 0..1);
+    // Once per view; see systems::viewport
+    let size = render_target_mut.size().unwrap_or((1, 1));
+    let camera = &*node.binder.uniform_groups[&ID(CAMERA_3D_BIND_GROUP_ID)];
+    for (rect, camera_group) in SceneViews::new(world, state, camera, size).views {
+        if !set_view(&mut pass, rect) {
+            continue;
+        }
+        pass.set_bind_group(1, camera_group, &[]);
+        pass.draw_indexed(0..sky.mesh.index_buffer.buffer.1, 0, 0..1);
+    }
 
     debug!("done recording; submitting render pass");
     drop(pass);
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.record(state.submission, encoder.finish());
 
     debug!("render_sky pass submitted");
     state.reporter.update(start_time.elapsed().as_secs_f64());
@@ -0,0 +1,147 @@
+use legion::world::SubWorld;
+use std::sync::{
+    mpsc::{self, Receiver},
+    Arc, Mutex, RwLock,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+use crate::{
+    parallel::*,
+    renderer::{buffer::texture::Texture, graph::RenderGraph, mesh::Mesh},
+    sources::{
+        events::Events,
+        loading::{AssetGroupLoaded, LoadState, LoadStates},
+        registry::{
+            load_texture, texture_bind_group_layout, AssetSource, MeshRegistry, ParsedMeshSource,
+            TextureRegistry, TextureType,
+        },
+    },
+};
+
+use super::hot_reload::{rebuild_meshes, swap_texture};
+
+// Textures and meshes loaded after startup (EngineBuilder::with_asset_streaming). The
+// registries are built with placeholders in their place (a white pixel, a unit cube), and a
+// loader thread decodes, parses and uploads the files on the rayon pool. Each frame,
+// stream_assets swaps whatever finished into the registries, the way hot reloading does:
+// nodes get the new bind groups, and entities cloned from a placeholder mesh are rebuilt.
+// Materials built from a streamed texture keep the placeholder.
+
+enum Streamed {
+    Texture(anyhow::Result<Texture>),
+    Mesh(anyhow::Result<ParsedMeshSource>),
+}
+
+// Resource, inserted by the engine when there's anything to stream
+pub struct AssetStream {
+    receiver: Mutex<Receiver<(AssetSource, Streamed)>>,
+}
+
+impl AssetStream {
+    pub fn start(
+        sources: Vec<AssetSource>,
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        format: wgpu::TextureFormat,
+        loads: &RwLock<LoadStates>,
+    ) -> Self {
+        let mut loads = loads.write().unwrap();
+        for source in &sources {
+            loads.start(source.id(), source.group_id());
+        }
+        info!("streaming {} assets", sources.len());
+
+        let (sender, receiver) = mpsc::channel();
+        let load = move || {
+            // Identical to the registry's, so the textures' bind groups work in its place
+            let image_layout =
+                texture_bind_group_layout(&device, "streamed_texture_layout", TextureType::Image);
+            let unfilterable_layout = texture_bind_group_layout(
+                &device,
+                "streamed_unfilterable_texture_layout",
+                TextureType::UnfilterableImage,
+            );
+            sources
+                .into_par_iter()
+                .for_each_with(sender, |sender, source| {
+                    let streamed = match &source {
+                        AssetSource::Texture { path, tex_type, .. } => {
+                            let layout = match tex_type {
+                                TextureType::Image => &image_layout,
+                                _ => &unfilterable_layout,
+                            };
+                            Streamed::Texture(load_texture(path, &device, &queue, format, layout))
+                        }
+                        AssetSource::Mesh { .. } => Streamed::Mesh(MeshRegistry::parse(&source)),
+                    };
+                    // Fails once the engine is gone, which is fine
+                    let _ = sender.send((source, streamed));
+                });
+        };
+        // No threads on the web, where streaming is off anyway (see
+        // EngineBuilder::with_asset_streaming)
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::spawn(load);
+        #[cfg(target_arch = "wasm32")]
+        load();
+
+        Self {
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+// Runs with the render graph's systems, before any node renders. That's after every game
+// system, so read the group events with an EventReader; see sources::events.
+#[system]
+#[write_component(Mesh)]
+pub fn stream_assets(
+    world: &mut SubWorld,
+    #[resource] stream: &AssetStream,
+    #[resource] textures: &Arc<RwLock<TextureRegistry>>,
+    #[resource] meshes: &Arc<RwLock<MeshRegistry>>,
+    #[resource] loads: &Arc<RwLock<LoadStates>>,
+    #[resource] events: &mut Events<AssetGroupLoaded>,
+    #[resource] graph: &Arc<RenderGraph>,
+) {
+    let finished: Vec<(AssetSource, Streamed)> =
+        stream.receiver.lock().unwrap().try_iter().collect();
+    if finished.is_empty() {
+        return;
+    }
+
+    let mut loads = loads.write().unwrap();
+    let mut replaced_meshes = vec![];
+    for (source, streamed) in finished {
+        let result = match streamed {
+            Streamed::Texture(texture) => texture.and_then(|texture| {
+                let bind_group = textures.write().unwrap().insert(&source, texture)?;
+                swap_texture(graph, &source.id(), &bind_group);
+                Ok(())
+            }),
+            Streamed::Mesh(parsed) => parsed.map(|parsed| {
+                replaced_meshes.extend(meshes.write().unwrap().insert(parsed));
+            }),
+        };
+        let state = match result {
+            Ok(()) => {
+                debug!("streamed asset: {}", source.path());
+                LoadState::Loaded
+            }
+            Err(err) => {
+                error!("error streaming asset {}: {}", source.path(), err);
+                LoadState::Failed(err.to_string())
+            }
+        };
+        if let Some(event) = loads.finish(source.id(), source.group_id(), state) {
+            info!(
+                "streamed asset group {}: {} loaded, {} failed",
+                event.group_id, event.loaded, event.failed
+            );
+            events.send(event);
+        }
+    }
+
+    rebuild_meshes(world, &meshes.read().unwrap(), &replaced_meshes);
+}
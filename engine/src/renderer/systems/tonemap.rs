@@ -0,0 +1,187 @@
+use instant::Instant;
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+use crate::{
+    constants::{FRAME_BIND_GROUP_ID, ID, TONEMAP_BIND_GROUP_ID},
+    renderer::{
+        buffer::{upload::Uploader, VERTEX2D_BUFFER_LAYOUT},
+        graph::{
+            node::{NodeBuilder, ShaderSource},
+            NodeState,
+        },
+        systems::{post_fx, quad::Quad},
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    sources::registry::TextureRegistry,
+};
+
+// Everything before the tonemap node renders into this
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TonemapOperator {
+    Aces,
+    Reinhard,
+    // No curve, anything above 1 is clipped
+    Clamp,
+}
+
+impl TonemapOperator {
+    fn index(&self) -> u32 {
+        match self {
+            TonemapOperator::Aces => 0,
+            TonemapOperator::Reinhard => 1,
+            TonemapOperator::Clamp => 2,
+        }
+    }
+}
+
+// Resource, inserted when the engine has a tonemap node. Read every frame, so systems can
+// animate it (fades, eye adaptation...).
+#[derive(Clone, Copy, Debug)]
+pub struct Exposure {
+    // Linear multiplier applied to the hdr color before the operator
+    pub exposure: f32,
+    pub operator: TonemapOperator,
+}
+
+impl Exposure {
+    pub fn new(operator: TonemapOperator) -> Self {
+        Self {
+            exposure: 1.0,
+            operator,
+        }
+    }
+
+    // In stops, so 0 is 1x and every +1 doubles the brightness
+    pub fn set_ev(&mut self, ev: f32) {
+        self.exposure = 2f32.powf(ev);
+    }
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self::new(TonemapOperator::Aces)
+    }
+}
+
+pub struct TonemapUniformGroup {}
+
+impl UniformGroupType<Self> for TonemapUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        UniformGroup::<TonemapUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(TonemapUniforms {
+                exposure: 1.0,
+                operator: 0,
+                encode_srgb: 0,
+                _padding: 0,
+            }))
+            .with_id(ID(TONEMAP_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TonemapUniforms {
+    pub exposure: f32,
+    pub operator: u32,
+    pub encode_srgb: u32,
+    pub _padding: u32,
+}
+
+#[system]
+pub fn tonemap_uniform(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] exposure: &Exposure,
+    #[resource] textures: &Arc<RwLock<TextureRegistry>>,
+    #[resource] tonemap_uniforms: &Arc<Mutex<GenericUniform<TonemapUniforms>>>,
+    #[resource] tonemap_uniforms_group: &Arc<Mutex<UniformGroup<TonemapUniformGroup>>>,
+) {
+    debug!("running system tonemap_uniform");
+    let mut forms = tonemap_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.exposure = exposure.exposure;
+    uniforms.operator = exposure.operator.index();
+    // sRGB surfaces encode on write, anything else needs it done in the shader
+    uniforms.encode_srgb = !textures.read().unwrap().format.describe().srgb as u32;
+
+    forms.write_buffer(
+        &queue,
+        tonemap_uniforms_group.lock().unwrap().default_buffer(0),
+    );
+}
+
+// Reads an hdr target through node input 0 and writes the swap chain, so it has to be the
+// master node. PostFxChainBuilder::with_tonemap sets this up.
+pub fn build_node_tonemap() -> NodeBuilder {
+    NodeBuilder::new(
+        "tonemap".to_owned(),
+        1,
+        1,
+        ShaderSource::WGSL(post_fx::with_template(include_str!(
+            "../shaders/post_fx/tonemap.wgsl"
+        ))),
+    )
+    .with_id(Uuid::new_v4())
+    .with_vertex_layout(VERTEX2D_BUFFER_LAYOUT)
+    .with_node_input()
+    .with_frame_uniforms()
+    .with_uniform_group(TonemapUniformGroup::builder())
+    .with_system(render_system)
+}
+
+#[system]
+pub fn render(
+    #[state] state: &mut NodeState,
+    #[resource] quad: &Quad,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system render_tonemap (graph node)");
+    let start_time = Instant::now();
+    let node = Arc::clone(&state.node);
+    let pipeline = node.pipeline.read().unwrap();
+
+    let render_target = state.render_target();
+    let render_target_mut = render_target.lock().unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Tonemap Encoder"),
+    });
+
+    let pass_res = render_target_mut.create_render_pass(&node.name, &mut encoder, true);
+    if pass_res.is_err() {
+        warn!("no target, aborting render pass: {}", node.name);
+        return;
+    }
+
+    let mut pass = pass_res.unwrap();
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, state.inputs[0].bind_group_ref(), &[]);
+    pass.set_bind_group(
+        1,
+        &node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)],
+        &[],
+    );
+    pass.set_bind_group(
+        2,
+        &node.binder.uniform_groups[&ID(TONEMAP_BIND_GROUP_ID)],
+        &[],
+    );
+
+    pass.set_vertex_buffer(0, quad.mesh.vertex_buffer.buffer.0.slice(..));
+    pass.set_index_buffer(
+        quad.mesh.index_buffer.buffer.0.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    pass.draw_indexed(0..quad.mesh.index_buffer.buffer.1, 0, 0..1);
+
+    drop(pass);
+    uploader.record(state.submission, encoder.finish());
+    state.reporter.update(start_time.elapsed().as_secs_f64());
+}
@@ -1,14 +1,12 @@
 use futures::task::SpawnExt;
 use iced_wgpu::{wgpu, Backend, Renderer, Settings, Viewport};
 use iced_winit::conversion;
-use std::{
-    sync::{Arc, Mutex},
-    time::Instant,
-};
+use instant::Instant;
+use std::sync::{Arc, Mutex};
 use wgpu::util::StagingBelt;
 
 use crate::{
-    renderer::graph::NodeState,
+    renderer::{buffer::upload::Uploader, graph::NodeState},
     sources::{
         metrics::SystemReporter,
         ui::iced::{IcedUI, IcedWinitHelper},
@@ -22,7 +20,7 @@ pub fn render(
     #[resource] helper: &Arc<Mutex<IcedWinitHelper>>,
     #[resource] staging_belt: &mut StagingBelt,
     #[resource] device: &Arc<wgpu::Device>,
-    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] uploader: &Arc<Uploader>,
 ) {
     let start_time = Instant::now();
     debug!("running system render_ui_iced");
@@ -54,7 +52,7 @@ pub fn render(
     });
 
     staging_belt.finish();
-    queue.submit(std::iter::once(encoder.finish()));
+    uploader.submit(encoder.finish());
 
     ui.local_pool
         .spawner()
@@ -15,11 +15,14 @@ use crate::{
         DEFAULT_DYNAMIC_BUFFER_MIN_BINDING_SIZE, DEFAULT_MAX_DYNAMIC_ENTITIES_PER_PASS,
         DEFAULT_MAX_INSTANCES_PER_BUFFER,
     },
-    renderer::uniform::generic::BufferState,
+    renderer::{buffer::upload::Uploader, uniform::generic::BufferState},
     sources::ResourceBuilder,
 };
 
-use super::{BufferBuilder, UniformBuilder};
+use super::{
+    storage::{StorageAccess, StorageBufferBuilder},
+    BufferBuilder, UniformBuilder,
+};
 
 #[derive(Clone, Copy)]
 pub enum BufferMode {
@@ -71,13 +74,12 @@ pub trait Group {
 pub struct GroupState {
     pub buffers: Arc<Vec<wgpu::Buffer>>,
     pub bind_group: Arc<wgpu::BindGroup>,
-    pub queue: Arc<wgpu::Queue>,
+    pub uploader: Arc<Uploader>,
 }
 
 impl GroupState {
     pub fn write_buffer(&self, index: usize, source_bytes: &[u8]) {
-        self.queue
-            .write_buffer(&self.buffers[index], 0, source_bytes)
+        self.uploader.write(&self.buffers[index], 0, source_bytes)
     }
 }
 
@@ -89,7 +91,7 @@ pub struct UniformGroup<N> {
     pub dynamic_offsets: DynamicOffsets,
 
     pub id: Uuid,
-    pub queue: Arc<wgpu::Queue>,
+    pub uploader: Arc<Uploader>,
     pub entity_count: Arc<Mutex<u64>>,
 
     _marker: PhantomData<N>,
@@ -105,8 +107,8 @@ impl<N> UniformGroup<N> {
     }
 
     pub fn write_buffer(&self, index: usize, source_bytes: &[u8]) {
-        self.queue
-            .write_buffer(&self.default_state.buffers[index], 0, source_bytes)
+        self.uploader
+            .write(&self.default_state.buffers[index], 0, source_bytes)
     }
 
     pub fn begin_dynamic_loading(&mut self) {
@@ -115,7 +117,7 @@ impl<N> UniformGroup<N> {
 
     pub fn load_dynamic_uniform(&mut self, source_bytes: &[u8]) {
         for i in 0..self.default_state.buffers.len() {
-            self.queue.write_buffer(
+            self.uploader.write(
                 &self.default_state.buffers[i],
                 self.dynamic_offsets.state[i],
                 source_bytes,
@@ -141,10 +143,12 @@ pub trait GroupBuilder {
         &mut self,
         device: &wgpu::Device,
         resources: &mut Resources,
-        queue: Arc<wgpu::Queue>,
+        uploader: Arc<Uploader>,
     ) -> Result<wgpu::BindGroupLayout>;
     fn dynamic(&self) -> Option<(Arc<Mutex<u64>>, Vec<(u64, u64)>)>;
     fn binding(&self) -> (Uuid, Arc<wgpu::BindGroup>);
+    // The layout's entries, once built
+    fn entries(&self) -> Vec<wgpu::BindGroupLayoutEntry>;
 }
 
 pub trait GroupResourceBuilder: GroupBuilder + ResourceBuilder {}
@@ -195,6 +199,22 @@ impl<N> UniformGroupBuilder<N> {
         self
     }
 
+    // Adds a read only storage buffer of up to capacity Ts at the next binding. Its contents
+    // are set through the Arc<Mutex<StorageBuffer<T>>> resource.
+    pub fn with_storage_buffer<T>(self, capacity: usize) -> Self
+    where
+        T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug + Send + Sync + 'static,
+    {
+        self.with_storage_buffer_access::<T>(capacity, StorageAccess::ReadOnly)
+    }
+
+    pub fn with_storage_buffer_access<T>(self, capacity: usize, access: StorageAccess) -> Self
+    where
+        T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug + Send + Sync + 'static,
+    {
+        self.with_uniform(StorageBufferBuilder::<T>::new(capacity, access))
+    }
+
     pub fn with_id(mut self, id: Uuid) -> Self {
         self.id = id;
         self
@@ -238,7 +258,7 @@ where
         &mut self,
         device: &wgpu::Device,
         resources: &mut Resources,
-        queue: Arc<wgpu::Queue>,
+        uploader: Arc<Uploader>,
     ) -> Result<wgpu::BindGroupLayout> {
         debug!(
             "UniformGroupBuilder: building {} with {} bind entries",
@@ -271,6 +291,7 @@ where
 
         let entries: Vec<wgpu::BindGroupLayoutEntry> = (0..buffer_states.len())
             .map(|i| {
+                let uniform = self.uniforms[i].lock().unwrap();
                 let has_dynamic_offset = buffer_states[i].mode.is_dynamic();
                 let min_binding_size = match has_dynamic_offset {
                     false => None, // buffer_states[i].element_size as u64,
@@ -279,9 +300,9 @@ where
 
                 wgpu::BindGroupLayoutEntry {
                     binding: i as u32,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    visibility: uniform.visibility(),
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
+                        ty: uniform.binding_type(),
                         has_dynamic_offset,
                         min_binding_size,
                     },
@@ -334,11 +355,11 @@ where
             default_state: GroupState {
                 buffers: Arc::new(buffer_states.into_iter().map(|s| s.buffer).collect()),
                 bind_group: Arc::clone(&self.bind_group.as_ref().unwrap()),
-                queue: Arc::clone(&queue),
+                uploader: Arc::clone(&uploader),
             },
             entity_count: Arc::clone(&self.entity_count),
             states: vec![],
-            queue,
+            uploader,
             id: self.id,
             mode: self.mode,
             _marker: PhantomData,
@@ -368,6 +389,10 @@ where
     fn binding(&self) -> (Uuid, Arc<wgpu::BindGroup>) {
         (self.id, Arc::clone(&self.bind_group.as_ref().unwrap()))
     }
+
+    fn entries(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.entries.clone().unwrap_or_default()
+    }
 }
 
 pub struct GroupStateBuilder<N> {
@@ -394,7 +419,7 @@ where
     pub fn single_state(
         &self,
         device: &wgpu::Device,
-        queue: &Arc<wgpu::Queue>,
+        uploader: &Arc<Uploader>,
     ) -> Result<GroupState> {
         debug!(
             "GroupStateBuilder: new state {} with {} bind entries",
@@ -448,7 +473,7 @@ where
         Ok(GroupState {
             buffers: Arc::new(buffer_states.into_iter().map(|s| s.buffer).collect()),
             bind_group: Arc::clone(&bind_group),
-            queue: Arc::clone(&queue),
+            uploader: Arc::clone(uploader),
         })
     }
 }
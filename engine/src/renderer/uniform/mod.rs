@@ -8,6 +8,7 @@ use crate::sources::ResourceBuilder;
 
 pub mod generic;
 pub mod group;
+pub mod storage;
 
 pub trait Uniform {
     fn write_buffer(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer);
@@ -25,6 +26,15 @@ pub trait UniformBuilder: ResourceBuilder {
     // -> (buffer, source size, max dynamic offsets per render pass)
     fn build_buffer(&mut self, device: &wgpu::Device, mode: BufferMode) -> BufferState;
     fn dynamic_size(&self) -> u64;
+
+    // Uniform buffers unless overridden, ie. by StorageBufferBuilder
+    fn binding_type(&self) -> wgpu::BufferBindingType {
+        wgpu::BufferBindingType::Uniform
+    }
+
+    fn visibility(&self) -> wgpu::ShaderStages {
+        wgpu::ShaderStages::VERTEX_FRAGMENT
+    }
 }
 
 pub trait BufferBuilder: Send + Sync {
@@ -0,0 +1,147 @@
+use legion::Resources;
+use std::{
+    any::type_name,
+    fmt::Debug,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use super::{generic::BufferState, group::BufferMode, BufferBuilder, Uniform, UniformBuilder};
+use crate::sources::ResourceBuilder;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageAccess {
+    // var<storage, read> in the shader, visible to both stages
+    ReadOnly,
+    // var<storage, read_write>; wgpu doesn't allow writable storage in vertex shaders, so
+    // these are only visible to the fragment stage
+    ReadWrite,
+}
+
+impl StorageAccess {
+    pub fn visibility(&self) -> wgpu::ShaderStages {
+        match self {
+            StorageAccess::ReadOnly => wgpu::ShaderStages::VERTEX_FRAGMENT,
+            StorageAccess::ReadWrite => wgpu::ShaderStages::FRAGMENT,
+        }
+    }
+}
+
+// A storage buffer holding up to capacity Ts, for arrays too large for a uniform buffer
+// (lights, bones, particles). Added to a group with UniformGroupBuilder::with_storage_buffer.
+pub struct StorageBufferBuilder<T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug> {
+    pub capacity: usize,
+    pub access: StorageAccess,
+    pub dest: Option<Arc<Mutex<StorageBuffer<T>>>>,
+}
+
+impl<T> StorageBufferBuilder<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug,
+{
+    pub fn new(capacity: usize, access: StorageAccess) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            access,
+            dest: None,
+        }
+    }
+
+    fn create_buffer(&self, device: &wgpu::Device) -> BufferState {
+        let size = (self.capacity * size_of::<T>()) as u64;
+        BufferState {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("Storage Buffer: {}", type_name::<T>())),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            element_size: size,
+            mode: BufferMode::Single,
+        }
+    }
+}
+
+impl<T> ResourceBuilder for StorageBufferBuilder<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug + Send + 'static,
+{
+    fn build_to_resource(&self, resources: &mut Resources) {
+        resources.insert(Arc::clone(self.dest.as_ref().unwrap()));
+    }
+}
+
+impl<T> UniformBuilder for StorageBufferBuilder<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug + Send + 'static,
+{
+    // Storage buffers are indexed by the shader, so the group's mode doesn't apply
+    fn build_buffer(&mut self, device: &wgpu::Device, _mode: BufferMode) -> BufferState {
+        self.dest = Some(Arc::new(Mutex::new(StorageBuffer {
+            source: vec![],
+            capacity: self.capacity,
+        })));
+        self.create_buffer(device)
+    }
+
+    fn dynamic_size(&self) -> u64 {
+        (self.capacity * size_of::<T>()) as u64
+    }
+
+    fn binding_type(&self) -> wgpu::BufferBindingType {
+        wgpu::BufferBindingType::Storage {
+            read_only: self.access == StorageAccess::ReadOnly,
+        }
+    }
+
+    fn visibility(&self) -> wgpu::ShaderStages {
+        self.access.visibility()
+    }
+}
+
+impl<T> BufferBuilder for StorageBufferBuilder<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug + Send + Sync,
+{
+    fn single_buffer(&self, device: &wgpu::Device) -> BufferState {
+        self.create_buffer(device)
+    }
+}
+
+// The CPU side of a storage buffer, inserted as an Arc<Mutex<StorageBuffer<T>>> resource.
+// Only the first capacity elements are uploaded, and the rest of the buffer keeps whatever
+// was last written to it, so shaders should be told the count (eg. in a uniform).
+#[derive(Clone, Debug)]
+pub struct StorageBuffer<T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug> {
+    pub source: Vec<T>,
+    pub capacity: usize,
+}
+
+impl<T> StorageBuffer<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug,
+{
+    pub fn mut_ref(&mut self) -> &mut Vec<T> {
+        &mut self.source
+    }
+
+    pub fn len(&self) -> usize {
+        self.source.len().min(self.capacity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Uniform for StorageBuffer<T>
+where
+    T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + Debug,
+{
+    fn write_buffer(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer) {
+        if self.source.is_empty() {
+            return;
+        }
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&self.source[..self.len()]));
+    }
+}
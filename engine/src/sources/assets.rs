@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+
+#[cfg(target_arch = "wasm32")]
+use once_cell::sync::Lazy;
+#[cfg(target_arch = "wasm32")]
+use std::{collections::HashMap, sync::RwLock};
+
+// Where the registries (and anything else loading a file by path) get its bytes from. On
+// desktop that's the filesystem. In the browser nothing can block, so files are fetched
+// ahead of time, relative to the page, and read out of memory after that; the async builds
+// (EngineBuilder::default_3d_async) fetch everything the builder knows about, and anything
+// else can be fetched with fetch before it's loaded.
+
+#[cfg(target_arch = "wasm32")]
+static FETCHED: Lazy<RwLock<HashMap<String, Vec<u8>>>> = Lazy::new(Default::default);
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    Ok(std::fs::read(path)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    FETCHED
+        .read()
+        .unwrap()
+        .get(path)
+        .cloned()
+        .ok_or_else(|| anyhow!("{} was never fetched; see sources::assets::fetch", path))
+}
+
+pub fn read_to_string(path: &str) -> Result<String> {
+    String::from_utf8(read(path)?).map_err(|err| anyhow!("{}: {}", path, err))
+}
+
+// Nothing to do on desktop, where files are read when they're loaded
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn fetch(_paths: &[String]) -> Result<()> {
+    Ok(())
+}
+
+// All at once; fails on the first file that can't be fetched
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch(paths: &[String]) -> Result<()> {
+    let paths: Vec<&String> = {
+        let fetched = FETCHED.read().unwrap();
+        paths
+            .iter()
+            .filter(|path| !fetched.contains_key(path.as_str()))
+            .collect()
+    };
+    info!("fetching {} assets", paths.len());
+    let files =
+        futures::future::try_join_all(paths.into_iter().map(|path| fetch_one(path))).await?;
+    FETCHED.write().unwrap().extend(files);
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_one(path: &str) -> Result<(String, Vec<u8>)> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let error = |err: wasm_bindgen::JsValue| anyhow!("error fetching {}: {:?}", path, err);
+    let window = web_sys::window().ok_or_else(|| anyhow!("fetch: no window"))?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(path))
+        .await
+        .map_err(error)?
+        .dyn_into()
+        .map_err(error)?;
+    if !response.ok() {
+        return Err(anyhow!(
+            "error fetching {}: {} {}",
+            path,
+            response.status(),
+            response.status_text()
+        ));
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(error)?)
+        .await
+        .map_err(error)?;
+    Ok((path.to_owned(), js_sys::Uint8Array::new(&buffer).to_vec()))
+}
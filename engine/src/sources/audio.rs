@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+use rodio::{
+    buffer::SamplesBuffer, Decoder, OutputStream, OutputStreamHandle, Source, SpatialSink,
+};
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+use uuid::Uuid;
+
+use super::assets;
+
+// Sounds are decoded once at load, and every playback gets its own copy of the samples.
+// Playback itself is done by systems::audio, one sink per AudioSource.
+
+pub struct Sound {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Arc<Vec<i16>>,
+}
+
+impl Sound {
+    // wav or ogg (vorbis)
+    pub fn load(path: &str) -> Result<Self> {
+        let bytes =
+            assets::read(path).map_err(|err| anyhow!("error loading sound {}: {}", path, err))?;
+        let decoder = Decoder::new(Cursor::new(bytes))
+            .map_err(|err| anyhow!("error decoding sound {}: {}", path, err))?;
+        Ok(Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            samples: Arc::new(decoder.collect()),
+        })
+    }
+
+    pub fn source(&self) -> SamplesBuffer<i16> {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.to_vec())
+    }
+}
+
+pub struct AudioRegistry {
+    pub sounds: HashMap<Uuid, Sound>,
+}
+
+impl AudioRegistry {
+    pub fn new() -> Self {
+        Self {
+            sounds: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Uuid> {
+        let id = Uuid::new_v4();
+        self.load_id(id, path)?;
+        Ok(id)
+    }
+
+    pub fn load_id(&mut self, id: Uuid, path: &str) -> Result<()> {
+        debug!("loading sound: {}", path);
+        self.sounds.insert(id, Sound::load(path)?);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&Sound> {
+        self.sounds.get(id)
+    }
+}
+
+// Rodio's falloff is 1/d^2 past one unit away, with the ears this far either side
+const EAR_OFFSET: f32 = 0.1;
+
+pub struct AudioListener {
+    pub position: [f32; 3],
+    // From the left ear to the right one
+    pub right: [f32; 3],
+    pub volume: f32,
+    // World units to rodio's; eg. 0.01 for a 2D game measured in pixels
+    pub scale: f32,
+    // Holds every AudioSource where it is; see systems::audio::pause_audio
+    pub paused: bool,
+
+    // None without an output device, in which case nothing plays
+    handle: Option<OutputStreamHandle>,
+}
+
+impl AudioListener {
+    pub fn new() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            right: [1.0, 0.0, 0.0],
+            volume: 1.0,
+            scale: 1.0,
+            paused: false,
+            handle: open_output(),
+        }
+    }
+
+    pub fn handle(&self) -> Option<&OutputStreamHandle> {
+        self.handle.as_ref()
+    }
+
+    // In rodio's units
+    pub fn to_local(&self, position: [f32; 3]) -> [f32; 3] {
+        [
+            position[0] * self.scale,
+            position[1] * self.scale,
+            position[2] * self.scale,
+        ]
+    }
+
+    pub fn ears(&self) -> ([f32; 3], [f32; 3]) {
+        let center = self.to_local(self.position);
+        let ear = |side: f32| -> [f32; 3] {
+            [
+                center[0] + self.right[0] * EAR_OFFSET * side,
+                center[1] + self.right[1] * EAR_OFFSET * side,
+                center[2] + self.right[2] * EAR_OFFSET * side,
+            ]
+        };
+        (ear(-1.0), ear(1.0))
+    }
+}
+
+// OutputStream isn't Send, so it lives on a thread of its own for as long as the game runs
+#[cfg(not(target_arch = "wasm32"))]
+fn open_output() -> Option<OutputStreamHandle> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("audio_output".to_owned())
+        .spawn(move || match OutputStream::try_default() {
+            Ok((_stream, handle)) => {
+                let _ = sender.send(Some(handle));
+                loop {
+                    std::thread::park();
+                }
+            }
+            Err(err) => {
+                warn!("no audio output, sounds won't play: {}", err);
+                let _ = sender.send(None);
+            }
+        });
+    if let Err(err) = spawned {
+        warn!("failed to start audio thread: {}", err);
+        return None;
+    }
+    receiver.recv().ok().flatten()
+}
+
+// No threads in the browser; the page is the only thread, and lives as long as the game
+#[cfg(target_arch = "wasm32")]
+fn open_output() -> Option<OutputStreamHandle> {
+    match OutputStream::try_default() {
+        Ok((stream, handle)) => {
+            std::mem::forget(stream);
+            Some(handle)
+        }
+        Err(err) => {
+            warn!("no audio output, sounds won't play: {}", err);
+            None
+        }
+    }
+}
+
+// Plays a sound from the registry. Panned and attenuated by the entity's Transform3D or
+// Position2D relative to the AudioListener, unless it isn't spatial (music, UI).
+pub struct AudioSource {
+    pub sound: Uuid,
+    pub volume: f32,
+    pub looping: bool,
+    // Set to start playback (from the beginning), unset to stop it. Unset by
+    // systems::audio once a non-looping sound has finished.
+    pub playing: bool,
+    pub spatial: bool,
+
+    pub(crate) sink: Option<SpatialSink>,
+}
+
+impl AudioSource {
+    pub fn new(sound: Uuid) -> Self {
+        Self {
+            sound,
+            volume: 1.0,
+            looping: false,
+            playing: true,
+            spatial: true,
+            sink: None,
+        }
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    pub fn ambient(mut self) -> Self {
+        self.spatial = false;
+        self
+    }
+}
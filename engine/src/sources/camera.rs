@@ -2,11 +2,46 @@ use cgmath::EuclideanSpace;
 
 use crate::constants::OPENGL_TO_WGPU_MATRIX;
 
-pub struct Camera3D {
-    pub speed: f32,
-    pub sensitivity: f32,
-    pub scroll_sensitivity: f32,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // fov is vertical, in degrees
+    Perspective { fov: f32, near: f32, far: f32 },
+    // size is the height of the view volume in world units; width follows the aspect
+    Orthographic { size: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> cgmath::Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fov, near, far } => {
+                cgmath::perspective(cgmath::Deg(fov), aspect, near, far)
+            }
+            Projection::Orthographic { size, near, far } => {
+                let half_height = size / 2.0;
+                let half_width = half_height * aspect;
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
 
+    // [near, far]
+    pub fn clip(&self) -> [f32; 2] {
+        match *self {
+            Projection::Perspective { near, far, .. } => [near, far],
+            Projection::Orthographic { near, far, .. } => [near, far],
+        }
+    }
+}
+
+// Moved around by the CameraController3D resource (systems::camera_3d)
+pub struct Camera3D {
     // State
     pub pos: cgmath::Point3<f32>,
     pub dir: cgmath::Point3<f32>,
@@ -16,32 +51,26 @@ pub struct Camera3D {
 
     pub up: cgmath::Vector3<f32>,
     pub aspect: f32,
-    pub fov: f32,
-
-    pub z_near: f32,
-    pub z_far: f32,
+    pub projection: Projection,
 
     pub first: bool,
-    pub right_click_move: bool,
 }
 
 impl Camera3D {
     pub fn default(screen_width: f32, screen_height: f32) -> Self {
         Self {
-            speed: 0.2,
-            sensitivity: 0.15,
-            scroll_sensitivity: 0.5,
             pos: (0.0, 8.0, -20.0).into(),
             dir: (0.0, -0.6, 1.0).into(),
             pitch: -5.0,
             yaw: 90.0,
             up: cgmath::Vector3::unit_y(),
             aspect: screen_width / screen_height,
-            fov: 45.0,
-            z_near: 0.01,
-            z_far: 10000.0,
+            projection: Projection::Perspective {
+                fov: 45.0,
+                near: 0.01,
+                far: 10000.0,
+            },
             first: true,
-            right_click_move: false,
         }
     }
 
@@ -51,15 +80,31 @@ impl Camera3D {
 
     pub fn build_view_proj(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.pos, self.pos + self.dir.to_vec(), self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fov), self.aspect, self.z_near, self.z_far);
+        let proj = self.projection.matrix(self.aspect);
         return OPENGL_TO_WGPU_MATRIX * proj * view;
     }
 }
 
+// What one unit of 2D world space (Position2D, Render2D sizes, colliders...) is on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Units2D {
+    // A physical pixel, so everything shrinks on hiDPI displays
+    Pixels,
+    // A logical point: physical pixels over the window's scale factor, so sizes match across
+    // displays
+    Points,
+    // pixels_per_unit logical points, eg. 16 for a game of 16x16 tiles one unit apart
+    World { pixels_per_unit: f32 },
+}
+
 pub struct Camera2D {
     pub pos: cgmath::Point2<f32>,
+    // The view in physical pixels; camera_2d_system keeps it at the screen size
     pub size: cgmath::Point2<f32>,
     pub zoom: f32,
+    pub units: Units2D,
+    // The window's, kept up to date by the engine
+    pub scale_factor: f32,
 }
 
 impl Camera2D {
@@ -68,6 +113,33 @@ impl Camera2D {
             pos: (0.0, 0.0).into(),
             size: (screen_width, screen_height).into(),
             zoom: 1.0,
+            units: Units2D::Points,
+            scale_factor: 1.0,
+        }
+    }
+
+    // Physical pixels per world unit, before zoom
+    pub fn pixels_per_unit(&self) -> f32 {
+        match self.units {
+            Units2D::Pixels => 1.0,
+            Units2D::Points => self.scale_factor,
+            Units2D::World { pixels_per_unit } => pixels_per_unit * self.scale_factor,
         }
     }
+
+    // Half the view's width and height in world units: the 2D shaders' view.zw
+    pub fn half_extents(&self) -> [f32; 2] {
+        let pixels = (self.pixels_per_unit() * self.zoom).max(f32::EPSILON);
+        [self.size.x / 2.0 / pixels, self.size.y / 2.0 / pixels]
+    }
+
+    // Normalized device coordinates (y up) to world space; the inverse of the 2D shaders'
+    // (world + view.xy) / view.zw
+    pub fn ndc_to_world(&self, ndc: [f32; 2]) -> [f32; 2] {
+        let [half_width, half_height] = self.half_extents();
+        [
+            ndc[0] * half_width - self.pos.x,
+            ndc[1] * half_height - self.pos.y,
+        ]
+    }
 }
@@ -0,0 +1,93 @@
+use legion::{
+    storage::{Component, IntoComponentSource},
+    Entity, World,
+};
+use std::sync::{Arc, Mutex};
+
+type WorldCommand = Box<dyn FnOnce(&mut World) + Send>;
+
+// World changes queued from any thread (asset loaders, networking, scripting...), since
+// Engine::world() can't be reached once Engine::start() has taken over the thread.
+//
+// Clone the queue from Engine::command_queue() before starting, or take it from the
+// resources in a system. Commands are applied in order, once per frame, right before
+// the schedule executes.
+#[derive(Clone, Default)]
+pub struct CommandQueue {
+    pending: Arc<Mutex<Vec<WorldCommand>>>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn spawn<T>(&self, components: T)
+    where
+        Option<T>: IntoComponentSource,
+        T: Send + 'static,
+    {
+        self.exec(move |world| {
+            world.push(components);
+        });
+    }
+
+    // Spawns, then hands the new entity to `then` in the same sync point
+    pub fn spawn_then<T, F>(&self, components: T, then: F)
+    where
+        Option<T>: IntoComponentSource,
+        T: Send + 'static,
+        F: FnOnce(&mut World, Entity) + Send + 'static,
+    {
+        self.exec(move |world| {
+            let entity = world.push(components);
+            then(world, entity);
+        });
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.exec(move |world| {
+            if !world.remove(entity) {
+                warn!("despawn command: no such entity {:?}", entity);
+            }
+        });
+    }
+
+    pub fn add_component<C: Component>(&self, entity: Entity, component: C) {
+        self.exec(move |world| match world.entry(entity) {
+            Some(mut entry) => entry.add_component(component),
+            None => warn!("add_component command: no such entity {:?}", entity),
+        });
+    }
+
+    pub fn remove_component<C: Component>(&self, entity: Entity) {
+        self.exec(move |world| match world.entry(entity) {
+            Some(mut entry) => entry.remove_component::<C>(),
+            None => warn!("remove_component command: no such entity {:?}", entity),
+        });
+    }
+
+    // Anything else
+    pub fn exec(&self, command: impl FnOnce(&mut World) + Send + 'static) {
+        self.pending.lock().unwrap().push(Box::new(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn apply(&self, world: &mut World) {
+        // Taken first, so commands can queue more commands (applied next frame)
+        let commands = std::mem::take(&mut *self.pending.lock().unwrap());
+        if !commands.is_empty() {
+            debug!("applying {} queued world commands", commands.len());
+        }
+        for command in commands {
+            command(world);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{constants::DEFAULT_BACKGROUND_FPS, sources::limiter::FrameLimiter};
+
+// When the engine stops its event loop by itself; EngineCommand::Exit always does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitPolicy {
+    // The window's close button, or the InputMap's QUIT_ACTION (Escape by default)
+    OnCloseOrQuit,
+    // Only the close button
+    OnClose,
+    // Never; the game sees close requests through EngineCommands::take_close_request
+    Manual,
+}
+
+impl Default for ExitPolicy {
+    fn default() -> Self {
+        ExitPolicy::OnCloseOrQuit
+    }
+}
+
+// What the engine does while in the background: the window unfocused or minimized, or the
+// app suspended (eg. on mobile). Games hear about it through WindowEvent::Background in
+// Events<sources::events::WindowEvent>.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackgroundPolicy {
+    // Frame cap in the background; None keeps the foreground's. While minimized or
+    // suspended there's no surface to draw to, so frames don't run at all (and the event loop
+    // idles at this rate).
+    pub fps: Option<f32>,
+    // Sets EngineState::paused, and unsets it on coming back (unless the game did already)
+    pub pause: bool,
+    // Pauses every AudioSource; see systems::audio::pause_audio
+    pub pause_audio: bool,
+}
+
+impl Default for BackgroundPolicy {
+    fn default() -> Self {
+        Self {
+            fps: Some(DEFAULT_BACKGROUND_FPS),
+            pause: false,
+            pause_audio: false,
+        }
+    }
+}
+
+// The engine's side of BackgroundPolicy: why the window is in the background, if it is, and
+// what's been done about it
+pub(crate) struct BackgroundState {
+    pub policy: BackgroundPolicy,
+    pub limiter: Option<FrameLimiter>,
+    pub focused: bool,
+    pub minimized: bool,
+    pub suspended: bool,
+    // As of the last update()
+    pub background: bool,
+    pub visible: bool,
+    // Whether EngineState::paused was set by the policy, rather than the game
+    pub paused: bool,
+}
+
+impl BackgroundState {
+    pub fn new(policy: BackgroundPolicy) -> Self {
+        Self {
+            policy,
+            limiter: policy.fps.map(FrameLimiter::new),
+            focused: true,
+            minimized: false,
+            suspended: false,
+            background: false,
+            visible: true,
+            paused: false,
+        }
+    }
+
+    // Returns whether the window entered (true) or left the background since the last call,
+    // and whether it became visible again
+    pub fn update(&mut self) -> (Option<bool>, bool) {
+        let background = !self.focused || self.minimized || self.suspended;
+        let visible = !self.minimized && !self.suspended;
+        let changed = match background != self.background {
+            true => Some(background),
+            false => None,
+        };
+        let shown = visible && !self.visible;
+        self.background = background;
+        self.visible = visible;
+        (changed, shown)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorMode {
+    // Grabbed (and hidden) while the right mouse button is held
+    GrabOnRightClick,
+    Free,
+    // e.g. for first person cameras; release it with EngineCommand::ReleaseCursor
+    Grabbed,
+}
+
+impl Default for CursorMode {
+    fn default() -> Self {
+        CursorMode::GrabOnRightClick
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineCommand {
+    Exit,
+    ToggleFullscreen,
+    // Until the next right click (GrabOnRightClick) or GrabCursor
+    ReleaseCursor,
+    GrabCursor,
+    SetCursorMode(CursorMode),
+}
+
+// Event loop control for game systems, which can't reach the Engine once it has started.
+// Commands are handled by the engine between frames, in order.
+#[derive(Clone, Default)]
+pub struct EngineCommands {
+    inner: Arc<Mutex<EngineCommandState>>,
+}
+
+#[derive(Default)]
+struct EngineCommandState {
+    pending: Vec<EngineCommand>,
+    close_requested: bool,
+}
+
+impl EngineCommands {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&self, command: EngineCommand) {
+        self.inner.lock().unwrap().pending.push(command);
+    }
+
+    pub fn exit(&self) {
+        self.push(EngineCommand::Exit);
+    }
+
+    // Whether the window's close button was pressed since the last call. Only with
+    // ExitPolicy::Manual; otherwise the engine has already exited.
+    pub fn take_close_request(&self) -> bool {
+        std::mem::take(&mut self.inner.lock().unwrap().close_requested)
+    }
+
+    pub(crate) fn request_close(&self) {
+        self.inner.lock().unwrap().close_requested = true;
+    }
+
+    pub(crate) fn take(&self) -> Vec<EngineCommand> {
+        std::mem::take(&mut self.inner.lock().unwrap().pending)
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex};
+
+// Display changes (window resizes, render scale, fullscreen), requested from anywhere at
+// any point in the frame. Resizing the surface and graph targets while nodes are recording
+// leaves half-resized targets behind, so instead they're batched here and applied together:
+//
+// - fullscreen, by the engine between frames (window calls must stay on the main thread)
+// - size and render scale, by begin_render_graph, before any target is touched
+//
+// Toggling fullscreen resizes the window, which then comes back through request_resize.
+#[derive(Clone, Default)]
+pub struct DisplayQueue {
+    inner: Arc<Mutex<DisplayState>>,
+}
+
+#[derive(Default)]
+struct DisplayState {
+    current: DisplaySettings,
+    size: Option<(u32, u32)>,
+    render_scale: Option<f32>,
+    fullscreen: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DisplaySettings {
+    // Node targets are rendered at this fraction of the window size; the master is not
+    pub render_scale: f32,
+    pub fullscreen: bool,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            render_scale: 1.0,
+            fullscreen: false,
+        }
+    }
+}
+
+// Everything applied in one go
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayChange {
+    pub size: Option<(u32, u32)>,
+    pub render_scale: Option<f32>,
+}
+
+impl DisplayQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Starting state, for windows created fullscreen
+    pub(crate) fn with_fullscreen(self, fullscreen: bool) -> Self {
+        self.inner.lock().unwrap().current.fullscreen = fullscreen;
+        self
+    }
+
+    // Settings as of the last applied change
+    pub fn settings(&self) -> DisplaySettings {
+        self.inner.lock().unwrap().current
+    }
+
+    pub fn request_resize(&self, size: (u32, u32)) {
+        self.inner.lock().unwrap().size = Some(size);
+    }
+
+    pub fn set_render_scale(&self, scale: f32) {
+        self.inner.lock().unwrap().render_scale = Some(scale.max(0.1).min(2.0));
+    }
+
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.inner.lock().unwrap().fullscreen = Some(fullscreen);
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        let mut state = self.inner.lock().unwrap();
+        let fullscreen = state.fullscreen.unwrap_or(state.current.fullscreen);
+        state.fullscreen = Some(!fullscreen);
+    }
+
+    pub(crate) fn take_fullscreen(&self) -> Option<bool> {
+        let mut state = self.inner.lock().unwrap();
+        let fullscreen = state.fullscreen.take()?;
+        if fullscreen == state.current.fullscreen {
+            return None;
+        }
+        state.current.fullscreen = fullscreen;
+        Some(fullscreen)
+    }
+
+    pub(crate) fn take_change(&self) -> Option<DisplayChange> {
+        let mut state = self.inner.lock().unwrap();
+        let render_scale = state
+            .render_scale
+            .take()
+            .filter(|scale| *scale != state.current.render_scale);
+        let change = DisplayChange {
+            size: state.size.take(),
+            render_scale,
+        };
+        match change.size.is_some() || change.render_scale.is_some() {
+            true => Some(change),
+            false => None,
+        }
+    }
+
+    // Once the targets have been resized; settings() keeps the old render scale until then
+    pub(crate) fn applied(&self, change: &DisplayChange) {
+        if let Some(scale) = change.render_scale {
+            self.inner.lock().unwrap().current.render_scale = scale;
+        }
+    }
+
+    // A change which couldn't be applied (minimized) keeps its render scale for the next one,
+    // unless a newer one was requested since
+    pub(crate) fn defer(&self, change: &DisplayChange) {
+        if let Some(scale) = change.render_scale {
+            self.inner.lock().unwrap().render_scale.get_or_insert(scale);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Result};
+use image::{Rgba32FImage, RgbaImage};
+use std::{
+    f32::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Same order as the registry's cubemaps
+const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Written next to the faces (or the equirect, as <name>.irradiance.txt), so the integration
+// only runs once per environment
+const CACHE_FILE: &str = "irradiance.txt";
+
+// Equirects are integrated at (at most) this width; irradiance has no detail to lose
+const EQUIRECT_WIDTH: u32 = 512;
+
+// The engine's default cubemap, for when no environment is loaded
+pub const DEFAULT_IRRADIANCE: [[f32; 3]; 9] = [
+    [0.4167677, 0.41648358, 0.38331264],
+    [-0.0043605487, -0.0026134395, -0.0006568894],
+    [-0.01213964, -0.008434562, 0.023041306],
+    [0.46987548, 0.4635618, 0.42295167],
+    [0.015393221, 0.015422308, 0.010281778],
+    [-0.011692239, -0.014198665, -0.019392435],
+    [0.27746662, 0.27147454, 0.24605234],
+    [-0.00097278244, 0.010546771, 0.045822047],
+    [0.3920225, 0.36590222, 0.32920602],
+];
+
+// Spherical harmonic coefficients of an environment's diffuse irradiance, in the order
+// L00, L1-1, L10, L11, L2-2, L2-1, L20, L21, L22
+// http://graphics.stanford.edu/papers/envmap/envmap.pdf
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IrradianceCoefficients {
+    pub data: [[f32; 3]; 9],
+}
+
+impl IrradianceCoefficients {
+    // A cubemap directory (px.png, nx.png, ...) or an equirectangular image, from its cache
+    // if it has one
+    pub fn load(path: &str) -> Result<Self> {
+        let cache = match is_equirect(path) {
+            true => PathBuf::from(format!("{}.{}", path, CACHE_FILE)),
+            false => Path::new(path).join(CACHE_FILE),
+        };
+        if cache.exists() {
+            match Self::read_cache(&cache) {
+                Ok(coeffs) => return Ok(coeffs),
+                Err(err) => warn!("{}, recomputing", err),
+            }
+        }
+
+        let coeffs = match is_equirect(path) {
+            true => Self::from_equirect(&load_equirect(path)?),
+            false => Self::from_cubemap(&load_cubemap_faces(path)?),
+        };
+        if let Err(err) = coeffs.write_cache(&cache) {
+            warn!("couldn't cache irradiance at {}: {}", cache.display(), err);
+        }
+        Ok(coeffs)
+    }
+
+    pub fn read_cache(cache: &Path) -> Result<Self> {
+        let text = fs::read_to_string(cache)?;
+        let values = text
+            .split_whitespace()
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|err| anyhow!("{}: {}", cache.display(), err))?;
+        if values.len() != 27 {
+            return Err(anyhow!(
+                "{}: expected 27 values, found {}",
+                cache.display(),
+                values.len()
+            ));
+        }
+
+        let mut coeffs = Self::default();
+        for (i, value) in values.into_iter().enumerate() {
+            coeffs.data[i / 3][i % 3] = value;
+        }
+        Ok(coeffs)
+    }
+
+    pub fn write_cache(&self, cache: &Path) -> Result<()> {
+        let text: Vec<String> = self
+            .data
+            .iter()
+            .map(|c| format!("{} {} {}", c[0], c[1], c[2]))
+            .collect();
+        fs::write(cache, text.join("\n"))?;
+        Ok(())
+    }
+
+    pub fn from_cubemap(faces: &[RgbaImage]) -> Self {
+        // Integrate over environment and compute coefficients
+        let mut coeffs = IrradianceCoefficients::default();
+        let size = (faces[0].width(), faces[1].height());
+
+        debug!("computing irradiance coefficients, face_size: {:?}", size);
+
+        for (i, face) in faces.iter().enumerate() {
+            debug!("integrating face {}", i);
+            for col in 0..size.0 {
+                for row in 0..size.1 {
+                    let cube_coords = CubeCoords {
+                        u: col as f32 / size.0 as f32,
+                        v: row as f32 / size.1 as f32,
+                        index: i,
+                    };
+
+                    let cartesian = cube_coords.to_cartesian();
+                    let (x, y, z) = (cartesian[0], cartesian[1], cartesian[2]);
+                    let spherical = SphereCoords::from(cartesian);
+                    let d_omega = (2.0 * PI / size.0 as f32)
+                        * (2.0 * PI / size.0 as f32)
+                        * sinc(spherical.theta);
+
+                    let hdr = cube_coords.sample(size, face);
+                    coeffs.add_sample([x, y, z], hdr, d_omega);
+                }
+            }
+        }
+        coeffs
+    }
+
+    // With the mapping the equirect_to_cube shader uses: u is the angle around +y, starting
+    // at -x, and v the angle down from +y
+    pub fn from_equirect(image: &Rgba32FImage) -> Self {
+        let mut coeffs = IrradianceCoefficients::default();
+        let image = match image.width() > EQUIRECT_WIDTH {
+            true => image::imageops::resize(
+                image,
+                EQUIRECT_WIDTH,
+                (EQUIRECT_WIDTH / 2).max(1),
+                image::imageops::FilterType::Triangle,
+            ),
+            false => image.clone(),
+        };
+        let (width, height) = image.dimensions();
+
+        debug!(
+            "computing irradiance coefficients, equirect size: {:?}",
+            (width, height)
+        );
+
+        for row in 0..height {
+            let theta = PI * (row as f32 + 0.5) / height as f32;
+            let d_omega = (2.0 * PI / width as f32) * (PI / height as f32) * theta.sin();
+            for col in 0..width {
+                let phi = 2.0 * PI * ((col as f32 + 0.5) / width as f32 - 0.5);
+                let direction = [
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ];
+                let pixel = image.get_pixel(col, row).0;
+                coeffs.add_sample(direction, [pixel[0], pixel[1], pixel[2]], d_omega);
+            }
+        }
+        coeffs
+    }
+
+    // Radiance from a direction, over a solid angle
+    fn add_sample(&mut self, direction: [f32; 3], hdr: [f32; 3], d_omega: f32) {
+        let [x, y, z] = direction;
+        let coeffs = self;
+        for col in 0..3 {
+            /* L_{00}.  Note that Y_{00} = 0.282095 */
+            let c = 0.282095;
+            coeffs.data[0][col] += hdr[col] * c * d_omega;
+
+            /* L_{1m}. -1 <= m <= 1.  The linear terms */
+            let c = 0.488603;
+            coeffs.data[1][col] += hdr[col] * (c * y) * d_omega; /* Y_{1-1} = 0.488603 y  */
+            coeffs.data[2][col] += hdr[col] * (c * z) * d_omega; /* Y_{10}  = 0.488603 z  */
+            coeffs.data[3][col] += hdr[col] * (c * x) * d_omega; /* Y_{11}  = 0.488603 x  */
+
+            /* The Quadratic terms, L_{2m} -2 <= m <= 2 */
+
+            /* First, L_{2-2}, L_{2-1}, L_{21} corresponding to xy,yz,xz */
+            let c = 1.092548;
+            coeffs.data[4][col] += hdr[col] * (c * x * y) * d_omega; /* Y_{2-2} = 1.092548 xy */
+            coeffs.data[5][col] += hdr[col] * (c * y * z) * d_omega; /* Y_{2-1} = 1.092548 yz */
+            coeffs.data[7][col] += hdr[col] * (c * x * z) * d_omega; /* Y_{21}  = 1.092548 xz */
+
+            /* L_{20}.  Note that Y_{20} = 0.315392 (3z^2 - 1) */
+            let c = 0.315392;
+            coeffs.data[6][col] += hdr[col] * (c * (3.0 * z * z - 1.0)) * d_omega;
+
+            /* L_{22}.  Note that Y_{22} = 0.546274 (x^2 - y^2) */
+            let c = 0.546274;
+            coeffs.data[8][col] += hdr[col] * (c * (x * x - y * y)) * d_omega;
+        }
+    }
+
+    // One symmetric 4x4 matrix per color channel; irradiance along a normal n is
+    // (n, 1) . M (n, 1). Indexed [channel][row][col].
+    pub fn to_matrices(&self) -> [[[f32; 4]; 4]; 3] {
+        let mut matrix: [[[f32; 4]; 4]; 3] = Default::default();
+        let c1 = 0.429043;
+        let c2 = 0.511664;
+        let c3 = 0.743125;
+        let c4 = 0.886227;
+        let c5 = 0.247708;
+
+        for (col, m) in matrix.iter_mut().enumerate() {
+            m[0][0] = c1 * self.data[8][col]; /* c1 L_{22}  */
+            m[0][1] = c1 * self.data[4][col]; /* c1 L_{2-2} */
+            m[0][2] = c1 * self.data[7][col]; /* c1 L_{21}  */
+            m[0][3] = c2 * self.data[3][col]; /* c2 L_{11}  */
+
+            m[1][0] = c1 * self.data[4][col]; /* c1 L_{2-2} */
+            m[1][1] = -c1 * self.data[8][col]; /*-c1 L_{22}  */
+            m[1][2] = c1 * self.data[5][col]; /* c1 L_{2-1} */
+            m[1][3] = c2 * self.data[1][col]; /* c2 L_{1-1} */
+
+            m[2][0] = c1 * self.data[7][col]; /* c1 L_{21}  */
+            m[2][1] = c1 * self.data[5][col]; /* c1 L_{2-1} */
+            m[2][2] = c3 * self.data[6][col]; /* c3 L_{20}  */
+            m[2][3] = c2 * self.data[2][col]; /* c2 L_{10}  */
+
+            m[3][0] = c2 * self.data[3][col]; /* c2 L_{11}  */
+            m[3][1] = c2 * self.data[1][col]; /* c2 L_{1-1} */
+            m[3][2] = c2 * self.data[2][col]; /* c2 L_{10}  */
+
+            /* c4 L_{00} - c5 L_{20} */
+            m[3][3] = c4 * self.data[0][col] - c5 * self.data[6][col];
+        }
+
+        matrix
+    }
+}
+
+// Cubemaps are directories; a cubemap path with an image extension is an equirectangular
+// image instead (see renderer::buffer::equirect)
+pub fn is_equirect(path: &str) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some("hdr" | "exr" | "png" | "jpg" | "jpeg")
+    )
+}
+
+// Linear radiance for .hdr and .exr; other images stay sRGB encoded, like cubemap faces
+pub fn load_equirect(path: &str) -> Result<Rgba32FImage> {
+    debug!("loading equirect at {}", path);
+    Ok(image::open(path)
+        .map_err(|err| anyhow!("{}: {}", path, err))?
+        .into_rgba32f())
+}
+
+pub fn load_cubemap_faces(path: &str) -> Result<Vec<RgbaImage>> {
+    FACES
+        .iter()
+        .map(|face| {
+            let face_path = format!("{}/{}.png", path, face);
+            debug!("loading cubemap face at {}", face_path);
+            Ok(image::io::Reader::open(&face_path)
+                .map_err(|err| anyhow!("{}: {}", face_path, err))?
+                .decode()
+                .map_err(|err| anyhow!("{}: {}", face_path, err))?
+                .into_rgba8())
+        })
+        .collect()
+}
+
+struct SphereCoords {
+    theta: f32,
+}
+
+impl From<[f32; 3]> for SphereCoords {
+    fn from(cartesian: [f32; 3]) -> Self {
+        Self {
+            theta: cartesian[1].atan2(cartesian[0]),
+        }
+    }
+}
+
+pub struct CubeCoords {
+    pub index: usize,
+    pub u: f32,
+    pub v: f32,
+}
+
+impl CubeCoords {
+    pub fn sample(&self, size: (u32, u32), face: &RgbaImage) -> [f32; 3] {
+        let pixel = face
+            .get_pixel(
+                (self.u * size.0 as f32) as u32,
+                (self.v * size.1 as f32) as u32,
+            )
+            .0;
+        [
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+        ]
+    }
+
+    // https://en.wikipedia.org/wiki/Cube_mapping#Memory_addressing
+    pub fn to_cartesian(&self) -> [f32; 3] {
+        let uc = 2.0 * self.u - 1.0;
+        let vc = 2.0 * self.v - 1.0;
+        match self.index {
+            0 => [1.0, vc, -uc],  // POSITIVE X
+            1 => [-1.0, vc, uc],  // NEGATIVE X
+            2 => [uc, 1.0, -vc],  // POSITIVE Y
+            3 => [uc, -1.0, vc],  // NEGATIVE Y
+            4 => [uc, vc, 1.0],   // POSITIVE Z
+            5 => [-uc, vc, -1.0], // NEGATIVE Z
+            _ => panic!("cubemap cannot have more than 6 faces"),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1.0 * 10.0_f32.powf(-4.0) {
+        1.0
+    } else {
+        x.sin() / x
+    }
+}
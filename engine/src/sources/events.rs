@@ -0,0 +1,132 @@
+use std::{marker::PhantomData, path::PathBuf};
+
+use legion::system;
+
+// Events sent by systems (the engine's or the game's), as a resource per event type. Double
+// buffered: Stage::Clear swaps the buffers at the end of every frame, so an event lives for the
+// frame it was sent in and the one after. iter() only sees this frame's, which suits systems
+// running after the sender (eg. game systems reading CollisionEvents); systems that might run
+// before it keep an EventReader and read() instead, to see every event exactly once.
+//
+// The engine's are Events<CollisionEvent> (2D only), Events<WindowEvent>,
+// Events<SurfaceEvent> and Events<AssetGroupLoaded>; see EngineBuilder::with_event for the
+// game's own.
+pub struct Events<T> {
+    current: Vec<T>,
+    previous: Vec<T>,
+    // Ids of the first event in each buffer; an event's id is its index among all ever sent
+    current_start: usize,
+    previous_start: usize,
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            current: vec![],
+            previous: vec![],
+            current_start: 0,
+            previous_start: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    // Events sent this frame
+    pub fn iter(&self) -> std::slice::Iter<T> {
+        self.current.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_empty()
+    }
+
+    // A reader that skips everything already sent
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader {
+            next: self.current_start + self.current.len(),
+            event: PhantomData,
+        }
+    }
+
+    // Events the reader hasn't seen yet, from last frame's and this frame's; anything older is
+    // gone
+    pub fn read<'a>(&'a self, reader: &mut EventReader<T>) -> impl Iterator<Item = &'a T> {
+        let previous = reader.next.saturating_sub(self.previous_start);
+        let current = reader.next.saturating_sub(self.current_start);
+        reader.next = self.current_start + self.current.len();
+        self.previous
+            .iter()
+            .skip(previous)
+            .chain(self.current.iter().skip(current))
+    }
+
+    // Ends the frame; run by update_events_system
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+        self.previous_start = self.current_start;
+        self.current_start += self.previous.len();
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A system's position in an Events<T>. Defaults to the very start, so a new reader sees
+// whatever's still buffered.
+pub struct EventReader<T> {
+    next: usize,
+    event: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            event: PhantomData,
+        }
+    }
+}
+
+// In Stage::Clear, for every event type
+#[system]
+pub fn update_events<T: Send + Sync + 'static>(#[resource] events: &mut Events<T>) {
+    events.update();
+}
+
+// Sent by begin_render_graph when the window's surface can't provide a frame. All but
+// Reconfigured skip the frame: it's drawn offscreen and never presented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceEvent {
+    // The surface was outdated or lost (eg. mid-resize) and has been reconfigured
+    Reconfigured,
+    Timeout,
+    Outdated,
+    Lost,
+    OutOfMemory,
+}
+
+// Sent by Engine::handle_event, before the frame that sees them. Sizes are physical.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowEvent {
+    Resized { width: u32, height: u32 },
+    ScaleFactorChanged(f64),
+    Focused(bool),
+    // The close button, whatever the ExitPolicy
+    CloseRequested,
+    DroppedFile(PathBuf),
+    // The app was suspended (mobile), its surface gone until Resumed
+    Suspended,
+    Resumed,
+    // Entering (true) or leaving the background; see sources::control::BackgroundPolicy
+    Background(bool),
+}
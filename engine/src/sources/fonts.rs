@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use fontdue::{Font, FontSettings};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::assets;
+
+// TrueType / OpenType fonts for Text2D, rasterized on demand into the glyph atlas
+// (see renderer::systems::render_2d::text).
+pub struct FontRegistry {
+    pub fonts: HashMap<Uuid, Font>,
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        Self {
+            fonts: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Uuid> {
+        let id = Uuid::new_v4();
+        self.load_id(id, path)?;
+        Ok(id)
+    }
+
+    pub fn load_id(&mut self, id: Uuid, path: &str) -> Result<()> {
+        debug!("loading font: {}", path);
+        let bytes =
+            assets::read(path).map_err(|err| anyhow!("error loading font {}: {}", path, err))?;
+        self.load_bytes(id, &bytes)
+            .map_err(|err| anyhow!("{}: {}", path, err))
+    }
+
+    pub fn load_bytes(&mut self, id: Uuid, bytes: &[u8]) -> Result<()> {
+        let font = Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|err| anyhow!("error parsing font: {}", err))?;
+        self.fonts.insert(id, font);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&Font> {
+        self.fonts.get(id)
+    }
+}
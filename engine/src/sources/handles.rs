@@ -0,0 +1,71 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+use uuid::Uuid;
+
+// A mesh or texture in the registries, returned by their builders' load_named and looked up by
+// name with Registry::mesh and Registry::texture (or Engine::mesh and Engine::texture). Engine
+// meshes and textures are named after their constants, eg. "unit_square" for
+// UNIT_SQUARE_MESH_ID and "render_2d_common" for RENDER_2D_COMMON_TEXTURE_ID.
+//
+// Components still hold plain ids, so use id() for those (eg. Render2D::texture). Groups
+// declared with #[mesh_group] or #[texture_group] make their own, with handle().
+pub struct Handle<T> {
+    id: Uuid,
+    group_id: Uuid,
+    asset: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn new(id: Uuid, group_id: Uuid) -> Self {
+        Self {
+            id,
+            group_id,
+            asset: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn group_id(&self) -> Uuid {
+        self.group_id
+    }
+}
+
+// Derives would require T: Clone etc.
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.group_id == other.group_id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.group_id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("id", &self.id)
+            .field("group_id", &self.group_id)
+            .finish()
+    }
+}
@@ -0,0 +1,241 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+use iced_winit::winit::event::VirtualKeyCode;
+use std::collections::HashMap;
+use winit_input_helper::WinitInputHelper;
+
+// Pressed to close the window; unbind it to keep Escape for the game
+pub const QUIT_ACTION: &str = "quit";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    // 0 is left, 1 is right, 2 is middle (as in WinitInputHelper)
+    Mouse(usize),
+    GamepadButton(Button),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisBinding {
+    // -1 while the first is held, 1 while the second is
+    Keys(VirtualKeyCode, VirtualKeyCode),
+    GamepadAxis(Axis),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct ActionState {
+    pressed: bool,
+    held: bool,
+    released: bool,
+}
+
+// Named actions and axes, bound to keys, mouse buttons and gamepad inputs. Resolved by the
+// engine once a frame, before any systems run, so game systems can ask for
+// actions.pressed("jump") instead of a key. Bindings can be changed at runtime.
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+
+    action_states: HashMap<String, ActionState>,
+    axis_values: HashMap<String, f32>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::new().with_action(QUIT_ACTION, Binding::Key(VirtualKeyCode::Escape))
+    }
+}
+
+impl InputMap {
+    // No bindings, not even QUIT_ACTION; see default()
+    pub fn new() -> Self {
+        Self {
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+            action_states: HashMap::new(),
+            axis_values: HashMap::new(),
+        }
+    }
+
+    pub fn with_action(mut self, action: &str, binding: Binding) -> Self {
+        self.bind(action, binding);
+        self
+    }
+
+    pub fn with_axis(mut self, axis: &str, binding: AxisBinding) -> Self {
+        self.bind_axis(axis, binding);
+        self
+    }
+
+    // Adds to the action's bindings; any of them triggers it
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        self.actions
+            .entry(action.to_owned())
+            .or_insert_with(Vec::new)
+            .push(binding);
+    }
+
+    // Adds to the axis' bindings; the one pushed furthest wins
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        self.axes
+            .entry(axis.to_owned())
+            .or_insert_with(Vec::new)
+            .push(binding);
+    }
+
+    // Removes all of an action's or axis' bindings
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+        self.action_states.remove(name);
+        self.axis_values.remove(name);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], |bindings| bindings)
+    }
+
+    // This frame only
+    pub fn pressed(&self, action: &str) -> bool {
+        self.action_states
+            .get(action)
+            .map_or(false, |state| state.pressed)
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.action_states
+            .get(action)
+            .map_or(false, |state| state.held)
+    }
+
+    // This frame only
+    pub fn released(&self, action: &str) -> bool {
+        self.action_states
+            .get(action)
+            .map_or(false, |state| state.released)
+    }
+
+    // In [-1, 1]; 0 for unknown axes
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.axis_values.get(axis).copied().unwrap_or_default()
+    }
+
+    pub fn update(&mut self, input: &WinitInputHelper, gamepads: &Gamepads) {
+        self.action_states = self
+            .actions
+            .iter()
+            .map(|(action, bindings)| {
+                let mut state = ActionState::default();
+                for binding in bindings {
+                    let (pressed, held, released) = match *binding {
+                        Binding::Key(key) => (
+                            input.key_pressed(key),
+                            input.key_held(key),
+                            input.key_released(key),
+                        ),
+                        Binding::Mouse(button) => (
+                            input.mouse_pressed(button),
+                            input.mouse_held(button),
+                            input.mouse_released(button),
+                        ),
+                        Binding::GamepadButton(button) => (
+                            gamepads.pressed.contains(&button),
+                            gamepads.held(button),
+                            gamepads.released.contains(&button),
+                        ),
+                    };
+                    state.pressed |= pressed;
+                    state.held |= held;
+                    state.released |= released;
+                }
+                (action.clone(), state)
+            })
+            .collect();
+
+        self.axis_values = self
+            .axes
+            .iter()
+            .map(|(axis, bindings)| {
+                let value = bindings
+                    .iter()
+                    .map(|binding| match *binding {
+                        AxisBinding::Keys(negative, positive) => {
+                            input.key_held(positive) as i32 as f32
+                                - input.key_held(negative) as i32 as f32
+                        }
+                        AxisBinding::GamepadAxis(gamepad_axis) => gamepads.axis(gamepad_axis),
+                    })
+                    .fold(0.0_f32, |a, b| if b.abs() > a.abs() { b } else { a });
+                (axis.clone(), value.max(-1.0).min(1.0))
+            })
+            .collect();
+    }
+}
+
+// Every connected gamepad, polled by the engine once a frame. Buttons and axes are merged
+// across gamepads; there's no way to tell players apart yet.
+pub struct Gamepads {
+    // None when the platform has no gamepad support
+    gilrs: Option<Gilrs>,
+    pub pressed: Vec<Button>,
+    pub released: Vec<Button>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                warn!("gamepads disabled: {}", err);
+                None
+            }
+        };
+        Self {
+            gilrs,
+            pressed: vec![],
+            released: vec![],
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => self.pressed.push(button),
+                EventType::ButtonReleased(button, _) => self.released.push(button),
+                EventType::Connected => info!("gamepad {} connected", event.id),
+                EventType::Disconnected => info!("gamepad {} disconnected", event.id),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn held(&self, button: Button) -> bool {
+        self.gilrs.as_ref().map_or(false, |gilrs| {
+            gilrs
+                .gamepads()
+                .any(|(_, gamepad)| gamepad.is_pressed(button))
+        })
+    }
+
+    // The gamepad pushing the axis furthest
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.gilrs.as_ref().map_or(0.0, |gilrs| {
+            gilrs
+                .gamepads()
+                .map(|(_, gamepad)| gamepad.value(axis))
+                .fold(0.0, |a, b| if b.abs() > a.abs() { b } else { a })
+        })
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,330 @@
+use legion::{storage::Component, Entity, EntityStore, IntoQuery, World};
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    components::{Position2D, Transform3D},
+    renderer::systems::{render_2d::Render2D, render_3d::forward_basic::Render3D},
+    sources::names::NameIndex,
+    systems::{lighting_2d::Light2D, lighting_3d::Light3D, particle_3d::ParticleSystem3D},
+};
+
+// Components the entity inspector (sources::ui::inspector) can show and edit, as a flat list
+// of named numbers
+pub trait Inspect: Component {
+    fn fields(&self) -> Vec<(&'static str, f32)>;
+    // Index into fields
+    fn set_field(&mut self, index: usize, value: f32);
+}
+
+#[derive(Clone, Copy)]
+pub struct InspectedComponent {
+    pub name: &'static str,
+    read: fn(&World, Entity) -> Option<Vec<(&'static str, f32)>>,
+    write: fn(&mut World, Entity, usize, f32),
+}
+
+fn read<T: Inspect>(world: &World, entity: Entity) -> Option<Vec<(&'static str, f32)>> {
+    world
+        .entry_ref(entity)
+        .ok()?
+        .get_component::<T>()
+        .ok()
+        .map(T::fields)
+}
+
+fn write<T: Inspect>(world: &mut World, entity: Entity, index: usize, value: f32) {
+    if let Some(mut entry) = world.entry(entity) {
+        if let Ok(component) = entry.get_component_mut::<T>() {
+            component.set_field(index, value);
+        }
+    }
+}
+
+// The components shown, in order. Default has the engine's own; games add theirs with
+// with_component.
+pub struct InspectorRegistry {
+    pub components: Vec<InspectedComponent>,
+}
+
+impl InspectorRegistry {
+    pub fn new() -> Self {
+        Self { components: vec![] }
+    }
+
+    pub fn with_component<T: Inspect>(mut self, name: &'static str) -> Self {
+        self.components.push(InspectedComponent {
+            name,
+            read: read::<T>,
+            write: write::<T>,
+        });
+        self
+    }
+}
+
+impl Default for InspectorRegistry {
+    fn default() -> Self {
+        Self::new()
+            .with_component::<Transform3D>("Transform3D")
+            .with_component::<Position2D>("Position2D")
+            .with_component::<Render3D>("Render3D")
+            .with_component::<Render2D>("Render2D")
+            .with_component::<Light3D>("Light3D")
+            .with_component::<Light2D>("Light2D")
+            .with_component::<ParticleSystem3D>("ParticleSystem3D")
+    }
+}
+
+// What the inspector panel shows; its view can't see the world, so the engine copies this
+// out of it every frame
+#[derive(Default)]
+pub struct InspectorSnapshot {
+    // Every entity, with its label, refreshed once a second
+    pub entities: Vec<(Entity, String)>,
+    pub selected: Option<Entity>,
+    // The selected entity's registered components: index into the registry, and fields
+    pub components: Vec<(usize, Vec<(&'static str, f32)>)>,
+    // Text typed into a (component, field) that isn't applied yet, or doesn't parse
+    pub edits: HashMap<(usize, usize), String>,
+    // Last selection seen from elsewhere; see Inspector::follow
+    followed: Option<Entity>,
+}
+
+// Resource (Arc), inserted with EngineBuilder::with_inspector
+pub struct Inspector {
+    pub registry: InspectorRegistry,
+    pub snapshot: Mutex<InspectorSnapshot>,
+}
+
+impl Inspector {
+    pub fn new(registry: InspectorRegistry) -> Self {
+        Self {
+            registry,
+            snapshot: Default::default(),
+        }
+    }
+
+    pub fn select(&self, entity: Option<Entity>) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        if snapshot.selected != entity {
+            snapshot.selected = entity;
+            snapshot.components.clear();
+            snapshot.edits.clear();
+        }
+    }
+
+    // Selects whatever another tool (the gizmos) just selected, leaving the inspector's own
+    // selection alone until that changes
+    pub fn follow(&self, selected: Option<Entity>) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        if snapshot.followed == selected {
+            return;
+        }
+        snapshot.followed = selected;
+        drop(snapshot);
+        if selected.is_some() {
+            self.select(selected);
+        }
+    }
+
+    pub fn component(&self, index: usize) -> &InspectedComponent {
+        &self.registry.components[index]
+    }
+
+    // Applies an edit from the panel
+    pub fn write(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: usize,
+        field: usize,
+        value: f32,
+    ) {
+        (self.component(component).write)(world, entity, field, value);
+    }
+
+    // Expensive, should not be called every frame
+    pub fn refresh_entities(&self, world: &World, names: &NameIndex) {
+        let mut entities = <Entity>::query()
+            .iter(world)
+            .map(|entity| (*entity, names.label(*entity)))
+            .collect::<Vec<(Entity, String)>>();
+        entities.sort_by(|a, b| a.1.cmp(&b.1));
+        self.snapshot.lock().unwrap().entities = entities;
+    }
+
+    pub fn refresh_selected(&self, world: &World) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        let entity = match snapshot.selected {
+            Some(entity) => entity,
+            None => return,
+        };
+        // Deleted since it was selected
+        if world.entry_ref(entity).is_err() {
+            snapshot.selected = None;
+            snapshot.components.clear();
+            snapshot.edits.clear();
+            return;
+        }
+        snapshot.components = self
+            .registry
+            .components
+            .iter()
+            .enumerate()
+            .filter_map(|(index, component)| Some((index, (component.read)(world, entity)?)))
+            .collect();
+    }
+}
+
+impl Inspect for Transform3D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("position.x", self.position[0]),
+            ("position.y", self.position[1]),
+            ("position.z", self.position[2]),
+            ("rotation.x", self.rotation[0]),
+            ("rotation.y", self.rotation[1]),
+            ("rotation.z", self.rotation[2]),
+            ("scale.x", self.scale[0]),
+            ("scale.y", self.scale[1]),
+            ("scale.z", self.scale[2]),
+        ]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        let mut fields = [&mut self.position, &mut self.rotation, &mut self.scale];
+        if let Some(field) = fields.get_mut(index / 3) {
+            field[index % 3] = value;
+        }
+    }
+}
+
+impl Inspect for Position2D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![("x", self.x), ("y", self.y)]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.x = value,
+            1 => self.y = value,
+            _ => {}
+        }
+    }
+}
+
+impl Inspect for Render3D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("color.r", self.color[0]),
+            ("color.g", self.color[1]),
+            ("color.b", self.color[2]),
+            ("color.a", self.color[3]),
+            ("mix", self.mix),
+        ]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0..=3 => self.color[index] = value,
+            4 => self.mix = value,
+            _ => {}
+        }
+    }
+}
+
+impl Inspect for Render2D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("color.r", self.color[0]),
+            ("color.g", self.color[1]),
+            ("color.b", self.color[2]),
+            ("color.a", self.color[3]),
+            ("mix", self.mix),
+            ("width", self.width),
+            ("height", self.height),
+            ("zindex", self.zindex),
+        ]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0..=3 => self.color[index] = value,
+            4 => self.mix = value,
+            5 => self.width = value,
+            6 => self.height = value,
+            7 => self.zindex = value,
+            _ => {}
+        }
+    }
+}
+
+impl Inspect for Light3D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("color.r", self.color[0]),
+            ("color.g", self.color[1]),
+            ("color.b", self.color[2]),
+            ("intensity", self.intensity),
+            ("range", self.range),
+            ("direction.x", self.direction[0]),
+            ("direction.y", self.direction[1]),
+            ("direction.z", self.direction[2]),
+        ]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0..=2 => self.color[index] = value,
+            3 => self.intensity = value,
+            4 => self.range = value,
+            5..=7 => self.direction[index - 5] = value,
+            _ => {}
+        }
+    }
+}
+
+impl Inspect for Light2D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![("linear", self.linear), ("quadratic", self.quadratic)]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.linear = value,
+            1 => self.quadratic = value,
+            _ => {}
+        }
+    }
+}
+
+// With the first emitter's settings
+impl Inspect for ParticleSystem3D {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        let mut fields = vec![("lifetime", self.lifetime)];
+        if let Some(emitter) = self.emitters.first() {
+            let emitter = emitter.lock().unwrap();
+            fields.extend_from_slice(&[
+                ("emitter.position.x", emitter.position[0]),
+                ("emitter.position.y", emitter.position[1]),
+                ("emitter.position.z", emitter.position[2]),
+                ("emitter.rate", emitter.rate as f32),
+            ]);
+        }
+        fields
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        if index == 0 {
+            self.lifetime = value.max(0.0);
+            return;
+        }
+        if let Some(emitter) = self.emitters.first() {
+            let mut emitter = emitter.lock().unwrap();
+            match index {
+                1..=3 => emitter.position[index - 1] = value,
+                4 => emitter.rate = value.max(0.0).round() as u32,
+                _ => {}
+            }
+        }
+    }
+}
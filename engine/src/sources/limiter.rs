@@ -0,0 +1,50 @@
+use instant::Instant;
+use std::{thread, time::Duration};
+
+use crate::constants::FRAME_LIMITER_SPIN_MICROS;
+
+// Caps the frame rate (EngineBuilder::with_frame_cap) by waiting out the rest of each frame's
+// time before the next one starts. Most of the wait is slept and the end of it spun, so that
+// frames start on time; FrameMetrics::frame_delta shows how well that works.
+pub struct FrameLimiter {
+    target: Duration,
+    // When the next frame is due
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self {
+            target: Duration::from_secs_f32(1.0 / fps),
+            next: None,
+        }
+    }
+
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    // Blocks until the next frame is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let next = match self.next {
+            Some(next) if next > now => next,
+            // The first frame, or one that ran late: start now rather than rushing the
+            // following ones to catch up
+            _ => {
+                self.next = Some(now + self.target);
+                return;
+            }
+        };
+
+        let spin = Duration::from_micros(FRAME_LIMITER_SPIN_MICROS);
+        let remaining = next - now;
+        if remaining > spin {
+            thread::sleep(remaining - spin);
+        }
+        while Instant::now() < next {
+            std::hint::spin_loop();
+        }
+        self.next = Some(next + self.target);
+    }
+}
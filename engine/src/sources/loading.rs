@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use uuid::Uuid;
+
+// Startup asset loading progress. There is no loading screen in the engine yet; whatever
+// draws one can subscribe with EngineBuilder::with_load_progress. Callbacks are invoked
+// from loader threads, in completion order.
+
+#[derive(Clone, Debug)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    // The asset which just finished loading
+    pub asset: String,
+}
+
+impl LoadProgress {
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.0,
+            total => self.loaded as f32 / total as f32,
+        }
+    }
+}
+
+pub type LoadProgressCallback = Arc<dyn Fn(&LoadProgress) + Send + Sync>;
+
+pub struct LoadProgressReporter {
+    loaded: AtomicUsize,
+    total: usize,
+    callback: Option<LoadProgressCallback>,
+}
+
+impl LoadProgressReporter {
+    pub fn new(total: usize, callback: Option<LoadProgressCallback>) -> Self {
+        Self {
+            loaded: AtomicUsize::new(0),
+            total,
+            callback,
+        }
+    }
+
+    pub fn finish(&self, asset: &str) {
+        let loaded = self.loaded.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!("loaded asset {}/{}: {}", loaded, self.total, asset);
+        if let Some(callback) = &self.callback {
+            callback(&LoadProgress {
+                loaded,
+                total: self.total,
+                asset: asset.to_owned(),
+            });
+        }
+    }
+}
+
+// Of an asset streamed in after startup; see EngineBuilder::with_asset_streaming
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadState {
+    // The registry holds a placeholder in its place
+    Loading,
+    Loaded,
+    // The placeholder stays
+    Failed(String),
+}
+
+// Sent to Events<AssetGroupLoaded> once every streamed asset in a texture or mesh group has
+// loaded (or failed to)
+#[derive(Clone, Debug)]
+pub struct AssetGroupLoaded {
+    pub group_id: Uuid,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+// Resource (Arc<RwLock>), also Registry::loads
+#[derive(Default)]
+pub struct LoadStates {
+    states: HashMap<Uuid, LoadState>,
+    // Per group: streamed assets still loading, loaded, failed
+    groups: HashMap<Uuid, (usize, usize, usize)>,
+}
+
+impl LoadStates {
+    // Loaded for anything that wasn't streamed
+    pub fn status(&self, id: &Uuid) -> LoadState {
+        self.states.get(id).cloned().unwrap_or(LoadState::Loaded)
+    }
+
+    // Whether a group has streamed assets still loading
+    pub fn group_loading(&self, group_id: &Uuid) -> bool {
+        self.groups
+            .get(group_id)
+            .map_or(false, |(loading, _, _)| *loading > 0)
+    }
+
+    // Fraction of every streamed asset done loading, failed or not, for loading screens
+    pub fn fraction(&self) -> f32 {
+        let (loading, done) = self.groups.values().fold((0, 0), |(loading, done), group| {
+            (loading + group.0, done + group.1 + group.2)
+        });
+        match loading + done {
+            0 => 1.0,
+            total => done as f32 / total as f32,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.groups.values().any(|(loading, _, _)| *loading > 0)
+    }
+
+    pub(crate) fn start(&mut self, id: Uuid, group_id: Uuid) {
+        self.states.insert(id, LoadState::Loading);
+        self.groups.entry(group_id).or_default().0 += 1;
+    }
+
+    // Some once it finishes its group
+    pub(crate) fn finish(
+        &mut self,
+        id: Uuid,
+        group_id: Uuid,
+        state: LoadState,
+    ) -> Option<AssetGroupLoaded> {
+        let group = self.groups.entry(group_id).or_default();
+        group.0 = group.0.saturating_sub(1);
+        match state {
+            LoadState::Failed(_) => group.2 += 1,
+            _ => group.1 += 1,
+        }
+        self.states.insert(id, state);
+        match group.0 {
+            0 => Some(AssetGroupLoaded {
+                group_id,
+                loaded: group.1,
+                failed: group.2,
+            }),
+            _ => None,
+        }
+    }
+}
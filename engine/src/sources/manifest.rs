@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Written by ember-assetc next to its outputs, and read by the texture and mesh registries
+// so that source assets are swapped for their preprocessed versions at startup.
+//
+// One entry per line, tab separated: kind, source path, output path.
+// Source paths are relative to the asset root given to ember-assetc, and are matched
+// against the end of the paths given to the registries. Output paths are relative
+// to the manifest itself.
+pub const MANIFEST_FILE_NAME: &str = "assets.manifest";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AssetKind {
+    Texture,
+    Cubemap,
+    Mesh,
+    // Derived from a cubemap source, in the irradiance.txt format (see
+    // IrradianceCoefficients::write_cache)
+    Irradiance,
+    // Derived from a cubemap source: a KTX2 cubemap whose levels are the environment
+    // convolved for increasing roughness, for specular image based lighting
+    Prefiltered,
+}
+
+impl AssetKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AssetKind::Texture => "texture",
+            AssetKind::Cubemap => "cubemap",
+            AssetKind::Mesh => "mesh",
+            AssetKind::Irradiance => "irradiance",
+            AssetKind::Prefiltered => "prefiltered",
+        }
+    }
+
+    fn parse(kind: &str) -> Result<Self> {
+        Ok(match kind {
+            "texture" => AssetKind::Texture,
+            "cubemap" => AssetKind::Cubemap,
+            "mesh" => AssetKind::Mesh,
+            "irradiance" => AssetKind::Irradiance,
+            "prefiltered" => AssetKind::Prefiltered,
+            other => return Err(anyhow!("unknown asset kind in manifest: {}", other)),
+        })
+    }
+}
+
+pub struct ManifestEntry {
+    pub kind: AssetKind,
+    pub source: String,
+    pub output: String,
+}
+
+#[derive(Default)]
+pub struct AssetManifest {
+    pub entries: Vec<ManifestEntry>,
+    // Directory containing the manifest; outputs are resolved against it
+    pub root: PathBuf,
+}
+
+impl AssetManifest {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            entries: vec![],
+            root: root.to_owned(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| anyhow!("error loading asset manifest {:?}: {}", path, err))?;
+
+        let entries = text
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                if fields.len() != 3 {
+                    return Err(anyhow!("malformed asset manifest line: {}", line));
+                }
+                Ok(ManifestEntry {
+                    kind: AssetKind::parse(fields[0])?,
+                    source: fields[1].to_owned(),
+                    output: fields[2].to_owned(),
+                })
+            })
+            .collect::<Result<Vec<ManifestEntry>>>()?;
+
+        Ok(Self {
+            entries,
+            root: path.parent().map_or_else(PathBuf::new, Path::to_owned),
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut text = String::from("# ember asset manifest\n");
+        for entry in &self.entries {
+            text.push_str(&format!(
+                "{}\t{}\t{}\n",
+                entry.kind.as_str(),
+                entry.source,
+                entry.output
+            ));
+        }
+        fs::write(self.root.join(MANIFEST_FILE_NAME), text)?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, kind: AssetKind, source: &str, output: &str) {
+        self.entries.push(ManifestEntry {
+            kind,
+            source: normalize(source),
+            output: normalize(output),
+        });
+    }
+
+    // Full path of the preprocessed output for a source asset, if there is one
+    pub fn resolve(&self, kind: AssetKind, source: &str) -> Option<String> {
+        let source = normalize(source);
+        self.entries
+            .iter()
+            .find(|entry| {
+                entry.kind == kind
+                    && (source == entry.source || source.ends_with(&format!("/{}", entry.source)))
+            })
+            .map(|entry| self.root.join(&entry.output).to_string_lossy().into_owned())
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.replace('\\', "/").trim_end_matches('/').to_owned()
+}
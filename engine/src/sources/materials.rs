@@ -0,0 +1,378 @@
+use anyhow::{anyhow, Result};
+use std::{collections::HashMap, fmt::Write as _, fs, sync::Arc};
+use uuid::Uuid;
+use wgpu::{util::DeviceExt, BindGroup};
+
+use crate::{
+    constants::{ID, PBR_DEFAULT_MATERIAL_ID},
+    renderer::buffer::texture::Texture,
+};
+
+use super::registry::{TextureRegistry, TextureType};
+
+// Texture maps and factors for RenderPBR, bound together as one TextureType::Material group.
+// Maps are image textures from one texture group, laid out like glTF's: roughness in the
+// green channel of metallic_roughness and metalness in blue, occlusion in red. Missing maps
+// leave their factor as is.
+//
+// Todo: images load into the surface's (srgb) format, which is wrong for the non-color maps
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub texture_group: Uuid,
+    pub albedo: Option<Uuid>,
+    pub normal: Option<Uuid>,
+    pub metallic_roughness: Option<Uuid>,
+    pub occlusion: Option<Uuid>,
+
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub occlusion_strength: f32,
+}
+
+impl Material {
+    pub fn new(texture_group: Uuid) -> Self {
+        Self {
+            texture_group,
+            albedo: None,
+            normal: None,
+            metallic_roughness: None,
+            occlusion: None,
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 0.0,
+            roughness: 0.5,
+            occlusion_strength: 1.0,
+        }
+    }
+
+    pub fn colored(color: [f32; 4]) -> Self {
+        Self {
+            base_color: color,
+            ..Self::new(Uuid::nil())
+        }
+    }
+
+    pub fn with_albedo(mut self, texture: Uuid) -> Self {
+        self.albedo = Some(texture);
+        self
+    }
+
+    pub fn with_normal(mut self, texture: Uuid) -> Self {
+        self.normal = Some(texture);
+        self
+    }
+
+    pub fn with_metallic_roughness(mut self, texture: Uuid) -> Self {
+        self.metallic_roughness = Some(texture);
+        self
+    }
+
+    pub fn with_occlusion(mut self, texture: Uuid, strength: f32) -> Self {
+        self.occlusion = Some(texture);
+        self.occlusion_strength = strength;
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    // A material file: one `key = value` per line, and # for comments. Maps are texture ids
+    // and base_color is four numbers; anything left out keeps Material::new's value, eg.
+    //
+    //   texture_group = 0b1e...
+    //   albedo = 6f4c...
+    //   base_color = 1 0.8 0.6 1
+    //   roughness = 0.3
+    pub fn parse(text: &str) -> Result<Material> {
+        let mut material = Material::new(Uuid::nil());
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| anyhow!("line {}: {}", number + 1, message);
+            let float = |value: &str| {
+                value
+                    .parse::<f32>()
+                    .map_err(|_| error(format!("{} isn't a number", value)))
+            };
+            let texture = |value: &str| {
+                Uuid::parse_str(value).map_err(|_| error(format!("{} isn't a texture id", value)))
+            };
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error(format!("expected key = value, not {}", line))),
+            };
+            match key {
+                "texture_group" => material.texture_group = texture(value)?,
+                "albedo" => material.albedo = Some(texture(value)?),
+                "normal" => material.normal = Some(texture(value)?),
+                "metallic_roughness" => material.metallic_roughness = Some(texture(value)?),
+                "occlusion" => material.occlusion = Some(texture(value)?),
+                "base_color" => {
+                    let color = value
+                        .split_whitespace()
+                        .map(float)
+                        .collect::<Result<Vec<f32>>>()?;
+                    if color.len() != 4 {
+                        return Err(error(format!(
+                            "base_color needs 4 numbers, not {}",
+                            color.len()
+                        )));
+                    }
+                    material.base_color = [color[0], color[1], color[2], color[3]];
+                }
+                "metallic" => material.metallic = float(value)?,
+                "roughness" => material.roughness = float(value)?,
+                "occlusion_strength" => material.occlusion_strength = float(value)?,
+                other => return Err(error(format!("unknown key {}", other))),
+            }
+        }
+        Ok(material)
+    }
+
+    // In the format parse reads
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "texture_group = {}", self.texture_group);
+        let maps = [
+            ("albedo", self.albedo),
+            ("normal", self.normal),
+            ("metallic_roughness", self.metallic_roughness),
+            ("occlusion", self.occlusion),
+        ];
+        for (key, texture) in maps.iter() {
+            if let Some(texture) = texture {
+                let _ = writeln!(out, "{} = {}", key, texture);
+            }
+        }
+        let [r, g, b, a] = self.base_color;
+        let _ = writeln!(out, "base_color = {} {} {} {}", r, g, b, a);
+        let _ = writeln!(out, "metallic = {}", self.metallic);
+        let _ = writeln!(out, "roughness = {}", self.roughness);
+        let _ = writeln!(out, "occlusion_strength = {}", self.occlusion_strength);
+        out
+    }
+
+    fn uniforms(&self) -> MaterialUniforms {
+        MaterialUniforms {
+            base_color: self.base_color,
+            params: [
+                self.metallic,
+                self.roughness,
+                self.occlusion_strength,
+                match self.normal {
+                    Some(_) => 1.0,
+                    None => 0.0,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniforms {
+    pub base_color: [f32; 4],
+    // [metallic, roughness, occlusion strength, has normal map]
+    pub params: [f32; 4],
+}
+
+pub struct MaterialRegistry {
+    pub materials: HashMap<Uuid, Material>,
+    pub bind_groups: HashMap<Uuid, Arc<BindGroup>>,
+    // Paths of the materials loaded from files, which save writes back to
+    pub files: HashMap<Uuid, String>,
+
+    // 1x1 white, standing in for missing maps
+    fallback: Texture,
+    sampler: wgpu::Sampler,
+}
+
+impl MaterialRegistry {
+    pub fn get(&self, id: &Uuid) -> Option<&Material> {
+        self.materials.get(id)
+    }
+
+    pub fn bind_group(&self, id: &Uuid) -> Option<&Arc<BindGroup>> {
+        self.bind_groups.get(id)
+    }
+
+    // Writes a material loaded from a file back to it, eg. after the material editor changed it
+    pub fn save(&self, id: &Uuid) -> Result<()> {
+        let path = self
+            .files
+            .get(id)
+            .ok_or_else(|| anyhow!("material {} wasn't loaded from a file", id))?;
+        let material = self.get(id).ok_or_else(|| anyhow!("no material {}", id))?;
+        fs::write(path, material.to_text())
+            .map_err(|e| anyhow!("failed to write material {}: {}", path, e))
+    }
+
+    // Adds or replaces a material; its maps have to be in the texture registry already
+    pub fn insert(
+        &mut self,
+        id: Uuid,
+        material: Material,
+        textures: &TextureRegistry,
+        device: &wgpu::Device,
+    ) -> Result<()> {
+        let map = |texture: Option<Uuid>| -> Result<&wgpu::TextureView> {
+            let texture = match texture {
+                Some(texture) => texture,
+                None => return Ok(&self.fallback.view),
+            };
+            textures
+                .textures
+                .get(&material.texture_group)
+                .and_then(|group| group.get(&texture))
+                .map(|texture| &texture.view)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "material {}: no texture {} in group {}",
+                        id,
+                        texture,
+                        material.texture_group
+                    )
+                })
+        };
+        let albedo = map(material.albedo)?;
+        let normal = map(material.normal)?;
+        let metallic_roughness = map(material.metallic_roughness)?;
+        let occlusion = map(material.occlusion)?;
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("material_uniforms_{}", id)),
+            contents: bytemuck::cast_slice(&[material.uniforms()]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: textures.bind_group_layout(TextureType::Material),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(albedo),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(normal),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(metallic_roughness),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(occlusion),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&format!("material_bind_group_{}", id)),
+        });
+
+        self.bind_groups.insert(id, Arc::new(bind_group));
+        self.materials.insert(id, material);
+        Ok(())
+    }
+}
+
+pub struct MaterialRegistryBuilder {
+    pub to_build: HashMap<Uuid, Material>,
+    pub files: HashMap<Uuid, String>,
+}
+
+impl MaterialRegistryBuilder {
+    pub fn new() -> Self {
+        Self {
+            to_build: HashMap::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, material: Material) -> Uuid {
+        let id = Uuid::new_v4();
+        self.register_id(id, material);
+        id
+    }
+
+    pub fn register_id(&mut self, id: Uuid, material: Material) {
+        self.to_build.insert(id, material);
+    }
+
+    // A material file (see Material::parse), read when the registry is built
+    pub fn load(&mut self, id: Uuid, path: &str) {
+        self.files.insert(id, path.to_owned());
+    }
+
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        textures: &TextureRegistry,
+    ) -> Result<MaterialRegistry> {
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        let fallback = Texture::load_image(
+            device,
+            queue,
+            textures.format,
+            &white,
+            textures.bind_group_layout(TextureType::Image),
+            Some("material_fallback"),
+        )?;
+
+        // Maps are usually tiled
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut registry = MaterialRegistry {
+            materials: HashMap::new(),
+            bind_groups: HashMap::new(),
+            files: self.files.clone(),
+            fallback,
+            sampler,
+        };
+
+        registry.insert(
+            ID(PBR_DEFAULT_MATERIAL_ID),
+            Material::new(Uuid::nil()),
+            textures,
+            device,
+        )?;
+        for (id, material) in &self.to_build {
+            debug!("building material {}", id);
+            registry.insert(*id, material.clone(), textures, device)?;
+        }
+        for (id, path) in &self.files {
+            debug!("loading material {} from {}", id, path);
+            let text = fs::read_to_string(path)
+                .map_err(|e| anyhow!("failed to read material {}: {}", path, e))?;
+            let material =
+                Material::parse(&text).map_err(|e| anyhow!("in material {}: {}", path, e))?;
+            registry.insert(*id, material, textures, device)?;
+        }
+        Ok(registry)
+    }
+}
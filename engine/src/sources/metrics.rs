@@ -1,17 +1,22 @@
 // use imgui::im_str;
+use instant::Instant;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{Arc, Mutex, MutexGuard},
-    time::{Duration, Instant},
+    time::Duration,
 };
 use uuid::Uuid;
 
+use super::names::NameIndex;
+
 // use super::ui::imgui::ImguiWindow;
 
 pub struct EngineMetrics {
     pub systems: HashMap<Uuid, Arc<Mutex<SystemMetrics>>>,
     pub ui: Arc<Mutex<EngineMetricsUI>>,
     pub fps: Arc<Mutex<u32>>,
+    // Each run_frame, as FrameMetrics::frame_delta
+    pub frames: Arc<Mutex<TimingHistory>>,
 }
 
 impl EngineMetrics {
@@ -20,9 +25,27 @@ impl EngineMetrics {
             ui: Default::default(),
             fps: Arc::new(Mutex::new(0)),
             systems: HashMap::new(),
+            frames: Default::default(),
         }
     }
 
+    // Every timing history, frames first, then systems by name
+    pub fn histories(&self) -> Vec<(String, TimingHistory)> {
+        let mut systems = self
+            .systems
+            .values()
+            .map(|system| {
+                let system = system.lock().unwrap();
+                (system.system_name.to_owned(), system.history.clone())
+            })
+            .collect::<Vec<(String, TimingHistory)>>();
+        systems.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut histories = vec![("frame".to_owned(), self.frames.lock().unwrap().clone())];
+        histories.extend(systems);
+        histories
+    }
+
     // pub fn register_system(&mut self, metrics: Arc<Mutex<SystemMetrics>>) -> Uuid {
     //     let id = Uuid::new_v4();
     //     self.percent_system_shares
@@ -42,6 +65,11 @@ impl EngineMetrics {
         reporter
     }
 
+    // Expensive, should not be called every frame
+    pub fn calculate_entities(&self, names: &NameIndex) {
+        self.ui.lock().unwrap().entity_names = names.labels();
+    }
+
     pub fn mut_system(&mut self, id: &Uuid) -> MutexGuard<SystemMetrics> {
         self.systems.get(id).unwrap().lock().unwrap()
     }
@@ -92,6 +120,49 @@ pub struct EngineMetricsUI {
     pub avg_fps: u32,
     pub percent_system_shares: HashMap<Uuid, (String, u32)>,
     pub avg_execution_time: f64,
+    pub entity_names: Vec<String>,
+
+    // Pixel under the cursor, if the probe is on; see renderer::systems::probe
+    pub probe: Option<ProbeSample>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProbeSample {
+    pub position: (u32, u32),
+    pub rgba8: [u8; 4],
+    // As stored in the target, ie. after tonemapping and sRGB encoding
+    pub srgb: [f32; 4],
+    pub linear: [f32; 4],
+}
+
+impl ProbeSample {
+    pub fn from_rgba8(position: (u32, u32), rgba8: [u8; 4]) -> Self {
+        let srgb = [
+            rgba8[0] as f32 / 255.0,
+            rgba8[1] as f32 / 255.0,
+            rgba8[2] as f32 / 255.0,
+            rgba8[3] as f32 / 255.0,
+        ];
+        let to_linear = |c: f32| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Self {
+            position,
+            rgba8,
+            srgb,
+            // Alpha is never encoded
+            linear: [
+                to_linear(srgb[0]),
+                to_linear(srgb[1]),
+                to_linear(srgb[2]),
+                srgb[3],
+            ],
+        }
+    }
 }
 
 // impl ImguiWindow for EngineMetricsUI {
@@ -163,6 +234,8 @@ impl EngineReporter {
 #[derive(Default)]
 pub struct SystemMetrics {
     pub system_name: String,
+    // Every run, in seconds
+    pub history: TimingHistory,
 
     // Stats (updated once per second by reporter)
     avg_run_time: f64,
@@ -199,6 +272,7 @@ impl SystemReporter {
     pub fn update(&mut self, run_time: f64) {
         self.total_run_time += run_time;
         self.frame_count += 1;
+        self.target.lock().unwrap().history.push(run_time);
 
         if self.last_reported.elapsed() >= Duration::from_secs(1) {
             self.report();
@@ -214,3 +288,73 @@ impl SystemReporter {
         self.target.lock().unwrap().avg_run_time = avg;
     }
 }
+
+// Upper bounds of the histogram buckets, in seconds; the last bucket is everything above
+pub const TIMING_BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.002, 0.004, 0.008, 0.0167, 0.0333, 0.05, 0.1, 0.25,
+];
+const TIMING_HISTORY_LENGTH: usize = 1024;
+
+// Timings in seconds: the most recent ones (for percentiles), and counts since the engine
+// started (for histograms)
+#[derive(Clone, Debug)]
+pub struct TimingHistory {
+    recent: VecDeque<f64>,
+    pub count: u64,
+    pub sum: f64,
+    // Per TIMING_BUCKETS bound, then above the last one; not cumulative
+    pub buckets: [u64; TIMING_BUCKETS.len() + 1],
+}
+
+impl Default for TimingHistory {
+    fn default() -> Self {
+        Self {
+            recent: VecDeque::with_capacity(TIMING_HISTORY_LENGTH),
+            count: 0,
+            sum: 0.0,
+            buckets: [0; TIMING_BUCKETS.len() + 1],
+        }
+    }
+}
+
+impl TimingHistory {
+    pub fn push(&mut self, seconds: f64) {
+        if self.recent.len() == TIMING_HISTORY_LENGTH {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(seconds);
+        self.count += 1;
+        self.sum += seconds;
+        let bucket = TIMING_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(TIMING_BUCKETS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    // Of the recent timings, with percentile in [0, 100]; 0 before any
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        let mut sorted = self.recent.iter().copied().collect::<Vec<f64>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted[rank.max(1).min(sorted.len()) - 1]
+    }
+
+    pub fn recent_mean(&self) -> f64 {
+        match self.recent.len() {
+            0 => 0.0,
+            len => self.recent.iter().sum::<f64>() / len as f64,
+        }
+    }
+
+    pub fn recent_max(&self) -> f64 {
+        self.recent.iter().copied().fold(0.0, f64::max)
+    }
+
+    pub fn recent_len(&self) -> usize {
+        self.recent.len()
+    }
+}
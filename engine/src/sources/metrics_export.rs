@@ -0,0 +1,203 @@
+use anyhow::Result;
+use std::{fmt::Write as _, fs, net::SocketAddr, path::PathBuf, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use super::metrics::{EngineMetrics, TimingHistory, TIMING_BUCKETS};
+
+// Frame and per-system timings out of the engine, to track performance outside of it: served
+// in the Prometheus text format at any path of an http address, and/or written as a CSV of
+// percentiles (of the last TimingHistory's worth of runs) when the engine exits.
+// See EngineBuilder::with_metrics_export.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsExport {
+    pub prometheus: Option<SocketAddr>,
+    pub csv: Option<PathBuf>,
+
+    started: bool,
+    finished: bool,
+}
+
+impl MetricsExport {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_prometheus(mut self, address: SocketAddr) -> Self {
+        self.prometheus = Some(address);
+        self
+    }
+
+    pub fn with_csv(mut self, path: impl Into<PathBuf>) -> Self {
+        self.csv = Some(path.into());
+        self
+    }
+
+    // Once the engine starts; the endpoint runs on its own thread until the process exits
+    pub(crate) fn start(&mut self, metrics: &Arc<EngineMetrics>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        if let Some(address) = self.prometheus {
+            match serve_prometheus(address, Arc::clone(metrics)) {
+                Ok(()) => info!("serving metrics at http://{}", address),
+                Err(err) => warn!("failed to serve metrics at {}: {}", address, err),
+            }
+        }
+    }
+
+    // Once the engine exits
+    pub(crate) fn finish(&mut self, metrics: &EngineMetrics) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        if let Some(path) = &self.csv {
+            match fs::write(path, csv(metrics)) {
+                Ok(()) => info!("wrote metrics to {}", path.display()),
+                Err(err) => warn!("failed to write metrics to {}: {}", path.display(), err),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn serve_prometheus(address: SocketAddr, metrics: Arc<EngineMetrics>) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            // Whatever was asked for, it gets the metrics
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let body = prometheus(&metrics);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    Ok(())
+}
+
+// No sockets or threads in the browser
+#[cfg(target_arch = "wasm32")]
+fn serve_prometheus(_address: SocketAddr, _metrics: Arc<EngineMetrics>) -> Result<()> {
+    Err(anyhow::anyhow!("not available on the web"))
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &str, history: &TimingHistory) {
+    let bucket = |bound: &str| match labels.is_empty() {
+        true => format!("le=\"{}\"", bound),
+        false => format!("{},le=\"{}\"", labels, bound),
+    };
+    let mut cumulative = 0;
+    for (bound, count) in TIMING_BUCKETS.iter().zip(history.buckets.iter()) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}}} {}",
+            name,
+            bucket(&bound.to_string()),
+            cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{}}} {}",
+        name,
+        bucket("+Inf"),
+        history.count
+    );
+
+    let labels = match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", labels),
+    };
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, history.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels, history.count);
+}
+
+pub fn prometheus(metrics: &EngineMetrics) -> String {
+    let mut out = String::new();
+    let histories = metrics.histories();
+
+    let _ = writeln!(
+        out,
+        "# HELP ember_fps Frames per second, over the last second."
+    );
+    let _ = writeln!(out, "# TYPE ember_fps gauge");
+    let _ = writeln!(out, "ember_fps {}", *metrics.fps.lock().unwrap());
+
+    let _ = writeln!(
+        out,
+        "# HELP ember_frame_seconds Time spent running each frame."
+    );
+    let _ = writeln!(out, "# TYPE ember_frame_seconds histogram");
+    write_histogram(&mut out, "ember_frame_seconds", "", &histories[0].1);
+
+    let _ = writeln!(
+        out,
+        "# HELP ember_system_seconds Run time of each system and render node."
+    );
+    let _ = writeln!(out, "# TYPE ember_system_seconds histogram");
+    for (name, history) in &histories[1..] {
+        let labels = format!("system=\"{}\"", escape_label(name));
+        write_histogram(&mut out, "ember_system_seconds", &labels, history);
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP ember_recent_seconds Percentiles of the most recent frames and system runs."
+    );
+    let _ = writeln!(out, "# TYPE ember_recent_seconds gauge");
+    for (name, history) in &histories {
+        for quantile in [0.5, 0.9, 0.99] {
+            let _ = writeln!(
+                out,
+                "ember_recent_seconds{{system=\"{}\",quantile=\"{}\"}} {}",
+                escape_label(name),
+                quantile,
+                history.percentile(quantile * 100.0)
+            );
+        }
+    }
+    out
+}
+
+// One row per history, frames first; times in milliseconds
+pub fn csv(metrics: &EngineMetrics) -> String {
+    let mut out = String::from("name,runs,recent_runs,mean_ms,p50_ms,p90_ms,p99_ms,max_ms\n");
+    for (name, history) in metrics.histories() {
+        let _ = writeln!(
+            out,
+            "\"{}\",{},{},{:.4},{:.4},{:.4},{:.4},{:.4}",
+            name.replace('"', "\"\""),
+            history.count,
+            history.recent_len(),
+            history.recent_mean() * 1000.0,
+            history.percentile(50.0) * 1000.0,
+            history.percentile(90.0) * 1000.0,
+            history.percentile(99.0) * 1000.0,
+            history.recent_max() * 1000.0,
+        );
+    }
+    out
+}
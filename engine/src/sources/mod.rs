@@ -1,11 +1,34 @@
 use legion::Resources;
 
+pub mod assets;
+pub mod audio;
 pub mod camera;
+pub mod commands;
+pub mod control;
+pub mod display;
+pub mod environment;
+pub mod events;
+pub mod fonts;
+pub mod handles;
+pub mod input;
+pub mod inspector;
+pub mod limiter;
+pub mod loading;
+pub mod manifest;
+pub mod materials;
 pub mod metrics;
+pub mod metrics_export;
+pub mod names;
+pub mod plugin;
+pub mod prefab;
 pub mod primitives;
 pub mod registry;
+pub mod replay;
+pub mod rng;
 pub mod schedule;
+pub mod time;
 pub mod ui;
+pub mod window;
 
 pub trait ResourceBuilder {
     fn build_to_resource(&self, resources: &mut Resources);
@@ -0,0 +1,67 @@
+use legion::Entity;
+use std::collections::HashMap;
+
+// Engine-wide index of entity names, rebuilt every frame by systems::names::name_index.
+// Use label() in log messages so that unnamed entities still show their id.
+#[derive(Default)]
+pub struct NameIndex {
+    names: HashMap<Entity, String>,
+    entities: HashMap<String, Vec<Entity>>,
+}
+
+impl NameIndex {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        self.names.get(&entity).map(String::as_str)
+    }
+
+    // All entities with the given name
+    pub fn find(&self, name: &str) -> &[Entity] {
+        self.entities.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn label(&self, entity: Entity) -> String {
+        match self.name(entity) {
+            Some(name) => format!("{} ({:?})", name, entity),
+            None => format!("{:?}", entity),
+        }
+    }
+
+    // Sorted by name, for UI listings
+    pub fn labels(&self) -> Vec<String> {
+        let mut names = self
+            .names
+            .iter()
+            .map(|(entity, name)| (name, entity))
+            .collect::<Vec<(&String, &Entity)>>();
+        names.sort();
+        names
+            .into_iter()
+            .map(|(name, entity)| format!("{} ({:?})", name, entity))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity, name: &str) {
+        self.names.insert(entity, name.to_owned());
+        self.entities
+            .entry(name.to_owned())
+            .or_insert_with(Vec::new)
+            .push(entity);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.names.clear();
+        self.entities.clear();
+    }
+}
@@ -0,0 +1,27 @@
+use crate::EngineBuilder;
+
+// A feature (audio, physics, a game's own systems) packaged up for EngineBuilder::with_plugin,
+// so it can live in its own crate. build() gets the builder before default_2d / default_3d
+// runs, and can add anything the builder can: systems (with_stage_system, with_fixed_system),
+// resources (with_resource), events (with_event), overlay nodes (with_overlay_node), assets
+// (with_texture_group, with_mesh_group, with_sound...) and other plugins.
+//
+// struct ScorePlugin;
+//
+// impl Plugin for ScorePlugin {
+//     fn build(&self, engine: EngineBuilder) -> EngineBuilder {
+//         engine
+//             .with_resource(Score::default())
+//             .with_event::<ScoreChanged>()
+//             .with_system(score_system())
+//     }
+// }
+pub trait Plugin {
+    fn build(&self, engine: EngineBuilder) -> EngineBuilder;
+
+    // Plugins are only added once (by name), however many times with_plugin is called for
+    // them, eg. by other plugins that depend on them
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
@@ -0,0 +1,453 @@
+use anyhow::{anyhow, Result};
+use legion::{storage::Component, world::Entry, Entity, World};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    components::{Position2D, Transform3D},
+    renderer::{
+        buffer::texture::Texture,
+        mesh::Mesh,
+        systems::{render_2d::Render2D, render_3d::forward_basic::Render3D},
+    },
+    sources::{
+        commands::CommandQueue,
+        handles::Handle,
+        inspector::Inspect,
+        registry::{MeshRegistry, Registry, TextureRegistry},
+    },
+    systems::{lighting_2d::Light2D, lighting_3d::Light3D},
+};
+
+// A component in a prefab, cloned onto every entity spawned from it
+trait PrefabComponent: Send + Sync {
+    fn add_to(&self, entry: &mut Entry);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Component + Clone> PrefabComponent for T {
+    fn add_to(&self, entry: &mut Entry) {
+        entry.add_component(self.clone());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// A bundle of components and assets to spawn entities from, registered by name with
+// EngineBuilder::with_prefab (or with_prefab_file, for the text format below) and spawned
+// with Engine::spawn_prefab or PrefabRegistry::queue_spawn.
+//
+// let enemy = Prefab::new()
+//     .with(Render3D::default("enemy"))
+//     .with(Transform3D::default())
+//     .with_mesh(Meshes::Skull.handle());
+// ...
+// let transform = Transform3D { position, ..Default::default() };
+// engine.spawn_prefab("enemy", Prefab::new().with(transform));
+#[derive(Clone, Default)]
+pub struct Prefab {
+    components: Vec<Arc<dyn PrefabComponent>>,
+    // Cloned from the registry for every entity, as with Engine::clone_mesh
+    mesh: Option<Handle<Mesh>>,
+    // Set on the entity's Render2D or Render3D
+    texture: Option<Handle<Texture>>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Replaces any component of the same type already in the prefab
+    pub fn with<T: Component + Clone>(mut self, component: T) -> Self {
+        self.components
+            .retain(|existing| !existing.as_any().is::<T>());
+        self.components.push(Arc::new(component));
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: Handle<Mesh>) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Handle<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.components
+            .iter()
+            .find_map(|component| component.as_any().downcast_ref::<T>())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.mesh.is_none() && self.texture.is_none()
+    }
+
+    // This prefab with the overrides' components (and mesh and texture, if they have them)
+    // in place of its own
+    pub fn merged(&self, overrides: &Prefab) -> Prefab {
+        let mut merged = self.clone();
+        merged.components.retain(|component| {
+            let any = component.as_any();
+            !overrides
+                .components
+                .iter()
+                .any(|other| other.as_any().type_id() == any.type_id())
+        });
+        merged
+            .components
+            .extend(overrides.components.iter().cloned());
+        merged.mesh = overrides.mesh.or(self.mesh);
+        merged.texture = overrides.texture.or(self.texture);
+        merged
+    }
+
+    fn spawn(&self, world: &mut World, meshes: &MeshRegistry, name: &str) -> Entity {
+        let entity = world.push((PrefabInstance(name.to_owned()),));
+        let mut entry = world.entry(entity).unwrap();
+        for component in &self.components {
+            component.add_to(&mut entry);
+        }
+        if let Some(texture) = self.texture {
+            if let Ok(render) = entry.get_component_mut::<Render2D>() {
+                render.texture = texture.id();
+            }
+            if let Ok(render) = entry.get_component_mut::<Render3D>() {
+                render.texture = texture.id();
+            }
+        }
+        if let Some(mesh) = self.mesh {
+            entry.add_component(meshes.clone_mesh(&mesh.id(), &mesh.group_id()));
+        }
+        entity
+    }
+}
+
+// The prefab an entity was spawned from
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefabInstance(pub String);
+
+// How a component type is written in prefab files: its Inspect fields, by name, over a
+// template for everything else (names, enums...). Fields equal to the template's are left out.
+#[derive(Clone)]
+struct PrefabFormat {
+    name: &'static str,
+    template: Arc<dyn PrefabComponent>,
+    build: fn(&dyn Any, &[(usize, f32)]) -> Arc<dyn PrefabComponent>,
+    fields: fn(&dyn Any) -> Option<Vec<(&'static str, f32)>>,
+}
+
+fn build<T: Inspect + Clone>(
+    template: &dyn Any,
+    fields: &[(usize, f32)],
+) -> Arc<dyn PrefabComponent> {
+    let mut component = template.downcast_ref::<T>().unwrap().clone();
+    for (index, value) in fields {
+        component.set_field(*index, *value);
+    }
+    Arc::new(component)
+}
+
+fn fields<T: Inspect>(component: &dyn Any) -> Option<Vec<(&'static str, f32)>> {
+    component.downcast_ref::<T>().map(T::fields)
+}
+
+// The component types prefab files can have. Default has the engine's own; games add theirs
+// with with_component (or EngineBuilder::with_prefab_format).
+#[derive(Clone)]
+pub struct PrefabFormats {
+    formats: Vec<PrefabFormat>,
+}
+
+impl PrefabFormats {
+    pub fn new() -> Self {
+        Self { formats: vec![] }
+    }
+
+    pub fn with_component<T: Inspect + Clone>(mut self, name: &'static str, template: T) -> Self {
+        self.formats.retain(|format| format.name != name);
+        self.formats.push(PrefabFormat {
+            name,
+            template: Arc::new(template),
+            build: build::<T>,
+            fields: fields::<T>,
+        });
+        self
+    }
+}
+
+impl Default for PrefabFormats {
+    fn default() -> Self {
+        Self::new()
+            .with_component("Transform3D", Transform3D::default())
+            .with_component("Position2D", Position2D { x: 0.0, y: 0.0 })
+            .with_component("Render3D", Render3D::default("prefab"))
+            .with_component(
+                "Render2D",
+                Render2D::solid_rect("prefab", 1.0, 1.0, [1.0, 1.0, 1.0, 1.0]),
+            )
+            .with_component("Light3D", Light3D::point([1.0, 1.0, 1.0], 10.0))
+            .with_component("Light2D", Light2D::new(0.09, 0.032))
+    }
+}
+
+enum PrefabSource {
+    Built(Prefab),
+    File(String),
+}
+
+// Collected by the EngineBuilder; files are parsed once the registries can resolve their
+// asset names
+#[derive(Default)]
+pub struct PrefabRegistryBuilder {
+    prefabs: Vec<(String, PrefabSource)>,
+    formats: PrefabFormats,
+}
+
+impl PrefabRegistryBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs
+            .push((name.to_owned(), PrefabSource::Built(prefab)));
+    }
+
+    pub fn load(&mut self, name: &str, path: &str) {
+        self.prefabs
+            .push((name.to_owned(), PrefabSource::File(path.to_owned())));
+    }
+
+    pub fn with_format<T: Inspect + Clone>(&mut self, name: &'static str, template: T) {
+        self.formats = self.formats.clone().with_component(name, template);
+    }
+
+    pub fn build(self, registry: &Registry) -> Result<PrefabRegistry> {
+        let prefabs = PrefabRegistry {
+            prefabs: Default::default(),
+            formats: Arc::new(self.formats),
+            meshes: Arc::clone(&registry.meshes),
+            textures: Arc::clone(&registry.textures),
+        };
+        for (name, source) in self.prefabs {
+            match source {
+                PrefabSource::Built(prefab) => prefabs.insert(&name, prefab),
+                PrefabSource::File(path) => prefabs.load(&name, &path)?,
+            }
+        }
+        Ok(prefabs)
+    }
+}
+
+// Every prefab, by name; a resource, so game systems can spawn them with queue_spawn.
+// Clones share the same prefabs.
+//
+// Prefab files are plain text: `mesh = <name>` and `texture = <name>` lines for the assets
+// (see Engine::mesh and Engine::texture), then a `[Component]` section per component with
+// `field = value` lines for its Inspect fields. Blank lines and lines starting with # are
+// ignored.
+//
+// mesh = skull
+//
+// [Render3D]
+// color.r = 0.8
+//
+// [Transform3D]
+// scale.x = 2
+#[derive(Clone)]
+pub struct PrefabRegistry {
+    prefabs: Arc<RwLock<HashMap<String, Prefab>>>,
+    formats: Arc<PrefabFormats>,
+    meshes: Arc<RwLock<MeshRegistry>>,
+    textures: Arc<RwLock<TextureRegistry>>,
+}
+
+impl PrefabRegistry {
+    // Replaces any prefab of the same name
+    pub fn insert(&self, name: &str, prefab: Prefab) {
+        self.prefabs
+            .write()
+            .unwrap()
+            .insert(name.to_owned(), prefab);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Prefab> {
+        self.prefabs.read().unwrap().get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.prefabs.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // Spawns the prefab with the overrides' components in place of its own (Prefab::new()
+    // for none). None, with a warning, if there's no such prefab.
+    pub fn spawn(&self, world: &mut World, name: &str, overrides: &Prefab) -> Option<Entity> {
+        let prefab = match self.get(name) {
+            Some(prefab) => prefab,
+            None => {
+                warn!("spawn_prefab: no prefab named {}", name);
+                return None;
+            }
+        };
+        let prefab = match overrides.is_empty() {
+            true => prefab,
+            false => prefab.merged(overrides),
+        };
+        Some(prefab.spawn(world, &self.meshes.read().unwrap(), name))
+    }
+
+    // For systems, which can't reach the world: spawned when the queue is next applied
+    pub fn queue_spawn(&self, queue: &CommandQueue, name: &str, overrides: Prefab) {
+        let prefabs = self.clone();
+        let name = name.to_owned();
+        queue.exec(move |world| {
+            prefabs.spawn(world, &name, &overrides);
+        });
+    }
+
+    // Parses a prefab file and inserts it
+    pub fn load(&self, name: &str, path: &str) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read prefab {}: {}", path, e))?;
+        let prefab = self
+            .parse(&text)
+            .map_err(|e| anyhow!("in prefab {}: {}", path, e))?;
+        self.insert(name, prefab);
+        Ok(())
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Prefab> {
+        let mut prefab = Prefab::new();
+        // The current section's format and the fields set so far
+        let mut section: Option<(&PrefabFormat, Vec<(usize, f32)>)> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| anyhow!("line {}: {}", number + 1, message);
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].trim();
+                let format = self
+                    .formats
+                    .formats
+                    .iter()
+                    .find(|format| format.name == name)
+                    .ok_or_else(|| error(format!("unknown component {}", name)))?;
+                if let Some((format, values)) = section.replace((format, vec![])) {
+                    prefab
+                        .components
+                        .push((format.build)(format.template.as_any(), &values));
+                }
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error(format!("expected key = value, not {}", line))),
+            };
+            match &mut section {
+                Some((format, values)) => {
+                    let index = (format.fields)(format.template.as_any())
+                        .unwrap_or_default()
+                        .iter()
+                        .position(|(field, _)| *field == key)
+                        .ok_or_else(|| error(format!("{} has no field {}", format.name, key)))?;
+                    let value = value
+                        .parse::<f32>()
+                        .map_err(|_| error(format!("{} isn't a number", value)))?;
+                    values.push((index, value));
+                }
+                None => match key {
+                    "mesh" => {
+                        prefab.mesh = Some(
+                            self.meshes
+                                .read()
+                                .unwrap()
+                                .handle(value)
+                                .ok_or_else(|| error(format!("no mesh named {}", value)))?,
+                        )
+                    }
+                    "texture" => {
+                        prefab.texture = Some(
+                            self.textures
+                                .read()
+                                .unwrap()
+                                .handle(value)
+                                .ok_or_else(|| error(format!("no texture named {}", value)))?,
+                        )
+                    }
+                    other => return Err(error(format!("unknown asset {}", other))),
+                },
+            }
+        }
+        if let Some((format, values)) = section {
+            prefab
+                .components
+                .push((format.build)(format.template.as_any(), &values));
+        }
+        Ok(prefab)
+    }
+
+    // The prefab in the format parse reads. Components without a PrefabFormats entry, and
+    // assets without names, can't be written and are left out.
+    pub fn to_text(&self, prefab: &Prefab) -> String {
+        let mut out = String::new();
+        if let Some(mesh) = prefab.mesh {
+            match asset_name(&self.meshes.read().unwrap().names, mesh) {
+                Some(name) => {
+                    let _ = writeln!(out, "mesh = {}", name);
+                }
+                None => warn!("prefab mesh {:?} has no name, leaving it out", mesh),
+            }
+        }
+        if let Some(texture) = prefab.texture {
+            match asset_name(&self.textures.read().unwrap().names, texture) {
+                Some(name) => {
+                    let _ = writeln!(out, "texture = {}", name);
+                }
+                None => warn!("prefab texture {:?} has no name, leaving it out", texture),
+            }
+        }
+
+        for component in &prefab.components {
+            let format = self.formats.formats.iter().find_map(|format| {
+                (format.fields)(component.as_any()).map(|fields| (format, fields))
+            });
+            let (format, values) = match format {
+                Some(format) => format,
+                None => continue,
+            };
+            let defaults = (format.fields)(format.template.as_any()).unwrap_or_default();
+            let _ = writeln!(out, "\n[{}]", format.name);
+            for ((field, value), (_, default)) in values.iter().zip(defaults) {
+                if *value != default {
+                    let _ = writeln!(out, "{} = {}", field, value);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn asset_name<T>(names: &HashMap<String, Handle<T>>, handle: Handle<T>) -> Option<&str> {
+    names
+        .iter()
+        .find(|(_, named)| **named == handle)
+        .map(|(name, _)| name.as_str())
+}
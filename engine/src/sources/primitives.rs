@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{f32::consts::PI, sync::Arc};
 
 use crate::renderer::{
     buffer::{IndexBuffer, Vertex2D, Vertex3D, VertexBuffer},
@@ -10,7 +10,38 @@ use super::registry::MeshBuilder;
 pub enum PrimitiveMesh {
     UnitSquare,
     UnitCube,
+    // 3d, in the xy plane facing +z; see systems::particle_3d
+    UnitQuad,
     ScreenQuad,
+
+    // The rest are 3d, centered on the origin and inside the unit cube, with any axis along
+    // y. Segments go around the axis and rings along it; the registry has one of each (see
+    // constants::PRIMITIVE_MESH_GROUP_ID), or build your own with MeshBuilder::build.
+    Sphere {
+        segments: u32,
+        rings: u32,
+    },
+    Cylinder {
+        segments: u32,
+    },
+    // Hemispheres on the ends of a cylinder, all of radius 0.25; rings per hemisphere
+    Capsule {
+        segments: u32,
+        rings: u32,
+    },
+    // In the xz plane facing +y, split into subdivisions^2 quads
+    Plane {
+        subdivisions: u32,
+    },
+    // Lying in the xz plane; thickness is the tube's radius, the ring's is 0.5 - thickness
+    Torus {
+        segments: u32,
+        sides: u32,
+        thickness: f32,
+    },
+    Cone {
+        segments: u32,
+    },
 }
 
 impl MeshBuilder for PrimitiveMesh {
@@ -18,11 +49,268 @@ impl MeshBuilder for PrimitiveMesh {
         match &self {
             PrimitiveMesh::UnitSquare => unit_square(&device),
             PrimitiveMesh::UnitCube => unit_cube(&device),
+            PrimitiveMesh::UnitQuad => unit_quad(&device),
             PrimitiveMesh::ScreenQuad => screen_quad(&device),
+            PrimitiveMesh::Sphere { segments, rings } => {
+                mesh_3d("sphere", sphere(*segments, *rings), &device)
+            }
+            PrimitiveMesh::Cylinder { segments } => {
+                mesh_3d("cylinder", cylinder(*segments), &device)
+            }
+            PrimitiveMesh::Capsule { segments, rings } => {
+                mesh_3d("capsule", capsule(*segments, *rings), &device)
+            }
+            PrimitiveMesh::Plane { subdivisions } => {
+                mesh_3d("plane", plane(*subdivisions), &device)
+            }
+            PrimitiveMesh::Torus {
+                segments,
+                sides,
+                thickness,
+            } => mesh_3d("torus", torus(*segments, *sides, *thickness), &device),
+            PrimitiveMesh::Cone { segments } => mesh_3d("cone", cone(*segments), &device),
         }
     }
 }
 
+fn mesh_3d(
+    name: &str,
+    (vertices, indices): (Vec<Vertex3D>, Vec<u32>),
+    device: &wgpu::Device,
+) -> Mesh {
+    Mesh {
+        vertex_buffer: VertexBuffer::new_3d(name, &vertices, device),
+        index_buffer: IndexBuffer::new(&indices, device),
+        vertices: Arc::new(bytemuck::cast_slice(&vertices).to_vec()),
+        indices: Arc::new(indices),
+        source: None,
+    }
+}
+
+// One point of a lathe profile: radius, height, the outward normal in the (radius, height)
+// plane, and the v coordinate
+struct ProfilePoint {
+    r: f32,
+    y: f32,
+    normal: [f32; 2],
+    v: f32,
+}
+
+// Sweeps profile around the y axis. The surface faces to the right of the profile (in
+// the radius, height plane), so profiles going up face outwards. Each ring repeats its
+// first vertex for the u = 1 seam.
+fn lathe(
+    profile: &[ProfilePoint],
+    segments: u32,
+    vertices: &mut Vec<Vertex3D>,
+    indices: &mut Vec<u32>,
+) {
+    let segments = segments.max(3);
+    let first = vertices.len() as u32;
+    for point in profile {
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            vertices.push(Vertex3D {
+                position: [point.r * sin, point.y, point.r * cos],
+                uvs: [u, point.v],
+                normal: [
+                    point.normal[0] * sin,
+                    point.normal[1],
+                    point.normal[0] * cos,
+                ],
+            });
+        }
+    }
+
+    let stride = segments + 1;
+    for i in 0..profile.len().saturating_sub(1) as u32 {
+        for j in 0..segments {
+            let a = first + i * stride + j;
+            let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+            indices.extend_from_slice(&[a, b, c, a, c, d]);
+        }
+    }
+}
+
+// A flat cap at height y, facing +y if up, else -y
+fn disc(
+    radius: f32,
+    y: f32,
+    up: bool,
+    segments: u32,
+    vertices: &mut Vec<Vertex3D>,
+    indices: &mut Vec<u32>,
+) {
+    let segments = segments.max(3);
+    let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+    let center = vertices.len() as u32;
+    vertices.push(Vertex3D {
+        position: [0.0, y, 0.0],
+        uvs: [0.5, 0.5],
+        normal,
+    });
+    for j in 0..=segments {
+        let (sin, cos) = (j as f32 / segments as f32 * 2.0 * PI).sin_cos();
+        vertices.push(Vertex3D {
+            position: [radius * sin, y, radius * cos],
+            uvs: [0.5 + sin / 2.0, 0.5 + cos / 2.0],
+            normal,
+        });
+    }
+    for j in 0..segments {
+        let (a, b) = (center + 1 + j, center + 2 + j);
+        match up {
+            true => indices.extend_from_slice(&[center, a, b]),
+            false => indices.extend_from_slice(&[center, b, a]),
+        }
+    }
+}
+
+// Radius 0.5, with rings + 1 rows of vertices from the bottom pole to the top
+pub fn sphere(segments: u32, rings: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let rings = rings.max(2);
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|i| {
+            let t = i as f32 / rings as f32;
+            let (sin, cos) = ((t - 0.5) * PI).sin_cos();
+            ProfilePoint {
+                r: 0.5 * cos,
+                y: 0.5 * sin,
+                normal: [cos, sin],
+                v: 1.0 - t,
+            }
+        })
+        .collect();
+
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    lathe(&profile, segments, &mut vertices, &mut indices);
+    (vertices, indices)
+}
+
+// Radius 0.5 and height 1, capped
+pub fn cylinder(segments: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let profile = [
+        ProfilePoint {
+            r: 0.5,
+            y: -0.5,
+            normal: [1.0, 0.0],
+            v: 1.0,
+        },
+        ProfilePoint {
+            r: 0.5,
+            y: 0.5,
+            normal: [1.0, 0.0],
+            v: 0.0,
+        },
+    ];
+
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    lathe(&profile, segments, &mut vertices, &mut indices);
+    disc(0.5, -0.5, false, segments, &mut vertices, &mut indices);
+    disc(0.5, 0.5, true, segments, &mut vertices, &mut indices);
+    (vertices, indices)
+}
+
+pub fn capsule(segments: u32, rings: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let rings = rings.max(1);
+    let radius = 0.25;
+    // Bottom hemisphere up to its equator, then the top one from its equator; the rows
+    // between the two equators are the cylinder
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|i| (i as f32 / rings as f32 - 1.0, -radius))
+        .chain((0..=rings).map(|i| (i as f32 / rings as f32, radius)))
+        .map(|(t, center)| {
+            let (sin, cos) = (t * PI / 2.0).sin_cos();
+            let y = center + radius * sin;
+            ProfilePoint {
+                r: radius * cos,
+                y,
+                normal: [cos, sin],
+                v: 0.5 - y,
+            }
+        })
+        .collect();
+
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    lathe(&profile, segments, &mut vertices, &mut indices);
+    (vertices, indices)
+}
+
+pub fn plane(subdivisions: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let n = subdivisions.max(1);
+    let mut vertices = Vec::with_capacity(((n + 1) * (n + 1)) as usize);
+    for k in 0..=n {
+        for i in 0..=n {
+            let (u, v) = (i as f32 / n as f32, k as f32 / n as f32);
+            vertices.push(Vertex3D {
+                position: [u - 0.5, 0.0, v - 0.5],
+                uvs: [u, v],
+                normal: [0.0, 1.0, 0.0],
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((n * n * 6) as usize);
+    for k in 0..n {
+        for i in 0..n {
+            let a = k * (n + 1) + i;
+            let (b, c, d) = (a + 1, a + n + 2, a + n + 1);
+            indices.extend_from_slice(&[a, c, b, a, d, c]);
+        }
+    }
+    (vertices, indices)
+}
+
+pub fn torus(segments: u32, sides: u32, thickness: f32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let sides = sides.max(3);
+    let thickness = thickness.clamp(0.0, 0.25);
+    let center = 0.5 - thickness;
+    // Around the tube from its inner edge, under and back over the top
+    let profile: Vec<ProfilePoint> = (0..=sides)
+        .map(|i| {
+            let t = i as f32 / sides as f32;
+            let (sin, cos) = ((2.0 * t - 1.0) * PI).sin_cos();
+            ProfilePoint {
+                r: center + thickness * cos,
+                y: thickness * sin,
+                normal: [cos, sin],
+                v: t,
+            }
+        })
+        .collect();
+
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    lathe(&profile, segments, &mut vertices, &mut indices);
+    (vertices, indices)
+}
+
+// Base of radius 0.5 at y = -0.5, apex at 0.5
+pub fn cone(segments: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    // Perpendicular to the slope, (-0.5, 1)
+    let length = (1.0f32 + 0.25).sqrt();
+    let normal = [1.0 / length, 0.5 / length];
+    let profile = [
+        ProfilePoint {
+            r: 0.5,
+            y: -0.5,
+            normal,
+            v: 1.0,
+        },
+        ProfilePoint {
+            r: 0.0,
+            y: 0.5,
+            normal,
+            v: 0.0,
+        },
+    ];
+
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    lathe(&profile, segments, &mut vertices, &mut indices);
+    disc(0.5, -0.5, false, segments, &mut vertices, &mut indices);
+    (vertices, indices)
+}
+
 pub fn unit_square(device: &wgpu::Device) -> Mesh {
     let vertices = [
         Vertex2D {
@@ -48,8 +336,9 @@ pub fn unit_square(device: &wgpu::Device) -> Mesh {
     Mesh {
         vertex_buffer: VertexBuffer::new_2d("unit_square", &vertices, &device),
         index_buffer: IndexBuffer::new(&indices, &device),
-        vertices: bytemuck::cast_slice(&vertices).to_vec(),
-        indices: indices.to_vec(),
+        vertices: Arc::new(bytemuck::cast_slice(&vertices).to_vec()),
+        indices: Arc::new(indices.to_vec()),
+        source: None,
     }
 }
 
@@ -57,8 +346,44 @@ pub fn unit_cube(device: &wgpu::Device) -> Mesh {
     Mesh {
         vertex_buffer: VertexBuffer::new_3d("unit_cube", &UNIT_CUBE_VERTICES, &device),
         index_buffer: IndexBuffer::new(&UNIT_CUBE_INDICES, &device),
-        vertices: bytemuck::cast_slice(&UNIT_CUBE_VERTICES).to_vec(),
-        indices: UNIT_CUBE_INDICES.to_vec(),
+        vertices: Arc::new(bytemuck::cast_slice(&UNIT_CUBE_VERTICES).to_vec()),
+        indices: Arc::new(UNIT_CUBE_INDICES.to_vec()),
+        source: None,
+    }
+}
+
+pub fn unit_quad(device: &wgpu::Device) -> Mesh {
+    let vertices = [
+        Vertex3D {
+            position: [-0.5, -0.5, 0.0],
+            uvs: [0.0, 1.0],
+            normal: [0.0, 0.0, 1.0],
+        },
+        Vertex3D {
+            position: [0.5, -0.5, 0.0],
+            uvs: [1.0, 1.0],
+            normal: [0.0, 0.0, 1.0],
+        },
+        Vertex3D {
+            position: [0.5, 0.5, 0.0],
+            uvs: [1.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        },
+        Vertex3D {
+            position: [-0.5, 0.5, 0.0],
+            uvs: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        },
+    ];
+
+    let indices = [0, 1, 2, 0, 2, 3];
+
+    Mesh {
+        vertex_buffer: VertexBuffer::new_3d("unit_quad", &vertices, &device),
+        index_buffer: IndexBuffer::new(&indices, &device),
+        vertices: Arc::new(bytemuck::cast_slice(&vertices).to_vec()),
+        indices: Arc::new(indices.to_vec()),
+        source: None,
     }
 }
 
@@ -87,8 +412,9 @@ pub fn screen_quad(device: &wgpu::Device) -> Mesh {
     Mesh {
         vertex_buffer: VertexBuffer::new_2d("screen_quad", &vertices, &device),
         index_buffer: IndexBuffer::new(&indices, &device),
-        vertices: bytemuck::cast_slice(&vertices).to_vec(),
-        indices: indices.to_vec(),
+        vertices: Arc::new(bytemuck::cast_slice(&vertices).to_vec()),
+        indices: Arc::new(indices.to_vec()),
+        source: None,
     }
 }
 
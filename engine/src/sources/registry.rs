@@ -1,30 +1,45 @@
 use anyhow::{anyhow, Result};
-use image::{io::Reader as ImageReader, ImageBuffer, Rgba};
-
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use image::{ImageBuffer, Rgba};
 use std::{
     collections::HashMap,
+    path::Path,
     rc::Rc,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 use uuid::Uuid;
 use wgpu::BindGroup;
 
 use crate::{
     constants::{
-        ID, PRIMITIVE_MESH_GROUP_ID, SCREEN_QUAD_MESH_ID, UNIT_CUBE_MESH_ID, UNIT_SQUARE_MESH_ID,
+        ID, MAX_TEXTURES_2D_BATCH, PRIMITIVE_MESH_GROUP_ID, SCREEN_QUAD_MESH_ID,
+        UNIT_CAPSULE_MESH_ID, UNIT_CONE_MESH_ID, UNIT_CUBE_MESH_ID, UNIT_CYLINDER_MESH_ID,
+        UNIT_PLANE_MESH_ID, UNIT_QUAD_MESH_ID, UNIT_SPHERE_MESH_ID, UNIT_SQUARE_MESH_ID,
+        UNIT_TORUS_MESH_ID,
     },
+    parallel::*,
     renderer::{
-        buffer::texture::Texture,
-        mesh::{Mesh, ObjLoader},
+        buffer::{dds, equirect, ktx2::Ktx2Image, texture::Texture},
+        gltf::{GltfLoader, GltfMaterial, GltfModel},
+        mesh::{BinaryMesh, Mesh, ObjLoader, ParsedMesh},
+        shader::ShaderRegistry,
     },
 };
 
-use super::primitives::PrimitiveMesh;
+use super::{
+    assets,
+    environment::{self, IrradianceCoefficients},
+    handles::Handle,
+    loading::{LoadProgressCallback, LoadProgressReporter, LoadState, LoadStates},
+    manifest::{AssetKind, AssetManifest},
+    primitives::PrimitiveMesh,
+};
 
 pub struct Registry {
     pub textures: Arc<RwLock<TextureRegistry>>,
     pub meshes: Arc<RwLock<MeshRegistry>>,
+    pub shaders: Arc<RwLock<ShaderRegistry>>,
+    // Also a resource; see renderer::systems::streaming
+    pub loads: Arc<RwLock<LoadStates>>,
 }
 
 impl Registry {
@@ -34,6 +49,7 @@ impl Registry {
         texture_format: wgpu::TextureFormat,
         texture_builder: TextureRegistryBuilder,
         mesh_builder: MeshRegistryBuilder,
+        shaders: ShaderRegistry,
     ) -> Result<Registry> {
         Ok(Registry {
             textures: Arc::new(RwLock::new(texture_builder.build(
@@ -41,21 +57,49 @@ impl Registry {
                 queue,
                 texture_format,
             )?)),
-            meshes: Arc::new(RwLock::new(mesh_builder.build(device))),
+            meshes: Arc::new(RwLock::new(mesh_builder.build(device)?)),
+            shaders: Arc::new(RwLock::new(shaders)),
+            loads: Default::default(),
         })
     }
+
+    // Loaded for anything that wasn't streamed, including ids the registries don't have
+    pub fn status(&self, id: &Uuid) -> LoadState {
+        self.loads.read().unwrap().status(id)
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<Handle<Mesh>> {
+        self.meshes.read().unwrap().handle(name)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<Handle<Texture>> {
+        self.textures.read().unwrap().handle(name)
+    }
 }
 
 pub struct TextureRegistry {
     pub textures: HashMap<Uuid, HashMap<Uuid, Texture>>,
     pub shared: HashMap<Uuid, Arc<BindGroup>>,
     pub format: wgpu::TextureFormat,
+    // From load_environment; see renderer::systems::sky::EnvironmentUniformGroup
+    pub environment: Option<IrradianceCoefficients>,
+    pub names: HashMap<String, Handle<Texture>>,
 
     bind_layout: wgpu::BindGroupLayout,
+    unfilterable_bind_layout: wgpu::BindGroupLayout,
+    depth_bind_layout: wgpu::BindGroupLayout,
+    depth_buffer_bind_layout: wgpu::BindGroupLayout,
+    depth_cube_bind_layout: wgpu::BindGroupLayout,
+    material_bind_layout: wgpu::BindGroupLayout,
     cube_bind_layouts: HashMap<usize, wgpu::BindGroupLayout>,
+    image_array_bind_layouts: HashMap<usize, wgpu::BindGroupLayout>,
 }
 
 impl TextureRegistry {
+    pub fn handle(&self, name: &str) -> Option<Handle<Texture>> {
+        self.names.get(name).copied()
+    }
+
     pub fn texture_group(&self, group_id: &Uuid) -> HashMap<Uuid, Arc<BindGroup>> {
         self.textures[group_id]
             .iter()
@@ -63,20 +107,118 @@ impl TextureRegistry {
             .collect()
     }
 
+    // Reloads an image texture from disk, replacing the one in its group. Returns the new bind
+    // group; nodes holding the old one have to be handed it (see hot_reload::swap_texture).
+    pub fn reload(
+        &mut self,
+        source: &AssetSource,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Arc<BindGroup>> {
+        let (path, tex_type) = match source {
+            AssetSource::Texture { path, tex_type, .. } => (path, *tex_type),
+            _ => return Err(anyhow!("{}: not a texture", source.path())),
+        };
+        let layout = self.bind_group_layout(tex_type);
+        let texture = load_texture(path, device, queue, self.format, layout)?;
+        self.insert(source, texture)
+    }
+
+    // Replaces a texture loaded elsewhere (a streaming thread) in its group; see reload
+    pub fn insert(&mut self, source: &AssetSource, texture: Texture) -> Result<Arc<BindGroup>> {
+        let (id, group_id) = match source {
+            AssetSource::Texture { id, group_id, .. } => (id, group_id),
+            _ => return Err(anyhow!("{}: not a texture", source.path())),
+        };
+        let bind_group = Arc::clone(texture.bind_group.as_ref().unwrap());
+        self.textures
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow!("{}: no texture group {}", source.path(), group_id))?
+            .insert(*id, texture);
+        Ok(bind_group)
+    }
+
     pub fn bind_group_layout(&self, tex_type: TextureType) -> &wgpu::BindGroupLayout {
         match tex_type {
             TextureType::Image => &self.bind_layout,
+            TextureType::UnfilterableImage => &self.unfilterable_bind_layout,
+            TextureType::Depth => &self.depth_bind_layout,
+            TextureType::DepthBuffer => &self.depth_buffer_bind_layout,
+            TextureType::DepthCube => &self.depth_cube_bind_layout,
+            TextureType::Material => &self.material_bind_layout,
             TextureType::Cubemap => &self.cube_bind_layouts[&1usize],
             TextureType::CubemapN { n } => &self.cube_bind_layouts[&n],
+            TextureType::ImageArray { n } => &self.image_array_bind_layouts[&n],
+        }
+    }
+
+    // One bind group of image textures from a group, in order (see TextureType::ImageArray),
+    // padded to n with the first
+    pub fn image_array(
+        &self,
+        device: &wgpu::Device,
+        group_id: &Uuid,
+        ids: &[Uuid],
+        n: usize,
+    ) -> Result<BindGroup> {
+        if ids.is_empty() || ids.len() > n {
+            return Err(anyhow!(
+                "image arrays of {} can't hold {} textures",
+                n,
+                ids.len()
+            ));
         }
+        let group = self
+            .textures
+            .get(group_id)
+            .ok_or_else(|| anyhow!("no texture group {}", group_id))?;
+        let textures = ids
+            .iter()
+            .map(|id| {
+                group
+                    .get(id)
+                    .ok_or_else(|| anyhow!("no texture {} in group {}", id, group_id))
+            })
+            .collect::<Result<Vec<&Texture>>>()?;
+
+        let mut entries: Vec<wgpu::BindGroupEntry> = (0..n)
+            .map(|i| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(
+                    &textures.get(i).unwrap_or(&textures[0]).view,
+                ),
+            })
+            .collect();
+        entries.push(wgpu::BindGroupEntry {
+            binding: n as u32,
+            resource: wgpu::BindingResource::Sampler(&textures[0].sampler),
+        });
+        Ok(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: self.bind_group_layout(TextureType::ImageArray { n }),
+            entries: &entries,
+            label: Some("image_array_bind_group"),
+        }))
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum TextureType {
     Image,
+    // Float formats which can't be sampled with filtering, eg. Rgba32Float targets
+    UnfilterableImage,
+    // Depth targets, sampled with a comparison sampler (shadow maps)
+    Depth,
+    // Depth buffers read as plain values, through depth channels (eg. depth of field)
+    DepthBuffer,
+    // Depth cube targets, sampled by direction with a comparison sampler (point light shadows)
+    DepthCube,
+    // A PBR material's maps and factors; see sources::materials
+    Material,
     Cubemap,
     CubemapN { n: usize },
+    // n images sharing the first one's sampler, eg. the textures of a 2D instance batch. Built
+    // from loaded images with TextureRegistry::image_array, rather than loaded.
+    ImageArray { n: usize },
 }
 
 impl TextureType {
@@ -93,8 +235,28 @@ impl TextureType {
             _ => None,
         }
     }
+
+    // What the type's bind group layout holds; node shaders are checked against these
+    pub fn layout_entries(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        match *self {
+            TextureType::Image => image_layout_entries(true),
+            TextureType::UnfilterableImage => image_layout_entries(false),
+            TextureType::Depth => depth_layout_entries(wgpu::TextureViewDimension::D2, true),
+            TextureType::DepthBuffer => depth_layout_entries(wgpu::TextureViewDimension::D2, false),
+            TextureType::DepthCube => depth_layout_entries(wgpu::TextureViewDimension::Cube, true),
+            TextureType::Material => material_layout_entries(),
+            TextureType::Cubemap => cube_layout_entries(1),
+            TextureType::CubemapN { n } => cube_layout_entries(n),
+            TextureType::ImageArray { n } => image_array_layout_entries(n),
+        }
+    }
 }
 
+// A cubemap is a directory with one image per face, or an equirectangular image (see
+// environment::is_equirect)
+const CUBEMAP_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+const CUBEMAP_FACE_EXTENSION: &str = "png";
+
 pub struct TextureDescriptor {
     id: Uuid,
     path: String,
@@ -107,6 +269,12 @@ pub struct TextureDescriptor {
 pub struct TextureRegistryBuilder {
     pub to_load: HashMap<Uuid, Vec<TextureDescriptor>>,
     pub to_share: HashMap<Uuid, Vec<(Uuid, Uuid)>>,
+    pub names: HashMap<String, Handle<Texture>>,
+    pub manifest: Option<Arc<AssetManifest>>,
+    pub environment: Option<String>,
+    // Build with placeholders for swappable textures, to be streamed in afterwards; see
+    // renderer::systems::streaming
+    pub stream: bool,
 }
 
 impl TextureRegistryBuilder {
@@ -114,9 +282,42 @@ impl TextureRegistryBuilder {
         Self {
             to_load: HashMap::new(),
             to_share: HashMap::new(),
+            names: HashMap::new(),
+            manifest: None,
+            environment: None,
+            stream: false,
         }
     }
 
+    pub fn with_manifest(&mut self, manifest: Arc<AssetManifest>) {
+        self.manifest = Some(manifest);
+    }
+
+    // Preprocessed .ktx2 (or .dds) to load instead of the source image(s), if any
+    fn ktx2_path(&self, descriptor: &TextureDescriptor, kind: AssetKind) -> Option<String> {
+        if is_texture_container(&descriptor.path) {
+            return Some(descriptor.path.to_owned());
+        }
+        self.manifest
+            .as_ref()
+            .and_then(|manifest| manifest.resolve(kind, &descriptor.path))
+    }
+
+    // Whether a texture can be replaced once the registry is built (hot reloading, streaming):
+    // images which aren't part of a shared bind group
+    fn is_swappable(&self, descriptor: &TextureDescriptor) -> bool {
+        let shared = self
+            .to_share
+            .values()
+            .flatten()
+            .any(|(_, id)| *id == descriptor.id);
+        let image = matches!(
+            descriptor.texture_type,
+            TextureType::Image | TextureType::UnfilterableImage
+        );
+        image && descriptor.bind_group.is_none() && !shared
+    }
+
     pub fn load(
         &mut self,
         path: &str,
@@ -153,6 +354,41 @@ impl TextureRegistryBuilder {
         }
     }
 
+    // Loads a texture to be looked up by name once the registry is built; a name used twice
+    // refers to the last texture loaded with it
+    pub fn load_named(
+        &mut self,
+        name: &str,
+        path: &str,
+        tex_type: TextureType,
+        group_id: Uuid,
+    ) -> Handle<Texture> {
+        let handle = Handle::new(self.load(path, tex_type, group_id, None), group_id);
+        self.name(name, handle);
+        handle
+    }
+
+    // Names a texture loaded with load or load_id
+    pub fn name(&mut self, name: &str, handle: Handle<Texture>) {
+        self.names.insert(name.to_owned(), handle);
+    }
+
+    // Source path of a texture that's been queued for loading
+    pub fn path(&self, id: &Uuid) -> Option<&str> {
+        self.to_load
+            .values()
+            .flatten()
+            .find(|descriptor| descriptor.id == *id)
+            .map(|descriptor| descriptor.path.as_str())
+    }
+
+    // A cubemap directory (or equirectangular image) to light PBR meshes with. Its irradiance
+    // is read from the manifest's output for it (see ember-assetc), or computed at build time
+    // and read from the irradiance.txt left next to it the first time.
+    pub fn load_environment(&mut self, path: &str) {
+        self.environment = Some(path.to_owned());
+    }
+
     pub fn with_shared_group(&mut self, shared_group_id: Uuid, textures: Vec<(Uuid, Uuid)>) {
         self.to_share.insert(shared_group_id, textures);
     }
@@ -163,8 +399,29 @@ impl TextureRegistryBuilder {
         queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
     ) -> Result<TextureRegistry> {
-        let bind_layout = image_bind_group_layout(device, "texture_bind_group_layout");
-        let cube_bind_layout = cube_bind_group_layout(device, "cube_bind_group_layout");
+        let bind_layout =
+            texture_bind_group_layout(device, "texture_bind_group_layout", TextureType::Image);
+        let unfilterable_bind_layout = texture_bind_group_layout(
+            device,
+            "unfilterable_texture_bind_group_layout",
+            TextureType::UnfilterableImage,
+        );
+        let depth_bind_layout =
+            texture_bind_group_layout(device, "depth_bind_group_layout", TextureType::Depth);
+        let depth_buffer_bind_layout = texture_bind_group_layout(
+            device,
+            "depth_buffer_bind_group_layout",
+            TextureType::DepthBuffer,
+        );
+        let depth_cube_bind_layout = texture_bind_group_layout(
+            device,
+            "depth_cube_bind_group_layout",
+            TextureType::DepthCube,
+        );
+        let material_bind_layout =
+            texture_bind_group_layout(device, "material_bind_group_layout", TextureType::Material);
+        let cube_bind_layout =
+            texture_bind_group_layout(device, "cube_bind_group_layout", TextureType::Cubemap);
 
         let mut cubemap_Ns: Vec<usize> = vec![0];
         let mut cube_bind_layouts: HashMap<usize, wgpu::BindGroupLayout> = HashMap::new();
@@ -183,13 +440,26 @@ impl TextureRegistryBuilder {
         }
 
         // two cubemaps, one bind group
-        let cube_2_bind_layout = cube_2_bind_group_layout(device, "cube_2_bind_group_layout");
+        let cube_2_bind_layout = texture_bind_group_layout(
+            device,
+            "cube_2_bind_group_layout",
+            TextureType::CubemapN { n: 2 },
+        );
 
         cube_bind_layouts.insert(1usize, cube_bind_layout);
         cube_bind_layouts.insert(2usize, cube_2_bind_layout);
 
-        let file_ext = "png";
-        let dirs = vec!["px", "nx", "py", "ny", "pz", "nz"];
+        let mut image_array_bind_layouts: HashMap<usize, wgpu::BindGroupLayout> = HashMap::new();
+        image_array_bind_layouts.insert(
+            MAX_TEXTURES_2D_BATCH,
+            texture_bind_group_layout(
+                device,
+                "image_array_bind_group_layout",
+                TextureType::ImageArray {
+                    n: MAX_TEXTURES_2D_BATCH,
+                },
+            ),
+        );
 
         let mut textures: HashMap<Uuid, HashMap<Uuid, Texture>> = HashMap::new();
 
@@ -197,82 +467,92 @@ impl TextureRegistryBuilder {
             let group_textures = group
                 .into_par_iter()
                 .map(|descriptor| {
-                    match descriptor.texture_type {
-                        TextureType::Image => {
-                            let rgba = ImageReader::open(&descriptor.path)
-                                .map_err(|err| {
-                                    anyhow!("error loading texture {}: - {}", descriptor.path, err)
-                                })?
-                                .decode()?
-                                .into_rgba8();
-                            Ok((
-                                descriptor.id,
-                                Texture::load_image(
-                                    device,
-                                    queue,
-                                    format,
-                                    &rgba,
-                                    &bind_layout,
-                                    None,
-                                )?,
-                            ))
+                    if self.stream && self.is_swappable(descriptor) {
+                        let layout = match descriptor.texture_type {
+                            TextureType::Image => &bind_layout,
+                            _ => &unfilterable_bind_layout,
+                        };
+                        return Ok((
+                            descriptor.id,
+                            placeholder_texture(device, queue, format, layout)?,
+                        ));
+                    }
+
+                    let ktx2_kind = match descriptor.texture_type {
+                        TextureType::Image | TextureType::UnfilterableImage => AssetKind::Texture,
+                        _ => AssetKind::Cubemap,
+                    };
+                    if let Some(path) = self.ktx2_path(descriptor, ktx2_kind) {
+                        debug!("loading ktx2 texture at {}", path);
+                        let layout = match descriptor.texture_type {
+                            TextureType::Image => &bind_layout,
+                            TextureType::UnfilterableImage => &unfilterable_bind_layout,
+                            TextureType::Depth => &depth_bind_layout,
+                            TextureType::DepthBuffer => &depth_buffer_bind_layout,
+                            TextureType::DepthCube => &depth_cube_bind_layout,
+                            TextureType::Material => &material_bind_layout,
+                            TextureType::Cubemap => &cube_bind_layouts[&1usize],
+                            TextureType::CubemapN { n } => &cube_bind_layouts[&n],
+                            TextureType::ImageArray { n } => &image_array_bind_layouts[&n],
+                        };
+                        // e.g. a truncated or unsupported .ktx2; an image preprocessed by
+                        // ember-assetc can load its source instead
+                        let has_source = path != descriptor.path && ktx2_kind == AssetKind::Texture;
+                        match load_ktx2(&path, device, queue, layout) {
+                            Ok(texture) => return Ok((descriptor.id, texture)),
+                            Err(err) if has_source => {
+                                warn!("{}, loading {} instead", err, descriptor.path)
+                            }
+                            Err(err) => return Err(err),
                         }
-                        TextureType::Cubemap => {
-                            let faces: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = dirs
-                                .iter()
-                                .map(|dir| {
-                                    // dir is direction not directory
-                                    let img_path =
-                                        format!("{}/{}.{}", descriptor.path, dir, file_ext);
-                                    debug!("loading cubemap at {}", img_path);
-                                    image::io::Reader::open(img_path)
-                                        .unwrap()
-                                        .decode()
-                                        .unwrap()
-                                        .into_rgba8()
-                                })
-                                .collect();
+                    }
 
+                    match descriptor.texture_type {
+                        TextureType::Image | TextureType::UnfilterableImage => {
+                            let layout = match descriptor.texture_type {
+                                TextureType::Image => &bind_layout,
+                                _ => &unfilterable_bind_layout,
+                            };
                             Ok((
                                 descriptor.id,
-                                Texture::load_cubemap(
-                                    &device,
-                                    &queue,
-                                    wgpu::TextureFormat::Rgba8UnormSrgb,
-                                    &faces,
-                                    &cube_bind_layouts[&1usize],
-                                    None,
-                                )?,
+                                load_image(&descriptor.path, device, queue, format, layout)?,
                             ))
                         }
-                        TextureType::CubemapN { n } => {
-                            let faces: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = dirs
-                                .iter()
-                                .map(|dir| {
-                                    // dir is direction not directory
-                                    let img_path =
-                                        format!("{}/{}.{}", descriptor.path, dir, file_ext);
-                                    debug!("loading cubemap at {}", img_path);
-                                    image::io::Reader::open(img_path)
-                                        .unwrap()
-                                        .decode()
-                                        .unwrap()
-                                        .into_rgba8()
-                                })
-                                .collect();
-
-                            Ok((
-                                descriptor.id,
-                                Texture::load_cubemap(
-                                    &device,
-                                    &queue,
-                                    wgpu::TextureFormat::Rgba8UnormSrgb,
-                                    &faces,
-                                    &cube_bind_layouts[&n],
-                                    None,
-                                )?,
+                        TextureType::Depth | TextureType::DepthBuffer | TextureType::DepthCube => {
+                            Err(anyhow!(
+                                "{}: depth textures are render targets, and can't be loaded",
+                                descriptor.path
                             ))
                         }
+                        TextureType::Material => Err(anyhow!(
+                            "{}: load a material's maps as images, then register the material",
+                            descriptor.path
+                        )),
+                        TextureType::ImageArray { .. } => Err(anyhow!(
+                            "{}: load an image array's textures as images, then build it with \
+                             TextureRegistry::image_array",
+                            descriptor.path
+                        )),
+                        TextureType::Cubemap => Ok((
+                            descriptor.id,
+                            load_cubemap(
+                                &descriptor.path,
+                                device,
+                                queue,
+                                &cube_bind_layouts[&1usize],
+                                &bind_layout,
+                            )?,
+                        )),
+                        TextureType::CubemapN { n } => Ok((
+                            descriptor.id,
+                            load_cubemap(
+                                &descriptor.path,
+                                device,
+                                queue,
+                                &cube_bind_layouts[&n],
+                                &bind_layout,
+                            )?,
+                        )),
                     }
                 })
                 .collect::<Result<HashMap<Uuid, Texture>>>()?;
@@ -335,7 +615,11 @@ impl TextureRegistryBuilder {
             if group_textures.len() == 2 {
                 if group_textures[0].texture_type.is_cubemap() {
                     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        layout: &cube_2_bind_group_layout(device, &format!("cube2_layout_{}", id)),
+                        layout: &texture_bind_group_layout(
+                            device,
+                            &format!("cube2_layout_{}", id),
+                            TextureType::CubemapN { n: 2 },
+                        ),
                         entries: &[
                             wgpu::BindGroupEntry {
                                 binding: 0,
@@ -372,25 +656,198 @@ impl TextureRegistryBuilder {
             }
         }
 
+        let environment = match &self.environment {
+            Some(path) => {
+                info!("loading environment irradiance: {}", path);
+                let preprocessed = self
+                    .manifest
+                    .as_ref()
+                    .and_then(|manifest| manifest.resolve(AssetKind::Irradiance, path));
+                Some(match preprocessed {
+                    Some(cache) => IrradianceCoefficients::read_cache(Path::new(&cache))?,
+                    None => IrradianceCoefficients::load(path)?,
+                })
+            }
+            None => None,
+        };
+
         Ok(TextureRegistry {
             textures,
             shared: shared_groups,
+            environment,
+            names: self.names.clone(),
             bind_layout,
+            unfilterable_bind_layout,
+            depth_bind_layout,
+            depth_buffer_bind_layout,
+            depth_cube_bind_layout,
+            material_bind_layout,
             cube_bind_layouts,
+            image_array_bind_layouts,
             format,
         })
     }
 }
 
+// Stands in for a streamed texture until it's loaded
+fn placeholder_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    layout: &wgpu::BindGroupLayout,
+) -> Result<Texture> {
+    let white = ImageBuffer::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+    Texture::load_image(device, queue, format, &white, layout, None)
+}
+
+// .ktx2 and .dds files hold finished textures, compressed or not, with their mip chains
+fn is_texture_container(path: &str) -> bool {
+    path.ends_with(".ktx2") || path.ends_with(".dds")
+}
+
+// An image, .ktx2 or .dds, by extension
+pub(crate) fn load_texture(
+    path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    layout: &wgpu::BindGroupLayout,
+) -> Result<Texture> {
+    match is_texture_container(path) {
+        true => load_ktx2(path, device, queue, layout),
+        false => load_image(path, device, queue, format, layout),
+    }
+}
+
+fn load_image(
+    path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    layout: &wgpu::BindGroupLayout,
+) -> Result<Texture> {
+    let bytes =
+        assets::read(path).map_err(|err| anyhow!("error loading texture {}: - {}", path, err))?;
+    let image = image::load_from_memory(&bytes)?;
+    match is_hdr(path) {
+        true => Texture::load_hdr(device, queue, &image.into_rgba32f(), layout, None),
+        false => Texture::load_image(device, queue, format, &image.into_rgba8(), layout, None),
+    }
+}
+
+// Loaded as linear Rgba16Float (texture::HDR_FORMAT)
+fn is_hdr(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".hdr") || path.ends_with(".exr")
+}
+
+// A directory of face images, or an equirectangular image rendered into one.
+// image_layout is an Image layout, for the equirect.
+fn load_cubemap(
+    path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    image_layout: &wgpu::BindGroupLayout,
+) -> Result<Texture> {
+    if environment::is_equirect(path) {
+        debug!("loading equirectangular cubemap at {}", path);
+        let bytes = assets::read(path)
+            .map_err(|err| anyhow!("error loading cubemap {}: - {}", path, err))?;
+        let image = image::load_from_memory(&bytes)?;
+        // LDR images are sRGB, like cubemap faces, and linear once sampled
+        let (equirect, width) = match is_hdr(path) {
+            true => {
+                let image = image.into_rgba32f();
+                let texture = Texture::load_hdr(device, queue, &image, image_layout, Some(path))?;
+                (texture, image.width())
+            }
+            false => {
+                let image = image.into_rgba8();
+                let texture = Texture::load_image(
+                    device,
+                    queue,
+                    wgpu::TextureFormat::Rgba8UnormSrgb,
+                    &image,
+                    image_layout,
+                    Some(path),
+                )?;
+                (texture, image.width())
+            }
+        };
+        return equirect::to_cubemap(
+            device,
+            queue,
+            &equirect,
+            width,
+            image_layout,
+            layout,
+            Some(path),
+        );
+    }
+
+    let faces = CUBEMAP_FACES
+        .iter()
+        .map(|dir| {
+            // dir is direction not directory
+            let img_path = format!("{}/{}.{}", path, dir, CUBEMAP_FACE_EXTENSION);
+            debug!("loading cubemap at {}", img_path);
+            Ok(image::load_from_memory(&assets::read(&img_path)?)?.into_rgba8())
+        })
+        .collect::<Result<Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>>>()?;
+    Texture::load_cubemap(
+        device,
+        queue,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        &faces,
+        layout,
+        None,
+    )
+}
+
+fn load_ktx2(
+    path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> Result<Texture> {
+    let bytes =
+        assets::read(path).map_err(|err| anyhow!("error loading texture {}: - {}", path, err))?;
+    let image = match path.ends_with(".dds") {
+        true => dds::read(&bytes),
+        false => Ktx2Image::from_bytes(&bytes),
+    }
+    .map_err(|err| anyhow!("{}: {}", path, err))?;
+    Texture::load_ktx2(device, queue, &image, layout, Some(path))
+}
+
 pub trait MeshBuilder: Send + Sync {
     fn build(&self, device: Arc<wgpu::Device>) -> Mesh;
 }
 
 pub struct MeshRegistry {
     pub groups: HashMap<Uuid, HashMap<Uuid, Arc<dyn MeshBuilder>>>,
+    // glTF model id -> its primitives, each registered as a mesh in the model's group
+    pub models: HashMap<Uuid, Vec<ModelPrimitive>>,
+    pub names: HashMap<String, Handle<Mesh>>,
     pub device: Arc<wgpu::Device>,
 }
 
+// A mesh file's registry entries; see MeshRegistry::parse
+pub struct ParsedMeshSource {
+    pub id: Uuid,
+    pub group_id: Uuid,
+    pub entries: Vec<(Uuid, Arc<dyn MeshBuilder>)>,
+    // For glTF models
+    pub primitives: Option<Vec<ModelPrimitive>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModelPrimitive {
+    pub mesh_id: Uuid,
+    pub material: GltfMaterial,
+}
+
 impl MeshRegistry {
     pub fn register<M: MeshBuilder + 'static>(&mut self, builder: M, group_id: &Uuid) -> Uuid {
         let id = Uuid::new_v4();
@@ -418,22 +875,301 @@ impl MeshRegistry {
         }
     }
 
+    pub fn handle(&self, name: &str) -> Option<Handle<Mesh>> {
+        self.names.get(name).copied()
+    }
+
     pub fn clone_mesh(&self, mesh_id: &Uuid, group_id: &Uuid) -> Mesh {
-        self.groups[group_id][mesh_id].build(Arc::clone(&self.device))
+        let mut mesh = self.groups[group_id][mesh_id].build(Arc::clone(&self.device));
+        mesh.source = Some((*group_id, *mesh_id));
+        mesh
+    }
+
+    // Re-parses a mesh file, replacing its entries. Returns the ids of every replaced mesh,
+    // so that entities cloned from them can be rebuilt. A glTF model keeps its primitive ids
+    // as long as it has the same number of primitives.
+    pub fn reload(&mut self, source: &AssetSource) -> Result<Vec<Uuid>> {
+        Ok(self.insert(MeshRegistry::parse(source)?))
+    }
+
+    // The registry isn't needed to parse, so it can happen on another thread; see insert
+    pub fn parse(source: &AssetSource) -> Result<ParsedMeshSource> {
+        let (id, group_id, path) = match source {
+            AssetSource::Mesh { id, group_id, path } => (id, group_id, path),
+            _ => return Err(anyhow!("{}: not a mesh", source.path())),
+        };
+
+        // The watched path is already resolved, so no manifest is needed
+        let models = Mutex::new(HashMap::new());
+        let entries = MeshRegistryBuilder::new().parse_entries(*id, path, &models)?;
+        Ok(ParsedMeshSource {
+            id: *id,
+            group_id: *group_id,
+            entries,
+            primitives: models.into_inner().unwrap().remove(id),
+        })
+    }
+
+    // Replaces a parsed file's entries; see reload
+    pub fn insert(&mut self, parsed: ParsedMeshSource) -> Vec<Uuid> {
+        let ParsedMeshSource {
+            id,
+            group_id,
+            mut entries,
+            primitives,
+        } = parsed;
+        if let Some(mut primitives) = primitives {
+            let old = self
+                .models
+                .get(&id)
+                .map(|old| old.as_slice())
+                .unwrap_or(&[]);
+            if old.len() == primitives.len() {
+                for (primitive, old) in primitives.iter_mut().zip(old) {
+                    for entry in entries.iter_mut() {
+                        if entry.0 == primitive.mesh_id {
+                            entry.0 = old.mesh_id;
+                        }
+                    }
+                    primitive.mesh_id = old.mesh_id;
+                }
+            }
+            self.models.insert(id, primitives);
+        }
+
+        let group = self.groups.entry(group_id).or_insert_with(HashMap::new);
+        let ids = entries.iter().map(|(id, _)| *id).collect();
+        group.extend(entries);
+        ids
+    }
+
+    // Primitives of a glTF model loaded by id, for drawing them with their own materials
+    pub fn model(&self, model_id: &Uuid) -> Option<&[ModelPrimitive]> {
+        self.models
+            .get(model_id)
+            .map(|primitives| primitives.as_slice())
+    }
+}
+
+// A file loaded through one of the registry builders
+#[derive(Clone, Debug)]
+pub enum AssetSource {
+    Texture {
+        id: Uuid,
+        group_id: Uuid,
+        path: String,
+        tex_type: TextureType,
+    },
+    Mesh {
+        id: Uuid,
+        group_id: Uuid,
+        path: String,
+    },
+}
+
+impl AssetSource {
+    pub fn path(&self) -> &str {
+        match self {
+            AssetSource::Texture { path, .. } => path,
+            AssetSource::Mesh { path, .. } => path,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        match self {
+            AssetSource::Texture { id, .. } => *id,
+            AssetSource::Mesh { id, .. } => *id,
+        }
+    }
+
+    pub fn group_id(&self) -> Uuid {
+        match self {
+            AssetSource::Texture { group_id, .. } => *group_id,
+            AssetSource::Mesh { group_id, .. } => *group_id,
+        }
+    }
+}
+
+// Every file the builders will load, resolved the way they resolve it (manifest outputs,
+// working directory), for hot reloading and streaming; see renderer::systems::hot_reload.
+// Cubemaps are directories and shared bind groups are only built once, so neither is watched.
+pub fn watch(textures: &TextureRegistryBuilder, meshes: &MeshRegistryBuilder) -> Vec<AssetSource> {
+    let mut sources: Vec<AssetSource> = textures
+        .to_load
+        .values()
+        .flatten()
+        .filter(|descriptor| textures.is_swappable(descriptor))
+        .map(|descriptor| AssetSource::Texture {
+            id: descriptor.id,
+            group_id: descriptor.texture_group,
+            path: textures
+                .ktx2_path(descriptor, AssetKind::Texture)
+                .unwrap_or_else(|| descriptor.path.to_owned()),
+            tex_type: descriptor.texture_type,
+        })
+        .collect();
+
+    let base_path = std::env::current_dir().unwrap();
+    for (group_id, group) in &meshes.to_load {
+        for (id, path) in group {
+            let path = base_path.join(path).to_str().unwrap().to_owned();
+            let path = match path.ends_with(".embm") || GltfLoader::is_gltf(&path) {
+                true => path,
+                false => meshes
+                    .manifest
+                    .as_ref()
+                    .and_then(|manifest| manifest.resolve(AssetKind::Mesh, &path))
+                    .unwrap_or(path),
+            };
+            sources.push(AssetSource::Mesh {
+                id: *id,
+                group_id: *group_id,
+                path,
+            });
+        }
     }
+    sources
+}
+
+// Every file the builders will read, as they'll read it, for fetching ahead of time on the
+// web; see sources::assets. External glTF buffers are only known once the model is fetched,
+// see GltfLoader::buffer_paths.
+pub fn fetch_list(textures: &TextureRegistryBuilder, meshes: &MeshRegistryBuilder) -> Vec<String> {
+    let mut paths: Vec<String> = vec![];
+    for descriptor in textures.to_load.values().flatten() {
+        let kind = match descriptor.texture_type {
+            TextureType::Image | TextureType::UnfilterableImage => AssetKind::Texture,
+            TextureType::Cubemap | TextureType::CubemapN { .. } => AssetKind::Cubemap,
+            _ => continue,
+        };
+        match (textures.ktx2_path(descriptor, kind), kind) {
+            (Some(path), _) => paths.push(path),
+            (None, AssetKind::Cubemap) if !environment::is_equirect(&descriptor.path) => {
+                for face in CUBEMAP_FACES.iter() {
+                    paths.push(format!(
+                        "{}/{}.{}",
+                        descriptor.path, face, CUBEMAP_FACE_EXTENSION
+                    ));
+                }
+            }
+            (None, _) => paths.push(descriptor.path.to_owned()),
+        }
+    }
+    for (_, path) in meshes.to_load.values().flatten() {
+        paths.push(meshes.resolve(path));
+        if GltfLoader::is_gltf(path) {
+            paths.extend(meshes.binary(path));
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
 }
 
 pub struct MeshRegistryBuilder {
     pub to_load: HashMap<Uuid, Vec<(Uuid, String)>>,
+    pub names: HashMap<String, Handle<Mesh>>,
+    pub manifest: Option<Arc<AssetManifest>>,
+    pub progress: Option<LoadProgressCallback>,
+    // Build with placeholders (unit cubes), to be streamed in afterwards; see
+    // renderer::systems::streaming
+    pub stream: bool,
 }
 
 impl MeshRegistryBuilder {
     pub fn new() -> Self {
         Self {
             to_load: HashMap::new(),
+            names: HashMap::new(),
+            manifest: None,
+            progress: None,
+            stream: false,
         }
     }
 
+    pub fn with_manifest(&mut self, manifest: Arc<AssetManifest>) {
+        self.manifest = Some(manifest);
+    }
+
+    pub fn with_progress(&mut self, callback: LoadProgressCallback) {
+        self.progress = Some(callback);
+    }
+
+    // The file a mesh is read from: its preprocessed binary, if the manifest has one. glTF
+    // models are read as well, for their materials.
+    fn resolve(&self, path: &str) -> String {
+        if GltfLoader::is_gltf(path) {
+            return path.to_owned();
+        }
+        self.binary(path).unwrap_or_else(|| path.to_owned())
+    }
+
+    // The preprocessed binary for a source mesh, if the manifest has one
+    fn binary(&self, path: &str) -> Option<String> {
+        if path.ends_with(".embm") {
+            return None;
+        }
+        self.manifest
+            .as_ref()
+            .and_then(|manifest| manifest.resolve(AssetKind::Mesh, path))
+    }
+
+    fn parse(&self, path: &str) -> Result<BinaryMesh> {
+        if path.ends_with(".embm") {
+            return BinaryMesh::read(path);
+        }
+        match self.binary(path) {
+            Some(binary) => BinaryMesh::read(&binary),
+            None => ObjLoader::new(path.to_owned()).parse_cached(),
+        }
+    }
+
+    // The file's registry entries: the whole mesh by id, plus one per primitive for glTF
+    fn parse_entries(
+        &self,
+        mesh_id: Uuid,
+        path: &str,
+        models: &Mutex<HashMap<Uuid, Vec<ModelPrimitive>>>,
+    ) -> Result<Vec<(Uuid, Arc<dyn MeshBuilder>)>> {
+        if !GltfLoader::is_gltf(path) {
+            let mesh = self.parse(path)?;
+            return Ok(vec![(
+                mesh_id,
+                ParsedMesh::new(path.to_owned(), mesh).arc_dyn(),
+            )]);
+        }
+
+        // A preprocessed model still has its materials read from the glTF
+        let loader = GltfLoader::new(path.to_owned());
+        let model = match self.binary(path) {
+            Some(binary) => GltfModel {
+                mesh: BinaryMesh::read(&binary)?,
+                materials: loader.materials()?,
+            },
+            None => loader.parse()?,
+        };
+        let mut entries: Vec<(Uuid, Arc<dyn MeshBuilder>)> = vec![];
+        let mut primitives: Vec<ModelPrimitive> = vec![];
+        for (i, material) in model.materials.iter().enumerate() {
+            let primitive_id = Uuid::new_v4();
+            let mesh = model.primitive_mesh(i);
+            entries.push((
+                primitive_id,
+                ParsedMesh::new(format!("{}#{}", path, i), mesh).arc_dyn(),
+            ));
+            primitives.push(ModelPrimitive {
+                mesh_id: primitive_id,
+                material: material.clone(),
+            });
+        }
+        entries.push((
+            mesh_id,
+            ParsedMesh::new(path.to_owned(), model.mesh).arc_dyn(),
+        ));
+        models.lock().unwrap().insert(mesh_id, primitives);
+        Ok(entries)
+    }
+
     pub fn load(&mut self, path: &str, group_id: &Uuid) -> Uuid {
         let id = Uuid::new_v4();
         match self.to_load.get_mut(group_id) {
@@ -454,7 +1190,20 @@ impl MeshRegistryBuilder {
         }
     }
 
-    pub fn build(&self, device: Arc<wgpu::Device>) -> MeshRegistry {
+    // Loads a mesh to be looked up by name once the registry is built; a name used twice refers
+    // to the last mesh loaded with it
+    pub fn load_named(&mut self, name: &str, path: &str, group_id: &Uuid) -> Handle<Mesh> {
+        let handle = Handle::new(self.load(path, group_id), *group_id);
+        self.name(name, handle);
+        handle
+    }
+
+    // Names a mesh loaded with load or load_id
+    pub fn name(&mut self, name: &str, handle: Handle<Mesh>) {
+        self.names.insert(name.to_owned(), handle);
+    }
+
+    pub fn build(&self, device: Arc<wgpu::Device>) -> Result<MeshRegistry> {
         let mut num_meshes = 0;
         let _ = &self
             .to_load
@@ -468,125 +1217,225 @@ impl MeshRegistryBuilder {
 
         let base_path = std::env::current_dir().unwrap();
 
+        let progress = LoadProgressReporter::new(num_meshes, self.progress.clone());
+
+        // Every file is parsed up front, in parallel across groups and files
+        let models = Mutex::new(HashMap::new());
         let mut groups: HashMap<Uuid, HashMap<Uuid, Arc<dyn MeshBuilder>>> = self
             .to_load
             .to_owned()
             .into_par_iter()
             .map(|(group_id, group)| {
-                (
-                    group_id,
-                    group
-                        .into_par_iter()
-                        .map(|(mesh_id, path)| {
-                            (
-                                mesh_id,
-                                ObjLoader::new(base_path.join(&path).to_str().unwrap().to_owned())
-                                    .arc_dyn(),
-                            )
-                        })
-                        .collect(),
-                )
+                let meshes = group
+                    .into_par_iter()
+                    .map(|(mesh_id, path)| {
+                        if self.stream {
+                            let placeholder: Arc<dyn MeshBuilder> =
+                                Arc::new(PrimitiveMesh::UnitCube);
+                            return Ok(vec![(mesh_id, placeholder)]);
+                        }
+                        let path = base_path.join(&path).to_str().unwrap().to_owned();
+                        let entries = self
+                            .parse_entries(mesh_id, &path, &models)
+                            .map_err(|err| anyhow!("error loading mesh {}: {}", path, err))?;
+                        progress.finish(&path);
+                        Ok(entries)
+                    })
+                    .collect::<Result<Vec<Vec<(Uuid, Arc<dyn MeshBuilder>)>>>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect::<HashMap<Uuid, Arc<dyn MeshBuilder>>>();
+                Ok((group_id, meshes))
             })
-            .collect();
+            .collect::<Result<HashMap<Uuid, HashMap<Uuid, Arc<dyn MeshBuilder>>>>>()?;
 
         // Common shapes
         let mut primitive_group: HashMap<Uuid, Arc<dyn MeshBuilder>> = HashMap::new();
         primitive_group.insert(ID(UNIT_SQUARE_MESH_ID), Arc::new(PrimitiveMesh::UnitSquare));
         primitive_group.insert(ID(UNIT_CUBE_MESH_ID), Arc::new(PrimitiveMesh::UnitCube));
+        primitive_group.insert(ID(UNIT_QUAD_MESH_ID), Arc::new(PrimitiveMesh::UnitQuad));
         primitive_group.insert(ID(SCREEN_QUAD_MESH_ID), Arc::new(PrimitiveMesh::ScreenQuad));
+        primitive_group.insert(
+            ID(UNIT_SPHERE_MESH_ID),
+            Arc::new(PrimitiveMesh::Sphere {
+                segments: 32,
+                rings: 16,
+            }),
+        );
+        primitive_group.insert(
+            ID(UNIT_CYLINDER_MESH_ID),
+            Arc::new(PrimitiveMesh::Cylinder { segments: 32 }),
+        );
+        primitive_group.insert(
+            ID(UNIT_CAPSULE_MESH_ID),
+            Arc::new(PrimitiveMesh::Capsule {
+                segments: 32,
+                rings: 8,
+            }),
+        );
+        primitive_group.insert(
+            ID(UNIT_PLANE_MESH_ID),
+            Arc::new(PrimitiveMesh::Plane { subdivisions: 16 }),
+        );
+        primitive_group.insert(
+            ID(UNIT_TORUS_MESH_ID),
+            Arc::new(PrimitiveMesh::Torus {
+                segments: 32,
+                sides: 16,
+                thickness: 0.125,
+            }),
+        );
+        primitive_group.insert(
+            ID(UNIT_CONE_MESH_ID),
+            Arc::new(PrimitiveMesh::Cone { segments: 32 }),
+        );
         groups.insert(ID(PRIMITIVE_MESH_GROUP_ID), primitive_group);
 
-        MeshRegistry {
+        let mut names = self.names.clone();
+        for (name, id) in &[
+            ("unit_square", UNIT_SQUARE_MESH_ID),
+            ("unit_cube", UNIT_CUBE_MESH_ID),
+            ("unit_quad", UNIT_QUAD_MESH_ID),
+            ("screen_quad", SCREEN_QUAD_MESH_ID),
+            ("unit_sphere", UNIT_SPHERE_MESH_ID),
+            ("unit_cylinder", UNIT_CYLINDER_MESH_ID),
+            ("unit_capsule", UNIT_CAPSULE_MESH_ID),
+            ("unit_plane", UNIT_PLANE_MESH_ID),
+            ("unit_torus", UNIT_TORUS_MESH_ID),
+            ("unit_cone", UNIT_CONE_MESH_ID),
+        ] {
+            names
+                .entry(name.to_string())
+                .or_insert_with(|| Handle::new(ID(id), ID(PRIMITIVE_MESH_GROUP_ID)));
+        }
+
+        Ok(MeshRegistry {
             groups,
+            models: models.into_inner().unwrap(),
+            names,
             device: Arc::clone(&device),
-        }
+        })
     }
 }
 
-fn image_bind_group_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
+// A texture type's bind group layout
+pub(crate) fn texture_bind_group_layout(
+    device: &wgpu::Device,
+    label: &str,
+    tex_type: TextureType,
+) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-        ],
+        entries: &tex_type.layout_entries(),
         label: Some(label),
     })
 }
 
-fn cube_bind_group_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
-    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::Cube,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-        ],
-        label: Some(label),
-    })
+fn texture_entry(
+    binding: u32,
+    dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+    sampled_entry(
+        binding,
+        dimension,
+        wgpu::TextureSampleType::Float { filterable: true },
+    )
 }
 
-fn cube_2_bind_group_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
-    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::Cube,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::Cube,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
+fn sampled_entry(
+    binding: u32,
+    dimension: wgpu::TextureViewDimension,
+    sample_type: wgpu::TextureSampleType,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: dimension,
+            sample_type,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32, sampler: wgpu::SamplerBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(sampler),
+        count: None,
+    }
+}
+
+fn image_layout_entries(filterable: bool) -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        sampled_entry(
+            0,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable },
+        ),
+        sampler_entry(
+            1,
+            match filterable {
+                true => wgpu::SamplerBindingType::Filtering,
+                false => wgpu::SamplerBindingType::NonFiltering,
             },
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+        ),
+    ]
+}
+
+fn depth_layout_entries(
+    dimension: wgpu::TextureViewDimension,
+    comparison: bool,
+) -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        sampled_entry(0, dimension, wgpu::TextureSampleType::Depth),
+        sampler_entry(
+            1,
+            match comparison {
+                true => wgpu::SamplerBindingType::Comparison,
+                false => wgpu::SamplerBindingType::NonFiltering,
             },
-        ],
-        label: Some(label),
-    })
+        ),
+    ]
+}
+
+// n cubemaps, each followed by its sampler
+fn cube_layout_entries(n: usize) -> Vec<wgpu::BindGroupLayoutEntry> {
+    (0..n as u32)
+        .flat_map(|i| {
+            vec![
+                texture_entry(i * 2, wgpu::TextureViewDimension::Cube),
+                sampler_entry(i * 2 + 1, wgpu::SamplerBindingType::Filtering),
+            ]
+        })
+        .collect()
+}
+
+// n images, then one sampler for all of them
+fn image_array_layout_entries(n: usize) -> Vec<wgpu::BindGroupLayoutEntry> {
+    let mut entries: Vec<wgpu::BindGroupLayoutEntry> = (0..n as u32)
+        .map(|binding| texture_entry(binding, wgpu::TextureViewDimension::D2))
+        .collect();
+    entries.push(sampler_entry(n as u32, wgpu::SamplerBindingType::Filtering));
+    entries
+}
+
+// albedo, normal, metallic_roughness and occlusion maps, their sampler, then MaterialUniforms
+fn material_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    let mut entries: Vec<wgpu::BindGroupLayoutEntry> = (0..4)
+        .map(|binding| texture_entry(binding, wgpu::TextureViewDimension::D2))
+        .collect();
+    entries.push(sampler_entry(4, wgpu::SamplerBindingType::Filtering));
+    entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 5,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    });
+    entries
 }
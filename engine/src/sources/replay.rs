@@ -0,0 +1,521 @@
+use anyhow::{anyhow, Result};
+use iced_winit::winit::{
+    dpi::PhysicalPosition,
+    event::{
+        DeviceEvent, DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+        MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+    window::WindowId,
+};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufWriter, Write},
+};
+
+// Keyboard and mouse input, recorded to a file by frame and replayed from it in place of the
+// real input; see EngineBuilder::with_input_recording and with_input_replay. Along with
+// EngineBuilder::with_deterministic, a replay runs the game exactly as it was recorded.
+//
+// One event per line: the frame it arrived before, then
+//   key <scancode> <down|up> <VirtualKeyCode, or ->
+//   char <code point>
+//   mouse <left|right|middle|other button number> <down|up>
+//   cursor <x> <y>
+//   wheel <lines|pixels> <x> <y>
+//   motion <dx> <dy>
+//   modifiers <bits>
+//
+// Gamepads (sources::input::Gamepads) aren't recorded, and the UI still sees the real input.
+pub enum InputLogMode {
+    Record(String),
+    Replay(String),
+}
+
+impl InputLogMode {
+    pub fn open(self) -> Result<InputLog> {
+        match self {
+            InputLogMode::Record(path) => {
+                let file = File::create(&path)
+                    .map_err(|e| anyhow!("failed to create input log {}: {}", path, e))?;
+                info!("recording input to {}", path);
+                Ok(InputLog::Recording(BufWriter::new(file)))
+            }
+            InputLogMode::Replay(path) => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| anyhow!("failed to read input log {}: {}", path, e))?;
+                let pending = text
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+                    .map(|(number, line)| {
+                        parse_line(line).map_err(|e| anyhow!("{} line {}: {}", path, number + 1, e))
+                    })
+                    .collect::<Result<VecDeque<_>>>()?;
+                info!("replaying {} input events from {}", pending.len(), path);
+                Ok(InputLog::Replaying(pending))
+            }
+        }
+    }
+}
+
+pub enum InputLog {
+    Recording(BufWriter<File>),
+    Replaying(VecDeque<(u64, RecordedInput)>),
+}
+
+impl InputLog {
+    // Records the event if it's input. While replaying, true if it's real input, which
+    // the engine should ignore.
+    pub(crate) fn filter(&mut self, frame: u64, event: &Event<()>) -> bool {
+        let input = match RecordedInput::from_event(event) {
+            Some(input) => input,
+            None => return false,
+        };
+        match self {
+            InputLog::Recording(writer) => {
+                if let Err(e) = writeln!(writer, "{} {}", frame, input.to_line()) {
+                    warn!("failed to record input: {}", e);
+                }
+                false
+            }
+            InputLog::Replaying(_) => true,
+        }
+    }
+
+    // When the frame's events are done: the recorded ones to feed the input in their place
+    // (for a replay), or none once the log is written out (for a recording)
+    pub(crate) fn end_events(&mut self, frame: u64) -> Vec<Event<'static, ()>> {
+        match self {
+            InputLog::Recording(writer) => {
+                if let Err(e) = writer.flush() {
+                    warn!("failed to write input log: {}", e);
+                }
+                vec![]
+            }
+            InputLog::Replaying(pending) => {
+                let mut events = vec![];
+                while let Some((_, input)) = pending.front().filter(|(at, _)| *at <= frame) {
+                    events.push(input.to_event());
+                    pending.pop_front();
+                }
+                if !events.is_empty() && pending.is_empty() {
+                    info!("input replay finished at frame {}", frame);
+                }
+                events
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordedInput {
+    Key {
+        scancode: u32,
+        pressed: bool,
+        key: Option<VirtualKeyCode>,
+    },
+    Char(char),
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    Wheel {
+        lines: bool,
+        x: f32,
+        y: f32,
+    },
+    MouseMotion {
+        dx: f64,
+        dy: f64,
+    },
+    Modifiers(u32),
+}
+
+impl RecordedInput {
+    fn from_event(event: &Event<()>) -> Option<Self> {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::KeyboardInput { input, .. } => Some(RecordedInput::Key {
+                    scancode: input.scancode,
+                    pressed: input.state == ElementState::Pressed,
+                    key: input.virtual_keycode,
+                }),
+                WindowEvent::ReceivedCharacter(c) => Some(RecordedInput::Char(*c)),
+                WindowEvent::MouseInput { state, button, .. } => Some(RecordedInput::MouseButton {
+                    button: *button,
+                    pressed: *state == ElementState::Pressed,
+                }),
+                WindowEvent::CursorMoved { position, .. } => Some(RecordedInput::CursorMoved {
+                    x: position.x,
+                    y: position.y,
+                }),
+                WindowEvent::MouseWheel { delta, .. } => Some(match delta {
+                    MouseScrollDelta::LineDelta(x, y) => RecordedInput::Wheel {
+                        lines: true,
+                        x: *x,
+                        y: *y,
+                    },
+                    MouseScrollDelta::PixelDelta(position) => RecordedInput::Wheel {
+                        lines: false,
+                        x: position.x as f32,
+                        y: position.y as f32,
+                    },
+                }),
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    Some(RecordedInput::Modifiers(modifiers.bits()))
+                }
+                _ => None,
+            },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => Some(RecordedInput::MouseMotion {
+                dx: delta.0,
+                dy: delta.1,
+            }),
+            _ => None,
+        }
+    }
+
+    // Ids winit never handed out; winit_input_helper doesn't look at them
+    #[allow(deprecated)]
+    fn to_event(self) -> Event<'static, ()> {
+        let device_id = unsafe { DeviceId::dummy() };
+        let window_id = unsafe { WindowId::dummy() };
+        let state = |pressed| match pressed {
+            true => ElementState::Pressed,
+            false => ElementState::Released,
+        };
+        let event = match self {
+            RecordedInput::Key {
+                scancode,
+                pressed,
+                key,
+            } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode,
+                    state: state(pressed),
+                    virtual_keycode: key,
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+            RecordedInput::Char(c) => WindowEvent::ReceivedCharacter(c),
+            RecordedInput::MouseButton { button, pressed } => WindowEvent::MouseInput {
+                device_id,
+                state: state(pressed),
+                button,
+                modifiers: ModifiersState::empty(),
+            },
+            RecordedInput::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(x, y),
+                modifiers: ModifiersState::empty(),
+            },
+            RecordedInput::Wheel { lines, x, y } => WindowEvent::MouseWheel {
+                device_id,
+                delta: match lines {
+                    true => MouseScrollDelta::LineDelta(x, y),
+                    false => {
+                        MouseScrollDelta::PixelDelta(PhysicalPosition::new(x as f64, y as f64))
+                    }
+                },
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            },
+            RecordedInput::MouseMotion { dx, dy } => {
+                return Event::DeviceEvent {
+                    device_id,
+                    event: DeviceEvent::MouseMotion { delta: (dx, dy) },
+                }
+            }
+            RecordedInput::Modifiers(bits) => {
+                WindowEvent::ModifiersChanged(ModifiersState::from_bits_truncate(bits))
+            }
+        };
+        Event::WindowEvent { window_id, event }
+    }
+
+    fn to_line(self) -> String {
+        let state = |pressed| match pressed {
+            true => "down",
+            false => "up",
+        };
+        match self {
+            RecordedInput::Key {
+                scancode,
+                pressed,
+                key,
+            } => format!(
+                "key {} {} {}",
+                scancode,
+                state(pressed),
+                key.map_or("-".to_owned(), |key| format!("{:?}", key))
+            ),
+            RecordedInput::Char(c) => format!("char {}", c as u32),
+            RecordedInput::MouseButton { button, pressed } => {
+                let button = match button {
+                    MouseButton::Left => "left".to_owned(),
+                    MouseButton::Right => "right".to_owned(),
+                    MouseButton::Middle => "middle".to_owned(),
+                    MouseButton::Other(other) => other.to_string(),
+                };
+                format!("mouse {} {}", button, state(pressed))
+            }
+            RecordedInput::CursorMoved { x, y } => format!("cursor {} {}", x, y),
+            RecordedInput::Wheel { lines, x, y } => format!(
+                "wheel {} {} {}",
+                if lines { "lines" } else { "pixels" },
+                x,
+                y
+            ),
+            RecordedInput::MouseMotion { dx, dy } => format!("motion {} {}", dx, dy),
+            RecordedInput::Modifiers(bits) => format!("modifiers {}", bits),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<(u64, RecordedInput)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let arg = |index: usize| {
+        parts
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("expected {} values in {}", index + 1, line))
+    };
+    let number = |index: usize| -> Result<f64> {
+        let value = arg(index)?;
+        value
+            .parse()
+            .map_err(|_| anyhow!("{} isn't a number", value))
+    };
+    let pressed = |index: usize| -> Result<bool> {
+        match arg(index)? {
+            "down" => Ok(true),
+            "up" => Ok(false),
+            other => Err(anyhow!("expected down or up, not {}", other)),
+        }
+    };
+
+    let frame = number(0)? as u64;
+    let input = match arg(1)? {
+        "key" => RecordedInput::Key {
+            scancode: number(2)? as u32,
+            pressed: pressed(3)?,
+            key: match arg(4)? {
+                "-" => None,
+                name => Some(
+                    KEYS.iter()
+                        .copied()
+                        .find(|key| format!("{:?}", key) == name)
+                        .ok_or_else(|| anyhow!("unknown key {}", name))?,
+                ),
+            },
+        },
+        "char" => RecordedInput::Char(
+            std::char::from_u32(number(2)? as u32)
+                .ok_or_else(|| anyhow!("bad char in {}", line))?,
+        ),
+        "mouse" => RecordedInput::MouseButton {
+            button: match arg(2)? {
+                "left" => MouseButton::Left,
+                "right" => MouseButton::Right,
+                "middle" => MouseButton::Middle,
+                _ => MouseButton::Other(number(2)? as u16),
+            },
+            pressed: pressed(3)?,
+        },
+        "cursor" => RecordedInput::CursorMoved {
+            x: number(2)?,
+            y: number(3)?,
+        },
+        "wheel" => RecordedInput::Wheel {
+            lines: arg(2)? == "lines",
+            x: number(3)? as f32,
+            y: number(4)? as f32,
+        },
+        "motion" => RecordedInput::MouseMotion {
+            dx: number(2)?,
+            dy: number(3)?,
+        },
+        "modifiers" => RecordedInput::Modifiers(number(2)? as u32),
+        other => return Err(anyhow!("unknown input {}", other)),
+    };
+    Ok((frame, input))
+}
+
+// Every key, to read back their Debug names
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        F13,
+        F14,
+        F15,
+        F16,
+        F17,
+        F18,
+        F19,
+        F20,
+        F21,
+        F22,
+        F23,
+        F24,
+        Snapshot,
+        Scroll,
+        Pause,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Back,
+        Return,
+        Space,
+        Compose,
+        Caret,
+        Numlock,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadDivide,
+        NumpadDecimal,
+        NumpadComma,
+        NumpadEnter,
+        NumpadEquals,
+        NumpadMultiply,
+        NumpadSubtract,
+        AbntC1,
+        AbntC2,
+        Apostrophe,
+        Apps,
+        Asterisk,
+        At,
+        Ax,
+        Backslash,
+        Calculator,
+        Capital,
+        Colon,
+        Comma,
+        Convert,
+        Equals,
+        Grave,
+        Kana,
+        Kanji,
+        LAlt,
+        LBracket,
+        LControl,
+        LShift,
+        LWin,
+        Mail,
+        MediaSelect,
+        MediaStop,
+        Minus,
+        Mute,
+        MyComputer,
+        NavigateForward,
+        NavigateBackward,
+        NextTrack,
+        NoConvert,
+        OEM102,
+        Period,
+        PlayPause,
+        Plus,
+        Power,
+        PrevTrack,
+        RAlt,
+        RBracket,
+        RControl,
+        RShift,
+        RWin,
+        Semicolon,
+        Slash,
+        Sleep,
+        Stop,
+        Sysrq,
+        Tab,
+        Underline,
+        Unlabeled,
+        VolumeDown,
+        VolumeUp,
+        Wake,
+        WebBack,
+        WebFavorites,
+        WebForward,
+        WebHome,
+        WebRefresh,
+        WebSearch,
+        WebStop,
+        Yen,
+        Copy,
+        Paste,
+        Cut,
+    ]
+};
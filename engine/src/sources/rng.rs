@@ -0,0 +1,54 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+// Every random number the engine draws (particle emitters, variations...), as a resource, so
+// that a run can be reproduced from its seed; see EngineBuilder::with_deterministic. Seeded
+// from entropy otherwise, with the seed logged when the engine starts, so that a run can still
+// be reproduced after the fact. It's an Rng, and games should draw from it too rather than
+// rand::thread_rng.
+//
+// Systems working in parallel fork a generator per task, in a fixed order, so that the numbers
+// don't depend on which thread gets there first.
+pub struct EngineRng {
+    rng: StdRng,
+    seed: u64,
+}
+
+impl EngineRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // A generator of its own, seeded from this one
+    pub fn fork(&mut self) -> StdRng {
+        StdRng::seed_from_u64(self.rng.next_u64())
+    }
+}
+
+impl RngCore for EngineRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -1,10 +1,17 @@
-use std::{marker::PhantomData, sync::Arc};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
-use legion::systems::{Builder as ScheduleBuilder, ParallelRunnable, Runnable};
+use legion::{
+    systems::{Builder as ScheduleBuilder, ParallelRunnable, Runnable},
+    Resources, Schedule, World,
+};
 
-use crate::renderer::graph::NodeState;
+use crate::{components::FrameMetrics, renderer::graph::NodeState};
 
-use super::metrics::SystemReporter;
+use super::{metrics::SystemReporter, time::Time};
 
 pub enum Step {
     Stateless {
@@ -64,6 +71,134 @@ impl SubSchedule {
     }
 }
 
+// The parts of a frame, run in this order as separate legion schedules (so each ends in a
+// flush). Simulation, and the FixedStage with it, is skipped while EngineState::paused;
+// the rest keep running so that the camera, UI and rendering still work. Clear ends the
+// frame's events (see sources::events).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Input,
+    Simulation,
+    UniformLoad,
+    Render,
+    Clear,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Input,
+        Stage::Simulation,
+        Stage::UniformLoad,
+        Stage::Render,
+        Stage::Clear,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EngineState {
+    pub paused: bool,
+}
+
+pub struct StagedSchedule {
+    stages: Vec<(Stage, Schedule)>,
+}
+
+impl StagedSchedule {
+    pub fn builder() -> StagedScheduleBuilder {
+        StagedScheduleBuilder {
+            builders: Stage::ALL
+                .iter()
+                .map(|stage| (*stage, Schedule::builder()))
+                .collect(),
+        }
+    }
+
+    pub fn paused(resources: &Resources) -> bool {
+        resources
+            .get::<EngineState>()
+            .map_or(false, |state| state.paused)
+    }
+
+    pub fn execute(&mut self, world: &mut World, resources: &mut Resources) {
+        for (stage, schedule) in self.stages.iter_mut() {
+            if *stage == Stage::Simulation && Self::paused(resources) {
+                continue;
+            }
+            schedule.execute(world, resources);
+        }
+    }
+}
+
+pub struct StagedScheduleBuilder {
+    builders: Vec<(Stage, ScheduleBuilder)>,
+}
+
+impl StagedScheduleBuilder {
+    pub fn stage(&mut self, stage: Stage) -> &mut ScheduleBuilder {
+        &mut self
+            .builders
+            .iter_mut()
+            .find(|(builder_stage, _)| *builder_stage == stage)
+            .unwrap()
+            .1
+    }
+
+    pub fn build(self) -> StagedSchedule {
+        StagedSchedule {
+            stages: self
+                .builders
+                .into_iter()
+                .map(|(stage, mut builder)| (stage, builder.build()))
+                .collect(),
+        }
+    }
+}
+
+// Systems stepped at a constant rate instead of once per frame, so that physics doesn't
+// depend on the frame rate. Runs right before the main schedule, as many steps as the
+// frame's scaled time covers (see sources::time::Time, which keeps the leftover time);
+// whatever's left over becomes FrameMetrics::alpha().
+pub struct FixedStage {
+    pub schedule: Schedule,
+    pub timestep: Duration,
+}
+
+impl FixedStage {
+    pub fn new(schedule: Schedule, hz: f32) -> Self {
+        Self {
+            schedule,
+            timestep: Duration::from_secs_f32(1.0 / hz),
+        }
+    }
+
+    pub fn execute(
+        &mut self,
+        world: &mut World,
+        resources: &mut Resources,
+        frame_metrics: &RwLock<FrameMetrics>,
+    ) {
+        let steps = resources
+            .get_mut::<Time>()
+            .unwrap()
+            .take_fixed_steps(self.timestep);
+        for _ in 0..steps {
+            frame_metrics
+                .write()
+                .unwrap()
+                .begin_fixed_step(self.timestep);
+            resources
+                .get_mut::<Time>()
+                .unwrap()
+                .begin_fixed_step(self.timestep);
+            self.schedule.execute(world, resources);
+        }
+
+        let alpha = resources.get::<Time>().unwrap().alpha();
+        frame_metrics.write().unwrap().end_fixed_steps(alpha);
+        resources.get_mut::<Time>().unwrap().end_fixed_steps();
+    }
+}
+
 pub trait Schedulable {
     fn schedule(&self, schedule: &mut ScheduleBuilder);
 }
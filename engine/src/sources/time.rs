@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use crate::constants::MAX_FIXED_STEPS_PER_FRAME;
+
+// Simulation time, as a resource: the frame's time (FrameMetrics::frame_delta) scaled by
+// `scale`, and stopped while `paused`. Physics, particles and animation advance by delta()
+// rather than FrameMetrics::delta, so games can slow them down (bullet time) or pause and
+// step them a frame at a time to debug, while the camera, UI and rendering carry on.
+//
+// Pausing time differs from EngineState::paused, which skips the simulation systems
+// altogether; with time paused they still run, with a delta of zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Time {
+    // Eg. 0.25 for quarter speed; zero stops time like pausing does
+    pub scale: f32,
+    pub paused: bool,
+    // Queued by step and step_frame, for the next frame while paused
+    step: Option<Duration>,
+    // The fixed update stage's, once it's run; see sources::schedule::FixedStage
+    fixed_timestep: Option<Duration>,
+    // Used for every frame instead of its real time; see Time::fixed
+    fixed_delta: Option<Duration>,
+
+    delta: Duration,
+    frame_delta: Duration,
+    elapsed: Duration,
+    // Scaled time the fixed update stage hasn't stepped through yet
+    accumulator: Duration,
+    fixed_step: Option<Duration>,
+    alpha: f32,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+            step: None,
+            fixed_timestep: None,
+            fixed_delta: None,
+            delta: Duration::from_secs(0),
+            frame_delta: Duration::from_secs(0),
+            elapsed: Duration::from_secs(0),
+            accumulator: Duration::from_secs(0),
+            fixed_step: None,
+            alpha: 1.0,
+        }
+    }
+
+    // Every frame takes exactly `delta` (before scaling), however long it really took, so that
+    // runs are reproducible; see EngineBuilder::with_deterministic
+    pub fn fixed(delta: Duration) -> Self {
+        Self {
+            fixed_delta: Some(delta),
+            ..Self::new()
+        }
+    }
+
+    // The fixed timestep for systems in the fixed update stage, the frame's scaled time
+    // otherwise
+    pub fn delta(&self) -> Duration {
+        self.fixed_step.unwrap_or(self.delta)
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta().as_secs_f32()
+    }
+
+    // How much of a frame's worth of simulation to do, for systems that move things by a
+    // fixed amount per frame (eg. physics_2d with Velocity2D): 1 in the fixed update stage
+    // (which steps less often instead), otherwise the scaled time over the frame's time, ie.
+    // the scale, or 0 while paused.
+    pub fn frame_scale(&self) -> f32 {
+        if self.fixed_step.is_some() {
+            return 1.0;
+        }
+        match self.frame_delta.as_secs_f32() {
+            raw if raw > 0.0 => self.delta.as_secs_f32() / raw,
+            _ => 0.0,
+        }
+    }
+
+    // Total scaled time since the engine started
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    // As FrameMetrics::alpha, for the scaled fixed steps
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn fixed_timestep(&self) -> Option<Duration> {
+        self.fixed_timestep
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // While paused, advances the next frame by `delta` (unscaled); replaces any step not yet
+    // taken
+    pub fn step(&mut self, delta: Duration) {
+        self.step = Some(delta);
+    }
+
+    // While paused, advances the next frame by one fixed step, or by the last frame's time
+    // without a fixed update stage
+    pub fn step_frame(&mut self) {
+        self.step(self.fixed_timestep.unwrap_or(self.frame_delta));
+    }
+
+    // At the start of every frame, before any schedule runs
+    pub(crate) fn begin_frame(&mut self, frame_delta: Duration) {
+        let frame_delta = self.fixed_delta.unwrap_or(frame_delta);
+        self.frame_delta = frame_delta;
+        self.delta = match (self.paused, self.step.take()) {
+            (false, _) => frame_delta.mul_f32(self.scale.max(0.0)),
+            (true, Some(step)) => step,
+            (true, None) => Duration::from_secs(0),
+        };
+        self.elapsed += self.delta;
+    }
+
+    // Adds the frame's scaled time to the accumulator and takes as many fixed steps out of it
+    // as fit, dropping the rest if that's more than MAX_FIXED_STEPS_PER_FRAME
+    pub(crate) fn take_fixed_steps(&mut self, timestep: Duration) -> u32 {
+        self.fixed_timestep = Some(timestep);
+        self.accumulator += self.delta;
+
+        let mut steps = 0;
+        while self.accumulator >= timestep {
+            if steps == MAX_FIXED_STEPS_PER_FRAME {
+                debug!("fixed update stage fell behind, dropping steps");
+                self.accumulator = Duration::from_secs(0);
+                break;
+            }
+            self.accumulator -= timestep;
+            steps += 1;
+        }
+        self.alpha = self.accumulator.as_secs_f32() / timestep.as_secs_f32();
+        steps
+    }
+
+    pub(crate) fn begin_fixed_step(&mut self, timestep: Duration) {
+        self.fixed_step = Some(timestep);
+    }
+
+    pub(crate) fn end_fixed_steps(&mut self) {
+        self.fixed_step = None;
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,90 @@
+use iced_wgpu::Renderer;
+use iced_winit::widget::{scrollable, Column, Scrollable, Text};
+use iced_winit::{Color, Element, Length};
+use std::sync::{Arc, RwLock, Weak};
+
+use crate::{
+    renderer::graph::RenderGraph,
+    sources::ui::iced::{UIAction, UIPanel},
+};
+
+// The render graph as the exports see it (RenderGraph::export_dot): every node with its kind,
+// inputs, outputs and target format, then its channels and chains. See
+// GraphBuilder::with_graph_panel.
+pub struct RenderGraphPanel {
+    // Filled in once the graph is built; the graph holds the UI, so not an Arc
+    graph: Arc<RwLock<Weak<RenderGraph>>>,
+    list: scrollable::State,
+}
+
+impl RenderGraphPanel {
+    pub fn new(graph: Arc<RwLock<Weak<RenderGraph>>>) -> Self {
+        Self {
+            graph,
+            list: Default::default(),
+        }
+    }
+}
+
+fn label(text: impl Into<String>) -> Text<Renderer> {
+    Text::new(text).size(14).color(Color::WHITE)
+}
+
+impl UIPanel for RenderGraphPanel {
+    fn view(&mut self) -> Element<UIAction, Renderer> {
+        let graph = match self.graph.read().unwrap().upgrade() {
+            Some(graph) => graph,
+            None => return label("Render graph (building)").into(),
+        };
+        let summaries = graph.summarize();
+
+        let mut list = Scrollable::new(&mut self.list)
+            .height(Length::Units(300))
+            .spacing(2);
+        for summary in &summaries {
+            let chain = match summary.chain {
+                Some(chain) => format!(", chain {}", chain),
+                None => String::new(),
+            };
+            list = list.push(label(format!(
+                "{} ({}): in {}, out {}, {}{}",
+                summary.name, summary.kind, summary.inputs, summary.outputs, summary.format, chain
+            )));
+        }
+        for (from, output, to) in &graph.channels {
+            list = list.push(
+                label(format!(
+                    "{} [{}] -> {}",
+                    graph.node_name(from),
+                    output,
+                    graph.node_name(to)
+                ))
+                .color(Color::from_rgb(0.7, 0.8, 1.0)),
+            );
+        }
+        for (from, to) in &graph.depth_channels {
+            list = list.push(
+                label(format!(
+                    "{} [depth] -> {}",
+                    graph.node_name(from),
+                    graph.node_name(to)
+                ))
+                .color(Color::from_rgb(0.7, 0.8, 1.0)),
+            );
+        }
+        for (index, chain) in graph.chains.iter().enumerate() {
+            let names: Vec<String> = chain.iter().map(|id| graph.node_name(id)).collect();
+            list = list.push(
+                label(format!("chain {}: {}", index, names.join(" > ")))
+                    .color(Color::from_rgb(1.0, 0.9, 0.1)),
+            );
+        }
+
+        Column::new()
+            .width(Length::Units(420))
+            .spacing(6)
+            .push(label(format!("Render graph ({} nodes)", summaries.len())).size(18))
+            .push(list)
+            .into()
+    }
+}
@@ -3,11 +3,11 @@ use futures::executor::LocalPool;
 use iced::{Point, Size};
 use iced_wgpu::{wgpu, Backend, Renderer, Settings, Viewport};
 use iced_winit::{conversion, futures, program, winit, Clipboard, Debug};
-use legion::Resources;
+use instant::Instant;
+use legion::{Resources, World};
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
-    time::Instant,
 };
 use uuid::Uuid;
 use wgpu::util::StagingBelt;
@@ -24,11 +24,18 @@ pub struct IcedWinitHelper {
 impl IcedWinitHelper {
     pub fn new(window: &winit::window::Window) -> Self {
         let physical_size = window.inner_size();
-        let viewport = Viewport::with_physical_size(
+        Self::with_viewport(Viewport::with_physical_size(
             Size::new(physical_size.width, physical_size.height),
             window.scale_factor(),
-        );
+        ))
+    }
 
+    // No window to take the size and scale factor from
+    pub fn headless(size: (u32, u32)) -> Self {
+        Self::with_viewport(Viewport::with_physical_size(Size::new(size.0, size.1), 1.0))
+    }
+
+    fn with_viewport(viewport: Viewport) -> Self {
         let cursor_position = PhysicalPosition::new(-1.0, -1.0);
         let modifiers = ModifiersState::default();
 
@@ -45,23 +52,27 @@ pub struct IcedUI {
     pub renderer: Arc<Mutex<Renderer>>,
     pub local_pool: LocalPool,
     pub state: program::State<Controls>,
+
+    // Sent by panels, run by the engine on the world after the UI updates
+    pub actions: Arc<Mutex<Vec<UIAction>>>,
 }
 
 impl IcedUI {
     pub fn new(
         target: Arc<Mutex<RenderTarget>>,
         device: &Arc<wgpu::Device>,
-        window: &winit::window::Window,
         format: wgpu::TextureFormat,
         helper: &IcedWinitHelper,
         debug: &mut Debug,
+        panels: Vec<Box<dyn UIPanel>>,
     ) -> (Self, StagingBelt) {
         let mut renderer = Renderer::new(Backend::new(&device, Settings::default(), format));
 
         let staging_belt = StagingBelt::new(5 * 1024);
         let local_pool = LocalPool::new();
 
-        let controls = Controls::new();
+        let actions = Arc::new(Mutex::new(vec![]));
+        let controls = Controls::new(panels, Arc::clone(&actions));
         let state = program::State::new(
             controls,
             helper.viewport.logical_size(),
@@ -75,6 +86,7 @@ impl IcedUI {
                 local_pool,
                 state,
                 renderer: Arc::new(Mutex::new(renderer)),
+                actions,
             },
             staging_belt,
         )
@@ -95,6 +107,33 @@ impl IcedUI {
             ui_debug,
         );
     }
+
+    pub fn take_actions(&self) -> Vec<UIAction> {
+        self.actions.lock().unwrap().drain(..).collect()
+    }
+}
+
+// A game's own UI, drawn by the iced UI system alongside the engine's. Panels only see their
+// own state when building widgets (hold Arcs to resources to show them); changes go back to
+// the game as UIActions. See EngineBuilder::with_ui_panel.
+pub trait UIPanel: Send {
+    fn view(&mut self) -> Element<UIAction, Renderer>;
+}
+
+// Runs on the world and resources once the UI has handled its events
+#[derive(Clone)]
+pub struct UIAction(pub Arc<dyn Fn(&mut World, &mut Resources) + Send + Sync>);
+
+impl UIAction {
+    pub fn new(action: impl Fn(&mut World, &mut Resources) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(action))
+    }
+}
+
+impl std::fmt::Debug for UIAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UIAction")
+    }
 }
 
 use iced_winit::widget::slider::{self, Slider};
@@ -104,18 +143,23 @@ use iced_winit::{Alignment, Color, Command, Element, Length, Program};
 #[derive(Debug, Clone)]
 pub enum Message {
     BackgroundColorChanged(Color),
+    Panel(UIAction),
 }
 
 pub struct Controls {
     background_color: Color,
     sliders: [slider::State; 3],
+    panels: Vec<Box<dyn UIPanel>>,
+    actions: Arc<Mutex<Vec<UIAction>>>,
 }
 
 impl Controls {
-    pub fn new() -> Controls {
+    pub fn new(panels: Vec<Box<dyn UIPanel>>, actions: Arc<Mutex<Vec<UIAction>>>) -> Controls {
         Controls {
             background_color: Color::BLACK,
             sliders: Default::default(),
+            panels,
+            actions,
         }
     }
 
@@ -133,6 +177,9 @@ impl Program for Controls {
             Message::BackgroundColorChanged(color) => {
                 self.background_color = color;
             }
+            Message::Panel(action) => {
+                self.actions.lock().unwrap().push(action);
+            }
         }
 
         Command::none()
@@ -173,10 +220,19 @@ impl Program for Controls {
                 .step(0.01),
             );
 
+        // Game panels down the left
+        let panels = self
+            .panels
+            .iter_mut()
+            .fold(Column::new().padding(10).spacing(10), |column, panel| {
+                column.push(panel.view().map(Message::Panel))
+            });
+
         Row::new()
             .width(Length::Fill)
             .height(Length::Fill)
             .align_items(Alignment::End)
+            .push(panels)
             .push(
                 Column::new()
                     .width(Length::Fill)
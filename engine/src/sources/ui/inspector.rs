@@ -0,0 +1,186 @@
+use iced_wgpu::Renderer;
+use iced_winit::widget::{
+    button, scrollable, text_input, Button, Column, Row, Scrollable, Text, TextInput,
+};
+use iced_winit::{Color, Element, Length};
+use std::sync::Arc;
+
+use crate::{
+    components::{Name, Transform3D},
+    renderer::systems::gizmo::GizmoState,
+    sources::{
+        inspector::Inspector,
+        ui::iced::{UIAction, UIPanel},
+    },
+};
+
+// Lists the world's entities, and shows the selected one's registered components (see
+// sources::inspector) as fields to type numbers into. Selecting an entity also selects it
+// for the gizmos, if they're on.
+pub struct InspectorPanel {
+    inspector: Arc<Inspector>,
+    entity_list: scrollable::State,
+    entity_buttons: Vec<button::State>,
+    spawn_button: button::State,
+    delete_button: button::State,
+    // One per field of the selected entity, in order
+    field_inputs: Vec<text_input::State>,
+}
+
+impl InspectorPanel {
+    pub fn new(inspector: Arc<Inspector>) -> Self {
+        Self {
+            inspector,
+            entity_list: Default::default(),
+            entity_buttons: vec![],
+            spawn_button: Default::default(),
+            delete_button: Default::default(),
+            field_inputs: vec![],
+        }
+    }
+}
+
+fn label(text: impl Into<String>) -> Text<Renderer> {
+    Text::new(text).size(14).color(Color::WHITE)
+}
+
+fn select(entity: Option<legion::Entity>, inspector: &Arc<Inspector>) -> UIAction {
+    let inspector = Arc::clone(inspector);
+    UIAction::new(move |_, resources| {
+        inspector.select(entity);
+        if let Some(mut gizmo) = resources.get_mut::<GizmoState>() {
+            gizmo.selected = entity;
+        }
+    })
+}
+
+impl UIPanel for InspectorPanel {
+    fn view(&mut self) -> Element<UIAction, Renderer> {
+        let Self {
+            inspector,
+            entity_list,
+            entity_buttons,
+            spawn_button,
+            delete_button,
+            field_inputs,
+        } = self;
+        let inspector: &Arc<Inspector> = inspector;
+        let mut snapshot = inspector.snapshot.lock().unwrap();
+
+        // Typed text only sticks around while its field is focused; after that the field
+        // goes back to showing the component's value
+        let mut field = 0;
+        for (component, fields) in snapshot.components.clone() {
+            for index in 0..fields.len() {
+                if !field_inputs
+                    .get(field)
+                    .map_or(false, |input| input.is_focused())
+                {
+                    snapshot.edits.remove(&(component, index));
+                }
+                field += 1;
+            }
+        }
+        field_inputs.resize_with(field, Default::default);
+        entity_buttons.resize_with(snapshot.entities.len(), Default::default);
+
+        let spawn = {
+            let inspector = Arc::clone(inspector);
+            UIAction::new(move |world, _| {
+                let entity = world.push((Name::new("entity"), Transform3D::origin()));
+                let mut snapshot = inspector.snapshot.lock().unwrap();
+                snapshot
+                    .entities
+                    .push((entity, format!("entity ({:?})", entity)));
+                drop(snapshot);
+                inspector.select(Some(entity));
+            })
+        };
+        let mut actions = Row::new()
+            .spacing(10)
+            .push(Button::new(spawn_button, label("Spawn")).on_press(spawn));
+        if let Some(selected) = snapshot.selected {
+            let inspector = Arc::clone(inspector);
+            let delete = UIAction::new(move |world, resources| {
+                world.remove(selected);
+                let mut snapshot = inspector.snapshot.lock().unwrap();
+                snapshot.entities.retain(|(entity, _)| *entity != selected);
+                drop(snapshot);
+                inspector.select(None);
+                if let Some(mut gizmo) = resources.get_mut::<GizmoState>() {
+                    gizmo.selected = None;
+                }
+            });
+            actions = actions.push(Button::new(delete_button, label("Delete")).on_press(delete));
+        }
+
+        let entities = snapshot
+            .entities
+            .iter()
+            .zip(entity_buttons.iter_mut())
+            .fold(
+                Scrollable::new(entity_list)
+                    .height(Length::Units(200))
+                    .spacing(2),
+                |list, ((entity, name), state)| {
+                    let text = match snapshot.selected == Some(*entity) {
+                        true => label(name.to_owned()).color(Color::from_rgb(1.0, 0.9, 0.1)),
+                        false => label(name.to_owned()),
+                    };
+                    list.push(
+                        Button::new(state, text)
+                            .padding(2)
+                            .on_press(select(Some(*entity), inspector)),
+                    )
+                },
+            );
+
+        let mut column = Column::new()
+            .width(Length::Units(320))
+            .spacing(6)
+            .push(label(format!("Entities ({})", snapshot.entities.len())).size(18))
+            .push(actions)
+            .push(entities);
+
+        let selected = match snapshot.selected {
+            Some(selected) => selected,
+            None => return column.into(),
+        };
+        let mut inputs = field_inputs.iter_mut();
+        for (component, fields) in snapshot.components.iter() {
+            column = column.push(label(inspector.component(*component).name).size(16));
+            for (index, (name, value)) in fields.iter().enumerate() {
+                let key = (*component, index);
+                let text = match snapshot.edits.get(&key) {
+                    Some(text) => text.to_owned(),
+                    None => format!("{}", value),
+                };
+                let inspector = Arc::clone(inspector);
+                let input = TextInput::new(inputs.next().unwrap(), "", &text, move |text| {
+                    let inspector = Arc::clone(&inspector);
+                    UIAction::new(move |world, _| {
+                        if let Ok(value) = text.trim().parse::<f32>() {
+                            inspector.write(world, selected, key.0, key.1, value);
+                        }
+                        inspector
+                            .snapshot
+                            .lock()
+                            .unwrap()
+                            .edits
+                            .insert(key, text.clone());
+                    })
+                })
+                .size(14)
+                .padding(2)
+                .width(Length::Units(120));
+                column = column.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(label(*name).width(Length::Units(140)))
+                        .push(input),
+                );
+            }
+        }
+        column.into()
+    }
+}
@@ -0,0 +1,274 @@
+use iced_wgpu::Renderer;
+use iced_winit::widget::{
+    button, image, scrollable, slider, Button, Column, Image, Row, Scrollable, Slider, Text,
+};
+use iced_winit::{Color, Element, Length};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+use uuid::Uuid;
+
+use crate::{
+    constants::{ID, PBR_DEFAULT_MATERIAL_ID},
+    renderer::systems::render_3d::material_preview::MaterialPreview,
+    sources::{
+        materials::{Material, MaterialRegistry},
+        registry::TextureRegistry,
+        ui::iced::{UIAction, UIPanel},
+    },
+};
+
+// Lists the MaterialRegistry's materials. The selected one is shown on a spinning sphere (see
+// render_3d::material_preview), with sliders for its factors, and if it was loaded from a
+// material file it can be saved back to it. See EngineBuilder::with_material_editor.
+pub struct MaterialEditorPanel {
+    materials: Arc<RwLock<MaterialRegistry>>,
+    preview: Arc<MaterialPreview>,
+    material_list: scrollable::State,
+    material_buttons: Vec<button::State>,
+    save_button: button::State,
+    // Base color red, green, blue and alpha, then metallic, roughness and occlusion strength
+    sliders: [slider::State; 7],
+    // How the last save went
+    status: Arc<Mutex<Option<String>>>,
+}
+
+impl MaterialEditorPanel {
+    pub fn new(materials: Arc<RwLock<MaterialRegistry>>, preview: Arc<MaterialPreview>) -> Self {
+        Self {
+            materials,
+            preview,
+            material_list: Default::default(),
+            material_buttons: vec![],
+            save_button: Default::default(),
+            sliders: Default::default(),
+            status: Default::default(),
+        }
+    }
+}
+
+fn label(text: impl Into<String>) -> Text<Renderer> {
+    Text::new(text).size(14).color(Color::WHITE)
+}
+
+// The file's name for materials loaded from one
+fn name(registry: &MaterialRegistry, id: &Uuid) -> String {
+    if *id == ID(PBR_DEFAULT_MATERIAL_ID) {
+        return "default".to_owned();
+    }
+    match registry.files.get(id) {
+        Some(path) => Path::new(path).file_name().map_or_else(
+            || path.to_owned(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+        None => id.to_string(),
+    }
+}
+
+fn select(id: Uuid, preview: &Arc<MaterialPreview>) -> UIAction {
+    let preview = Arc::clone(preview);
+    UIAction::new(move |_, _| {
+        *preview.selected.lock().unwrap() = Some(id);
+    })
+}
+
+// Rebuilds the material's bind group, which is fine at the rate sliders move
+fn edit(id: Uuid, material: Material, materials: &Arc<RwLock<MaterialRegistry>>) -> UIAction {
+    let materials = Arc::clone(materials);
+    UIAction::new(move |_, resources| {
+        let device = resources.get::<Arc<wgpu::Device>>();
+        let textures = resources.get::<Arc<RwLock<TextureRegistry>>>();
+        let (device, textures) = match (device, textures) {
+            (Some(device), Some(textures)) => (device, textures),
+            _ => return,
+        };
+        let result = materials.write().unwrap().insert(
+            id,
+            material.clone(),
+            &textures.read().unwrap(),
+            &device,
+        );
+        if let Err(err) = result {
+            warn!("material editor: {}", err);
+        }
+    })
+}
+
+fn save(
+    id: Uuid,
+    materials: &Arc<RwLock<MaterialRegistry>>,
+    status: &Arc<Mutex<Option<String>>>,
+) -> UIAction {
+    let materials = Arc::clone(materials);
+    let status = Arc::clone(status);
+    UIAction::new(move |_, _| {
+        let message = match materials.read().unwrap().save(&id) {
+            Ok(()) => "Saved".to_owned(),
+            Err(err) => {
+                warn!("material editor: {}", err);
+                format!("{}", err)
+            }
+        };
+        *status.lock().unwrap() = Some(message);
+    })
+}
+
+// A slider over [0, 1] for one of the material's factors
+fn factor<'a>(
+    name: &str,
+    state: &'a mut slider::State,
+    value: f32,
+    set: fn(&mut Material, f32),
+    (id, material): (Uuid, &Material),
+    materials: &Arc<RwLock<MaterialRegistry>>,
+) -> Row<'a, UIAction, Renderer> {
+    let material = material.clone();
+    let materials = Arc::clone(materials);
+    let slider = Slider::new(state, 0.0..=1.0, value, move |value| {
+        let mut material = material.clone();
+        set(&mut material, value);
+        edit(id, material, &materials)
+    })
+    .step(0.01);
+
+    Row::new()
+        .spacing(10)
+        .push(label(name).width(Length::Units(120)))
+        .push(slider)
+        .push(label(format!("{:.2}", value)).width(Length::Units(40)))
+}
+
+impl UIPanel for MaterialEditorPanel {
+    fn view(&mut self) -> Element<UIAction, Renderer> {
+        let Self {
+            materials,
+            preview,
+            material_list,
+            material_buttons,
+            save_button,
+            sliders,
+            status,
+        } = self;
+        let registry = materials.read().unwrap();
+        let selected = *preview.selected.lock().unwrap();
+
+        // Sorted, so the buttons keep their states between frames
+        let mut entries: Vec<(Uuid, String)> = registry
+            .materials
+            .keys()
+            .map(|id| (*id, name(&registry, id)))
+            .collect();
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+        material_buttons.resize_with(entries.len(), Default::default);
+
+        let list = entries.iter().zip(material_buttons.iter_mut()).fold(
+            Scrollable::new(material_list)
+                .height(Length::Units(160))
+                .spacing(2),
+            |list, ((id, name), state)| {
+                let text = match selected == Some(*id) {
+                    true => label(name.to_owned()).color(Color::from_rgb(1.0, 0.9, 0.1)),
+                    false => label(name.to_owned()),
+                };
+                list.push(
+                    Button::new(state, text)
+                        .padding(2)
+                        .on_press(select(*id, preview)),
+                )
+            },
+        );
+
+        let column = Column::new()
+            .width(Length::Units(320))
+            .spacing(6)
+            .push(label(format!("Materials ({})", entries.len())).size(18))
+            .push(list);
+
+        let (id, material) = match selected.and_then(|id| registry.get(&id).map(|m| (id, m))) {
+            Some(selected) => selected,
+            None => return column.push(label("Select a material to edit it")).into(),
+        };
+
+        let mut column = match &*preview.image.lock().unwrap() {
+            Some(((width, height), pixels)) => column.push(
+                Image::new(image::Handle::from_pixels(*width, *height, pixels.clone()))
+                    .width(Length::Units(240)),
+            ),
+            None => column,
+        };
+
+        let [red, green, blue, alpha, metallic, roughness, occlusion] = sliders;
+        let [r, g, b, a] = material.base_color;
+        column = column
+            .push(factor(
+                "red",
+                red,
+                r,
+                |m, v| m.base_color[0] = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "green",
+                green,
+                g,
+                |m, v| m.base_color[1] = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "blue",
+                blue,
+                b,
+                |m, v| m.base_color[2] = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "alpha",
+                alpha,
+                a,
+                |m, v| m.base_color[3] = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "metallic",
+                metallic,
+                material.metallic,
+                |m, v| m.metallic = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "roughness",
+                roughness,
+                material.roughness,
+                |m, v| m.roughness = v,
+                (id, material),
+                materials,
+            ))
+            .push(factor(
+                "occlusion strength",
+                occlusion,
+                material.occlusion_strength,
+                |m, v| m.occlusion_strength = v,
+                (id, material),
+                materials,
+            ));
+
+        column = match registry.files.contains_key(&id) {
+            true => column.push(
+                Button::new(save_button, label("Save"))
+                    .padding(2)
+                    .on_press(save(id, materials, status)),
+            ),
+            false => column.push(label("Not from a material file, so it can't be saved")),
+        };
+        if let Some(status) = &*status.lock().unwrap() {
+            column = column.push(label(status.to_owned()));
+        }
+        column.into()
+    }
+}
@@ -1,2 +1,6 @@
+pub mod graph;
 pub mod iced;
 pub mod imgui;
+pub mod inspector;
+pub mod material_editor;
+pub mod probe;
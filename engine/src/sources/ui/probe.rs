@@ -0,0 +1,64 @@
+use iced_wgpu::Renderer;
+use iced_winit::widget::{Column, Text};
+use iced_winit::{Color, Element, Length};
+use std::sync::Arc;
+
+use crate::sources::{
+    metrics::EngineMetrics,
+    ui::iced::{UIAction, UIPanel},
+};
+
+// The pixel under the cursor, as sampled by renderer::systems::probe: its position, the
+// stored bytes, and those as sRGB and linear floats. See GraphBuilder::with_pixel_probe.
+pub struct PixelProbePanel {
+    metrics: Arc<EngineMetrics>,
+}
+
+impl PixelProbePanel {
+    pub fn new(metrics: Arc<EngineMetrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+fn label(text: impl Into<String>) -> Text<Renderer> {
+    Text::new(text).size(14).color(Color::WHITE)
+}
+
+fn floats(values: [f32; 4]) -> String {
+    format!(
+        "{:.3}, {:.3}, {:.3}, {:.3}",
+        values[0], values[1], values[2], values[3]
+    )
+}
+
+impl UIPanel for PixelProbePanel {
+    fn view(&mut self) -> Element<UIAction, Renderer> {
+        let title = label("Pixel probe (F6)").size(18);
+        let sample = match self.metrics.ui.lock().unwrap().probe {
+            Some(sample) => sample,
+            None => {
+                return Column::new()
+                    .width(Length::Units(260))
+                    .spacing(4)
+                    .push(title)
+                    .push(label("No sample, move the cursor over the window"))
+                    .into()
+            }
+        };
+        let [r, g, b, a] = sample.rgba8;
+
+        Column::new()
+            .width(Length::Units(260))
+            .spacing(4)
+            // The title in the sampled color, as a swatch
+            .push(title.color(Color::from_rgb8(r, g, b)))
+            .push(label(format!(
+                "position: {}, {}",
+                sample.position.0, sample.position.1
+            )))
+            .push(label(format!("rgba8: {}, {}, {}, {}", r, g, b, a)))
+            .push(label(format!("srgb: {}", floats(sample.srgb))))
+            .push(label(format!("linear: {}", floats(sample.linear))))
+            .into()
+    }
+}
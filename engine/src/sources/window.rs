@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Result};
+use iced_winit::winit::{
+    dpi::LogicalSize,
+    event_loop::EventLoop,
+    window::{Fullscreen, Icon, WindowBuilder},
+};
+use uuid::Uuid;
+
+use crate::constants::{DEFAULT_SCREEN_HEIGHT, DEFAULT_SCREEN_WIDTH};
+
+use super::{assets, registry::TextureRegistryBuilder};
+
+pub enum WindowIcon {
+    // Any texture loaded through the builder (with_texture_group), by id
+    Texture(Uuid),
+    Rgba {
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+}
+
+impl WindowIcon {
+    fn load(&self, textures: &TextureRegistryBuilder) -> Result<Icon> {
+        match self {
+            WindowIcon::Texture(id) => {
+                let path = textures
+                    .path(id)
+                    .ok_or_else(|| anyhow!("window icon: no texture registered as {}", id))?;
+                let image = image::load_from_memory(&assets::read(path)?)?.into_rgba8();
+                let (width, height) = image.dimensions();
+                Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+            }
+            WindowIcon::Rgba {
+                rgba,
+                width,
+                height,
+            } => Ok(Icon::from_rgba(rgba.clone(), *width, *height)?),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FullscreenMode {
+    Windowed,
+    // Covers the current monitor at the desktop resolution
+    Borderless,
+    // Switches the primary monitor to its largest video mode
+    Exclusive,
+}
+
+impl FullscreenMode {
+    fn fullscreen(&self, event_loop: &EventLoop<()>) -> Option<Fullscreen> {
+        match self {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+            FullscreenMode::Exclusive => {
+                let mode = event_loop.primary_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|mode| {
+                        let size = mode.size();
+                        (size.width * size.height, mode.refresh_rate())
+                    })
+                });
+                match mode {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => {
+                        warn!("no video modes for exclusive fullscreen, using borderless");
+                        Some(Fullscreen::Borderless(None))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MacOsWindowConfig {
+    pub title_hidden: bool,
+    pub titlebar_transparent: bool,
+    pub fullsize_content_view: bool,
+}
+
+#[derive(Default)]
+pub struct WebWindowConfig {
+    // Id of the element the canvas goes in; the page's body if None
+    pub parent: Option<String>,
+}
+
+#[derive(Default)]
+pub struct WindowsWindowConfig {
+    // Defaults to the window icon
+    pub taskbar_icon: Option<WindowIcon>,
+}
+
+pub struct WindowConfig {
+    pub title: String,
+    pub icon: Option<WindowIcon>,
+
+    // Logical size of the window when windowed
+    pub size: (u32, u32),
+    pub resizable: bool,
+    pub fullscreen: FullscreenMode,
+    pub decorations: bool,
+    // Fifo is vsync and always available; Mailbox and Immediate aren't supported everywhere
+    pub present_mode: wgpu::PresentMode,
+
+    // Shown in the title, and logged with any panic
+    pub app_name: Option<String>,
+    pub app_version: Option<String>,
+
+    pub macos: MacOsWindowConfig,
+    pub windows: WindowsWindowConfig,
+    pub web: WebWindowConfig,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Ember Engine".to_owned(),
+            icon: None,
+            size: (DEFAULT_SCREEN_WIDTH, DEFAULT_SCREEN_HEIGHT),
+            resizable: true,
+            fullscreen: FullscreenMode::Windowed,
+            decorations: true,
+            present_mode: wgpu::PresentMode::Fifo,
+            app_name: None,
+            app_version: None,
+            macos: Default::default(),
+            windows: Default::default(),
+            web: Default::default(),
+        }
+    }
+}
+
+impl WindowConfig {
+    // "name version", falling back to the plain title
+    pub fn full_title(&self) -> String {
+        match (&self.app_name, &self.app_version) {
+            (Some(name), Some(version)) => format!("{} {}", name, version),
+            (Some(name), None) => name.to_owned(),
+            _ => self.title.to_owned(),
+        }
+    }
+
+    pub fn apply(
+        &self,
+        mut builder: WindowBuilder,
+        textures: &TextureRegistryBuilder,
+        event_loop: &EventLoop<()>,
+    ) -> WindowBuilder {
+        builder = builder
+            .with_title(self.full_title())
+            .with_inner_size(LogicalSize::new(self.size.0 as f64, self.size.1 as f64))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_fullscreen(self.fullscreen.fullscreen(event_loop));
+
+        // A broken icon shouldn't keep the game from starting
+        let icon = self
+            .icon
+            .as_ref()
+            .and_then(|icon| match icon.load(textures) {
+                Ok(icon) => Some(icon),
+                Err(err) => {
+                    warn!("failed to load window icon: {}", err);
+                    None
+                }
+            });
+        builder = builder.with_window_icon(icon.clone());
+
+        #[cfg(target_os = "macos")]
+        {
+            use iced_winit::winit::platform::macos::WindowBuilderExtMacOS;
+            builder = builder
+                .with_title_hidden(self.macos.title_hidden)
+                .with_titlebar_transparent(self.macos.titlebar_transparent)
+                .with_fullsize_content_view(self.macos.fullsize_content_view);
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            use iced_winit::winit::platform::windows::WindowBuilderExtWindows;
+            let taskbar_icon = match &self.windows.taskbar_icon {
+                Some(taskbar_icon) => taskbar_icon.load(textures).ok(),
+                None => icon,
+            };
+            builder = builder.with_taskbar_icon(taskbar_icon);
+        }
+
+        builder
+    }
+
+    // The browser only shows the window once its canvas is in the page
+    #[cfg(target_arch = "wasm32")]
+    pub fn attach_canvas(&self, window: &iced_winit::winit::window::Window) -> Result<()> {
+        use iced_winit::winit::platform::web::WindowExtWebSys;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| anyhow!("no document to add the canvas to"))?;
+        let parent: web_sys::Element = match &self.web.parent {
+            Some(id) => document
+                .get_element_by_id(id)
+                .ok_or_else(|| anyhow!("no element with id {} to add the canvas to", id))?,
+            None => document
+                .body()
+                .ok_or_else(|| anyhow!("no body to add the canvas to"))?
+                .into(),
+        };
+        parent
+            .append_child(&window.canvas())
+            .map_err(|err| anyhow!("failed to add the canvas: {:?}", err))?;
+        Ok(())
+    }
+
+    // Puts the app name and version in front of panic messages, for crash reports
+    pub fn install_panic_hook(&self) {
+        if self.app_name.is_none() {
+            return;
+        }
+        let app = self.full_title();
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            error!("{} crashed: {}", app, info);
+            default_hook(info);
+        }));
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3, VectorSpace};
+use legion::{world::SubWorld, IntoQuery};
+use std::sync::Arc;
+
+use crate::{sources::time::Time, systems::camera_3d::matrix2array_4d};
+
+// Bone matrices are uploaded as a fixed size uniform array
+pub const MAX_JOINTS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: [f32; 3],
+    // Quaternion, [x, y, z, w]
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl JointTransform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let [x, y, z, w] = self.rotation;
+        Matrix4::from_translation(Vector3::from(self.translation))
+            * Matrix4::from(Quaternion::new(w, x, y, z))
+            * Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], self.scale[2])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub name: String,
+    // Always a joint before this one
+    pub parent: Option<usize>,
+    // Mesh space to this joint's space, in the bind pose
+    pub inverse_bind: [[f32; 4]; 4],
+    // Local transform used when no clip animates the joint
+    pub rest: JointTransform,
+}
+
+// Component for Render3D entities with a skinned Mesh (Vertex3DSkinned)
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    pub pose: Vec<JointTransform>,
+
+    // Per joint, global * inverse bind; written by animation_system, uploaded by forward_skinned
+    pub skin: Vec<[[f32; 4]; 4]>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Result<Self> {
+        if joints.len() > MAX_JOINTS {
+            return Err(anyhow!(
+                "skeleton has {} joints, at most {} are supported",
+                joints.len(),
+                MAX_JOINTS
+            ));
+        }
+        for (i, joint) in joints.iter().enumerate() {
+            if matches!(joint.parent, Some(parent) if parent >= i) {
+                return Err(anyhow!(
+                    "joint {} ({}) must come after its parent",
+                    i,
+                    joint.name
+                ));
+            }
+        }
+
+        let mut skeleton = Self {
+            pose: joints.iter().map(|joint| joint.rest).collect(),
+            skin: vec![matrix2array_4d(Matrix4::identity()); joints.len()],
+            joints,
+        };
+        skeleton.update_skin();
+        Ok(skeleton)
+    }
+
+    pub fn joint(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    // Parents come first, so one pass resolves every global transform
+    pub fn update_skin(&mut self) {
+        let mut globals: Vec<Matrix4<f32>> = Vec::with_capacity(self.joints.len());
+        for (i, joint) in self.joints.iter().enumerate() {
+            let local = self.pose[i].matrix();
+            let global = match joint.parent {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            };
+            globals.push(global);
+            self.skin[i] = matrix2array_4d(global * Matrix4::from(joint.inverse_bind));
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Keyframes<T> {
+    // Seconds, ascending
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+}
+
+impl<T: Copy> Keyframes<T> {
+    // Clamps to the first and last keyframes
+    fn sample(&self, time: f32, interpolate: impl Fn(T, T, f32) -> T) -> Option<T> {
+        let last = self.times.len().min(self.values.len()).checked_sub(1)?;
+        let next = self.times[..=last].iter().position(|t| *t > time);
+        Some(match next {
+            Some(0) => self.values[0],
+            None => self.values[last],
+            Some(next) => {
+                let (t0, t1) = (self.times[next - 1], self.times[next]);
+                let amount = (time - t0) / (t1 - t0);
+                interpolate(self.values[next - 1], self.values[next], amount)
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JointChannel {
+    pub joint: usize,
+    pub translation: Option<Keyframes<[f32; 3]>>,
+    pub rotation: Option<Keyframes<[f32; 4]>>,
+    pub scale: Option<Keyframes<[f32; 3]>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<JointChannel>,
+}
+
+impl AnimationClip {
+    // Joints without a channel are left as they are
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| -> [f32; 3] {
+            Vector3::from(a).lerp(Vector3::from(b), t).into()
+        };
+        let slerp = |a: [f32; 4], b: [f32; 4], t: f32| -> [f32; 4] {
+            let a = Quaternion::new(a[3], a[0], a[1], a[2]);
+            let b = Quaternion::new(b[3], b[0], b[1], b[2]);
+            let q = a.slerp(b, t);
+            [q.v.x, q.v.y, q.v.z, q.s]
+        };
+
+        for channel in &self.channels {
+            let joint = match pose.get_mut(channel.joint) {
+                Some(joint) => joint,
+                None => continue,
+            };
+            if let Some(t) = channel
+                .translation
+                .as_ref()
+                .and_then(|k| k.sample(time, lerp))
+            {
+                joint.translation = t;
+            }
+            if let Some(r) = channel
+                .rotation
+                .as_ref()
+                .and_then(|k| k.sample(time, slerp))
+            {
+                joint.rotation = r;
+            }
+            if let Some(s) = channel.scale.as_ref().and_then(|k| k.sample(time, lerp)) {
+                joint.scale = s;
+            }
+        }
+    }
+}
+
+// Plays one clip on the entity's Skeleton
+pub struct Animator {
+    pub clip: Arc<AnimationClip>,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+}
+
+impl Animator {
+    pub fn new(clip: Arc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: true,
+        }
+    }
+
+    pub fn play(&mut self, clip: Arc<AnimationClip>) {
+        self.clip = clip;
+        self.time = 0.0;
+        self.playing = true;
+    }
+}
+
+#[system]
+#[write_component(Animator)]
+#[write_component(Skeleton)]
+pub fn animation(world: &mut SubWorld, #[resource] time: &Time) {
+    let delta = time.delta_secs();
+
+    par_for_each_mut!(<(&mut Animator, &mut Skeleton)>::query(), world, |(
+        animator,
+        skeleton,
+    )| {
+        if animator.playing {
+            animator.time += delta * animator.speed;
+            let duration = animator.clip.duration;
+            if animator.looping && duration > 0.0 {
+                animator.time = animator.time.rem_euclid(duration);
+            } else if animator.time >= duration {
+                animator.time = duration;
+                animator.playing = false;
+            }
+        }
+
+        animator.clip.sample(animator.time, &mut skeleton.pose);
+        skeleton.update_skin();
+    });
+}
@@ -0,0 +1,128 @@
+use legion::{world::SubWorld, IntoQuery};
+use uuid::Uuid;
+
+use crate::{
+    renderer::{
+        buffer::instance::{InstanceGroup, InstanceMutator},
+        systems::render_2d::forward_instance::Render2DInstance,
+    },
+    sources::time::Time,
+};
+
+// Plays frames from a sprite sheet: a texture split into a grid of equally sized cells,
+// read left to right, top to bottom.
+//
+// As a component next to an InstanceGroup<Render2DInstance>, animation_2d_system switches the
+// group to the atlas texture and animates every instance in lockstep (or just `instance`).
+// For instances which animate independently (eg. particles), push a SpriteAnimation as one
+// of the instance's mutators instead; the group's texture must then be the atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteAnimation {
+    pub atlas: Uuid,
+    // [columns, rows]
+    pub grid: [u32; 2],
+    // Cells actually used, from the first
+    pub frames: u32,
+    pub fps: f32,
+    pub looping: bool,
+    pub playing: bool,
+    // Only animate this instance of the group
+    pub instance: Option<u32>,
+
+    pub frame: u32,
+    elapsed: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(atlas: Uuid, columns: u32, rows: u32, fps: f32) -> Self {
+        Self {
+            atlas,
+            grid: [columns.max(1), rows.max(1)],
+            frames: columns.max(1) * rows.max(1),
+            fps,
+            looping: true,
+            playing: true,
+            instance: None,
+            frame: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn with_frames(mut self, frames: u32) -> Self {
+        self.frames = frames.max(1).min(self.grid[0] * self.grid[1]);
+        self
+    }
+
+    pub fn once(mut self) -> Self {
+        self.looping = false;
+        self
+    }
+
+    pub fn for_instance(mut self, id: u32) -> Self {
+        self.instance = Some(id);
+        self
+    }
+
+    // Back to the first frame
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.playing = true;
+    }
+
+    // Non-looping animations stop (and stay) on their last frame
+    pub fn advance(&mut self, delta: f32) {
+        if !self.playing || self.fps <= 0.0 {
+            return;
+        }
+        self.elapsed += delta;
+        let frame_time = 1.0 / self.fps;
+        while self.elapsed >= frame_time {
+            self.elapsed -= frame_time;
+            if self.frame + 1 < self.frames {
+                self.frame += 1;
+            } else if self.looping {
+                self.frame = 0;
+            } else {
+                self.playing = false;
+                self.elapsed = 0.0;
+                break;
+            }
+        }
+    }
+
+    // [u, v, width, height] of the current frame's cell
+    pub fn uv_rect(&self) -> [f32; 4] {
+        let (width, height) = (1.0 / self.grid[0] as f32, 1.0 / self.grid[1] as f32);
+        let (column, row) = (self.frame % self.grid[0], self.frame / self.grid[0]);
+        [column as f32 * width, row as f32 * height, width, height]
+    }
+}
+
+impl InstanceMutator<Render2DInstance> for SpriteAnimation {
+    fn mutate(&mut self, instance: &mut Render2DInstance, delta: f32) {
+        self.advance(delta);
+        instance.uv_rect = self.uv_rect();
+    }
+}
+
+#[system]
+#[write_component(SpriteAnimation)]
+#[write_component(InstanceGroup<Render2DInstance>)]
+pub fn animation_2d(world: &mut SubWorld, #[resource] time: &Time) {
+    debug!("running system animation_2d");
+    let delta = time.delta_secs();
+
+    let mut query = <(&mut SpriteAnimation, &mut InstanceGroup<Render2DInstance>)>::query();
+    query.for_each_mut(world, |(animation, group)| {
+        animation.advance(delta);
+        group.texture = animation.atlas;
+
+        let uv_rect = animation.uv_rect();
+        for instance in group.instances.iter_mut() {
+            if animation.instance.map_or(true, |id| id == instance.id) {
+                instance.uv_rect = uv_rect;
+            }
+        }
+    });
+}
@@ -0,0 +1,101 @@
+use legion::{world::SubWorld, IntoQuery, World};
+use rodio::{Source, SpatialSink};
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    components::{Position2D, Transform3D},
+    sources::audio::{AudioListener, AudioRegistry, AudioSource},
+};
+
+#[system]
+#[write_component(AudioSource)]
+#[read_component(Position2D)]
+#[read_component(Transform3D)]
+pub fn audio(
+    world: &mut SubWorld,
+    #[resource] listener: &AudioListener,
+    #[resource] sounds: &Arc<RwLock<AudioRegistry>>,
+) {
+    debug!("running system audio");
+    let handle = match listener.handle() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let sounds = sounds.read().unwrap();
+    let (left_ear, right_ear) = listener.ears();
+
+    let mut query = <(&mut AudioSource, Option<&Transform3D>, Option<&Position2D>)>::query();
+    query.for_each_mut(world, |(source, transform_3d, position_2d)| {
+        let position = match (source.spatial, transform_3d, position_2d) {
+            (true, Some(transform), _) => transform.position,
+            (true, None, Some(position)) => [position.x, position.y, 0.0],
+            _ => listener.position,
+        };
+
+        // Finished, or stopped by the game
+        if !source.playing
+            || (!source.looping && source.sink.as_ref().map_or(false, |sink| sink.empty()))
+        {
+            if let Some(sink) = source.sink.take() {
+                sink.stop();
+            }
+            source.playing = false;
+            return;
+        }
+
+        if source.sink.is_none() {
+            let sound = match sounds.get(&source.sound) {
+                Some(sound) => sound,
+                None => {
+                    warn!("audio source: no sound registered as {}", source.sound);
+                    source.playing = false;
+                    return;
+                }
+            };
+            let sink = match SpatialSink::try_new(
+                handle,
+                listener.to_local(position),
+                left_ear,
+                right_ear,
+            ) {
+                Ok(sink) => sink,
+                Err(err) => {
+                    warn!("audio source: failed to start playback: {}", err);
+                    source.playing = false;
+                    return;
+                }
+            };
+            match source.looping {
+                true => sink.append(sound.source().repeat_infinite()),
+                false => sink.append(sound.source()),
+            }
+            source.sink = Some(sink);
+        }
+
+        let sink = source.sink.as_ref().unwrap();
+        if sink.is_paused() != listener.paused {
+            match listener.paused {
+                true => sink.pause(),
+                false => sink.play(),
+            }
+        }
+        sink.set_volume(source.volume * listener.volume);
+        sink.set_emitter_position(listener.to_local(position));
+        sink.set_left_ear_position(left_ear);
+        sink.set_right_ear_position(right_ear);
+    });
+}
+
+// Pauses (or resumes) every playing AudioSource right away, rather than with the next audio
+// system, which doesn't run while the simulation is paused
+pub fn pause_audio(world: &mut World, listener: &mut AudioListener, paused: bool) {
+    listener.paused = paused;
+    for source in <&AudioSource>::query().iter(world) {
+        if let Some(sink) = &source.sink {
+            match paused {
+                true => sink.pause(),
+                false => sink.play(),
+            }
+        }
+    }
+}
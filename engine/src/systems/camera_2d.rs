@@ -3,12 +3,16 @@ use std::sync::{Arc, Mutex};
 
 use crate::{
     constants::{CAMERA_2D_BIND_GROUP_ID, ID},
-    renderer::uniform::{
-        generic::{GenericUniform, GenericUniformBuilder},
-        group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
-        Uniform,
+    renderer::{
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+        SCREEN_SIZE,
     },
     sources::camera::Camera2D,
+    systems::frame::FrameUniforms,
 };
 
 pub struct Camera2DUniformGroup {}
@@ -18,7 +22,8 @@ impl UniformGroupType<Self> for Camera2DUniformGroup {
         UniformGroup::<Camera2DUniformGroup>::builder()
             .with_uniform(GenericUniformBuilder::from_source(Camera2DUniforms {
                 view: [1.0, 1.0, 1.0, 1.0],
-                _padding: [0.0; 32],
+                pixel: [1.0, 1.0, 0.0, 0.0],
+                _padding: [0.0; 28],
                 __padding: [0.0; 28],
             }))
             .with_id(ID(CAMERA_2D_BIND_GROUP_ID))
@@ -28,8 +33,11 @@ impl UniformGroupType<Self> for Camera2DUniformGroup {
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Camera2DUniforms {
+    // [x, y, half width, half height] in world units; see Camera2D::half_extents
     pub view: [f32; 4],
-    pub _padding: [f32; 32],
+    // x: world units per physical pixel (zoomed), y: the window's scale factor
+    pub pixel: [f32; 4],
+    pub _padding: [f32; 28],
     pub __padding: [f32; 28],
 }
 
@@ -37,11 +45,23 @@ pub struct Camera2DUniforms {
 pub fn camera_2d(
     #[resource] camera: &Arc<Mutex<Camera2D>>,
     #[resource] camera_uniform: &Arc<Mutex<GenericUniform<Camera2DUniforms>>>,
+    #[resource] frame_uniform: &Arc<Mutex<GenericUniform<FrameUniforms>>>,
 ) {
-    let camera = camera.lock().unwrap();
+    let mut camera = camera.lock().unwrap();
     let mut camera_uniform = camera_uniform.lock().unwrap();
 
-    camera_uniform.mut_ref().view = [camera.pos.x, camera.pos.y, camera.size.x, camera.size.y];
+    let screen = *SCREEN_SIZE.read().unwrap();
+    camera.size = (screen.0 as f32, screen.1 as f32).into();
+    let [half_width, half_height] = camera.half_extents();
+    camera_uniform.mut_ref().view = [camera.pos.x, camera.pos.y, half_width, half_height];
+    camera_uniform.mut_ref().pixel = [
+        1.0 / (camera.pixels_per_unit() * camera.zoom).max(f32::EPSILON),
+        camera.scale_factor,
+        0.0,
+        0.0,
+    ];
+    frame_uniform.lock().unwrap().mut_ref().camera_pos =
+        [camera.pos.x, camera.pos.y, camera.zoom, 0.0];
 }
 
 // TODO: Make this a macro?
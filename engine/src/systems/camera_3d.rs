@@ -1,4 +1,4 @@
-use cgmath::{Angle, Deg, EuclideanSpace, Matrix2, Matrix3, Matrix4, SquareMatrix};
+use cgmath::{Angle, Deg, EuclideanSpace, Matrix2, Matrix3, Matrix4, Point3, SquareMatrix};
 use std::sync::{Arc, Mutex, RwLock};
 use winit_input_helper::WinitInputHelper;
 
@@ -12,6 +12,7 @@ use crate::{
         Uniform,
     },
     sources::{camera::Camera3D, ui::iced::IcedWinitHelper},
+    systems::frame::FrameUniforms,
 };
 
 pub struct Camera3DUniformGroup {}
@@ -38,69 +39,163 @@ pub struct Camera3DUniforms {
     pub clip: [f32; 2],
 }
 
+impl From<&Camera3D> for Camera3DUniforms {
+    fn from(camera: &Camera3D) -> Self {
+        let view_proj = camera.build_view_proj();
+        Self {
+            view_pos: [camera.pos.x, camera.pos.y, camera.pos.z, 0.0],
+            view_proj: matrix2array_4d(view_proj),
+            inv_view_proj: matrix2array_4d(view_proj.invert().unwrap()),
+            clip: camera.projection.clip(),
+        }
+    }
+}
+
+// Resource, read by camera_3d_system to move the Camera3D every frame. Picked with
+// EngineBuilder::with_camera_controller, and can be swapped at runtime.
+#[derive(Clone, Copy, Debug)]
+pub enum CameraController3D {
+    Fps(FpsController),
+    Orbit(OrbitController),
+    // Left to game systems
+    Fixed,
+}
+
+impl Default for CameraController3D {
+    fn default() -> Self {
+        CameraController3D::Fps(FpsController::default())
+    }
+}
+
+// Mouse look, WASD to move and scroll for altitude
+#[derive(Clone, Copy, Debug)]
+pub struct FpsController {
+    pub speed: f32,
+    pub sensitivity: f32,
+    pub scroll_sensitivity: f32,
+    // Only look and move while the right mouse button is held
+    pub right_click_move: bool,
+}
+
+impl Default for FpsController {
+    fn default() -> Self {
+        Self {
+            speed: 0.2,
+            sensitivity: 0.15,
+            scroll_sensitivity: 0.5,
+            right_click_move: false,
+        }
+    }
+}
+
+// Right mouse drag to circle around target, scroll to zoom
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    pub min_distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            sensitivity: 0.3,
+            zoom_sensitivity: 1.0,
+            min_distance: 0.5,
+        }
+    }
+}
+
 #[system]
 pub fn camera_3d(
     #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] controller: &mut CameraController3D,
     #[resource] camera_uniform: &Arc<Mutex<GenericUniform<Camera3DUniforms>>>,
     #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+    #[resource] frame_uniform: &Arc<Mutex<GenericUniform<FrameUniforms>>>,
 ) {
     let mut camera = camera.lock().unwrap();
     let mut camera_uniforms = camera_uniform.lock().unwrap();
     let input = input.read().unwrap();
 
-    info!("{}", input.mouse_held(1));
+    match controller {
+        CameraController3D::Fps(fps) => fps_controller(&mut camera, fps, &input),
+        CameraController3D::Orbit(orbit) => orbit_controller(&mut camera, orbit, &input),
+        CameraController3D::Fixed => (),
+    }
 
-    if (camera.right_click_move && input.mouse_held(1)) || (!camera.right_click_move) {
-        // Mouse movement
+    *camera_uniforms.mut_ref() = Camera3DUniforms::from(&*camera);
 
-        let (dx, dy) = if camera.first {
-            camera.first = false;
-            input.mouse().unwrap_or_default()
-        } else {
-            input.mouse_diff()
-        };
+    frame_uniform.lock().unwrap().mut_ref().camera_pos =
+        [camera.pos.x, camera.pos.y, camera.pos.z, 0.0];
+}
 
-        camera.yaw += dx * camera.sensitivity;
-        camera.pitch -= dy * camera.sensitivity;
-        if camera.pitch > 89.0 {
-            camera.pitch = 89.0;
-        } else if camera.pitch < -89.9 {
-            camera.pitch = -89.9;
-        }
+fn fps_controller(camera: &mut Camera3D, fps: &FpsController, input: &WinitInputHelper) {
+    if fps.right_click_move && !input.mouse_held(1) {
+        return;
+    }
 
-        camera.dir.x = Angle::cos(Deg(camera.yaw)) * Angle::cos(Deg(camera.pitch));
-        camera.dir.y = Angle::sin(Deg(camera.pitch));
-        camera.dir.z = Angle::sin(Deg(camera.yaw)) * Angle::cos(Deg(camera.pitch));
+    // Mouse movement
 
-        // WASD movement
+    let (dx, dy) = if camera.first {
+        camera.first = false;
+        input.mouse().unwrap_or_default()
+    } else {
+        input.mouse_diff()
+    };
 
-        if input.key_held(winit::event::VirtualKeyCode::W) {
-            let delta = (camera.dir * camera.speed).to_vec();
-            camera.pos += delta;
-        } else if input.key_held(winit::event::VirtualKeyCode::S) {
-            let delta = -(camera.dir * camera.speed).to_vec();
-            camera.pos += delta;
-        }
-        if input.key_held(winit::event::VirtualKeyCode::D) {
-            let delta = camera.dir.to_vec().cross(camera.up) * camera.speed;
-            camera.pos += delta;
-        } else if input.key_held(winit::event::VirtualKeyCode::A) {
-            let delta = -(camera.dir.to_vec().cross(camera.up) * camera.speed);
-            camera.pos += delta;
-        }
+    look(camera, dx * fps.sensitivity, -dy * fps.sensitivity);
 
-        // Scroll altitude
-        camera.pos.y += input.scroll_diff() * camera.scroll_sensitivity;
+    // WASD movement
+
+    if input.key_held(winit::event::VirtualKeyCode::W) {
+        let delta = (camera.dir * fps.speed).to_vec();
+        camera.pos += delta;
+    } else if input.key_held(winit::event::VirtualKeyCode::S) {
+        let delta = -(camera.dir * fps.speed).to_vec();
+        camera.pos += delta;
+    }
+    if input.key_held(winit::event::VirtualKeyCode::D) {
+        let delta = camera.dir.to_vec().cross(camera.up) * fps.speed;
+        camera.pos += delta;
+    } else if input.key_held(winit::event::VirtualKeyCode::A) {
+        let delta = -(camera.dir.to_vec().cross(camera.up) * fps.speed);
+        camera.pos += delta;
     }
 
-    // Camera matrices
-    let view_proj = camera.build_view_proj();
-    let inv_view_proj = view_proj.invert().unwrap();
+    // Scroll altitude
+    camera.pos.y += input.scroll_diff() * fps.scroll_sensitivity;
+}
+
+fn orbit_controller(camera: &mut Camera3D, orbit: &mut OrbitController, input: &WinitInputHelper) {
+    let (dx, dy) = match input.mouse_held(1) {
+        true => input.mouse_diff(),
+        false => (0.0, 0.0),
+    };
+    look(camera, dx * orbit.sensitivity, -dy * orbit.sensitivity);
+
+    orbit.distance =
+        (orbit.distance - input.scroll_diff() * orbit.zoom_sensitivity).max(orbit.min_distance);
+    camera.pos = orbit.target - camera.dir.to_vec() * orbit.distance;
+}
+
+// Turns the camera by yaw and pitch, in degrees
+fn look(camera: &mut Camera3D, yaw: f32, pitch: f32) {
+    camera.yaw += yaw;
+    camera.pitch += pitch;
+    if camera.pitch > 89.0 {
+        camera.pitch = 89.0;
+    } else if camera.pitch < -89.9 {
+        camera.pitch = -89.9;
+    }
 
-    camera_uniforms.mut_ref().view_pos = [camera.pos.x, camera.pos.y, camera.pos.z, 0.0];
-    camera_uniforms.mut_ref().view_proj = matrix2array_4d(view_proj);
-    camera_uniforms.mut_ref().inv_view_proj = matrix2array_4d(inv_view_proj);
-    camera_uniforms.mut_ref().clip = [0.01, 10000.0];
+    camera.dir.x = Angle::cos(Deg(camera.yaw)) * Angle::cos(Deg(camera.pitch));
+    camera.dir.y = Angle::sin(Deg(camera.pitch));
+    camera.dir.z = Angle::sin(Deg(camera.yaw)) * Angle::cos(Deg(camera.pitch));
 }
 
 // TODO: Make this a macro?
@@ -0,0 +1,327 @@
+use legion::{world::SubWorld, Entity, IntoQuery};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    components::{Position2D, Velocity2D},
+    sources::events::Events,
+};
+
+// Broadphase cell size, in world units. Colliders bigger than a cell are hashed into every
+// cell they overlap, so this only needs to be roughly the size of a typical collider.
+const CELL_SIZE: f32 = 64.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape2D {
+    Aabb { half_extents: [f32; 2] },
+    Circle { radius: f32 },
+}
+
+// Collision shape for an entity with a Position2D. Every overlapping pair raises a
+// CollisionEvent; kinematic colliders are also pushed out of whatever they hit (and lose
+// the Velocity2D pointing into it), while the others never move.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Collider2D {
+    pub shape: Shape2D,
+    // From the entity's position to the shape's center
+    pub offset: [f32; 2],
+    pub kinematic: bool,
+    // Events only, never resolved against
+    pub sensor: bool,
+}
+
+impl Collider2D {
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Self::new(Shape2D::Aabb {
+            half_extents: [width / 2.0, height / 2.0],
+        })
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::new(Shape2D::Circle { radius })
+    }
+
+    fn new(shape: Shape2D) -> Self {
+        Self {
+            shape,
+            offset: [0.0, 0.0],
+            kinematic: false,
+            sensor: false,
+        }
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = [x, y];
+        self
+    }
+
+    pub fn kinematic(mut self) -> Self {
+        self.kinematic = true;
+        self
+    }
+
+    pub fn sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    // Whether a world space point is inside the shape, for an entity at `position`
+    pub fn contains(&self, position: &Position2D, point: [f32; 2]) -> bool {
+        let d = [
+            point[0] - position.x - self.offset[0],
+            point[1] - position.y - self.offset[1],
+        ];
+        match self.shape {
+            Shape2D::Aabb { half_extents } => {
+                d[0].abs() <= half_extents[0] && d[1].abs() <= half_extents[1]
+            }
+            Shape2D::Circle { radius } => d[0] * d[0] + d[1] * d[1] <= radius * radius,
+        }
+    }
+
+    fn half_extents(&self) -> [f32; 2] {
+        match self.shape {
+            Shape2D::Aabb { half_extents } => half_extents,
+            Shape2D::Circle { radius } => [radius, radius],
+        }
+    }
+}
+
+// Read from Events<CollisionEvent>, sent by collision_2d_system
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity,
+    // Unit vector from a towards b
+    pub normal: [f32; 2],
+    pub depth: f32,
+}
+
+struct Body {
+    entity: Entity,
+    center: [f32; 2],
+    collider: Collider2D,
+}
+
+// The colliders from the last collision_2d pass, hashed by cell, for point queries (eg.
+// particles; see particle_2d::ParticleCollision2D). A resource.
+#[derive(Default)]
+pub struct Broadphase2D {
+    bodies: Vec<Body>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Broadphase2D {
+    // The first solid (non-sensor) collider containing point: its entity, the normal out of it
+    // and how deep point is
+    pub fn point(&self, point: [f32; 2]) -> Option<(Entity, [f32; 2], f32)> {
+        self.cells
+            .get(&(cell(point[0]), cell(point[1])))?
+            .iter()
+            .map(|&i| &self.bodies[i])
+            .filter(|body| !body.collider.sensor)
+            .find_map(|body| {
+                point_contact(body.center, &body.collider, point)
+                    .map(|(normal, depth)| (body.entity, normal, depth))
+            })
+    }
+}
+
+// (normal out of the collider towards point, depth) if point is inside one centered at center
+pub fn point_contact(
+    center: [f32; 2],
+    collider: &Collider2D,
+    point: [f32; 2],
+) -> Option<([f32; 2], f32)> {
+    contact(
+        (center, collider.shape),
+        (point, Shape2D::Circle { radius: 0.0 }),
+    )
+}
+
+#[system]
+#[read_component(Entity)]
+#[read_component(Collider2D)]
+#[write_component(Position2D)]
+#[write_component(Velocity2D)]
+pub fn collision_2d(
+    world: &mut SubWorld,
+    #[resource] collisions: &mut Events<CollisionEvent>,
+    #[resource] broadphase: &mut Broadphase2D,
+) {
+    debug!("running system collision_2d");
+
+    let bodies: Vec<Body> = <(Entity, &Collider2D, &Position2D)>::query()
+        .iter(world)
+        .map(|(entity, collider, position)| Body {
+            entity: *entity,
+            center: [
+                position.x + collider.offset[0],
+                position.y + collider.offset[1],
+            ],
+            collider: *collider,
+        })
+        .collect();
+
+    // Broadphase: spatial hash
+    let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (i, body) in bodies.iter().enumerate() {
+        let half = body.collider.half_extents();
+        for x in cell(body.center[0] - half[0])..=cell(body.center[0] + half[0]) {
+            for y in cell(body.center[1] - half[1])..=cell(body.center[1] + half[1]) {
+                cells.entry((x, y)).or_insert_with(Vec::new).push(i);
+            }
+        }
+    }
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    for cell in cells.values() {
+        for (n, &i) in cell.iter().enumerate() {
+            for &j in &cell[n + 1..] {
+                pairs.insert((i.min(j), i.max(j)));
+            }
+        }
+    }
+
+    // Narrowphase, collecting the corrections for kinematic bodies as we go
+    let mut corrections: HashMap<Entity, ([f32; 2], [f32; 2])> = HashMap::new();
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+    for (i, j) in pairs {
+        let (a, b) = (&bodies[i], &bodies[j]);
+        let (normal, depth) =
+            match contact((a.center, a.collider.shape), (b.center, b.collider.shape)) {
+                Some(contact) => contact,
+                None => continue,
+            };
+        collisions.send(CollisionEvent {
+            a: a.entity,
+            b: b.entity,
+            normal,
+            depth,
+        });
+
+        if a.collider.sensor || b.collider.sensor {
+            continue;
+        }
+        let share = match (a.collider.kinematic, b.collider.kinematic) {
+            (true, true) => (0.5, 0.5),
+            (true, false) => (1.0, 0.0),
+            (false, true) => (0.0, 1.0),
+            (false, false) => continue,
+        };
+        for (entity, amount) in [(a.entity, -share.0), (b.entity, share.1)] {
+            if amount == 0.0 {
+                continue;
+            }
+            let (offset, normals) = corrections.entry(entity).or_insert(([0.0; 2], [0.0; 2]));
+            offset[0] += normal[0] * depth * amount;
+            offset[1] += normal[1] * depth * amount;
+            // Direction it was pushed in, to cancel velocity against
+            normals[0] += normal[0] * amount.signum();
+            normals[1] += normal[1] * amount.signum();
+        }
+    }
+
+    *broadphase = Broadphase2D { bodies, cells };
+
+    for (entity, (offset, pushed)) in corrections {
+        let mut entry = match world.entry_mut(entity) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if let Ok(position) = entry.get_component_mut::<Position2D>() {
+            position.x += offset[0];
+            position.y += offset[1];
+        }
+        if let Ok(velocity) = entry.get_component_mut::<Velocity2D>() {
+            let length = (pushed[0] * pushed[0] + pushed[1] * pushed[1]).sqrt();
+            if length > 0.0 {
+                let normal = [pushed[0] / length, pushed[1] / length];
+                let into = velocity.vx * normal[0] + velocity.vy * normal[1];
+                if into < 0.0 {
+                    velocity.vx -= normal[0] * into;
+                    velocity.vy -= normal[1] * into;
+                }
+            }
+        }
+    }
+}
+
+fn cell(x: f32) -> i32 {
+    (x / CELL_SIZE).floor() as i32
+}
+
+// (normal from a to b, penetration depth) if the (center, shape)s overlap
+fn contact(a: ([f32; 2], Shape2D), b: ([f32; 2], Shape2D)) -> Option<([f32; 2], f32)> {
+    let (a_center, b_center) = (a.0, b.0);
+    match (a.1, b.1) {
+        (Shape2D::Aabb { half_extents: ha }, Shape2D::Aabb { half_extents: hb }) => {
+            let d = [b_center[0] - a_center[0], b_center[1] - a_center[1]];
+            let overlap = [ha[0] + hb[0] - d[0].abs(), ha[1] + hb[1] - d[1].abs()];
+            if overlap[0] <= 0.0 || overlap[1] <= 0.0 {
+                return None;
+            }
+            match overlap[0] < overlap[1] {
+                true => Some(([sign(d[0]), 0.0], overlap[0])),
+                false => Some(([0.0, sign(d[1])], overlap[1])),
+            }
+        }
+        (Shape2D::Circle { radius: ra }, Shape2D::Circle { radius: rb }) => {
+            let d = [b_center[0] - a_center[0], b_center[1] - a_center[1]];
+            let distance = (d[0] * d[0] + d[1] * d[1]).sqrt();
+            if distance >= ra + rb {
+                return None;
+            }
+            match distance > 0.0 {
+                true => Some(([d[0] / distance, d[1] / distance], ra + rb - distance)),
+                false => Some(([1.0, 0.0], ra + rb)),
+            }
+        }
+        (Shape2D::Aabb { half_extents }, Shape2D::Circle { radius }) => {
+            aabb_circle(a_center, half_extents, b_center, radius)
+        }
+        (Shape2D::Circle { radius }, Shape2D::Aabb { half_extents }) => {
+            aabb_circle(b_center, half_extents, a_center, radius)
+                .map(|(normal, depth)| ([-normal[0], -normal[1]], depth))
+        }
+    }
+}
+
+// Normal from the box towards the circle
+fn aabb_circle(
+    center: [f32; 2],
+    half: [f32; 2],
+    circle: [f32; 2],
+    radius: f32,
+) -> Option<([f32; 2], f32)> {
+    let d = [circle[0] - center[0], circle[1] - center[1]];
+    let closest = [
+        d[0].max(-half[0]).min(half[0]),
+        d[1].max(-half[1]).min(half[1]),
+    ];
+
+    // Circle center inside the box: out through the nearest edge
+    if closest == d {
+        let edge = [half[0] - d[0].abs(), half[1] - d[1].abs()];
+        return match edge[0] < edge[1] {
+            true => Some(([sign(d[0]), 0.0], edge[0] + radius)),
+            false => Some(([0.0, sign(d[1])], edge[1] + radius)),
+        };
+    }
+
+    let to_circle = [d[0] - closest[0], d[1] - closest[1]];
+    let distance = (to_circle[0] * to_circle[0] + to_circle[1] * to_circle[1]).sqrt();
+    if distance >= radius {
+        return None;
+    }
+    Some((
+        [to_circle[0] / distance, to_circle[1] / distance],
+        radius - distance,
+    ))
+}
+
+fn sign(x: f32) -> f32 {
+    match x < 0.0 {
+        true => -1.0,
+        false => 1.0,
+    }
+}
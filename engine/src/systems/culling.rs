@@ -0,0 +1,175 @@
+use cgmath::{Matrix4, Vector4};
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity, IntoQuery};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    components::Transform3D,
+    renderer::{
+        buffer::instance::InstanceGroup,
+        mesh::{Mesh, MeshBounds},
+        systems::render_3d::forward_instance::Render3DInstance,
+    },
+    sources::camera::Camera3D,
+    systems::animation::Skeleton,
+};
+
+// Local space bounding box of an entity's Mesh, added by frustum_culling_system to every
+// entity with a Mesh (and a Transform3D or an InstanceGroup<Render3DInstance>) on its first
+// frame. Skinned meshes move outside their bind pose, so they're never culled.
+//
+// forward_basic and forward_instance skip entities which aren't `visible`. Shadow maps
+// still draw them, since things off screen can cast shadows onto it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds3D {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    // Inside the camera's frustum last time culling ran
+    pub visible: bool,
+}
+
+impl Bounds3D {
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let stride = (mesh.vertices.len() / (mesh.vertex_buffer.size as usize).max(1)).max(3);
+        let bounds = MeshBounds::from_vertices(&mesh.vertices, stride);
+        Self {
+            min: bounds.min,
+            max: bounds.max,
+            visible: true,
+        }
+    }
+
+    // The box around this one once transformed; still axis aligned, so a bit bigger
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> ([f32; 3], [f32; 3]) {
+        let center = [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        ];
+        let extent = [
+            (self.max[0] - self.min[0]) / 2.0,
+            (self.max[1] - self.min[1]) / 2.0,
+            (self.max[2] - self.min[2]) / 2.0,
+        ];
+        let world = matrix * Vector4::new(center[0], center[1], center[2], 1.0);
+        let columns = [matrix.x, matrix.y, matrix.z];
+
+        let mut min = [0.0; 3];
+        let mut max = [0.0; 3];
+        for axis in 0..3 {
+            let reach: f32 = (0..3).map(|j| columns[j][axis].abs() * extent[j]).sum();
+            min[axis] = world[axis] - reach;
+            max[axis] = world[axis] + reach;
+        }
+        (min, max)
+    }
+}
+
+// Six planes (a, b, c, d) facing inwards, so a point is inside where ax + by + cz + d >= 0
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    // Gribb & Hartmann, for wgpu's [0, 1] depth range
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| -> [f32; 4] {
+            [
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            ]
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let add = |a: [f32; 4], b: [f32; 4]| [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]];
+        let sub = |a: [f32; 4], b: [f32; 4]| [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]];
+        Self {
+            planes: [
+                add(r3, r0),
+                sub(r3, r0),
+                add(r3, r1),
+                sub(r3, r1),
+                r2,
+                sub(r3, r2),
+            ],
+        }
+    }
+
+    // Conservative: boxes near a corner of the frustum can pass without being inside
+    pub fn intersects_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane's normal
+            let corner = [
+                if plane[0] >= 0.0 { max[0] } else { min[0] },
+                if plane[1] >= 0.0 { max[1] } else { min[1] },
+                if plane[2] >= 0.0 { max[2] } else { min[2] },
+            ];
+            plane[0] * corner[0] + plane[1] * corner[1] + plane[2] * corner[2] + plane[3] >= 0.0
+        })
+    }
+}
+
+// Same as the 3D render systems
+fn model_matrix(transform: &Transform3D) -> Matrix4<f32> {
+    Matrix4::from_translation(transform.position.into())
+        * Matrix4::from_angle_x(cgmath::Deg(transform.rotation[0]))
+        * Matrix4::from_angle_y(cgmath::Deg(transform.rotation[1]))
+        * Matrix4::from_angle_z(cgmath::Deg(transform.rotation[2]))
+        * Matrix4::from_nonuniform_scale(transform.scale[0], transform.scale[1], transform.scale[2])
+}
+
+fn instance_matrix(instance: &Render3DInstance) -> Matrix4<f32> {
+    Matrix4::from_cols(
+        instance.model_x.into(),
+        instance.model_y.into(),
+        instance.model_z.into(),
+        instance.model_w.into(),
+    )
+}
+
+#[system]
+#[read_component(Entity)]
+#[read_component(Mesh)]
+#[read_component(Transform3D)]
+#[read_component(Skeleton)]
+#[read_component(InstanceGroup<Render3DInstance>)]
+#[write_component(Bounds3D)]
+pub fn frustum_culling(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+) {
+    debug!("running system frustum_culling");
+
+    // New entities are drawn on their first frame, and culled from the next
+    let mut query = <(Entity, &Mesh)>::query().filter(
+        !component::<Bounds3D>()
+            & !component::<Skeleton>()
+            & (component::<Transform3D>() | component::<InstanceGroup<Render3DInstance>>()),
+    );
+    query.for_each(world, |(entity, mesh)| {
+        command_buffer.add_component(*entity, Bounds3D::from_mesh(mesh));
+    });
+
+    let frustum = Frustum::from_view_proj(&camera.lock().unwrap().build_view_proj());
+
+    let mut query = <(&mut Bounds3D, &Transform3D)>::query()
+        .filter(!component::<InstanceGroup<Render3DInstance>>());
+    par_for_each_mut!(query, world, |(bounds, transform)| {
+        let (min, max) = bounds.transformed(&model_matrix(transform));
+        bounds.visible = frustum.intersects_aabb(min, max);
+    });
+
+    // A group is drawn whole, so it's visible if any of its instances are. Instances with
+    // their own meshes (see InstanceGroup::push_with_mesh) aren't covered by the entity's
+    // bounds, so those groups are always drawn.
+    let mut query = <(&mut Bounds3D, &InstanceGroup<Render3DInstance>)>::query();
+    par_for_each_mut!(query, world, |(bounds, group)| {
+        bounds.visible = group.is_partitioned()
+            || group.instances.iter().any(|instance| {
+                let (min, max) = bounds.transformed(&instance_matrix(instance));
+                frustum.intersects_aabb(min, max)
+            });
+    });
+}
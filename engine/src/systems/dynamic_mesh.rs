@@ -0,0 +1,212 @@
+use legion::{world::SubWorld, IntoQuery};
+use std::{mem::size_of, ops::Range, sync::Arc};
+
+use crate::{
+    renderer::{
+        buffer::{upload::Uploader, IndexBuffer, Vertex3D, VertexBuffer, VERTEX3D_FLOATS},
+        mesh::Mesh,
+    },
+    systems::culling::Bounds3D,
+};
+
+// Geometry which changes at runtime (trails, ropes, editable terrain), kept on the CPU
+// next to the entity's Mesh. Edits mark the ranges they touch, and
+// dynamic_mesh_upload_system copies just those into the Mesh's buffers each frame.
+//
+// The Mesh comes from build_mesh, with buffers big enough for the capacity given to
+// with_capacity. Growing past it reallocates them at twice the size, which is a full upload.
+pub struct DynamicMesh {
+    name: String,
+    vertices: Vec<Vertex3D>,
+    indices: Vec<u32>,
+
+    // Since the last upload
+    dirty_vertices: Option<Range<usize>>,
+    dirty_indices: Option<Range<usize>>,
+
+    vertex_capacity: usize,
+    index_capacity: usize,
+}
+
+impl DynamicMesh {
+    pub fn new(name: &str, vertices: Vec<Vertex3D>, indices: Vec<u32>) -> Self {
+        Self {
+            name: name.to_owned(),
+            vertex_capacity: vertices.len(),
+            index_capacity: indices.len(),
+            vertices,
+            indices,
+            dirty_vertices: None,
+            dirty_indices: None,
+        }
+    }
+
+    pub fn with_capacity(mut self, vertices: usize, indices: usize) -> Self {
+        self.vertex_capacity = self.vertex_capacity.max(vertices);
+        self.index_capacity = self.index_capacity.max(indices);
+        self
+    }
+
+    // The Mesh component to add alongside this one
+    pub fn build_mesh(&mut self, device: &wgpu::Device) -> Mesh {
+        self.dirty_vertices = None;
+        self.dirty_indices = None;
+        Mesh {
+            vertex_buffer: VertexBuffer::dynamic_3d(
+                &self.name,
+                &self.vertices,
+                self.vertex_capacity,
+                device,
+            ),
+            index_buffer: IndexBuffer::dynamic(&self.indices, self.index_capacity, device),
+            vertices: Arc::new(bytemuck::cast_slice(&self.vertices).to_vec()),
+            indices: Arc::new(self.indices.clone()),
+            source: None,
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vertex3D] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn vertices_mut(&mut self, range: Range<usize>) -> &mut [Vertex3D] {
+        mark(&mut self.dirty_vertices, range.clone());
+        &mut self.vertices[range]
+    }
+
+    pub fn indices_mut(&mut self, range: Range<usize>) -> &mut [u32] {
+        mark(&mut self.dirty_indices, range.clone());
+        &mut self.indices[range]
+    }
+
+    pub fn push_vertices(&mut self, vertices: &[Vertex3D]) {
+        let start = self.vertices.len();
+        self.vertices.extend_from_slice(vertices);
+        mark(&mut self.dirty_vertices, start..self.vertices.len());
+    }
+
+    pub fn push_indices(&mut self, indices: &[u32]) {
+        let start = self.indices.len();
+        self.indices.extend_from_slice(indices);
+        mark(&mut self.dirty_indices, start..self.indices.len());
+    }
+
+    // Nothing past len is uploaded or drawn, but its buffer space is kept
+    pub fn truncate(&mut self, vertices: usize, indices: usize) {
+        if vertices < self.vertices.len() {
+            self.vertices.truncate(vertices);
+            mark(&mut self.dirty_vertices, vertices..vertices);
+        }
+        if indices < self.indices.len() {
+            self.indices.truncate(indices);
+            mark(&mut self.dirty_indices, indices..indices);
+        }
+    }
+
+    // Replaces everything, eg. a rope rebuilt from its points every frame
+    pub fn set(&mut self, vertices: &[Vertex3D], indices: &[u32]) {
+        self.truncate(0, 0);
+        self.push_vertices(vertices);
+        self.push_indices(indices);
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_vertices.is_some() || self.dirty_indices.is_some()
+    }
+
+    // Copies the dirty ranges into mesh and its buffers
+    fn upload(&mut self, mesh: &mut Mesh, device: &wgpu::Device, uploader: &Uploader) {
+        // Meshes are only ever shared when cloned from the registry, so this is just a
+        // guard against a DynamicMesh being put next to one of those
+        let unique = Arc::get_mut(&mut mesh.vertex_buffer.buffer).is_some()
+            && Arc::get_mut(&mut mesh.index_buffer.buffer).is_some();
+        if !unique
+            || self.vertices.len() > self.vertex_capacity
+            || self.indices.len() > self.index_capacity
+        {
+            if self.vertices.len() > self.vertex_capacity {
+                self.vertex_capacity = self.vertices.len().max(self.vertex_capacity * 2);
+            }
+            if self.indices.len() > self.index_capacity {
+                self.index_capacity = self.indices.len().max(self.index_capacity * 2);
+            }
+            debug!(
+                "reallocating dynamic mesh {} for {} vertices and {} indices",
+                self.name, self.vertex_capacity, self.index_capacity
+            );
+            *mesh = self.build_mesh(device);
+            return;
+        }
+
+        if let Some(range) = self.dirty_vertices.take() {
+            let range = range.start.min(self.vertices.len())..range.end.min(self.vertices.len());
+            uploader.write(
+                &mesh.vertex_buffer.buffer.0,
+                (range.start * size_of::<Vertex3D>()) as u64,
+                bytemuck::cast_slice(&self.vertices[range.clone()]),
+            );
+            // Copied first if shared
+            let vertices = Arc::make_mut(&mut mesh.vertices);
+            vertices.resize(self.vertices.len() * VERTEX3D_FLOATS, 0.0);
+            vertices[range.start * VERTEX3D_FLOATS..range.end * VERTEX3D_FLOATS]
+                .copy_from_slice(bytemuck::cast_slice(&self.vertices[range]));
+
+            let count = self.vertices.len() as u32;
+            Arc::get_mut(&mut mesh.vertex_buffer.buffer).unwrap().1 = count;
+            mesh.vertex_buffer.size = count;
+        }
+
+        if let Some(range) = self.dirty_indices.take() {
+            let range = range.start.min(self.indices.len())..range.end.min(self.indices.len());
+            uploader.write(
+                &mesh.index_buffer.buffer.0,
+                (range.start * size_of::<u32>()) as u64,
+                bytemuck::cast_slice(&self.indices[range.clone()]),
+            );
+            let indices = Arc::make_mut(&mut mesh.indices);
+            indices.resize(self.indices.len(), 0);
+            indices[range.clone()].copy_from_slice(&self.indices[range]);
+
+            let count = self.indices.len() as u32;
+            Arc::get_mut(&mut mesh.index_buffer.buffer).unwrap().1 = count;
+            mesh.index_buffer.size = count;
+        }
+    }
+}
+
+fn mark(dirty: &mut Option<Range<usize>>, range: Range<usize>) {
+    *dirty = Some(match dirty.take() {
+        Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+        None => range,
+    });
+}
+
+// Runs before frustum_culling_system, which uses the refreshed Bounds3D the same frame
+#[system]
+#[write_component(DynamicMesh)]
+#[write_component(Mesh)]
+#[write_component(Bounds3D)]
+pub fn dynamic_mesh_upload(
+    world: &mut SubWorld,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+) {
+    debug!("running system dynamic_mesh_upload");
+    let mut query = <(&mut DynamicMesh, &mut Mesh, Option<&mut Bounds3D>)>::query();
+    query.for_each_mut(world, |(dynamic, mesh, bounds)| {
+        if !dynamic.is_dirty() {
+            return;
+        }
+        dynamic.upload(mesh, device, uploader);
+        if let Some(bounds) = bounds {
+            *bounds = Bounds3D {
+                visible: bounds.visible,
+                ..Bounds3D::from_mesh(mesh)
+            };
+        }
+    });
+}
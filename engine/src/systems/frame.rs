@@ -0,0 +1,99 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{
+    components::FrameMetrics,
+    constants::{FRAME_BIND_GROUP_ID, ID},
+    renderer::{
+        graph::scale_size,
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+        SCREEN_SIZE,
+    },
+    sources::display::DisplayQueue,
+};
+
+// The frame uniform group is owned by the engine, and is built before any render graph node.
+// Any node can bind it with NodeBuilder::with_frame_uniforms(), and its system should set
+// node.binder.uniform_groups[&ID(FRAME_BIND_GROUP_ID)] at the matching bind group index.
+//
+// WGSL:
+//
+// struct FrameUniforms {
+//     time: f32;
+//     delta: f32;
+//     frame: u32;
+//     resolution: vec2<f32>;
+//     inv_resolution: vec2<f32>;
+//     camera_pos: vec4<f32>;
+// };
+
+pub struct FrameUniformGroup {}
+
+impl UniformGroupType<Self> for FrameUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        let screen_size = SCREEN_SIZE.read().unwrap();
+        UniformGroup::<FrameUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(FrameUniforms {
+                time: 0.0,
+                delta: 0.0,
+                frame: 0,
+                _padding: 0.0,
+                resolution: [screen_size.0 as f32, screen_size.1 as f32],
+                inv_resolution: [1.0 / screen_size.0 as f32, 1.0 / screen_size.1 as f32],
+                camera_pos: Default::default(),
+            }))
+            .with_id(ID(FRAME_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FrameUniforms {
+    pub time: f32,
+    pub delta: f32,
+    pub frame: u32,
+    pub _padding: f32,
+    pub resolution: [f32; 2],
+    pub inv_resolution: [f32; 2],
+    // 3D: [x, y, z, 0], 2D: [x, y, zoom, 0]
+    pub camera_pos: [f32; 4],
+}
+
+#[system]
+pub fn frame(
+    #[resource] frame_metrics: &Arc<RwLock<FrameMetrics>>,
+    #[resource] frame_uniform: &Arc<Mutex<GenericUniform<FrameUniforms>>>,
+    #[resource] display: &DisplayQueue,
+) {
+    debug!("running system frame_uniform_loader");
+    let frame_metrics = frame_metrics.read().unwrap();
+    let mut frame_uniform = frame_uniform.lock().unwrap();
+    // What node targets render at (the master is always at the screen size, and nodes with a
+    // target scale of their own scale it again)
+    let render_size = scale_size(
+        *SCREEN_SIZE.read().unwrap(),
+        display.settings().render_scale,
+    );
+
+    let uniforms = frame_uniform.mut_ref();
+    uniforms.time = frame_metrics.elapsed().as_secs_f32();
+    uniforms.delta = frame_metrics.delta().as_secs_f32();
+    uniforms.frame = frame_metrics.frame() as u32;
+    uniforms.resolution = [render_size.0 as f32, render_size.1 as f32];
+    uniforms.inv_resolution = [1.0 / render_size.0 as f32, 1.0 / render_size.1 as f32];
+}
+
+#[system]
+pub fn frame_uniform(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] frame_uniform: &Arc<Mutex<GenericUniform<FrameUniforms>>>,
+    #[resource] frame_uniform_group: &Arc<Mutex<UniformGroup<FrameUniformGroup>>>,
+) {
+    frame_uniform.lock().unwrap().write_buffer(
+        &queue,
+        frame_uniform_group.lock().unwrap().default_buffer(0),
+    );
+}
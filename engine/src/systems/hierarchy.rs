@@ -0,0 +1,119 @@
+use legion::{
+    storage::Component, systems::CommandBuffer, world::SubWorld, Entity, EntityStore, IntoQuery,
+};
+use std::collections::HashMap;
+
+use crate::components::{Children, Local, Parent, Position2D, Relative, Transform3D};
+
+// Writes children's Transform3Ds from their parents' and their LocalTransform3Ds. At the start
+// of the uniform load stage, after the game and physics have moved things.
+#[system]
+#[read_component(Parent)]
+#[write_component(Children)]
+#[write_component(Transform3D)]
+#[write_component(Local<Transform3D>)]
+pub fn transform_propagation_3d(world: &mut SubWorld, commands: &mut CommandBuffer) {
+    debug!("running system transform_propagation_3d");
+    let parents = sync_children(world, commands);
+    propagate::<Transform3D>(world, &parents);
+}
+
+// Same for Position2Ds, from LocalPosition2Ds
+#[system]
+#[read_component(Parent)]
+#[write_component(Children)]
+#[write_component(Position2D)]
+#[write_component(Local<Position2D>)]
+pub fn transform_propagation_2d(world: &mut SubWorld, commands: &mut CommandBuffer) {
+    debug!("running system transform_propagation_2d");
+    let parents = sync_children(world, commands);
+    propagate::<Position2D>(world, &parents);
+}
+
+// (child, parent) for every Parent, parents first, so children see their parent's transform
+// from this frame. Children in a cycle are left out, with a warning.
+fn sync_children(world: &mut SubWorld, commands: &mut CommandBuffer) -> Vec<(Entity, Entity)> {
+    let pairs: Vec<(Entity, Entity)> = <(Entity, &Parent)>::query()
+        .iter(world)
+        .map(|(child, parent)| (*child, parent.0))
+        .collect();
+    let parent_of: HashMap<Entity, Entity> = pairs.iter().copied().collect();
+
+    let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (child, parent) in &pairs {
+        children.entry(*parent).or_default().push(*child);
+    }
+    for (entity, existing) in <(Entity, &mut Children)>::query().iter_mut(world) {
+        let current = children.remove(entity).unwrap_or_default();
+        if existing.0 != current {
+            existing.0 = current;
+        }
+    }
+    for (parent, list) in children {
+        if world.entry_ref(parent).is_ok() {
+            commands.add_component(parent, Children(list));
+        }
+    }
+
+    let depth = |mut entity: Entity| -> Option<usize> {
+        let mut depth = 0;
+        while let Some(parent) = parent_of.get(&entity) {
+            depth += 1;
+            if depth > parent_of.len() {
+                return None;
+            }
+            entity = *parent;
+        }
+        Some(depth)
+    };
+    let mut ordered: Vec<(usize, Entity, Entity)> = pairs
+        .iter()
+        .filter_map(|(child, parent)| match depth(*child) {
+            Some(depth) => Some((depth, *child, *parent)),
+            None => {
+                warn!("{:?} is its own ancestor, leaving it where it is", child);
+                None
+            }
+        })
+        .collect();
+    // Stable, so siblings keep their query order
+    ordered.sort_by_key(|(depth, ..)| *depth);
+    ordered
+        .into_iter()
+        .map(|(_, child, parent)| (child, parent))
+        .collect()
+}
+
+fn propagate<T: Relative + Component>(world: &mut SubWorld, parents: &[(Entity, Entity)]) {
+    for (child, parent) in parents {
+        let parent_transform = match world
+            .entry_ref(*parent)
+            .ok()
+            .and_then(|entry| entry.get_component::<T>().ok().copied())
+        {
+            Some(transform) => transform,
+            // Despawned, or nothing to be relative to
+            None => continue,
+        };
+        let mut entry = match world.entry_mut(*child) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let transform = match entry
+            .get_component::<Local<T>>()
+            .ok()
+            .and_then(|local| local.pending(&parent_transform))
+        {
+            Some(transform) => transform,
+            None => continue,
+        };
+        match entry.get_component_mut::<T>() {
+            Ok(current) => *current = transform,
+            // Nothing to write to yet
+            Err(_) => continue,
+        }
+        if let Ok(local) = entry.get_component_mut::<Local<T>>() {
+            local.applied(&parent_transform);
+        }
+    }
+}
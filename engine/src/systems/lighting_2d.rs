@@ -10,8 +10,16 @@ use crate::{
         group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
         Uniform,
     },
+    systems::collision_2d::{Collider2D, Shape2D},
 };
 
+// Light2Ds past this many are ignored; the uniforms have a slot for each
+pub const MAX_LIGHTS_2D: usize = 5;
+
+// Occluder2Ds past this many are ignored. Every shadowed light marches all of them per pixel,
+// so keep scenes well under it.
+pub const MAX_OCCLUDERS_2D: usize = 32;
+
 pub struct Lighting2DUniformGroup {}
 
 impl UniformGroupType<Self> for Lighting2DUniformGroup {
@@ -24,6 +32,9 @@ impl UniformGroupType<Self> for Lighting2DUniformGroup {
                 light_3: Default::default(),
                 light_4: Default::default(),
                 global: [0.1, 1.0, 1.0, 1.0],
+                shadows: [[0.0; 4]; MAX_LIGHTS_2D],
+                count: [0; 4],
+                occluders: [Occluder2DUniform::default(); MAX_OCCLUDERS_2D],
             }))
             .with_id(ID(LIGHTING_2D_BIND_GROUP_ID))
     }
@@ -38,39 +49,161 @@ pub struct Lighting2DUniforms {
     pub light_3: [f32; 4],
     pub light_4: [f32; 4],
     pub global: [f32; 4],
+    // Per light: x is 0 unshadowed, 1 hard, 2 soft, and y the soft shadow's radius
+    pub shadows: [[f32; 4]; MAX_LIGHTS_2D],
+    // x lights, y occluders
+    pub count: [u32; 4],
+    pub occluders: [Occluder2DUniform; MAX_OCCLUDERS_2D],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Occluder2DUniform {
+    // [x, y, half width, half height] for boxes, [x, y, radius, 0] for circles
+    pub bounds: [f32; 4],
+    // x is the shape: 0 box, 1 circle
+    pub kind: [u32; 4],
 }
 
+// Point light at its entity's Position2D. Lighting only applies once there's at least one
+// (unlit otherwise), at the ambient level (Lighting2DUniforms::global.x) plus each light's.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Light2D {
     pub linear: f32,
     pub quadratic: f32,
+    pub shadow: Shadow2D,
+}
+
+impl Light2D {
+    pub fn new(linear: f32, quadratic: f32) -> Self {
+        Self {
+            linear,
+            quadratic,
+            shadow: Shadow2D::None,
+        }
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow2D) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+// Shadows cast by Occluder2Ds, worked out per pixel from their distance field in the 2D
+// lighting shaders; see shaders/include/lighting_2d.wgsl
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shadow2D {
+    None,
+    Hard,
+    // Penumbrae as if the light were a disc of this radius, in world units
+    Soft { radius: f32 },
+}
+
+impl Shadow2D {
+    fn uniform(&self) -> [f32; 4] {
+        match *self {
+            Shadow2D::None => [0.0; 4],
+            Shadow2D::Hard => [1.0, 0.0, 0.0, 0.0],
+            Shadow2D::Soft { radius } => [2.0, radius, 0.0, 0.0],
+        }
+    }
+}
+
+// Blocks Light2Ds with shadows, as a shape around its entity's Position2D. Entities with a
+// Collider2D can use the same shape (from_collider).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Occluder2D {
+    pub shape: Shape2D,
+    // From the entity's position to the shape's center
+    pub offset: [f32; 2],
+}
+
+impl Occluder2D {
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Self {
+            shape: Shape2D::Aabb {
+                half_extents: [width / 2.0, height / 2.0],
+            },
+            offset: [0.0, 0.0],
+        }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self {
+            shape: Shape2D::Circle { radius },
+            offset: [0.0, 0.0],
+        }
+    }
+
+    pub fn from_collider(collider: &Collider2D) -> Self {
+        Self {
+            shape: collider.shape,
+            offset: collider.offset,
+        }
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = [x, y];
+        self
+    }
+
+    fn uniform(&self, pos: &Position2D) -> Occluder2DUniform {
+        let (x, y) = (pos.x + self.offset[0], pos.y + self.offset[1]);
+        match self.shape {
+            Shape2D::Aabb { half_extents } => Occluder2DUniform {
+                bounds: [x, y, half_extents[0], half_extents[1]],
+                kind: [0; 4],
+            },
+            Shape2D::Circle { radius } => Occluder2DUniform {
+                bounds: [x, y, radius, 0.0],
+                kind: [1, 0, 0, 0],
+            },
+        }
+    }
 }
 
 #[system]
 #[read_component(Light2D)]
+#[read_component(Occluder2D)]
 #[read_component(Position2D)]
 pub fn lighting_2d(
     world: &mut SubWorld,
     #[resource] lighting_2d_uniforms: &Arc<Mutex<GenericUniform<Lighting2DUniforms>>>,
 ) {
-    let mut forms = lighting_2d_uniforms.lock().unwrap();
+    // Slots without a light are zeroed, which the shaders skip
+    let mut lights = [[0.0; 4]; MAX_LIGHTS_2D];
+    let mut shadows = [[0.0; 4]; MAX_LIGHTS_2D];
+    let mut num_lights = 0;
     let mut query = <(&Light2D, &Position2D)>::query();
-    let mut i = 0;
-    for (light, pos) in query.iter_mut(world) {
-        let flat = [pos.x, pos.y, light.linear, light.quadratic];
-        match i {
-            0 => forms.mut_ref().light_0 = flat,
-            1 => forms.mut_ref().light_1 = flat,
-            2 => forms.mut_ref().light_2 = flat,
-            3 => forms.mut_ref().light_3 = flat,
-            4 => forms.mut_ref().light_4 = flat,
-            _ => {}
-        }
-        i += 1;
-        if i == 5 {
+    for (i, (light, pos)) in query.iter(world).take(MAX_LIGHTS_2D).enumerate() {
+        lights[i] = [pos.x, pos.y, light.linear, light.quadratic];
+        shadows[i] = light.shadow.uniform();
+        num_lights = i + 1;
+    }
+
+    let mut forms = lighting_2d_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.light_0 = lights[0];
+    uniforms.light_1 = lights[1];
+    uniforms.light_2 = lights[2];
+    uniforms.light_3 = lights[3];
+    uniforms.light_4 = lights[4];
+    uniforms.shadows = shadows;
+
+    let mut count = 0;
+    let mut query = <(&Occluder2D, &Position2D)>::query();
+    for (occluder, pos) in query.iter(world) {
+        if count == MAX_OCCLUDERS_2D {
+            warn!(
+                "more than {} Occluder2Ds, ignoring the rest",
+                MAX_OCCLUDERS_2D
+            );
             break;
         }
+        uniforms.occluders[count] = occluder.uniform(pos);
+        count += 1;
     }
+    uniforms.count = [num_lights as u32, count as u32, 0, 0];
 }
 
 #[system]
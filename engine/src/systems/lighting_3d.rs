@@ -0,0 +1,262 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use std::sync::{Arc, Mutex};
+
+use legion::{world::SubWorld, IntoQuery};
+
+use crate::{
+    components::Transform3D,
+    constants::{ID, IDENTITY_MATRIX_4, LIGHTING_3D_BIND_GROUP_ID, OPENGL_TO_WGPU_MATRIX},
+    renderer::{
+        graph::target::CubeTarget,
+        uniform::{
+            generic::{GenericUniform, GenericUniformBuilder},
+            group::{UniformGroup, UniformGroupBuilder, UniformGroupType},
+            Uniform,
+        },
+    },
+    systems::camera_3d::matrix2array_4d,
+};
+
+// Light3Ds past this many are ignored; keep in sync with the array in the 3D shaders
+pub const MAX_LIGHTS_3D: usize = 16;
+// Of the point shadow map's faces; see Light3D::with_shadows
+pub const POINT_SHADOW_NEAR: f32 = 0.05;
+
+pub struct Lighting3DUniformGroup {}
+
+impl UniformGroupType<Self> for Lighting3DUniformGroup {
+    fn builder() -> UniformGroupBuilder<Self> {
+        UniformGroup::<Lighting3DUniformGroup>::builder()
+            .with_uniform(GenericUniformBuilder::from_source(Lighting3DUniforms {
+                light_view_proj: IDENTITY_MATRIX_4,
+                direction: [0.0, -0.3, 1.0, 0.0],
+                color: [0.5, 0.5, 0.5, 1.0],
+                count: [0; 4],
+                lights: [Light3DUniform::default(); MAX_LIGHTS_3D],
+                point_shadow_view_proj: [IDENTITY_MATRIX_4; 6],
+                point_shadow: [0.0; 4],
+            }))
+            .with_id(ID(LIGHTING_3D_BIND_GROUP_ID))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Lighting3DUniforms {
+    // World space to the shadow map's clip space
+    pub light_view_proj: [[f32; 4]; 4],
+    pub direction: [f32; 4],
+    pub color: [f32; 4],
+    // x: how many of lights are set; y: 1 + the index of the one casting point shadows, or 0
+    pub count: [u32; 4],
+    pub lights: [Light3DUniform; MAX_LIGHTS_3D],
+    // World space to each face of the point shadow map's clip space, in cube order (see
+    // graph::target::CubeTarget::view_projs)
+    pub point_shadow_view_proj: [[[f32; 4]; 4]; 6],
+    // xyz: the light's position; w: the far plane of the faces, its range
+    pub point_shadow: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Light3DUniform {
+    // w is the kind: 0 directional, 1 point, 2 spot
+    pub position: [f32; 4],
+    // w is the range
+    pub direction: [f32; 4],
+    // a is the intensity
+    pub color: [f32; 4],
+    // Cosines of the spot's inner and outer angles
+    pub cone: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light3DKind {
+    Directional,
+    Point,
+    // Half angles in degrees; full intensity inside inner, fading to nothing at outer
+    Spot { inner: f32, outer: f32 },
+}
+
+// Lights on top of the DirectionalLight3D. Point and spot lights sit at their entity's
+// Transform3D (or the origin without one). Unshadowed, but for one point light; see
+// with_shadows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light3D {
+    pub kind: Light3DKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // Point and spot lights fade out completely by this distance
+    pub range: f32,
+    // The way the light shines, for directional and spot lights
+    pub direction: [f32; 3],
+    pub shadows: bool,
+}
+
+impl Light3D {
+    pub fn directional(direction: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            kind: Light3DKind::Directional,
+            color,
+            intensity: 1.0,
+            range: 0.0,
+            direction,
+            shadows: false,
+        }
+    }
+
+    pub fn point(color: [f32; 3], range: f32) -> Self {
+        Self {
+            kind: Light3DKind::Point,
+            color,
+            intensity: 1.0,
+            range,
+            direction: [0.0, -1.0, 0.0],
+            shadows: false,
+        }
+    }
+
+    pub fn spot(direction: [f32; 3], color: [f32; 3], range: f32, inner: f32, outer: f32) -> Self {
+        Self {
+            kind: Light3DKind::Spot { inner, outer },
+            color,
+            intensity: 1.0,
+            range,
+            direction,
+            shadows: false,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // Point lights only: casts shadows in every direction, out to its range, from a depth
+    // cube drawn by default_3d. Only the first point light with shadows gets them; the 3D
+    // forward node (Render3D) is the one that shows them.
+    pub fn with_shadows(mut self) -> Self {
+        self.shadows = true;
+        self
+    }
+
+    pub fn uniform(&self, position: [f32; 3]) -> Light3DUniform {
+        let (kind, cone) = match self.kind {
+            Light3DKind::Directional => (0.0, [0.0; 4]),
+            Light3DKind::Point => (1.0, [0.0; 4]),
+            Light3DKind::Spot { inner, outer } => (
+                2.0,
+                [inner.to_radians().cos(), outer.to_radians().cos(), 0.0, 0.0],
+            ),
+        };
+        let direction = Vector3::from(self.direction).normalize();
+        Light3DUniform {
+            position: [position[0], position[1], position[2], kind],
+            direction: [direction.x, direction.y, direction.z, self.range],
+            color: [self.color[0], self.color[1], self.color[2], self.intensity],
+            cone,
+        }
+    }
+}
+
+// The sun. Only the first one found is used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectionalLight3D {
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+
+    // The shadow map covers a box of extent x extent around center
+    pub center: [f32; 3],
+    pub extent: f32,
+}
+
+impl Default for DirectionalLight3D {
+    fn default() -> Self {
+        Self {
+            direction: [0.0, -0.3, 1.0],
+            color: [0.5, 0.5, 0.5],
+            center: [0.0, 0.0, 0.0],
+            extent: 50.0,
+        }
+    }
+}
+
+impl DirectionalLight3D {
+    pub fn view_proj(&self) -> cgmath::Matrix4<f32> {
+        let direction = Vector3::from(self.direction).normalize();
+        let center = Point3::from(self.center);
+        let eye = center - direction * self.extent;
+
+        // look_at breaks down when looking straight up or down
+        let up = match direction.y.abs() > 0.99 {
+            true => Vector3::unit_z(),
+            false => Vector3::unit_y(),
+        };
+
+        let half = self.extent / 2.0;
+        let view = cgmath::Matrix4::look_at_rh(eye, center, up);
+        let proj = cgmath::ortho(-half, half, -half, half, 0.1, self.extent * 2.0);
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+#[system]
+#[read_component(DirectionalLight3D)]
+#[read_component(Light3D)]
+#[read_component(Transform3D)]
+pub fn lighting_3d(
+    world: &mut SubWorld,
+    #[resource] lighting_3d_uniforms: &Arc<Mutex<GenericUniform<Lighting3DUniforms>>>,
+) {
+    let mut query = <&DirectionalLight3D>::query();
+    let light = match query.iter(world).next() {
+        Some(light) => light.clone(),
+        None => DirectionalLight3D::default(),
+    };
+
+    let mut forms = lighting_3d_uniforms.lock().unwrap();
+    let uniforms = forms.mut_ref();
+    uniforms.light_view_proj = matrix2array_4d(light.view_proj());
+    uniforms.direction = [
+        light.direction[0],
+        light.direction[1],
+        light.direction[2],
+        0.0,
+    ];
+    uniforms.color = [light.color[0], light.color[1], light.color[2], 1.0];
+
+    let mut count = 0;
+    let mut shadowed = 0;
+    let mut query = <(&Light3D, Option<&Transform3D>)>::query();
+    for (light, transform) in query.iter(world) {
+        if count == MAX_LIGHTS_3D {
+            warn!("more than {} Light3Ds, ignoring the rest", MAX_LIGHTS_3D);
+            break;
+        }
+        let position = transform.map_or([0.0; 3], |transform| transform.position);
+        uniforms.lights[count] = light.uniform(position);
+        count += 1;
+
+        if shadowed == 0 && light.shadows && light.kind == Light3DKind::Point {
+            shadowed = count;
+            let view_projs = CubeTarget::view_projs(position, POINT_SHADOW_NEAR, light.range);
+            for (face, view_proj) in view_projs.iter().enumerate() {
+                uniforms.point_shadow_view_proj[face] = matrix2array_4d(*view_proj);
+            }
+            uniforms.point_shadow = [position[0], position[1], position[2], light.range];
+        }
+    }
+    uniforms.count = [count as u32, shadowed as u32, 0, 0];
+}
+
+#[system]
+pub fn lighting_3d_uniform(
+    #[resource] queue: &Arc<wgpu::Queue>,
+    #[resource] lighting_uniforms: &Arc<Mutex<GenericUniform<Lighting3DUniforms>>>,
+    #[resource] lighting_uniforms_group: &Arc<Mutex<UniformGroup<Lighting3DUniformGroup>>>,
+) {
+    lighting_uniforms.lock().unwrap().write_buffer(
+        &queue,
+        lighting_uniforms_group.lock().unwrap().default_buffer(0),
+    );
+}
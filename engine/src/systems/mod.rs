@@ -1,6 +1,19 @@
+pub mod animation;
+pub mod animation_2d;
+pub mod audio;
 pub mod camera_2d;
 pub mod camera_3d;
+pub mod collision_2d;
+pub mod culling;
+pub mod dynamic_mesh;
+pub mod frame;
+pub mod hierarchy;
 pub mod lighting_2d;
+pub mod lighting_3d;
+pub mod names;
 pub mod particle_2d;
+pub mod particle_3d;
 pub mod physics_2d;
 pub mod physics_3d;
+pub mod picking;
+pub mod viewport;
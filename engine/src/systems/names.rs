@@ -0,0 +1,18 @@
+use legion::{world::SubWorld, Entity, IntoQuery};
+use std::sync::{Arc, RwLock};
+
+use crate::{components::Name, sources::names::NameIndex};
+
+// Rebuilt from scratch so that despawned or renamed entities drop out
+#[system]
+#[read_component(Name)]
+pub fn name_index(world: &mut SubWorld, #[resource] names: &Arc<RwLock<NameIndex>>) {
+    debug!("running system name_index");
+    let mut names = names.write().unwrap();
+    names.clear();
+
+    let mut query = <(Entity, &Name)>::query();
+    for (entity, name) in query.iter(world) {
+        names.insert(*entity, &name.0);
+    }
+}
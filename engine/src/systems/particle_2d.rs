@@ -1,18 +1,20 @@
 use cgmath::{Angle, InnerSpace};
 use legion::{world::SubWorld, IntoQuery, World};
-use rand::Rng;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rand::{Rng, RngCore};
 use std::{
     ops::{Add, Mul, Sub},
-    sync::{Arc, Mutex, RwLock},
+    sync::{Arc, Mutex},
 };
 use uuid::Uuid;
 
 use crate::{
-    components::{FrameMetrics, ParticleMutator2D},
+    components::ParticleMutator2D,
+    parallel::*,
     renderer::{
         buffer::instance::InstanceGroup, systems::render_2d::forward_instance::Render2DInstance,
     },
+    sources::{rng::EngineRng, time::Time},
+    systems::collision_2d::{point_contact, Broadphase2D, Collider2D},
 };
 
 pub struct ParticleSystem2D {
@@ -23,9 +25,105 @@ pub struct ParticleSystem2D {
     pub emitters: Vec<Arc<Mutex<ParticleEmitter2D>>>,
 
     pub lifetime: f32,
-    pub scale: Interpolator<SmoothF32x2>,
-    pub speed: Interpolator<SmoothF32x2>,
-    pub color: Interpolator<SmoothF32x4>,
+    pub scale: ParticleCurve<SmoothF32x2>,
+    pub speed: ParticleCurve<SmoothF32x2>,
+    pub color: ParticleCurve<SmoothF32x4>,
+
+    // None to pass through everything
+    pub collision: Option<ParticleCollision2D>,
+}
+
+// What live particles collide with, as points, and what happens when they do
+pub struct ParticleCollision2D {
+    // Every solid Collider2D, through the Broadphase2D
+    pub world: bool,
+    // Colliders which needn't be entities, eg. the ground, as (position, collider)
+    pub bounds: Vec<([f32; 2], Collider2D)>,
+    pub response: ParticleHit2D,
+}
+
+pub enum ParticleHit2D {
+    // Out of the collider, reflected, losing damping (0 to 1) of the particle's speed
+    Bounce {
+        damping: f32,
+    },
+    Die,
+    // Dies, bursting the emitter (eg. one with no rate, in another system) where it hit
+    SubEmit {
+        emitter: Arc<Mutex<ParticleEmitter2D>>,
+        count: u32,
+    },
+}
+
+impl ParticleCollision2D {
+    pub fn new(response: ParticleHit2D) -> Self {
+        Self {
+            world: true,
+            bounds: vec![],
+            response,
+        }
+    }
+
+    pub fn with_bounds(mut self, position: [f32; 2], collider: Collider2D) -> Self {
+        self.bounds.push((position, collider));
+        self
+    }
+
+    // Only the bounds
+    pub fn without_world(mut self) -> Self {
+        self.world = false;
+        self
+    }
+
+    // (normal out of what point is in, depth)
+    fn contact(&self, broadphase: &Broadphase2D, point: [f32; 2]) -> Option<([f32; 2], f32)> {
+        let hit = self.bounds.iter().find_map(|(position, collider)| {
+            let center = [
+                position[0] + collider.offset[0],
+                position[1] + collider.offset[1],
+            ];
+            point_contact(center, collider, point)
+        });
+        match hit {
+            Some(hit) => Some(hit),
+            None if self.world => broadphase
+                .point(point)
+                .map(|(_, normal, depth)| (normal, depth)),
+            None => None,
+        }
+    }
+
+    // Whether the particle died
+    fn collide(&self, broadphase: &Broadphase2D, mutator: &mut ParticleMutator2D) -> bool {
+        let motion = &mut mutator.motion;
+        let position = motion.transform.position;
+        let (normal, depth) = match self.contact(broadphase, position) {
+            Some(contact) => contact,
+            None => return false,
+        };
+
+        match &self.response {
+            ParticleHit2D::Bounce { damping } => {
+                motion.transform.position = [
+                    position[0] + normal[0] * depth,
+                    position[1] + normal[1] * depth,
+                ];
+                let velocity = &mut motion.velocity;
+                let into = velocity.vx * normal[0] + velocity.vy * normal[1];
+                if into < 0.0 {
+                    let keep = 1.0 - damping.clamp(0.0, 1.0);
+                    velocity.vx = (velocity.vx - 2.0 * into * normal[0]) * keep;
+                    velocity.vy = (velocity.vy - 2.0 * into * normal[1]) * keep;
+                }
+                false
+            }
+            ParticleHit2D::Die => true,
+            ParticleHit2D::SubEmit { emitter, count } => {
+                emitter.lock().unwrap().emit_burst_at(position, *count);
+                true
+            }
+        }
+    }
 }
 
 impl Default for ParticleSystem2D {
@@ -43,9 +141,9 @@ impl Default for ParticleSystem2D {
 impl ParticleSystem2D {
     pub fn new(
         lifetime: f32,
-        speed: Interpolator<SmoothF32x2>,
-        scale: Interpolator<SmoothF32x2>,
-        color: Interpolator<SmoothF32x4>,
+        speed: impl Into<ParticleCurve<SmoothF32x2>>,
+        scale: impl Into<ParticleCurve<SmoothF32x2>>,
+        color: impl Into<ParticleCurve<SmoothF32x4>>,
         num_particles: u32,
         emitters: Vec<ParticleEmitter2D>,
     ) -> Self {
@@ -54,18 +152,19 @@ impl ParticleSystem2D {
             mutators: vec![],
             id: Uuid::new_v4(),
             num_particles,
+            collision: None,
             lifetime,
-            speed,
-            scale,
-            color,
+            speed: speed.into(),
+            scale: scale.into(),
+            color: color.into(),
         }
     }
 
     pub fn new_empty(
         lifetime: f32,
-        speed: Interpolator<SmoothF32x2>,
-        scale: Interpolator<SmoothF32x2>,
-        color: Interpolator<SmoothF32x4>,
+        speed: impl Into<ParticleCurve<SmoothF32x2>>,
+        scale: impl Into<ParticleCurve<SmoothF32x2>>,
+        color: impl Into<ParticleCurve<SmoothF32x4>>,
         num_particles: u32,
     ) -> Self {
         Self::new(lifetime, speed, scale, color, num_particles, vec![])
@@ -73,9 +172,9 @@ impl ParticleSystem2D {
 
     pub fn from_emitters(
         lifetime: f32,
-        speed: Interpolator<SmoothF32x2>,
-        scale: Interpolator<SmoothF32x2>,
-        color: Interpolator<SmoothF32x4>,
+        speed: impl Into<ParticleCurve<SmoothF32x2>>,
+        scale: impl Into<ParticleCurve<SmoothF32x2>>,
+        color: impl Into<ParticleCurve<SmoothF32x4>>,
         num_particles: u32,
         emitters: Vec<ParticleEmitter2D>,
     ) -> Self {
@@ -85,10 +184,16 @@ impl ParticleSystem2D {
     pub fn push(&mut self, emitter: ParticleEmitter2D) {
         self.emitters.push(Arc::new(Mutex::new(emitter)));
     }
+
+    pub fn with_collision(mut self, collision: ParticleCollision2D) -> Self {
+        self.collision = Some(collision);
+        self
+    }
 }
 
 pub fn init_particle_systems(world: &mut World) {
-    <(&mut ParticleSystem2D, &mut InstanceGroup<Render2DInstance>)>::query().par_for_each_mut(
+    par_for_each_mut!(
+        <(&mut ParticleSystem2D, &mut InstanceGroup<Render2DInstance>)>::query(),
         world,
         |(system, group)| {
             for _ in 0..system.num_particles {
@@ -103,10 +208,17 @@ pub fn init_particle_systems(world: &mut World) {
 pub enum EmitterShape {
     Line { end: [f32; 2], reverse: bool },
     Arc { radius: [f32; 2], angle: f32 },
+    // The outline of a rectangle centered on the emitter, launching outwards, counter-clockwise
+    // from its bottom left corner
+    Rectangle { size: [f32; 2] },
+    // Anywhere between two circles around the emitter, launching outwards
+    Ring { inner: f32, outer: f32 },
+    // From the emitter, within spread degrees of direction
+    Point { direction: [f32; 2], spread: f32 },
 }
 
 impl Shape2D for EmitterShape {
-    fn parametric(&self, t: f32, pos: [f32; 2]) -> [[f32; 2]; 2] {
+    fn parametric(&self, t: f32, pos: [f32; 2], rng: &mut dyn RngCore) -> [[f32; 2]; 2] {
         match &self {
             EmitterShape::Line { end, reverse } => {
                 let dx = end[0] - pos[0];
@@ -128,12 +240,38 @@ impl Shape2D for EmitterShape {
                     [dir.x, dir.y],
                 ]
             }
+            EmitterShape::Rectangle { size } => {
+                let [w, h] = *size;
+                let [left, bottom] = [pos[0] - w / 2.0, pos[1] - h / 2.0];
+                let p = t * 2.0 * (w + h);
+                if p < w {
+                    [[left + p, bottom], [0.0, -1.0]]
+                } else if p < w + h {
+                    [[left + w, bottom + p - w], [1.0, 0.0]]
+                } else if p < 2.0 * w + h {
+                    [[left + w - (p - w - h), bottom + h], [0.0, 1.0]]
+                } else {
+                    [[left, bottom + h - (p - 2.0 * w - h)], [-1.0, 0.0]]
+                }
+            }
+            EmitterShape::Ring { inner, outer } => {
+                let radius = inner + (outer - inner) * rng.gen::<f32>();
+                let cos = Angle::cos(cgmath::Deg(t * 360.0));
+                let sin = Angle::sin(cgmath::Deg(t * 360.0));
+                [[pos[0] + cos * radius, pos[1] + sin * radius], [cos, sin]]
+            }
+            EmitterShape::Point { direction, spread } => {
+                let angle = cgmath::Rad(direction[1].atan2(direction[0]))
+                    + cgmath::Rad::from(cgmath::Deg((t - 0.5) * spread));
+                [pos, [Angle::cos(angle), Angle::sin(angle)]]
+            }
         }
     }
 }
 
 pub trait Shape2D {
-    fn parametric(&self, t: f32, pos: [f32; 2]) -> [[f32; 2]; 2];
+    // Draws from rng for anything t doesn't cover (eg. a Ring's radius)
+    fn parametric(&self, t: f32, pos: [f32; 2], rng: &mut dyn RngCore) -> [[f32; 2]; 2];
 }
 
 pub enum EmitterMode {
@@ -142,21 +280,25 @@ pub enum EmitterMode {
 }
 
 impl EmitterMode {
-    pub fn emit(&mut self, shape: &EmitterShape, pos: [f32; 2], zones: u32) -> [[f32; 2]; 2] {
+    pub fn emit(
+        &mut self,
+        shape: &EmitterShape,
+        pos: [f32; 2],
+        zones: u32,
+        rng: &mut dyn RngCore,
+    ) -> [[f32; 2]; 2] {
         match self {
             EmitterMode::Random => {
                 if zones > 0 {
-                    shape.parametric(
-                        ((rand::thread_rng().gen::<f32>() * (zones as f32)) as u32) as f32
-                            / (zones as f32),
-                        pos,
-                    )
+                    let t = ((rng.gen::<f32>() * (zones as f32)) as u32) as f32 / (zones as f32);
+                    shape.parametric(t, pos, rng)
                 } else {
-                    shape.parametric(rand::thread_rng().gen(), pos)
+                    let t = rng.gen();
+                    shape.parametric(t, pos, rng)
                 }
             }
             EmitterMode::Direction { next, reverse } => {
-                let out = shape.parametric(*next as f32 / zones as f32, pos);
+                let out = shape.parametric(*next as f32 / zones as f32, pos, rng);
                 if *reverse {
                     if *next == 0 {
                         *next = zones;
@@ -182,16 +324,137 @@ pub struct ParticleEmitter2D {
     pub shape: EmitterShape,
     pub mode: EmitterMode,
     pub zones: u32,
+    // Particles per frame, while emitting
     pub rate: u32,
     pub launch_freq: f32,
+    // Particles per unit the emitter moves, while emitting (eg. trails)
+    pub distance_rate: f32,
+    // Particles at the start of every cycle
+    pub burst: u32,
+    // Seconds before the first cycle starts
+    pub start_delay: f32,
+    // Seconds per cycle (None for one endless cycle); emitters stop after their first cycle
+    // unless looping
+    pub duration: Option<f32>,
+    pub looping: bool,
+    pub progress: EmitterProgress,
+}
+
+// Where an emitter is in its cycles; see ParticleEmitter2D::restart
+#[derive(Default)]
+pub struct EmitterProgress {
+    elapsed: f32,
+    // The last cycle to have started, for bursts
+    cycle: Option<u32>,
+    bursts: u32,
+    // From emit_burst_at; only ParticleSystem2D emits these, other systems ignore them
+    bursts_at: Vec<([f32; 2], u32)>,
+    distance: f32,
+    last_position: Option<[f32; 2]>,
 }
 
 impl ParticleEmitter2D {
-    pub fn emit(&mut self, _delta: f32) -> Vec<[[f32; 2]; 2]> {
-        (0..self.rate)
-            .into_iter()
-            .map(|_| self.mode.emit(&self.shape, self.position, self.zones))
-            .collect()
+    pub fn with_shape(mut self, shape: EmitterShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    pub fn with_distance_rate(mut self, distance_rate: f32) -> Self {
+        self.distance_rate = distance_rate;
+        self
+    }
+
+    pub fn with_start_delay(mut self, start_delay: f32) -> Self {
+        self.start_delay = start_delay;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: f32, looping: bool) -> Self {
+        self.duration = Some(duration);
+        self.looping = looping;
+        self
+    }
+
+    // Emitted on the next frame along with everything else, whether or not the emitter is
+    // in a cycle
+    pub fn emit_burst(&mut self, count: u32) {
+        self.progress.bursts += count;
+    }
+
+    // Like emit_burst, but from position instead of the emitter's
+    pub fn emit_burst_at(&mut self, position: [f32; 2], count: u32) {
+        self.progress.bursts_at.push((position, count));
+    }
+
+    // Back to before the start delay
+    pub fn restart(&mut self) {
+        self.progress = EmitterProgress::default();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match self.duration {
+            Some(duration) => !self.looping && self.progress.elapsed >= self.start_delay + duration,
+            None => false,
+        }
+    }
+
+    pub fn emit(&mut self, delta: f32, rng: &mut dyn RngCore) -> Vec<[[f32; 2]; 2]> {
+        let bursts_at = std::mem::take(&mut self.progress.bursts_at);
+        let count = self.advance(delta);
+        let mut emitted: Vec<[[f32; 2]; 2]> = (0..count)
+            .map(|_| self.mode.emit(&self.shape, self.position, self.zones, rng))
+            .collect();
+        for (position, count) in bursts_at {
+            emitted
+                .extend((0..count).map(|_| self.mode.emit(&self.shape, position, self.zones, rng)));
+        }
+        emitted
+    }
+
+    // How many particles to emit this frame
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        self.progress.elapsed += delta;
+        let cycle = self.cycle();
+
+        let moved = match self.progress.last_position {
+            Some([x, y]) => {
+                ((self.position[0] - x).powi(2) + (self.position[1] - y).powi(2)).sqrt()
+            }
+            None => 0.0,
+        };
+        self.progress.last_position = Some(self.position);
+
+        let mut count = std::mem::take(&mut self.progress.bursts);
+        if let Some(cycle) = cycle {
+            count += self.rate;
+            if self.progress.cycle != Some(cycle) {
+                self.progress.cycle = Some(cycle);
+                count += self.burst;
+            }
+            self.progress.distance += moved * self.distance_rate;
+            let trail = self.progress.distance.floor();
+            self.progress.distance -= trail;
+            count += trail as u32;
+        }
+        count
+    }
+
+    // Which cycle the emitter is in, if it's emitting
+    fn cycle(&self) -> Option<u32> {
+        let t = self.progress.elapsed - self.start_delay;
+        if t < 0.0 {
+            return None;
+        }
+        match self.duration {
+            Some(duration) if self.looping => Some((t / duration.max(0.0001)) as u32),
+            Some(duration) if t >= duration => None,
+            _ => Some(0),
+        }
     }
 }
 
@@ -207,6 +470,12 @@ impl Default for ParticleEmitter2D {
             rate: 10,
             mode: EmitterMode::Random,
             launch_freq: 10.0,
+            distance_rate: 0.0,
+            burst: 0,
+            start_delay: 0.0,
+            duration: None,
+            looping: false,
+            progress: EmitterProgress::default(),
         }
     }
 }
@@ -216,24 +485,29 @@ impl Default for ParticleEmitter2D {
 #[write_component(InstanceGroup<Render2DInstance>)]
 pub fn particle_2d_emission(
     world: &mut SubWorld,
-    #[resource] frame_metrics: &Arc<RwLock<FrameMetrics>>,
+    #[resource] time: &Time,
+    #[resource] rng: &mut EngineRng,
+    #[resource] broadphase: &Broadphase2D,
 ) {
-    let delta = frame_metrics.read().unwrap().delta().as_secs_f32();
-    <(&mut ParticleSystem2D, &mut InstanceGroup<Render2DInstance>)>::query().par_for_each_mut(
-        world,
-        |(system, group)| {
-            let emitted: Arc<Mutex<Vec<[[f32; 2]; 2]>>> = Arc::new(Mutex::new(
-                system
-                    .emitters
-                    .iter()
-                    .map(|emitter| emitter.lock().unwrap().emit(delta))
-                    .flatten()
-                    .collect(),
-            ));
-
-            let launch_speed = system.speed.initial().0;
-            let launch_scale = system.scale.initial().0;
-            let launch_color = system.color.initial().0;
+    let delta = time.delta_secs();
+    // A generator per system, forked in query order
+    let systems: Vec<_> = <(&mut ParticleSystem2D, &mut InstanceGroup<Render2DInstance>)>::query()
+        .iter_mut(world)
+        .map(|entry| (entry, rng.fork()))
+        .collect();
+    systems
+        .into_par_iter()
+        .for_each(|((system, group), mut rng)| {
+            let emitted: Vec<[[f32; 2]; 2]> = system
+                .emitters
+                .iter()
+                .map(|emitter| emitter.lock().unwrap().emit(delta, &mut rng))
+                .flatten()
+                .collect();
+            let mut emitted: Vec<([[f32; 2]; 2], f32)> = emitted
+                .into_iter()
+                .map(|pos_dir| (pos_dir, rng.gen()))
+                .collect();
 
             // - update active particles
             // - deactivate expired particles
@@ -244,31 +518,47 @@ pub fn particle_2d_emission(
                 .enumerate()
                 .for_each(|(i, instance)| {
                     let mut mutator = system.mutators[i].lock().unwrap();
+                    let alive = mutator.lifetime >= 0.0 && mutator.lifetime <= system.lifetime;
+                    if let Some(collision) = system.collision.as_ref().filter(|_| alive) {
+                        if collision.collide(broadphase, &mut mutator) {
+                            mutator.reset();
+                        }
+                    }
                     // mutate active particles
                     if mutator.lifetime >= 0.0 && mutator.lifetime <= system.lifetime {
-                        let t = mutator.lifetime / system.lifetime;
-                        instance.color = system.color.linear(t).0;
-                        mutator.motion.transform.scale = system.scale.linear(t).0;
-                        mutator.motion.speed = system.speed.linear(t).0;
+                        let (t, variation) =
+                            (mutator.lifetime / system.lifetime, mutator.variation);
+                        instance.color = system.color.sample(t, variation).0;
+                        mutator.motion.transform.scale = system.scale.sample(t, variation).0;
+                        mutator.motion.speed = system.speed.sample(t, variation).0;
                     // recycle expired particles
-                    } else {
-                        if mutator.lifetime > system.lifetime {
-                            mutator.reset();
-                        }
-                        if mutator.lifetime == -1.0 {
-                            let mut emitted = emitted.lock().unwrap();
-                            let range = emitted.len().saturating_sub(1)..;
-                            let next = emitted.drain(range).next_back();
-                            drop(emitted);
-                            if let Some(pos_dir) = next {
-                                mutator.launch(pos_dir[0], pos_dir[1], launch_scale, launch_speed);
-                                instance.color = launch_color;
-                            }
-                        }
+                    } else if mutator.lifetime > system.lifetime {
+                        mutator.reset();
                     }
                 });
-        },
-    );
+
+            // Launched in instance order, so which particle gets which launch doesn't depend
+            // on the threads
+            for (instance, mutator) in group.instances.iter_mut().zip(&system.mutators) {
+                if emitted.is_empty() {
+                    break;
+                }
+                let mut mutator = mutator.lock().unwrap();
+                if mutator.lifetime != -1.0 {
+                    continue;
+                }
+                if let Some((pos_dir, variation)) = emitted.pop() {
+                    mutator.launch(
+                        pos_dir[0],
+                        pos_dir[1],
+                        system.scale.sample(0.0, variation).0,
+                        system.speed.sample(0.0, variation).0,
+                    );
+                    mutator.variation = variation;
+                    instance.color = system.color.sample(0.0, variation).0;
+                }
+            }
+        });
 }
 
 pub trait Quantity:
@@ -309,12 +599,167 @@ where
     }
 }
 
-#[derive(Clone, Copy, Add, Sub, Mul)]
-pub struct SmoothF32(f32);
+// How a curve gets to a keyframe from the one before it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    In,
+    Out,
+    InOut,
+    // Holds the previous value, then jumps
+    Step,
+}
+
+impl Ease {
+    pub fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Ease::Linear => t,
+            Ease::In => t * t,
+            Ease::Out => t * (2.0 - t),
+            Ease::InOut => t * t * (3.0 - 2.0 * t),
+            Ease::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe<T: Quantity> {
+    pub time: f32,
+    pub value: T,
+    pub ease: Ease,
+}
+
+// Keyframes over a particle's life, from 0 to 1, eg. fading in, holding, then fading out:
+//
+// Curve::new([1.0, 1.0, 1.0, 0.0])
+//     .key(0.2, [1.0, 1.0, 1.0, 1.0], Ease::Out)
+//     .key(0.8, [1.0, 1.0, 1.0, 1.0], Ease::Linear)
+//     .key(1.0, [1.0, 1.0, 1.0, 0.0], Ease::In)
+//
+// Before the first key and after the last one, curves hold their values.
+#[derive(Clone)]
+pub struct Curve<T: Quantity> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T> Curve<T>
+where
+    T: Quantity,
+{
+    // Starts at value
+    pub fn new<I: Into<T>>(value: I) -> Self {
+        Self {
+            keys: vec![Keyframe {
+                time: 0.0,
+                value: value.into(),
+                ease: Ease::Linear,
+            }],
+        }
+    }
+
+    // Kept in time order; keys at the same time as an existing one go after it, for jumps
+    pub fn key<I: Into<T>>(mut self, time: f32, value: I, ease: Ease) -> Self {
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(
+            index,
+            Keyframe {
+                time,
+                value: value.into(),
+                ease,
+            },
+        );
+        self
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    pub fn sample(&self, t: f32) -> T {
+        let next = self.keys.partition_point(|key| key.time <= t);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].value;
+        }
+        let (from, to) = (&self.keys[next - 1], &self.keys[next]);
+        let param = (t - from.time) / (to.time - from.time);
+        from.value + (to.value - from.value) * to.ease.apply(param)
+    }
+
+    pub fn initial(&self) -> T {
+        self.keys[0].value
+    }
+
+    pub fn target(&self) -> T {
+        self.keys[self.keys.len() - 1].value
+    }
+}
+
+impl<T> From<Interpolator<T>> for Curve<T>
+where
+    T: Quantity,
+{
+    fn from(interpolator: Interpolator<T>) -> Self {
+        Curve::new(interpolator.from).key(1.0, interpolator.to, Ease::Linear)
+    }
+}
+
+// A particle system's scale, speed or color: one curve for every particle, or each particle
+// somewhere between two, picked at launch
+#[derive(Clone)]
+pub enum ParticleCurve<T: Quantity> {
+    Curve(Curve<T>),
+    Between(Curve<T>, Curve<T>),
+}
+
+impl<T> ParticleCurve<T>
+where
+    T: Quantity,
+{
+    // variation from 0 (the first curve) to 1 (the second)
+    pub fn sample(&self, t: f32, variation: f32) -> T {
+        match self {
+            ParticleCurve::Curve(curve) => curve.sample(t),
+            ParticleCurve::Between(from, to) => {
+                let from = from.sample(t);
+                from + (to.sample(t) - from) * variation
+            }
+        }
+    }
+}
+
+impl<T> From<Curve<T>> for ParticleCurve<T>
+where
+    T: Quantity,
+{
+    fn from(curve: Curve<T>) -> Self {
+        ParticleCurve::Curve(curve)
+    }
+}
+
+impl<T> From<Interpolator<T>> for ParticleCurve<T>
+where
+    T: Quantity,
+{
+    fn from(interpolator: Interpolator<T>) -> Self {
+        ParticleCurve::Curve(interpolator.into())
+    }
+}
+
+#[derive(Clone, Copy, Add, Sub, Mul, From)]
+pub struct SmoothF32(pub f32);
 impl Quantity for SmoothF32 {}
 
 #[derive(Clone, Copy, From)]
-pub struct SmoothF32x2([f32; 2]);
+pub struct SmoothF32x2(pub [f32; 2]);
 impl Add for SmoothF32x2 {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
@@ -336,7 +781,7 @@ impl Mul<f32> for SmoothF32x2 {
 impl Quantity for SmoothF32x2 {}
 
 #[derive(Clone, Copy, From)]
-pub struct SmoothF32x4([f32; 4]);
+pub struct SmoothF32x4(pub [f32; 4]);
 impl Add for SmoothF32x4 {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
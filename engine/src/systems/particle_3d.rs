@@ -0,0 +1,308 @@
+use cgmath::{InnerSpace, Vector3};
+use legion::{world::SubWorld, IntoQuery, World};
+use rand::{Rng, RngCore};
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    parallel::*,
+    renderer::{
+        buffer::instance::InstanceGroup, systems::render_3d::forward_instance::Render3DInstance,
+    },
+    sources::{camera::Camera3D, rng::EngineRng, time::Time},
+    systems::particle_2d::{Interpolator, ParticleCurve, SmoothF32, SmoothF32x2, SmoothF32x4},
+};
+
+// Camera facing quads, drawn by the forward_instance node. Give the entity an
+// InstanceGroup<Render3DInstance> and the "unit_quad" mesh, eg.
+// engine.clone_mesh(engine.mesh("unit_quad").unwrap()).
+pub struct ParticleSystem3D {
+    particles: Vec<Particle3D>,
+    pub num_particles: u32,
+
+    pub emitters: Vec<Arc<Mutex<ParticleEmitter3D>>>,
+
+    pub lifetime: f32,
+    // Billboard [width, height]
+    pub scale: ParticleCurve<SmoothF32x2>,
+    // Units per second
+    pub speed: ParticleCurve<SmoothF32>,
+    pub color: ParticleCurve<SmoothF32x4>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle3D {
+    position: Vector3<f32>,
+    // Unit length; scaled by the system's speed
+    direction: Vector3<f32>,
+    // -1 when dead
+    lifetime: f32,
+    // See ParticleCurve::sample
+    variation: f32,
+}
+
+impl Default for Particle3D {
+    fn default() -> Self {
+        Self {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 0.0, 0.0),
+            lifetime: -1.0,
+            variation: 0.0,
+        }
+    }
+}
+
+impl Default for ParticleSystem3D {
+    fn default() -> Self {
+        Self::new(
+            3.0,
+            Interpolator::<SmoothF32>::new(2.0, 0.5),
+            Interpolator::<SmoothF32x2>::new([0.2, 0.2], [0.0, 0.0]),
+            Interpolator::<SmoothF32x4>::new([1.0, 1.0, 0.0, 1.0], [1.0, 0.0, 1.0, 1.0]),
+            2000,
+            vec![],
+        )
+    }
+}
+
+impl ParticleSystem3D {
+    pub fn new(
+        lifetime: f32,
+        speed: impl Into<ParticleCurve<SmoothF32>>,
+        scale: impl Into<ParticleCurve<SmoothF32x2>>,
+        color: impl Into<ParticleCurve<SmoothF32x4>>,
+        num_particles: u32,
+        emitters: Vec<ParticleEmitter3D>,
+    ) -> Self {
+        Self {
+            emitters: emitters.into_iter().map(Mutex::new).map(Arc::new).collect(),
+            particles: vec![],
+            num_particles,
+            lifetime,
+            speed: speed.into(),
+            scale: scale.into(),
+            color: color.into(),
+        }
+    }
+
+    pub fn push(&mut self, emitter: ParticleEmitter3D) {
+        self.emitters.push(Arc::new(Mutex::new(emitter)));
+    }
+}
+
+pub fn init_particle_systems_3d(world: &mut World) {
+    par_for_each_mut!(
+        <(&mut ParticleSystem3D, &mut InstanceGroup<Render3DInstance>)>::query(),
+        world,
+        |(system, group)| {
+            for _ in 0..system.num_particles {
+                system.particles.push(Particle3D::default());
+                group.push(hidden_instance(), vec![]);
+            }
+        },
+    );
+}
+
+pub enum EmitterShape3D {
+    // From the surface, outwards
+    Sphere {
+        radius: f32,
+    },
+    // From the tip, within angle (degrees) of the direction
+    Cone {
+        direction: [f32; 3],
+        angle: f32,
+    },
+    // From anywhere inside, towards the direction
+    Box {
+        half_extents: [f32; 3],
+        direction: [f32; 3],
+    },
+}
+
+impl EmitterShape3D {
+    // [position, direction]
+    pub fn emit(&self, pos: Vector3<f32>, rng: &mut dyn RngCore) -> (Vector3<f32>, Vector3<f32>) {
+        match self {
+            EmitterShape3D::Sphere { radius } => {
+                let dir = random_direction(rng);
+                (pos + dir * *radius, dir)
+            }
+            EmitterShape3D::Cone { direction, angle } => {
+                let axis = Vector3::from(*direction).normalize();
+                let helper = if axis.y.abs() < 0.99 {
+                    Vector3::unit_y()
+                } else {
+                    Vector3::unit_x()
+                };
+                let u = helper.cross(axis).normalize();
+                let v = axis.cross(u);
+
+                let cos_theta = rng.gen_range(angle.to_radians().cos()..=1.0);
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = rng.gen_range(0.0..2.0 * PI);
+                let dir = u * sin_theta * phi.cos() + v * sin_theta * phi.sin() + axis * cos_theta;
+                (pos, dir)
+            }
+            EmitterShape3D::Box {
+                half_extents,
+                direction,
+            } => {
+                let offset = Vector3::new(
+                    rng.gen_range(-1.0..=1.0) * half_extents[0],
+                    rng.gen_range(-1.0..=1.0) * half_extents[1],
+                    rng.gen_range(-1.0..=1.0) * half_extents[2],
+                );
+                let direction = Vector3::from(*direction);
+                let dir = if direction.magnitude2() > 0.0 {
+                    direction.normalize()
+                } else {
+                    random_direction(rng)
+                };
+                (pos + offset, dir)
+            }
+        }
+    }
+}
+
+fn random_direction(rng: &mut dyn RngCore) -> Vector3<f32> {
+    let z: f32 = rng.gen_range(-1.0..=1.0);
+    let phi: f32 = rng.gen_range(0.0..2.0 * PI);
+    let r = (1.0 - z * z).sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub struct ParticleEmitter3D {
+    pub position: [f32; 3],
+    pub shape: EmitterShape3D,
+    // Particles per frame
+    pub rate: u32,
+}
+
+impl ParticleEmitter3D {
+    pub fn emit(&mut self, rng: &mut dyn RngCore) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        (0..self.rate)
+            .map(|_| self.shape.emit(self.position.into(), rng))
+            .collect()
+    }
+}
+
+impl Default for ParticleEmitter3D {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            shape: EmitterShape3D::Sphere { radius: 0.0 },
+            rate: 10,
+        }
+    }
+}
+
+#[system]
+#[write_component(ParticleSystem3D)]
+pub fn particle_3d_emission(
+    world: &mut SubWorld,
+    #[resource] time: &Time,
+    #[resource] rng: &mut EngineRng,
+) {
+    let delta = time.delta_secs();
+    // A generator per system, forked in query order
+    let systems: Vec<_> = <&mut ParticleSystem3D>::query()
+        .iter_mut(world)
+        .map(|system| (system, rng.fork()))
+        .collect();
+    systems.into_par_iter().for_each(|(system, mut rng)| {
+        let mut emitted: Vec<(Vector3<f32>, Vector3<f32>)> = system
+            .emitters
+            .iter()
+            .map(|emitter| emitter.lock().unwrap().emit(&mut rng))
+            .flatten()
+            .collect();
+
+        let lifetime = system.lifetime;
+        let speed = &system.speed;
+        for particle in system.particles.iter_mut() {
+            if particle.lifetime > lifetime {
+                particle.lifetime = -1.0;
+            }
+            if particle.lifetime < 0.0 {
+                match emitted.pop() {
+                    Some((position, direction)) => {
+                        particle.position = position;
+                        particle.direction = direction;
+                        particle.lifetime = 0.0;
+                        particle.variation = rng.gen();
+                    }
+                    None => continue,
+                }
+            }
+
+            let t = particle.lifetime / lifetime;
+            particle.position += particle.direction * speed.sample(t, particle.variation).0 * delta;
+            particle.lifetime += delta;
+        }
+    });
+}
+
+// Faces every live particle towards the camera and sorts them back to front. Runs even
+// while paused, so particles stay facing a moving camera.
+#[system]
+#[read_component(ParticleSystem3D)]
+#[write_component(InstanceGroup<Render3DInstance>)]
+pub fn particle_3d_billboard(world: &mut SubWorld, #[resource] camera: &Arc<Mutex<Camera3D>>) {
+    let camera = camera.lock().unwrap();
+    let view = camera.build_view();
+    let right = Vector3::new(view.x.x, view.y.x, view.z.x);
+    let up = Vector3::new(view.x.y, view.y.y, view.z.y);
+    // Towards the camera, so the quad's front face is the one in view
+    let back = Vector3::new(view.x.z, view.y.z, view.z.z);
+    let camera_pos = Vector3::new(camera.pos.x, camera.pos.y, camera.pos.z);
+    drop(camera);
+
+    par_for_each_mut!(
+        <(&ParticleSystem3D, &mut InstanceGroup<Render3DInstance>)>::query(),
+        world,
+        |(system, group)| {
+            let mut alive: Vec<(f32, &Particle3D)> = system
+                .particles
+                .iter()
+                .filter(|particle| particle.lifetime >= 0.0)
+                .map(|particle| ((particle.position - camera_pos).dot(-back), particle))
+                .collect();
+            alive.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            let mut alive = alive.into_iter().map(|(_, particle)| particle);
+            for instance in group.instances.iter_mut() {
+                let particle = match alive.next() {
+                    Some(particle) => particle,
+                    None => {
+                        let (group_id, id) = (instance.group_id, instance.id);
+                        *instance = hidden_instance();
+                        instance.group_id = group_id;
+                        instance.id = id;
+                        continue;
+                    }
+                };
+
+                let t = (particle.lifetime / system.lifetime).min(1.0);
+                let scale = system.scale.sample(t, particle.variation).0;
+                instance.model_x = (right * scale[0]).extend(0.0).into();
+                instance.model_y = (up * scale[1]).extend(0.0).into();
+                instance.model_z = back.extend(0.0).into();
+                instance.model_w = particle.position.extend(1.0).into();
+                instance.color = system.color.sample(t, particle.variation).0;
+                instance.mix = 0.0;
+            }
+        },
+    );
+}
+
+// Zero scale, so it's drawn as degenerate triangles
+fn hidden_instance() -> Render3DInstance {
+    let mut instance = Render3DInstance::new([0.0, 0.0, 0.0, 0.0]);
+    instance.model_x = [0.0, 0.0, 0.0, 0.0];
+    instance.model_y = [0.0, 0.0, 0.0, 0.0];
+    instance.model_z = [0.0, 0.0, 0.0, 0.0];
+    instance
+}
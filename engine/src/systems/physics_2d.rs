@@ -1,7 +1,11 @@
-use crate::components::{Position2D, Velocity2D};
+use crate::{
+    components::{Position2D, Velocity2D},
+    sources::time::Time,
+};
 
+// Velocities are per frame (or per fixed step), scaled with the Time
 #[system(for_each)]
-pub fn physics_2d(pos: &mut Position2D, vel: &mut Velocity2D) {
+pub fn physics_2d(pos: &mut Position2D, vel: &mut Velocity2D, #[resource] time: &Time) {
     // Todo: replace hardcoding w/ some global config resource
     if vel.bounce {
         if pos.x <= -(1440 as f32) || pos.x >= (1440 as f32) {
@@ -12,6 +16,7 @@ pub fn physics_2d(pos: &mut Position2D, vel: &mut Velocity2D) {
         }
     }
 
-    pos.x += vel.vx;
-    pos.y += vel.vy;
+    let scale = time.frame_scale();
+    pos.x += vel.vx * scale;
+    pos.y += vel.vy * scale;
 }
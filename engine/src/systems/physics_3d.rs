@@ -1,15 +1,18 @@
 use legion::{world::SubWorld, IntoQuery};
-use std::sync::{Arc, RwLock};
 
-use crate::components::{DeltaTransform3D, FrameMetrics, Transform3D};
+use crate::{
+    components::{DeltaTransform3D, Transform3D},
+    sources::time::Time,
+};
 
 #[system]
 #[read_component(DeltaTransform3D)]
 #[write_component(Transform3D)]
-pub fn physics_3d(world: &mut SubWorld, #[resource] frame_metrics: &Arc<RwLock<FrameMetrics>>) {
-    let delta = frame_metrics.read().unwrap().delta().as_secs_f32();
+pub fn physics_3d(world: &mut SubWorld, #[resource] time: &Time) {
+    let delta = time.delta_secs();
 
-    <(&mut Transform3D, &DeltaTransform3D)>::query().par_for_each_mut(
+    par_for_each_mut!(
+        <(&mut Transform3D, &DeltaTransform3D)>::query(),
         world,
         |(transform, d_transform)| {
             transform.position[0] += d_transform.position[0] * delta;
@@ -0,0 +1,299 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+use legion::{world::SubWorld, Entity, IntoQuery};
+use std::sync::{Arc, Mutex, RwLock};
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    components::{Position2D, Transform3D},
+    renderer::{
+        mesh::{Mesh, MeshBounds},
+        SCREEN_SIZE,
+    },
+    sources::camera::{Camera2D, Camera3D},
+    systems::collision_2d::Collider2D,
+};
+
+// Makes an entity a candidate for picking_3d (with a Transform3D and a Mesh) or picking_2d
+// (with a Position2D and a Collider2D, which is the shape clicked on).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pickable {
+    // 3D: test the mesh's triangles, not just its bounding box
+    pub triangles: bool,
+
+    // Local bounds, recomputed when the vertex count changes
+    bounds: Option<(usize, [[f32; 3]; 2])>,
+}
+
+impl Pickable {
+    pub fn bounds() -> Self {
+        Self {
+            triangles: false,
+            bounds: None,
+        }
+    }
+
+    pub fn triangles() -> Self {
+        Self {
+            triangles: true,
+            bounds: None,
+        }
+    }
+}
+
+// The Pickable entity under the cursor, updated every frame by picking_3d_system
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PickedEntity {
+    pub entity: Option<Entity>,
+    pub point: [f32; 3],
+    pub distance: f32,
+}
+
+// The Pickable entity under the cursor, updated every frame by picking_2d_system
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PickedEntity2D {
+    pub entity: Option<Entity>,
+    // Cursor, in world space
+    pub point: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: [f32; 3],
+    // Normalized
+    pub dir: [f32; 3],
+}
+
+impl Ray {
+    // From the camera through a point on the screen, in window pixels from the top left
+    pub fn from_screen(camera: &Camera3D, cursor: (f32, f32), screen: (f32, f32)) -> Option<Self> {
+        let (x, y) = cursor_to_ndc(cursor, screen);
+        let inverse = camera.build_view_proj().invert()?;
+        let unproject = |z: f32| -> Vector3<f32> {
+            let point = inverse * Vector4::new(x, y, z, 1.0);
+            point.truncate() / point.w
+        };
+
+        // wgpu's depth range is [0, 1]
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        let dir = (far - near).normalize();
+        Some(Self {
+            origin: near.into(),
+            dir: dir.into(),
+        })
+    }
+
+    pub fn at(&self, t: f32) -> [f32; 3] {
+        [
+            self.origin[0] + self.dir[0] * t,
+            self.origin[1] + self.dir[1] * t,
+            self.origin[2] + self.dir[2] * t,
+        ]
+    }
+
+    // Distance along the ray to the box, if it hits (0 from inside)
+    pub fn intersect_aabb(&self, min: [f32; 3], max: [f32; 3]) -> Option<f32> {
+        let (mut t_min, mut t_max) = (0.0f32, f32::MAX);
+        for axis in 0..3 {
+            let inv = 1.0 / self.dir[axis];
+            let mut t0 = (min[axis] - self.origin[axis]) * inv;
+            let mut t1 = (max[axis] - self.origin[axis]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    // Möller–Trumbore, both faces
+    pub fn intersect_triangle(&self, a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Option<f32> {
+        let (a, b, c) = (Vector3::from(a), Vector3::from(b), Vector3::from(c));
+        let (origin, dir) = (Vector3::from(self.origin), Vector3::from(self.dir));
+        let (edge_1, edge_2) = (b - a, c - a);
+        let p = dir.cross(edge_2);
+        let det = edge_1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge_1);
+        let v = dir.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge_2.dot(q) * inv_det;
+        match t >= 0.0 {
+            true => Some(t),
+            false => None,
+        }
+    }
+
+    // Into the space of a model matrix. The direction isn't renormalized, so distances
+    // along the transformed ray are still world space distances.
+    fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let origin = matrix * Vector3::from(self.origin).extend(1.0);
+        let dir = matrix * Vector3::from(self.dir).extend(0.0);
+        Self {
+            origin: origin.truncate().into(),
+            dir: dir.truncate().into(),
+        }
+    }
+}
+
+fn cursor_to_ndc(cursor: (f32, f32), screen: (f32, f32)) -> (f32, f32) {
+    (
+        cursor.0 / screen.0 * 2.0 - 1.0,
+        1.0 - cursor.1 / screen.1 * 2.0,
+    )
+}
+
+// Same as the 3D render systems
+fn model_matrix(transform: &Transform3D) -> Matrix4<f32> {
+    Matrix4::from_translation(transform.position.into())
+        * Matrix4::from_angle_x(cgmath::Deg(transform.rotation[0]))
+        * Matrix4::from_angle_y(cgmath::Deg(transform.rotation[1]))
+        * Matrix4::from_angle_z(cgmath::Deg(transform.rotation[2]))
+        * Matrix4::from_nonuniform_scale(transform.scale[0], transform.scale[1], transform.scale[2])
+}
+
+// Positions are the first three floats of every vertex, whatever the vertex type
+fn intersect_mesh(ray: &Ray, mesh: &Mesh) -> Option<f32> {
+    let stride = mesh.vertices.len() / mesh.vertex_buffer.size.max(1) as usize;
+    if stride < 3 {
+        return None;
+    }
+    let position = |index: u32| -> [f32; 3] {
+        let start = index as usize * stride;
+        [
+            mesh.vertices[start],
+            mesh.vertices[start + 1],
+            mesh.vertices[start + 2],
+        ]
+    };
+    mesh.indices
+        .chunks_exact(3)
+        .filter_map(|tri| {
+            ray.intersect_triangle(position(tri[0]), position(tri[1]), position(tri[2]))
+        })
+        .fold(None, |closest: Option<f32>, t| match closest {
+            Some(closest) if closest <= t => Some(closest),
+            _ => Some(t),
+        })
+}
+
+#[system]
+#[read_component(Entity)]
+#[write_component(Pickable)]
+#[read_component(Transform3D)]
+#[read_component(Mesh)]
+pub fn picking_3d(
+    world: &mut SubWorld,
+    #[resource] camera: &Arc<Mutex<Camera3D>>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+    #[resource] picked: &mut PickedEntity,
+) {
+    debug!("running system picking_3d");
+    *picked = PickedEntity::default();
+
+    let cursor = match input.read().unwrap().mouse() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let screen = *SCREEN_SIZE.read().unwrap();
+    let ray = match Ray::from_screen(
+        &camera.lock().unwrap(),
+        cursor,
+        (screen.0 as f32, screen.1 as f32),
+    ) {
+        Some(ray) => ray,
+        None => return,
+    };
+
+    let mut query = <(Entity, &mut Pickable, &Transform3D, &Mesh)>::query();
+    query.for_each_mut(world, |(entity, pickable, transform, mesh)| {
+        let inverse = match model_matrix(transform).invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let local = ray.transform(&inverse);
+
+        let vertex_count = mesh.vertex_buffer.size as usize;
+        if !matches!(pickable.bounds, Some((count, _)) if count == vertex_count) {
+            let stride = (mesh.vertices.len() / vertex_count.max(1)).max(1);
+            let bounds = MeshBounds::from_vertices(&mesh.vertices, stride);
+            pickable.bounds = Some((vertex_count, [bounds.min, bounds.max]));
+        }
+        let [min, max] = pickable.bounds.unwrap().1;
+
+        let mut distance = match local.intersect_aabb(min, max) {
+            Some(distance) => distance,
+            None => return,
+        };
+        if pickable.triangles {
+            distance = match intersect_mesh(&local, mesh) {
+                Some(distance) => distance,
+                None => return,
+            };
+        }
+
+        if picked.entity.is_none() || distance < picked.distance {
+            *picked = PickedEntity {
+                entity: Some(*entity),
+                point: ray.at(distance),
+                distance,
+            };
+        }
+    });
+}
+
+#[system]
+#[read_component(Entity)]
+#[read_component(Pickable)]
+#[read_component(Position2D)]
+#[read_component(Collider2D)]
+pub fn picking_2d(
+    world: &mut SubWorld,
+    #[resource] camera: &Arc<Mutex<Camera2D>>,
+    #[resource] input: &Arc<RwLock<WinitInputHelper>>,
+    #[resource] picked: &mut PickedEntity2D,
+) {
+    debug!("running system picking_2d");
+    *picked = PickedEntity2D::default();
+
+    let cursor = match input.read().unwrap().mouse() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let screen = *SCREEN_SIZE.read().unwrap();
+    let (x, y) = cursor_to_ndc(cursor, (screen.0 as f32, screen.1 as f32));
+
+    let point = camera.lock().unwrap().ndc_to_world([x, y]);
+    picked.point = point;
+
+    // Closest center wins where colliders overlap
+    let mut closest = f32::MAX;
+    let mut query = <(Entity, &Pickable, &Position2D, &Collider2D)>::query();
+    for (entity, _, position, collider) in query.iter(world) {
+        if !collider.contains(position, point) {
+            continue;
+        }
+        let center = [
+            position.x + collider.offset[0] - point[0],
+            position.y + collider.offset[1] - point[1],
+        ];
+        let distance = center[0] * center[0] + center[1] * center[1];
+        if distance < closest {
+            closest = distance;
+            picked.entity = Some(*entity);
+        }
+    }
+}
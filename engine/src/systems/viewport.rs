@@ -0,0 +1,159 @@
+use legion::{component, systems::CommandBuffer, world::SubWorld, Entity, IntoQuery};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    renderer::{
+        buffer::upload::Uploader,
+        graph::NodeState,
+        uniform::group::{GroupState, GroupStateBuilder},
+        SCREEN_SIZE,
+    },
+    sources::camera::Camera3D,
+    systems::camera_3d::{Camera3DUniformGroup, Camera3DUniforms},
+};
+
+// A region of the render target drawn from the Camera3D on the same entity, for split
+// screen or a picture in picture minimap. When any exist, the sky and forward nodes draw
+// the scene once per viewport (see SceneViews) instead of once from the Camera3D resource.
+//
+// Frustum culling, shadows, ssao and the debug overlays still follow the Camera3D resource;
+// the sky stays centered on it too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    // x, y, width, height as fractions of the target, from the top left
+    pub rect: [f32; 4],
+    // Lower orders are drawn first, so overlays (minimaps) want a higher one
+    pub order: i32,
+}
+
+impl Viewport {
+    pub fn new(rect: [f32; 4]) -> Self {
+        Self { rect, order: 0 }
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    // Pixel rect (x, y, width, height) inside a target of this size, clamped to it
+    pub fn pixels(&self, size: (u32, u32)) -> (u32, u32, u32, u32) {
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        let x = (self.rect[0].clamp(0.0, 1.0) * width) as u32;
+        let y = (self.rect[1].clamp(0.0, 1.0) * height) as u32;
+        let w = ((self.rect[2].max(0.0) * width) as u32).min(size.0 - x);
+        let h = ((self.rect[3].max(0.0) * height) as u32).min(size.1 - y);
+        (x, y, w, h)
+    }
+
+    pub fn aspect(&self, size: (u32, u32)) -> f32 {
+        let (_, _, w, h) = self.pixels(size);
+        w.max(1) as f32 / h.max(1) as f32
+    }
+}
+
+// Draws the scene from the Camera3D on the same entity into a texture node's target
+// (GraphBuilder::with_texture_node, EngineBuilder::with_render_texture), for monitors,
+// mirrors or portals. The camera's aspect is left alone, so it should match the texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureCamera {
+    // The texture node's id, which is also the texture's
+    pub texture: Uuid,
+}
+
+// Camera uniforms for a Viewport or TextureCamera, added by viewport_3d_system
+pub struct ViewportCamera(pub GroupState);
+
+// The cameras a 3D node draws the scene from, each with the pixel rect to draw into. Texture
+// nodes draw from their TextureCamera, others once per Viewport, or else once over the whole
+// target from the Camera3D resource. Bounds follow the Camera3D resource, so only that last
+// one is culled. Nodes using this need to read Viewport, TextureCamera and ViewportCamera.
+pub struct SceneViews<'a> {
+    pub views: Vec<(Option<(u32, u32, u32, u32)>, &'a wgpu::BindGroup)>,
+    pub cull: bool,
+}
+
+impl<'a> SceneViews<'a> {
+    // camera is the node's own Camera3D group, size its target's
+    pub fn new(
+        world: &'a SubWorld,
+        state: &NodeState,
+        camera: &'a wgpu::BindGroup,
+        size: (u32, u32),
+    ) -> Self {
+        let mut views = vec![];
+        if state.texture_node {
+            let mut query = <(&TextureCamera, &ViewportCamera)>::query();
+            views.extend(
+                query
+                    .iter(world)
+                    .filter(|(texture_camera, _)| texture_camera.texture == state.node.id)
+                    .map(|(_, group)| (None, &*group.0.bind_group)),
+            );
+        } else {
+            let mut viewports = <(&Viewport, &ViewportCamera)>::query()
+                .iter(world)
+                .collect::<Vec<_>>();
+            viewports.sort_by_key(|(viewport, _)| viewport.order);
+            views.extend(
+                viewports
+                    .into_iter()
+                    .map(|(viewport, group)| (Some(viewport.pixels(size)), &*group.0.bind_group)),
+            );
+        }
+        let cull = views.is_empty();
+        if cull && !state.texture_node {
+            views.push((None, camera));
+        }
+        Self { views, cull }
+    }
+}
+
+// Restricts the pass to a view's rect, if it has one. False when the rect is empty and
+// there's nothing to draw.
+pub fn set_view(pass: &mut wgpu::RenderPass, rect: Option<(u32, u32, u32, u32)>) -> bool {
+    if let Some((x, y, w, h)) = rect {
+        if w == 0 || h == 0 {
+            return false;
+        }
+        pass.set_viewport(x as f32, y as f32, w as f32, h as f32, 0.0, 1.0);
+        pass.set_scissor_rect(x, y, w, h);
+    }
+    true
+}
+
+#[system]
+#[read_component(Viewport)]
+#[read_component(TextureCamera)]
+#[write_component(Camera3D)]
+#[read_component(ViewportCamera)]
+pub fn viewport_3d(
+    world: &mut SubWorld,
+    command_buffer: &mut CommandBuffer,
+    #[resource] device: &Arc<wgpu::Device>,
+    #[resource] uploader: &Arc<Uploader>,
+    #[resource] group_builder: &Arc<Mutex<GroupStateBuilder<Camera3DUniformGroup>>>,
+) {
+    let group_builder = group_builder.lock().unwrap();
+    let mut query = <(Entity, &Camera3D)>::query().filter(
+        (component::<Viewport>() | component::<TextureCamera>()) & !component::<ViewportCamera>(),
+    );
+    query.for_each(world, |(entity, _)| {
+        debug!("allocating camera buffers for new viewport");
+        command_buffer.add_component(
+            *entity,
+            ViewportCamera(group_builder.single_state(device, uploader).unwrap()),
+        );
+    });
+
+    let screen_size = *SCREEN_SIZE.read().unwrap();
+    let mut query = <(Option<&Viewport>, &mut Camera3D, &ViewportCamera)>::query();
+    query.for_each_mut(world, |(viewport, camera, ViewportCamera(group_state))| {
+        if let Some(viewport) = viewport {
+            camera.aspect = viewport.aspect(screen_size);
+        }
+        let source = &[Camera3DUniforms::from(&*camera)];
+        group_state.write_buffer(0, bytemuck::cast_slice(source));
+    });
+}
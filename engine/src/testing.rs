@@ -0,0 +1,294 @@
+use anyhow::{anyhow, Result};
+use image::{Rgba, RgbaImage};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{constants::*, Engine, EngineBuilder};
+
+// Snapshot tests for the renderer: a headless engine (see EngineBuilder::headless) loads a
+// scene, renders a few frames and its last one is compared against a golden PNG, so that
+// regressions in the graph, its nodes or targets show up in `cargo test`. Runs are made
+// reproducible with EngineBuilder::with_deterministic.
+//
+//     #[test]
+//     fn sprites() -> anyhow::Result<()> {
+//         SnapshotTest::new("sprites").run_2d(ember::engine_builder(), |engine| {
+//             engine.world().push(...);
+//             Ok(())
+//         })
+//     }
+//
+// Golden images live in tests/golden of the crate under test, as <name>.png. A test without
+// one fails; run it with EMBER_UPDATE_SNAPSHOTS=1 to (re)write it, and check it in. A failed
+// comparison writes <name>.actual.png and <name>.diff.png (differing pixels in red) next to
+// the golden image.
+pub struct SnapshotTest {
+    name: String,
+    size: (u32, u32),
+    frames: u32,
+    seed: u64,
+    threshold: f32,
+    max_diff_ratio: f32,
+    golden_dir: PathBuf,
+}
+
+impl SnapshotTest {
+    pub fn new(name: &str) -> Self {
+        // Set by cargo for tests; the working directory otherwise
+        let root = env::var_os("CARGO_MANIFEST_DIR").map_or_else(PathBuf::new, PathBuf::from);
+        Self {
+            name: name.to_owned(),
+            size: (256, 256),
+            frames: 3,
+            seed: 0,
+            threshold: DEFAULT_SNAPSHOT_THRESHOLD,
+            max_diff_ratio: DEFAULT_SNAPSHOT_MAX_DIFF_RATIO,
+            golden_dir: root.join("tests").join("golden"),
+        }
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    // How many to render (at a fixed 60 per second) before comparing the last; at least 1
+    pub fn with_frames(mut self, frames: u32) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    // For the EngineRng
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // How different a pixel may look from the golden one (0 to 1, see compare), and the
+    // fraction of pixels that may differ by more than that
+    pub fn with_tolerance(mut self, threshold: f32, max_diff_ratio: f32) -> Self {
+        self.threshold = threshold;
+        self.max_diff_ratio = max_diff_ratio;
+        self
+    }
+
+    pub fn with_golden_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.golden_dir = dir.as_ref().to_owned();
+        self
+    }
+
+    // `builder` is built headless with build_2d, after which `scene` sets up the world
+    pub fn run_2d<F>(self, builder: EngineBuilder, scene: F) -> Result<()>
+    where
+        F: FnOnce(&mut Engine) -> Result<()>,
+    {
+        let mut engine = self.headless(builder).build_2d()?;
+        scene(&mut engine)?;
+        self.check(&mut engine)
+    }
+
+    // See run_2d
+    pub fn run_3d<F>(self, builder: EngineBuilder, scene: F) -> Result<()>
+    where
+        F: FnOnce(&mut Engine) -> Result<()>,
+    {
+        let mut engine = self.headless(builder).build_3d()?;
+        scene(&mut engine)?;
+        self.check(&mut engine)
+    }
+
+    fn headless(&self, builder: EngineBuilder) -> EngineBuilder {
+        builder
+            .headless(self.size.0, self.size.1)
+            .with_deterministic(self.seed, 60.0)
+    }
+
+    // Renders the frames and compares the last one against the golden image
+    pub fn check(&self, engine: &mut Engine) -> Result<()> {
+        let mut frame = engine.render_frame_to_image()?;
+        for _ in 1..self.frames {
+            frame = engine.render_frame_to_image()?;
+        }
+
+        let golden_path = self.path("png");
+        if env::var_os("EMBER_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(&self.golden_dir)?;
+            frame.save(&golden_path)?;
+            info!("snapshot {}: wrote {}", self.name, golden_path.display());
+            return Ok(());
+        }
+        let golden = match image::open(&golden_path) {
+            Ok(golden) => golden.to_rgba8(),
+            Err(err) => {
+                return Err(anyhow!(
+                    "snapshot {}: no golden image at {} ({}); run with EMBER_UPDATE_SNAPSHOTS=1 \
+                     to write it",
+                    self.name,
+                    golden_path.display(),
+                    err
+                ))
+            }
+        };
+
+        let diff = compare(&frame, &golden, self.threshold)?;
+        if diff.ratio() <= self.max_diff_ratio {
+            return Ok(());
+        }
+        let (actual_path, diff_path) = (self.path("actual.png"), self.path("diff.png"));
+        frame.save(&actual_path)?;
+        diff.image.save(&diff_path)?;
+        Err(anyhow!(
+            "snapshot {}: {} of {} pixels differ ({:.3}%, at most {:.3}% allowed); see {} and {}",
+            self.name,
+            diff.differing,
+            diff.total,
+            diff.ratio() * 100.0,
+            self.max_diff_ratio * 100.0,
+            actual_path.display(),
+            diff_path.display()
+        ))
+    }
+
+    fn path(&self, extension: &str) -> PathBuf {
+        self.golden_dir.join(format!("{}.{}", self.name, extension))
+    }
+}
+
+pub struct ImageDiff {
+    pub differing: usize,
+    pub total: usize,
+    // The golden image faded out, with the differing pixels in red
+    pub image: RgbaImage,
+}
+
+impl ImageDiff {
+    pub fn ratio(&self) -> f32 {
+        self.differing as f32 / self.total.max(1) as f32
+    }
+}
+
+// Pixel by pixel, by their distance in YIQ space (as pixelmatch does), which follows how
+// different colors look better than rgb does. `threshold` is a fraction of the largest
+// possible distance, ie. 0 counts any change and 1 none.
+pub fn compare(actual: &RgbaImage, golden: &RgbaImage, threshold: f32) -> Result<ImageDiff> {
+    if actual.dimensions() != golden.dimensions() {
+        return Err(anyhow!(
+            "image sizes differ: {:?}, golden {:?}",
+            actual.dimensions(),
+            golden.dimensions()
+        ));
+    }
+    let max_delta = MAX_YIQ_DELTA * threshold * threshold;
+    let mut image = RgbaImage::new(golden.width(), golden.height());
+    let mut differing = 0;
+    for ((a, g), out) in actual.pixels().zip(golden.pixels()).zip(image.pixels_mut()) {
+        *out = if yiq_delta(a, g) > max_delta {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let gray = 255.0 - (255.0 - yiq(g)[0]) * 0.1;
+            Rgba([gray as u8, gray as u8, gray as u8, 255])
+        };
+    }
+
+    Ok(ImageDiff {
+        differing,
+        total: (golden.width() * golden.height()) as usize,
+        image,
+    })
+}
+
+// The largest yiq_delta, between red and cyan (35217.18; black and white are only 32857.13),
+// rounded up so f32 rounding can't put them past it
+const MAX_YIQ_DELTA: f32 = 35218.0;
+
+fn yiq_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let (a, b) = (yiq(a), yiq(b));
+    let (y, i, q) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+// Blended onto white first, so transparency counts
+fn yiq(pixel: &Rgba<u8>) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    let blend = |c: u8| 255.0 + (c as f32 - 255.0) * alpha;
+    let (r, g, b) = (blend(pixel[0]), blend(pixel[1]), blend(pixel[2]));
+    [
+        r * 0.2989 + g * 0.5866 + b * 0.1145,
+        r * 0.5960 - g * 0.2742 - b * 0.3218,
+        r * 0.2115 - g * 0.5226 + b * 0.3111,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const CYAN: Rgba<u8> = Rgba([0, 255, 255, 255]);
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    // 4x4 of `base` but for its first pixel
+    fn image(base: Rgba<u8>, first: Rgba<u8>) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(4, 4, base);
+        image.put_pixel(0, 0, first);
+        image
+    }
+
+    #[test]
+    fn identical_images_match() -> Result<()> {
+        let golden = image(WHITE, RED);
+        let diff = compare(&golden.clone(), &golden, 0.0)?;
+        assert_eq!(diff.differing, 0);
+        assert_eq!(diff.total, 16);
+        assert_eq!(diff.ratio(), 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn size_mismatch_is_an_error() {
+        let actual = RgbaImage::from_pixel(4, 4, WHITE);
+        let golden = RgbaImage::from_pixel(4, 3, WHITE);
+        assert!(compare(&actual, &golden, 0.1).is_err());
+    }
+
+    #[test]
+    fn threshold_is_a_fraction_of_the_largest_delta() {
+        let largest = yiq_delta(&RED, &CYAN);
+        assert!(largest <= MAX_YIQ_DELTA);
+        assert!(largest > MAX_YIQ_DELTA - 1.0);
+        assert!(yiq_delta(&BLACK, &WHITE) < largest);
+        assert_eq!(yiq_delta(&RED, &RED), 0.0);
+    }
+
+    #[test]
+    fn threshold_one_counts_no_change() -> Result<()> {
+        let diff = compare(&image(CYAN, RED), &RgbaImage::from_pixel(4, 4, RED), 1.0)?;
+        assert_eq!(diff.differing, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn transparent_pixels_blend_onto_white() {
+        assert_eq!(yiq_delta(&Rgba([0, 0, 0, 0]), &WHITE), 0.0);
+    }
+
+    #[test]
+    fn differs_just_past_the_threshold() -> Result<()> {
+        let (a, b) = (Rgba([128, 128, 128, 255]), Rgba([120, 128, 128, 255]));
+        let actual = image(WHITE, a);
+        let golden = image(WHITE, b);
+        // The threshold at which this pair's delta is exactly the limit
+        let threshold = (yiq_delta(&a, &b) / MAX_YIQ_DELTA).sqrt();
+
+        let under = compare(&actual, &golden, threshold * 1.01)?;
+        assert_eq!(under.differing, 0);
+        let over = compare(&actual, &golden, threshold * 0.99)?;
+        assert_eq!(over.differing, 1);
+        assert_eq!(over.image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use ember::{
+    components::{DeltaTransform3D, Transform2D, Transform3D},
+    renderer::systems::{
+        render_2d::forward_instance::Render2DInstance, render_3d::forward_pbr::RenderPBR,
+    },
+    testing::SnapshotTest,
+};
+use std::sync::{Arc, Mutex};
+
+// These render on a real adapter, so they're ignored by default: run them with
+// `cargo test -p ember --test snapshots -- --ignored`, and with EMBER_UPDATE_SNAPSHOTS=1 to
+// write the golden images in tests/golden after an intended change.
+
+#[test]
+#[ignore = "needs a GPU"]
+fn instances_2d() -> anyhow::Result<()> {
+    SnapshotTest::new("instances_2d").run_2d(ember::engine_builder(), |engine| {
+        let mut group = Render2DInstance::new_default_group();
+        for (x, color) in [
+            (-64.0, [1.0, 0.2, 0.2, 1.0]),
+            (0.0, [0.2, 1.0, 0.2, 1.0]),
+            (64.0, [0.2, 0.2, 1.0, 0.5]),
+        ] {
+            let transform = Transform2D::new(x, x / 2.0, 24.0, 24.0);
+            group.push(
+                Render2DInstance::new(color).with_rotation(x / 256.0),
+                vec![Arc::new(Mutex::new(transform))],
+            );
+        }
+        let mesh = engine.clone_mesh(engine.mesh("unit_square").unwrap());
+        engine.world().push((group, mesh));
+        Ok(())
+    })
+}
+
+#[test]
+#[ignore = "needs a GPU"]
+fn pbr_cube_3d() -> anyhow::Result<()> {
+    SnapshotTest::new("pbr_cube_3d")
+        .with_frames(10)
+        .run_3d(ember::engine_builder(), |engine| {
+            let mesh = engine.clone_mesh(engine.mesh("unit_cube").unwrap());
+            engine.world().push((
+                RenderPBR::colored("snapshot_cube", [0.8, 0.3, 0.1, 1.0]),
+                Transform3D {
+                    position: [0.0, 0.0, 0.0],
+                    rotation: [30.0, 45.0, 0.0],
+                    scale: [3.0, 3.0, 3.0],
+                },
+                // Spun at a fixed rate, so the deterministic clock is covered too
+                DeltaTransform3D {
+                    rotation: [0.0, 30.0, 0.0],
+                    ..Default::default()
+                },
+                mesh,
+            ));
+            Ok(())
+        })
+}
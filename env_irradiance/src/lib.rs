@@ -0,0 +1,3 @@
+// The spherical harmonics now live in the engine, which computes them at startup; this
+// crate is just the command line front end for precomputing an environment's cache.
+pub use ember::sources::environment::*;
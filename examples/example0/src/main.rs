@@ -1,7 +1,6 @@
 use ember::{
-    components::Motion2D,
-    constants::{ID, PRIMITIVE_MESH_GROUP_ID, UNIT_SQUARE_MESH_ID},
-    renderer::systems::render_2d::forward_instance::Render2DInstance,
+    components::Motion2D, renderer::systems::render_2d::forward_instance::Render2DInstance,
+    sources::rng::EngineRng,
 };
 use rand::Rng;
 use std::sync::{Arc, Mutex};
@@ -13,9 +12,9 @@ fn main() {
     let (mut engine, event_loop) = ember::engine_builder().default_2d().unwrap();
 
     let mut instance_group = Render2DInstance::new_default_group();
-    let instance_mesh = engine.clone_mesh(&ID(UNIT_SQUARE_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID));
+    let instance_mesh = engine.clone_mesh(engine.mesh("unit_square").unwrap());
 
-    let mut rng = rand::thread_rng();
+    let mut rng = engine.resources().get_mut::<EngineRng>().unwrap().fork();
     for _i in 0..5000 {
         instance_group.push(
             Render2DInstance::new([1.0, 1.0, 1.0, 1.0]),
@@ -1,5 +1,4 @@
 use ember::{
-    constants::{ID, PRIMITIVE_MESH_GROUP_ID, UNIT_SQUARE_MESH_ID},
     renderer::systems::render_2d::forward_instance::Render2DInstance,
     systems::particle_2d::{EmitterShape, ParticleEmitter2D, ParticleSystem2D},
 };
@@ -11,7 +10,7 @@ fn main() {
     let (mut engine, event_loop) = ember::engine_builder().default_2d().unwrap();
 
     let particle_group = Render2DInstance::new_default_group();
-    let particle_mesh = engine.clone_mesh(&ID(UNIT_SQUARE_MESH_ID), &ID(PRIMITIVE_MESH_GROUP_ID));
+    let particle_mesh = engine.clone_mesh(engine.mesh("unit_square").unwrap());
 
     let mut particle_system = ParticleSystem2D::default();
     particle_system.num_particles = 5000;
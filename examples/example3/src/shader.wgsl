@@ -3,10 +3,13 @@
 // -------------------------------------------------
 
 
-struct QuadUniforms {
-    dimensions: vec2<f32>;
+struct FrameUniforms {
     time: f32;
     delta: f32;
+    frame: u32;
+    resolution: vec2<f32>;
+    inv_resolution: vec2<f32>;
+    camera_pos: vec4<f32>;
 };
 
 
@@ -18,7 +21,7 @@ struct CameraUniforms {
 };
 
 [[group(0), binding(0)]]
-var<uniform> quad: QuadUniforms;
+var<uniform> frame_uniforms: FrameUniforms;
 
 [[group(1), binding(0)]]
 var<uniform> camera: CameraUniforms;
@@ -210,7 +213,7 @@ fn scene(target: vec3<f32>) -> f32 {
     //     ),
     // );
 
-    let r = quad.delta * 0.05;
+    let r = frame_uniforms.delta * 0.05;
     let rot_mat = mat3x3<f32>(
         vec3<f32>(cos(r), 0.0, -sin(r)),
         vec3<f32>(0.0, 1.0, 0.0),
@@ -331,7 +334,7 @@ fn render(ray_src: vec3<f32>, ray_dir: vec3<f32>, ray_del: RayDelta) -> vec3<f32
         // let reflected_hit = cast_ray(frag_pos, reflected_ray);
         let occlusion = scene_occlusion(frag_pos, normal);
 
-        // let ray_del = ray_delta(in.screen_pos, quad.dimensions, camera.inv_view_proj, camera.clip.y - camera.clip.x);
+        // let ray_del = ray_delta(in.screen_pos, frame_uniforms.resolution, camera.inv_view_proj, camera.clip.y - camera.clip.x);
         // let grid_ray_src = ray_src * vec3<f32>(1.0, 0.0, 1.0);
         // let grid_mat = grid(frag_pos, grid_ray_src, ray_dir, ray_del.dx, ray_del.dy, 0.2);
         // obj_color = vec3<f32>(grid_mat, grid_mat, grid_mat);
@@ -368,7 +371,7 @@ fn render(ray_src: vec3<f32>, ray_dir: vec3<f32>, ray_del: RayDelta) -> vec3<f32
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let ray_src = in.ray_origin;
     let ray_dir = normalize(in.ray);
-    let ray_del = ray_delta(in.screen_pos*quad.dimensions, quad.dimensions, camera.inv_view_proj, camera.clip.y - camera.clip.x);
+    let ray_del = ray_delta(in.screen_pos*frame_uniforms.resolution, frame_uniforms.resolution, camera.inv_view_proj, camera.clip.y - camera.clip.x);
 
     var total: vec3<f32> = vec3<f32>(0.0);
     let multisampling = 1;
@@ -0,0 +1,551 @@
+use ember::{
+    components::Position2D,
+    constants::ID,
+    renderer::{
+        buffer::instance::{InstanceGroup, InstanceMutator},
+        systems::render_2d::forward_instance::Render2DInstance,
+    },
+    sources::{audio::AudioSource, events::Events, input::InputMap, time::Time},
+    systems::{
+        collision_2d::{Collider2D, CollisionEvent, Shape2D},
+        particle_2d::ParticleEmitter2D,
+    },
+};
+use iced_winit::winit::window::Window;
+use legion::{system, world::SubWorld, Entity, EntityStore, IntoQuery, World};
+use rand::{rngs::StdRng, Rng};
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+use crate::{hud::Hud, input, scene::Wave};
+
+// Half-extents of the visible world, which wraps at the edges
+pub const ARENA: [f32; 2] = [1920.0, 1080.0];
+
+// Registered with EngineBuilder::with_sound
+pub const SHOOT_SOUND: &str = "6d1e2bd4-51e4-4f0c-9a39-0c0f9e0b7a11";
+pub const EXPLOSION_SOUND: &str = "0b7f5f0e-8f34-4a3b-a7a4-5e7f1c2d9b62";
+pub const THRUST_SOUND: &str = "c43d8a52-2f6b-4e51-8d1a-93b8f4e6a0c7";
+
+// Sources kept per sound, so a few can overlap
+const SOUND_VOICES: usize = 4;
+
+const MAX_BULLETS: usize = 32;
+const MAX_ASTEROIDS: usize = 96;
+
+const SHIP_RADIUS: f32 = 24.0;
+const SHIP_TURN_SPEED: f32 = 3.5;
+const SHIP_THRUST: f32 = 900.0;
+const SHIP_DRAG: f32 = 0.6;
+const SHIP_LIVES: u32 = 3;
+const RESPAWN_SHIELD: f32 = 2.0;
+
+const BULLET_RADIUS: f32 = 6.0;
+const BULLET_SPEED: f32 = 1400.0;
+const BULLET_LIFETIME: f32 = 1.2;
+const FIRE_COOLDOWN: f32 = 0.15;
+
+// Asteroids split in two until they get this small
+const MIN_ASTEROID_RADIUS: f32 = 30.0;
+const EXPLOSION_PARTICLES: u32 = 60;
+
+// One instance in the game's instance group
+pub struct Body {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub alive: bool,
+}
+
+impl Body {
+    fn new(color: [f32; 4]) -> Self {
+        Self {
+            position: [0.0, 0.0],
+            velocity: [0.0, 0.0],
+            radius: 0.0,
+            color,
+            alive: false,
+        }
+    }
+
+    fn step(&mut self, delta: f32) {
+        for axis in 0..2 {
+            self.position[axis] += self.velocity[axis] * delta;
+            if self.position[axis] > ARENA[axis] {
+                self.position[axis] -= 2.0 * ARENA[axis];
+            } else if self.position[axis] < -ARENA[axis] {
+                self.position[axis] += 2.0 * ARENA[axis];
+            }
+        }
+    }
+}
+
+impl InstanceMutator<Render2DInstance> for Body {
+    fn mutate(&mut self, instance: &mut Render2DInstance, _delta: f32) {
+        let scale = if self.alive { self.radius } else { 0.0 };
+        instance.model = [self.position[0], self.position[1], scale, scale];
+        instance.color = self.color;
+    }
+}
+
+// The body an entity collides for, with its Position2D and Collider2D following it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Piece {
+    Ship,
+    Bullet(usize),
+    Asteroid(usize),
+}
+
+// A pooled sound effect's AudioSource, restarted rather than spawned for every shot
+pub struct Voice;
+
+// The looping AudioSource playing while the ship thrusts
+pub struct ThrustVoice;
+
+#[derive(PartialEq, Debug)]
+enum State {
+    Playing,
+    GameOver,
+}
+
+// A resource, so the HUD's buttons can reach it
+pub struct Game {
+    waves: Vec<Wave>,
+    explosions: Arc<Mutex<ParticleEmitter2D>>,
+    hud: Arc<Mutex<Hud>>,
+
+    ship: Arc<Mutex<Body>>,
+    // Sits in front of the ship to show its heading
+    nose: Arc<Mutex<Body>>,
+    bullets: Vec<(Arc<Mutex<Body>>, f32)>,
+    asteroids: Vec<Arc<Mutex<Body>>>,
+
+    state: State,
+    heading: f32,
+    shield: f32,
+    fire_cooldown: f32,
+    thrusting: bool,
+    wave: usize,
+    score: u32,
+    lives: u32,
+    title: Option<String>,
+    // Sounds to start this frame, and where
+    sounds: Vec<(Uuid, [f32; 2])>,
+    // Forked from the EngineRng, so that recorded games replay the same
+    rng: StdRng,
+}
+
+impl Game {
+    // Pushes every body the game will ever need into the group up front, each with an entity
+    // colliding for it, along with the sound voices
+    pub fn new(
+        world: &mut World,
+        group: &mut InstanceGroup<Render2DInstance>,
+        waves: Vec<Wave>,
+        explosions: Arc<Mutex<ParticleEmitter2D>>,
+        hud: Arc<Mutex<Hud>>,
+        rng: StdRng,
+    ) -> Self {
+        let mut push = |color: [f32; 4], piece: Option<Piece>| {
+            let body = Arc::new(Mutex::new(Body::new(color)));
+            group.push(Render2DInstance::new(color), vec![body.clone()]);
+            if let Some(piece) = piece {
+                world.push((
+                    piece,
+                    Position2D { x: 0.0, y: 0.0 },
+                    // Events only; the game decides what a hit does
+                    Collider2D::circle(0.0).sensor(),
+                ));
+            }
+            body
+        };
+
+        let asteroids = (0..MAX_ASTEROIDS)
+            .map(|i| push([0.55, 0.5, 0.45, 1.0], Some(Piece::Asteroid(i))))
+            .collect();
+        let bullets = (0..MAX_BULLETS)
+            .map(|i| (push([1.0, 0.9, 0.3, 1.0], Some(Piece::Bullet(i))), 0.0))
+            .collect();
+        let ship = push([0.3, 0.8, 1.0, 1.0], Some(Piece::Ship));
+        let nose = push([1.0, 1.0, 1.0, 1.0], None);
+
+        for sound in [SHOOT_SOUND, EXPLOSION_SOUND] {
+            for _ in 0..SOUND_VOICES {
+                world.push((
+                    Voice,
+                    idle(AudioSource::new(ID(sound))),
+                    Position2D { x: 0.0, y: 0.0 },
+                ));
+            }
+        }
+        world.push((
+            ThrustVoice,
+            idle(AudioSource::new(ID(THRUST_SOUND)).looping()),
+            Position2D { x: 0.0, y: 0.0 },
+        ));
+
+        let mut game = Self {
+            waves,
+            explosions,
+            hud,
+            ship,
+            nose,
+            bullets,
+            asteroids,
+            state: State::Playing,
+            heading: PI / 2.0,
+            shield: 0.0,
+            fire_cooldown: 0.0,
+            thrusting: false,
+            wave: 0,
+            score: 0,
+            lives: SHIP_LIVES,
+            title: None,
+            sounds: vec![],
+            rng,
+        };
+        game.restart();
+        game
+    }
+
+    pub fn restart(&mut self) {
+        self.state = State::Playing;
+        self.wave = 0;
+        self.score = 0;
+        self.lives = SHIP_LIVES;
+        for (bullet, _) in &self.bullets {
+            bullet.lock().unwrap().alive = false;
+        }
+        self.respawn_ship();
+        self.spawn_wave();
+    }
+
+    fn respawn_ship(&mut self) {
+        let mut ship = self.ship.lock().unwrap();
+        ship.position = [0.0, 0.0];
+        ship.velocity = [0.0, 0.0];
+        ship.radius = SHIP_RADIUS;
+        ship.alive = true;
+        self.heading = PI / 2.0;
+        self.shield = RESPAWN_SHIELD;
+    }
+
+    fn spawn_wave(&mut self) {
+        // Waves repeat, faster each time around
+        let loops = (self.wave / self.waves.len()) as f32;
+        let wave = self.waves[self.wave % self.waves.len()];
+        let speed = wave.speed * (1.0 + 0.25 * loops);
+
+        let rng = &mut self.rng;
+        for asteroid in &self.asteroids {
+            asteroid.lock().unwrap().alive = false;
+        }
+        for asteroid in self.asteroids.iter().take(wave.asteroids as usize) {
+            let mut asteroid = asteroid.lock().unwrap();
+            let angle = rng.gen_range(0.0..2.0 * PI);
+            // Spawn along the edges, away from the ship
+            asteroid.position = [
+                ARENA[0] * rng.gen_range(-1.0..1.0),
+                ARENA[1] * if rng.gen::<bool>() { 1.0 } else { -1.0 },
+            ];
+            asteroid.velocity = [angle.cos() * speed, angle.sin() * speed];
+            asteroid.radius = wave.radius;
+            asteroid.alive = true;
+        }
+        self.changed();
+    }
+
+    // `hits` are the pieces that collided in the last collision pass
+    pub fn update(&mut self, input_map: &InputMap, hits: &[(Piece, Piece)], delta: f32) {
+        let mut explosion: Option<[f32; 2]> = None;
+
+        if self.state == State::GameOver {
+            self.thrusting = false;
+            if input_map.pressed(input::RESTART) {
+                self.restart();
+            }
+        } else {
+            self.steer(input_map, delta);
+            explosion = self.collide(hits);
+        }
+
+        self.step(delta);
+
+        if let Some(position) = explosion {
+            let mut explosions = self.explosions.lock().unwrap();
+            explosions.position = position;
+            explosions.emit_burst(EXPLOSION_PARTICLES);
+            self.sounds.push((ID(EXPLOSION_SOUND), position));
+        }
+    }
+
+    // The new window title, if it changed
+    pub fn take_title(&mut self) -> Option<String> {
+        self.title.take()
+    }
+
+    fn steer(&mut self, input_map: &InputMap, delta: f32) {
+        if input_map.held(input::TURN_LEFT) {
+            self.heading += SHIP_TURN_SPEED * delta;
+        }
+        if input_map.held(input::TURN_RIGHT) {
+            self.heading -= SHIP_TURN_SPEED * delta;
+        }
+        let direction = [self.heading.cos(), self.heading.sin()];
+
+        let mut ship = self.ship.lock().unwrap();
+        self.thrusting = input_map.held(input::THRUST);
+        for axis in 0..2 {
+            if self.thrusting {
+                ship.velocity[axis] += direction[axis] * SHIP_THRUST * delta;
+            }
+            ship.velocity[axis] *= 1.0 - SHIP_DRAG * delta;
+        }
+
+        self.fire_cooldown -= delta;
+        if self.fire_cooldown <= 0.0 && input_map.held(input::FIRE) {
+            if let Some((bullet, age)) = self
+                .bullets
+                .iter_mut()
+                .find(|(bullet, _)| !bullet.lock().unwrap().alive)
+            {
+                let mut bullet = bullet.lock().unwrap();
+                bullet.position = ship.position;
+                bullet.velocity = [
+                    ship.velocity[0] + direction[0] * BULLET_SPEED,
+                    ship.velocity[1] + direction[1] * BULLET_SPEED,
+                ];
+                bullet.radius = BULLET_RADIUS;
+                bullet.alive = true;
+                *age = 0.0;
+                self.fire_cooldown = FIRE_COOLDOWN;
+                self.sounds.push((ID(SHOOT_SOUND), ship.position));
+            }
+        }
+    }
+
+    fn step(&mut self, delta: f32) {
+        self.shield -= delta;
+
+        let mut ship = self.ship.lock().unwrap();
+        ship.step(delta);
+        // Flicker while shielded
+        ship.color[3] = if self.shield > 0.0 && (self.shield * 10.0) as u32 % 2 == 0 {
+            0.3
+        } else {
+            1.0
+        };
+
+        let mut nose = self.nose.lock().unwrap();
+        nose.alive = ship.alive;
+        nose.radius = SHIP_RADIUS * 0.35;
+        nose.position = [
+            ship.position[0] + self.heading.cos() * SHIP_RADIUS * 1.4,
+            ship.position[1] + self.heading.sin() * SHIP_RADIUS * 1.4,
+        ];
+
+        for (bullet, age) in &mut self.bullets {
+            let mut bullet = bullet.lock().unwrap();
+            *age += delta;
+            if *age > BULLET_LIFETIME {
+                bullet.alive = false;
+            }
+            bullet.step(delta);
+        }
+        for asteroid in &self.asteroids {
+            asteroid.lock().unwrap().step(delta);
+        }
+    }
+
+    // Where something exploded this frame, if anywhere
+    fn collide(&mut self, hits: &[(Piece, Piece)]) -> Option<[f32; 2]> {
+        let mut explosion = None;
+        let mut fragments: Vec<([f32; 2], f32, f32)> = vec![];
+        let mut ship_hit = false;
+
+        for hit in hits {
+            match *hit {
+                (Piece::Bullet(bullet), Piece::Asteroid(asteroid))
+                | (Piece::Asteroid(asteroid), Piece::Bullet(bullet)) => {
+                    let mut bullet = self.bullets[bullet].0.lock().unwrap();
+                    let mut asteroid = self.asteroids[asteroid].lock().unwrap();
+                    // Already spent on another hit this frame
+                    if !bullet.alive || !asteroid.alive {
+                        continue;
+                    }
+
+                    bullet.alive = false;
+                    asteroid.alive = false;
+                    explosion = Some(asteroid.position);
+                    self.score += (1000.0 / asteroid.radius) as u32 * 10;
+
+                    if asteroid.radius / 2.0 >= MIN_ASTEROID_RADIUS {
+                        let speed = (asteroid.velocity[0].powi(2) + asteroid.velocity[1].powi(2))
+                            .sqrt()
+                            * 1.3;
+                        fragments.push((asteroid.position, asteroid.radius / 2.0, speed));
+                    }
+                }
+                (Piece::Ship, Piece::Asteroid(asteroid))
+                | (Piece::Asteroid(asteroid), Piece::Ship) => {
+                    ship_hit |= self.asteroids[asteroid].lock().unwrap().alive
+                        && self.ship.lock().unwrap().alive;
+                }
+                _ => {}
+            }
+        }
+
+        let rng = &mut self.rng;
+        for (position, radius, speed) in fragments {
+            for _ in 0..2 {
+                if let Some(asteroid) = self
+                    .asteroids
+                    .iter()
+                    .find(|asteroid| !asteroid.lock().unwrap().alive)
+                {
+                    let angle = rng.gen_range(0.0..2.0 * PI);
+                    let mut asteroid = asteroid.lock().unwrap();
+                    asteroid.position = position;
+                    asteroid.velocity = [angle.cos() * speed, angle.sin() * speed];
+                    asteroid.radius = radius;
+                    asteroid.alive = true;
+                }
+            }
+        }
+
+        if ship_hit && self.shield <= 0.0 {
+            explosion = Some(self.ship.lock().unwrap().position);
+            self.lives -= 1;
+            if self.lives == 0 {
+                self.ship.lock().unwrap().alive = false;
+                self.state = State::GameOver;
+            } else {
+                self.respawn_ship();
+            }
+        }
+
+        if explosion.is_some() {
+            self.changed();
+        }
+
+        let cleared = self
+            .asteroids
+            .iter()
+            .all(|asteroid| !asteroid.lock().unwrap().alive);
+        if cleared {
+            self.wave += 1;
+            self.spawn_wave();
+        }
+
+        explosion
+    }
+
+    fn body(&self, piece: Piece) -> &Arc<Mutex<Body>> {
+        match piece {
+            Piece::Ship => &self.ship,
+            Piece::Bullet(index) => &self.bullets[index].0,
+            Piece::Asteroid(index) => &self.asteroids[index],
+        }
+    }
+
+    // Updates the window title and the HUD
+    fn changed(&mut self) {
+        let mut hud = self.hud.lock().unwrap();
+        hud.wave = self.wave;
+        hud.score = self.score;
+        hud.lives = self.lives;
+        hud.game_over = self.state == State::GameOver;
+        self.title = Some(match self.state {
+            State::Playing => format!(
+                "Asteroids | wave {} | score {} | lives {}",
+                self.wave + 1,
+                self.score,
+                self.lives
+            ),
+            State::GameOver => format!(
+                "Asteroids | game over, score {} | press enter to restart",
+                self.score
+            ),
+        });
+    }
+}
+
+// Stopped until the game starts it
+fn idle(mut source: AudioSource) -> AudioSource {
+    source.playing = false;
+    source
+}
+
+// After the engine's collision pass (both run in Stage::Simulation), so its CollisionEvents
+// are this frame's
+#[system]
+#[read_component(Piece)]
+#[read_component(Voice)]
+#[read_component(ThrustVoice)]
+#[write_component(Position2D)]
+#[write_component(Collider2D)]
+#[write_component(AudioSource)]
+pub fn asteroids(
+    world: &mut SubWorld,
+    #[resource] game: &mut Game,
+    #[resource] collisions: &Events<CollisionEvent>,
+    #[resource] input_map: &InputMap,
+    #[resource] time: &Time,
+    #[resource] window: &Arc<Window>,
+) {
+    let hits: Vec<(Piece, Piece)> = {
+        let world: &SubWorld = world;
+        let piece = |entity: Entity| {
+            world
+                .entry_ref(entity)
+                .ok()
+                .and_then(|entry| entry.get_component::<Piece>().ok().copied())
+        };
+        collisions
+            .iter()
+            .filter_map(|event| Some((piece(event.a)?, piece(event.b)?)))
+            .collect()
+    };
+    game.update(input_map, &hits, time.delta_secs());
+
+    // Where the next collision pass finds them
+    <(&Piece, &mut Position2D, &mut Collider2D)>::query().for_each_mut(
+        world,
+        |(piece, position, collider)| {
+            let body = game.body(*piece).lock().unwrap();
+            position.x = body.position[0];
+            position.y = body.position[1];
+            let radius = if body.alive { body.radius } else { 0.0 };
+            collider.shape = Shape2D::Circle { radius };
+        },
+    );
+
+    let mut voices = <(&Voice, &mut AudioSource, &mut Position2D)>::query();
+    for (sound, at) in game.sounds.drain(..) {
+        let voice = voices
+            .iter_mut(world)
+            .find(|(_, source, _)| source.sound == sound && !source.playing);
+        if let Some((_, source, position)) = voice {
+            source.playing = true;
+            position.x = at[0];
+            position.y = at[1];
+        }
+    }
+    let ship = game.ship.lock().unwrap().position;
+    <(&ThrustVoice, &mut AudioSource, &mut Position2D)>::query().for_each_mut(
+        world,
+        |(_, source, position)| {
+            source.playing = game.thrusting;
+            position.x = ship[0];
+            position.y = ship[1];
+        },
+    );
+
+    if let Some(title) = game.take_title() {
+        window.set_title(&title);
+    }
+}
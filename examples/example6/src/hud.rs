@@ -0,0 +1,86 @@
+use ember::sources::{
+    audio::AudioListener,
+    ui::iced::{UIAction, UIPanel},
+};
+use iced_wgpu::Renderer;
+use iced_winit::widget::{button, Button, Column, Row, Text};
+use iced_winit::{Color, Element, Length};
+use std::sync::{Arc, Mutex};
+
+use crate::game::Game;
+
+// What the HUD shows, kept up to date by the game
+#[derive(Clone, Default)]
+pub struct Hud {
+    pub wave: usize,
+    pub score: u32,
+    pub lives: u32,
+    pub game_over: bool,
+    pub muted: bool,
+}
+
+// Wave, score and lives, with buttons to restart the game and mute it
+pub struct HudPanel {
+    hud: Arc<Mutex<Hud>>,
+    restart: button::State,
+    mute: button::State,
+}
+
+impl HudPanel {
+    pub fn new(hud: Arc<Mutex<Hud>>) -> Self {
+        Self {
+            hud,
+            restart: Default::default(),
+            mute: Default::default(),
+        }
+    }
+}
+
+fn label(text: impl Into<String>) -> Text<Renderer> {
+    Text::new(text).size(14).color(Color::WHITE)
+}
+
+impl UIPanel for HudPanel {
+    fn view(&mut self) -> Element<UIAction, Renderer> {
+        let hud = self.hud.lock().unwrap().clone();
+        let status = match hud.game_over {
+            true => label(format!("Game over, score {}", hud.score))
+                .color(Color::from_rgb(1.0, 0.4, 0.3)),
+            false => label(format!(
+                "Wave {}   Score {}   Lives {}",
+                hud.wave + 1,
+                hud.score,
+                hud.lives
+            )),
+        };
+
+        let restart = UIAction::new(|_, resources| {
+            if let Some(mut game) = resources.get_mut::<Game>() {
+                game.restart();
+            }
+        });
+        let mute = {
+            let hud = Arc::clone(&self.hud);
+            UIAction::new(move |_, resources| {
+                if let Some(mut listener) = resources.get_mut::<AudioListener>() {
+                    let mut hud = hud.lock().unwrap();
+                    hud.muted = !hud.muted;
+                    listener.volume = if hud.muted { 0.0 } else { 1.0 };
+                }
+            })
+        };
+        let mute_label = if hud.muted { "Unmute" } else { "Mute" };
+
+        Column::new()
+            .width(Length::Units(320))
+            .spacing(8)
+            .push(status.size(18))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Button::new(&mut self.restart, label("Restart")).on_press(restart))
+                    .push(Button::new(&mut self.mute, label(mute_label)).on_press(mute)),
+            )
+            .into()
+    }
+}
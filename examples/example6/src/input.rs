@@ -0,0 +1,27 @@
+use ember::sources::input::{Binding, InputMap};
+use iced_winit::winit::event::VirtualKeyCode;
+
+// Game actions, resolved by the engine's InputMap each frame
+pub const THRUST: &str = "thrust";
+pub const TURN_LEFT: &str = "turn_left";
+pub const TURN_RIGHT: &str = "turn_right";
+pub const FIRE: &str = "fire";
+pub const RESTART: &str = "restart";
+
+// Each action bound to any number of keys, on top of the default Escape to quit
+pub fn input_map() -> InputMap {
+    [
+        (THRUST, VirtualKeyCode::W),
+        (THRUST, VirtualKeyCode::Up),
+        (TURN_LEFT, VirtualKeyCode::A),
+        (TURN_LEFT, VirtualKeyCode::Left),
+        (TURN_RIGHT, VirtualKeyCode::D),
+        (TURN_RIGHT, VirtualKeyCode::Right),
+        (FIRE, VirtualKeyCode::Space),
+        (RESTART, VirtualKeyCode::Return),
+    ]
+    .iter()
+    .fold(InputMap::default(), |map, (action, key)| {
+        map.with_action(action, Binding::Key(*key))
+    })
+}
@@ -0,0 +1,94 @@
+use ember::{
+    constants::ID,
+    renderer::{
+        buffer::instance::InstanceGroup,
+        systems::{post_fx::PostFxChainBuilder, render_2d::forward_instance::Render2DInstance},
+    },
+    sources::{audio::AudioListener, rng::EngineRng},
+    systems::particle_2d::{
+        Interpolator, ParticleEmitter2D, ParticleSystem2D, SmoothF32x2, SmoothF32x4,
+    },
+};
+use std::sync::{Arc, Mutex};
+
+mod game;
+mod hud;
+mod input;
+mod scene;
+
+// Ember example: asteroids
+//
+// A small but complete game, which keeps the 2D APIs honest when they change underneath
+// it. Covers game systems, input mapping, instancing, particles, collision events, sound
+// effects, a UI panel, waves loaded from prefab files and a post-processing chain, with the
+// window title as a second HUD.
+//
+// WASD / arrows to fly, space to shoot, enter to restart. Run with `--record <file>` to
+// record a game, and `--replay <file>` to play it back exactly.
+
+const REPLAY_SEED: u64 = 6;
+
+// Pixels to rodio's units, so sounds pan across the arena and fade out past a screen away
+const AUDIO_SCALE: f32 = 1.0 / 1000.0;
+
+fn main() {
+    std::env::set_var("RUST_LOG", "ember=info");
+    let hud = Arc::new(Mutex::new(hud::Hud::default()));
+    let sound = |name: &str| format!("{}/assets/sounds/{}", env!("CARGO_MANIFEST_DIR"), name);
+
+    let mut builder = scene::with_waves(ember::engine_builder())
+        .with_input_map(input::input_map())
+        .with_sound(ID(game::SHOOT_SOUND), &sound("shoot.wav"))
+        .with_sound(ID(game::EXPLOSION_SOUND), &sound("explosion.wav"))
+        .with_sound(ID(game::THRUST_SOUND), &sound("thrust.wav"))
+        .with_ui_panel(hud::HudPanel::new(Arc::clone(&hud)))
+        .with_post_fx(
+            PostFxChainBuilder::new()
+                .with_chromatic_aberration(1.5)
+                .with_vignette(0.4, 0.5)
+                .with_fxaa(),
+        )
+        .with_system(game::asteroids_system());
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, mode, path] = args.as_slice() {
+        builder = builder.with_deterministic(REPLAY_SEED, 60.0);
+        builder = match mode.as_str() {
+            "--record" => builder.with_input_recording(path),
+            "--replay" => builder.with_input_replay(path),
+            other => panic!("unknown option {}", other),
+        };
+    }
+    let (mut engine, event_loop) = builder.default_2d().unwrap();
+    let waves = scene::load_waves(&engine.prefabs()).unwrap();
+    engine.resources().get_mut::<AudioListener>().unwrap().scale = AUDIO_SCALE;
+
+    // Explosions; the game bursts the emitter at each hit
+    let mut particles = ParticleSystem2D::new_empty(
+        1.0,
+        Interpolator::<SmoothF32x2>::new([6.0, 6.0], [0.5, 0.5]),
+        Interpolator::<SmoothF32x2>::new([8.0, 8.0], [0.0, 0.0]),
+        Interpolator::<SmoothF32x4>::new([1.0, 0.8, 0.3, 1.0], [0.8, 0.1, 0.0, 1.0]),
+        600,
+    );
+    particles.push(ParticleEmitter2D {
+        rate: 0,
+        ..Default::default()
+    });
+    let explosions = Arc::clone(&particles.emitters[0]);
+    let particle_mesh = engine.clone_mesh(engine.mesh("unit_square").unwrap());
+    engine.world().push((
+        particles,
+        particle_mesh,
+        Render2DInstance::new_default_group(),
+    ));
+
+    // Ship, bullets and asteroids, each drawn as an instance and colliding as an entity
+    let mut bodies = InstanceGroup::new(1, engine.texture("render_2d_common").unwrap().id());
+    let rng = engine.resources().get_mut::<EngineRng>().unwrap().fork();
+    let game = game::Game::new(engine.world(), &mut bodies, waves, explosions, hud, rng);
+    let body_mesh = engine.clone_mesh(engine.mesh("unit_square").unwrap());
+    engine.world().push((bodies, body_mesh));
+    engine.resources().insert(game);
+
+    engine.start(event_loop);
+}
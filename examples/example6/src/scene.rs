@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use ember::{
+    sources::{inspector::Inspect, prefab::PrefabRegistry},
+    EngineBuilder,
+};
+
+// Prefab files (assets/waves/wave<n>.prefab), one [Wave] section each
+pub const WAVES: [&str; 5] = ["wave1", "wave2", "wave3", "wave4", "wave5"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Wave {
+    pub asteroids: u32,
+    pub speed: f32,
+    pub radius: f32,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            asteroids: 1,
+            speed: 100.0,
+            radius: 60.0,
+        }
+    }
+}
+
+// So prefab files can have a [Wave] section
+impl Inspect for Wave {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("asteroids", self.asteroids as f32),
+            ("speed", self.speed),
+            ("radius", self.radius),
+        ]
+    }
+
+    fn set_field(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.asteroids = value.max(0.0) as u32,
+            1 => self.speed = value,
+            2 => self.radius = value,
+            _ => {}
+        }
+    }
+}
+
+// Registers the wave files, parsed by the engine along with its other prefabs
+pub fn with_waves(builder: EngineBuilder) -> EngineBuilder {
+    WAVES.iter().fold(
+        builder.with_prefab_format("Wave", Wave::default()),
+        |builder, name| {
+            let path = format!(
+                "{}/assets/waves/{}.prefab",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            );
+            builder.with_prefab_file(name, &path)
+        },
+    )
+}
+
+// The waves, in order, once the engine is built
+pub fn load_waves(prefabs: &PrefabRegistry) -> Result<Vec<Wave>> {
+    WAVES
+        .iter()
+        .map(|name| {
+            prefabs
+                .get(name)
+                .and_then(|prefab| prefab.get::<Wave>().copied())
+                .ok_or_else(|| anyhow!("prefab {} has no [Wave]", name))
+        })
+        .collect()
+}
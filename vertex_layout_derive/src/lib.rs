@@ -91,6 +91,7 @@ fn type_info(ty: Type) -> (TokenStream2, usize) {
         "[f32 ; 2]" => (quote!(wgpu::VertexFormat::Float32x2), size_of!([f32; 2])),
         "[f32 ; 3]" => (quote!(wgpu::VertexFormat::Float32x3), size_of!([f32; 3])),
         "[f32 ; 4]" => (quote!(wgpu::VertexFormat::Float32x4), size_of!([f32; 4])),
+        "[u32 ; 4]" => (quote!(wgpu::VertexFormat::Uint32x4), size_of!([u32; 4])),
         other => panic!("unsupported type in instance struct: {}", other),
     }
 }